@@ -0,0 +1,96 @@
+//! Compares chain of 4 interceptor layers with single deferred layer of 4 interceptors
+//!
+//!Run with `cargo bench --bench deferred`
+
+use tonic_interceptor::{Interceptor, InterceptorService, Deferred, DeferredInterceptor, HeaderOps};
+
+use tonic_interceptor::{http, tonic};
+use tonic_interceptor::tonic::Status;
+use tower_service::Service;
+
+use core::task;
+use core::pin::pin;
+use core::future::{self, Future};
+use std::hint::black_box;
+use std::time::Instant;
+
+const ITERATIONS: u32 = 200_000;
+
+#[derive(Clone)]
+struct Metadata(&'static str);
+
+impl Interceptor for Metadata {
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<Status> {
+        black_box(headers.get("x-user"));
+        headers.insert(self.0, "1".parse().unwrap());
+        None
+    }
+
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
+
+#[derive(Clone)]
+struct Ops(&'static str);
+
+impl DeferredInterceptor for Ops {
+    fn on_request(&self, headers: &http::HeaderMap, ops: &mut HeaderOps, _: &mut http::Extensions) -> Option<Status> {
+        black_box(headers.get("x-user"));
+        ops.insert(self.0, http::HeaderValue::from_static("1"));
+        None
+    }
+
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
+
+fn headers() -> http::HeaderMap {
+    let mut headers = http::HeaderMap::new();
+    headers.insert("content-type", http::HeaderValue::from_static("application/grpc"));
+    headers.insert("te", http::HeaderValue::from_static("trailers"));
+    headers.insert("user-agent", http::HeaderValue::from_static("grpc-rust"));
+    headers.insert("grpc-timeout", http::HeaderValue::from_static("1S"));
+    headers.insert("x-user", http::HeaderValue::from_static("user"));
+    headers
+}
+
+fn bench<S: Service<http::Request<()>, Response = http::Response<()>>>(name: &str, mut service: S) where S::Error: core::fmt::Debug {
+    struct Noop;
+
+    impl std::task::Wake for Noop {
+        fn wake(self: std::sync::Arc<Self>) {
+        }
+    }
+
+    let waker = task::Waker::from(std::sync::Arc::new(Noop));
+    let mut ctx = task::Context::from_waker(&waker);
+    let headers = headers();
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        let mut request = http::Request::new(());
+        *request.headers_mut() = headers.clone();
+        let response = pin!(service.call(request));
+        match Future::poll(response, &mut ctx) {
+            task::Poll::Ready(result) => {
+                black_box(result.expect("response"));
+            },
+            task::Poll::Pending => unreachable!(),
+        }
+    }
+    let elapsed = start.elapsed();
+    println!("{}: {:?}/iter", name, elapsed / ITERATIONS);
+}
+
+fn main() {
+    let service = tower::service_fn(|request: http::Request<()>| {
+        black_box(request);
+        future::ready(Ok::<_, Status>(http::Response::new(())))
+    });
+
+    let layers = InterceptorService::new(Metadata("x-1"), InterceptorService::new(Metadata("x-2"), InterceptorService::new(Metadata("x-3"), InterceptorService::new(Metadata("x-4"), service))));
+    bench("4 layers", layers);
+
+    let deferred = InterceptorService::new(Deferred((Ops("x-1"), Ops("x-2"), Ops("x-3"), Ops("x-4"))), service);
+    bench("deferred", deferred);
+}
@@ -0,0 +1,56 @@
+//! Compares rejection cost for different response bodies
+//!
+//!Run with `cargo bench --bench reject`
+
+use tonic_interceptor::{EmptyBody, InterceptorFn, InterceptorService};
+
+use tonic_interceptor::{http, tonic};
+use tonic_interceptor::tonic::Status;
+use tower_service::Service;
+
+use core::task;
+use core::pin::pin;
+use core::future::{self, Future};
+use std::hint::black_box;
+use std::time::Instant;
+
+const ITERATIONS: u32 = 200_000;
+
+fn bench<B: EmptyBody>(name: &str) {
+    struct Noop;
+
+    impl std::task::Wake for Noop {
+        fn wake(self: std::sync::Arc<Self>) {
+        }
+    }
+
+    let waker = task::Waker::from(std::sync::Arc::new(Noop));
+    let mut ctx = task::Context::from_waker(&waker);
+    let service = tower::service_fn(|_: http::Request<()>| future::ready(Ok::<_, Status>(http::Response::new(B::empty()))));
+    let interceptor = InterceptorFn {
+        on_request: |_: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions| {
+            Some(Status::unauthenticated("missing token"))
+        },
+        on_response: |_: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions| {
+        }
+    };
+    let mut service = InterceptorService::new(interceptor, service);
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        let response = pin!(service.call(http::Request::new(())));
+        match Future::poll(response, &mut ctx) {
+            task::Poll::Ready(result) => {
+                black_box(result.expect("response"));
+            },
+            task::Poll::Pending => unreachable!(),
+        }
+    }
+    let elapsed = start.elapsed();
+    println!("{}: {:?}/iter", name, elapsed / ITERATIONS);
+}
+
+fn main() {
+    bench::<()>("unit");
+    bench::<tonic::body::BoxBody>("BoxBody");
+}
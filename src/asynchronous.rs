@@ -0,0 +1,489 @@
+//! Asynchronous interceptor
+//!
+//!`AsyncInterceptor` takes ownership of request's metadata and extensions for the duration of its future,
+//!which allows to perform I/O (e.g. token introspection or policy lookup) before request reaches inner service.
+//!
+//!Use `from_fn` or `from_fn_with_state` for simple cases:
+//!
+//!```rust,ignore
+//!let layer = tonic_interceptor::async_interceptor(tonic_interceptor::from_fn(|headers, _| {
+//!    let token = headers.get("authorization").cloned();
+//!    async move {
+//!        match validate(token).await {
+//!            true => Ok(()),
+//!            false => Err(tonic::Status::unauthenticated("invalid token")),
+//!        }
+//!    }
+//!}));
+//!```
+
+use crate::{DefaultBody, BodyFactory, Rejection, GRPC_STATUS_HEADER_CODE};
+use crate::response::ResponseTemplate;
+
+use core::task;
+use core::pin::Pin;
+use core::future::Future;
+
+///Request's parts, owned by `AsyncInterceptor` while it is running
+pub struct RequestHead {
+    uri: http::Uri,
+    ///Request metadata
+    pub headers: tonic::metadata::MetadataMap,
+    ///Request extensions
+    pub extensions: http::Extensions,
+}
+
+impl RequestHead {
+    #[inline(always)]
+    ///Creates new instance
+    pub fn new(uri: http::Uri, headers: tonic::metadata::MetadataMap, extensions: http::Extensions) -> Self {
+        Self {
+            uri,
+            headers,
+            extensions,
+        }
+    }
+
+    #[inline(always)]
+    ///Access request's URI
+    pub fn uri(&self) -> &http::Uri {
+        &self.uri
+    }
+}
+
+impl core::fmt::Debug for RequestHead {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("RequestHead").field("uri", &self.uri).field("headers", &self.headers).finish_non_exhaustive()
+    }
+}
+
+///Asynchronous interceptor
+pub trait AsyncInterceptor {
+    ///Future of `on_request`
+    type Future: Future<Output = Result<RequestHead, tonic::Status>>;
+
+    ///Callback on incoming request, resolving into request's parts to pass to inner service.
+    ///
+    ///Resolving into status will preempt request handling and immediately returns status
+    fn on_request(&self, request: RequestHead) -> Self::Future;
+
+    #[inline(always)]
+    ///Callback when response is being returned
+    ///
+    ///Not called for requests rejected by `on_request`.
+    fn on_response(&self, _status: tonic::Code, _headers: &mut http::HeaderMap, _extensions: &http::Extensions) {
+    }
+}
+
+impl<I: AsyncInterceptor + ?Sized> AsyncInterceptor for std::sync::Arc<I> {
+    type Future = I::Future;
+
+    #[inline(always)]
+    fn on_request(&self, request: RequestHead) -> Self::Future {
+        AsyncInterceptor::on_request(self.as_ref(), request)
+    }
+
+    #[inline(always)]
+    fn on_response(&self, status: tonic::Code, headers: &mut http::HeaderMap, extensions: &http::Extensions) {
+        AsyncInterceptor::on_response(self.as_ref(), status, headers, extensions)
+    }
+}
+
+///Outcome of `from_fn` closure's future
+///
+///Implemented for `Option<tonic::Status>` and `Result<(), tonic::Status>`
+pub trait Verdict {
+    ///Returns status to reject request with, if any
+    fn into_rejection(self) -> Option<tonic::Status>;
+}
+
+impl Verdict for Option<tonic::Status> {
+    #[inline(always)]
+    fn into_rejection(self) -> Option<tonic::Status> {
+        self
+    }
+}
+
+impl Verdict for Result<(), tonic::Status> {
+    #[inline(always)]
+    fn into_rejection(self) -> Option<tonic::Status> {
+        self.err()
+    }
+}
+
+pin_project_lite::pin_project! {
+    ///Future of `from_fn` based interceptors
+    pub struct FromFnFut<F> {
+        request: Option<RequestHead>,
+        #[pin]
+        fut: F,
+    }
+}
+
+impl<F> FromFnFut<F> {
+    #[inline(always)]
+    pub(crate) fn new(request: RequestHead, fut: F) -> Self {
+        Self {
+            request: Some(request),
+            fut,
+        }
+    }
+}
+
+impl<F> core::fmt::Debug for FromFnFut<F> {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("FromFnFut").field("is_complete", &self.request.is_none()).finish_non_exhaustive()
+    }
+}
+
+impl<F: Future> Future for FromFnFut<F> where F::Output: Verdict {
+    type Output = Result<RequestHead, tonic::Status>;
+
+    fn poll(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let this = self.project();
+        match Future::poll(this.fut, ctx) {
+            task::Poll::Ready(verdict) => {
+                let request = this.request.take().expect("Future polled after completion");
+                match verdict.into_rejection() {
+                    Some(status) => task::Poll::Ready(Err(status)),
+                    None => task::Poll::Ready(Ok(request)),
+                }
+            },
+            task::Poll::Pending => task::Poll::Pending,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+#[repr(transparent)]
+///Interceptor created by `from_fn`
+pub struct FromFn<F>(F);
+
+impl<F> core::fmt::Debug for FromFn<F> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("FromFn").finish_non_exhaustive()
+    }
+}
+
+impl<R: Verdict, Fut: Future<Output = R>, F: Fn(&mut tonic::metadata::MetadataMap, &mut http::Extensions) -> Fut> AsyncInterceptor for FromFn<F> {
+    type Future = FromFnFut<Fut>;
+
+    #[inline]
+    fn on_request(&self, mut request: RequestHead) -> Self::Future {
+        let fut = (self.0)(&mut request.headers, &mut request.extensions);
+        FromFnFut {
+            request: Some(request),
+            fut,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+///Interceptor created by `from_fn_with_state`
+pub struct FromFnWithState<S, F> {
+    state: S,
+    fun: F,
+}
+
+impl<S: core::fmt::Debug, F> core::fmt::Debug for FromFnWithState<S, F> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("FromFnWithState").field("state", &self.state).finish_non_exhaustive()
+    }
+}
+
+impl<S, R: Verdict, Fut: Future<Output = R>, F: Fn(&S, &mut tonic::metadata::MetadataMap, &mut http::Extensions) -> Fut> AsyncInterceptor for FromFnWithState<S, F> {
+    type Future = FromFnFut<Fut>;
+
+    #[inline]
+    fn on_request(&self, mut request: RequestHead) -> Self::Future {
+        let fut = (self.fun)(&self.state, &mut request.headers, &mut request.extensions);
+        FromFnFut {
+            request: Some(request),
+            fut,
+        }
+    }
+}
+
+#[inline(always)]
+///Creates asynchronous interceptor out of `fun`
+///
+///`fun` is called with request's metadata and extensions, which it can modify right away.
+///Returned future must own everything it needs and resolves into `Result<(), tonic::Status>` or `Option<tonic::Status>`.
+///Implement `AsyncInterceptor` directly to modify request after asynchronous work is done.
+pub fn from_fn<R: Verdict, Fut: Future<Output = R>, F: Fn(&mut tonic::metadata::MetadataMap, &mut http::Extensions) -> Fut>(fun: F) -> FromFn<F> {
+    FromFn(fun)
+}
+
+#[inline(always)]
+///Creates asynchronous interceptor out of `fun`, which is called with reference to `state` as first argument
+///
+///See `from_fn` for details.
+pub fn from_fn_with_state<S, R: Verdict, Fut: Future<Output = R>, F: Fn(&S, &mut tonic::metadata::MetadataMap, &mut http::Extensions) -> Fut>(state: S, fun: F) -> FromFnWithState<S, F> {
+    FromFnWithState {
+        state,
+        fun,
+    }
+}
+
+///Layer of `AsyncInterceptor`
+#[derive(Clone, Copy)]
+pub struct AsyncInterceptorLayer<I, B = DefaultBody> {
+    interceptor: I,
+    body: B,
+}
+
+impl<I> AsyncInterceptorLayer<I> {
+    #[inline(always)]
+    ///Creates new instance
+    pub const fn new(interceptor: I) -> Self {
+        Self {
+            interceptor,
+            body: DefaultBody,
+        }
+    }
+}
+
+impl<I, B> AsyncInterceptorLayer<I, B> {
+    #[inline(always)]
+    ///Access interceptor
+    pub fn get_ref(&self) -> &I {
+        &self.interceptor
+    }
+
+    #[inline(always)]
+    ///Access interceptor mutably
+    pub fn get_mut(&mut self) -> &mut I {
+        &mut self.interceptor
+    }
+
+    #[inline(always)]
+    ///Returns interceptor
+    pub fn into_inner(self) -> I {
+        self.interceptor
+    }
+
+    #[inline]
+    ///Sets factory of rejection response body
+    ///
+    ///See `InterceptorService::with_body`.
+    pub fn with_body<B2>(self, body: B2) -> AsyncInterceptorLayer<I, B2> {
+        AsyncInterceptorLayer {
+            interceptor: self.interceptor,
+            body,
+        }
+    }
+}
+
+impl<I: core::fmt::Debug, B> core::fmt::Debug for AsyncInterceptorLayer<I, B> {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("AsyncInterceptorLayer").field("interceptor", &self.interceptor).finish_non_exhaustive()
+    }
+}
+
+impl<S, I: AsyncInterceptor + Clone, B: Clone> tower_layer::Layer<S> for AsyncInterceptorLayer<I, B> {
+    type Service = AsyncInterceptorService<I, S, B>;
+
+    #[inline(always)]
+    fn layer(&self, inner: S) -> Self::Service {
+        AsyncInterceptorService::new(self.interceptor.clone(), inner).with_body(self.body.clone())
+    }
+}
+
+///Service of `AsyncInterceptor`
+///
+///Inner service must be `Clone`, as it is called only after interceptor's future completes.
+#[derive(Clone)]
+pub struct AsyncInterceptorService<I, S, B = DefaultBody> {
+    interceptor: I,
+    body: B,
+    inner: S,
+}
+
+impl<I: core::fmt::Debug, S: core::fmt::Debug, B> core::fmt::Debug for AsyncInterceptorService<I, S, B> {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("AsyncInterceptorService").field("interceptor", &self.interceptor).field("inner", &self.inner).finish_non_exhaustive()
+    }
+}
+
+impl<I, S> AsyncInterceptorService<I, S> {
+    #[inline(always)]
+    ///Creates new instance
+    pub fn new(interceptor: I, inner: S) -> Self {
+        Self {
+            interceptor,
+            body: DefaultBody,
+            inner,
+        }
+    }
+}
+
+impl<I, S, B> AsyncInterceptorService<I, S, B> {
+    #[inline(always)]
+    ///Access inner service
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    #[inline(always)]
+    ///Access inner service mutably
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    #[inline(always)]
+    ///Returns inner service
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    #[inline(always)]
+    ///Access interceptor
+    pub fn interceptor_ref(&self) -> &I {
+        &self.interceptor
+    }
+
+    #[inline(always)]
+    ///Access interceptor mutably
+    pub fn interceptor_mut(&mut self) -> &mut I {
+        &mut self.interceptor
+    }
+
+    #[inline]
+    ///Sets factory of rejection response body
+    ///
+    ///See `InterceptorService::with_body`.
+    pub fn with_body<B2>(self, body: B2) -> AsyncInterceptorService<I, S, B2> {
+        AsyncInterceptorService {
+            interceptor: self.interceptor,
+            body,
+            inner: self.inner,
+        }
+    }
+}
+
+impl<ReqBody, ResBody, S: tower_service::Service<http::Request<ReqBody>, Response = http::Response<ResBody>> + Clone, I: AsyncInterceptor + Clone, B: BodyFactory<ResBody> + Clone> tower_service::Service<http::Request<ReqBody>> for AsyncInterceptorService<I, S, B> {
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = AsyncInterceptorFut<I, S, ReqBody, B>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        //Take service that is ready, leaving its clone in place
+        let clone = self.inner.clone();
+        let inner = core::mem::replace(&mut self.inner, clone);
+
+        let (mut parts, body) = req.into_parts();
+        let rejection = Rejection {
+            //Captured before interceptor has a chance to modify it
+            template: ResponseTemplate::from_parts(&parts.headers, parts.version),
+            body: self.body.clone(),
+            echoed: None,
+            carried: None,
+        };
+
+        let headers = crate::compat::metadata_from_headers(core::mem::take(&mut parts.headers));
+        let extensions = core::mem::take(&mut parts.extensions);
+        let fut = self.interceptor.on_request(RequestHead::new(parts.uri.clone(), headers, extensions));
+
+        AsyncInterceptorFut {
+            interceptor: self.interceptor.clone(),
+            rejection,
+            state: State::Intercept {
+                fut,
+                request: Some((parts, body)),
+                inner,
+            },
+        }
+    }
+}
+
+impl<I, S: tonic::server::NamedService, B> tonic::server::NamedService for AsyncInterceptorService<I, S, B> {
+    const NAME: &'static str = S::NAME;
+}
+
+pin_project_lite::pin_project! {
+    #[project = StateProj]
+    enum State<IF, S, SF, ReqBody> {
+        Intercept {
+            #[pin]
+            fut: IF,
+            request: Option<(http::request::Parts, ReqBody)>,
+            inner: S,
+        },
+        Call {
+            #[pin]
+            fut: SF,
+        },
+    }
+}
+
+pin_project_lite::pin_project! {
+    ///Asynchronous interception service future
+    pub struct AsyncInterceptorFut<I: AsyncInterceptor, S: tower_service::Service<http::Request<ReqBody>>, ReqBody, B = DefaultBody> {
+        interceptor: I,
+        rejection: Rejection<B>,
+        #[pin]
+        state: State<I::Future, S, S::Future, ReqBody>,
+    }
+}
+
+impl<I: AsyncInterceptor, S: tower_service::Service<http::Request<ReqBody>>, ReqBody, B> core::fmt::Debug for AsyncInterceptorFut<I, S, ReqBody, B> {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let state = match self.state {
+            State::Intercept { .. } => "Intercept",
+            State::Call { .. } => "Call",
+        };
+        fmt.debug_struct("AsyncInterceptorFut").field("state", &state).finish_non_exhaustive()
+    }
+}
+
+impl<ReqBody, ResBody, S: tower_service::Service<http::Request<ReqBody>, Response = http::Response<ResBody>>, I: AsyncInterceptor, B: BodyFactory<ResBody>> Future for AsyncInterceptorFut<I, S, ReqBody, B> {
+    type Output = Result<S::Response, S::Error>;
+
+    fn poll(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let mut this = self.project();
+        loop {
+            let fut = match this.state.as_mut().project() {
+                StateProj::Intercept { fut, request, inner } => match Future::poll(fut, ctx) {
+                    task::Poll::Ready(Ok(head)) => {
+                        let (mut parts, body) = request.take().expect("Future polled after completion");
+                        parts.headers = crate::compat::metadata_into_headers(head.headers);
+                        parts.extensions = head.extensions;
+                        inner.call(http::Request::from_parts(parts, body))
+                    },
+                    task::Poll::Ready(Err(status)) => {
+                        return task::Poll::Ready(Ok(this.rejection.respond_with(&status, |_, _, _| ())));
+                    },
+                    task::Poll::Pending => return task::Poll::Pending,
+                },
+                StateProj::Call { fut } => match Future::poll(fut, ctx) {
+                    task::Poll::Ready(Ok(resp)) => {
+                        let (mut parts, body) = resp.into_parts();
+
+                        let status = parts.headers.get(GRPC_STATUS_HEADER_CODE).map(|header| tonic::Code::from_bytes(header.as_bytes())).unwrap_or(tonic::Code::Unknown);
+
+                        this.interceptor.on_response(status, &mut parts.headers, &parts.extensions);
+                        return task::Poll::Ready(Ok(http::Response::from_parts(parts, body)));
+                    },
+                    task::Poll::Ready(Err(error)) => return task::Poll::Ready(Err(error)),
+                    task::Poll::Pending => return task::Poll::Pending,
+                },
+            };
+            this.state.set(State::Call {
+                fut,
+            });
+        }
+    }
+}
+
+#[inline(always)]
+///Creates layer for `AsyncInterceptor`
+pub fn async_interceptor<I: AsyncInterceptor>(interceptor: I) -> AsyncInterceptorLayer<I> {
+    AsyncInterceptorLayer::new(interceptor)
+}
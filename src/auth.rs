@@ -0,0 +1,14 @@
+//! Authentication of incoming calls
+
+mod clock;
+pub use clock::{Clock, SystemClock};
+mod extract;
+pub use extract::{TokenExtractor, TokenSource, Token};
+#[cfg(feature = "oauth2")]
+mod introspection;
+#[cfg(feature = "oauth2")]
+pub use introspection::{HttpClient, FailurePolicy, OAuthIdentity, Introspection, IntrospectionBuilder};
+#[cfg(feature = "hmac")]
+mod signature;
+#[cfg(feature = "hmac")]
+pub use signature::{HmacSignature, canonical_string, sign, KEY_ID, SIGNATURE_TIMESTAMP, SIGNATURE};
@@ -0,0 +1,36 @@
+///Source of current time
+pub trait Clock {
+    ///Returns number of seconds since UNIX epoch
+    fn now(&self) -> u64;
+
+    #[inline(always)]
+    ///Returns number of milliseconds since UNIX epoch
+    ///
+    ///By default it is derived from `now`, hence has precision of seconds.
+    fn now_millis(&self) -> u64 {
+        self.now().saturating_mul(1000)
+    }
+}
+
+impl<F: Fn() -> u64> Clock for F {
+    #[inline(always)]
+    fn now(&self) -> u64 {
+        (self)()
+    }
+}
+
+#[derive(Copy, Clone, Default, Debug)]
+///System clock
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    #[inline]
+    fn now(&self) -> u64 {
+        std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0)
+    }
+
+    #[inline]
+    fn now_millis(&self) -> u64 {
+        std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|time| time.as_millis() as u64).unwrap_or(0)
+    }
+}
@@ -0,0 +1,110 @@
+use std::borrow::Cow;
+
+const AUTHORIZATION: &str = "authorization";
+const COOKIE: &str = "cookie";
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+///Source of token
+///
+///Once token is extracted, its source is inserted into request extensions for audit.
+pub enum TokenSource {
+    ///`authorization: Bearer <token>`
+    Bearer,
+    ///Whole value of metadata key, e.g. `x-access-token`
+    Metadata(Cow<'static, str>),
+    ///Value of cookie with name, as used by grpc-web clients
+    Cookie(Cow<'static, str>),
+}
+
+impl TokenSource {
+    fn extract<'a>(&self, headers: &'a tonic::metadata::MetadataMap) -> Option<&'a str> {
+        let token = match self {
+            Self::Bearer => headers.get_all(AUTHORIZATION).iter().filter_map(|value| value.to_str().ok()).find_map(|value| match value.split_once(' ') {
+                Some((scheme, token)) if scheme.eq_ignore_ascii_case("bearer") => Some(token.trim()),
+                _ => None,
+            }),
+            Self::Metadata(key) => headers.get(key.as_ref()).and_then(|value| value.to_str().ok()).map(str::trim),
+            //HTTP/2 allows cookies to be split into multiple headers
+            Self::Cookie(name) => headers.get_all(COOKIE).iter().filter_map(|value| value.to_str().ok()).flat_map(|value| value.split(';')).find_map(|cookie| match cookie.trim().split_once('=') {
+                Some((cookie, value)) if cookie == name => Some(value.trim_matches('"')),
+                _ => None,
+            }),
+        };
+        token.filter(|token| !token.is_empty())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+///Token extracted from request
+pub struct Token<'a> {
+    ///Value of token
+    pub value: &'a str,
+    ///Source of token
+    pub source: &'a TokenSource,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+///Ordered list of token sources
+///
+///Token is taken from the first source, which has it, while empty values are treated as absent.
+///Default extractor uses `authorization: Bearer <token>` only.
+pub struct TokenExtractor {
+    sources: Vec<TokenSource>,
+}
+
+impl TokenExtractor {
+    #[inline(always)]
+    ///Creates new instance without sources, which never finds token
+    pub const fn new() -> Self {
+        Self {
+            sources: Vec::new(),
+        }
+    }
+
+    #[inline]
+    ///Appends `source`
+    pub fn source(mut self, source: TokenSource) -> Self {
+        self.sources.push(source);
+        self
+    }
+
+    #[inline(always)]
+    ///Appends `authorization: Bearer <token>`
+    pub fn bearer(self) -> Self {
+        self.source(TokenSource::Bearer)
+    }
+
+    #[inline(always)]
+    ///Appends value of metadata `key`
+    pub fn metadata(self, key: impl Into<Cow<'static, str>>) -> Self {
+        self.source(TokenSource::Metadata(key.into()))
+    }
+
+    #[inline(always)]
+    ///Appends value of cookie `name`
+    pub fn cookie(self, name: impl Into<Cow<'static, str>>) -> Self {
+        self.source(TokenSource::Cookie(name.into()))
+    }
+
+    #[inline(always)]
+    ///Returns sources in order of precedence
+    pub fn sources(&self) -> &[TokenSource] {
+        &self.sources
+    }
+
+    #[inline]
+    ///Returns token of the first source, which has it
+    pub fn extract<'a>(&'a self, headers: &'a tonic::metadata::MetadataMap) -> Option<Token<'a>> {
+        self.sources.iter().find_map(|source| source.extract(headers).map(|value| Token {
+            value,
+            source,
+        }))
+    }
+}
+
+impl Default for TokenExtractor {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::new().bearer()
+    }
+}
@@ -0,0 +1,371 @@
+use super::{TokenExtractor, TokenSource};
+use crate::asynchronous::{AsyncInterceptor, RequestHead};
+use crate::client::BoxError;
+use crate::identity::{Mechanism, PeerIdentity};
+use crate::timer::{Clock, Instant, StdClock};
+
+use core::fmt;
+use core::pin::Pin;
+use core::future::Future;
+use core::time::Duration;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+///HTTP client used to call introspection endpoint
+pub trait HttpClient {
+    ///Sends `request`, returning response with whole body
+    fn send(&self, request: http::Request<bytes::Bytes>) -> impl Future<Output = Result<http::Response<bytes::Bytes>, BoxError>> + Send;
+}
+
+impl<T: HttpClient + Send + Sync> HttpClient for Arc<T> {
+    #[inline(always)]
+    fn send(&self, request: http::Request<bytes::Bytes>) -> impl Future<Output = Result<http::Response<bytes::Bytes>, BoxError>> + Send {
+        HttpClient::send(self.as_ref(), request)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+///Handling of requests, which token cannot be introspected due to endpoint failure
+pub enum FailurePolicy {
+    ///Request is passed without identity
+    Open,
+    ///Request is rejected with `UNAVAILABLE`
+    Closed,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+///Identity of active token, inserted into request extensions by `Introspection`
+///
+///It is also available as `extra` of `PeerIdentity`, which should be preferred.
+pub struct OAuthIdentity {
+    ///Subject of token, `sub`
+    pub subject: Option<String>,
+    ///Scopes of token, `scope`
+    pub scopes: Vec<String>,
+    ///Expiration time of token as seconds since UNIX epoch, `exp`
+    pub expires_at: Option<u64>,
+}
+
+impl OAuthIdentity {
+    #[inline]
+    ///Returns whether token has `scope`
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|granted| granted == scope)
+    }
+}
+
+impl From<OAuthIdentity> for PeerIdentity {
+    #[inline]
+    fn from(identity: OAuthIdentity) -> Self {
+        let mut peer = PeerIdentity::new(Mechanism::Bearer);
+        peer.subject = identity.subject.clone();
+        peer.scopes = identity.scopes.clone();
+        peer.with_extra(identity)
+    }
+}
+
+#[derive(serde::Deserialize)]
+//Members of introspection response (RFC 7662 section 2.2), which are used by `Introspection`
+struct IntrospectionResponse {
+    active: bool,
+    #[serde(default)]
+    sub: Option<String>,
+    #[serde(default)]
+    scope: Option<String>,
+    //NumericDate, which is integer
+    #[serde(default)]
+    exp: Option<u64>,
+}
+
+struct Entry {
+    //None for inactive token
+    identity: Option<OAuthIdentity>,
+    expires: Instant,
+}
+
+struct Inner<C, K> {
+    client: C,
+    endpoint: http::Uri,
+    authorization: http::HeaderValue,
+    extractor: TokenExtractor,
+    positive_ttl: Duration,
+    negative_ttl: Duration,
+    capacity: usize,
+    policy: FailurePolicy,
+    cache: Mutex<HashMap<String, Entry>>,
+    clock: K,
+}
+
+impl<C: HttpClient, K: Clock> Inner<C, K> {
+    fn cached(&self, token: &str) -> Option<Option<OAuthIdentity>> {
+        let cache = self.cache.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+        match cache.get(token) {
+            Some(entry) if entry.expires > self.clock.now() => Some(entry.identity.clone()),
+            _ => None,
+        }
+    }
+
+    fn store(&self, token: String, identity: Option<OAuthIdentity>) {
+        let ttl = match identity.as_ref() {
+            Some(identity) => match identity.expires_at {
+                Some(expires_at) => self.positive_ttl.min(Duration::from_secs(expires_at.saturating_sub(unix_now()))),
+                None => self.positive_ttl,
+            },
+            None => self.negative_ttl,
+        };
+        if self.capacity == 0 || ttl.is_zero() {
+            return;
+        }
+
+        let now = self.clock.now();
+        let mut cache = self.cache.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+        if cache.len() >= self.capacity && !cache.contains_key(&token) {
+            cache.retain(|_, entry| entry.expires > now);
+            if cache.len() >= self.capacity {
+                let oldest = cache.iter().min_by_key(|(_, entry)| entry.expires).map(|(token, _)| token.clone());
+                if let Some(oldest) = oldest {
+                    cache.remove(&oldest);
+                }
+            }
+        }
+        cache.insert(token, Entry {
+            identity,
+            expires: now + ttl,
+        });
+    }
+
+    async fn introspect(&self, token: &str) -> Result<Option<OAuthIdentity>, BoxError> {
+        let mut body = String::from("token=");
+        form_encode(token, &mut body);
+        body.push_str("&token_type_hint=access_token");
+
+        let request = http::Request::post(self.endpoint.clone())
+                                    .header(http::header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+                                    .header(http::header::ACCEPT, "application/json")
+                                    .header(http::header::AUTHORIZATION, self.authorization.clone())
+                                    .body(bytes::Bytes::from(body))?;
+        let response = self.client.send(request).await?;
+        if response.status() != http::StatusCode::OK {
+            return Err(format!("introspection endpoint responded with {}", response.status()).into());
+        }
+
+        let response: IntrospectionResponse = serde_json::from_slice(response.body())?;
+        let identity = OAuthIdentity {
+            subject: response.sub,
+            scopes: response.scope.as_deref().unwrap_or_default().split_ascii_whitespace().map(str::to_owned).collect(),
+            expires_at: response.exp,
+        };
+
+        match response.active && identity.expires_at.is_none_or(|expires_at| expires_at > unix_now()) {
+            true => Ok(Some(identity)),
+            false => Ok(None),
+        }
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |now| now.as_secs())
+}
+
+///Builder of `Introspection`
+pub struct IntrospectionBuilder<C, K = StdClock> {
+    inner: Inner<C, K>,
+}
+
+impl<C: HttpClient, K: Clock> IntrospectionBuilder<C, K> {
+    #[inline]
+    ///Sets sources of token, `authorization: Bearer <token>` by default
+    pub fn extractor(mut self, extractor: TokenExtractor) -> Self {
+        self.inner.extractor = extractor;
+        self
+    }
+
+    #[inline]
+    ///Sets time to cache active token, 60 seconds by default
+    ///
+    ///Token is never cached beyond its expiration time.
+    pub fn positive_ttl(mut self, ttl: Duration) -> Self {
+        self.inner.positive_ttl = ttl;
+        self
+    }
+
+    #[inline]
+    ///Sets time to cache inactive token, 10 seconds by default
+    pub fn negative_ttl(mut self, ttl: Duration) -> Self {
+        self.inner.negative_ttl = ttl;
+        self
+    }
+
+    #[inline]
+    ///Sets maximum number of cached tokens, 10000 by default
+    ///
+    ///Once cache is full, expired entries are removed, and then the one closest to expiration.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.inner.capacity = capacity;
+        self
+    }
+
+    #[inline]
+    ///Sets handling of endpoint failures, `FailurePolicy::Closed` by default
+    pub fn on_failure(mut self, policy: FailurePolicy) -> Self {
+        self.inner.policy = policy;
+        self
+    }
+
+    #[inline]
+    ///Sets clock, which measures expiration of cached tokens, `StdClock` by default
+    ///
+    ///Expiration time of token itself (`exp`) is always compared against system time.
+    pub fn clock<K2: Clock>(self, clock: K2) -> IntrospectionBuilder<C, K2> {
+        let inner = self.inner;
+        IntrospectionBuilder {
+            inner: Inner {
+                client: inner.client,
+                endpoint: inner.endpoint,
+                authorization: inner.authorization,
+                extractor: inner.extractor,
+                positive_ttl: inner.positive_ttl,
+                negative_ttl: inner.negative_ttl,
+                capacity: inner.capacity,
+                policy: inner.policy,
+                cache: inner.cache,
+                clock,
+            },
+        }
+    }
+
+    #[inline(always)]
+    ///Creates interceptor
+    pub fn build(self) -> Introspection<C, K> {
+        Introspection {
+            inner: Arc::new(self.inner),
+        }
+    }
+}
+
+impl<C, K> fmt::Debug for IntrospectionBuilder<C, K> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("IntrospectionBuilder").field("endpoint", &self.inner.endpoint).finish_non_exhaustive()
+    }
+}
+
+///Asynchronous interceptor, which validates opaque token using OAuth2 token introspection (RFC 7662)
+///
+///Token is posted to introspection endpoint, authenticating with client credentials via HTTP Basic authentication.
+///Active token is accepted, inserting `PeerIdentity`, `OAuthIdentity` and `TokenSource` into request extensions,
+///while missing or inactive token is rejected with `UNAUTHENTICATED`.
+///
+///Results are cached by token, while endpoint failures are handled according to `FailurePolicy` and not cached.
+pub struct Introspection<C, K = StdClock> {
+    inner: Arc<Inner<C, K>>,
+}
+
+impl<C: HttpClient> Introspection<C> {
+    ///Starts building interceptor, calling `endpoint` with `client` on behalf of `client_id`
+    pub fn builder(client: C, endpoint: http::Uri, client_id: &str, client_secret: &str) -> IntrospectionBuilder<C> {
+        //RFC 6749 requires credentials to be form encoded before joining them
+        let mut credentials = String::new();
+        form_encode(client_id, &mut credentials);
+        credentials.push(':');
+        form_encode(client_secret, &mut credentials);
+        let mut authorization = http::HeaderValue::from_str(&format!("Basic {}", crate::base64(credentials.as_bytes()))).expect("base64 is valid header value");
+        authorization.set_sensitive(true);
+
+        IntrospectionBuilder {
+            inner: Inner {
+                client,
+                endpoint,
+                authorization,
+                extractor: TokenExtractor::default(),
+                positive_ttl: Duration::from_secs(60),
+                negative_ttl: Duration::from_secs(10),
+                capacity: 10_000,
+                policy: FailurePolicy::Closed,
+                cache: Mutex::new(HashMap::new()),
+                clock: StdClock,
+            },
+        }
+    }
+
+}
+
+impl<C, K> Introspection<C, K> {
+    #[inline]
+    ///Returns number of cached tokens, including expired ones
+    pub fn cached(&self) -> usize {
+        self.inner.cache.lock().unwrap_or_else(std::sync::PoisonError::into_inner).len()
+    }
+}
+
+impl<C, K> Clone for Introspection<C, K> {
+    #[inline(always)]
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<C, K> fmt::Debug for Introspection<C, K> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("Introspection").field("endpoint", &self.inner.endpoint).field("policy", &self.inner.policy).finish_non_exhaustive()
+    }
+}
+
+impl<C: HttpClient + Send + Sync + 'static, K: Clock + Send + Sync + 'static> AsyncInterceptor for Introspection<C, K> {
+    type Future = Pin<Box<dyn Future<Output = Result<RequestHead, tonic::Status>> + Send>>;
+
+    fn on_request(&self, mut request: RequestHead) -> Self::Future {
+        let inner = self.inner.clone();
+        Box::pin(async move {
+            let (token, source) = match inner.extractor.extract(&request.headers) {
+                Some(token) => (token.value.to_owned(), token.source.clone()),
+                None => return Err(tonic::Status::unauthenticated("missing bearer token")),
+            };
+
+            let identity = match inner.cached(&token) {
+                Some(identity) => identity,
+                None => match inner.introspect(&token).await {
+                    Ok(identity) => {
+                        inner.store(token, identity.clone());
+                        identity
+                    },
+                    Err(_) => match inner.policy {
+                        FailurePolicy::Open => return Ok(request),
+                        FailurePolicy::Closed => return Err(tonic::Status::unavailable("token introspection failed")),
+                    },
+                },
+            };
+
+            match identity {
+                Some(identity) => {
+                    request.extensions.insert(PeerIdentity::from(identity.clone()).with_extra(source.clone()));
+                    request.extensions.insert(identity);
+                    request.extensions.insert::<TokenSource>(source);
+                    Ok(request)
+                },
+                None => Err(tonic::Status::unauthenticated("inactive token")),
+            }
+        })
+    }
+}
+
+//Encodes `value` as `application/x-www-form-urlencoded`
+fn form_encode(value: &str, out: &mut String) {
+    const HEX: &[u8; 16] = b"0123456789ABCDEF";
+
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'*' => out.push(byte as char),
+            b' ' => out.push('+'),
+            byte => {
+                out.push('%');
+                out.push(HEX[(byte >> 4) as usize] as char);
+                out.push(HEX[(byte & 0xf) as usize] as char);
+            }
+        }
+    }
+}
@@ -0,0 +1,210 @@
+use super::{Clock, SystemClock};
+use crate::Interceptor;
+use crate::identity::{Mechanism, PeerIdentity};
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use core::time::Duration;
+use std::collections::HashMap;
+
+///Header with id of the key used to sign request
+pub const KEY_ID: &str = "x-key-id";
+///Header with time of signing as seconds since UNIX epoch
+pub const SIGNATURE_TIMESTAMP: &str = "x-signature-timestamp";
+///Header with hex encoded signature
+pub const SIGNATURE: &str = "x-signature";
+
+///Builds string to sign.
+///
+///Format is method path, timestamp and then every header in configured order as `name:value`, each on its own line.
+///Multiple values of the same header are joined by `,`, while missing header has empty value.
+pub fn canonical_string<H: AsRef<str>>(path: &str, timestamp: &str, headers: &[H], metadata: &tonic::metadata::MetadataMap) -> Vec<u8> {
+    let mut result = Vec::with_capacity(path.len() + timestamp.len() + 2);
+    result.extend_from_slice(path.as_bytes());
+    result.push(b'\n');
+    result.extend_from_slice(timestamp.as_bytes());
+    result.push(b'\n');
+
+    for name in headers {
+        let name = name.as_ref();
+        result.extend_from_slice(name.as_bytes());
+        result.push(b':');
+        if name.ends_with("-bin") {
+            for (idx, value) in metadata.get_all_bin(name).iter().enumerate() {
+                if idx > 0 {
+                    result.push(b',');
+                }
+                result.extend_from_slice(value.as_encoded_bytes());
+            }
+        } else {
+            for (idx, value) in metadata.get_all(name).iter().enumerate() {
+                if idx > 0 {
+                    result.push(b',');
+                }
+                result.extend_from_slice(value.as_encoded_bytes());
+            }
+        }
+        result.push(b'\n');
+    }
+
+    result
+}
+
+///Signs `canonical` string with HMAC-SHA256, returning lower case hex encoded signature
+pub fn sign(secret: &[u8], canonical: &[u8]) -> String {
+    const HEX: &[u8; 16] = b"0123456789abcdef";
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(canonical);
+
+    let mut result = String::with_capacity(64);
+    for byte in mac.finalize().into_bytes() {
+        result.push(HEX[(byte >> 4) as usize] as char);
+        result.push(HEX[(byte & 0xf) as usize] as char);
+    }
+    result
+}
+
+fn decode_hex(value: &[u8]) -> Option<Vec<u8>> {
+    fn digit(byte: u8) -> Option<u8> {
+        match byte {
+            b'0'..=b'9' => Some(byte - b'0'),
+            b'a'..=b'f' => Some(byte - b'a' + 10),
+            b'A'..=b'F' => Some(byte - b'A' + 10),
+            _ => None,
+        }
+    }
+
+    if value.len() & 1 == 1 {
+        return None;
+    }
+
+    value.chunks(2).map(|pair| Some(digit(pair[0])? << 4 | digit(pair[1])?)).collect()
+}
+
+#[derive(Clone, Debug)]
+///Server interceptor which validates HMAC-SHA256 signature of incoming requests.
+///
+///Requests must carry `x-key-id`, `x-signature-timestamp` and `x-signature`, with signature computed over `canonical_string`.
+///Failed validation is rejected with `UNAUTHENTICATED`, while accepted request gets `identity::PeerIdentity` with key id as subject.
+pub struct HmacSignature<C = SystemClock> {
+    keys: HashMap<String, Vec<u8>>,
+    headers: Vec<String>,
+    max_skew: Duration,
+    clock: C,
+}
+
+impl HmacSignature {
+    #[inline]
+    ///Creates new instance without keys, allowing clock skew of 5 minutes.
+    pub fn new() -> Self {
+        Self {
+            keys: HashMap::new(),
+            headers: Vec::new(),
+            max_skew: Duration::from_secs(5 * 60),
+            clock: SystemClock,
+        }
+    }
+}
+
+impl Default for HmacSignature {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: Clock> HmacSignature<C> {
+    #[inline]
+    ///Adds key with `id`
+    pub fn key(mut self, id: impl Into<String>, secret: impl Into<Vec<u8>>) -> Self {
+        self.keys.insert(id.into(), secret.into());
+        self
+    }
+
+    #[inline]
+    ///Adds header to be covered by signature
+    ///
+    ///Name must be lower case.
+    pub fn header(mut self, name: impl Into<String>) -> Self {
+        self.headers.push(name.into());
+        self
+    }
+
+    #[inline]
+    ///Sets maximum allowed difference between signature timestamp and current time.
+    pub fn max_skew(mut self, max_skew: Duration) -> Self {
+        self.max_skew = max_skew;
+        self
+    }
+
+    #[inline]
+    ///Sets clock to use
+    pub fn clock<C2: Clock>(self, clock: C2) -> HmacSignature<C2> {
+        HmacSignature {
+            keys: self.keys,
+            headers: self.headers,
+            max_skew: self.max_skew,
+            clock,
+        }
+    }
+
+    //Returns id of key, which signed request
+    fn validate<'a>(&self, path: &str, headers: &'a tonic::metadata::MetadataMap) -> Result<&'a str, tonic::Status> {
+        let (key_id, timestamp, signature) = match (headers.get(KEY_ID), headers.get(SIGNATURE_TIMESTAMP), headers.get(SIGNATURE)) {
+            (Some(key_id), Some(timestamp), Some(signature)) => (key_id, timestamp, signature),
+            _ => return Err(tonic::Status::unauthenticated("Missing request signature")),
+        };
+
+        let timestamp = match timestamp.to_str() {
+            Ok(timestamp) => timestamp,
+            Err(_) => return Err(tonic::Status::unauthenticated("Invalid signature timestamp")),
+        };
+        let time = match timestamp.parse::<u64>() {
+            Ok(time) => time,
+            Err(_) => return Err(tonic::Status::unauthenticated("Invalid signature timestamp")),
+        };
+        if self.clock.now().abs_diff(time) > self.max_skew.as_secs() {
+            return Err(tonic::Status::unauthenticated("Signature timestamp is out of range"));
+        }
+
+        let (key_id, secret) = match key_id.to_str().ok().and_then(|key_id| self.keys.get(key_id).map(|secret| (key_id, secret))) {
+            Some(key) => key,
+            None => return Err(tonic::Status::unauthenticated("Unknown key id")),
+        };
+        let signature = match decode_hex(signature.as_encoded_bytes()) {
+            Some(signature) => signature,
+            None => return Err(tonic::Status::unauthenticated("Invalid signature")),
+        };
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts any key length");
+        mac.update(&canonical_string(path, timestamp, &self.headers, headers));
+        match mac.verify_slice(&signature) {
+            Ok(()) => Ok(key_id),
+            Err(_) => Err(tonic::Status::unauthenticated("Invalid signature")),
+        }
+    }
+}
+
+impl<C: Clock> Interceptor for HmacSignature<C> {
+    #[inline]
+    fn on_request(&self, _: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+        Some(tonic::Status::internal("HmacSignature requires request URI"))
+    }
+
+    #[inline]
+    fn on_request_with_uri(&self, uri: &http::Uri, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        match self.validate(uri.path(), headers) {
+            Ok(key_id) => {
+                extensions.insert(PeerIdentity::new(Mechanism::Signature).subject(key_id));
+                None
+            },
+            Err(status) => Some(status),
+        }
+    }
+
+    #[inline(always)]
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
@@ -0,0 +1,514 @@
+//! Authorization of authenticated callers
+//!
+//!`Rbac` grants access to methods by roles of caller, which are provided by authentication interceptor as `identity::PeerIdentity` or `Roles` extension:
+//!
+//!```rust
+//!use tonic_interceptor::authz::Rbac;
+//!
+//!let rbac = Rbac::builder()
+//!    .allow("admin", "AdminService/*")
+//!    .allow("reader", "*/Get*")
+//!    .deny("reader", "*/GetSecret")
+//!    .allow_unauthenticated("/grpc.health.v1.Health/*")
+//!    .build();
+//!```
+//!
+//!Method is denied unless one of caller's roles has matching allow rule, while deny rule takes precedence over any allow rule.
+//!Methods in unauthenticated list are accepted regardless of identity.
+//!
+//!When tables are not enough, `authorize` turns closure into interceptor, while `authorize_async` allows policy engines, which perform I/O.
+//!Closure receives identity of caller, taken from extension of its choice, method path and request metadata:
+//!
+//!```rust
+//!use tonic_interceptor::authz;
+//!
+//!//Tenant of caller, inserted by authentication interceptor
+//!struct TenantId(String);
+//!
+//!//Callers may only access their own tenant
+//!let own_tenant = authz::authorize(|tenant: Option<&TenantId>, method: &str, metadata: &tonic::metadata::MetadataMap| {
+//!    let tenant = tenant.ok_or_else(|| tonic::Status::unauthenticated("missing tenant"))?;
+//!    match metadata.get("x-tenant") {
+//!        Some(requested) if requested == tenant.0.as_str() => Ok(()),
+//!        _ => Err(tonic::Status::permission_denied(format!("method '{}' is not allowed for tenant '{}'", method, tenant.0))),
+//!    }
+//!});
+//!```
+
+use crate::Interceptor;
+use crate::identity::PeerIdentity;
+use crate::asynchronous::{AsyncInterceptor, FromFnFut, RequestHead, Verdict};
+
+use core::fmt;
+use core::future::Future;
+use core::marker::PhantomData;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Default)]
+///Roles of authenticated caller
+///
+///Used by `Rbac` when request has no `identity::PeerIdentity`, which should be preferred.
+pub struct Roles(pub Vec<String>);
+
+impl Roles {
+    #[inline(always)]
+    ///Returns whether caller has `role`
+    pub fn has(&self, role: &str) -> bool {
+        self.0.iter().any(|own| own == role)
+    }
+}
+
+impl<R: Into<String>> core::iter::FromIterator<R> for Roles {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = R>>(roles: I) -> Self {
+        Self(roles.into_iter().map(Into::into).collect())
+    }
+}
+
+//Matches `text` against `pattern` where `*` is any sequence of characters
+fn glob(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.as_bytes();
+    let text = text.as_bytes();
+    let mut pattern_idx = 0;
+    let mut text_idx = 0;
+    //Position of last `*` and text position it was tried at
+    let mut backtrack = None;
+
+    while text_idx < text.len() {
+        match pattern.get(pattern_idx) {
+            Some(b'*') => {
+                backtrack = Some((pattern_idx, text_idx));
+                pattern_idx += 1;
+            },
+            Some(byte) if *byte == text[text_idx] => {
+                pattern_idx += 1;
+                text_idx += 1;
+            },
+            _ => match backtrack {
+                Some((star, start)) => {
+                    pattern_idx = star + 1;
+                    text_idx = start + 1;
+                    backtrack = Some((star, start + 1));
+                },
+                None => return false,
+            },
+        }
+    }
+
+    pattern[pattern_idx..].iter().all(|byte| *byte == b'*')
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+///Pattern of method, where `*` matches any sequence of characters
+///
+///Forms:
+///
+///- `/pkg.Service/Method` or `pkg.Service/Method` - matches full name of service;
+///- `Service/Method` - service without package matches name of service regardless of its package;
+///- `Method` - method of any service;
+///- `*` - any method.
+pub struct MethodPattern {
+    service: Option<String>,
+    method: String,
+}
+
+impl MethodPattern {
+    ///Parses pattern
+    pub fn new(pattern: &str) -> Self {
+        let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+        match pattern.split_once('/') {
+            Some((service, method)) => Self {
+                service: Some(service.to_owned()),
+                method: method.to_owned(),
+            },
+            None => Self {
+                service: None,
+                method: pattern.to_owned(),
+            },
+        }
+    }
+
+    ///Returns whether method `path` is matched
+    pub fn matches(&self, path: &str) -> bool {
+        let path = path.strip_prefix('/').unwrap_or(path);
+        let (service, method) = path.split_once('/').unwrap_or(("", path));
+        if !glob(&self.method, method) {
+            return false;
+        }
+
+        match &self.service {
+            None => true,
+            Some(pattern) if pattern.contains('.') => glob(pattern, service),
+            Some(pattern) => glob(pattern, service) || glob(pattern, service.rsplit('.').next().unwrap_or(service)),
+        }
+    }
+}
+
+impl From<&str> for MethodPattern {
+    #[inline(always)]
+    fn from(pattern: &str) -> Self {
+        Self::new(pattern)
+    }
+}
+
+impl fmt::Display for MethodPattern {
+    #[inline]
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.service {
+            Some(service) => write!(fmt, "{}/{}", service, self.method),
+            None => fmt.write_str(&self.method),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+///Effect of matching rule
+pub enum Effect {
+    ///Method is allowed, unless denied by other rule
+    Allow,
+    ///Method is denied regardless of other rules
+    Deny,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+///Rule granting or revoking access to methods for role
+pub struct RbacRule {
+    ///Role of caller
+    pub role: String,
+    ///Effect of rule
+    pub effect: Effect,
+    ///Methods
+    pub method: MethodPattern,
+}
+
+#[derive(Clone, Debug)]
+///Role based authorization of methods
+///
+///Caller without `PeerIdentity` or `Roles` is rejected with `UNAUTHENTICATED`, while denied method is rejected with `PERMISSION_DENIED`.
+///It must be placed after authentication interceptor.
+pub struct Rbac {
+    rules: Vec<RbacRule>,
+    unauthenticated: Vec<MethodPattern>,
+}
+
+impl Rbac {
+    #[inline(always)]
+    ///Starts building instance, which denies everything
+    pub fn builder() -> RbacBuilder {
+        RbacBuilder {
+            rules: Vec::new(),
+            unauthenticated: Vec::new(),
+        }
+    }
+
+    #[inline(always)]
+    ///Returns rules
+    pub fn rules(&self) -> &[RbacRule] {
+        &self.rules
+    }
+
+    ///Authorizes call of method `path` by caller with `roles`
+    pub fn check(&self, path: &str, roles: Option<&[String]>) -> Result<(), tonic::Status> {
+        if self.unauthenticated.iter().any(|method| method.matches(path)) {
+            return Ok(());
+        }
+
+        let roles = match roles {
+            Some(roles) => roles,
+            None => return Err(tonic::Status::unauthenticated(format!("method '{}' requires authentication", path))),
+        };
+
+        let mut allowed = false;
+        for rule in self.rules.iter().filter(|rule| roles.contains(&rule.role) && rule.method.matches(path)) {
+            match rule.effect {
+                Effect::Deny => {
+                    allowed = false;
+                    break;
+                },
+                Effect::Allow => allowed = true,
+            }
+        }
+
+        match allowed {
+            true => Ok(()),
+            false => Err(tonic::Status::permission_denied(format!("method '{}' is not allowed", path))),
+        }
+    }
+}
+
+#[inline]
+fn roles(extensions: &http::Extensions) -> Option<&[String]> {
+    match PeerIdentity::from_extensions(extensions) {
+        Some(identity) => Some(&identity.roles),
+        None => extensions.get::<Roles>().map(|roles| roles.0.as_slice()),
+    }
+}
+
+impl Interceptor for Rbac {
+    #[inline(always)]
+    fn on_request(&self, _: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+        Some(tonic::Status::internal("Rbac requires request URI"))
+    }
+
+    #[inline]
+    fn on_request_with_uri(&self, uri: &http::Uri, _: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        self.check(uri.path(), roles(extensions)).err()
+    }
+
+    #[inline]
+    fn on_request_headers(&self, uri: &http::Uri, _: &mut http::HeaderMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        self.check(uri.path(), roles(extensions)).err()
+    }
+
+    #[inline(always)]
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
+
+#[derive(Clone, Debug)]
+///Builder of `Rbac`
+pub struct RbacBuilder {
+    rules: Vec<RbacRule>,
+    unauthenticated: Vec<MethodPattern>,
+}
+
+impl RbacBuilder {
+    #[inline]
+    ///Adds rule
+    pub fn rule(mut self, rule: RbacRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    #[inline]
+    ///Allows `role` to call `method`
+    pub fn allow(self, role: impl Into<String>, method: impl Into<MethodPattern>) -> Self {
+        self.rule(RbacRule {
+            role: role.into(),
+            effect: Effect::Allow,
+            method: method.into(),
+        })
+    }
+
+    #[inline]
+    ///Denies `role` to call `method`, regardless of allow rules
+    pub fn deny(self, role: impl Into<String>, method: impl Into<MethodPattern>) -> Self {
+        self.rule(RbacRule {
+            role: role.into(),
+            effect: Effect::Deny,
+            method: method.into(),
+        })
+    }
+
+    #[inline]
+    ///Allows anyone to call `method`, including callers without identity
+    pub fn allow_unauthenticated(mut self, method: impl Into<MethodPattern>) -> Self {
+        self.unauthenticated.push(method.into());
+        self
+    }
+
+    #[inline(always)]
+    ///Creates instance
+    pub fn build(self) -> Rbac {
+        Rbac {
+            rules: self.rules,
+            unauthenticated: self.unauthenticated,
+        }
+    }
+}
+
+///Interceptor created by `authorize`
+pub struct Custom<T, F> {
+    fun: F,
+    _identity: PhantomData<fn(&T)>,
+}
+
+impl<T, F: Clone> Clone for Custom<T, F> {
+    #[inline(always)]
+    fn clone(&self) -> Self {
+        Self {
+            fun: self.fun.clone(),
+            _identity: PhantomData,
+        }
+    }
+}
+
+impl<T, F> fmt::Debug for Custom<T, F> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("Custom").field("identity", &core::any::type_name::<T>()).finish_non_exhaustive()
+    }
+}
+
+impl<T: Send + Sync + 'static, F: Fn(Option<&T>, &str, &tonic::metadata::MetadataMap) -> Result<(), tonic::Status>> Interceptor for Custom<T, F> {
+    #[inline(always)]
+    fn on_request(&self, _: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+        Some(tonic::Status::internal("Custom requires request URI"))
+    }
+
+    #[inline]
+    fn on_request_with_uri(&self, uri: &http::Uri, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        (self.fun)(extensions.get(), uri.path(), headers).err()
+    }
+
+    #[inline(always)]
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
+
+#[inline(always)]
+///Creates interceptor, which authorizes request by `fun`
+///
+///`fun` is called with extension `T` as identity of caller, if present, method path and request metadata.
+///Returning error rejects request.
+pub fn authorize<T, F: Fn(Option<&T>, &str, &tonic::metadata::MetadataMap) -> Result<(), tonic::Status>>(fun: F) -> Custom<T, F> {
+    Custom {
+        fun,
+        _identity: PhantomData,
+    }
+}
+
+///Asynchronous interceptor created by `authorize_async`
+pub struct CustomAsync<T, F> {
+    fun: F,
+    _identity: PhantomData<fn(&T)>,
+}
+
+impl<T, F: Clone> Clone for CustomAsync<T, F> {
+    #[inline(always)]
+    fn clone(&self) -> Self {
+        Self {
+            fun: self.fun.clone(),
+            _identity: PhantomData,
+        }
+    }
+}
+
+impl<T, F> fmt::Debug for CustomAsync<T, F> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("CustomAsync").field("identity", &core::any::type_name::<T>()).finish_non_exhaustive()
+    }
+}
+
+impl<T: Send + Sync + 'static, R: Verdict, Fut: Future<Output = R>, F: Fn(Option<&T>, &str, &tonic::metadata::MetadataMap) -> Fut> AsyncInterceptor for CustomAsync<T, F> {
+    type Future = FromFnFut<Fut>;
+
+    #[inline]
+    fn on_request(&self, request: RequestHead) -> Self::Future {
+        let fut = (self.fun)(request.extensions.get(), request.uri().path(), &request.headers);
+        FromFnFut::new(request, fut)
+    }
+}
+
+#[inline(always)]
+///Creates asynchronous interceptor, which authorizes request by `fun`
+///
+///Arguments are the same as in `authorize`, but returned future must own everything it needs.
+///It resolves into `Result<(), tonic::Status>` or `Option<tonic::Status>`.
+pub fn authorize_async<T, R: Verdict, Fut: Future<Output = R>, F: Fn(Option<&T>, &str, &tonic::metadata::MetadataMap) -> Fut>(fun: F) -> CustomAsync<T, F> {
+    CustomAsync {
+        fun,
+        _identity: PhantomData,
+    }
+}
+
+#[cfg(feature = "serde")]
+mod de {
+    use super::{Rbac, MethodPattern};
+
+    use core::fmt;
+    use serde::de;
+
+    //Role with its allowed and denied methods
+    struct RoleRules {
+        role: String,
+        allow: Vec<String>,
+        deny: Vec<String>,
+    }
+
+    struct RoleRulesVisitor;
+
+    impl<'de> de::Visitor<'de> for RoleRulesVisitor {
+        type Value = RoleRules;
+
+        fn expecting(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+            fmt.write_str("role rules")
+        }
+
+        fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+            const FIELDS: &[&str] = &["role", "allow", "deny"];
+
+            let mut role = None;
+            let mut allow = None;
+            let mut deny = None;
+
+            while let Some(field) = map.next_key::<String>()? {
+                match field.as_str() {
+                    "role" if role.is_none() => role = Some(map.next_value()?),
+                    "allow" if allow.is_none() => allow = Some(map.next_value()?),
+                    "deny" if deny.is_none() => deny = Some(map.next_value()?),
+                    "role" => return Err(de::Error::duplicate_field("role")),
+                    "allow" => return Err(de::Error::duplicate_field("allow")),
+                    "deny" => return Err(de::Error::duplicate_field("deny")),
+                    _ => return Err(de::Error::unknown_field(&field, FIELDS)),
+                }
+            }
+
+            Ok(RoleRules {
+                role: role.ok_or_else(|| de::Error::missing_field("role"))?,
+                allow: allow.unwrap_or_default(),
+                deny: deny.unwrap_or_default(),
+            })
+        }
+    }
+
+    impl<'de> de::Deserialize<'de> for RoleRules {
+        #[inline(always)]
+        fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            deserializer.deserialize_map(RoleRulesVisitor)
+        }
+    }
+
+    struct RbacVisitor;
+
+    impl<'de> de::Visitor<'de> for RbacVisitor {
+        type Value = Rbac;
+
+        fn expecting(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+            fmt.write_str("rbac configuration")
+        }
+
+        fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+            const FIELDS: &[&str] = &["rules", "allow_unauthenticated"];
+
+            let mut rules: Option<Vec<RoleRules>> = None;
+            let mut unauthenticated: Option<Vec<String>> = None;
+
+            while let Some(field) = map.next_key::<String>()? {
+                match field.as_str() {
+                    "rules" if rules.is_none() => rules = Some(map.next_value()?),
+                    "allow_unauthenticated" if unauthenticated.is_none() => unauthenticated = Some(map.next_value()?),
+                    "rules" => return Err(de::Error::duplicate_field("rules")),
+                    "allow_unauthenticated" => return Err(de::Error::duplicate_field("allow_unauthenticated")),
+                    _ => return Err(de::Error::unknown_field(&field, FIELDS)),
+                }
+            }
+
+            let mut builder = rules.unwrap_or_default().into_iter().fold(Rbac::builder(), |builder, rules| {
+                let builder = rules.allow.iter().fold(builder, |builder, method| builder.allow(rules.role.as_str(), method.as_str()));
+                rules.deny.iter().fold(builder, |builder, method| builder.deny(rules.role.as_str(), method.as_str()))
+            });
+            for method in unauthenticated.unwrap_or_default() {
+                builder = builder.allow_unauthenticated(MethodPattern::new(&method));
+            }
+            Ok(builder.build())
+        }
+    }
+
+    impl<'de> de::Deserialize<'de> for Rbac {
+        #[inline(always)]
+        fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            deserializer.deserialize_map(RbacVisitor)
+        }
+    }
+}
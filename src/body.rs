@@ -0,0 +1,460 @@
+//! Inspection of request messages
+//!
+//!`UnaryInspector` is a layer, which buffers request message of selected unary methods and checks it before inner service is called:
+//!
+//!```rust
+//!use tonic_interceptor::body::UnaryInspector;
+//!
+//!let inspector = UnaryInspector::new("/pkg.Search/Query", |message: &[u8]| match message.is_empty() {
+//!    true => Err(tonic::Status::invalid_argument("empty query")),
+//!    false => Ok(()),
+//!}).max_message_size(16 * 1024);
+//!//Pass `inspector.layer()` to `Server::layer`
+//!# let _ = inspector.layer();
+//!```
+//!
+//!With `prost` feature, message can be decoded using `UnaryInspector::with_message`.
+//!
+//!Once check passes, buffered body is passed to inner service as it is.
+//!Compressed messages are rejected, unless `gzip` feature is enabled, which allows to decompress `gzip` messages.
+
+use crate::matcher::MethodMatcher;
+use crate::response::{self, ResponseTemplate};
+use crate::compat::{self, Body as _};
+use crate::EmptyBody;
+
+use core::fmt;
+use core::task;
+use core::pin::Pin;
+use core::future::Future;
+use std::sync::Arc;
+
+use bytes::{Buf, Bytes, BytesMut};
+
+//Compressed flag and length
+const FRAME_HEADER_SIZE: usize = 5;
+
+//Check of message, given method path
+pub(crate) type Check = dyn Fn(&str, &[u8]) -> Result<(), tonic::Status> + Send + Sync;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+///Handling of requests, which message cannot be inspected
+pub enum Uninspectable {
+    ///Pass request to inner service without inspection
+    PassThrough,
+    ///Reject request
+    Reject,
+}
+
+#[derive(Clone)]
+///Layer inspecting request message of unary methods
+///
+///Request body is buffered until single message is complete and request stream is over,
+///hence only methods, which client sends single message, should be matched.
+///
+///- Message over `max_message_size` (64KiB by default) is rejected with `RESOURCE_EXHAUSTED`, unless configured to pass through;
+///- Request of streaming method, i.e. with more than one message, is passed through, unless configured to be rejected with `UNIMPLEMENTED`.
+///
+///Note that request of streaming method is detected only once client sends its second message.
+pub struct UnaryInspector {
+    matchers: Vec<MethodMatcher>,
+    check: Arc<Check>,
+    max_message_size: usize,
+    on_oversized: Uninspectable,
+    on_streaming: Uninspectable,
+}
+
+impl UnaryInspector {
+    #[inline]
+    ///Creates new instance, checking raw message of methods matched by `matcher`
+    pub fn new<F: Fn(&[u8]) -> Result<(), tonic::Status> + Send + Sync + 'static>(matcher: impl Into<MethodMatcher>, check: F) -> Self {
+        Self::with_methods(vec![matcher.into()], Arc::new(move |_: &str, message: &[u8]| check(message)))
+    }
+
+    pub(crate) fn with_methods(matchers: Vec<MethodMatcher>, check: Arc<Check>) -> Self {
+        Self {
+            matchers,
+            check,
+            max_message_size: 64 * 1024,
+            on_oversized: Uninspectable::Reject,
+            on_streaming: Uninspectable::PassThrough,
+        }
+    }
+
+    #[cfg(feature = "prost")]
+    #[inline]
+    ///Creates new instance, checking message `M` of methods matched by `matcher`
+    ///
+    ///Message, which cannot be decoded, is rejected with `INVALID_ARGUMENT`.
+    pub fn with_message<M: prost::Message + Default, F: Fn(&M) -> Result<(), tonic::Status> + Send + Sync + 'static>(matcher: impl Into<MethodMatcher>, check: F) -> Self {
+        Self::new(matcher, move |message: &[u8]| match M::decode(message) {
+            Ok(message) => check(&message),
+            Err(error) => Err(tonic::Status::invalid_argument(format!("invalid message: {}", error))),
+        })
+    }
+
+    #[inline]
+    ///Sets maximum size of inspected message, applied to decompressed message too
+    pub fn max_message_size(mut self, max_message_size: usize) -> Self {
+        self.max_message_size = max_message_size;
+        self
+    }
+
+    #[inline]
+    ///Sets handling of message over maximum size
+    pub fn on_oversized(mut self, on_oversized: Uninspectable) -> Self {
+        self.on_oversized = on_oversized;
+        self
+    }
+
+    #[inline]
+    ///Sets handling of request with more than one message
+    pub fn on_streaming(mut self, on_streaming: Uninspectable) -> Self {
+        self.on_streaming = on_streaming;
+        self
+    }
+
+    #[inline]
+    ///Returns layer
+    pub fn layer(&self) -> UnaryInspectorLayer {
+        UnaryInspectorLayer {
+            inspector: self.clone(),
+        }
+    }
+
+    #[inline]
+    fn matches(&self, path: &str) -> bool {
+        self.matchers.iter().any(|matcher| matcher.matches(path))
+    }
+
+    //Checks complete frame
+    fn inspect(&self, path: &str, frame: &[u8], headers: &http::HeaderMap) -> Result<(), tonic::Status> {
+        let message = &frame[FRAME_HEADER_SIZE..];
+        match frame[0] {
+            0 => (self.check)(path, message),
+            1 => {
+                let encoding = headers.get("grpc-encoding").map(|encoding| encoding.to_str().unwrap_or("<invalid>")).unwrap_or("identity");
+                match encoding {
+                    #[cfg(feature = "gzip")]
+                    "gzip" => (self.check)(path, &self.decompress_gzip(message)?),
+                    encoding => Err(tonic::Status::unimplemented(format!("message compression '{}' is not supported", encoding))),
+                }
+            },
+            flag => Err(tonic::Status::internal(format!("invalid compression flag {}", flag))),
+        }
+    }
+
+    #[cfg(feature = "gzip")]
+    fn decompress_gzip(&self, message: &[u8]) -> Result<Vec<u8>, tonic::Status> {
+        use std::io::Read;
+
+        let mut decompressed = Vec::new();
+        let limit = self.max_message_size as u64 + 1;
+        if let Err(error) = flate2::read::GzDecoder::new(message).take(limit).read_to_end(&mut decompressed) {
+            return Err(tonic::Status::internal(format!("failed to decompress message: {}", error)));
+        }
+        match decompressed.len() > self.max_message_size {
+            true => Err(tonic::Status::resource_exhausted(format!("decompressed message exceeds limit of {} bytes", self.max_message_size))),
+            false => Ok(decompressed),
+        }
+    }
+}
+
+impl fmt::Debug for UnaryInspector {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("UnaryInspector").field("matchers", &self.matchers).field("max_message_size", &self.max_message_size)
+                                           .field("on_oversized", &self.on_oversized).field("on_streaming", &self.on_streaming).finish_non_exhaustive()
+    }
+}
+
+#[derive(Clone, Debug)]
+///Layer of `UnaryInspector`
+pub struct UnaryInspectorLayer {
+    inspector: UnaryInspector,
+}
+
+impl<S> tower_layer::Layer<S> for UnaryInspectorLayer {
+    type Service = UnaryInspectorService<S>;
+
+    #[inline(always)]
+    fn layer(&self, inner: S) -> Self::Service {
+        UnaryInspectorService {
+            inspector: self.inspector.clone(),
+            inner,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+///Service of `UnaryInspector`
+pub struct UnaryInspectorService<S> {
+    inspector: UnaryInspector,
+    inner: S,
+}
+
+impl<S: tonic::server::NamedService> tonic::server::NamedService for UnaryInspectorService<S> {
+    const NAME: &'static str = S::NAME;
+}
+
+impl<ReqBody, ResBody, S> tower_service::Service<http::Request<ReqBody>> for UnaryInspectorService<S> where ReqBody: http_body::Body<Data = Bytes> + Unpin, ReqBody::Error: Into<crate::client::BoxError>, S: tower_service::Service<http::Request<InspectedBody<ReqBody>>, Response = http::Response<ResBody>> + Clone, ResBody: EmptyBody {
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = UnaryInspectorFut<S, ReqBody>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        if !self.inspector.matches(req.uri().path()) {
+            return UnaryInspectorFut {
+                state: State::Call {
+                    fut: self.inner.call(req.map(InspectedBody::inner)),
+                },
+            };
+        }
+
+        //Inner service is ready, hence it is used once message is buffered
+        let inner = self.inner.clone();
+        let inner = core::mem::replace(&mut self.inner, inner);
+        let (parts, body) = req.into_parts();
+        UnaryInspectorFut {
+            state: State::Buffer {
+                buffer: Some(Buffer {
+                    inspector: self.inspector.clone(),
+                    inner,
+                    parts,
+                    body: compat::Incoming::new(body),
+                    data: BytesMut::new(),
+                }),
+            },
+        }
+    }
+}
+
+//Request being buffered
+struct Buffer<S, B> {
+    inspector: UnaryInspector,
+    inner: S,
+    parts: http::request::Parts,
+    body: compat::Incoming<B>,
+    data: BytesMut,
+}
+
+//Outcome of buffering
+enum Buffered {
+    //Single message is received
+    Complete,
+    //Message exceeds limit
+    Oversized(usize),
+    //More than one message is received
+    Streaming,
+    //Request stream ended without complete message, passed to inner service as it is
+    Incomplete,
+    //Request body failed
+    Failed(tonic::Status),
+}
+
+impl<S, B: http_body::Body<Data = Bytes> + Unpin> Buffer<S, B> where B::Error: Into<crate::client::BoxError> {
+    fn poll_buffer(&mut self, ctx: &mut task::Context<'_>) -> task::Poll<Buffered> {
+        loop {
+            if self.data.len() >= FRAME_HEADER_SIZE {
+                let len = (&self.data[1..FRAME_HEADER_SIZE]).get_u32() as usize;
+                if len > self.inspector.max_message_size {
+                    return task::Poll::Ready(Buffered::Oversized(len));
+                }
+                let frame_size = FRAME_HEADER_SIZE + len;
+                if self.data.len() > frame_size {
+                    return task::Poll::Ready(Buffered::Streaming);
+                } else if self.data.len() == frame_size && self.body.is_end_stream() {
+                    return task::Poll::Ready(Buffered::Complete);
+                }
+            }
+
+            match Pin::new(&mut self.body).poll_data(ctx) {
+                task::Poll::Ready(Some(Ok(data))) => self.data.extend_from_slice(&data),
+                task::Poll::Ready(Some(Err(error))) => return task::Poll::Ready(Buffered::Failed(tonic::Status::from_error(error.into()))),
+                task::Poll::Ready(None) => {
+                    let is_complete = self.data.len() >= FRAME_HEADER_SIZE && self.data.len() == FRAME_HEADER_SIZE + (&self.data[1..FRAME_HEADER_SIZE]).get_u32() as usize;
+                    return task::Poll::Ready(match is_complete {
+                        true => Buffered::Complete,
+                        false => Buffered::Incomplete,
+                    });
+                },
+                task::Poll::Pending => return task::Poll::Pending,
+            }
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    #[project = StateProj]
+    enum State<S: tower_service::Service<http::Request<InspectedBody<B>>>, B> {
+        Buffer {
+            buffer: Option<Buffer<S, B>>,
+        },
+        Call {
+            #[pin]
+            fut: S::Future,
+        },
+        Rejected {
+            status: Option<(tonic::Status, ResponseTemplate)>,
+        },
+    }
+}
+
+pin_project_lite::pin_project! {
+    ///Future of `UnaryInspectorService`
+    pub struct UnaryInspectorFut<S: tower_service::Service<http::Request<InspectedBody<B>>>, B> {
+        #[pin]
+        state: State<S, B>,
+    }
+}
+
+impl<S: tower_service::Service<http::Request<InspectedBody<B>>>, B> fmt::Debug for UnaryInspectorFut<S, B> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = match self.state {
+            State::Buffer { .. } => "Buffer",
+            State::Call { .. } => "Call",
+            State::Rejected { .. } => "Rejected",
+        };
+        fmt.debug_struct("UnaryInspectorFut").field("state", &state).finish_non_exhaustive()
+    }
+}
+
+impl<ResBody, B, S> Future for UnaryInspectorFut<S, B> where B: http_body::Body<Data = Bytes> + Unpin, B::Error: Into<crate::client::BoxError>, S: tower_service::Service<http::Request<InspectedBody<B>>, Response = http::Response<ResBody>>, ResBody: EmptyBody {
+    type Output = Result<S::Response, S::Error>;
+
+    fn poll(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let mut this = self.project();
+        loop {
+            let next = match this.state.as_mut().project() {
+                StateProj::Call { fut } => return fut.poll(ctx),
+                StateProj::Rejected { status } => {
+                    let (status, template) = status.take().expect("Future polled after completion");
+                    return task::Poll::Ready(Ok(response::status_to_response(&status, &template)));
+                },
+                StateProj::Buffer { buffer: state } => {
+                    let buffer = state.as_mut().expect("Future polled after completion");
+                    let buffered = match buffer.poll_buffer(ctx) {
+                        task::Poll::Ready(buffered) => buffered,
+                        task::Poll::Pending => return task::Poll::Pending,
+                    };
+                    let mut buffer = state.take().expect("Future polled after completion");
+
+                    let inspector = &buffer.inspector;
+                    let rejection = match buffered {
+                        Buffered::Complete => inspector.inspect(buffer.parts.uri.path(), &buffer.data, &buffer.parts.headers).err(),
+                        Buffered::Oversized(len) => match inspector.on_oversized {
+                            Uninspectable::PassThrough => None,
+                            Uninspectable::Reject => Some(tonic::Status::resource_exhausted(format!("message of {} bytes exceeds limit of {} bytes", len, inspector.max_message_size))),
+                        },
+                        Buffered::Streaming => match inspector.on_streaming {
+                            Uninspectable::PassThrough => None,
+                            Uninspectable::Reject => Some(tonic::Status::unimplemented("streaming request is not allowed")),
+                        },
+                        Buffered::Incomplete => None,
+                        Buffered::Failed(status) => Some(status),
+                    };
+
+                    match rejection {
+                        Some(status) => State::Rejected {
+                            status: Some((status, ResponseTemplate::from_parts(&buffer.parts.headers, buffer.parts.version))),
+                        },
+                        None => {
+                            let body = InspectedBody {
+                                buffered: Some(buffer.data.freeze()).filter(|data| !data.is_empty()),
+                                rest: Some(buffer.body),
+                            };
+                            State::Call {
+                                fut: buffer.inner.call(http::Request::from_parts(buffer.parts, body)),
+                            }
+                        },
+                    }
+                },
+            };
+            this.state.set(next);
+        }
+    }
+}
+
+///Request body passed to inner service by `UnaryInspectorService`
+///
+///It yields buffered data first, followed by the rest of request body.
+pub struct InspectedBody<B> {
+    buffered: Option<Bytes>,
+    rest: Option<compat::Incoming<B>>,
+}
+
+impl<B> InspectedBody<B> {
+    #[inline(always)]
+    fn inner(body: B) -> Self {
+        Self {
+            buffered: None,
+            rest: Some(compat::Incoming::new(body)),
+        }
+    }
+
+    #[inline(always)]
+    ///Returns whether request message is buffered
+    pub fn is_buffered(&self) -> bool {
+        self.buffered.is_some()
+    }
+}
+
+impl<B: Default> Default for InspectedBody<B> {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::inner(B::default())
+    }
+}
+
+impl<B> fmt::Debug for InspectedBody<B> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("InspectedBody").field("buffered", &self.buffered).finish_non_exhaustive()
+    }
+}
+
+compat::impl_body! {
+    [B: http_body::Body<Data = Bytes> + Unpin] InspectedBody<B> {
+        type Data = Bytes;
+        type Error = B::Error;
+
+        fn poll_data(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Option<Result<Self::Data, Self::Error>>> {
+            let this = self.get_mut();
+            if let Some(buffered) = this.buffered.take() {
+                return task::Poll::Ready(Some(Ok(buffered)));
+            }
+            match this.rest.as_mut() {
+                Some(rest) => Pin::new(rest).poll_data(ctx),
+                None => task::Poll::Ready(None),
+            }
+        }
+
+        fn poll_trailers(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Result<Option<http::HeaderMap>, Self::Error>> {
+            match self.get_mut().rest.as_mut() {
+                Some(rest) => Pin::new(rest).poll_trailers(ctx),
+                None => task::Poll::Ready(Ok(None)),
+            }
+        }
+
+        #[inline]
+        fn is_end_stream(&self) -> bool {
+            self.buffered.is_none() && self.rest.as_ref().is_none_or(|rest| rest.is_end_stream())
+        }
+
+        #[inline]
+        fn size_hint(&self) -> http_body::SizeHint {
+            let buffered = self.buffered.as_ref().map_or(0, |buffered| buffered.len() as u64);
+            let mut hint = self.rest.as_ref().map_or_else(|| http_body::SizeHint::with_exact(0), |rest| rest.size_hint());
+            if let Some(upper) = hint.upper() {
+                hint.set_upper(upper + buffered);
+            }
+            hint.set_lower(hint.lower() + buffered);
+            hint
+        }
+    }
+}
@@ -0,0 +1,168 @@
+//! Fluent interceptor builder
+//!
+//!```rust,ignore
+//!let interceptor = tonic_interceptor::InterceptorBuilder::new().on_request(|headers, _| {
+//!    headers.remove("x-internal");
+//!    None
+//!}).build();
+//!```
+//!
+//!Callbacks that are not provided do nothing, using zero-sized `Noop`.
+
+use crate::{Interceptor, ErrorHandler, PropagateError};
+
+///Callback on incoming request
+///
+///Implemented for `Fn(&mut tonic::metadata::MetadataMap, &mut http::Extensions) -> Option<tonic::Status>` and `Noop`
+pub trait RequestCallback {
+    ///Calls callback
+    fn call(&self, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status>;
+}
+
+impl<F: Fn(&mut tonic::metadata::MetadataMap, &mut http::Extensions) -> Option<tonic::Status>> RequestCallback for F {
+    #[inline(always)]
+    fn call(&self, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        (self)(headers, extensions)
+    }
+}
+
+///Callback on response
+///
+///Implemented for `Fn(tonic::Code, &mut http::HeaderMap, &http::Extensions)` and `Noop`
+pub trait ResponseCallback {
+    ///Calls callback
+    fn call(&self, status: tonic::Code, headers: &mut http::HeaderMap, extensions: &http::Extensions);
+}
+
+impl<F: Fn(tonic::Code, &mut http::HeaderMap, &http::Extensions)> ResponseCallback for F {
+    #[inline(always)]
+    fn call(&self, status: tonic::Code, headers: &mut http::HeaderMap, extensions: &http::Extensions) {
+        (self)(status, headers, extensions)
+    }
+}
+
+#[derive(Clone, Copy, Default, Debug)]
+///Callback, which does nothing
+pub struct Noop;
+
+impl RequestCallback for Noop {
+    #[inline(always)]
+    fn call(&self, _: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+        None
+    }
+}
+
+impl ResponseCallback for Noop {
+    #[inline(always)]
+    fn call(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+///Builder of interceptor out of closures
+pub struct InterceptorBuilder<OnReq = Noop, OnResp = Noop, OnErr = PropagateError> {
+    on_request: OnReq,
+    on_response: OnResp,
+    on_error: OnErr,
+}
+
+impl<OnReq, OnResp, OnErr> core::fmt::Debug for InterceptorBuilder<OnReq, OnResp, OnErr> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("InterceptorBuilder").finish_non_exhaustive()
+    }
+}
+
+impl InterceptorBuilder {
+    #[inline(always)]
+    ///Creates new instance, which does nothing
+    pub const fn new() -> Self {
+        Self {
+            on_request: Noop,
+            on_response: Noop,
+            on_error: PropagateError,
+        }
+    }
+}
+
+impl<OnReq, OnResp, OnErr> InterceptorBuilder<OnReq, OnResp, OnErr> {
+    #[inline]
+    ///Sets callback on incoming request
+    ///
+    ///See `Interceptor::on_request`.
+    pub fn on_request<F: Fn(&mut tonic::metadata::MetadataMap, &mut http::Extensions) -> Option<tonic::Status>>(self, fun: F) -> InterceptorBuilder<F, OnResp, OnErr> {
+        InterceptorBuilder {
+            on_request: fun,
+            on_response: self.on_response,
+            on_error: self.on_error,
+        }
+    }
+
+    #[inline]
+    ///Sets callback on response
+    ///
+    ///See `Interceptor::on_response`.
+    pub fn on_response<F: Fn(tonic::Code, &mut http::HeaderMap, &http::Extensions)>(self, fun: F) -> InterceptorBuilder<OnReq, F, OnErr> {
+        InterceptorBuilder {
+            on_request: self.on_request,
+            on_response: fun,
+            on_error: self.on_error,
+        }
+    }
+
+    #[inline]
+    ///Sets handler of inner service errors
+    ///
+    ///Built interceptor is `ErrorHandler` itself, hence it should be passed to `InterceptorService::with_error_handler` to take effect.
+    pub fn on_error<E, F: Fn(&E) -> Option<tonic::Status>>(self, fun: F) -> InterceptorBuilder<OnReq, OnResp, F> {
+        InterceptorBuilder {
+            on_request: self.on_request,
+            on_response: self.on_response,
+            on_error: fun,
+        }
+    }
+
+    #[inline(always)]
+    ///Builds interceptor
+    pub fn build(self) -> Built<OnReq, OnResp, OnErr> {
+        Built {
+            on_request: self.on_request,
+            on_response: self.on_response,
+            on_error: self.on_error,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+///Interceptor created by `InterceptorBuilder`
+pub struct Built<OnReq = Noop, OnResp = Noop, OnErr = PropagateError> {
+    on_request: OnReq,
+    on_response: OnResp,
+    on_error: OnErr,
+}
+
+impl<OnReq, OnResp, OnErr> core::fmt::Debug for Built<OnReq, OnResp, OnErr> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("Built").finish_non_exhaustive()
+    }
+}
+
+impl<OnReq: RequestCallback, OnResp: ResponseCallback, OnErr> Interceptor for Built<OnReq, OnResp, OnErr> {
+    #[inline(always)]
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        self.on_request.call(headers, extensions)
+    }
+
+    #[inline(always)]
+    fn on_response(&self, status: tonic::Code, headers: &mut http::HeaderMap, extensions: &http::Extensions) {
+        self.on_response.call(status, headers, extensions)
+    }
+}
+
+impl<E, OnReq, OnResp, OnErr: ErrorHandler<E>> ErrorHandler<E> for Built<OnReq, OnResp, OnErr> {
+    #[inline(always)]
+    fn on_error(&self, error: &E) -> Option<tonic::Status> {
+        self.on_error.on_error(error)
+    }
+}
@@ -0,0 +1,494 @@
+//! Response cache of unary methods
+//!
+//!`UnaryCache` is a layer, which keeps successful responses of selected methods for short time:
+//!
+//!```rust
+//!use tonic_interceptor::cache::UnaryCache;
+//!
+//!use core::time::Duration;
+//!
+//!let cache = UnaryCache::new("/pkg.Config/*", Duration::from_secs(5)).vary("x-tenant").max_entries(100);
+//!//Pass `cache.layer()` to `Server::layer`
+//!# let _ = cache.layer();
+//!```
+//!
+//!Key of response is method path and values of selected metadata, while request message is not part of it.
+//!Hence only methods, which response is determined by metadata alone, such as `GetConfig` or `ListRegions`, should be cached.
+//!
+//!Response is stored once its trailers report `OK` status, provided its body fits within size limit.
+//!Cached response is replayed with the same headers, body and trailers, without calling inner service.
+
+use crate::matcher::MethodMatcher;
+use crate::timer::{Clock, Instant, StdClock};
+use crate::compat;
+
+use core::task;
+use core::pin::Pin;
+use core::future::Future;
+use core::time::Duration;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use bytes::{Bytes, BytesMut};
+
+const GRPC_STATUS_HEADER_CODE: &str = "grpc-status";
+
+struct Entry {
+    headers: http::HeaderMap,
+    data: Bytes,
+    trailers: http::HeaderMap,
+    expires: Instant,
+}
+
+struct Store {
+    entries: HashMap<Vec<u8>, Arc<Entry>>,
+    max_entries: usize,
+}
+
+impl Store {
+    fn get(&mut self, key: &[u8], now: Instant) -> Option<Arc<Entry>> {
+        match self.entries.get(key) {
+            Some(entry) if entry.expires > now => Some(entry.clone()),
+            Some(_) => {
+                self.entries.remove(key);
+                None
+            },
+            None => None,
+        }
+    }
+
+    fn insert(&mut self, key: Vec<u8>, entry: Entry, now: Instant) {
+        if self.entries.len() >= self.max_entries && !self.entries.contains_key(&key) {
+            self.entries.retain(|_, entry| entry.expires > now);
+            if self.entries.len() >= self.max_entries {
+                let oldest = self.entries.iter().min_by_key(|(_, entry)| entry.expires).map(|(key, _)| key.clone());
+                if let Some(oldest) = oldest {
+                    self.entries.remove(&oldest);
+                }
+            }
+        }
+        self.entries.insert(key, Arc::new(entry));
+    }
+}
+
+fn lock(store: &Mutex<Store>) -> std::sync::MutexGuard<'_, Store> {
+    match store.lock() {
+        Ok(store) => store,
+        Err(error) => error.into_inner(),
+    }
+}
+
+///Layer caching successful responses of unary methods
+///
+///By default up to 1000 responses are kept, each with body of at most 64KiB.
+///Once full, expired responses are evicted first, then ones closest to expiration.
+///Expiration is measured by `Clock`, which is `StdClock` by default.
+pub struct UnaryCache<C = StdClock> {
+    matcher: MethodMatcher,
+    ttl: Duration,
+    max_entry_size: usize,
+    vary: Vec<http::header::HeaderName>,
+    store: Arc<Mutex<Store>>,
+    clock: Arc<C>,
+}
+
+impl UnaryCache {
+    #[inline]
+    ///Creates new instance, caching responses of methods matched by `matcher` for `ttl`
+    pub fn new(matcher: impl Into<MethodMatcher>, ttl: Duration) -> Self {
+        Self {
+            matcher: matcher.into(),
+            ttl,
+            max_entry_size: 64 * 1024,
+            vary: Vec::new(),
+            store: Arc::new(Mutex::new(Store {
+                entries: HashMap::new(),
+                max_entries: 1000,
+            })),
+            clock: Arc::new(StdClock),
+        }
+    }
+}
+
+impl<C> UnaryCache<C> {
+    #[inline]
+    ///Sets clock, which measures expiration
+    pub fn clock<C2: Clock>(self, clock: C2) -> UnaryCache<C2> {
+        UnaryCache {
+            matcher: self.matcher,
+            ttl: self.ttl,
+            max_entry_size: self.max_entry_size,
+            vary: self.vary,
+            store: self.store,
+            clock: Arc::new(clock),
+        }
+    }
+
+    #[inline]
+    ///Sets maximum size of cached response body
+    pub fn max_entry_size(mut self, max_entry_size: usize) -> Self {
+        self.max_entry_size = max_entry_size;
+        self
+    }
+
+    #[inline]
+    ///Sets maximum number of cached responses, at least one
+    pub fn max_entries(self, max_entries: usize) -> Self {
+        lock(&self.store).max_entries = max_entries.max(1);
+        self
+    }
+
+    #[inline]
+    ///Adds metadata `key`, which values are part of cache key
+    ///
+    ///Panics if key is not valid metadata key.
+    pub fn vary(mut self, key: &str) -> Self {
+        match http::header::HeaderName::from_bytes(key.as_bytes()) {
+            Ok(key) => self.vary.push(key),
+            Err(_) => panic!("UnaryCache::vary: invalid metadata key '{}'", key),
+        }
+        self
+    }
+
+    #[inline]
+    ///Returns number of cached responses, including expired ones, which are not evicted yet
+    pub fn len(&self) -> usize {
+        lock(&self.store).entries.len()
+    }
+
+    #[inline]
+    ///Returns whether there are no cached responses
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    #[inline]
+    ///Removes every cached response
+    pub fn clear(&self) {
+        lock(&self.store).entries.clear();
+    }
+
+    #[inline]
+    ///Returns layer
+    pub fn layer(&self) -> UnaryCacheLayer<C> {
+        UnaryCacheLayer {
+            cache: self.clone(),
+        }
+    }
+
+    //Returns cache key of request, if its method is cached
+    fn key(&self, path: &str, headers: &http::HeaderMap) -> Option<Vec<u8>> {
+        if !self.matcher.matches(path) {
+            return None;
+        }
+
+        //Metadata values cannot contain NUL, while SOH separates values of the same key
+        let mut key = path.as_bytes().to_vec();
+        for name in self.vary.iter() {
+            key.push(0);
+            for value in headers.get_all(name) {
+                key.push(1);
+                key.extend_from_slice(value.as_bytes());
+            }
+        }
+        Some(key)
+    }
+}
+
+impl<C> Clone for UnaryCache<C> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            matcher: self.matcher.clone(),
+            ttl: self.ttl,
+            max_entry_size: self.max_entry_size,
+            vary: self.vary.clone(),
+            store: self.store.clone(),
+            clock: self.clock.clone(),
+        }
+    }
+}
+
+impl<C> core::fmt::Debug for UnaryCache<C> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("UnaryCache").field("matcher", &self.matcher).field("ttl", &self.ttl).field("max_entry_size", &self.max_entry_size).field("vary", &self.vary).finish_non_exhaustive()
+    }
+}
+
+#[derive(Clone, Debug)]
+///Layer of `UnaryCache`
+pub struct UnaryCacheLayer<C = StdClock> {
+    cache: UnaryCache<C>,
+}
+
+impl<S, C> tower_layer::Layer<S> for UnaryCacheLayer<C> {
+    type Service = UnaryCacheService<S, C>;
+
+    #[inline(always)]
+    fn layer(&self, inner: S) -> Self::Service {
+        UnaryCacheService {
+            cache: self.cache.clone(),
+            inner,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+///Service of `UnaryCache`
+pub struct UnaryCacheService<S, C = StdClock> {
+    cache: UnaryCache<C>,
+    inner: S,
+}
+
+impl<S: tonic::server::NamedService, C> tonic::server::NamedService for UnaryCacheService<S, C> {
+    const NAME: &'static str = S::NAME;
+}
+
+impl<ReqBody, ResBody, S: tower_service::Service<http::Request<ReqBody>, Response = http::Response<ResBody>>, C: Clock + Send + Sync + 'static> tower_service::Service<http::Request<ReqBody>> for UnaryCacheService<S, C> {
+    type Response = http::Response<CachedBody<ResBody>>;
+    type Error = S::Error;
+    type Future = UnaryCacheFut<S::Future, ResBody>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        let key = match self.cache.key(req.uri().path(), req.headers()) {
+            Some(key) => key,
+            None => return UnaryCacheFut {
+                hit: None,
+                fill: None,
+                inner: Some(self.inner.call(req)),
+            },
+        };
+
+        let cached = lock(&self.cache.store).get(&key, self.cache.clock.now());
+        match cached {
+            Some(entry) => {
+                let mut response = http::Response::new(CachedBody::cached(entry.data.clone(), entry.trailers.clone()));
+                *response.headers_mut() = entry.headers.clone();
+                UnaryCacheFut {
+                    hit: Some(response),
+                    fill: None,
+                    inner: None,
+                }
+            },
+            None => UnaryCacheFut {
+                hit: None,
+                fill: Some(Fill {
+                    store: self.cache.store.clone(),
+                    key,
+                    headers: http::HeaderMap::new(),
+                    data: BytesMut::new(),
+                    limit: self.cache.max_entry_size,
+                    ttl: self.cache.ttl,
+                    clock: self.cache.clock.clone(),
+                }),
+                inner: Some(self.inner.call(req)),
+            },
+        }
+    }
+}
+
+//Response being buffered to be stored once it is complete
+struct Fill {
+    store: Arc<Mutex<Store>>,
+    key: Vec<u8>,
+    headers: http::HeaderMap,
+    data: BytesMut,
+    limit: usize,
+    ttl: Duration,
+    clock: Arc<dyn Clock + Send + Sync>,
+}
+
+impl Fill {
+    //Returns whether response can still be stored
+    #[inline]
+    fn push(&mut self, data: &Bytes) -> bool {
+        if self.data.len() + data.len() > self.limit {
+            return false;
+        }
+        self.data.extend_from_slice(data);
+        true
+    }
+
+    fn store(self, trailers: &http::HeaderMap) {
+        if trailers.get(GRPC_STATUS_HEADER_CODE).is_none_or(|code| code != "0") {
+            return;
+        }
+        let now = self.clock.now();
+        let entry = Entry {
+            headers: self.headers,
+            data: self.data.freeze(),
+            trailers: trailers.clone(),
+            expires: now + self.ttl,
+        };
+        lock(&self.store).insert(self.key, entry, now);
+    }
+}
+
+pin_project_lite::pin_project! {
+    ///Future of `UnaryCacheService`
+    pub struct UnaryCacheFut<F, B> {
+        hit: Option<http::Response<CachedBody<B>>>,
+        fill: Option<Fill>,
+        #[pin]
+        inner: Option<F>,
+    }
+}
+
+impl<F, B> core::fmt::Debug for UnaryCacheFut<F, B> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("UnaryCacheFut").field("is_hit", &self.inner.is_none()).finish_non_exhaustive()
+    }
+}
+
+impl<B, E, F: Future<Output = Result<http::Response<B>, E>>> Future for UnaryCacheFut<F, B> {
+    type Output = Result<http::Response<CachedBody<B>>, E>;
+
+    fn poll(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let this = self.project();
+        let inner = match this.inner.as_pin_mut() {
+            Some(inner) => inner,
+            None => return task::Poll::Ready(Ok(this.hit.take().expect("Future polled after completion"))),
+        };
+
+        let response = match Future::poll(inner, ctx) {
+            task::Poll::Ready(Ok(response)) => response,
+            task::Poll::Ready(Err(error)) => return task::Poll::Ready(Err(error)),
+            task::Poll::Pending => return task::Poll::Pending,
+        };
+
+        //Trailers-only response is never successful unary response
+        let mut fill = this.fill.take().filter(|_| !response.headers().contains_key(GRPC_STATUS_HEADER_CODE));
+        if let Some(fill) = fill.as_mut() {
+            fill.headers = response.headers().clone();
+        }
+        task::Poll::Ready(Ok(response.map(|body| CachedBody::inner(body, fill))))
+    }
+}
+
+pin_project_lite::pin_project! {
+    #[project = CachedBodyProj]
+    enum Body<B> {
+        Inner {
+            #[pin]
+            body: compat::Incoming<B>,
+            fill: Option<Fill>,
+        },
+        Cached {
+            data: Option<Bytes>,
+            trailers: Option<http::HeaderMap>,
+        },
+    }
+}
+
+pin_project_lite::pin_project! {
+    ///Response body of `UnaryCacheService`, which is either inner service's body or cached one
+    pub struct CachedBody<B> {
+        #[pin]
+        inner: Body<B>,
+    }
+}
+
+impl<B> CachedBody<B> {
+    #[inline(always)]
+    fn inner(body: B, fill: Option<Fill>) -> Self {
+        Self {
+            inner: Body::Inner {
+                body: compat::Incoming::new(body),
+                fill,
+            },
+        }
+    }
+
+    #[inline(always)]
+    fn cached(data: Bytes, trailers: http::HeaderMap) -> Self {
+        Self {
+            inner: Body::Cached {
+                data: Some(data).filter(|data| !data.is_empty()),
+                trailers: Some(trailers),
+            },
+        }
+    }
+
+    #[inline(always)]
+    ///Returns whether body is replayed from cache
+    pub fn is_cached(&self) -> bool {
+        matches!(self.inner, Body::Cached { .. })
+    }
+}
+
+impl<B: Default> Default for CachedBody<B> {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::inner(B::default(), None)
+    }
+}
+
+impl<B> core::fmt::Debug for CachedBody<B> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("CachedBody").field("is_cached", &self.is_cached()).finish_non_exhaustive()
+    }
+}
+
+compat::impl_body! {
+    [B: http_body::Body<Data = Bytes>] CachedBody<B> {
+        type Data = Bytes;
+        type Error = B::Error;
+
+        fn poll_data(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Option<Result<Self::Data, Self::Error>>> {
+            match self.project().inner.project() {
+                CachedBodyProj::Inner { body, fill } => {
+                    let result = compat::Body::poll_data(body, ctx);
+                    match &result {
+                        task::Poll::Ready(Some(Ok(data))) => if fill.as_mut().is_some_and(|fill| !fill.push(data)) {
+                            *fill = None;
+                        },
+                        task::Poll::Ready(Some(Err(_))) => *fill = None,
+                        _ => (),
+                    }
+                    result
+                },
+                CachedBodyProj::Cached { data, .. } => task::Poll::Ready(data.take().map(Ok)),
+            }
+        }
+
+        fn poll_trailers(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Result<Option<http::HeaderMap>, Self::Error>> {
+            match self.project().inner.project() {
+                CachedBodyProj::Inner { body, fill } => {
+                    let result = compat::Body::poll_trailers(body, ctx);
+                    if let task::Poll::Ready(result) = &result {
+                        if let (Some(fill), Ok(Some(trailers))) = (fill.take(), result) {
+                            fill.store(trailers);
+                        }
+                        *fill = None;
+                    }
+                    result
+                },
+                CachedBodyProj::Cached { trailers, .. } => task::Poll::Ready(Ok(trailers.take())),
+            }
+        }
+
+        #[inline]
+        fn is_end_stream(&self) -> bool {
+            match &self.inner {
+                Body::Inner { body, .. } => body.is_end_stream(),
+                Body::Cached { data, trailers } => data.is_none() && trailers.is_none(),
+            }
+        }
+
+        #[inline]
+        fn size_hint(&self) -> http_body::SizeHint {
+            match &self.inner {
+                Body::Inner { body, .. } => body.size_hint(),
+                Body::Cached { data, .. } => http_body::SizeHint::with_exact(data.as_ref().map_or(0, |data| data.len() as u64)),
+            }
+        }
+    }
+}
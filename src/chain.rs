@@ -0,0 +1,164 @@
+//! Runtime composition of interceptors
+//!
+//!Unlike `#[derive(Interceptor)]`, chain is assembled at runtime, hence its interceptors are type erased.
+
+use crate::{Interceptor, BoxedInterceptor, LazyMetadata};
+use crate::control::{ChainControl, Toggle};
+use crate::profile::{ProfileSink, Profiled};
+
+use std::borrow::Cow;
+use std::sync::Arc;
+
+type BoxedSink = Arc<dyn ProfileSink + Send + Sync>;
+
+#[derive(Clone, Default)]
+///Sequence of interceptors
+///
+///- Requests are passed to interceptors in order of insertion, stopping at first rejection;
+///- Responses are passed to interceptors in reverse order.
+pub struct InterceptorChain {
+    interceptors: Vec<BoxedInterceptor>,
+    profile: Option<BoxedSink>,
+    control: ChainControl,
+}
+
+impl InterceptorChain {
+    #[inline(always)]
+    ///Creates empty chain, which accepts every request
+    pub const fn new() -> Self {
+        Self {
+            interceptors: Vec::new(),
+            profile: None,
+            control: ChainControl::new(),
+        }
+    }
+
+    ///Enables profiling of every interceptor in chain, reporting to `sink`
+    ///
+    ///Interceptors are wrapped into `Profiled` on insertion, hence chain without profiling has no overhead.
+    pub fn profile<S: ProfileSink + Send + Sync + 'static>(mut self, sink: S) -> Self {
+        let sink: BoxedSink = Arc::new(sink);
+        self.interceptors = self.interceptors.drain(..).map(|interceptor| profiled(interceptor, &sink)).collect();
+        self.profile = Some(sink);
+        self
+    }
+
+    #[inline]
+    ///Appends interceptor to the end of chain
+    pub fn push<I: Interceptor + Send + Sync + 'static>(&mut self, interceptor: I) {
+        let interceptor = match self.profile.as_ref() {
+            Some(sink) => Arc::new(Profiled::new(interceptor, sink.clone())) as BoxedInterceptor,
+            None => Arc::new(interceptor),
+        };
+        self.interceptors.push(interceptor);
+    }
+
+    #[inline]
+    ///Appends interceptor to the end of chain
+    pub fn with<I: Interceptor + Send + Sync + 'static>(mut self, interceptor: I) -> Self {
+        self.push(interceptor);
+        self
+    }
+
+    ///Appends interceptor to the end of chain, wrapped into `Toggle` addressed by `name`
+    pub fn push_toggle<I: Interceptor + Send + Sync + 'static>(&mut self, name: impl Into<Cow<'static, str>>, interceptor: I) {
+        let toggle = Toggle::new(interceptor);
+        self.control.register(name.into(), toggle.handle());
+        self.push(toggle);
+    }
+
+    #[inline]
+    ///Appends interceptor to the end of chain, wrapped into `Toggle` addressed by `name`
+    pub fn with_toggle<I: Interceptor + Send + Sync + 'static>(mut self, name: impl Into<Cow<'static, str>>, interceptor: I) -> Self {
+        self.push_toggle(name, interceptor);
+        self
+    }
+
+    #[inline]
+    ///Returns control of toggles inserted so far
+    pub fn control(&self) -> ChainControl {
+        self.control.clone()
+    }
+
+    #[inline(always)]
+    ///Returns number of interceptors
+    pub fn len(&self) -> usize {
+        self.interceptors.len()
+    }
+
+    #[inline(always)]
+    ///Returns whether chain has no interceptors
+    pub fn is_empty(&self) -> bool {
+        self.interceptors.is_empty()
+    }
+}
+
+impl core::fmt::Debug for InterceptorChain {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("InterceptorChain").field("len", &self.interceptors.len()).field("profiled", &self.profile.is_some()).finish()
+    }
+}
+
+#[inline(always)]
+fn profiled(interceptor: BoxedInterceptor, sink: &BoxedSink) -> BoxedInterceptor {
+    Arc::new(Profiled::new(interceptor, sink.clone()))
+}
+
+impl Extend<BoxedInterceptor> for InterceptorChain {
+    #[inline(always)]
+    fn extend<T: IntoIterator<Item = BoxedInterceptor>>(&mut self, iter: T) {
+        match self.profile.as_ref() {
+            Some(sink) => self.interceptors.extend(iter.into_iter().map(|interceptor| profiled(interceptor, sink))),
+            None => self.interceptors.extend(iter),
+        }
+    }
+}
+
+impl core::iter::FromIterator<BoxedInterceptor> for InterceptorChain {
+    #[inline(always)]
+    fn from_iter<T: IntoIterator<Item = BoxedInterceptor>>(iter: T) -> Self {
+        Self {
+            interceptors: iter.into_iter().collect(),
+            profile: None,
+            control: ChainControl::new(),
+        }
+    }
+}
+
+impl Interceptor for InterceptorChain {
+    #[inline]
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        self.interceptors.iter().find_map(|interceptor| interceptor.on_request(headers, extensions))
+    }
+
+    #[inline]
+    fn on_request_with_uri(&self, uri: &http::Uri, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        self.interceptors.iter().find_map(|interceptor| interceptor.on_request_with_uri(uri, headers, extensions))
+    }
+
+    #[inline]
+    fn on_request_lazy(&self, uri: &http::Uri, headers: &mut LazyMetadata<'_>, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        self.interceptors.iter().find_map(|interceptor| interceptor.on_request_lazy(uri, headers, extensions))
+    }
+
+    #[inline]
+    fn on_request_headers(&self, uri: &http::Uri, headers: &mut http::HeaderMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        self.interceptors.iter().find_map(|interceptor| interceptor.on_request_headers(uri, headers, extensions))
+    }
+
+    #[inline]
+    fn on_response(&self, status: tonic::Code, headers: &mut http::HeaderMap, extensions: &http::Extensions) {
+        for interceptor in self.interceptors.iter().rev() {
+            interceptor.on_response(status, headers, extensions);
+        }
+    }
+
+    #[cfg(feature = "diagnostics")]
+    #[inline]
+    fn on_poll_stats(&self, stats: crate::diagnostics::PollStats) {
+        for interceptor in self.interceptors.iter() {
+            interceptor.on_poll_stats(stats);
+        }
+    }
+}
@@ -0,0 +1,470 @@
+//! Client side interceptor
+//!
+//!Applies to outgoing calls, typically by wrapping `tonic::transport::Channel` using `tower::ServiceBuilder`:
+//!
+//!```rust,ignore
+//!let channel = tower::ServiceBuilder::new().layer(tonic_interceptor::client::interceptor(MyInterceptor)).service(channel);
+//!let client = MyServiceClient::new(channel);
+//!```
+//!
+//!## Single threaded environments
+//!
+//!`ClientInterceptor`, `ClientInterceptorService` and `RoutingHint` impose no `Send` bounds and do not depend on runtime or system clock,
+//!hence they are usable over transports that are not `Send` (e.g. `tonic-web-wasm-client` on `wasm32-unknown-unknown`).
+//!`BearerAuth` requires `Send` transport, use `LocalBearerAuth` instead.
+//!
+//!Time is not available there without `wasm` feature, which makes `timer::Instant` follow JS clock and provides `timer::WasmTimer`:
+//!
+//!- `Retry` requires either `tokio` or `wasm` feature, sleeping with `timer::DefaultTimer`, though it still requires `Send` transport;
+//!- `PropagateDeadline` and `CachedToken` require `tokio` feature, of which they only use task-local and synchronization, both available there.
+
+use crate::compat;
+
+use core::task;
+use core::pin::Pin;
+use core::future::Future;
+
+const GRPC_STATUS_HEADER_CODE: &str = "grpc-status";
+
+mod token;
+mod routing;
+pub use routing::RoutingHint;
+pub use token::{TokenSource, BearerAuth, BearerAuthService, LocalTokenSource, LocalBearerAuth, LocalBearerAuthService};
+#[cfg(feature = "tokio")]
+pub use token::{Token, TokenRefresh, CachedToken};
+#[cfg(any(feature = "tokio", feature = "wasm"))]
+mod retry;
+#[cfg(any(feature = "tokio", feature = "wasm"))]
+pub use retry::{Retry, RetryService};
+#[cfg(feature = "tokio")]
+mod deadline;
+#[cfg(feature = "tokio")]
+pub use deadline::PropagateDeadline;
+#[cfg(feature = "opentelemetry")]
+mod otel;
+#[cfg(feature = "opentelemetry")]
+pub use otel::InjectContext;
+#[cfg(feature = "hmac")]
+mod hmac;
+#[cfg(feature = "hmac")]
+pub use self::hmac::HmacSign;
+
+///Error type of client service.
+///
+///It is the same type as `tonic::codegen::StdError` and rejection's `Status` is returned within so
+///that generated client code will return it as it is.
+pub type BoxError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+///Client interceptor
+///
+///Conversion of request and response headers into `MetadataMap` and back is lossless, same as for server's `Interceptor`.
+pub trait ClientInterceptor {
+    ///Callback on outgoing request, allowing you to modify headers or extensions
+    ///
+    ///Returning status will abort request and status is returned as error of the call.
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status>;
+
+    #[inline(always)]
+    ///Callback on outgoing request, with access to its URI
+    ///
+    ///This is what service calls, by default forwarding to `on_request`.
+    ///Override it when interceptor depends on method path.
+    fn on_request_with_uri(&self, _uri: &http::Uri, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        self.on_request(headers, extensions)
+    }
+
+    ///Callback when response headers are received
+    fn on_response(&self, headers: &tonic::metadata::MetadataMap, extensions: &http::Extensions);
+
+    #[inline(always)]
+    ///Callback when response trailers are received
+    ///
+    ///Not called for trailers-only responses, in which case status is part of headers passed to `on_response`
+    fn on_trailers(&self, _trailers: &tonic::metadata::MetadataMap) {
+    }
+
+    #[inline(always)]
+    ///Callback when call completes with effective status code.
+    ///
+    ///It is called exactly once per call, including calls rejected by `on_request` or failed by transport.
+    ///If response is dropped before its end, code is `Cancelled`, unless status has been already received.
+    fn on_complete(&self, _code: tonic::Code) {
+    }
+}
+
+impl<I: ClientInterceptor> ClientInterceptor for std::sync::Arc<I> {
+    #[inline(always)]
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        ClientInterceptor::on_request(self.as_ref(), headers, extensions)
+    }
+
+    #[inline(always)]
+    fn on_request_with_uri(&self, uri: &http::Uri, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        ClientInterceptor::on_request_with_uri(self.as_ref(), uri, headers, extensions)
+    }
+
+    #[inline(always)]
+    fn on_response(&self, headers: &tonic::metadata::MetadataMap, extensions: &http::Extensions) {
+        ClientInterceptor::on_response(self.as_ref(), headers, extensions)
+    }
+
+    #[inline(always)]
+    fn on_trailers(&self, trailers: &tonic::metadata::MetadataMap) {
+        ClientInterceptor::on_trailers(self.as_ref(), trailers)
+    }
+
+    #[inline(always)]
+    fn on_complete(&self, code: tonic::Code) {
+        ClientInterceptor::on_complete(self.as_ref(), code)
+    }
+}
+
+///Client layer
+#[derive(Clone, Copy)]
+#[repr(transparent)]
+pub struct ClientInterceptorLayer<I>(I);
+
+impl<I> ClientInterceptorLayer<I> {
+    #[inline(always)]
+    ///Creates new instance
+    pub const fn new(interceptor: I) -> Self {
+        Self(interceptor)
+    }
+
+    #[inline(always)]
+    ///Access interceptor
+    pub fn get_ref(&self) -> &I {
+        &self.0
+    }
+
+    #[inline(always)]
+    ///Access interceptor mutably
+    pub fn get_mut(&mut self) -> &mut I {
+        &mut self.0
+    }
+
+    #[inline(always)]
+    ///Returns interceptor
+    pub fn into_inner(self) -> I {
+        self.0
+    }
+}
+
+impl<I: core::fmt::Debug> core::fmt::Debug for ClientInterceptorLayer<I> {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_tuple("ClientInterceptorLayer").field(&self.0).finish()
+    }
+}
+
+impl<S, I: ClientInterceptor + Clone> tower_layer::Layer<S> for ClientInterceptorLayer<I> {
+    type Service = ClientInterceptorService<I, S>;
+
+    #[inline(always)]
+    fn layer(&self, inner: S) -> Self::Service {
+        ClientInterceptorService::new(self.0.clone(), inner)
+    }
+}
+
+///Client service
+#[derive(Clone)]
+pub struct ClientInterceptorService<I, S> {
+    interceptor: I,
+    inner: S
+}
+
+impl<I: core::fmt::Debug, S: core::fmt::Debug> core::fmt::Debug for ClientInterceptorService<I, S> {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("ClientInterceptorService").field("interceptor", &self.interceptor).field("inner", &self.inner).finish()
+    }
+}
+
+impl<I, S> ClientInterceptorService<I, S> {
+    #[inline(always)]
+    ///Creates new instance
+    pub fn new(interceptor: I, inner: S) -> Self {
+        Self {
+            interceptor,
+            inner
+        }
+    }
+
+    #[inline(always)]
+    ///Access inner service
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    #[inline(always)]
+    ///Access inner service mutably
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    #[inline(always)]
+    ///Returns inner service
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    #[inline(always)]
+    ///Access interceptor
+    pub fn interceptor_ref(&self) -> &I {
+        &self.interceptor
+    }
+
+    #[inline(always)]
+    ///Access interceptor mutably
+    pub fn interceptor_mut(&mut self) -> &mut I {
+        &mut self.interceptor
+    }
+}
+
+impl<ReqBody, ResBody, S: tower_service::Service<http::Request<ReqBody>, Response = http::Response<ResBody>>, I: ClientInterceptor + Clone> tower_service::Service<http::Request<ReqBody>> for ClientInterceptorService<I, S> where S::Error: Into<BoxError> {
+    type Response = http::Response<ClientResponseBody<I, ResBody>>;
+    type Error = BoxError;
+    type Future = ClientInterceptorFut<I, S::Future>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    #[inline(always)]
+    fn call(&mut self, mut req: http::Request<ReqBody>) -> Self::Future {
+        let (mut parts, body) = req.into_parts();
+
+        let mut headers = crate::compat::metadata_from_headers(parts.headers);
+        match self.interceptor.on_request_with_uri(&parts.uri, &mut headers, &mut parts.extensions) {
+            None => {
+                parts.headers = crate::compat::metadata_into_headers(headers);
+                req = http::Request::from_parts(parts, body);
+                ClientInterceptorFut::fut(self.interceptor.clone(), self.inner.call(req))
+            }
+            Some(status) => ClientInterceptorFut::status(self.interceptor.clone(), status),
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    #[project = InnerProj]
+    enum Inner<F> {
+        Fut {
+            #[pin]
+            fut: F,
+        },
+        Status {
+            status: Option<tonic::Status>,
+        },
+    }
+}
+
+pin_project_lite::pin_project! {
+    ///Client interception future
+    ///
+    ///If dropped before response is received, call completes with `Cancelled`.
+    pub struct ClientInterceptorFut<I: ClientInterceptor, F> {
+        interceptor: Option<I>,
+        #[pin]
+        inner: Inner<F>,
+    }
+
+    impl<I: ClientInterceptor, F> PinnedDrop for ClientInterceptorFut<I, F> {
+        fn drop(this: Pin<&mut Self>) {
+            let this = this.project();
+            if let Some(interceptor) = this.interceptor.take() {
+                let code = match this.inner.project() {
+                    InnerProj::Status { status: Some(status) } => status.code(),
+                    _ => tonic::Code::Cancelled,
+                };
+                interceptor.on_complete(code);
+            }
+        }
+    }
+}
+
+impl<I: ClientInterceptor, F> core::fmt::Debug for ClientInterceptorFut<I, F> {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let state = match self.inner {
+            Inner::Fut { .. } => "Fut",
+            Inner::Status { .. } => "Status",
+        };
+        fmt.debug_struct("ClientInterceptorFut").field("state", &state).finish_non_exhaustive()
+    }
+}
+
+impl<I: ClientInterceptor, F> ClientInterceptorFut<I, F> {
+    #[inline(always)]
+    fn status(interceptor: I, status: tonic::Status) -> Self {
+        Self {
+            interceptor: Some(interceptor),
+            inner: Inner::Status {
+                status: Some(status),
+            },
+        }
+    }
+
+    #[inline(always)]
+    fn fut(interceptor: I, fut: F) -> Self {
+        Self {
+            interceptor: Some(interceptor),
+            inner: Inner::Fut {
+                fut,
+            },
+        }
+    }
+}
+
+impl<ResBody, E: Into<BoxError>, I: ClientInterceptor, F: Future<Output = Result<http::Response<ResBody>, E>>> Future for ClientInterceptorFut<I, F> {
+    type Output = Result<http::Response<ClientResponseBody<I, ResBody>>, BoxError>;
+
+    fn poll(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let this = self.project();
+        let intercepter = this.interceptor;
+        let fut = match this.inner.project() {
+            InnerProj::Fut { fut } => fut,
+            InnerProj::Status { status } => {
+                let status = status.take().expect("Future polled after completion");
+                if let Some(interceptor) = intercepter.take() {
+                    interceptor.on_complete(status.code());
+                }
+                return task::Poll::Ready(Err(Box::new(status)));
+            }
+        };
+        match Future::poll(fut, ctx) {
+            task::Poll::Ready(Result::Ok(resp)) => {
+                let interceptor = intercepter.take().expect("Future polled after completion");
+                let (mut parts, body) = resp.into_parts();
+
+                let headers = crate::compat::metadata_from_headers(parts.headers);
+                interceptor.on_response(&headers, &parts.extensions);
+                parts.headers = crate::compat::metadata_into_headers(headers);
+
+                let status = parts.headers.get(GRPC_STATUS_HEADER_CODE).map(|header| tonic::Code::from_bytes(header.as_bytes()));
+                task::Poll::Ready(Ok(http::Response::from_parts(parts, ClientResponseBody::new(interceptor, body, status))))
+            },
+            task::Poll::Ready(Result::Err(error)) => {
+                let status = tonic::Status::from_error(error.into());
+                if let Some(interceptor) = intercepter.take() {
+                    interceptor.on_complete(status.code());
+                }
+                task::Poll::Ready(Err(Box::new(status)))
+            },
+            task::Poll::Pending => task::Poll::Pending,
+        }
+    }
+}
+
+struct Completion<I: ClientInterceptor> {
+    interceptor: I,
+    //Status known from response headers (i.e. trailers-only response)
+    status: Option<tonic::Code>,
+    is_complete: bool,
+}
+
+impl<I: ClientInterceptor> Completion<I> {
+    #[inline(always)]
+    fn complete(&mut self, code: tonic::Code) {
+        if !self.is_complete {
+            self.is_complete = true;
+            self.interceptor.on_complete(code);
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    ///Response body wrapper, notifying interceptor of trailers and call completion
+    pub struct ClientResponseBody<I: ClientInterceptor, B> {
+        completion: Completion<I>,
+        #[pin]
+        inner: compat::Incoming<B>,
+    }
+
+    impl<I: ClientInterceptor, B> PinnedDrop for ClientResponseBody<I, B> {
+        fn drop(this: Pin<&mut Self>) {
+            let completion = this.project().completion;
+            completion.complete(completion.status.unwrap_or(tonic::Code::Cancelled));
+        }
+    }
+}
+
+impl<I: ClientInterceptor, B> core::fmt::Debug for ClientResponseBody<I, B> {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("ClientResponseBody").field("status", &self.completion.status).field("is_complete", &self.completion.is_complete).finish_non_exhaustive()
+    }
+}
+
+impl<I: ClientInterceptor, B> ClientResponseBody<I, B> {
+    #[inline(always)]
+    fn new(interceptor: I, inner: B, status: Option<tonic::Code>) -> Self {
+        Self {
+            completion: Completion {
+                interceptor,
+                status,
+                is_complete: false,
+            },
+            inner: compat::Incoming::new(inner),
+        }
+    }
+}
+
+compat::impl_body! {
+    [I: ClientInterceptor, B: http_body::Body] ClientResponseBody<I, B> {
+        type Data = B::Data;
+        type Error = B::Error;
+
+        fn poll_data(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Option<Result<Self::Data, Self::Error>>> {
+            let this = self.project();
+
+            let result = compat::Body::poll_data(this.inner, ctx);
+            if let task::Poll::Ready(Some(Err(_))) = result {
+                this.completion.complete(tonic::Code::Unknown);
+            }
+            result
+        }
+
+        fn poll_trailers(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Result<Option<http::HeaderMap>, Self::Error>> {
+            let this = self.project();
+            let completion = this.completion;
+
+            match compat::Body::poll_trailers(this.inner, ctx) {
+                task::Poll::Ready(Ok(Some(trailers))) => {
+                    let trailers = crate::compat::metadata_from_headers(trailers);
+                    completion.interceptor.on_trailers(&trailers);
+                    let trailers = crate::compat::metadata_into_headers(trailers);
+
+                    let code = match trailers.get(GRPC_STATUS_HEADER_CODE) {
+                        Some(code) => tonic::Code::from_bytes(code.as_bytes()),
+                        None => completion.status.unwrap_or(tonic::Code::Unknown),
+                    };
+                    completion.complete(code);
+                    task::Poll::Ready(Ok(Some(trailers)))
+                },
+                task::Poll::Ready(Ok(None)) => {
+                    completion.complete(completion.status.unwrap_or(tonic::Code::Unknown));
+                    task::Poll::Ready(Ok(None))
+                },
+                task::Poll::Ready(Err(error)) => {
+                    completion.complete(tonic::Code::Unknown);
+                    task::Poll::Ready(Err(error))
+                },
+                task::Poll::Pending => task::Poll::Pending,
+            }
+        }
+
+        #[inline(always)]
+        fn is_end_stream(&self) -> bool {
+            self.inner.is_end_stream()
+        }
+
+        #[inline(always)]
+        fn size_hint(&self) -> http_body::SizeHint {
+            self.inner.size_hint()
+        }
+    }
+}
+
+#[inline(always)]
+///Creates client interceptor layer
+pub fn interceptor<I: ClientInterceptor>(interceptor: I) -> ClientInterceptorLayer<I> {
+    ClientInterceptorLayer::new(interceptor)
+}
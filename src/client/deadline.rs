@@ -0,0 +1,78 @@
+use super::ClientInterceptor;
+use crate::timeout;
+use crate::deadline::{Deadline, DeadlineContext};
+use crate::timer::{Clock, StdClock};
+
+use core::time::Duration;
+use core::convert::TryFrom;
+
+#[derive(Copy, Clone, Default, Debug)]
+///Client interceptor which propagates deadline of current call to outgoing `grpc-timeout`
+///
+///Deadline is taken from outgoing request's `Deadline` extension, otherwise from `DeadlineContext`.
+///Remaining time, minus safety margin, is set as `grpc-timeout`, unless request already specifies shorter timeout.
+///
+///If deadline has already expired, request is rejected with `DEADLINE_EXCEEDED`
+///
+///Remaining time is measured by `Clock`, which is `StdClock` by default.
+pub struct PropagateDeadline<C = StdClock> {
+    margin: Duration,
+    clock: C,
+}
+
+impl PropagateDeadline {
+    #[inline(always)]
+    ///Creates new instance without safety margin
+    pub const fn new() -> Self {
+        Self::with_margin(Duration::ZERO)
+    }
+
+    #[inline(always)]
+    ///Creates new instance, which subtracts `margin` from remaining time.
+    pub const fn with_margin(margin: Duration) -> Self {
+        Self {
+            margin,
+            clock: StdClock,
+        }
+    }
+}
+
+impl<C> PropagateDeadline<C> {
+    #[inline(always)]
+    ///Sets clock, which measures remaining time
+    pub fn clock<C2: Clock>(self, clock: C2) -> PropagateDeadline<C2> {
+        PropagateDeadline {
+            margin: self.margin,
+            clock,
+        }
+    }
+}
+
+impl<C: Clock> ClientInterceptor for PropagateDeadline<C> {
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        let deadline = match extensions.get::<Deadline>().copied().or_else(DeadlineContext::current) {
+            Some(deadline) => deadline,
+            None => return None,
+        };
+
+        let remaining = deadline.remaining_with(&self.clock).saturating_sub(self.margin);
+        if remaining.is_zero() {
+            return Some(tonic::Status::deadline_exceeded("Deadline exceeded before call"));
+        }
+
+        if let Some(timeout) = headers.get(timeout::GRPC_TIMEOUT).and_then(|timeout| timeout::parse(timeout.as_bytes())) {
+            if timeout <= remaining {
+                return None;
+            }
+        }
+
+        let timeout = timeout::encode(remaining);
+        let timeout = tonic::metadata::MetadataValue::try_from(timeout.as_bytes()).expect("valid grpc-timeout");
+        headers.insert(timeout::GRPC_TIMEOUT, timeout);
+        None
+    }
+
+    #[inline(always)]
+    fn on_response(&self, _: &tonic::metadata::MetadataMap, _: &http::Extensions) {
+    }
+}
@@ -0,0 +1,71 @@
+use super::ClientInterceptor;
+use crate::auth::{Clock, SystemClock, canonical_string, sign, KEY_ID, SIGNATURE_TIMESTAMP, SIGNATURE};
+
+use core::convert::TryFrom;
+
+#[derive(Clone, Debug)]
+///Client interceptor which signs outgoing requests with HMAC-SHA256
+///
+///Sets `x-key-id`, `x-signature-timestamp` and `x-signature`, as expected by `auth::HmacSignature`.
+pub struct HmacSign<C = SystemClock> {
+    key_id: tonic::metadata::AsciiMetadataValue,
+    secret: Vec<u8>,
+    headers: Vec<String>,
+    clock: C,
+}
+
+impl HmacSign {
+    #[inline]
+    ///Creates new instance
+    pub fn new(key_id: tonic::metadata::AsciiMetadataValue, secret: impl Into<Vec<u8>>) -> Self {
+        Self {
+            key_id,
+            secret: secret.into(),
+            headers: Vec::new(),
+            clock: SystemClock,
+        }
+    }
+}
+
+impl<C: Clock> HmacSign<C> {
+    #[inline]
+    ///Adds header to be covered by signature
+    ///
+    ///Name must be lower case and match server configuration.
+    pub fn header(mut self, name: impl Into<String>) -> Self {
+        self.headers.push(name.into());
+        self
+    }
+
+    #[inline]
+    ///Sets clock to use
+    pub fn clock<C2: Clock>(self, clock: C2) -> HmacSign<C2> {
+        HmacSign {
+            key_id: self.key_id,
+            secret: self.secret,
+            headers: self.headers,
+            clock,
+        }
+    }
+}
+
+impl<C: Clock> ClientInterceptor for HmacSign<C> {
+    #[inline]
+    fn on_request(&self, _: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+        None
+    }
+
+    fn on_request_with_uri(&self, uri: &http::Uri, headers: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+        let timestamp = self.clock.now().to_string();
+        let signature = sign(&self.secret, &canonical_string(uri.path(), &timestamp, &self.headers, headers));
+
+        headers.insert(KEY_ID, self.key_id.clone());
+        headers.insert(SIGNATURE_TIMESTAMP, tonic::metadata::AsciiMetadataValue::try_from(timestamp).expect("valid timestamp"));
+        headers.insert(SIGNATURE, tonic::metadata::AsciiMetadataValue::try_from(signature).expect("valid signature"));
+        None
+    }
+
+    #[inline(always)]
+    fn on_response(&self, _: &tonic::metadata::MetadataMap, _: &http::Extensions) {
+    }
+}
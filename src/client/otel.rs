@@ -0,0 +1,82 @@
+use super::ClientInterceptor;
+use crate::otel::{Propagator, GRPC_TRACE_BIN, encode_trace_bin};
+
+use opentelemetry::Context;
+use opentelemetry::propagation::TextMapPropagator;
+use opentelemetry::trace::TraceContextExt;
+
+use core::convert::TryFrom;
+
+struct MetadataInjector<'a>(&'a mut tonic::metadata::MetadataMap);
+
+impl opentelemetry::propagation::Injector for MetadataInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        let key = match tonic::metadata::AsciiMetadataKey::from_bytes(key.as_bytes()) {
+            Ok(key) => key,
+            Err(_) => return,
+        };
+        //Explicitly set by caller
+        if self.0.contains_key(&key) {
+            return;
+        }
+
+        if let Ok(value) = tonic::metadata::AsciiMetadataValue::try_from(value) {
+            self.0.insert(key, value);
+        }
+    }
+}
+
+#[derive(Clone, Default, Debug)]
+///Client interceptor which injects OpenTelemetry context into outgoing request
+///
+///Context is taken from outgoing request's `opentelemetry::Context` extension, otherwise current context is used.
+///When using `tracing`, span's context has to be supplied explicitly via extension.
+///
+///Headers are produced by configured propagator, followed by `grpc-trace-bin`.
+///Headers already present in request are not overwritten, and nothing is done when there is no active span.
+pub struct InjectContext {
+    propagator: Propagator,
+}
+
+impl InjectContext {
+    #[inline(always)]
+    ///Creates new instance using global propagator
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    ///Creates new instance using specified propagator
+    pub fn with_propagator<P: TextMapPropagator + Send + Sync + 'static>(propagator: P) -> Self {
+        Self {
+            propagator: Propagator::new(propagator),
+        }
+    }
+
+    fn inject(&self, context: &Context, headers: &mut tonic::metadata::MetadataMap) {
+        let span = context.span();
+        let span = span.span_context();
+        if !span.is_valid() {
+            return;
+        }
+
+        self.propagator.with(|propagator| propagator.inject_context(context, &mut MetadataInjector(headers)));
+        if !headers.contains_key(GRPC_TRACE_BIN) {
+            headers.insert_bin(GRPC_TRACE_BIN, tonic::metadata::BinaryMetadataValue::from_bytes(&encode_trace_bin(span)));
+        }
+    }
+}
+
+impl ClientInterceptor for InjectContext {
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        match extensions.get::<Context>() {
+            Some(context) => self.inject(context, headers),
+            None => Context::map_current(|context| self.inject(context, headers)),
+        }
+        None
+    }
+
+    #[inline(always)]
+    fn on_response(&self, _: &tonic::metadata::MetadataMap, _: &http::Extensions) {
+    }
+}
@@ -0,0 +1,469 @@
+//! Automatic retries of failed calls
+
+use super::BoxError;
+use crate::{proto, timeout};
+use crate::policy::GRPC_PREVIOUS_RPC_ATTEMPTS;
+use crate::timer::{DefaultTimer, Timer};
+use crate::compat;
+
+use core::{cmp, mem, task};
+use core::pin::Pin;
+use core::future::Future;
+use core::time::Duration;
+use std::sync::{Arc, Mutex};
+
+use bytes::{Buf, Bytes};
+
+const GRPC_STATUS_HEADER_CODE: &str = "grpc-status";
+
+#[derive(Clone)]
+struct Config {
+    max_attempts: u32,
+    codes: Vec<tonic::Code>,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    multiplier: f64,
+    jitter: f64,
+    buffer_limit: usize,
+    timeout: Option<Duration>,
+    #[cfg(feature = "prost-reflect")]
+    idempotent_only: bool,
+}
+
+///Layer to retry calls failed with one of configured codes.
+///
+///Retries are performed according to gRPC retry design:
+///
+///- Call is retried only if server hasn't committed to response, i.e. failure is transport error or trailers-only response;
+///- Request body is buffered while it is being sent, up to configured limit. Once body exceeds limit, call is no longer retried;
+///- Delay before next attempt is exponential backoff with jitter, unless server sent `google.rpc.RetryInfo` within status details, in which case its delay is used;
+///- Retry is not attempted if delay would exceed call's deadline, which is determined by `grpc-timeout` and configured overall timeout;
+///- Every attempt is sent with `grpc-timeout` of the time left until deadline, and fails with `DEADLINE_EXCEEDED` once deadline is reached;
+///- Every retry carries `grpc-previous-rpc-attempts` metadata with number of preceding attempts.
+///
+///Note that only first attempt receives original request's extensions as `http::Extensions` cannot be cloned.
+///
+///Backoff is slept and deadline is measured by `Timer`, which is `DefaultTimer` unless specified.
+#[derive(Clone)]
+pub struct Retry<T = DefaultTimer> {
+    config: Config,
+    timer: T,
+}
+
+impl Retry {
+    ///Creates new instance with default configuration:
+    ///
+    ///- Maximum 3 attempts;
+    ///- Retries only `UNAVAILABLE`;
+    ///- Backoff from 100ms up to 5s, with multiplier 2 and full jitter;
+    ///- Request buffer limit is 64KiB;
+    ///- No overall timeout, beside the one specified by `grpc-timeout`.
+    pub fn new() -> Self {
+        Self {
+            config: Config {
+                max_attempts: 3,
+                codes: vec![tonic::Code::Unavailable],
+                initial_backoff: Duration::from_millis(100),
+                max_backoff: Duration::from_secs(5),
+                multiplier: 2.0,
+                jitter: 1.0,
+                buffer_limit: 64 * 1024,
+                timeout: None,
+                #[cfg(feature = "prost-reflect")]
+                idempotent_only: false,
+            },
+            timer: DefaultTimer::default(),
+        }
+    }
+}
+
+impl<T> Retry<T> {
+    #[inline]
+    ///Sets timer, which sleeps backoff and measures deadline
+    pub fn timer<T2: Timer>(self, timer: T2) -> Retry<T2> {
+        Retry {
+            config: self.config,
+            timer,
+        }
+    }
+
+    #[inline(always)]
+    ///Sets maximum number of attempts, including the first one.
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.config.max_attempts = cmp::max(max_attempts, 1);
+        self
+    }
+
+    #[inline]
+    ///Adds code to retry on
+    pub fn retry_on(mut self, code: tonic::Code) -> Self {
+        if !self.config.codes.contains(&code) {
+            self.config.codes.push(code);
+        }
+        self
+    }
+
+    #[inline(always)]
+    ///Sets initial and maximum backoff
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.config.initial_backoff = initial;
+        self.config.max_backoff = cmp::max(initial, max);
+        self
+    }
+
+    #[inline(always)]
+    ///Sets backoff multiplier applied after each attempt.
+    pub fn multiplier(mut self, multiplier: f64) -> Self {
+        self.config.multiplier = multiplier.max(1.0);
+        self
+    }
+
+    #[inline(always)]
+    ///Sets jitter as fraction of backoff within `0..=1` range.
+    ///
+    ///Delay is randomly picked from `backoff * (1 - jitter)..=backoff` range, hence `0` disables jitter.
+    pub fn jitter(mut self, jitter: f64) -> Self {
+        self.config.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    #[inline(always)]
+    ///Sets maximum size of request body to buffer for retries.
+    pub fn buffer_limit(mut self, limit: usize) -> Self {
+        self.config.buffer_limit = limit;
+        self
+    }
+
+    #[inline(always)]
+    ///Sets overall timeout of the call, including all attempts.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.config.timeout = Some(timeout);
+        self
+    }
+
+    #[cfg(feature = "prost-reflect")]
+    #[inline(always)]
+    ///Sets whether to retry only calls, which `descriptor::MethodInfo` extension marks as idempotent.
+    ///
+    ///Calls without `MethodInfo` are not retried either.
+    pub fn idempotent_only(mut self, idempotent_only: bool) -> Self {
+        self.config.idempotent_only = idempotent_only;
+        self
+    }
+}
+
+impl Default for Retry {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S, T: Clone> tower_layer::Layer<S> for Retry<T> {
+    type Service = RetryService<S, T>;
+
+    #[inline(always)]
+    fn layer(&self, inner: S) -> Self::Service {
+        RetryService {
+            config: Arc::new(self.config.clone()),
+            timer: self.timer.clone(),
+            inner,
+        }
+    }
+}
+
+///Service retrying calls
+pub struct RetryService<S, T = DefaultTimer> {
+    config: Arc<Config>,
+    timer: T,
+    inner: S,
+}
+
+impl<S: Clone, T: Clone> Clone for RetryService<S, T> {
+    #[inline(always)]
+    fn clone(&self) -> Self {
+        Self {
+            config: self.config.clone(),
+            timer: self.timer.clone(),
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+fn random() -> f64 {
+    use std::hash::{BuildHasher, Hasher};
+
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u64(seed());
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(not(feature = "wasm"))]
+fn seed() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|time| time.as_nanos() as u64).unwrap_or(0)
+}
+
+#[cfg(feature = "wasm")]
+//System time is not available on `wasm32-unknown-unknown`, while `instant` asks JS for time there
+fn seed() -> u64 {
+    (instant::now() * 1_000_000.0) as u64
+}
+
+impl Config {
+    #[inline]
+    fn is_retryable(&self, code: tonic::Code) -> bool {
+        self.codes.contains(&code)
+    }
+
+    fn delay(&self, backoff: Duration) -> Duration {
+        backoff.mul_f64(1.0 - self.jitter * random())
+    }
+
+    #[inline]
+    fn next_backoff(&self, backoff: Duration) -> Duration {
+        cmp::min(backoff.mul_f64(self.multiplier), self.max_backoff)
+    }
+}
+
+fn request<B: http_body::Body + Send + Unpin + 'static>(parts: &http::request::Parts, body: &ReplayBody<B>) -> http::Request<crate::compat::BoxBody> where B::Error: Into<BoxError> {
+    let mut req = http::Request::new(crate::compat::box_body(body.replay()));
+    *req.method_mut() = parts.method.clone();
+    *req.uri_mut() = parts.uri.clone();
+    *req.version_mut() = parts.version;
+    *req.headers_mut() = parts.headers.clone();
+    req
+}
+
+//Awaits attempt, failing it with `DEADLINE_EXCEEDED` once `deadline` is reached
+async fn bounded<T: Timer, R, E: Into<BoxError>, F: Future<Output = Result<R, E>>>(timer: &T, deadline: Option<crate::timer::Instant>, attempt: F) -> Result<R, BoxError> {
+    let deadline = match deadline {
+        Some(deadline) => deadline,
+        None => return attempt.await.map_err(Into::into),
+    };
+
+    match crate::timer::timeout(timer, deadline.saturating_duration_since(timer.now()), attempt).await {
+        Some(result) => result.map_err(Into::into),
+        None => Err(Box::new(tonic::Status::deadline_exceeded("call deadline exceeded"))),
+    }
+}
+
+enum Outcome<R> {
+    Done(Result<R, BoxError>),
+    Retry(R, Option<Duration>),
+    RetryError(BoxError),
+}
+
+fn classify<ResBody>(config: &Config, result: Result<http::Response<ResBody>, BoxError>) -> Outcome<http::Response<ResBody>> {
+    match result {
+        Ok(response) => {
+            //Trailers-only response is the only one that doesn't commit to response
+            let code = match response.headers().get(GRPC_STATUS_HEADER_CODE) {
+                Some(code) => tonic::Code::from_bytes(code.as_bytes()),
+                None => return Outcome::Done(Ok(response)),
+            };
+
+            if config.is_retryable(code) {
+                let delay = tonic::Status::from_header_map(response.headers()).and_then(|status| proto::retry_delay(status.details()));
+                Outcome::Retry(response, delay)
+            } else {
+                Outcome::Done(Ok(response))
+            }
+        },
+        Err(error) => {
+            let status = tonic::Status::from_error(error);
+            if config.is_retryable(status.code()) {
+                Outcome::RetryError(Box::new(status))
+            } else {
+                Outcome::Done(Err(Box::new(status)))
+            }
+        }
+    }
+}
+
+impl<ReqBody, ResBody, S, T> tower_service::Service<http::Request<ReqBody>> for RetryService<S, T> where T: Timer + Clone + Send + Sync + 'static, ReqBody: http_body::Body + Send + Unpin + 'static, ReqBody::Error: Into<BoxError>, S: tower_service::Service<http::Request<crate::compat::BoxBody>, Response = http::Response<ResBody>> + Clone + Send + 'static, S::Future: Send, S::Error: Into<BoxError>, ResBody: Send + 'static {
+    type Response = S::Response;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        let inner = self.inner.clone();
+        let mut inner = mem::replace(&mut self.inner, inner);
+        let config = self.config.clone();
+        let timer = self.timer.clone();
+
+        Box::pin(async move {
+            let start = timer.now();
+            let (mut parts, body) = req.into_parts();
+            let extensions = mem::take(&mut parts.extensions);
+            #[cfg(feature = "prost-reflect")]
+            let max_attempts = match config.idempotent_only && !extensions.get::<crate::descriptor::MethodInfo>().is_some_and(|info| info.idempotent) {
+                true => 1,
+                false => config.max_attempts,
+            };
+            #[cfg(not(feature = "prost-reflect"))]
+            let max_attempts = config.max_attempts;
+            let body = ReplayBody::new(body, config.buffer_limit);
+
+            let timeout = match (timeout::from_headers(&parts.headers), config.timeout) {
+                (Some(left), Some(right)) => Some(cmp::min(left, right)),
+                (left, right) => left.or(right),
+            };
+            let deadline = timeout.map(|timeout| start + timeout);
+
+            let mut first = request(&parts, &body);
+            *first.extensions_mut() = extensions;
+            if let (Some(timeout), Some(_)) = (timeout, config.timeout) {
+                first.headers_mut().insert(timeout::GRPC_TIMEOUT, timeout::encode(timeout));
+            }
+
+            let mut result = bounded(&timer, deadline, inner.call(first)).await;
+            let mut attempt = 1;
+            let mut backoff = config.initial_backoff;
+            loop {
+                let (last, pushback) = match classify(&config, result) {
+                    Outcome::Done(result) => return result,
+                    Outcome::Retry(response, pushback) => (Ok(response), pushback),
+                    Outcome::RetryError(error) => (Err(error), None),
+                };
+
+                if attempt >= max_attempts || !body.is_replayable() {
+                    return last;
+                }
+
+                let delay = match pushback {
+                    Some(pushback) => pushback,
+                    None => config.delay(backoff),
+                };
+                backoff = config.next_backoff(backoff);
+
+                let remaining = match deadline {
+                    Some(deadline) => match deadline.checked_duration_since(timer.now() + delay) {
+                        Some(remaining) if !remaining.is_zero() => Some(remaining),
+                        _ => return last,
+                    },
+                    None => None,
+                };
+
+                timer.sleep(delay).await;
+                attempt += 1;
+
+                if let Err(error) = core::future::poll_fn(|ctx| inner.poll_ready(ctx)).await {
+                    return Err(error.into());
+                }
+                let mut req = request(&parts, &body);
+                req.headers_mut().insert(GRPC_PREVIOUS_RPC_ATTEMPTS, http::HeaderValue::from(attempt - 1));
+                if let Some(remaining) = remaining {
+                    req.headers_mut().insert(timeout::GRPC_TIMEOUT, timeout::encode(remaining));
+                }
+                result = bounded(&timer, deadline, inner.call(req)).await;
+            }
+        })
+    }
+}
+
+struct Replay<B> {
+    source: compat::Incoming<B>,
+    chunks: Vec<Bytes>,
+    //Number of chunks discarded due to overflow
+    discarded: usize,
+    len: usize,
+    limit: usize,
+    is_overflow: bool,
+    is_end: bool,
+}
+
+///Request body, which records its content while it is being sent, so that it can be sent again.
+struct ReplayBody<B> {
+    shared: Arc<Mutex<Replay<B>>>,
+    position: usize,
+}
+
+impl<B> ReplayBody<B> {
+    fn new(source: B, limit: usize) -> Self {
+        Self {
+            shared: Arc::new(Mutex::new(Replay {
+                source: compat::Incoming::new(source),
+                chunks: Vec::new(),
+                discarded: 0,
+                len: 0,
+                limit,
+                is_overflow: false,
+                is_end: false,
+            })),
+            position: 0,
+        }
+    }
+
+    fn replay(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+            position: 0,
+        }
+    }
+
+    fn is_replayable(&self) -> bool {
+        !self.shared.lock().unwrap_or_else(|error| error.into_inner()).is_overflow
+    }
+}
+
+compat::impl_body! {
+    [B: http_body::Body + Unpin] ReplayBody<B> where [B::Error: Into<BoxError>] {
+        type Data = Bytes;
+        type Error = tonic::Status;
+
+        fn poll_data(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Option<Result<Self::Data, Self::Error>>> {
+            let this = self.get_mut();
+            let mut shared = this.shared.lock().unwrap_or_else(|error| error.into_inner());
+
+            if this.position < shared.discarded {
+                //Outdated attempt, its data is no longer available
+                return task::Poll::Ready(None);
+            }
+
+            let idx = this.position - shared.discarded;
+            if let Some(chunk) = shared.chunks.get(idx) {
+                this.position += 1;
+                return task::Poll::Ready(Some(Ok(chunk.clone())));
+            } else if shared.is_end {
+                return task::Poll::Ready(None);
+            }
+
+            match Pin::new(&mut shared.source).poll_data(ctx) {
+                task::Poll::Ready(Some(Ok(mut data))) => {
+                    let chunk = data.copy_to_bytes(data.remaining());
+                    this.position += 1;
+
+                    shared.len += chunk.len();
+                    if shared.is_overflow {
+                        shared.discarded += 1;
+                    } else if shared.len > shared.limit {
+                        shared.is_overflow = true;
+                        shared.discarded += shared.chunks.len() + 1;
+                        shared.chunks = Vec::new();
+                    } else {
+                        shared.chunks.push(chunk.clone());
+                    }
+
+                    task::Poll::Ready(Some(Ok(chunk)))
+                },
+                task::Poll::Ready(Some(Err(error))) => task::Poll::Ready(Some(Err(tonic::Status::from_error(error.into())))),
+                task::Poll::Ready(None) => {
+                    shared.is_end = true;
+                    task::Poll::Ready(None)
+                },
+                task::Poll::Pending => task::Poll::Pending,
+            }
+        }
+
+        #[inline(always)]
+        fn poll_trailers(self: Pin<&mut Self>, _: &mut task::Context<'_>) -> task::Poll<Result<Option<http::HeaderMap>, Self::Error>> {
+            //gRPC requests have no trailers
+            task::Poll::Ready(Ok(None))
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use super::ClientInterceptor;
+use crate::matcher::MethodMatcher;
+
+use std::sync::Arc;
+
+type Extractor = dyn Fn(&http::Uri, &tonic::metadata::MetadataMap) -> Option<tonic::metadata::AsciiMetadataValue> + Send + Sync;
+
+#[derive(Clone)]
+struct Rule {
+    matcher: MethodMatcher,
+    extractor: Arc<Extractor>,
+    required: bool,
+}
+
+#[derive(Clone)]
+///Client interceptor which injects routing hint header, computed from request
+///
+///Extractor is selected by the most specific matching rule, with earlier rule winning among equally specific ones.
+///Methods without matching rule are sent as it is, as well as requests already carrying the header.
+///
+///If extractor of required rule returns `None`, call fails with `INVALID_ARGUMENT`.
+///
+///Method path is required, so calling `on_request` directly does nothing.
+pub struct RoutingHint {
+    header: tonic::metadata::AsciiMetadataKey,
+    rules: Arc<Vec<Rule>>,
+}
+
+impl RoutingHint {
+    #[inline]
+    ///Creates new instance, setting `header`
+    pub fn new(header: &'static str) -> Self {
+        Self {
+            header: tonic::metadata::AsciiMetadataKey::from_static(header),
+            rules: Arc::new(Vec::new()),
+        }
+    }
+
+    fn rule<F: Fn(&http::Uri, &tonic::metadata::MetadataMap) -> Option<tonic::metadata::AsciiMetadataValue> + Send + Sync + 'static>(mut self, matcher: MethodMatcher, extractor: F, required: bool) -> Self {
+        Arc::make_mut(&mut self.rules).push(Rule {
+            matcher,
+            extractor: Arc::new(extractor),
+            required,
+        });
+        self
+    }
+
+    #[inline]
+    ///Adds rule for methods matching `matcher`, which doesn't require hint.
+    pub fn method<F: Fn(&http::Uri, &tonic::metadata::MetadataMap) -> Option<tonic::metadata::AsciiMetadataValue> + Send + Sync + 'static>(self, matcher: impl Into<MethodMatcher>, extractor: F) -> Self {
+        self.rule(matcher.into(), extractor, false)
+    }
+
+    #[inline]
+    ///Adds rule for methods matching `matcher`, which requires hint.
+    pub fn require<F: Fn(&http::Uri, &tonic::metadata::MetadataMap) -> Option<tonic::metadata::AsciiMetadataValue> + Send + Sync + 'static>(self, matcher: impl Into<MethodMatcher>, extractor: F) -> Self {
+        self.rule(matcher.into(), extractor, true)
+    }
+
+    fn find(&self, path: &str) -> Option<&Rule> {
+        let mut result: Option<&Rule> = None;
+        for rule in self.rules.iter().filter(|rule| rule.matcher.matches(path)) {
+            match result {
+                Some(current) if current.matcher.specificity() >= rule.matcher.specificity() => (),
+                _ => result = Some(rule),
+            }
+        }
+        result
+    }
+}
+
+impl ClientInterceptor for RoutingHint {
+    #[inline(always)]
+    fn on_request(&self, _: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+        None
+    }
+
+    fn on_request_with_uri(&self, uri: &http::Uri, headers: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+        if headers.contains_key(&self.header) {
+            return None;
+        }
+
+        let rule = self.find(uri.path())?;
+        match (rule.extractor)(uri, headers) {
+            Some(value) => {
+                headers.insert(self.header.clone(), value);
+                None
+            },
+            None if rule.required => Some(tonic::Status::invalid_argument(format!("Missing {} for {}", self.header, uri.path()))),
+            None => None,
+        }
+    }
+
+    #[inline(always)]
+    fn on_response(&self, _: &tonic::metadata::MetadataMap, _: &http::Extensions) {
+    }
+}
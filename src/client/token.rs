@@ -0,0 +1,219 @@
+//! Token based authorization of outgoing calls
+
+use super::BoxError;
+
+use core::{mem, task};
+use core::pin::Pin;
+use core::future::Future;
+use std::rc::Rc;
+use std::sync::Arc;
+
+///Source of token to authorize outgoing calls
+pub trait TokenSource {
+    ///Retrieves token, refreshing it if necessary
+    ///
+    ///Returned status is used as error of the call.
+    fn token(&self) -> impl Future<Output = Result<tonic::metadata::MetadataValue<tonic::metadata::Ascii>, tonic::Status>> + Send;
+}
+
+impl<T: TokenSource + Send + Sync> TokenSource for Arc<T> {
+    #[inline(always)]
+    fn token(&self) -> impl Future<Output = Result<tonic::metadata::MetadataValue<tonic::metadata::Ascii>, tonic::Status>> + Send {
+        TokenSource::token(self.as_ref())
+    }
+}
+
+///Source of token, which is not required to be `Send`
+///
+///Suitable for single threaded environments (e.g. `wasm32-unknown-unknown`), where transport is not `Send` either.
+///Implemented for every `TokenSource`.
+pub trait LocalTokenSource {
+    ///Retrieves token, refreshing it if necessary
+    ///
+    ///Returned status is used as error of the call.
+    fn token(&self) -> impl Future<Output = Result<tonic::metadata::MetadataValue<tonic::metadata::Ascii>, tonic::Status>>;
+}
+
+impl<T: TokenSource> LocalTokenSource for T {
+    #[inline(always)]
+    fn token(&self) -> impl Future<Output = Result<tonic::metadata::MetadataValue<tonic::metadata::Ascii>, tonic::Status>> {
+        TokenSource::token(self)
+    }
+}
+
+///Layer to inject `authorization: Bearer <token>` into every outgoing call.
+///
+///Call future awaits token from `TokenSource` before dispatching request to the inner service.
+pub struct BearerAuth<T> {
+    source: Arc<T>,
+}
+
+impl<T> BearerAuth<T> {
+    #[inline(always)]
+    ///Creates new instance
+    pub fn new(source: T) -> Self {
+        Self {
+            source: Arc::new(source)
+        }
+    }
+}
+
+impl<T> Clone for BearerAuth<T> {
+    #[inline(always)]
+    fn clone(&self) -> Self {
+        Self {
+            source: self.source.clone()
+        }
+    }
+}
+
+impl<T, S> tower_layer::Layer<S> for BearerAuth<T> {
+    type Service = BearerAuthService<T, S>;
+
+    #[inline(always)]
+    fn layer(&self, inner: S) -> Self::Service {
+        BearerAuthService {
+            source: self.source.clone(),
+            inner,
+        }
+    }
+}
+
+///Service injecting bearer token
+pub struct BearerAuthService<T, S> {
+    source: Arc<T>,
+    inner: S,
+}
+
+impl<T, S: Clone> Clone for BearerAuthService<T, S> {
+    #[inline(always)]
+    fn clone(&self) -> Self {
+        Self {
+            source: self.source.clone(),
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+fn bearer(token: &tonic::metadata::MetadataValue<tonic::metadata::Ascii>) -> Result<http::HeaderValue, tonic::Status> {
+    const PREFIX: &[u8] = b"Bearer ";
+
+    let token = token.as_encoded_bytes();
+    let mut value = Vec::with_capacity(PREFIX.len() + token.len());
+    value.extend_from_slice(PREFIX);
+    value.extend_from_slice(token);
+    match http::HeaderValue::from_maybe_shared(bytes::Bytes::from(value)) {
+        Ok(mut value) => {
+            value.set_sensitive(true);
+            Ok(value)
+        },
+        Err(_) => Err(tonic::Status::unauthenticated("Invalid bearer token")),
+    }
+}
+
+impl<ReqBody, ResBody, T, S> tower_service::Service<http::Request<ReqBody>> for BearerAuthService<T, S> where ReqBody: Send + 'static, T: TokenSource + Send + Sync + 'static, S: tower_service::Service<http::Request<ReqBody>, Response = http::Response<ResBody>> + Clone + Send + 'static, S::Future: Send, S::Error: Into<BoxError> {
+    type Response = S::Response;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, mut req: http::Request<ReqBody>) -> Self::Future {
+        //Take service that is ready, leaving its clone in place
+        let inner = self.inner.clone();
+        let mut inner = mem::replace(&mut self.inner, inner);
+        let source = self.source.clone();
+
+        Box::pin(async move {
+            let token = TokenSource::token(&*source).await?;
+            req.headers_mut().insert(http::header::AUTHORIZATION, bearer(&token)?);
+            inner.call(req).await.map_err(Into::into)
+        })
+    }
+}
+
+///Layer to inject `authorization: Bearer <token>` into every outgoing call, without requiring `Send`
+///
+///Counterpart of `BearerAuth` for single threaded environments.
+pub struct LocalBearerAuth<T> {
+    source: Rc<T>,
+}
+
+impl<T> LocalBearerAuth<T> {
+    #[inline(always)]
+    ///Creates new instance
+    pub fn new(source: T) -> Self {
+        Self {
+            source: Rc::new(source)
+        }
+    }
+}
+
+impl<T> Clone for LocalBearerAuth<T> {
+    #[inline(always)]
+    fn clone(&self) -> Self {
+        Self {
+            source: self.source.clone()
+        }
+    }
+}
+
+impl<T, S> tower_layer::Layer<S> for LocalBearerAuth<T> {
+    type Service = LocalBearerAuthService<T, S>;
+
+    #[inline(always)]
+    fn layer(&self, inner: S) -> Self::Service {
+        LocalBearerAuthService {
+            source: self.source.clone(),
+            inner,
+        }
+    }
+}
+
+///Service injecting bearer token, without requiring `Send`
+pub struct LocalBearerAuthService<T, S> {
+    source: Rc<T>,
+    inner: S,
+}
+
+impl<T, S: Clone> Clone for LocalBearerAuthService<T, S> {
+    #[inline(always)]
+    fn clone(&self) -> Self {
+        Self {
+            source: self.source.clone(),
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<ReqBody, ResBody, T, S> tower_service::Service<http::Request<ReqBody>> for LocalBearerAuthService<T, S> where ReqBody: 'static, T: LocalTokenSource + 'static, S: tower_service::Service<http::Request<ReqBody>, Response = http::Response<ResBody>> + Clone + 'static, S::Error: Into<BoxError> {
+    type Response = S::Response;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, mut req: http::Request<ReqBody>) -> Self::Future {
+        //Take service that is ready, leaving its clone in place
+        let inner = self.inner.clone();
+        let mut inner = mem::replace(&mut self.inner, inner);
+        let source = self.source.clone();
+
+        Box::pin(async move {
+            let token = LocalTokenSource::token(&*source).await?;
+            req.headers_mut().insert(http::header::AUTHORIZATION, bearer(&token)?);
+            inner.call(req).await.map_err(Into::into)
+        })
+    }
+}
+
+#[cfg(feature = "tokio")]
+mod cached;
+#[cfg(feature = "tokio")]
+pub use cached::{Token, TokenRefresh, CachedToken};
@@ -0,0 +1,108 @@
+use super::TokenSource;
+use crate::timer::{Clock, Instant, StdClock};
+
+use core::future::Future;
+use std::sync::Mutex;
+use std::time::Duration;
+
+///Token retrieved from refresh
+pub struct Token {
+    ///Token value
+    pub value: tonic::metadata::MetadataValue<tonic::metadata::Ascii>,
+    ///Duration after which token expires, if `None` it is valid indefinitely.
+    pub expires_in: Option<Duration>,
+}
+
+///Source of fresh token for `CachedToken`
+pub trait TokenRefresh {
+    ///Requests new token
+    fn refresh(&self) -> impl Future<Output = Result<Token, tonic::Status>> + Send;
+}
+
+struct Cache {
+    value: tonic::metadata::MetadataValue<tonic::metadata::Ascii>,
+    expires_at: Option<Instant>,
+}
+
+///Token source that caches token until it expires.
+///
+///Token is refreshed once it is about to expire within configured margin.
+///Only one refresh is running at any time: concurrent calls wait for on-going refresh and use its result.
+///Expiration is measured by `Clock`, which is `StdClock` by default.
+pub struct CachedToken<R, C = StdClock> {
+    refresh: R,
+    margin: Duration,
+    cache: Mutex<Option<Cache>>,
+    refresh_lock: tokio::sync::Mutex<()>,
+    clock: C,
+}
+
+impl<R> CachedToken<R> {
+    ///Default margin before token expiration: 30 seconds.
+    pub const DEFAULT_MARGIN: Duration = Duration::from_secs(30);
+
+    #[inline(always)]
+    ///Creates new instance with default expiration margin
+    pub fn new(refresh: R) -> Self {
+        Self::with_margin(refresh, Self::DEFAULT_MARGIN)
+    }
+
+    #[inline(always)]
+    ///Creates new instance, refreshing token once it has less than `margin` left before expiration.
+    pub fn with_margin(refresh: R, margin: Duration) -> Self {
+        Self {
+            refresh,
+            margin,
+            cache: Mutex::new(None),
+            refresh_lock: tokio::sync::Mutex::const_new(()),
+            clock: StdClock,
+        }
+    }
+}
+
+impl<R, C> CachedToken<R, C> {
+    #[inline]
+    ///Sets clock, which measures expiration
+    pub fn clock<C2: Clock>(self, clock: C2) -> CachedToken<R, C2> {
+        CachedToken {
+            refresh: self.refresh,
+            margin: self.margin,
+            cache: self.cache,
+            refresh_lock: self.refresh_lock,
+            clock,
+        }
+    }
+
+    fn cached(&self) -> Option<tonic::metadata::MetadataValue<tonic::metadata::Ascii>> where C: Clock {
+        let cache = self.cache.lock().unwrap_or_else(|error| error.into_inner());
+        match cache.as_ref() {
+            Some(cache) => match cache.expires_at {
+                Some(expires_at) if self.clock.now() + self.margin >= expires_at => None,
+                _ => Some(cache.value.clone()),
+            },
+            None => None,
+        }
+    }
+}
+
+impl<R: TokenRefresh + Send + Sync, C: Clock + Send + Sync> TokenSource for CachedToken<R, C> {
+    async fn token(&self) -> Result<tonic::metadata::MetadataValue<tonic::metadata::Ascii>, tonic::Status> {
+        if let Some(token) = self.cached() {
+            return Ok(token);
+        }
+
+        let _guard = self.refresh_lock.lock().await;
+        //Whoever held lock before might have already refreshed token
+        if let Some(token) = self.cached() {
+            return Ok(token);
+        }
+
+        let token = self.refresh.refresh().await?;
+        let mut cache = self.cache.lock().unwrap_or_else(|error| error.into_inner());
+        *cache = Some(Cache {
+            value: token.value.clone(),
+            expires_at: token.expires_in.map(|expires_in| self.clock.now() + expires_in),
+        });
+        Ok(token.value)
+    }
+}
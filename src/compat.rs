@@ -0,0 +1,311 @@
+//Compatibility shim over API of tonic, http and http-body
+//
+//API, which differs between versions selected by `tonic011` and `tonic012` features, is wrapped here.
+//Outside of it, version only selects crates aliased in `lib.rs` and derives `Clone` of types stored within `http::Extensions`,
+//which http 1 requires.
+//
+//Bodies of this crate are written against `Body`, which mirrors http-body 0.4, and implement actual `http_body::Body` via `impl_body!`.
+//Bodies they wrap are read through `Incoming`, which provides the same interface over any `http_body::Body`.
+
+use tonic::metadata::MetadataMap;
+
+use core::task;
+use core::pin::Pin;
+
+#[cfg(feature = "tonic011")]
+///Value, which can be stored within `http::Extensions`
+///
+///It is any `Send + Sync + 'static` type, while with `tonic012` feature it must also be `Clone`.
+pub trait Extension: Send + Sync + 'static {}
+#[cfg(feature = "tonic011")]
+impl<T: Send + Sync + 'static> Extension for T {}
+
+#[cfg(not(feature = "tonic011"))]
+///Value, which can be stored within `http::Extensions`
+///
+///It is any `Clone + Send + Sync + 'static` type, while with `tonic011` feature it need not be `Clone`.
+pub trait Extension: Clone + Send + Sync + 'static {}
+#[cfg(not(feature = "tonic011"))]
+impl<T: Clone + Send + Sync + 'static> Extension for T {}
+
+#[cfg(any(feature = "tokio", feature = "wasm"))]
+//Body used by tonic services
+pub(crate) type BoxBody = tonic::body::BoxBody;
+
+#[inline(always)]
+//Wraps headers into metadata
+pub(crate) fn metadata_from_headers(headers: http::HeaderMap) -> MetadataMap {
+    MetadataMap::from_headers(headers)
+}
+
+#[inline(always)]
+//Unwraps headers of metadata
+pub(crate) fn metadata_into_headers(metadata: MetadataMap) -> http::HeaderMap {
+    metadata.into_headers()
+}
+
+#[inline(always)]
+//Writes status code, message, details and metadata of `status` into `headers`
+pub(crate) fn add_status_header(status: &tonic::Status, headers: &mut http::HeaderMap) -> Result<(), tonic::Status> {
+    status.add_header(headers)
+}
+
+#[cfg(any(feature = "tokio", feature = "wasm"))]
+#[inline(always)]
+//Boxes body into body of tonic services
+pub(crate) fn box_body<B: http_body::Body<Data = bytes::Bytes, Error = tonic::Status> + Send + 'static>(body: B) -> BoxBody {
+    tonic::body::BoxBody::new(body)
+}
+
+#[cfg(feature = "tokio")]
+#[inline(always)]
+//Creates body of single chunk, which cannot fail
+pub(crate) fn full_body(data: bytes::Bytes) -> impl http_body::Body<Data = bytes::Bytes, Error = tonic::Status> + Send + 'static {
+    #[cfg(feature = "tonic011")]
+    return http_body::Body::map_err(http_body::Full::new(data), |never| match never {});
+    #[cfg(not(feature = "tonic011"))]
+    return http_body_util::BodyExt::map_err(http_body_util::Full::new(data), |never| match never {});
+}
+
+//Body as defined by http-body 0.4: data chunks followed by optional trailers
+pub(crate) trait Body {
+    //Chunk of data
+    type Data: bytes::Buf;
+    //Error of body
+    type Error;
+
+    //Polls next chunk of data, returning `None` once data is over
+    fn poll_data(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Option<Result<Self::Data, Self::Error>>>;
+    //Polls trailers, once data is over
+    fn poll_trailers(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Result<Option<http::HeaderMap>, Self::Error>>;
+
+    #[inline(always)]
+    //Returns whether body is known to have nothing left
+    fn is_end_stream(&self) -> bool {
+        false
+    }
+
+    #[inline(always)]
+    //Returns bounds of remaining data size
+    fn size_hint(&self) -> http_body::SizeHint {
+        http_body::SizeHint::default()
+    }
+}
+
+#[cfg(not(feature = "tonic011"))]
+//Result of polling http-body 1 frame
+type PollFrame<B> = task::Poll<Option<Result<http_body::Frame<<B as Body>::Data>, <B as Body>::Error>>>;
+
+#[cfg(not(feature = "tonic011"))]
+//Polls frame of http-body 1 out of `Body`
+pub(crate) fn poll_frame<B: Body + ?Sized>(mut body: Pin<&mut B>, ctx: &mut task::Context<'_>) -> PollFrame<B> {
+    match body.as_mut().poll_data(ctx) {
+        task::Poll::Ready(Some(Ok(data))) => task::Poll::Ready(Some(Ok(http_body::Frame::data(data)))),
+        task::Poll::Ready(Some(Err(error))) => task::Poll::Ready(Some(Err(error))),
+        task::Poll::Ready(None) => match body.poll_trailers(ctx) {
+            task::Poll::Ready(Ok(Some(trailers))) => task::Poll::Ready(Some(Ok(http_body::Frame::trailers(trailers)))),
+            task::Poll::Ready(Ok(None)) => task::Poll::Ready(None),
+            task::Poll::Ready(Err(error)) => task::Poll::Ready(Some(Err(error))),
+            task::Poll::Pending => task::Poll::Pending,
+        },
+        task::Poll::Pending => task::Poll::Pending,
+    }
+}
+
+//Implements `Body` and `http_body::Body` using the same items:
+//
+//```ignore
+//impl_body! {
+//    [B: http_body::Body] MyBody<B> where [B::Error: Into<BoxError>] {
+//        type Data = B::Data;
+//        type Error = B::Error;
+//
+//        fn poll_data(..) {}
+//        fn poll_trailers(..) {}
+//    }
+//}
+//```
+macro_rules! impl_body {
+    ([$($generics:tt)*] $body:ty $(where [$($bounds:tt)*])? { type Data = $data:ty; type Error = $error:ty; $($items:tt)* }) => {
+        impl<$($generics)*> $crate::compat::Body for $body $(where $($bounds)*)? {
+            type Data = $data;
+            type Error = $error;
+
+            $($items)*
+        }
+
+        #[cfg(feature = "tonic011")]
+        impl<$($generics)*> http_body::Body for $body $(where $($bounds)*)? {
+            type Data = $data;
+            type Error = $error;
+
+            #[inline(always)]
+            fn poll_data(self: core::pin::Pin<&mut Self>, ctx: &mut core::task::Context<'_>) -> core::task::Poll<Option<Result<Self::Data, Self::Error>>> {
+                $crate::compat::Body::poll_data(self, ctx)
+            }
+
+            #[inline(always)]
+            fn poll_trailers(self: core::pin::Pin<&mut Self>, ctx: &mut core::task::Context<'_>) -> core::task::Poll<Result<Option<http::HeaderMap>, Self::Error>> {
+                $crate::compat::Body::poll_trailers(self, ctx)
+            }
+
+            #[inline(always)]
+            fn is_end_stream(&self) -> bool {
+                $crate::compat::Body::is_end_stream(self)
+            }
+
+            #[inline(always)]
+            fn size_hint(&self) -> http_body::SizeHint {
+                $crate::compat::Body::size_hint(self)
+            }
+        }
+
+        #[cfg(not(feature = "tonic011"))]
+        impl<$($generics)*> http_body::Body for $body $(where $($bounds)*)? {
+            type Data = $data;
+            type Error = $error;
+
+            #[inline(always)]
+            fn poll_frame(self: core::pin::Pin<&mut Self>, ctx: &mut core::task::Context<'_>) -> core::task::Poll<Option<Result<http_body::Frame<Self::Data>, Self::Error>>> {
+                $crate::compat::poll_frame(self, ctx)
+            }
+
+            #[inline(always)]
+            fn is_end_stream(&self) -> bool {
+                $crate::compat::Body::is_end_stream(self)
+            }
+
+            #[inline(always)]
+            fn size_hint(&self) -> http_body::SizeHint {
+                $crate::compat::Body::size_hint(self)
+            }
+        }
+    };
+}
+pub(crate) use impl_body;
+
+#[cfg(feature = "tonic011")]
+pin_project_lite::pin_project! {
+    //Body, wrapped by body of this crate, read as `Body`
+    pub struct Incoming<B> {
+        #[pin]
+        inner: B,
+    }
+}
+
+#[cfg(not(feature = "tonic011"))]
+pin_project_lite::pin_project! {
+    //Body, wrapped by body of this crate, read as `Body`
+    //
+    //Trailers frame, polled in place of data, is kept until trailers are polled.
+    pub struct Incoming<B> {
+        #[pin]
+        inner: B,
+        trailers: Option<http::HeaderMap>,
+        is_data_end: bool,
+    }
+}
+
+impl<B> Incoming<B> {
+    #[inline(always)]
+    pub(crate) fn new(inner: B) -> Self {
+        Self {
+            inner,
+            #[cfg(not(feature = "tonic011"))]
+            trailers: None,
+            #[cfg(not(feature = "tonic011"))]
+            is_data_end: false,
+        }
+    }
+}
+
+impl<B: Default> Default for Incoming<B> {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::new(B::default())
+    }
+}
+
+#[cfg(feature = "tonic011")]
+impl<B: http_body::Body> Body for Incoming<B> {
+    type Data = B::Data;
+    type Error = B::Error;
+
+    #[inline(always)]
+    fn poll_data(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Option<Result<Self::Data, Self::Error>>> {
+        http_body::Body::poll_data(self.project().inner, ctx)
+    }
+
+    #[inline(always)]
+    fn poll_trailers(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Result<Option<http::HeaderMap>, Self::Error>> {
+        http_body::Body::poll_trailers(self.project().inner, ctx)
+    }
+
+    #[inline(always)]
+    fn is_end_stream(&self) -> bool {
+        http_body::Body::is_end_stream(&self.inner)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> http_body::SizeHint {
+        http_body::Body::size_hint(&self.inner)
+    }
+}
+
+#[cfg(not(feature = "tonic011"))]
+impl<B: http_body::Body> Body for Incoming<B> {
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_data(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Option<Result<Self::Data, Self::Error>>> {
+        let mut this = self.project();
+        while !*this.is_data_end {
+            match http_body::Body::poll_frame(this.inner.as_mut(), ctx) {
+                task::Poll::Ready(Some(Ok(frame))) => match frame.into_data() {
+                    Ok(data) => return task::Poll::Ready(Some(Ok(data))),
+                    Err(frame) => if let Ok(trailers) = frame.into_trailers() {
+                        *this.trailers = Some(trailers);
+                        *this.is_data_end = true;
+                    },
+                },
+                task::Poll::Ready(Some(Err(error))) => return task::Poll::Ready(Some(Err(error))),
+                task::Poll::Ready(None) => *this.is_data_end = true,
+                task::Poll::Pending => return task::Poll::Pending,
+            }
+        }
+        task::Poll::Ready(None)
+    }
+
+    fn poll_trailers(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Result<Option<http::HeaderMap>, Self::Error>> {
+        let mut this = self.project();
+        //Remaining data is skipped, as http-body 0.4 does not expect trailers to be polled before it
+        while !*this.is_data_end {
+            match http_body::Body::poll_frame(this.inner.as_mut(), ctx) {
+                task::Poll::Ready(Some(Ok(frame))) => if let Ok(trailers) = frame.into_trailers() {
+                    *this.trailers = Some(trailers);
+                    *this.is_data_end = true;
+                },
+                task::Poll::Ready(Some(Err(error))) => return task::Poll::Ready(Err(error)),
+                task::Poll::Ready(None) => *this.is_data_end = true,
+                task::Poll::Pending => return task::Poll::Pending,
+            }
+        }
+        task::Poll::Ready(Ok(this.trailers.take()))
+    }
+
+    #[inline]
+    fn is_end_stream(&self) -> bool {
+        match self.is_data_end {
+            true => self.trailers.is_none(),
+            false => http_body::Body::is_end_stream(&self.inner),
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> http_body::SizeHint {
+        match self.is_data_end {
+            true => http_body::SizeHint::with_exact(0),
+            false => http_body::Body::size_hint(&self.inner),
+        }
+    }
+}
@@ -0,0 +1,526 @@
+//! Configuration of interceptor chain
+//!
+//!Chain is described as list of interceptors in order of execution.
+//!Every entry is identified by `name` and can be disabled with `enabled = false`, while the rest of its keys are parameters:
+//!
+//!- `rate_limit` - `requests` (required) per `period_secs` (default 1), `headers` (default false) to write `x-ratelimit-*` headers,
+//!  and `per_client` (default false) to limit every client address separately, see `RateLimit`;
+//!- `metadata_limit` - `max_entries` and/or `max_bytes`, optionally `max_ascii_entries`, `max_bin_entries` and `max_values_per_key`, see `MetadataLimit`;
+//!- `bearer_auth` - `token_env` (required), name of environment variable with expected token, see `BearerAuth`;
+//!- `method_allowlist` - `methods` (required), list of `MethodMatcher` patterns, see `MethodAllowlist`;
+//!- `logging` - `level` (default `info`) and `redact`, list of metadata keys to mask, see `Logging` (requires `tracing` feature).
+//!
+//!```toml
+//![[interceptors]]
+//!name = "bearer_auth"
+//!token_env = "API_TOKEN"
+//!
+//![[interceptors]]
+//!name = "rate_limit"
+//!requests = 100
+//!period_secs = 1
+//!```
+//!
+//!Configuration is validated by `build_chain`, which reports error with path to invalid value (e.g. `interceptors[1].requests`).
+//!Disabled entries are not validated.
+//!
+//!With `tokio` feature, built-in interceptors and chain implement `reload::FromConfig`, hence can be reloaded by `reload::Watched`.
+//!Single interceptor is built out of its `InterceptorConfig` regardless of `enabled`, reporting errors with path starting with its name.
+
+mod builtin;
+pub use builtin::{RateLimit, MetadataLimit, BearerAuth, MethodAllowlist, LogLevel};
+#[cfg(feature = "tracing")]
+pub use builtin::Logging;
+
+use crate::{BoxedInterceptor, InterceptorChain};
+use crate::matcher::MethodMatcher;
+#[cfg(feature = "tracing")]
+use crate::redact::Redactor;
+
+use core::fmt;
+use core::convert::TryFrom;
+use core::time::Duration;
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::de;
+
+#[derive(Clone, PartialEq, Debug)]
+///Value of interceptor parameter
+pub enum Param {
+    ///Boolean
+    Bool(bool),
+    ///Integer
+    Int(i64),
+    ///Floating point number
+    Float(f64),
+    ///String
+    Str(String),
+    ///List of values
+    List(Vec<Param>),
+}
+
+impl Param {
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::Bool(_) => "boolean",
+            Self::Int(_) => "integer",
+            Self::Float(_) => "float",
+            Self::Str(_) => "string",
+            Self::List(_) => "list",
+        }
+    }
+}
+
+struct ParamVisitor;
+
+impl<'de> de::Visitor<'de> for ParamVisitor {
+    type Value = Param;
+
+    fn expecting(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.write_str("boolean, number, string or list")
+    }
+
+    #[inline(always)]
+    fn visit_bool<E: de::Error>(self, value: bool) -> Result<Self::Value, E> {
+        Ok(Param::Bool(value))
+    }
+
+    #[inline(always)]
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Self::Value, E> {
+        Ok(Param::Int(value))
+    }
+
+    #[inline]
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Self::Value, E> {
+        match i64::try_from(value) {
+            Ok(value) => Ok(Param::Int(value)),
+            Err(_) => Err(E::invalid_value(de::Unexpected::Unsigned(value), &"integer within i64")),
+        }
+    }
+
+    #[inline(always)]
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<Self::Value, E> {
+        Ok(Param::Float(value))
+    }
+
+    #[inline(always)]
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+        Ok(Param::Str(value.to_owned()))
+    }
+
+    #[inline(always)]
+    fn visit_string<E: de::Error>(self, value: String) -> Result<Self::Value, E> {
+        Ok(Param::Str(value))
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut values = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(value) = seq.next_element()? {
+            values.push(value);
+        }
+        Ok(Param::List(values))
+    }
+}
+
+impl<'de> de::Deserialize<'de> for Param {
+    #[inline(always)]
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ParamVisitor)
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+///Configuration of single interceptor
+pub struct InterceptorConfig {
+    ///Name of interceptor
+    pub name: String,
+    ///Whether interceptor is part of chain, `true` by default
+    pub enabled: bool,
+    ///Parameters of interceptor
+    pub params: BTreeMap<String, Param>,
+}
+
+impl InterceptorConfig {
+    #[inline]
+    ///Creates enabled interceptor without parameters
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            enabled: true,
+            params: BTreeMap::new(),
+        }
+    }
+
+    #[inline]
+    ///Sets parameter
+    pub fn param(mut self, name: impl Into<String>, value: Param) -> Self {
+        self.params.insert(name.into(), value);
+        self
+    }
+}
+
+struct InterceptorConfigVisitor;
+
+impl<'de> de::Visitor<'de> for InterceptorConfigVisitor {
+    type Value = InterceptorConfig;
+
+    fn expecting(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.write_str("interceptor with name and parameters")
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut name = None;
+        let mut enabled = None;
+        let mut params = BTreeMap::new();
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "name" => match name {
+                    None => name = Some(map.next_value()?),
+                    Some(_) => return Err(de::Error::duplicate_field("name")),
+                },
+                "enabled" => match enabled {
+                    None => enabled = Some(map.next_value()?),
+                    Some(_) => return Err(de::Error::duplicate_field("enabled")),
+                },
+                _ => {
+                    let value = map.next_value()?;
+                    if params.contains_key(&key) {
+                        return Err(de::Error::custom(format_args!("duplicate parameter `{}`", key)));
+                    }
+                    params.insert(key, value);
+                },
+            }
+        }
+
+        Ok(InterceptorConfig {
+            name: name.ok_or_else(|| de::Error::missing_field("name"))?,
+            enabled: enabled.unwrap_or(true),
+            params,
+        })
+    }
+}
+
+impl<'de> de::Deserialize<'de> for InterceptorConfig {
+    #[inline(always)]
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(InterceptorConfigVisitor)
+    }
+}
+
+#[derive(Clone, Default, PartialEq, Debug)]
+///Configuration of interceptor chain
+pub struct ChainConfig {
+    ///Interceptors in order of execution
+    pub interceptors: Vec<InterceptorConfig>,
+}
+
+struct ChainConfigVisitor;
+
+impl<'de> de::Visitor<'de> for ChainConfigVisitor {
+    type Value = ChainConfig;
+
+    fn expecting(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.write_str("chain with list of interceptors")
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut interceptors = None;
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "interceptors" => match interceptors {
+                    None => interceptors = Some(map.next_value()?),
+                    Some(_) => return Err(de::Error::duplicate_field("interceptors")),
+                },
+                _ => return Err(de::Error::unknown_field(&key, &["interceptors"])),
+            }
+        }
+
+        Ok(ChainConfig {
+            interceptors: interceptors.unwrap_or_default(),
+        })
+    }
+}
+
+impl<'de> de::Deserialize<'de> for ChainConfig {
+    #[inline(always)]
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(ChainConfigVisitor)
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+///Error of building chain out of configuration
+pub struct ConfigError {
+    path: String,
+    message: String,
+}
+
+impl ConfigError {
+    #[inline(always)]
+    fn new(path: String, message: String) -> Self {
+        Self {
+            path,
+            message,
+        }
+    }
+
+    #[inline(always)]
+    ///Returns path to invalid value, e.g. `interceptors[1].requests`
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    #[inline(always)]
+    ///Returns description of error
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for ConfigError {
+    #[inline]
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "{}: {}", self.path, self.message)
+    }
+}
+
+impl std::error::Error for ConfigError {
+}
+
+//Parameters of single interceptor, tracking which of them were used
+struct Params<'a> {
+    path: &'a str,
+    name: &'a str,
+    params: &'a BTreeMap<String, Param>,
+    used: BTreeSet<&'a str>,
+}
+
+impl<'a> Params<'a> {
+    fn new(path: &'a str, config: &'a InterceptorConfig) -> Self {
+        Self {
+            path,
+            name: &config.name,
+            params: &config.params,
+            used: BTreeSet::new(),
+        }
+    }
+
+    fn error(&self, key: &str, message: String) -> ConfigError {
+        ConfigError::new(format!("{}.{}", self.path, key), message)
+    }
+
+    fn get(&mut self, key: &'a str) -> Option<&'a Param> {
+        self.used.insert(key);
+        self.params.get(key)
+    }
+
+    fn required<T>(&self, key: &str, value: Option<T>) -> Result<T, ConfigError> {
+        value.ok_or_else(|| self.error(key, format!("missing parameter of '{}'", self.name)))
+    }
+
+    fn positive(&mut self, key: &'a str) -> Result<Option<u64>, ConfigError> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(Param::Int(value)) if *value > 0 => Ok(Some(*value as u64)),
+            Some(Param::Int(value)) => Err(self.error(key, format!("expected positive integer, found {}", value))),
+            Some(value) => Err(self.error(key, format!("expected positive integer, found {}", value.kind()))),
+        }
+    }
+
+    fn boolean(&mut self, key: &'a str) -> Result<Option<bool>, ConfigError> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(Param::Bool(value)) => Ok(Some(*value)),
+            Some(value) => Err(self.error(key, format!("expected boolean, found {}", value.kind()))),
+        }
+    }
+
+    fn string(&mut self, key: &'a str) -> Result<Option<&'a str>, ConfigError> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(Param::Str(value)) => Ok(Some(value)),
+            Some(value) => Err(self.error(key, format!("expected string, found {}", value.kind()))),
+        }
+    }
+
+    fn strings(&mut self, key: &'a str) -> Result<Option<Vec<&'a str>>, ConfigError> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(Param::List(values)) => values.iter().enumerate().map(|(idx, value)| match value {
+                Param::Str(value) => Ok(value.as_str()),
+                value => Err(self.error(&format!("{}[{}]", key, idx), format!("expected string, found {}", value.kind()))),
+            }).collect::<Result<Vec<_>, _>>().map(Some),
+            Some(value) => Err(self.error(key, format!("expected list of strings, found {}", value.kind()))),
+        }
+    }
+
+    //Reports first parameter which is not known to interceptor
+    fn finish(self) -> Result<(), ConfigError> {
+        match self.params.keys().find(|key| !self.used.contains(key.as_str())) {
+            Some(key) => Err(self.error(key, format!("unknown parameter of '{}'", self.name))),
+            None => Ok(()),
+        }
+    }
+}
+
+fn rate_limit(params: &mut Params<'_>) -> Result<RateLimit, ConfigError> {
+    let requests = params.positive("requests")?;
+    let requests = params.required("requests", requests)?;
+    let requests = match u32::try_from(requests) {
+        Ok(requests) => requests,
+        Err(_) => return Err(params.error("requests", format!("expected at most {}, found {}", u32::MAX, requests))),
+    };
+    let period = params.positive("period_secs")?.unwrap_or(1);
+    let headers = params.boolean("headers")?.unwrap_or(false);
+    let per_client = params.boolean("per_client")?.unwrap_or(false);
+    Ok(RateLimit::new(requests, Duration::from_secs(period)).headers(headers).per_client(per_client))
+}
+
+fn metadata_limit(params: &mut Params<'_>) -> Result<MetadataLimit, ConfigError> {
+    let max_entries = params.positive("max_entries")?;
+    let max_ascii_entries = params.positive("max_ascii_entries")?;
+    let max_bin_entries = params.positive("max_bin_entries")?;
+    let max_values_per_key = params.positive("max_values_per_key")?;
+    let max_bytes = params.positive("max_bytes")?;
+    let mut limit = MetadataLimit::new();
+    if let Some(max_entries) = max_entries {
+        limit = limit.max_entries(max_entries as usize);
+    }
+    if let Some(max_ascii_entries) = max_ascii_entries {
+        limit = limit.max_ascii_entries(max_ascii_entries as usize);
+    }
+    if let Some(max_bin_entries) = max_bin_entries {
+        limit = limit.max_bin_entries(max_bin_entries as usize);
+    }
+    if let Some(max_values_per_key) = max_values_per_key {
+        limit = limit.max_values_per_key(max_values_per_key as usize);
+    }
+    if let Some(max_bytes) = max_bytes {
+        limit = limit.max_bytes(max_bytes as usize);
+    }
+    match (max_entries, max_ascii_entries, max_bin_entries, max_values_per_key, max_bytes) {
+        (None, None, None, None, None) => Err(params.error("max_entries", format!("'{}' requires max_entries or max_bytes", params.name))),
+        _ => Ok(limit),
+    }
+}
+
+fn bearer_auth(params: &mut Params<'_>) -> Result<BearerAuth, ConfigError> {
+    let var = params.string("token_env")?;
+    let var = params.required("token_env", var)?;
+    match std::env::var(var) {
+        Ok(token) if !token.is_empty() => Ok(BearerAuth::new(token)),
+        Ok(_) => Err(params.error("token_env", format!("environment variable '{}' is empty", var))),
+        Err(_) => Err(params.error("token_env", format!("environment variable '{}' is not set", var))),
+    }
+}
+
+fn method_allowlist(params: &mut Params<'_>) -> Result<MethodAllowlist, ConfigError> {
+    let methods = params.strings("methods")?;
+    let methods = params.required("methods", methods)?;
+    if methods.is_empty() {
+        return Err(params.error("methods", "expected at least one method".to_owned()));
+    }
+    for (idx, method) in methods.iter().enumerate() {
+        if *method != "*" && !method.starts_with('/') {
+            return Err(params.error(&format!("methods[{}]", idx), format!("expected method pattern, found '{}'", method)));
+        }
+    }
+    Ok(MethodAllowlist::new(methods.into_iter().map(MethodMatcher::new)))
+}
+
+#[cfg(feature = "tracing")]
+fn logging(params: &mut Params<'_>) -> Result<Logging, ConfigError> {
+    let level = match params.string("level")? {
+        None => LogLevel::Info,
+        Some(name) => match LogLevel::from_name(name) {
+            Some(level) => level,
+            None => return Err(params.error("level", format!("expected one of error, warn, info, debug, trace, found '{}'", name))),
+        },
+    };
+    let logging = Logging::new(level);
+    match params.strings("redact")? {
+        Some(keys) => Ok(logging.redactor(std::sync::Arc::new(keys.into_iter().fold(Redactor::new(), Redactor::key)))),
+        None => Ok(logging),
+    }
+}
+
+///Builds chain of enabled interceptors in configured order
+pub fn build_chain(config: ChainConfig) -> Result<BoxedInterceptor, ConfigError> {
+    let mut chain = InterceptorChain::new();
+
+    for (idx, interceptor) in config.interceptors.iter().enumerate() {
+        if !interceptor.enabled {
+            continue;
+        }
+
+        let path = format!("interceptors[{}]", idx);
+        let mut params = Params::new(&path, interceptor);
+        match interceptor.name.as_str() {
+            "rate_limit" => chain.push(rate_limit(&mut params)?),
+            "metadata_limit" => chain.push(metadata_limit(&mut params)?),
+            "bearer_auth" => chain.push(bearer_auth(&mut params)?),
+            "method_allowlist" => chain.push(method_allowlist(&mut params)?),
+            #[cfg(feature = "tracing")]
+            "logging" => chain.push(logging(&mut params)?),
+            #[cfg(not(feature = "tracing"))]
+            "logging" => return Err(ConfigError::new(format!("{}.name", path), "'logging' requires tracing feature".to_owned())),
+            name => return Err(ConfigError::new(format!("{}.name", path), format!("unknown interceptor '{}'", name))),
+        }
+        params.finish()?;
+    }
+
+    Ok(std::sync::Arc::new(chain))
+}
+
+#[cfg(feature = "tokio")]
+//Builds interceptor `name` out of its own configuration
+fn build_single<T>(config: &InterceptorConfig, name: &str, build: fn(&mut Params<'_>) -> Result<T, ConfigError>) -> Result<T, ConfigError> {
+    if config.name != name {
+        return Err(ConfigError::new(format!("{}.name", config.name), format!("expected '{}', found '{}'", name, config.name)));
+    }
+    let mut params = Params::new(&config.name, config);
+    let interceptor = build(&mut params)?;
+    params.finish()?;
+    Ok(interceptor)
+}
+
+#[cfg(feature = "tokio")]
+macro_rules! impl_from_config {
+    ($($ty:ident => $name:literal: $build:ident),+) => {
+        $(
+            impl crate::reload::FromConfig<InterceptorConfig> for $ty {
+                type Error = ConfigError;
+
+                #[inline(always)]
+                fn from_config(config: &InterceptorConfig) -> Result<Self, Self::Error> {
+                    build_single(config, $name, $build)
+                }
+            }
+        )+
+    };
+}
+
+#[cfg(feature = "tokio")]
+impl_from_config!(
+    RateLimit => "rate_limit": rate_limit,
+    MetadataLimit => "metadata_limit": metadata_limit,
+    BearerAuth => "bearer_auth": bearer_auth,
+    MethodAllowlist => "method_allowlist": method_allowlist
+);
+
+#[cfg(all(feature = "tokio", feature = "tracing"))]
+impl_from_config!(
+    Logging => "logging": logging
+);
+
+#[cfg(feature = "tokio")]
+impl crate::reload::FromConfig<ChainConfig> for BoxedInterceptor {
+    type Error = ConfigError;
+
+    #[inline(always)]
+    fn from_config(config: &ChainConfig) -> Result<Self, Self::Error> {
+        build_chain(config.clone())
+    }
+}
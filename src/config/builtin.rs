@@ -0,0 +1,485 @@
+use crate::Interceptor;
+use crate::auth::TokenExtractor;
+use crate::identity::{Mechanism, PeerIdentity};
+use crate::matcher::MethodMatcher;
+#[cfg(feature = "tracing")]
+use crate::redact::Redactor;
+use crate::limit::lock;
+use crate::net::RealIp;
+use crate::timer::{Clock, Instant, StdClock};
+
+use core::fmt;
+use core::time::Duration;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+#[cfg(feature = "tracing")]
+use std::sync::Arc;
+
+#[derive(Debug)]
+///Server interceptor which limits number of requests within fixed window
+///
+///Requests exceeding limit are rejected using `reject::rate_limited` with time until the end of window.
+///With `headers` enabled, every response carries `limit::Headers` as decided for its request.
+///Window is measured by `Clock`, which is `StdClock` by default.
+///
+///With `per_client` enabled, every client address, as returned by `net::RealIp::client`, has its own window,
+///while requests of unknown address share single window.
+///Up to 10000 addresses are tracked, evicting expired windows first, then the oldest ones.
+pub struct RateLimit<C = StdClock> {
+    requests: u32,
+    period: Duration,
+    headers: bool,
+    per_client: bool,
+    //Start of current window and number of requests within it
+    window: Mutex<(Instant, u32)>,
+    clients: Mutex<HashMap<IpAddr, (Instant, u32)>>,
+    clock: C,
+}
+
+const MAX_CLIENTS: usize = 10_000;
+
+impl RateLimit {
+    #[inline]
+    ///Creates new instance, allowing `requests` per every `period`
+    pub fn new(requests: u32, period: Duration) -> Self {
+        Self {
+            requests,
+            period,
+            headers: false,
+            per_client: false,
+            window: Mutex::new((StdClock.now(), 0)),
+            clients: Mutex::new(HashMap::new()),
+            clock: StdClock,
+        }
+    }
+}
+
+impl<C> RateLimit<C> {
+    #[inline]
+    ///Sets clock, which measures window, starting new window
+    pub fn clock<C2: Clock>(self, clock: C2) -> RateLimit<C2> {
+        RateLimit {
+            requests: self.requests,
+            period: self.period,
+            headers: self.headers,
+            per_client: self.per_client,
+            window: Mutex::new((clock.now(), 0)),
+            clients: Mutex::new(HashMap::new()),
+            clock,
+        }
+    }
+
+    #[inline]
+    ///Sets whether to write `x-ratelimit-*` headers onto every response
+    pub fn headers(mut self, headers: bool) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    #[inline]
+    ///Sets whether every client address has its own window
+    pub fn per_client(mut self, per_client: bool) -> Self {
+        self.per_client = per_client;
+        self
+    }
+
+    //Counts request within `window`, returning rejection, number of requests within window and time until its end
+    fn count(&self, window: &mut (Instant, u32), now: Instant) -> (Option<tonic::Status>, u32, Duration) {
+        let mut elapsed = now.saturating_duration_since(window.0);
+        let result = if elapsed >= self.period {
+            *window = (now, 1);
+            elapsed = Duration::ZERO;
+            None
+        } else if window.1 >= self.requests {
+            Some(crate::reject::rate_limited(self.period - elapsed))
+        } else {
+            window.1 += 1;
+            None
+        };
+        (result, window.1, self.period - elapsed)
+    }
+}
+
+impl<C: Clock> Interceptor for RateLimit<C> {
+    fn on_request(&self, _: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        let now = self.clock.now();
+        let client = match self.per_client {
+            true => RealIp::client(extensions),
+            false => None,
+        };
+        let (result, count, reset) = match client {
+            Some(client) => {
+                let mut clients = lock(&self.clients);
+                if clients.len() >= MAX_CLIENTS && !clients.contains_key(&client) {
+                    clients.retain(|_, (start, _)| now.saturating_duration_since(*start) < self.period);
+                    if clients.len() >= MAX_CLIENTS {
+                        if let Some(oldest) = clients.iter().min_by_key(|(_, (start, _))| *start).map(|(addr, _)| *addr) {
+                            clients.remove(&oldest);
+                        }
+                    }
+                }
+                self.count(clients.entry(client).or_insert((now, 0)), now)
+            },
+            None => self.count(&mut lock(&self.window), now),
+        };
+
+        if self.headers {
+            crate::limit::Headers::new(self.requests.into(), self.requests.saturating_sub(count).into(), reset).echo(extensions);
+        }
+        result
+    }
+
+    #[inline(always)]
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+///Server interceptor which limits size of request metadata
+///
+///Size is number of entries and total length of their names and values.
+///Entries can be limited separately for ASCII and binary (`-bin`) keys, as well as number of values of single key.
+///Every limit is checked within single pass over metadata.
+///Requests exceeding limit are rejected with `RESOURCE_EXHAUSTED`.
+pub struct MetadataLimit {
+    max_entries: Option<usize>,
+    max_ascii_entries: Option<usize>,
+    max_bin_entries: Option<usize>,
+    max_values_per_key: Option<usize>,
+    max_bytes: Option<usize>,
+}
+
+impl MetadataLimit {
+    #[inline(always)]
+    ///Creates new instance without limits
+    pub const fn new() -> Self {
+        Self {
+            max_entries: None,
+            max_ascii_entries: None,
+            max_bin_entries: None,
+            max_values_per_key: None,
+            max_bytes: None,
+        }
+    }
+
+    #[inline(always)]
+    ///Limits number of entries
+    pub const fn max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = Some(max_entries);
+        self
+    }
+
+    #[inline(always)]
+    ///Limits number of entries with ASCII keys
+    pub const fn max_ascii_entries(mut self, max_ascii_entries: usize) -> Self {
+        self.max_ascii_entries = Some(max_ascii_entries);
+        self
+    }
+
+    #[inline(always)]
+    ///Limits number of entries with binary keys
+    pub const fn max_bin_entries(mut self, max_bin_entries: usize) -> Self {
+        self.max_bin_entries = Some(max_bin_entries);
+        self
+    }
+
+    #[inline(always)]
+    ///Limits number of values of single key
+    pub const fn max_values_per_key(mut self, max_values_per_key: usize) -> Self {
+        self.max_values_per_key = Some(max_values_per_key);
+        self
+    }
+
+    #[inline(always)]
+    ///Limits total length of names and values
+    pub const fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    //Checks `entries` of key and size, which yield values of the same key consecutively
+    fn check<'a>(&self, len: usize, entries: impl Iterator<Item = (&'a str, usize)>) -> Option<tonic::Status> {
+        if let Some(max_entries) = self.max_entries {
+            if len > max_entries {
+                return Some(tonic::Status::resource_exhausted(format!("metadata exceeds limit of {} entries", max_entries)));
+            }
+        }
+
+        if self.max_ascii_entries.is_none() && self.max_bin_entries.is_none() && self.max_values_per_key.is_none() && self.max_bytes.is_none() {
+            return None;
+        }
+
+        let mut ascii = 0usize;
+        let mut bin = 0usize;
+        let mut total = 0usize;
+        let mut last_key = "";
+        let mut values = 0usize;
+        for (key, size) in entries {
+            if key.ends_with("-bin") {
+                bin += 1;
+                if let Some(max_bin_entries) = self.max_bin_entries {
+                    if bin > max_bin_entries {
+                        return Some(tonic::Status::resource_exhausted(format!("metadata exceeds limit of {} binary entries", max_bin_entries)));
+                    }
+                }
+            } else {
+                ascii += 1;
+                if let Some(max_ascii_entries) = self.max_ascii_entries {
+                    if ascii > max_ascii_entries {
+                        return Some(tonic::Status::resource_exhausted(format!("metadata exceeds limit of {} ASCII entries", max_ascii_entries)));
+                    }
+                }
+            }
+
+            if key == last_key {
+                values += 1;
+            } else {
+                last_key = key;
+                values = 1;
+            }
+            if let Some(max_values_per_key) = self.max_values_per_key {
+                if values > max_values_per_key {
+                    return Some(tonic::Status::resource_exhausted(format!("metadata '{}' exceeds limit of {} values", key, max_values_per_key)));
+                }
+            }
+
+            total = total.saturating_add(size);
+            if let Some(max_bytes) = self.max_bytes {
+                if total > max_bytes {
+                    return Some(tonic::Status::resource_exhausted(format!("metadata exceeds limit of {} bytes", max_bytes)));
+                }
+            }
+        }
+
+        None
+    }
+}
+
+impl Default for MetadataLimit {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Interceptor for MetadataLimit {
+    #[inline]
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+        self.check(headers.len(), headers.iter().map(|entry| match entry {
+            tonic::metadata::KeyAndValueRef::Ascii(key, value) => (key.as_str(), key.as_str().len() + value.as_bytes().len()),
+            tonic::metadata::KeyAndValueRef::Binary(key, value) => (key.as_str(), key.as_str().len() + value.as_encoded_bytes().len()),
+        }))
+    }
+
+    #[inline]
+    fn on_request_headers(&self, _: &http::Uri, headers: &mut http::HeaderMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+        self.check(headers.len(), headers.iter().map(|(key, value)| (key.as_str(), key.as_str().len() + value.len())))
+    }
+
+    #[inline(always)]
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
+
+#[derive(Clone)]
+///Server interceptor which requires token with expected value
+///
+///Token is taken from `authorization: Bearer <token>`, unless configured otherwise with `extractor`.
+///Source of accepted token is inserted into request extensions as `auth::TokenSource`,
+///along with anonymous `identity::PeerIdentity`, which has it as `extra`.
+///
+///Failed validation is rejected with `UNAUTHENTICATED`.
+pub struct BearerAuth {
+    token: String,
+    extractor: TokenExtractor,
+}
+
+impl BearerAuth {
+    #[inline]
+    ///Creates new instance, expecting `token`
+    pub fn new(token: impl Into<String>) -> Self {
+        Self {
+            token: token.into(),
+            extractor: TokenExtractor::default(),
+        }
+    }
+
+    #[inline]
+    ///Sets sources of token
+    pub fn extractor(mut self, extractor: TokenExtractor) -> Self {
+        self.extractor = extractor;
+        self
+    }
+}
+
+impl fmt::Debug for BearerAuth {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("BearerAuth").field("extractor", &self.extractor).finish_non_exhaustive()
+    }
+}
+
+//Compares in time, which depends only on length of `left`
+fn constant_eq(left: &[u8], right: &[u8]) -> bool {
+    left.len() == right.len() && left.iter().zip(right).fold(0u8, |acc, (left, right)| acc | (left ^ right)) == 0
+}
+
+impl Interceptor for BearerAuth {
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        let token = match self.extractor.extract(headers) {
+            Some(token) => token,
+            None => return Some(tonic::Status::unauthenticated("missing bearer token")),
+        };
+
+        match constant_eq(token.value.as_bytes(), self.token.as_bytes()) {
+            true => {
+                extensions.insert(token.source.clone());
+                extensions.insert(PeerIdentity::new(Mechanism::Bearer).with_extra(token.source.clone()));
+                None
+            },
+            false => Some(tonic::Status::unauthenticated("invalid bearer token")),
+        }
+    }
+
+    #[inline(always)]
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
+
+#[derive(Clone, Debug)]
+///Server interceptor which accepts only listed methods
+///
+///Other methods are rejected with `PERMISSION_DENIED`.
+pub struct MethodAllowlist {
+    methods: Vec<MethodMatcher>,
+}
+
+impl MethodAllowlist {
+    #[inline]
+    ///Creates new instance, accepting `methods`
+    pub fn new<M: Into<MethodMatcher>>(methods: impl IntoIterator<Item = M>) -> Self {
+        Self {
+            methods: methods.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl Interceptor for MethodAllowlist {
+    #[inline(always)]
+    fn on_request(&self, _: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+        Some(tonic::Status::internal("MethodAllowlist requires request URI"))
+    }
+
+    #[inline]
+    fn on_request_with_uri(&self, uri: &http::Uri, _: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+        match self.methods.iter().any(|method| method.matches(uri.path())) {
+            true => None,
+            false => Some(tonic::Status::permission_denied(format!("method '{}' is not allowed", uri.path()))),
+        }
+    }
+
+    #[inline(always)]
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+///Level of `Logging` events
+pub enum LogLevel {
+    ///`ERROR`
+    Error,
+    ///`WARN`
+    Warn,
+    ///`INFO`
+    Info,
+    ///`DEBUG`
+    Debug,
+    ///`TRACE`
+    Trace,
+}
+
+impl LogLevel {
+    ///Parses lower case name of level
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "error" => Some(Self::Error),
+            "warn" => Some(Self::Warn),
+            "info" => Some(Self::Info),
+            "debug" => Some(Self::Debug),
+            "trace" => Some(Self::Trace),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "tracing")]
+macro_rules! log {
+    ($level:expr, $($arg:tt)+) => {
+        match $level {
+            LogLevel::Error => tracing::error!($($arg)+),
+            LogLevel::Warn => tracing::warn!($($arg)+),
+            LogLevel::Info => tracing::info!($($arg)+),
+            LogLevel::Debug => tracing::debug!($($arg)+),
+            LogLevel::Trace => tracing::trace!($($arg)+),
+        }
+    };
+}
+
+#[cfg(feature = "tracing")]
+#[derive(Clone, Debug)]
+///Server interceptor which emits `tracing` event on every request and response
+///
+///Request event includes `peer` field with address of client, as returned by `net::RealIp::client`, when it is known.
+///With redactor, request event includes `metadata` field with secrets masked.
+pub struct Logging {
+    level: LogLevel,
+    redactor: Option<Arc<Redactor>>,
+}
+
+#[cfg(feature = "tracing")]
+impl Logging {
+    #[inline(always)]
+    ///Creates new instance, emitting events at `level`
+    pub const fn new(level: LogLevel) -> Self {
+        Self {
+            level,
+            redactor: None,
+        }
+    }
+
+    #[inline]
+    ///Sets redactor of request metadata, which is logged only when redactor is set
+    pub fn redactor(mut self, redactor: Arc<Redactor>) -> Self {
+        self.redactor = Some(redactor);
+        self
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl Interceptor for Logging {
+    #[inline]
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        let peer = RealIp::client(extensions).map(tracing::field::display);
+        match &self.redactor {
+            Some(redactor) => log!(self.level, peer, metadata = %redactor.display(headers), "grpc request"),
+            None => log!(self.level, peer, "grpc request"),
+        }
+        None
+    }
+
+    #[inline]
+    fn on_request_with_uri(&self, uri: &http::Uri, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        let peer = RealIp::client(extensions).map(tracing::field::display);
+        match &self.redactor {
+            Some(redactor) => log!(self.level, method = uri.path(), peer, metadata = %redactor.display(headers), "grpc request"),
+            None => log!(self.level, method = uri.path(), peer, "grpc request"),
+        }
+        None
+    }
+
+    #[inline]
+    fn on_response(&self, status: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+        log!(self.level, code = ?status, "grpc response");
+    }
+}
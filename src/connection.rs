@@ -0,0 +1,152 @@
+//! Connection-scoped interceptors
+//!
+//!`tonic` server clones service, layered via `Server::layer`, for every accepted connection, and then calls this clone for every stream of connection.
+//!`PerConnection` service creates new interceptor using `MakeInterceptor` whenever it is cloned, hence every connection gets own interceptor with its state:
+//!
+//!```rust
+//!use tonic_interceptor::InterceptorFn;
+//!use tonic_interceptor::connection::per_connection;
+//!
+//!use std::sync::Arc;
+//!use std::sync::atomic::{AtomicUsize, Ordering};
+//!
+//!let layer = per_connection(|| {
+//!    //Counter of requests on single connection
+//!    let requests = Arc::new(AtomicUsize::new(0));
+//!    InterceptorFn {
+//!        on_request: move |headers: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions| {
+//!            let requests = requests.fetch_add(1, Ordering::Relaxed) + 1;
+//!            headers.insert("x-connection-requests", requests.into());
+//!            None
+//!        },
+//!        on_response: |_, _: &mut http::HeaderMap, _: &http::Extensions| {},
+//!    }
+//!});
+//!//Pass `layer` to `Server::layer`
+//!# let _ = layer;
+//!```
+//!
+//!Note that service, which is cloned per request (e.g. behind `tower::buffer::Buffer`), gets interceptor per request instead.
+
+use crate::{Interceptor, InterceptorService, DefaultBody, PropagateError};
+
+use core::fmt;
+use core::task;
+
+///Factory of interceptors, creating one per connection
+pub trait MakeInterceptor {
+    ///Interceptor of single connection
+    type Interceptor: Interceptor;
+
+    ///Creates interceptor for new connection
+    fn make_interceptor(&self) -> Self::Interceptor;
+}
+
+impl<I: Interceptor, F: Fn() -> I> MakeInterceptor for F {
+    type Interceptor = I;
+
+    #[inline(always)]
+    fn make_interceptor(&self) -> Self::Interceptor {
+        (self)()
+    }
+}
+
+#[derive(Clone)]
+///Layer creating `PerConnection` services
+pub struct PerConnectionLayer<M> {
+    make: M,
+}
+
+impl<M> PerConnectionLayer<M> {
+    #[inline(always)]
+    ///Creates new instance
+    pub fn new(make: M) -> Self {
+        Self {
+            make,
+        }
+    }
+}
+
+impl<M> fmt::Debug for PerConnectionLayer<M> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("PerConnectionLayer").finish_non_exhaustive()
+    }
+}
+
+impl<S, M: MakeInterceptor + Clone> tower_layer::Layer<S> for PerConnectionLayer<M> {
+    type Service = PerConnection<M, S>;
+
+    #[inline(always)]
+    fn layer(&self, inner: S) -> Self::Service {
+        PerConnection {
+            service: InterceptorService::new(self.make.make_interceptor(), inner),
+            make: self.make.clone(),
+        }
+    }
+}
+
+///Service, which creates new interceptor whenever it is cloned
+///
+///Clone shares nothing with original service, but inner service and factory.
+pub struct PerConnection<M: MakeInterceptor, S> {
+    make: M,
+    service: InterceptorService<M::Interceptor, S>,
+}
+
+impl<M: MakeInterceptor, S> PerConnection<M, S> {
+    #[inline(always)]
+    ///Access interceptor of this service
+    pub fn interceptor(&self) -> &M::Interceptor {
+        &self.service.interceptor
+    }
+}
+
+impl<M: MakeInterceptor + Clone, S: Clone> Clone for PerConnection<M, S> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            make: self.make.clone(),
+            service: InterceptorService {
+                interceptor: self.make.make_interceptor(),
+                on_rejection: self.service.on_rejection,
+                capture: self.service.capture,
+                handler: PropagateError,
+                body: DefaultBody,
+                inner: self.service.inner.clone(),
+            },
+        }
+    }
+}
+
+impl<M: MakeInterceptor, S: fmt::Debug> fmt::Debug for PerConnection<M, S> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("PerConnection").field("inner", &self.service.inner).finish_non_exhaustive()
+    }
+}
+
+impl<M: MakeInterceptor, S: tonic::server::NamedService> tonic::server::NamedService for PerConnection<M, S> {
+    const NAME: &'static str = S::NAME;
+}
+
+impl<ReqBody, M: MakeInterceptor, S> tower_service::Service<http::Request<ReqBody>> for PerConnection<M, S> where M::Interceptor: Clone, InterceptorService<M::Interceptor, S>: tower_service::Service<http::Request<ReqBody>> {
+    type Response = <InterceptorService<M::Interceptor, S> as tower_service::Service<http::Request<ReqBody>>>::Response;
+    type Error = <InterceptorService<M::Interceptor, S> as tower_service::Service<http::Request<ReqBody>>>::Error;
+    type Future = <InterceptorService<M::Interceptor, S> as tower_service::Service<http::Request<ReqBody>>>::Future;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    #[inline(always)]
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        self.service.call(req)
+    }
+}
+
+#[inline(always)]
+///Creates layer, which creates interceptor per connection using `make`
+pub fn per_connection<M: MakeInterceptor>(make: M) -> PerConnectionLayer<M> {
+    PerConnectionLayer::new(make)
+}
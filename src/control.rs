@@ -0,0 +1,225 @@
+//! Runtime control of interceptors
+//!
+//!`Toggle` is kill switch, which turns wrapped interceptor off without redeploying:
+//!
+//!```rust
+//!use tonic_interceptor::InterceptorChain;
+//!use tonic_interceptor::control::Toggle;
+//!use tonic_interceptor::headers::EchoRequestHeader;
+//!
+//!const ECHO: EchoRequestHeader = EchoRequestHeader::new(&["x-request-id"]);
+//!
+//!let toggle = Toggle::new(ECHO);
+//!let handle = toggle.handle();
+//!//Later, when interceptor misbehaves
+//!handle.disable();
+//!
+//!//Within chain toggles are addressed by name
+//!let chain = InterceptorChain::new().with_toggle("echo", ECHO);
+//!let control = chain.control();
+//!assert!(control.disable("echo"));
+//!```
+//!
+//!Decision is sticky per request: request, which started enabled, gets its `on_response` even if toggle is disabled meanwhile, and vice versa.
+//!This relies on `ext::Carried`, hence outside of `InterceptorService` responses follow current state instead.
+
+use crate::{Interceptor, LazyMetadata};
+use crate::ext::Carried;
+
+use std::borrow::Cow;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[derive(Clone, Debug)]
+///Handle to enable or disable `Toggle`
+pub struct ToggleHandle {
+    state: Arc<AtomicBool>,
+}
+
+impl ToggleHandle {
+    #[inline(always)]
+    ///Enables interceptor for subsequent requests
+    pub fn enable(&self) {
+        self.state.store(true, Ordering::Release);
+    }
+
+    #[inline(always)]
+    ///Disables interceptor for subsequent requests
+    pub fn disable(&self) {
+        self.state.store(false, Ordering::Release);
+    }
+
+    #[inline(always)]
+    ///Returns whether interceptor is enabled
+    pub fn is_enabled(&self) -> bool {
+        self.state.load(Ordering::Acquire)
+    }
+
+    #[inline(always)]
+    fn id(&self) -> usize {
+        Arc::as_ptr(&self.state) as usize
+    }
+}
+
+//Decisions of toggles made for request, keyed by toggle's id
+#[derive(Clone)]
+struct Decisions(Vec<(usize, bool)>);
+
+#[derive(Clone, Debug)]
+///Interceptor, which skips wrapped interceptor entirely while disabled
+pub struct Toggle<I> {
+    handle: ToggleHandle,
+    inner: I,
+}
+
+impl<I: Interceptor> Toggle<I> {
+    #[inline]
+    ///Creates new instance, which is enabled
+    pub fn new(inner: I) -> Self {
+        Self {
+            handle: ToggleHandle {
+                state: Arc::new(AtomicBool::new(true)),
+            },
+            inner,
+        }
+    }
+
+    #[inline(always)]
+    ///Returns handle to control this instance
+    pub fn handle(&self) -> ToggleHandle {
+        self.handle.clone()
+    }
+
+    #[inline(always)]
+    ///Access wrapped interceptor
+    pub fn inner(&self) -> &I {
+        &self.inner
+    }
+
+    fn decide(&self, extensions: &mut http::Extensions) -> bool {
+        let enabled = self.handle.is_enabled();
+        let decision = (self.handle.id(), enabled);
+        let carried = Carried::of_request(extensions);
+        match carried.get_mut::<Decisions>() {
+            Some(decisions) => decisions.0.push(decision),
+            None => {
+                carried.insert(Decisions(vec![decision]));
+            },
+        }
+        enabled
+    }
+}
+
+impl<I: Interceptor> Interceptor for Toggle<I> {
+    #[inline]
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        match self.decide(extensions) {
+            true => self.inner.on_request(headers, extensions),
+            false => None,
+        }
+    }
+
+    #[inline]
+    fn on_request_with_uri(&self, uri: &http::Uri, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        match self.decide(extensions) {
+            true => self.inner.on_request_with_uri(uri, headers, extensions),
+            false => None,
+        }
+    }
+
+    #[inline]
+    fn on_request_lazy(&self, uri: &http::Uri, headers: &mut LazyMetadata<'_>, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        match self.decide(extensions) {
+            true => self.inner.on_request_lazy(uri, headers, extensions),
+            false => None,
+        }
+    }
+
+    #[inline]
+    fn on_request_headers(&self, uri: &http::Uri, headers: &mut http::HeaderMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        match self.decide(extensions) {
+            true => self.inner.on_request_headers(uri, headers, extensions),
+            false => None,
+        }
+    }
+
+    fn on_response(&self, status: tonic::Code, headers: &mut http::HeaderMap, extensions: &http::Extensions) {
+        let id = self.handle.id();
+        let decision = Carried::get_from::<Decisions>(extensions).and_then(|decisions| decisions.0.iter().rev().find(|(toggle, _)| *toggle == id));
+        let enabled = match decision {
+            Some((_, enabled)) => *enabled,
+            None => self.handle.is_enabled(),
+        };
+        if enabled {
+            self.inner.on_response(status, headers, extensions)
+        }
+    }
+
+    #[inline(always)]
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    #[cfg(feature = "diagnostics")]
+    #[inline]
+    fn on_poll_stats(&self, stats: crate::diagnostics::PollStats) {
+        if self.handle.is_enabled() {
+            self.inner.on_poll_stats(stats)
+        }
+    }
+}
+
+#[derive(Clone, Default, Debug)]
+///Toggles of `InterceptorChain`, addressed by name
+pub struct ChainControl {
+    toggles: Vec<(Cow<'static, str>, ToggleHandle)>,
+}
+
+impl ChainControl {
+    #[inline(always)]
+    ///Creates empty instance
+    pub const fn new() -> Self {
+        Self {
+            toggles: Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn register(&mut self, name: Cow<'static, str>, handle: ToggleHandle) {
+        self.toggles.push((name, handle));
+    }
+
+    #[inline]
+    ///Returns toggle with `name`
+    pub fn get(&self, name: &str) -> Option<&ToggleHandle> {
+        self.toggles.iter().find(|(toggle, _)| toggle == name).map(|(_, handle)| handle)
+    }
+
+    #[inline]
+    ///Returns names of toggles, in order of insertion
+    pub fn names(&self) -> impl Iterator<Item = &str> + '_ {
+        self.toggles.iter().map(|(name, _)| name.as_ref())
+    }
+
+    ///Enables every toggle with `name`, returning whether there is any
+    pub fn enable(&self, name: &str) -> bool {
+        self.toggles.iter().filter(|(toggle, _)| toggle == name).fold(false, |_, (_, handle)| {
+            handle.enable();
+            true
+        })
+    }
+
+    ///Disables every toggle with `name`, returning whether there is any
+    pub fn disable(&self, name: &str) -> bool {
+        self.toggles.iter().filter(|(toggle, _)| toggle == name).fold(false, |_, (_, handle)| {
+            handle.disable();
+            true
+        })
+    }
+
+    #[inline]
+    ///Returns whether toggle with `name` is enabled, if there is such toggle
+    pub fn is_enabled(&self, name: &str) -> Option<bool> {
+        self.get(name).map(ToggleHandle::is_enabled)
+    }
+}
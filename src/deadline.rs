@@ -0,0 +1,579 @@
+//! Call deadline
+//!
+//!Server side `InsertDeadline` makes deadline of incoming call available as `Deadline` extension.
+//!Handler can then propagate it to outgoing calls made on its behalf, either explicitly by inserting it into outgoing request's extensions,
+//!or by running its code within `DeadlineContext` so that `client::PropagateDeadline` can find it.
+//!
+//!`Clamp` bounds deadline of incoming call, rewriting its `grpc-timeout` before tonic sees it.
+//!
+//!`TaskLocalDeadline` additionally runs handler within `DeadlineContext`, so that `remaining` can be called anywhere in handler's task.
+//!
+//!`PerMethodTimeout` imposes server side timeout on each method, regardless of what client asked for:
+//!
+//!```rust
+//!use tonic_interceptor::deadline::PerMethodTimeout;
+//!
+//!use core::time::Duration;
+//!
+//!let timeout = PerMethodTimeout::new(Duration::from_secs(30)).method("/pkg.Search/Query", Duration::from_secs(2));
+//!//Pass `timeout.layer()` to `Server::layer`
+//!# let _ = timeout.layer();
+//!```
+
+use crate::{timeout, Interceptor, InterceptorService};
+use crate::matcher::MethodMatcher;
+use crate::timer::{BoxFuture, Clock, Instant, StdClock, Timer, TokioTimer};
+
+use core::task;
+use core::pin::Pin;
+use core::future::Future;
+use core::convert::TryFrom;
+use core::time::Duration;
+
+tokio::task_local! {
+    static CURRENT: Deadline;
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+///Point in time by which call must complete
+pub struct Deadline(pub Instant);
+
+impl Deadline {
+    #[inline]
+    ///Creates deadline which expires after `timeout` from now
+    pub fn after(timeout: Duration) -> Self {
+        Self::after_with(&StdClock, timeout)
+    }
+
+    #[inline]
+    ///Creates deadline which expires after `timeout` from now, as told by `clock`
+    pub fn after_with<C: Clock + ?Sized>(clock: &C, timeout: Duration) -> Self {
+        Self(clock.now() + timeout)
+    }
+
+    #[inline]
+    ///Creates deadline from `grpc-timeout` header, if present and valid.
+    pub fn from_headers(headers: &http::HeaderMap) -> Option<Self> {
+        timeout::from_headers(headers).map(Self::after)
+    }
+
+    #[inline]
+    ///Creates deadline from `grpc-timeout` metadata, if present and valid.
+    pub fn from_metadata(metadata: &tonic::metadata::MetadataMap) -> Option<Self> {
+        metadata.get(timeout::GRPC_TIMEOUT).and_then(|value| timeout::parse(value.as_bytes())).map(Self::after)
+    }
+
+    #[inline]
+    ///Returns time remaining until deadline, which is zero if deadline is already expired
+    pub fn remaining(&self) -> Duration {
+        self.remaining_with(&StdClock)
+    }
+
+    #[inline]
+    ///Returns time remaining until deadline, as told by `clock`
+    pub fn remaining_with<C: Clock + ?Sized>(&self, clock: &C) -> Duration {
+        self.0.saturating_duration_since(clock.now())
+    }
+
+    #[inline]
+    ///Returns whether deadline is already expired
+    pub fn is_expired(&self) -> bool {
+        self.remaining().is_zero()
+    }
+}
+
+///Deadline context of current task
+///
+///Makes deadline available to any code running within its scope.
+pub struct DeadlineContext;
+
+impl DeadlineContext {
+    #[inline(always)]
+    ///Runs `fut` with `deadline` as current deadline
+    pub fn scope<F: Future>(deadline: Deadline, fut: F) -> tokio::task::futures::TaskLocalFuture<Deadline, F> {
+        CURRENT.scope(deadline, fut)
+    }
+
+    #[inline(always)]
+    ///Runs `fun` with `deadline` as current deadline
+    pub fn sync_scope<R, F: FnOnce() -> R>(deadline: Deadline, fun: F) -> R {
+        CURRENT.sync_scope(deadline, fun)
+    }
+
+    #[inline]
+    ///Returns current deadline, if any
+    pub fn current() -> Option<Deadline> {
+        CURRENT.try_with(|deadline| *deadline).ok()
+    }
+}
+
+#[inline]
+///Returns time remaining until deadline of current task, if any
+///
+///Deadline is available within `DeadlineContext`, which is set by `TaskLocalDeadline` for handler's task.
+///Time is measured by `StdClock`, use `remaining_with` for other clocks.
+pub fn remaining() -> Option<Duration> {
+    DeadlineContext::current().map(|deadline| deadline.remaining())
+}
+
+#[inline]
+///Returns time remaining until deadline of current task as told by `clock`, if any
+pub fn remaining_with<C: Clock + ?Sized>(clock: &C) -> Option<Duration> {
+    DeadlineContext::current().map(|deadline| deadline.remaining_with(clock))
+}
+
+#[derive(Copy, Clone, Default, Debug)]
+///Server interceptor which inserts `Deadline` extension from `grpc-timeout` header of incoming request.
+///
+///Deadline is measured by `Clock`, which is `StdClock` by default.
+pub struct InsertDeadline<C = StdClock> {
+    clock: C,
+}
+
+impl InsertDeadline {
+    #[inline(always)]
+    ///Creates new instance
+    pub const fn new() -> Self {
+        Self {
+            clock: StdClock,
+        }
+    }
+}
+
+impl<C> InsertDeadline<C> {
+    #[inline(always)]
+    ///Sets clock, which measures deadline
+    pub fn clock<C2: Clock>(self, clock: C2) -> InsertDeadline<C2> {
+        InsertDeadline {
+            clock,
+        }
+    }
+}
+
+impl<C: Clock> Interceptor for InsertDeadline<C> {
+    #[inline]
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        if let Some(timeout) = headers.get(timeout::GRPC_TIMEOUT).and_then(|value| timeout::parse(value.as_bytes())) {
+            extensions.insert(Deadline::after_with(&self.clock, timeout));
+        }
+        None
+    }
+
+    #[inline(always)]
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+///Original `grpc-timeout` of request, which was rewritten by `Clamp`
+///
+///It is `None` if request had no timeout or its value was invalid.
+pub struct OriginalTimeout(pub Option<Duration>);
+
+#[derive(Copy, Clone, Debug)]
+///Server interceptor which clamps `grpc-timeout` of incoming request.
+///
+///Timeout above maximum is replaced with maximum, while missing or invalid timeout is replaced with default.
+///Acceptable values are left byte-identical.
+///When value is rewritten, original timeout is inserted as `OriginalTimeout` extension.
+///
+///Place it before `InsertDeadline` so that deadline is derived from clamped timeout.
+pub struct Clamp {
+    max: Duration,
+    default: Duration,
+}
+
+impl Clamp {
+    #[inline]
+    ///Creates new instance, with `default` being capped by `max`
+    pub fn new(max: Duration, default: Duration) -> Self {
+        Self {
+            max,
+            default: default.min(max),
+        }
+    }
+
+    //Returns replacement of `value`, if it is not acceptable
+    fn replacement(&self, value: Option<&[u8]>) -> Option<(http::HeaderValue, OriginalTimeout)> {
+        match value.and_then(timeout::parse) {
+            Some(original) if original <= self.max => None,
+            Some(original) => Some((timeout::encode(self.max), OriginalTimeout(Some(original)))),
+            None => Some((timeout::encode(self.default), OriginalTimeout(None))),
+        }
+    }
+}
+
+impl Interceptor for Clamp {
+    #[inline]
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        let value = headers.get(timeout::GRPC_TIMEOUT).map(|value| value.as_bytes());
+        if let Some((value, original)) = self.replacement(value) {
+            let value = tonic::metadata::AsciiMetadataValue::try_from(value.as_bytes()).expect("valid grpc-timeout");
+            headers.insert(timeout::GRPC_TIMEOUT, value);
+            extensions.insert(original);
+        }
+        None
+    }
+
+    #[inline]
+    fn on_request_headers(&self, _: &http::Uri, headers: &mut http::HeaderMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        if let Some((value, original)) = self.replacement(headers.get(timeout::GRPC_TIMEOUT).map(http::HeaderValue::as_bytes)) {
+            headers.insert(timeout::GRPC_TIMEOUT, value);
+            extensions.insert(original);
+        }
+        None
+    }
+
+    #[inline(always)]
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+///Timeout of request, imposed by `PerMethodTimeout`
+pub struct MethodTimeout {
+    ///Timeout configured for method
+    pub limit: Duration,
+    ///Timeout requested by client via `grpc-timeout`, if any
+    pub client: Option<Duration>,
+    ///Deadline of call, which is the earliest of both timeouts
+    pub deadline: Deadline,
+}
+
+impl MethodTimeout {
+    #[inline]
+    ///Returns effective timeout, which is the lesser of both timeouts
+    pub fn effective(&self) -> Duration {
+        match self.client {
+            Some(client) => client.min(self.limit),
+            None => self.limit,
+        }
+    }
+
+    ///Returns status of call, which exceeded its deadline
+    pub fn status(&self) -> tonic::Status {
+        match self.client {
+            Some(client) if client < self.limit => tonic::Status::deadline_exceeded(format!("client deadline of {:?} exceeded (method timeout is {:?})", client, self.limit)),
+            _ => tonic::Status::deadline_exceeded(format!("method timeout of {:?} exceeded", self.limit)),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+///Server interceptor which imposes timeout on each method
+///
+///Timeout is taken from the most specific `MethodMatcher`, falling back to default one.
+///Effective timeout of call is the lesser of method's timeout and client's `grpc-timeout`:
+///`grpc-timeout` is rewritten to it, while `MethodTimeout` and `Deadline` are inserted into request extensions.
+///
+///Interceptor alone only describes timeout, use `layer` to enforce it, responding with `DEADLINE_EXCEEDED` once it expires.
+///Expiration is measured by `Timer`, which is tokio's one by default.
+pub struct PerMethodTimeout<T = TokioTimer> {
+    methods: Vec<(MethodMatcher, Duration)>,
+    default: Duration,
+    timer: T,
+}
+
+impl PerMethodTimeout {
+    #[inline]
+    ///Creates new instance with `default` timeout for every method
+    pub fn new(default: Duration) -> Self {
+        Self {
+            methods: Vec::new(),
+            default,
+            timer: TokioTimer,
+        }
+    }
+}
+
+impl<T> PerMethodTimeout<T> {
+    #[inline]
+    ///Sets timer, which enforces timeout
+    pub fn timer<T2: Timer>(self, timer: T2) -> PerMethodTimeout<T2> {
+        PerMethodTimeout {
+            methods: self.methods,
+            default: self.default,
+            timer,
+        }
+    }
+
+    #[inline]
+    ///Sets `timeout` of methods matched by `matcher`
+    ///
+    ///When several matchers have the same specificity, the first one wins.
+    pub fn method(mut self, matcher: impl Into<MethodMatcher>, timeout: Duration) -> Self {
+        self.methods.push((matcher.into(), timeout));
+        self
+    }
+
+    ///Returns timeout configured for method `path`
+    pub fn timeout_for(&self, path: &str) -> Duration {
+        let mut result: Option<&(MethodMatcher, Duration)> = None;
+        for method in self.methods.iter().filter(|(matcher, _)| matcher.matches(path)) {
+            if result.is_none_or(|(current, _)| method.0.specificity() > current.specificity()) {
+                result = Some(method);
+            }
+        }
+        result.map(|(_, timeout)| *timeout).unwrap_or(self.default)
+    }
+
+    #[inline]
+    ///Returns layer, which enforces timeout
+    pub fn layer(&self) -> PerMethodTimeoutLayer<T> where T: Clone {
+        PerMethodTimeoutLayer {
+            timeout: self.clone(),
+        }
+    }
+}
+
+impl<T: Clock> Interceptor for PerMethodTimeout<T> {
+    #[inline]
+    fn on_request(&self, _: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+        None
+    }
+
+    fn on_request_headers(&self, uri: &http::Uri, headers: &mut http::HeaderMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        let limit = self.timeout_for(uri.path());
+        let client = timeout::from_headers(headers);
+        let effective = match client {
+            Some(client) if client <= limit => client,
+            _ => {
+                headers.insert(timeout::GRPC_TIMEOUT, timeout::encode(limit));
+                limit
+            },
+        };
+
+        let deadline = Deadline::after_with(&self.timer, effective);
+        extensions.insert(deadline);
+        extensions.insert(MethodTimeout {
+            limit,
+            client,
+            deadline,
+        });
+        None
+    }
+
+    #[inline(always)]
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
+
+#[derive(Clone, Debug)]
+///Layer of `PerMethodTimeout`, enforcing timeout of each call
+pub struct PerMethodTimeoutLayer<T = TokioTimer> {
+    timeout: PerMethodTimeout<T>,
+}
+
+impl<S, T: Clone> tower_layer::Layer<S> for PerMethodTimeoutLayer<T> {
+    type Service = InterceptorService<PerMethodTimeout<T>, Enforce<S, T>>;
+
+    #[inline(always)]
+    fn layer(&self, inner: S) -> Self::Service {
+        InterceptorService::new(self.timeout.clone(), Enforce {
+            inner,
+            timer: self.timeout.timer.clone(),
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+///Service racing inner service's future against `MethodTimeout` of request
+///
+///Once effective timeout elapses, `DEADLINE_EXCEEDED` response is returned without waiting for inner future.
+///Requests without `MethodTimeout` are passed through as they are.
+pub struct Enforce<S, T = TokioTimer> {
+    inner: S,
+    timer: T,
+}
+
+impl<S: tonic::server::NamedService, T> tonic::server::NamedService for Enforce<S, T> {
+    const NAME: &'static str = S::NAME;
+}
+
+impl<ReqBody, ResBody: crate::EmptyBody, S: tower_service::Service<http::Request<ReqBody>, Response = http::Response<ResBody>>, T: Timer> tower_service::Service<http::Request<ReqBody>> for Enforce<S, T> {
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = EnforceFut<S::Future>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    #[inline]
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        let timeout = req.extensions().get::<MethodTimeout>().copied();
+        EnforceFut {
+            timeout: timeout.map(|timeout| (timeout, self.timer.sleep(timeout.effective()))),
+            inner: self.inner.call(req),
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    ///Future of `Enforce`
+    pub struct EnforceFut<F> {
+        timeout: Option<(MethodTimeout, BoxFuture<()>)>,
+        #[pin]
+        inner: F,
+    }
+}
+
+impl<F> core::fmt::Debug for EnforceFut<F> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("EnforceFut").field("timeout", &self.timeout.as_ref().map(|(timeout, _)| timeout)).finish_non_exhaustive()
+    }
+}
+
+impl<ResBody: crate::EmptyBody, E, F: Future<Output = Result<http::Response<ResBody>, E>>> Future for EnforceFut<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let this = self.project();
+        if let task::Poll::Ready(result) = Future::poll(this.inner, ctx) {
+            return task::Poll::Ready(result);
+        }
+
+        match this.timeout {
+            Some((timeout, sleep)) => match Future::poll(sleep.as_mut(), ctx) {
+                task::Poll::Ready(()) => {
+                    task::Poll::Ready(Ok(crate::response::status_to_response(&timeout.status(), &crate::response::ResponseTemplate::new())))
+                },
+                task::Poll::Pending => task::Poll::Pending,
+            },
+            None => task::Poll::Pending,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Default, Debug)]
+///Server interceptor which inserts `Deadline` extension from `grpc-timeout` header of incoming request, same as `InsertDeadline`
+///
+///Use `layer` to also run inner service within `DeadlineContext` of the deadline, making it available via `remaining`.
+///Note that task-local is not inherited by spawned tasks, use `DeadlineContext::scope` to carry it over explicitly.
+pub struct TaskLocalDeadline<C = StdClock> {
+    insert: InsertDeadline<C>,
+}
+
+impl TaskLocalDeadline {
+    #[inline(always)]
+    ///Creates new instance
+    pub const fn new() -> Self {
+        Self {
+            insert: InsertDeadline::new(),
+        }
+    }
+}
+
+impl<C> TaskLocalDeadline<C> {
+    #[inline(always)]
+    ///Sets clock, which measures deadline
+    pub fn clock<C2: Clock>(self, clock: C2) -> TaskLocalDeadline<C2> {
+        TaskLocalDeadline {
+            insert: self.insert.clock(clock),
+        }
+    }
+
+    #[inline(always)]
+    ///Returns layer, which sets task-local deadline
+    pub fn layer(&self) -> TaskLocalDeadlineLayer<C> where C: Clone {
+        TaskLocalDeadlineLayer {
+            interceptor: self.clone(),
+        }
+    }
+}
+
+impl<C: Clock> Interceptor for TaskLocalDeadline<C> {
+    #[inline]
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        self.insert.on_request(headers, extensions)
+    }
+
+    #[inline(always)]
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
+
+#[derive(Copy, Clone, Default, Debug)]
+///Layer of `TaskLocalDeadline`
+pub struct TaskLocalDeadlineLayer<C = StdClock> {
+    interceptor: TaskLocalDeadline<C>,
+}
+
+impl<S, C: Clone> tower_layer::Layer<S> for TaskLocalDeadlineLayer<C> {
+    type Service = InterceptorService<TaskLocalDeadline<C>, ScopeDeadline<S>>;
+
+    #[inline(always)]
+    fn layer(&self, inner: S) -> Self::Service {
+        InterceptorService::new(self.interceptor.clone(), ScopeDeadline {
+            inner,
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+///Service running inner service within `DeadlineContext` of request's `Deadline`
+///
+///Requests without `Deadline` are passed through as they are.
+pub struct ScopeDeadline<S> {
+    inner: S,
+}
+
+impl<S: tonic::server::NamedService> tonic::server::NamedService for ScopeDeadline<S> {
+    const NAME: &'static str = S::NAME;
+}
+
+impl<ReqBody, S: tower_service::Service<http::Request<ReqBody>>> tower_service::Service<http::Request<ReqBody>> for ScopeDeadline<S> {
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = ScopeDeadlineFut<S::Future>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    #[inline]
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        let deadline = req.extensions().get::<Deadline>().copied();
+        //Handler may use task-local during creation of its future too
+        let inner = match deadline {
+            Some(deadline) => DeadlineContext::sync_scope(deadline, || self.inner.call(req)),
+            None => self.inner.call(req),
+        };
+        ScopeDeadlineFut {
+            deadline,
+            inner,
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    ///Future of `ScopeDeadline`
+    pub struct ScopeDeadlineFut<F> {
+        deadline: Option<Deadline>,
+        #[pin]
+        inner: F,
+    }
+}
+
+impl<F> core::fmt::Debug for ScopeDeadlineFut<F> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("ScopeDeadlineFut").field("deadline", &self.deadline).finish_non_exhaustive()
+    }
+}
+
+impl<F: Future> Future for ScopeDeadlineFut<F> {
+    type Output = F::Output;
+
+    #[inline]
+    fn poll(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let this = self.project();
+        match this.deadline {
+            Some(deadline) => DeadlineContext::sync_scope(*deadline, || Future::poll(this.inner, ctx)),
+            None => Future::poll(this.inner, ctx),
+        }
+    }
+}
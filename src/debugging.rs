@@ -0,0 +1,712 @@
+//! Debugging of interceptors
+//!
+//!`DiffLogger` reports which metadata wrapped interceptor adds, removes or changes:
+//!
+//!```rust
+//!use tonic_interceptor::debugging::DiffLogger;
+//!use tonic_interceptor::redact::Redactor;
+//!use tonic_interceptor::OnResponse;
+//!
+//!let logger = DiffLogger::new(OnResponse(|_, _: &mut http::HeaderMap, _: &http::Extensions| ()), |diff| eprintln!("{}", diff))
+//!    .redactor(Redactor::new().key("authorization"))
+//!    .activate_on("x-debug-diff");
+//!//Enable for every request at runtime
+//!logger.toggle().enable();
+//!```
+//!
+//!Snapshots are taken only for requests which carry activation metadata, or while toggle is enabled.
+//!Otherwise wrapped interceptor is called as it is.
+//!
+//!`PayloadLog` is a layer, which captures truncated request and response bodies of sampled or activated calls.
+
+use crate::{Interceptor, LazyMetadata};
+use crate::ext::Carried;
+use crate::matcher::MethodMatcher;
+use crate::redact::Redactor;
+use crate::compat;
+
+use core::fmt;
+use core::task;
+use core::pin::Pin;
+use core::future::Future;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use bytes::{Bytes, BytesMut};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+///Phase of call, in which metadata is changed
+pub enum Phase {
+    ///Request metadata, changed by `on_request`
+    Request,
+    ///Response headers, changed by `on_response`
+    Response,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+///Changes of metadata made by interceptor
+///
+///Values are redacted. Binary values are base64 encoded, as they are sent.
+///Multiple values of the same key are compared in order.
+pub struct MetadataDiff {
+    ///Name of interceptor
+    pub interceptor: &'static str,
+    ///Phase of call
+    pub phase: Phase,
+    ///Method path, empty if unknown
+    pub method: String,
+    ///Added entries
+    pub added: Vec<(String, String)>,
+    ///Removed entries
+    pub removed: Vec<(String, String)>,
+    ///Changed entries, as key with values before and after
+    pub changed: Vec<(String, String, String)>,
+}
+
+impl MetadataDiff {
+    #[inline]
+    ///Returns whether metadata is left intact
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl fmt::Display for MetadataDiff {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let phase = match self.phase {
+            Phase::Request => "request",
+            Phase::Response => "response",
+        };
+        write!(fmt, "{} {} {}:", self.interceptor, phase, self.method)?;
+        for (key, value) in self.added.iter() {
+            write!(fmt, " +{}: {};", key, value)?;
+        }
+        for (key, value) in self.removed.iter() {
+            write!(fmt, " -{}: {};", key, value)?;
+        }
+        for (key, before, after) in self.changed.iter() {
+            write!(fmt, " ~{}: {} => {};", key, before, after)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+///Handle to enable `DiffLogger` for every request
+pub struct DiffToggle {
+    enabled: Arc<AtomicBool>,
+}
+
+impl DiffToggle {
+    #[inline(always)]
+    ///Enables snapshots of every request
+    pub fn enable(&self) {
+        self.enabled.store(true, Ordering::Release);
+    }
+
+    #[inline(always)]
+    ///Disables snapshots, unless request carries activation metadata
+    pub fn disable(&self) {
+        self.enabled.store(false, Ordering::Release);
+    }
+
+    #[inline(always)]
+    ///Returns whether snapshots of every request are enabled
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Acquire)
+    }
+}
+
+//Marker of call with snapshots enabled, carried to `on_response`
+#[derive(Clone)]
+struct Active {
+    method: String,
+}
+
+//Raw entries of metadata in order
+type Snapshot = Vec<(String, Vec<u8>)>;
+
+fn snapshot_headers(headers: &http::HeaderMap) -> Snapshot {
+    headers.iter().map(|(key, value)| (key.as_str().to_owned(), value.as_bytes().to_vec())).collect()
+}
+
+fn snapshot_metadata(metadata: &tonic::metadata::MetadataMap) -> Snapshot {
+    metadata.iter().map(|entry| match entry {
+        tonic::metadata::KeyAndValueRef::Ascii(key, value) => (key.as_str().to_owned(), value.as_encoded_bytes().to_vec()),
+        tonic::metadata::KeyAndValueRef::Binary(key, value) => (key.as_str().to_owned(), value.as_encoded_bytes().to_vec()),
+    }).collect()
+}
+
+type DiffCallback = dyn Fn(&MetadataDiff) + Send + Sync;
+
+///Interceptor, which reports changes of metadata made by wrapped interceptor
+///
+///Metadata is compared before and after `on_request` and `on_response` of wrapped interceptor,
+///and non-empty difference is passed to callback, along with name of interceptor.
+///Values are compared as they are, but reported redacted by `Redactor`, which masks `authorization` unless configured otherwise.
+///
+///Comparison is done only when request carries activation metadata (see `activate_on`), or toggle is enabled.
+pub struct DiffLogger<I> {
+    inner: I,
+    callback: Arc<DiffCallback>,
+    redactor: Arc<Redactor>,
+    activation: Option<String>,
+    toggle: DiffToggle,
+}
+
+impl<I: Interceptor> DiffLogger<I> {
+    #[inline]
+    ///Creates new instance, reporting changes made by `inner` to `callback`
+    pub fn new<F: Fn(&MetadataDiff) + Send + Sync + 'static>(inner: I, callback: F) -> Self {
+        Self {
+            inner,
+            callback: Arc::new(callback),
+            redactor: Arc::new(Redactor::new().key("authorization")),
+            activation: None,
+            toggle: DiffToggle::default(),
+        }
+    }
+
+    #[inline]
+    ///Sets redactor of reported values
+    pub fn redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = Arc::new(redactor);
+        self
+    }
+
+    #[inline]
+    ///Enables comparison for requests carrying metadata `key`, regardless of its value
+    pub fn activate_on(mut self, key: impl Into<String>) -> Self {
+        self.activation = Some(key.into().to_ascii_lowercase());
+        self
+    }
+
+    #[inline]
+    ///Returns toggle, enabling comparison for every request
+    ///
+    ///Toggle is shared by clones of logger.
+    pub fn toggle(&self) -> DiffToggle {
+        self.toggle.clone()
+    }
+
+    #[inline(always)]
+    ///Access wrapped interceptor
+    pub fn inner(&self) -> &I {
+        &self.inner
+    }
+
+    //Returns whether request is compared, marking it for `on_response`
+    fn activate<F: FnOnce(&str) -> bool>(&self, contains_key: F, method: &str, extensions: &mut http::Extensions) -> bool {
+        let is_active = self.toggle.is_enabled() || self.activation.as_deref().is_some_and(contains_key);
+        if is_active {
+            Carried::of_request(extensions).insert(Active {
+                method: method.to_owned(),
+            });
+        }
+        is_active
+    }
+
+    fn report(&self, phase: Phase, method: &str, before: Snapshot, after: Snapshot) {
+        let mut diff = MetadataDiff {
+            interceptor: self.inner.name(),
+            phase,
+            method: method.to_owned(),
+            added: Vec::new(),
+            removed: Vec::new(),
+            changed: Vec::new(),
+        };
+
+        let mut keys: Vec<&str> = Vec::new();
+        for (key, _) in before.iter().chain(after.iter()) {
+            if !keys.contains(&key.as_str()) {
+                keys.push(key);
+            }
+        }
+        for key in keys {
+            let redact = |value: &[u8]| self.redactor.redact(key, &String::from_utf8_lossy(value)).into_owned();
+            let mut before = before.iter().filter(|(name, _)| name == key).map(|(_, value)| value);
+            let mut after = after.iter().filter(|(name, _)| name == key).map(|(_, value)| value);
+            loop {
+                match (before.next(), after.next()) {
+                    (Some(before), Some(after)) => if before != after {
+                        diff.changed.push((key.to_owned(), redact(before), redact(after)));
+                    },
+                    (Some(before), None) => diff.removed.push((key.to_owned(), redact(before))),
+                    (None, Some(after)) => diff.added.push((key.to_owned(), redact(after))),
+                    (None, None) => break,
+                }
+            }
+        }
+
+        if !diff.is_empty() {
+            (self.callback)(&diff);
+        }
+    }
+}
+
+impl<I: Clone> Clone for DiffLogger<I> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            callback: self.callback.clone(),
+            redactor: self.redactor.clone(),
+            activation: self.activation.clone(),
+            toggle: self.toggle.clone(),
+        }
+    }
+}
+
+impl<I: fmt::Debug> fmt::Debug for DiffLogger<I> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("DiffLogger").field("inner", &self.inner).field("activation", &self.activation).field("toggle", &self.toggle.is_enabled()).finish_non_exhaustive()
+    }
+}
+
+impl<I: Interceptor> Interceptor for DiffLogger<I> {
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        if !self.activate(|key| headers.contains_key(key), "", extensions) {
+            return self.inner.on_request(headers, extensions);
+        }
+        let before = snapshot_metadata(headers);
+        let status = self.inner.on_request(headers, extensions);
+        self.report(Phase::Request, "", before, snapshot_metadata(headers));
+        status
+    }
+
+    fn on_request_with_uri(&self, uri: &http::Uri, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        if !self.activate(|key| headers.contains_key(key), uri.path(), extensions) {
+            return self.inner.on_request_with_uri(uri, headers, extensions);
+        }
+        let before = snapshot_metadata(headers);
+        let status = self.inner.on_request_with_uri(uri, headers, extensions);
+        self.report(Phase::Request, uri.path(), before, snapshot_metadata(headers));
+        status
+    }
+
+    fn on_request_lazy(&self, uri: &http::Uri, headers: &mut LazyMetadata<'_>, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        if !self.activate(|key| headers.contains_key(key), uri.path(), extensions) {
+            return self.inner.on_request_lazy(uri, headers, extensions);
+        }
+        let before = snapshot_headers(headers.headers());
+        let status = self.inner.on_request_lazy(uri, headers, extensions);
+        self.report(Phase::Request, uri.path(), before, snapshot_headers(headers.headers()));
+        status
+    }
+
+    fn on_request_headers(&self, uri: &http::Uri, headers: &mut http::HeaderMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        if !self.activate(|key| headers.contains_key(key), uri.path(), extensions) {
+            return self.inner.on_request_headers(uri, headers, extensions);
+        }
+        let before = snapshot_headers(headers);
+        let status = self.inner.on_request_headers(uri, headers, extensions);
+        self.report(Phase::Request, uri.path(), before, snapshot_headers(headers));
+        status
+    }
+
+    fn on_response(&self, status: tonic::Code, headers: &mut http::HeaderMap, extensions: &http::Extensions) {
+        let method = match Carried::get_from::<Active>(extensions) {
+            Some(active) => active.method.as_str(),
+            None if self.toggle.is_enabled() => "",
+            None => return self.inner.on_response(status, headers, extensions),
+        };
+        let before = snapshot_headers(headers);
+        self.inner.on_response(status, headers, extensions);
+        self.report(Phase::Response, method, before, snapshot_headers(headers));
+    }
+
+    #[inline(always)]
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    #[cfg(feature = "diagnostics")]
+    #[inline(always)]
+    fn on_poll_stats(&self, stats: crate::diagnostics::PollStats) {
+        self.inner.on_poll_stats(stats)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+///Direction of payload
+pub enum Direction {
+    ///Request body, sent by client
+    Request,
+    ///Response body, sent by server
+    Response,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+///Payload of call in one direction, captured by `PayloadLog`
+pub struct Payload {
+    ///Method path
+    pub method: String,
+    ///Direction of payload
+    pub direction: Direction,
+    ///Captured bytes of body, including gRPC framing
+    pub bytes: Bytes,
+    ///Whether body is longer than captured bytes
+    pub truncated: bool,
+}
+
+impl Payload {
+    ///Returns captured bytes, encoded as lowercase hex
+    pub fn hex(&self) -> String {
+        const HEX: &[u8; 16] = b"0123456789abcdef";
+
+        let mut result = String::with_capacity(self.bytes.len() * 2);
+        for byte in self.bytes.iter() {
+            result.push(HEX[(byte >> 4) as usize] as char);
+            result.push(HEX[(byte & 0xf) as usize] as char);
+        }
+        result
+    }
+
+    #[inline]
+    ///Returns captured bytes, encoded as padded base64
+    pub fn base64(&self) -> String {
+        crate::base64(&self.bytes)
+    }
+}
+
+impl fmt::Display for Payload {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let direction = match self.direction {
+            Direction::Request => "request",
+            Direction::Response => "response",
+        };
+        write!(fmt, "{} {} payload: {}", self.method, direction, self.hex())?;
+        if self.truncated {
+            fmt.write_str("...")?;
+        }
+        Ok(())
+    }
+}
+
+type PayloadSink = dyn Fn(&Payload) + Send + Sync;
+
+#[derive(Clone)]
+///Layer logging payload of selected calls
+///
+///Call is logged when:
+///
+///1. Its method is not marked sensitive, which overrides everything else;
+///2. Request carries activation metadata (`x-debug-payload` by default), regardless of its value;
+///3. Otherwise, when method passes allow and deny lists, and call is sampled.
+///
+///Up to `max_bytes` (1KiB by default) of each direction is captured and passed to sink,
+///once body is complete or dropped.
+///No call is sampled by default, hence only activated calls are logged.
+pub struct PayloadLog {
+    sink: Arc<PayloadSink>,
+    max_bytes: usize,
+    activation: Option<http::header::HeaderName>,
+    percent: u8,
+    counter: Arc<AtomicU64>,
+    allow: Vec<MethodMatcher>,
+    deny: Vec<MethodMatcher>,
+    sensitive: Vec<MethodMatcher>,
+}
+
+impl PayloadLog {
+    #[inline]
+    ///Creates new instance, passing captured payloads to `sink`
+    pub fn new<F: Fn(&Payload) + Send + Sync + 'static>(sink: F) -> Self {
+        Self {
+            sink: Arc::new(sink),
+            max_bytes: 1024,
+            activation: Some(http::header::HeaderName::from_static("x-debug-payload")),
+            percent: 0,
+            counter: Arc::new(AtomicU64::new(0)),
+            allow: Vec::new(),
+            deny: Vec::new(),
+            sensitive: Vec::new(),
+        }
+    }
+
+    #[inline(always)]
+    ///Sets maximum number of captured bytes per direction
+    pub fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    #[inline]
+    ///Sets activation metadata, or disables activation if `None`
+    pub fn activate_on(mut self, activation: Option<http::header::HeaderName>) -> Self {
+        self.activation = activation;
+        self
+    }
+
+    #[inline]
+    ///Sets `percent` of calls to sample
+    ///
+    ///Percentage above 100 is treated as 100.
+    ///Calls are sampled evenly, e.g. with 25% every fourth call is logged.
+    pub fn sample(mut self, percent: u8) -> Self {
+        self.percent = percent.min(100);
+        self
+    }
+
+    #[inline]
+    ///Restricts sampling to methods matched by `matcher`, in addition to already added ones
+    pub fn allow(mut self, matcher: impl Into<MethodMatcher>) -> Self {
+        self.allow.push(matcher.into());
+        self
+    }
+
+    #[inline]
+    ///Excludes methods matched by `matcher` from sampling
+    pub fn deny(mut self, matcher: impl Into<MethodMatcher>) -> Self {
+        self.deny.push(matcher.into());
+        self
+    }
+
+    #[inline]
+    ///Marks methods matched by `matcher` as sensitive, which payload is never logged
+    pub fn sensitive(mut self, matcher: impl Into<MethodMatcher>) -> Self {
+        self.sensitive.push(matcher.into());
+        self
+    }
+
+    #[inline]
+    ///Returns layer
+    pub fn layer(&self) -> PayloadLogLayer {
+        PayloadLogLayer {
+            log: self.clone(),
+        }
+    }
+
+    ///Returns whether call of method `path` with `headers` is logged
+    pub fn is_logged(&self, path: &str, headers: &http::HeaderMap) -> bool {
+        if self.sensitive.iter().any(|matcher| matcher.matches(path)) {
+            return false;
+        }
+        if self.activation.as_ref().is_some_and(|activation| headers.contains_key(activation)) {
+            return true;
+        }
+        if (!self.allow.is_empty() && !self.allow.iter().any(|matcher| matcher.matches(path))) || self.deny.iter().any(|matcher| matcher.matches(path)) {
+            return false;
+        }
+
+        match self.percent {
+            0 => false,
+            100 => true,
+            percent => {
+                let percent = u64::from(percent);
+                let count = self.counter.fetch_add(1, Ordering::Relaxed);
+                count.wrapping_mul(percent) / 100 != count.wrapping_add(1).wrapping_mul(percent) / 100
+            }
+        }
+    }
+
+    fn capture(&self, method: &str, direction: Direction) -> Capture {
+        Capture {
+            sink: self.sink.clone(),
+            method: method.to_owned(),
+            direction,
+            buffer: BytesMut::new(),
+            limit: self.max_bytes,
+            truncated: false,
+        }
+    }
+}
+
+impl fmt::Debug for PayloadLog {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("PayloadLog").field("max_bytes", &self.max_bytes).field("activation", &self.activation).field("percent", &self.percent)
+                                      .field("allow", &self.allow).field("deny", &self.deny).field("sensitive", &self.sensitive).finish_non_exhaustive()
+    }
+}
+
+//Bytes of body being captured, passed to sink once complete or dropped
+struct Capture {
+    sink: Arc<PayloadSink>,
+    method: String,
+    direction: Direction,
+    buffer: BytesMut,
+    limit: usize,
+    truncated: bool,
+}
+
+impl Capture {
+    #[inline]
+    fn push(&mut self, data: &[u8]) {
+        let available = self.limit - self.buffer.len();
+        if data.len() > available {
+            self.truncated = true;
+        }
+        self.buffer.extend_from_slice(&data[..data.len().min(available)]);
+    }
+}
+
+impl Drop for Capture {
+    fn drop(&mut self) {
+        let payload = Payload {
+            method: core::mem::take(&mut self.method),
+            direction: self.direction,
+            bytes: core::mem::take(&mut self.buffer).freeze(),
+            truncated: self.truncated,
+        };
+        (self.sink)(&payload);
+    }
+}
+
+#[derive(Clone, Debug)]
+///Layer of `PayloadLog`
+pub struct PayloadLogLayer {
+    log: PayloadLog,
+}
+
+impl<S> tower_layer::Layer<S> for PayloadLogLayer {
+    type Service = PayloadLogService<S>;
+
+    #[inline(always)]
+    fn layer(&self, inner: S) -> Self::Service {
+        PayloadLogService {
+            log: self.log.clone(),
+            inner,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+///Service of `PayloadLog`
+pub struct PayloadLogService<S> {
+    log: PayloadLog,
+    inner: S,
+}
+
+impl<S: tonic::server::NamedService> tonic::server::NamedService for PayloadLogService<S> {
+    const NAME: &'static str = S::NAME;
+}
+
+impl<ReqBody, ResBody, S: tower_service::Service<http::Request<PayloadBody<ReqBody>>, Response = http::Response<ResBody>>> tower_service::Service<http::Request<ReqBody>> for PayloadLogService<S> {
+    type Response = http::Response<PayloadBody<ResBody>>;
+    type Error = S::Error;
+    type Future = PayloadLogFut<S::Future>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        let path = req.uri().path();
+        let (request, response) = match self.log.is_logged(path, req.headers()) {
+            true => (Some(self.log.capture(path, Direction::Request)), Some(self.log.capture(path, Direction::Response))),
+            false => (None, None),
+        };
+        PayloadLogFut {
+            capture: response,
+            inner: self.inner.call(req.map(|body| PayloadBody::new(body, request))),
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    ///Future of `PayloadLogService`
+    pub struct PayloadLogFut<F> {
+        capture: Option<Capture>,
+        #[pin]
+        inner: F,
+    }
+}
+
+impl<F> fmt::Debug for PayloadLogFut<F> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("PayloadLogFut").field("is_logged", &self.capture.is_some()).finish_non_exhaustive()
+    }
+}
+
+impl<B, E, F: Future<Output = Result<http::Response<B>, E>>> Future for PayloadLogFut<F> {
+    type Output = Result<http::Response<PayloadBody<B>>, E>;
+
+    fn poll(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let this = self.project();
+        match this.inner.poll(ctx) {
+            task::Poll::Ready(Ok(response)) => {
+                let capture = this.capture.take();
+                task::Poll::Ready(Ok(response.map(|body| PayloadBody::new(body, capture))))
+            },
+            task::Poll::Ready(Err(error)) => task::Poll::Ready(Err(error)),
+            task::Poll::Pending => task::Poll::Pending,
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    ///Body of `PayloadLogService`, which copies data of logged calls
+    pub struct PayloadBody<B> {
+        #[pin]
+        inner: compat::Incoming<B>,
+        capture: Option<Capture>,
+    }
+}
+
+impl<B> PayloadBody<B> {
+    #[inline(always)]
+    fn new(inner: B, capture: Option<Capture>) -> Self {
+        Self {
+            inner: compat::Incoming::new(inner),
+            capture,
+        }
+    }
+
+    #[inline(always)]
+    ///Returns whether payload is being logged
+    pub fn is_logged(&self) -> bool {
+        self.capture.is_some()
+    }
+}
+
+impl<B: Default> Default for PayloadBody<B> {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::new(B::default(), None)
+    }
+}
+
+impl<B> fmt::Debug for PayloadBody<B> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("PayloadBody").field("is_logged", &self.is_logged()).finish_non_exhaustive()
+    }
+}
+
+compat::impl_body! {
+    [B: http_body::Body<Data = Bytes>] PayloadBody<B> {
+        type Data = Bytes;
+        type Error = B::Error;
+
+        fn poll_data(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Option<Result<Self::Data, Self::Error>>> {
+            let this = self.project();
+            let result = this.inner.poll_data(ctx);
+            match &result {
+                task::Poll::Ready(Some(Ok(data))) => if let Some(capture) = this.capture.as_mut() {
+                    capture.push(data);
+                },
+                task::Poll::Ready(None) => *this.capture = None,
+                _ => (),
+            }
+            result
+        }
+
+        #[inline(always)]
+        fn poll_trailers(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Result<Option<http::HeaderMap>, Self::Error>> {
+            self.project().inner.poll_trailers(ctx)
+        }
+
+        #[inline(always)]
+        fn is_end_stream(&self) -> bool {
+            self.inner.is_end_stream()
+        }
+
+        #[inline(always)]
+        fn size_hint(&self) -> http_body::SizeHint {
+            self.inner.size_hint()
+        }
+    }
+}
@@ -0,0 +1,229 @@
+//! Deferred header mutations
+//!
+//!`DeferredInterceptor` doesn't modify headers directly, but records operations into `HeaderOps`.
+//!When wrapped into `Deferred`, recorded operations are applied at once, after all callbacks are done.
+//!
+//!Tuples of deferred interceptors are deferred interceptors themselves, which allows to combine multiple interceptors
+//!within single service, without intermediate conversions.
+//!Every interceptor observes original request headers, without operations recorded by preceding ones.
+
+use crate::{Interceptor, InterceptorLayer};
+
+#[derive(Clone, Debug)]
+enum Op {
+    Insert(http::HeaderValue),
+    Append(http::HeaderValue),
+    Remove,
+}
+
+#[derive(Clone, Default, Debug)]
+///Recorded header mutations
+pub struct HeaderOps {
+    ops: Vec<(&'static str, Op)>,
+}
+
+impl HeaderOps {
+    #[inline(always)]
+    ///Creates empty instance
+    pub const fn new() -> Self {
+        Self {
+            ops: Vec::new(),
+        }
+    }
+
+    #[inline]
+    ///Records insertion of `value`, replacing existing values of `key`.
+    ///
+    ///`key` must be valid lower case header name.
+    pub fn insert(&mut self, key: &'static str, value: http::HeaderValue) -> &mut Self {
+        self.ops.push((key, Op::Insert(value)));
+        self
+    }
+
+    #[inline]
+    ///Records appending of `value` to existing values of `key`
+    ///
+    ///`key` must be valid lower case header name.
+    pub fn append(&mut self, key: &'static str, value: http::HeaderValue) -> &mut Self {
+        self.ops.push((key, Op::Append(value)));
+        self
+    }
+
+    #[inline]
+    ///Records removal of all values of `key`
+    pub fn remove(&mut self, key: &'static str) -> &mut Self {
+        self.ops.push((key, Op::Remove));
+        self
+    }
+
+    #[inline(always)]
+    ///Returns whether no operation is recorded
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    #[inline(always)]
+    ///Returns number of recorded operations
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    ///Applies recorded operations in order, clearing itself.
+    ///
+    ///Operations on the same key are merged first, so that every key is looked up once.
+    pub fn apply(&mut self, headers: &mut http::HeaderMap) {
+        struct Merged {
+            key: &'static str,
+            //Whether existing values are discarded
+            replace: bool,
+            values: Vec<http::HeaderValue>,
+        }
+
+        let mut merged: Vec<Merged> = Vec::new();
+        for (key, op) in self.ops.drain(..) {
+            let idx = match merged.iter().position(|merged| merged.key == key) {
+                Some(idx) => idx,
+                None => {
+                    merged.push(Merged {
+                        key,
+                        replace: false,
+                        values: Vec::new(),
+                    });
+                    merged.len() - 1
+                }
+            };
+            let merged = &mut merged[idx];
+
+            match op {
+                Op::Insert(value) => {
+                    merged.replace = true;
+                    merged.values.clear();
+                    merged.values.push(value);
+                },
+                Op::Append(value) => merged.values.push(value),
+                Op::Remove => {
+                    merged.replace = true;
+                    merged.values.clear();
+                },
+            }
+        }
+
+        for merged in merged {
+            let mut values = merged.values.into_iter();
+            match headers.entry(http::header::HeaderName::from_static(merged.key)) {
+                http::header::Entry::Occupied(mut entry) => {
+                    if merged.replace {
+                        match values.next() {
+                            Some(value) => {
+                                entry.insert(value);
+                            },
+                            None => {
+                                entry.remove_entry_mult();
+                                continue;
+                            }
+                        }
+                    }
+                    for value in values {
+                        entry.append(value);
+                    }
+                },
+                http::header::Entry::Vacant(entry) => if let Some(value) = values.next() {
+                    let mut entry = entry.insert_entry(value);
+                    for value in values {
+                        entry.append(value);
+                    }
+                },
+            }
+        }
+    }
+}
+
+///Interceptor recording header mutations instead of applying them
+pub trait DeferredInterceptor {
+    ///Callback on incoming request, allowing you to record header mutations or modify extensions
+    ///
+    ///Returning status will preempt request handling and immediately returns status
+    fn on_request(&self, headers: &http::HeaderMap, ops: &mut HeaderOps, extensions: &mut http::Extensions) -> Option<tonic::Status>;
+
+    ///Callback when response is being returned
+    fn on_response(&self, status: tonic::Code, headers: &mut http::HeaderMap, extensions: &http::Extensions);
+}
+
+impl<I: DeferredInterceptor> DeferredInterceptor for std::sync::Arc<I> {
+    #[inline(always)]
+    fn on_request(&self, headers: &http::HeaderMap, ops: &mut HeaderOps, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        DeferredInterceptor::on_request(self.as_ref(), headers, ops, extensions)
+    }
+
+    #[inline(always)]
+    fn on_response(&self, status: tonic::Code, headers: &mut http::HeaderMap, extensions: &http::Extensions) {
+        DeferredInterceptor::on_response(self.as_ref(), status, headers, extensions)
+    }
+}
+
+macro_rules! impl_tuple {
+    ($($name:ident: $idx:tt),+; $($rev:tt),+) => {
+        impl<$($name: DeferredInterceptor),+> DeferredInterceptor for ($($name,)+) {
+            #[inline]
+            fn on_request(&self, headers: &http::HeaderMap, ops: &mut HeaderOps, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+                $(
+                    if let Some(status) = self.$idx.on_request(headers, ops, extensions) {
+                        return Some(status);
+                    }
+                )+
+                None
+            }
+
+            #[inline]
+            fn on_response(&self, status: tonic::Code, headers: &mut http::HeaderMap, extensions: &http::Extensions) {
+                $(
+                    self.$rev.on_response(status, headers, extensions);
+                )+
+            }
+        }
+    };
+}
+
+impl_tuple!(A: 0, B: 1; 1, 0);
+impl_tuple!(A: 0, B: 1, C: 2; 2, 1, 0);
+impl_tuple!(A: 0, B: 1, C: 2, D: 3; 3, 2, 1, 0);
+impl_tuple!(A: 0, B: 1, C: 2, D: 3, E: 4; 4, 3, 2, 1, 0);
+impl_tuple!(A: 0, B: 1, C: 2, D: 3, E: 4, F: 5; 5, 4, 3, 2, 1, 0);
+
+#[derive(Clone, Copy, Default, Debug)]
+#[repr(transparent)]
+///Adapter of `DeferredInterceptor` to `Interceptor`, which applies recorded operations at once.
+///
+///Responses are passed to tuple elements in reverse order.
+pub struct Deferred<I>(pub I);
+
+impl<I: DeferredInterceptor> Interceptor for Deferred<I> {
+    #[inline]
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        let mut raw = crate::compat::metadata_into_headers(core::mem::take(headers));
+        let result = self.on_request_headers(&http::Uri::default(), &mut raw, extensions);
+        *headers = crate::compat::metadata_from_headers(raw);
+        result
+    }
+
+    #[inline]
+    fn on_request_headers(&self, _: &http::Uri, headers: &mut http::HeaderMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        let mut ops = HeaderOps::new();
+        let result = self.0.on_request(headers, &mut ops, extensions);
+        if result.is_none() {
+            ops.apply(headers);
+        }
+        result
+    }
+
+    #[inline(always)]
+    fn on_response(&self, status: tonic::Code, headers: &mut http::HeaderMap, extensions: &http::Extensions) {
+        self.0.on_response(status, headers, extensions)
+    }
+}
+
+#[inline(always)]
+///Creates interceptor layer for `DeferredInterceptor`
+pub fn deferred_interceptor<I: DeferredInterceptor>(interceptor: I) -> InterceptorLayer<Deferred<I>> {
+    crate::interceptor(Deferred(interceptor))
+}
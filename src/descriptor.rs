@@ -0,0 +1,187 @@
+//! Method information from protobuf descriptors
+//!
+//!`MethodInfoInterceptor` looks up method of incoming call in encoded `FileDescriptorSet`,
+//!such as the one shipped for reflection, and makes it available as `MethodInfo` extension:
+//!
+//!```rust,ignore
+//!use tonic_interceptor::descriptor::MethodInfoInterceptor;
+//!
+//!const DESCRIPTOR: &[u8] = tonic::include_file_descriptor_set!("descriptor");
+//!
+//!let interceptor = MethodInfoInterceptor::new(DESCRIPTOR).expect("valid descriptor").reject_unknown(true);
+//!```
+//!
+//!Interceptors running after it can rely on `MethodInfo`, e.g. to treat idempotent methods differently.
+//!
+//!Descriptors are decoded into `prost_reflect::DescriptorPool`, which requires `prost-reflect` feature.
+
+use crate::Interceptor;
+
+use prost_reflect::{DescriptorError, DescriptorPool};
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+///Description of called method
+pub struct MethodInfo {
+    ///Fully qualified service name, e.g. `pkg.v1.Users`
+    pub service: Arc<str>,
+    ///Method name, e.g. `Get`
+    pub method: Arc<str>,
+    ///Whether client sends stream of messages
+    pub client_streaming: bool,
+    ///Whether server sends stream of messages
+    pub server_streaming: bool,
+    ///Whether method is marked as `NO_SIDE_EFFECTS` or `IDEMPOTENT`
+    pub idempotent: bool,
+}
+
+impl MethodInfo {
+    #[inline(always)]
+    ///Retrieves method info from extensions
+    pub fn from_extensions(extensions: &http::Extensions) -> Option<&Self> {
+        extensions.get()
+    }
+
+    #[inline(always)]
+    ///Returns whether method is unary
+    pub fn is_unary(&self) -> bool {
+        !self.client_streaming && !self.server_streaming
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+///Methods of decoded `FileDescriptorSet`, indexed by path
+pub struct Descriptors {
+    pool: DescriptorPool,
+    methods: HashMap<String, MethodInfo>,
+}
+
+impl Descriptors {
+    ///Decodes `FileDescriptorSet`
+    ///
+    ///Set must be complete, i.e. it must include every imported file.
+    pub fn decode(bytes: &[u8]) -> Result<Self, DescriptorError> {
+        DescriptorPool::decode(bytes).map(Self::from_pool)
+    }
+
+    ///Indexes methods of every service in `pool`
+    pub fn from_pool(pool: DescriptorPool) -> Self {
+        let mut methods = HashMap::new();
+        for service in pool.services() {
+            let service_name: Arc<str> = service.full_name().into();
+            for method in service.methods() {
+                //IDEMPOTENCY_UNKNOWN is 0, while NO_SIDE_EFFECTS and IDEMPOTENT imply idempotency
+                let level = method.method_descriptor_proto().options.as_ref().and_then(|options| options.idempotency_level);
+                methods.insert(format!("/{}/{}", service_name, method.name()), MethodInfo {
+                    service: service_name.clone(),
+                    method: method.name().into(),
+                    client_streaming: method.is_client_streaming(),
+                    server_streaming: method.is_server_streaming(),
+                    idempotent: level.is_some_and(|level| level == 1 || level == 2),
+                });
+            }
+        }
+
+        Self {
+            pool,
+            methods,
+        }
+    }
+
+    #[inline(always)]
+    ///Access descriptor pool
+    pub fn pool(&self) -> &DescriptorPool {
+        &self.pool
+    }
+
+    #[inline]
+    ///Returns method of `path`, e.g. `/pkg.v1.Users/Get`
+    pub fn method(&self, path: &str) -> Option<&MethodInfo> {
+        self.methods.get(path)
+    }
+
+    #[inline(always)]
+    ///Returns number of methods
+    pub fn len(&self) -> usize {
+        self.methods.len()
+    }
+
+    #[inline(always)]
+    ///Returns whether there are no methods
+    pub fn is_empty(&self) -> bool {
+        self.methods.is_empty()
+    }
+}
+
+#[derive(Clone, Debug)]
+///Server interceptor which inserts `MethodInfo` extension of called method
+///
+///Unknown methods are passed through without extension, unless configured to be rejected with `UNIMPLEMENTED`.
+pub struct MethodInfoInterceptor {
+    descriptors: Arc<Descriptors>,
+    reject_unknown: bool,
+}
+
+impl MethodInfoInterceptor {
+    #[inline]
+    ///Creates new instance from encoded `FileDescriptorSet`
+    pub fn new(bytes: &[u8]) -> Result<Self, DescriptorError> {
+        Descriptors::decode(bytes).map(Self::from_descriptors)
+    }
+
+    #[inline]
+    ///Creates new instance from already decoded `descriptors`
+    pub fn from_descriptors(descriptors: Descriptors) -> Self {
+        Self {
+            descriptors: Arc::new(descriptors),
+            reject_unknown: false,
+        }
+    }
+
+    #[inline(always)]
+    ///Sets whether unknown methods are rejected
+    pub fn reject_unknown(mut self, reject_unknown: bool) -> Self {
+        self.reject_unknown = reject_unknown;
+        self
+    }
+
+    #[inline(always)]
+    ///Access descriptors
+    pub fn descriptors(&self) -> &Descriptors {
+        &self.descriptors
+    }
+
+    fn apply(&self, path: &str, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        match self.descriptors.method(path) {
+            Some(info) => {
+                extensions.insert(info.clone());
+                None
+            },
+            None if self.reject_unknown => Some(tonic::Status::unimplemented(format!("unknown method '{}'", path))),
+            None => None,
+        }
+    }
+}
+
+impl Interceptor for MethodInfoInterceptor {
+    #[inline(always)]
+    fn on_request(&self, _: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+        None
+    }
+
+    #[inline]
+    fn on_request_with_uri(&self, uri: &http::Uri, _: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        self.apply(uri.path(), extensions)
+    }
+
+    #[inline]
+    fn on_request_headers(&self, uri: &http::Uri, _: &mut http::HeaderMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        self.apply(uri.path(), extensions)
+    }
+
+    #[inline(always)]
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
@@ -0,0 +1,278 @@
+//! Rich status details
+//!
+//!`StatusDetails` builds `google.rpc.Status` with standard error details, which is sent as `grpc-status-details-bin`:
+//!
+//!```rust
+//!use tonic_interceptor::details::StatusDetails;
+//!
+//!let status = StatusDetails::new().bad_request("name", "must not be empty").into_status(tonic::Code::InvalidArgument, "invalid request");
+//!
+//!let details = StatusDetails::from_status(&status).expect("valid details");
+//!assert_eq!(details.bad_request.unwrap().field_violations[0].field, "name");
+//!```
+//!
+//!Messages are equivalent to ones defined in `google/rpc/error_details.proto`.
+
+use prost::Message;
+
+const BAD_REQUEST_TYPE: &str = "type.googleapis.com/google.rpc.BadRequest";
+const ERROR_INFO_TYPE: &str = "type.googleapis.com/google.rpc.ErrorInfo";
+const PRECONDITION_FAILURE_TYPE: &str = "type.googleapis.com/google.rpc.PreconditionFailure";
+const QUOTA_FAILURE_TYPE: &str = "type.googleapis.com/google.rpc.QuotaFailure";
+const RETRY_INFO_TYPE: &str = "type.googleapis.com/google.rpc.RetryInfo";
+
+#[derive(Clone, PartialEq, Message)]
+struct Any {
+    #[prost(string, tag = "1")]
+    type_url: String,
+    #[prost(bytes = "vec", tag = "2")]
+    value: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+//google.rpc.Status
+struct RpcStatus {
+    #[prost(int32, tag = "1")]
+    code: i32,
+    #[prost(string, tag = "2")]
+    message: String,
+    #[prost(message, repeated, tag = "3")]
+    details: Vec<Any>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+///Field violation of `BadRequest`
+pub struct FieldViolation {
+    ///Path to the field
+    #[prost(string, tag = "1")]
+    pub field: String,
+    ///Description of why field is invalid
+    #[prost(string, tag = "2")]
+    pub description: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+///`google.rpc.BadRequest`
+pub struct BadRequest {
+    ///Violations of request fields
+    #[prost(message, repeated, tag = "1")]
+    pub field_violations: Vec<FieldViolation>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+///`google.rpc.ErrorInfo`
+pub struct ErrorInfo {
+    ///Reason of error, as `UPPER_SNAKE_CASE` constant
+    #[prost(string, tag = "1")]
+    pub reason: String,
+    ///Logical grouping of reason, typically service name
+    #[prost(string, tag = "2")]
+    pub domain: String,
+    ///Additional structured details
+    #[prost(btree_map = "string, string", tag = "3")]
+    pub metadata: std::collections::BTreeMap<String, String>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+///Violation of `PreconditionFailure`
+pub struct PreconditionViolation {
+    ///Type of precondition
+    #[prost(string, tag = "1")]
+    pub r#type: String,
+    ///Subject, relative to the type, that failed
+    #[prost(string, tag = "2")]
+    pub subject: String,
+    ///Description of how precondition failed
+    #[prost(string, tag = "3")]
+    pub description: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+///`google.rpc.PreconditionFailure`
+pub struct PreconditionFailure {
+    ///Failed preconditions
+    #[prost(message, repeated, tag = "1")]
+    pub violations: Vec<PreconditionViolation>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+///Violation of `QuotaFailure`
+pub struct QuotaViolation {
+    ///Subject on which quota check failed
+    #[prost(string, tag = "1")]
+    pub subject: String,
+    ///Description of how quota check failed
+    #[prost(string, tag = "2")]
+    pub description: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+///`google.rpc.QuotaFailure`
+pub struct QuotaFailure {
+    ///Quota violations
+    #[prost(message, repeated, tag = "1")]
+    pub violations: Vec<QuotaViolation>,
+}
+
+#[derive(Clone, Copy, PartialEq, Message)]
+///`google.protobuf.Duration`
+pub struct ProtoDuration {
+    ///Seconds
+    #[prost(int64, tag = "1")]
+    pub seconds: i64,
+    ///Fraction of second in nanoseconds
+    #[prost(int32, tag = "2")]
+    pub nanos: i32,
+}
+
+impl From<core::time::Duration> for ProtoDuration {
+    #[inline]
+    fn from(duration: core::time::Duration) -> Self {
+        Self {
+            seconds: duration.as_secs().min(i64::MAX as u64) as i64,
+            nanos: duration.subsec_nanos() as i32,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Message)]
+///`google.rpc.RetryInfo`
+pub struct RetryInfo {
+    ///Delay after which client should retry
+    #[prost(message, optional, tag = "1")]
+    pub retry_delay: Option<ProtoDuration>,
+}
+
+#[derive(Clone, Default, Debug, PartialEq)]
+///Builder of status with standard error details
+pub struct StatusDetails {
+    ///Bad request details
+    pub bad_request: Option<BadRequest>,
+    ///Error info details
+    pub error_info: Option<ErrorInfo>,
+    ///Precondition failure details
+    pub precondition_failure: Option<PreconditionFailure>,
+    ///Quota failure details
+    pub quota_failure: Option<QuotaFailure>,
+    ///Retry info details
+    pub retry_info: Option<RetryInfo>,
+}
+
+impl StatusDetails {
+    #[inline(always)]
+    ///Creates empty instance
+    pub const fn new() -> Self {
+        Self {
+            bad_request: None,
+            error_info: None,
+            precondition_failure: None,
+            quota_failure: None,
+            retry_info: None,
+        }
+    }
+
+    ///Adds field violation to `BadRequest`
+    pub fn bad_request<F: Into<String>, D: Into<String>>(mut self, field: F, description: D) -> Self {
+        self.bad_request.get_or_insert_with(Default::default).field_violations.push(FieldViolation {
+            field: field.into(),
+            description: description.into(),
+        });
+        self
+    }
+
+    ///Sets `ErrorInfo`
+    pub fn error_info<R: Into<String>, D: Into<String>, K: Into<String>, V: Into<String>, M: IntoIterator<Item = (K, V)>>(mut self, reason: R, domain: D, metadata: M) -> Self {
+        self.error_info = Some(ErrorInfo {
+            reason: reason.into(),
+            domain: domain.into(),
+            metadata: metadata.into_iter().map(|(key, value)| (key.into(), value.into())).collect(),
+        });
+        self
+    }
+
+    ///Adds violation to `PreconditionFailure`
+    pub fn precondition_failure<T: Into<String>, S: Into<String>, D: Into<String>>(mut self, r#type: T, subject: S, description: D) -> Self {
+        self.precondition_failure.get_or_insert_with(Default::default).violations.push(PreconditionViolation {
+            r#type: r#type.into(),
+            subject: subject.into(),
+            description: description.into(),
+        });
+        self
+    }
+
+    ///Adds violation to `QuotaFailure`
+    pub fn quota_failure<S: Into<String>, D: Into<String>>(mut self, subject: S, description: D) -> Self {
+        self.quota_failure.get_or_insert_with(Default::default).violations.push(QuotaViolation {
+            subject: subject.into(),
+            description: description.into(),
+        });
+        self
+    }
+
+    ///Sets `RetryInfo`
+    pub fn retry_info(mut self, retry_delay: core::time::Duration) -> Self {
+        self.retry_info = Some(RetryInfo {
+            retry_delay: Some(retry_delay.into()),
+        });
+        self
+    }
+
+    ///Returns whether there are no details
+    pub fn is_empty(&self) -> bool {
+        self.bad_request.is_none() && self.error_info.is_none() && self.precondition_failure.is_none() && self.quota_failure.is_none() && self.retry_info.is_none()
+    }
+
+    ///Creates status with `code`, `message` and details encoded as `google.rpc.Status`
+    pub fn into_status<M: Into<String>>(self, code: tonic::Code, message: M) -> tonic::Status {
+        fn any<M: Message>(type_url: &str, message: &M) -> Any {
+            Any {
+                type_url: type_url.to_owned(),
+                value: message.encode_to_vec(),
+            }
+        }
+
+        let message = message.into();
+        let mut details = Vec::new();
+        if let Some(bad_request) = self.bad_request.as_ref() {
+            details.push(any(BAD_REQUEST_TYPE, bad_request));
+        }
+        if let Some(error_info) = self.error_info.as_ref() {
+            details.push(any(ERROR_INFO_TYPE, error_info));
+        }
+        if let Some(precondition_failure) = self.precondition_failure.as_ref() {
+            details.push(any(PRECONDITION_FAILURE_TYPE, precondition_failure));
+        }
+        if let Some(quota_failure) = self.quota_failure.as_ref() {
+            details.push(any(QUOTA_FAILURE_TYPE, quota_failure));
+        }
+        if let Some(retry_info) = self.retry_info.as_ref() {
+            details.push(any(RETRY_INFO_TYPE, retry_info));
+        }
+
+        let status = RpcStatus {
+            code: code as i32,
+            message: message.clone(),
+            details,
+        };
+        tonic::Status::with_details(code, message, status.encode_to_vec().into())
+    }
+
+    ///Decodes details of `status`
+    ///
+    ///Unknown detail types are ignored, and empty details result in empty instance.
+    pub fn from_status(status: &tonic::Status) -> Result<Self, prost::DecodeError> {
+        let status = RpcStatus::decode(status.details())?;
+        let mut result = Self::new();
+        for detail in status.details {
+            match detail.type_url.as_str() {
+                BAD_REQUEST_TYPE => result.bad_request = Some(BadRequest::decode(detail.value.as_slice())?),
+                ERROR_INFO_TYPE => result.error_info = Some(ErrorInfo::decode(detail.value.as_slice())?),
+                PRECONDITION_FAILURE_TYPE => result.precondition_failure = Some(PreconditionFailure::decode(detail.value.as_slice())?),
+                QUOTA_FAILURE_TYPE => result.quota_failure = Some(QuotaFailure::decode(detail.value.as_slice())?),
+                RETRY_INFO_TYPE => result.retry_info = Some(RetryInfo::decode(detail.value.as_slice())?),
+                _ => (),
+            }
+        }
+        Ok(result)
+    }
+}
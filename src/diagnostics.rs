@@ -0,0 +1,120 @@
+//! Poll-level diagnostics of `InterceptorFut`
+//!
+//!With `diagnostics` feature, future of `InterceptorService` records how it was polled
+//!and reports it to `Interceptor::on_poll_stats` once it completes or is dropped.
+//!Without it, nothing is recorded.
+//!
+//!Stats are reported to interceptor, which future already holds, hence future of rejected request reports them
+//!only when `InterceptorService::call_on_response_for_rejections` is enabled.
+
+#[cfg(feature = "diagnostics")]
+pub use imp::PollStats;
+pub(crate) use imp::PollRecorder;
+
+#[cfg(feature = "diagnostics")]
+mod imp {
+    use crate::Interceptor;
+
+    use core::time::Duration;
+    use std::time::Instant;
+
+    #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+    ///Statistics of polling single service future
+    pub struct PollStats {
+        ///Number of times future was polled
+        pub polls: u64,
+        ///Total time spent inside `poll`
+        pub busy: Duration,
+        ///Time from the first poll until completion or drop
+        pub elapsed: Duration,
+        ///Whether future completed, rather than being dropped
+        pub completed: bool,
+    }
+
+    pub(crate) struct PollRecorder<I> {
+        //Resolved while interceptor is known to implement `Interceptor`, so that drop can report too
+        report: fn(&I, PollStats),
+        polls: u64,
+        busy: Duration,
+        first_poll: Option<Instant>,
+        is_reported: bool,
+    }
+
+    impl<I> PollRecorder<I> {
+        #[inline]
+        pub(crate) fn new() -> Self where I: Interceptor {
+            Self {
+                report: I::on_poll_stats,
+                polls: 0,
+                busy: Duration::ZERO,
+                first_poll: None,
+                is_reported: false,
+            }
+        }
+
+        #[inline]
+        pub(crate) fn start(&mut self) -> Instant {
+            let now = Instant::now();
+            self.polls += 1;
+            self.first_poll.get_or_insert(now);
+            now
+        }
+
+        #[inline]
+        pub(crate) fn finish(&mut self, started: Instant, is_ready: bool, interceptor: Option<&I>) {
+            self.busy += started.elapsed();
+            if is_ready {
+                self.report(true, interceptor);
+            }
+        }
+
+        #[inline]
+        pub(crate) fn dropped(&mut self, interceptor: Option<&I>) {
+            self.report(false, interceptor);
+        }
+
+        fn report(&mut self, completed: bool, interceptor: Option<&I>) {
+            if self.is_reported {
+                return;
+            }
+            self.is_reported = true;
+            if let (Some(interceptor), Some(first_poll)) = (interceptor, self.first_poll) {
+                (self.report)(interceptor, PollStats {
+                    polls: self.polls,
+                    busy: self.busy,
+                    elapsed: first_poll.elapsed(),
+                    completed,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "diagnostics"))]
+mod imp {
+    use core::marker::PhantomData;
+
+    pub(crate) struct PollRecorder<I>(PhantomData<fn(I)>);
+
+    pub(crate) struct Started;
+
+    impl<I> PollRecorder<I> {
+        #[inline(always)]
+        pub(crate) fn new() -> Self {
+            Self(PhantomData)
+        }
+
+        #[inline(always)]
+        pub(crate) fn start(&mut self) -> Started {
+            Started
+        }
+
+        #[inline(always)]
+        pub(crate) fn finish(&mut self, _: Started, _: bool, _: Option<&I>) {
+        }
+
+        #[inline(always)]
+        pub(crate) fn dropped(&mut self, _: Option<&I>) {
+        }
+    }
+}
@@ -0,0 +1,261 @@
+//! Typed extension helpers
+//!
+//!Interceptors communicate through `http::Extensions`, keyed by type.
+//!These helpers turn missing or duplicate extensions into `Internal` status, naming offending type,
+//!instead of panicking in later interceptors.
+
+use core::any::type_name;
+
+pub use crate::compat::Extension;
+
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+///Namespace of extensions inserted by this crate
+///
+///Wrapping value into `Scoped` makes it distinct from user's extension of the same type.
+pub struct Scoped<T>(pub T);
+
+impl<T> Scoped<T> {
+    #[inline(always)]
+    ///Returns wrapped value
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> core::ops::Deref for Scoped<T> {
+    type Target = T;
+
+    #[inline(always)]
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> core::ops::DerefMut for Scoped<T> {
+    #[inline(always)]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[inline]
+///Gets extension `T`, rejecting with `Internal` status if it is missing
+///
+///Missing extension means that interceptor which inserts it is not present in the chain, which is configuration error.
+pub fn get_or_reject<T: Send + Sync + 'static>(extensions: &http::Extensions) -> Result<&T, tonic::Status> {
+    extensions.get::<T>().ok_or_else(|| tonic::Status::internal(format!("missing extension '{}'", type_name::<T>())))
+}
+
+#[inline]
+///Gets extension `T` mutably, rejecting with `Internal` status if it is missing
+pub fn get_mut_or_reject<T: Send + Sync + 'static>(extensions: &mut http::Extensions) -> Result<&mut T, tonic::Status> {
+    extensions.get_mut::<T>().ok_or_else(|| tonic::Status::internal(format!("missing extension '{}'", type_name::<T>())))
+}
+
+#[inline]
+///Inserts extension `T`, rejecting with `Internal` status if it is already present
+///
+///Existing value is kept as it is. Duplicate usually means that two interceptors provide the same extension (e.g. two auth interceptors).
+pub fn insert_unique<T: Extension>(extensions: &mut http::Extensions, value: T) -> Result<(), tonic::Status> {
+    if extensions.get::<T>().is_some() {
+        return Err(tonic::Status::internal(format!("duplicate extension '{}'", type_name::<T>())));
+    }
+    extensions.insert(value);
+    Ok(())
+}
+
+#[derive(Debug, Default)]
+#[cfg_attr(not(feature = "tonic011"), derive(Clone))]
+///Extensions carried from request to `on_response` of the same call
+///
+///Stored in request extensions, from where `InterceptorService` takes it after calling interceptor,
+///retaining it until response is ready.
+///It is then present in extensions passed to `on_response`, whether response is produced by inner service, rejection or error handler.
+///Inner service never sees it.
+pub struct Carried {
+    extensions: http::Extensions,
+}
+
+impl Carried {
+    #[inline(always)]
+    ///Creates empty instance
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    ///Returns instance within request `extensions`, inserting empty one if necessary
+    pub fn of_request(extensions: &mut http::Extensions) -> &mut Self {
+        if extensions.get::<Self>().is_none() {
+            extensions.insert(Self::new());
+        }
+        extensions.get_mut::<Self>().expect("inserted")
+    }
+
+    #[inline]
+    ///Returns value of type `T` carried from request, given extensions of `on_response`
+    pub fn get_from<T: Send + Sync + 'static>(extensions: &http::Extensions) -> Option<&T> {
+        extensions.get::<Self>().and_then(|carried| carried.get::<T>())
+    }
+
+    #[inline(always)]
+    ///Gets value of type `T`
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.extensions.get::<T>()
+    }
+
+    #[inline(always)]
+    ///Gets value of type `T` mutably
+    pub fn get_mut<T: Send + Sync + 'static>(&mut self) -> Option<&mut T> {
+        self.extensions.get_mut::<T>()
+    }
+
+    #[inline(always)]
+    ///Inserts value, returning previous one of the same type
+    pub fn insert<T: Extension>(&mut self, value: T) -> Option<T> {
+        self.extensions.insert(value)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+///Request metadata to capture into `RequestMetadataSnapshot`
+///
+///Snapshot is limited by total size of captured keys and values, 4096 bytes by default.
+///Entries exceeding limit are skipped, marking snapshot as truncated.
+pub struct MetadataCapture {
+    keys: Option<&'static [&'static str]>,
+    max_bytes: usize,
+}
+
+impl MetadataCapture {
+    ///Default limit of snapshot size
+    pub const DEFAULT_MAX_BYTES: usize = 4096;
+
+    #[inline(always)]
+    ///Captures only listed keys
+    pub const fn keys(keys: &'static [&'static str]) -> Self {
+        Self {
+            keys: Some(keys),
+            max_bytes: Self::DEFAULT_MAX_BYTES,
+        }
+    }
+
+    #[inline(always)]
+    ///Captures every key
+    pub const fn all() -> Self {
+        Self {
+            keys: None,
+            max_bytes: Self::DEFAULT_MAX_BYTES,
+        }
+    }
+
+    #[inline(always)]
+    ///Sets limit of snapshot size in bytes
+    pub const fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    ///Creates snapshot of `headers`
+    pub fn capture(&self, headers: &http::HeaderMap) -> RequestMetadataSnapshot {
+        let mut entries = Vec::new();
+        let mut size = 0;
+        let mut is_truncated = false;
+        let mut push = |key: &http::header::HeaderName, value: &http::HeaderValue| {
+            let entry_size = key.as_str().len() + value.len();
+            match size + entry_size > self.max_bytes {
+                true => is_truncated = true,
+                false => {
+                    size += entry_size;
+                    entries.push((key.clone(), value.clone()));
+                }
+            }
+        };
+
+        match self.keys {
+            Some(keys) => for key in keys {
+                if let Ok(key) = http::header::HeaderName::from_bytes(key.as_bytes()) {
+                    for value in headers.get_all(&key) {
+                        push(&key, value);
+                    }
+                }
+            },
+            None => for (key, value) in headers.iter() {
+                push(key, value);
+            },
+        }
+
+        RequestMetadataSnapshot {
+            inner: std::sync::Arc::new(SnapshotInner {
+                entries,
+                is_truncated,
+            }),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct SnapshotInner {
+    entries: Vec<(http::header::HeaderName, http::HeaderValue)>,
+    is_truncated: bool,
+}
+
+#[derive(Clone, Debug)]
+///Immutable snapshot of request metadata, taken before interceptor is called
+///
+///Enabled by `InterceptorService::capture_request_metadata`, which carries it to `on_response` using `Carried`.
+///Values are as they are sent, i.e. binary values are base64 encoded.
+pub struct RequestMetadataSnapshot {
+    inner: std::sync::Arc<SnapshotInner>,
+}
+
+impl RequestMetadataSnapshot {
+    #[inline]
+    ///Returns snapshot carried from request, given extensions of `on_response`
+    pub fn from_extensions(extensions: &http::Extensions) -> Option<&Self> {
+        Carried::get_from::<Self>(extensions)
+    }
+
+    #[inline]
+    ///Returns first value of `key`
+    pub fn get(&self, key: &str) -> Option<&http::HeaderValue> {
+        self.inner.entries.iter().find(|(name, _)| name.as_str().eq_ignore_ascii_case(key)).map(|(_, value)| value)
+    }
+
+    #[inline]
+    ///Returns every value of `key`
+    pub fn get_all<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a http::HeaderValue> + 'a {
+        self.iter().filter(move |(name, _)| name.as_str().eq_ignore_ascii_case(key)).map(|(_, value)| value)
+    }
+
+    #[inline]
+    ///Returns whether `key` is captured
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.get(key).is_some()
+    }
+
+    #[inline]
+    ///Returns iterator over captured entries
+    pub fn iter(&self) -> impl Iterator<Item = (&http::header::HeaderName, &http::HeaderValue)> {
+        self.inner.entries.iter().map(|(name, value)| (name, value))
+    }
+
+    #[inline(always)]
+    ///Returns number of captured entries
+    pub fn len(&self) -> usize {
+        self.inner.entries.len()
+    }
+
+    #[inline(always)]
+    ///Returns whether nothing is captured
+    pub fn is_empty(&self) -> bool {
+        self.inner.entries.is_empty()
+    }
+
+    #[inline(always)]
+    ///Returns whether some entries are skipped due to size limit
+    pub fn is_truncated(&self) -> bool {
+        self.inner.is_truncated
+    }
+}
@@ -0,0 +1,306 @@
+//! Feature flags
+//!
+//!`Flagged` runs wrapped interceptor only for requests, which have flag enabled according to `FlagProvider`:
+//!
+//!```rust
+//!use tonic_interceptor::flags::{Flagged, DynamicFlags};
+//!
+//!# struct Maintenance;
+//!# impl tonic_interceptor::Interceptor for Maintenance {
+//!#     fn on_request(&self, _: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+//!#         Some(tonic::Status::unavailable("maintenance"))
+//!#     }
+//!#     fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {}
+//!# }
+//!let flags = DynamicFlags::new();
+//!let interceptor = Flagged::new("maintenance", flags.clone(), Maintenance);
+//!//Later, when flag system pushes update
+//!flags.set("maintenance", true);
+//!```
+//!
+//!Provider is consulted on every request, hence it must be infallible and cheap.
+//!Asynchronous flag backends should be polled in background and cached, e.g. by pushing updates into `DynamicFlags`.
+
+use crate::{Interceptor, LazyMetadata};
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use arc_swap::ArcSwap;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+///Tenant of call, inserted into request extensions by upstream interceptor
+pub struct Tenant(pub String);
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+///Identity of caller, inserted into request extensions by upstream interceptor
+pub struct Identity(pub String);
+
+#[derive(Clone, Copy, Debug)]
+///Context of flag evaluation
+pub struct FlagContext<'a> {
+    method: &'a str,
+    extensions: &'a http::Extensions,
+}
+
+impl<'a> FlagContext<'a> {
+    #[inline(always)]
+    ///Creates new instance
+    pub fn new(method: &'a str, extensions: &'a http::Extensions) -> Self {
+        Self {
+            method,
+            extensions,
+        }
+    }
+
+    #[inline(always)]
+    ///Returns method path, e.g. `/package.Service/Method`
+    ///
+    ///Empty when request URI is not available.
+    pub fn method(&self) -> &'a str {
+        self.method
+    }
+
+    #[inline]
+    ///Returns `Tenant` of call, if known
+    pub fn tenant(&self) -> Option<&'a str> {
+        self.extensions.get::<Tenant>().map(|tenant| tenant.0.as_str())
+    }
+
+    #[inline]
+    ///Returns `Identity` of caller, if known
+    pub fn identity(&self) -> Option<&'a str> {
+        self.extensions.get::<Identity>().map(|identity| identity.0.as_str())
+    }
+
+    #[inline(always)]
+    ///Returns arbitrary extension of request
+    pub fn extension<T: Send + Sync + 'static>(&self) -> Option<&'a T> {
+        self.extensions.get::<T>()
+    }
+}
+
+///Source of feature flags
+pub trait FlagProvider {
+    ///Returns whether `flag` is enabled for call
+    ///
+    ///Called on every request, hence it must not block.
+    fn is_enabled(&self, flag: &str, ctx: &FlagContext<'_>) -> bool;
+}
+
+impl<P: FlagProvider + ?Sized> FlagProvider for &P {
+    #[inline(always)]
+    fn is_enabled(&self, flag: &str, ctx: &FlagContext<'_>) -> bool {
+        P::is_enabled(self, flag, ctx)
+    }
+}
+
+impl<P: FlagProvider + ?Sized> FlagProvider for Box<P> {
+    #[inline(always)]
+    fn is_enabled(&self, flag: &str, ctx: &FlagContext<'_>) -> bool {
+        P::is_enabled(self, flag, ctx)
+    }
+}
+
+impl<P: FlagProvider + ?Sized> FlagProvider for Arc<P> {
+    #[inline(always)]
+    fn is_enabled(&self, flag: &str, ctx: &FlagContext<'_>) -> bool {
+        P::is_enabled(self, flag, ctx)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+///Fixed set of flags, which does not depend on call
+///
+///Unknown flags are disabled.
+pub struct StaticFlags {
+    flags: HashMap<String, bool>,
+}
+
+impl StaticFlags {
+    #[inline(always)]
+    ///Creates new instance with every flag disabled
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    ///Sets state of `flag`
+    pub fn set(mut self, flag: impl Into<String>, enabled: bool) -> Self {
+        self.flags.insert(flag.into(), enabled);
+        self
+    }
+
+    #[inline]
+    ///Returns whether `flag` is enabled
+    pub fn get(&self, flag: &str) -> bool {
+        self.flags.get(flag).copied().unwrap_or(false)
+    }
+}
+
+impl<K: Into<String>> core::iter::FromIterator<(K, bool)> for StaticFlags {
+    #[inline]
+    fn from_iter<T: IntoIterator<Item = (K, bool)>>(iter: T) -> Self {
+        Self {
+            flags: iter.into_iter().map(|(flag, enabled)| (flag.into(), enabled)).collect(),
+        }
+    }
+}
+
+impl FlagProvider for StaticFlags {
+    #[inline(always)]
+    fn is_enabled(&self, flag: &str, _: &FlagContext<'_>) -> bool {
+        self.get(flag)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+///Set of flags, which can be updated at runtime
+///
+///Clones share the same set, hence updates made through one are visible to every interceptor.
+///Set is kept in `ArcSwap`, hence evaluation never takes lock and updates never wait for in-flight evaluations.
+pub struct DynamicFlags {
+    flags: Arc<ArcSwap<StaticFlags>>,
+}
+
+impl DynamicFlags {
+    #[inline(always)]
+    ///Creates new instance with every flag disabled
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    ///Creates new instance with initial set of `flags`
+    pub fn with(flags: StaticFlags) -> Self {
+        Self {
+            flags: Arc::new(ArcSwap::from_pointee(flags)),
+        }
+    }
+
+    #[inline]
+    ///Returns snapshot of current flags
+    pub fn load(&self) -> Arc<StaticFlags> {
+        self.flags.load_full()
+    }
+
+    #[inline]
+    ///Replaces every flag with `flags`
+    pub fn store(&self, flags: StaticFlags) {
+        self.flags.store(Arc::new(flags));
+    }
+
+    ///Sets state of single `flag`, keeping the rest
+    pub fn set(&self, flag: impl Into<String>, enabled: bool) {
+        let flag = flag.into();
+        self.flags.rcu(|flags| {
+            let mut flags = StaticFlags::clone(flags);
+            flags.flags.insert(flag.clone(), enabled);
+            flags
+        });
+    }
+}
+
+impl FlagProvider for DynamicFlags {
+    #[inline(always)]
+    fn is_enabled(&self, flag: &str, _: &FlagContext<'_>) -> bool {
+        self.flags.load().get(flag)
+    }
+}
+
+#[derive(Clone, Debug)]
+///Interceptor, which runs wrapped interceptor only when flag is enabled for request
+///
+///Flag is evaluated per request only, hence responses are always passed to wrapped interceptor,
+///the same way as `InterceptorChain` passes responses of requests rejected by earlier interceptor.
+pub struct Flagged<I, P> {
+    flag: Cow<'static, str>,
+    provider: P,
+    inner: I,
+}
+
+impl<I: Interceptor, P: FlagProvider> Flagged<I, P> {
+    #[inline(always)]
+    ///Creates new instance, running `inner` when `flag` is enabled by `provider`
+    pub fn new(flag: impl Into<Cow<'static, str>>, provider: P, inner: I) -> Self {
+        Self {
+            flag: flag.into(),
+            provider,
+            inner,
+        }
+    }
+
+    #[inline(always)]
+    ///Returns name of flag
+    pub fn flag(&self) -> &str {
+        &self.flag
+    }
+
+    #[inline(always)]
+    ///Access provider
+    pub fn provider(&self) -> &P {
+        &self.provider
+    }
+
+    #[inline(always)]
+    ///Access wrapped interceptor
+    pub fn inner(&self) -> &I {
+        &self.inner
+    }
+
+    #[inline(always)]
+    fn is_enabled(&self, method: &str, extensions: &http::Extensions) -> bool {
+        self.provider.is_enabled(&self.flag, &FlagContext::new(method, extensions))
+    }
+}
+
+impl<I: Interceptor, P: FlagProvider> Interceptor for Flagged<I, P> {
+    #[inline]
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        match self.is_enabled("", extensions) {
+            true => self.inner.on_request(headers, extensions),
+            false => None,
+        }
+    }
+
+    #[inline]
+    fn on_request_with_uri(&self, uri: &http::Uri, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        match self.is_enabled(uri.path(), extensions) {
+            true => self.inner.on_request_with_uri(uri, headers, extensions),
+            false => None,
+        }
+    }
+
+    #[inline]
+    fn on_request_lazy(&self, uri: &http::Uri, headers: &mut LazyMetadata<'_>, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        match self.is_enabled(uri.path(), extensions) {
+            true => self.inner.on_request_lazy(uri, headers, extensions),
+            false => None,
+        }
+    }
+
+    #[inline]
+    fn on_request_headers(&self, uri: &http::Uri, headers: &mut http::HeaderMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        match self.is_enabled(uri.path(), extensions) {
+            true => self.inner.on_request_headers(uri, headers, extensions),
+            false => None,
+        }
+    }
+
+    #[inline(always)]
+    fn on_response(&self, status: tonic::Code, headers: &mut http::HeaderMap, extensions: &http::Extensions) {
+        self.inner.on_response(status, headers, extensions)
+    }
+
+    #[inline(always)]
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    #[cfg(feature = "diagnostics")]
+    #[inline(always)]
+    fn on_poll_stats(&self, stats: crate::diagnostics::PollStats) {
+        self.inner.on_poll_stats(stats)
+    }
+}
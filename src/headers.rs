@@ -0,0 +1,815 @@
+//! Metadata transformations
+//!
+//!`Transform` applies list of rules to request metadata and, separately, to response metadata.
+//!It is useful during migration of metadata key:
+//!
+//!```rust
+//!use tonic_interceptor::headers::{Transform, Rule};
+//!
+//!let transform = Transform::builder()
+//!    .request(Rule::copy_if_absent("x-old-tenant", "x-tenant").expect("valid rule"))
+//!    .request(Rule::remove("x-old-tenant").expect("valid rule"))
+//!    .response(Rule::copy_if_absent("x-tenant", "x-old-tenant").expect("valid rule"))
+//!    .build();
+//!```
+//!
+//!Rules apply in order, each one seeing result of previous ones.
+//!Values are moved without decoding, hence binary (`-bin`) values can only be copied to binary keys,
+//!which is checked by rule constructors.
+//!
+//!`ApplyResponseMeta` lets handler set response metadata through `ResponseMeta` extension of its response.
+//!
+//!`EchoRequestHeader` copies values of request keys, such as `x-request-id`, onto response.
+//!
+//!`Normalize` resolves keys, which are delivered multiple times, so that every interceptor sees the same value.
+
+use crate::Interceptor;
+
+use core::fmt;
+use core::convert::TryFrom;
+use std::borrow::Cow;
+use http::header::{HeaderName, HeaderValue};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+///Error of creating `Rule`
+pub struct InvalidRule {
+    message: String,
+}
+
+impl fmt::Display for InvalidRule {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for InvalidRule {
+}
+
+#[inline(always)]
+fn is_bin(key: &HeaderName) -> bool {
+    key.as_str().ends_with("-bin")
+}
+
+fn key(key: &str) -> Result<HeaderName, InvalidRule> {
+    match HeaderName::from_bytes(key.as_bytes()) {
+        Ok(name) if name.as_str() == key => Ok(name),
+        _ => Err(InvalidRule {
+            message: format!("invalid metadata key '{}'", key),
+        }),
+    }
+}
+
+fn pair(from: &str, to: &str) -> Result<(HeaderName, HeaderName), InvalidRule> {
+    let from = key(from)?;
+    let to = key(to)?;
+    if is_bin(&from) != is_bin(&to) {
+        return Err(InvalidRule {
+            message: format!("cannot move metadata '{}' to '{}' of different type", from, to),
+        });
+    }
+    Ok((from, to))
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+///Transformation of metadata
+///
+///Keys with multiple values are handled as a whole.
+pub enum Rule {
+    ///Copies values of `from` to `to`, unless `to` is present
+    CopyIfAbsent {
+        ///Source key
+        from: HeaderName,
+        ///Destination key
+        to: HeaderName,
+    },
+    ///Moves values of `from` to `to`, replacing values of `to`
+    ///
+    ///Does nothing if `from` is not present.
+    Rename {
+        ///Source key
+        from: HeaderName,
+        ///Destination key
+        to: HeaderName,
+    },
+    ///Removes every value of `key`
+    Remove {
+        ///Key to remove
+        key: HeaderName,
+    },
+    ///Sets `value` of `key`, unless it is present
+    ///
+    ///Value of binary key must be base64 encoded.
+    SetIfAbsent {
+        ///Key to set
+        key: HeaderName,
+        ///Value to set
+        value: HeaderValue,
+    },
+}
+
+impl Rule {
+    #[inline]
+    ///Creates `CopyIfAbsent` rule
+    pub fn copy_if_absent(from: &str, to: &str) -> Result<Self, InvalidRule> {
+        let (from, to) = pair(from, to)?;
+        Ok(Rule::CopyIfAbsent { from, to })
+    }
+
+    #[inline]
+    ///Creates `Rename` rule
+    pub fn rename(from: &str, to: &str) -> Result<Self, InvalidRule> {
+        let (from, to) = pair(from, to)?;
+        Ok(Rule::Rename { from, to })
+    }
+
+    #[inline]
+    ///Creates `Remove` rule
+    pub fn remove(key: &str) -> Result<Self, InvalidRule> {
+        Ok(Rule::Remove { key: self::key(key)? })
+    }
+
+    ///Creates `SetIfAbsent` rule with ASCII `value`
+    pub fn set_if_absent(key: &str, value: &str) -> Result<Self, InvalidRule> {
+        let key = self::key(key)?;
+        if is_bin(&key) {
+            return Err(InvalidRule {
+                message: format!("metadata '{}' requires binary value", key),
+            });
+        }
+        match tonic::metadata::AsciiMetadataValue::try_from(value) {
+            Ok(_) => Ok(Rule::SetIfAbsent {
+                key,
+                value: HeaderValue::from_str(value).expect("valid ASCII metadata is valid header value"),
+            }),
+            Err(_) => Err(InvalidRule {
+                message: format!("metadata '{}' has invalid value", key),
+            }),
+        }
+    }
+
+    ///Creates `SetIfAbsent` rule with binary `value`, encoding it as base64
+    pub fn set_bin_if_absent(key: &str, value: &[u8]) -> Result<Self, InvalidRule> {
+        let key = self::key(key)?;
+        if !is_bin(&key) {
+            return Err(InvalidRule {
+                message: format!("metadata '{}' requires ASCII value", key),
+            });
+        }
+        let value = tonic::metadata::BinaryMetadataValue::from_bytes(value);
+        let value = HeaderValue::from_bytes(value.as_encoded_bytes()).expect("base64 is valid header value");
+        Ok(Rule::SetIfAbsent { key, value })
+    }
+
+    ///Applies rule to `headers`
+    pub fn apply(&self, headers: &mut http::HeaderMap) {
+        match self {
+            Rule::CopyIfAbsent { from, to } => {
+                if headers.contains_key(to) {
+                    return;
+                }
+                let values: Vec<_> = headers.get_all(from).iter().cloned().collect();
+                for value in values {
+                    headers.append(to.clone(), value);
+                }
+            },
+            Rule::Rename { from, to } => {
+                if let http::header::Entry::Occupied(entry) = headers.entry(from) {
+                    let values: Vec<_> = entry.remove_entry_mult().1.collect();
+                    headers.remove(to);
+                    for value in values {
+                        headers.append(to.clone(), value);
+                    }
+                }
+            },
+            Rule::Remove { key } => {
+                headers.remove(key);
+            },
+            Rule::SetIfAbsent { key, value } => {
+                if !headers.contains_key(key) {
+                    headers.insert(key.clone(), value.clone());
+                }
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+///Interceptor which transforms request and response metadata
+///
+///Request rules apply before request reaches service, while response rules apply to response headers.
+///Note that response rules cannot see metadata sent in trailers.
+pub struct Transform {
+    request: Vec<Rule>,
+    response: Vec<Rule>,
+}
+
+impl Transform {
+    #[inline(always)]
+    ///Starts building transform
+    pub fn builder() -> TransformBuilder {
+        TransformBuilder {
+            inner: Self::default(),
+        }
+    }
+
+    #[inline(always)]
+    ///Returns rules of request metadata
+    pub fn request_rules(&self) -> &[Rule] {
+        &self.request
+    }
+
+    #[inline(always)]
+    ///Returns rules of response metadata
+    pub fn response_rules(&self) -> &[Rule] {
+        &self.response
+    }
+
+    #[inline]
+    fn apply_request(&self, headers: &mut http::HeaderMap) {
+        for rule in self.request.iter() {
+            rule.apply(headers);
+        }
+    }
+}
+
+impl Interceptor for Transform {
+    #[inline]
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+        if !self.request.is_empty() {
+            let mut raw = crate::compat::metadata_into_headers(core::mem::take(headers));
+            self.apply_request(&mut raw);
+            *headers = crate::compat::metadata_from_headers(raw);
+        }
+        None
+    }
+
+    #[inline]
+    fn on_request_headers(&self, _: &http::Uri, headers: &mut http::HeaderMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+        self.apply_request(headers);
+        None
+    }
+
+    #[inline]
+    fn on_response(&self, _: tonic::Code, headers: &mut http::HeaderMap, _: &http::Extensions) {
+        for rule in self.response.iter() {
+            rule.apply(headers);
+        }
+    }
+}
+
+#[derive(Debug)]
+///Builder of `Transform`
+pub struct TransformBuilder {
+    inner: Transform,
+}
+
+impl TransformBuilder {
+    #[inline]
+    ///Adds rule of request metadata
+    pub fn request(mut self, rule: Rule) -> Self {
+        self.inner.request.push(rule);
+        self
+    }
+
+    #[inline]
+    ///Adds rule of response metadata
+    pub fn response(mut self, rule: Rule) -> Self {
+        self.inner.response.push(rule);
+        self
+    }
+
+    #[inline(always)]
+    ///Builds transform
+    pub fn build(self) -> Transform {
+        self.inner
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum MetaValue {
+    Ascii(Cow<'static, str>),
+    Binary(bytes::Bytes),
+}
+
+//Entries of `ResponseMeta`, stored inline as long as there are few of them
+type MetaEntries = smallvec::SmallVec<[(Cow<'static, str>, MetaValue); 4]>;
+
+#[derive(Default)]
+///Response metadata set by handler, to be written by `ApplyResponseMeta`
+///
+///Insert it into extensions of response:
+///
+///```rust
+///use tonic_interceptor::headers::ResponseMeta;
+///
+///let mut response = tonic::Response::new(());
+///response.extensions_mut().insert(ResponseMeta::set("x-cache", "hit").with("x-cache-age", "10"));
+///```
+///
+///Entries are validated only when applied, hence inserting never fails.
+///Up to 4 entries are stored inline, without allocation.
+pub struct ResponseMeta {
+    //Interceptor has only shared access to response extensions, so entries are drained under lock
+    entries: std::sync::Mutex<MetaEntries>,
+}
+
+impl ResponseMeta {
+    #[inline(always)]
+    ///Creates empty instance
+    pub const fn new() -> Self {
+        Self {
+            entries: std::sync::Mutex::new(MetaEntries::new_const()),
+        }
+    }
+
+    #[inline]
+    ///Creates instance with single ASCII entry
+    pub fn set(key: impl Into<Cow<'static, str>>, value: impl Into<Cow<'static, str>>) -> Self {
+        let mut result = Self::new();
+        result.insert(key, value);
+        result
+    }
+
+    #[inline(always)]
+    fn entries_mut(&mut self) -> &mut MetaEntries {
+        self.entries.get_mut().unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    #[inline]
+    ///Adds ASCII entry
+    pub fn insert(&mut self, key: impl Into<Cow<'static, str>>, value: impl Into<Cow<'static, str>>) {
+        self.entries_mut().push((key.into(), MetaValue::Ascii(value.into())));
+    }
+
+    #[inline]
+    ///Adds binary entry, which is encoded as base64 when written
+    pub fn insert_bin(&mut self, key: impl Into<Cow<'static, str>>, value: impl Into<bytes::Bytes>) {
+        self.entries_mut().push((key.into(), MetaValue::Binary(value.into())));
+    }
+
+    #[inline]
+    ///Adds ASCII entry, returning self
+    pub fn with(mut self, key: impl Into<Cow<'static, str>>, value: impl Into<Cow<'static, str>>) -> Self {
+        self.insert(key, value);
+        self
+    }
+
+    #[inline]
+    ///Returns number of entries
+    pub fn len(&self) -> usize {
+        self.entries().len()
+    }
+
+    #[inline]
+    ///Returns whether there are no entries
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    #[inline(always)]
+    fn entries(&self) -> std::sync::MutexGuard<'_, MetaEntries> {
+        self.entries.lock().unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    #[inline]
+    fn take(&self) -> MetaEntries {
+        core::mem::take(&mut *self.entries())
+    }
+}
+
+impl Clone for ResponseMeta {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            entries: std::sync::Mutex::new(self.entries().clone()),
+        }
+    }
+}
+
+impl fmt::Debug for ResponseMeta {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let entries = self.entries();
+        fmt.debug_map().entries(entries.iter().map(|(key, value)| (key, value))).finish()
+    }
+}
+
+#[inline(always)]
+fn ignore_invalid(_: &str, _: &'static str) {
+}
+
+//Converts entry into header, returning reason if it is invalid
+fn meta_header(key: &str, value: &MetaValue) -> Result<(HeaderName, HeaderValue), &'static str> {
+    let name = match HeaderName::from_bytes(key.as_bytes()) {
+        Ok(name) if name.as_str() == key => name,
+        _ => return Err("invalid key"),
+    };
+    if key.starts_with("grpc-") {
+        return Err("reserved key");
+    }
+
+    match value {
+        MetaValue::Ascii(value) => {
+            if is_bin(&name) {
+                return Err("binary key with ASCII value");
+            }
+            match tonic::metadata::AsciiMetadataValue::try_from(value.as_ref()) {
+                Ok(_) => Ok((name, HeaderValue::from_str(value).expect("valid ASCII metadata is valid header value"))),
+                Err(_) => Err("invalid value"),
+            }
+        },
+        MetaValue::Binary(value) => {
+            if !is_bin(&name) {
+                return Err("ASCII key with binary value");
+            }
+            let value = tonic::metadata::BinaryMetadataValue::from_bytes(value);
+            Ok((name, HeaderValue::from_bytes(value.as_encoded_bytes()).expect("base64 is valid header value")))
+        },
+    }
+}
+
+#[derive(Clone, Copy)]
+///Interceptor which writes `ResponseMeta` of response extensions into response metadata
+///
+///Entries are appended in order of insertion and the container is left empty.
+///Invalid entries are skipped, reporting key and reason to `on_invalid` callback.
+///Keys starting with `grpc-` are reserved and considered invalid.
+///
+///Note that only response headers are available, hence `ResponseMeta` of streaming response must be set before its first message.
+pub struct ApplyResponseMeta<F = fn(&str, &'static str)> {
+    on_invalid: F,
+}
+
+impl ApplyResponseMeta {
+    #[inline(always)]
+    ///Creates new instance, which silently skips invalid entries
+    pub const fn new() -> Self {
+        Self {
+            on_invalid: ignore_invalid,
+        }
+    }
+}
+
+impl Default for ApplyResponseMeta {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F> ApplyResponseMeta<F> {
+    #[inline(always)]
+    ///Sets callback, called with key and reason of every invalid entry
+    pub fn on_invalid<N: Fn(&str, &'static str)>(self, on_invalid: N) -> ApplyResponseMeta<N> {
+        ApplyResponseMeta {
+            on_invalid,
+        }
+    }
+}
+
+impl<F> fmt::Debug for ApplyResponseMeta<F> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("ApplyResponseMeta").finish_non_exhaustive()
+    }
+}
+
+impl<F: Fn(&str, &'static str)> Interceptor for ApplyResponseMeta<F> {
+    #[inline(always)]
+    fn on_request(&self, _: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+        None
+    }
+
+    #[inline(always)]
+    fn on_request_headers(&self, _: &http::Uri, _: &mut http::HeaderMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+        None
+    }
+
+    fn on_response(&self, _: tonic::Code, headers: &mut http::HeaderMap, extensions: &http::Extensions) {
+        let meta = match extensions.get::<ResponseMeta>() {
+            Some(meta) => meta,
+            None => return,
+        };
+
+        for (key, value) in meta.take() {
+            match meta_header(&key, &value) {
+                Ok((name, value)) => {
+                    headers.append(name, value);
+                },
+                Err(reason) => (self.on_invalid)(&key, reason),
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+///Request headers to be echoed onto response of the same call
+///
+///Stored in request extensions, from where `InterceptorService` takes it after calling interceptor,
+///retaining it until response is ready.
+///Entries are then written onto every response: produced by inner service, rejection or error handler's one.
+///Keys, which are already present in response, are left as they are.
+pub struct Echoed {
+    headers: http::HeaderMap,
+}
+
+impl Echoed {
+    #[inline(always)]
+    ///Creates empty instance
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    ///Adds entry
+    pub fn append(&mut self, key: HeaderName, value: HeaderValue) {
+        self.headers.append(key, value);
+    }
+
+    #[inline]
+    ///Sets entry, replacing existing ones of `key`
+    pub fn insert(&mut self, key: HeaderName, value: HeaderValue) {
+        self.headers.insert(key, value);
+    }
+
+    #[inline(always)]
+    ///Returns entries
+    pub fn headers(&self) -> &http::HeaderMap {
+        &self.headers
+    }
+
+    #[inline(always)]
+    ///Returns whether there are no entries
+    pub fn is_empty(&self) -> bool {
+        self.headers.is_empty()
+    }
+
+    ///Writes entries onto response `headers`, unless response already has them
+    pub fn apply(self, headers: &mut http::HeaderMap) {
+        let mut current = None;
+        for (key, value) in self.headers {
+            if let Some(key) = key {
+                current = match headers.contains_key(&key) {
+                    true => None,
+                    false => Some(key),
+                };
+            }
+            if let Some(key) = &current {
+                headers.append(key.clone(), value);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+///Interceptor which echoes values of configured request keys onto response
+///
+///```rust
+///use tonic_interceptor::headers::EchoRequestHeader;
+///
+///const ECHO: EchoRequestHeader = EchoRequestHeader::new(&["x-request-id"]);
+///```
+///
+///Values are captured into `Echoed` request extension, which requires `InterceptorService` to be applied to response.
+///Hence values are echoed even when request is rejected, regardless of `call_on_response_for_rejections`,
+///or when inner service's error is replaced by error handler.
+///
+///Keys must be lower case, as required by HTTP/2.
+pub struct EchoRequestHeader {
+    keys: &'static [&'static str],
+}
+
+impl EchoRequestHeader {
+    #[inline(always)]
+    ///Creates new instance
+    pub const fn new(keys: &'static [&'static str]) -> Self {
+        Self {
+            keys,
+        }
+    }
+
+    #[inline(always)]
+    ///Returns keys to echo
+    pub const fn keys(&self) -> &'static [&'static str] {
+        self.keys
+    }
+
+    //Adds captured entries to `Echoed` extension, merging with one set by other interceptor
+    fn capture<F: FnMut(HeaderName, &mut Echoed)>(&self, extensions: &mut http::Extensions, mut capture: F) {
+        let mut echoed = extensions.remove::<Echoed>().unwrap_or_default();
+        for key in self.keys {
+            if let Ok(key) = HeaderName::from_bytes(key.as_bytes()) {
+                capture(key, &mut echoed);
+            }
+        }
+        if !echoed.is_empty() {
+            extensions.insert(echoed);
+        }
+    }
+}
+
+impl Interceptor for EchoRequestHeader {
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        self.capture(extensions, |key, echoed| {
+            let values: Vec<_> = match is_bin(&key) {
+                true => headers.get_all_bin(key.as_str()).iter().map(|value| HeaderValue::from_bytes(value.as_encoded_bytes())).collect(),
+                false => headers.get_all(key.as_str()).iter().map(|value| HeaderValue::from_bytes(value.as_encoded_bytes())).collect(),
+            };
+            for value in values.into_iter().flatten() {
+                echoed.append(key.clone(), value);
+            }
+        });
+        None
+    }
+
+    fn on_request_headers(&self, _: &http::Uri, headers: &mut http::HeaderMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        self.capture(extensions, |key, echoed| {
+            for value in headers.get_all(&key) {
+                echoed.append(key.clone(), value.clone());
+            }
+        });
+        None
+    }
+
+    #[inline(always)]
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+///Policy of key with multiple values
+pub enum DuplicatePolicy {
+    ///Keeps first value
+    First,
+    ///Keeps last value
+    Last,
+    ///Joins values with separator, which must be valid metadata value
+    ///
+    ///Binary values should be joined with `,` to remain decodable.
+    Join(&'static str),
+    ///Rejects request with `INVALID_ARGUMENT` if values differ, otherwise keeps single value
+    Reject,
+}
+
+#[inline]
+fn is_blank(value: &HeaderValue) -> bool {
+    value.as_bytes().iter().all(u8::is_ascii_whitespace)
+}
+
+//Returns value for comparison, ignoring padding of binary value
+#[inline]
+fn comparable(value: &HeaderValue, bin: bool) -> &[u8] {
+    let value = value.as_bytes();
+    match bin {
+        true => value.iter().rposition(|byte| *byte != b'=').map_or(&value[..0], |end| &value[..=end]),
+        false => value,
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+///Interceptor which resolves keys with multiple values according to configured policy
+///
+///```rust
+///use tonic_interceptor::headers::{DuplicatePolicy, Normalize};
+///
+///let normalize = Normalize::new().key("x-tenant", DuplicatePolicy::Reject).expect("valid key")
+///                                .key("x-forwarded-for", DuplicatePolicy::Join(", ")).expect("valid key")
+///                                .default_policy(DuplicatePolicy::First)
+///                                .blank_as_absent(true);
+///```
+///
+///Proxies may deliver the same key multiple times, after which interceptors reading first or every value
+///of key disagree on its value. Hence it should be the first interceptor of chain.
+///
+///Casing of keys needs no normalization, as `HeaderMap` lower cases them already.
+///Binary values are compared in encoded form, ignoring base64 padding.
+pub struct Normalize {
+    keys: Vec<(HeaderName, DuplicatePolicy)>,
+    default: Option<DuplicatePolicy>,
+    blank_as_absent: bool,
+}
+
+impl Normalize {
+    #[inline(always)]
+    ///Creates new instance without policies
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    ///Sets `policy` of `key`, replacing previous one
+    pub fn key(mut self, key: &str, policy: DuplicatePolicy) -> Result<Self, InvalidRule> {
+        let key = self::key(key)?;
+        self.keys.retain(|(existing, _)| *existing != key);
+        self.keys.push((key, policy));
+        Ok(self)
+    }
+
+    #[inline(always)]
+    ///Sets `policy` of keys without own policy
+    pub fn default_policy(mut self, policy: DuplicatePolicy) -> Self {
+        self.default = Some(policy);
+        self
+    }
+
+    #[inline(always)]
+    ///Sets whether whitespace-only values of normalized keys are removed, as if key was absent
+    pub fn blank_as_absent(mut self, blank_as_absent: bool) -> Self {
+        self.blank_as_absent = blank_as_absent;
+        self
+    }
+
+    #[inline]
+    ///Returns policy of `key`
+    pub fn policy(&self, key: &str) -> Option<&DuplicatePolicy> {
+        match self.keys.iter().find(|(existing, _)| existing.as_str() == key) {
+            Some((_, policy)) => Some(policy),
+            None => self.default.as_ref(),
+        }
+    }
+
+    ///Applies policies to `headers`
+    ///
+    ///Returns error if key with `Reject` policy has different values.
+    pub fn apply(&self, headers: &mut http::HeaderMap) -> Result<(), tonic::Status> {
+        for (key, policy) in self.keys.iter() {
+            self.apply_key(key, policy, headers)?;
+        }
+
+        if let Some(policy) = &self.default {
+            let keys: Vec<HeaderName> = headers.keys().filter(|key| !self.keys.iter().any(|(existing, _)| existing == *key)).cloned().collect();
+            for key in keys.iter() {
+                self.apply_key(key, policy, headers)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn apply_key(&self, key: &HeaderName, policy: &DuplicatePolicy, headers: &mut http::HeaderMap) -> Result<(), tonic::Status> {
+        //Single value is left as it is, unless it is blank
+        let values = headers.get_all(key);
+        let mut iter = values.iter();
+        if iter.next().is_none() || (iter.next().is_none() && !(self.blank_as_absent && values.iter().any(is_blank))) {
+            return Ok(());
+        }
+
+        let values: Vec<HeaderValue> = match headers.entry(key) {
+            http::header::Entry::Occupied(entry) => entry.remove_entry_mult().1.filter(|value| !self.blank_as_absent || !is_blank(value)).collect(),
+            http::header::Entry::Vacant(_) => return Ok(()),
+        };
+        let value = match policy {
+            DuplicatePolicy::First => values.into_iter().next(),
+            DuplicatePolicy::Last => values.into_iter().last(),
+            DuplicatePolicy::Join(separator) => match values.len() {
+                0 | 1 => values.into_iter().next(),
+                _ => {
+                    let mut joined = Vec::new();
+                    for (idx, value) in values.iter().enumerate() {
+                        if idx > 0 {
+                            joined.extend_from_slice(separator.as_bytes());
+                        }
+                        joined.extend_from_slice(value.as_bytes());
+                    }
+                    match HeaderValue::from_bytes(&joined) {
+                        Ok(joined) => Some(joined),
+                        Err(_) => values.into_iter().next(),
+                    }
+                },
+            },
+            DuplicatePolicy::Reject => {
+                let bin = is_bin(key);
+                let mut values = values.into_iter();
+                let first = values.next();
+                if let Some(first) = first.as_ref() {
+                    if values.any(|value| comparable(&value, bin) != comparable(first, bin)) {
+                        return Err(tonic::Status::invalid_argument(format!("metadata '{}' has conflicting values", key)));
+                    }
+                }
+                first
+            },
+        };
+
+        if let Some(value) = value {
+            headers.insert(key.clone(), value);
+        }
+        Ok(())
+    }
+}
+
+impl Interceptor for Normalize {
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+        let mut raw = crate::compat::metadata_into_headers(core::mem::take(headers));
+        let result = self.apply(&mut raw);
+        *headers = crate::compat::metadata_from_headers(raw);
+        result.err()
+    }
+
+    #[inline]
+    fn on_request_headers(&self, _: &http::Uri, headers: &mut http::HeaderMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+        self.apply(headers).err()
+    }
+
+    #[inline(always)]
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
@@ -0,0 +1,158 @@
+//! Identity of authenticated caller
+//!
+//!Built-in authentication interceptors insert `PeerIdentity` into request extensions, so that authorization and logging can be written regardless of mechanism:
+//!
+//!```rust
+//!use tonic_interceptor::identity::PeerIdentity;
+//!
+//!fn audit(extensions: &http::Extensions) -> &str {
+//!    match PeerIdentity::from_extensions(extensions) {
+//!        Some(identity) => identity.subject.as_deref().unwrap_or("anonymous"),
+//!        None => "unauthenticated",
+//!    }
+//!}
+//!```
+//!
+//!Mechanism specific data is available via `extra`, e.g. `auth::OAuthIdentity` of token introspection.
+
+use core::fmt;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+///Mechanism, which established identity
+pub enum Mechanism {
+    ///Bearer token, either opaque or introspected
+    Bearer,
+    ///Signed JWT
+    Jwt,
+    ///Client certificate of mutual TLS
+    Mtls,
+    ///Static API key
+    ApiKey,
+    ///Request signature, such as `auth::HmacSignature`
+    Signature,
+    ///Credentials of peer process over unix domain socket
+    Uds,
+    ///Other mechanism, identified by name
+    Other(&'static str),
+}
+
+impl Mechanism {
+    #[inline]
+    ///Returns lower case name of mechanism
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Bearer => "bearer",
+            Self::Jwt => "jwt",
+            Self::Mtls => "mtls",
+            Self::ApiKey => "api-key",
+            Self::Signature => "signature",
+            Self::Uds => "uds",
+            Self::Other(name) => name,
+        }
+    }
+}
+
+impl fmt::Display for Mechanism {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.write_str(self.name())
+    }
+}
+
+#[cfg_attr(not(feature = "tonic011"), derive(Clone))]
+///Identity of caller, inserted into request extensions by authentication interceptor
+pub struct PeerIdentity {
+    ///Subject, such as user or key id
+    pub subject: Option<String>,
+    ///Issuer of credentials
+    pub issuer: Option<String>,
+    ///Mechanism of authentication
+    pub mechanism: Mechanism,
+    ///Roles granted to caller
+    pub roles: Vec<String>,
+    ///Scopes granted to credentials
+    pub scopes: Vec<String>,
+    ///Mechanism specific data, keyed by type
+    pub extra: http::Extensions,
+}
+
+impl PeerIdentity {
+    #[inline]
+    ///Creates anonymous identity, authenticated by `mechanism`
+    pub fn new(mechanism: Mechanism) -> Self {
+        Self {
+            subject: None,
+            issuer: None,
+            mechanism,
+            roles: Vec::new(),
+            scopes: Vec::new(),
+            extra: http::Extensions::new(),
+        }
+    }
+
+    #[inline(always)]
+    ///Returns identity of request, if authenticated
+    pub fn from_extensions(extensions: &http::Extensions) -> Option<&Self> {
+        extensions.get()
+    }
+
+    #[inline]
+    ///Sets subject
+    pub fn subject(mut self, subject: impl Into<String>) -> Self {
+        self.subject = Some(subject.into());
+        self
+    }
+
+    #[inline]
+    ///Sets issuer
+    pub fn issuer(mut self, issuer: impl Into<String>) -> Self {
+        self.issuer = Some(issuer.into());
+        self
+    }
+
+    #[inline]
+    ///Adds role
+    pub fn role(mut self, role: impl Into<String>) -> Self {
+        self.roles.push(role.into());
+        self
+    }
+
+    #[inline]
+    ///Adds scope
+    pub fn scope(mut self, scope: impl Into<String>) -> Self {
+        self.scopes.push(scope.into());
+        self
+    }
+
+    #[inline]
+    ///Adds mechanism specific `value`
+    pub fn with_extra<T: crate::ext::Extension>(mut self, value: T) -> Self {
+        self.extra.insert(value);
+        self
+    }
+
+    #[inline]
+    ///Returns whether caller has `role`
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.iter().any(|own| own == role)
+    }
+
+    #[inline]
+    ///Returns whether credentials have `scope`
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|granted| granted == scope)
+    }
+}
+
+impl fmt::Debug for PeerIdentity {
+    #[inline]
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("PeerIdentity")
+           .field("subject", &self.subject)
+           .field("issuer", &self.issuer)
+           .field("mechanism", &self.mechanism)
+           .field("roles", &self.roles)
+           .field("scopes", &self.scopes)
+           .finish_non_exhaustive()
+    }
+}
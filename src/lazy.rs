@@ -0,0 +1,190 @@
+//! Lazy metadata view
+//!
+//!`InterceptorService` converts whole `http::HeaderMap` into `MetadataMap` and back for every request.
+//!`LazyMetadata` provides commonly used subset of `MetadataMap` API over `http::HeaderMap` instead,
+//!translating only keys and values that are actually accessed.
+//!
+//!As view cannot hand out references to metadata types, values are returned by value.
+//!Otherwise semantics are the same as of `MetadataMap::from_headers`:
+//!values with opaque (non-ASCII) bytes are returned as they are, with `to_str()` failing on them,
+//!and binary values are not validated until decoded.
+//!Valid binary values are decoded in place and re-encoded without padding, which does not change their decoded bytes.
+
+use tonic::metadata::{AsciiMetadataValue, BinaryMetadataValue, MetadataMap};
+
+use core::convert::TryFrom;
+
+const BIN_KEY: &str = "value-bin";
+
+#[inline(always)]
+fn is_bin(key: &str) -> bool {
+    key.ends_with("-bin")
+}
+
+fn to_ascii(value: &http::HeaderValue) -> AsciiMetadataValue {
+    let mut result = match AsciiMetadataValue::try_from(value.as_bytes()) {
+        Ok(result) => result,
+        //Header value is always valid ASCII metadata value, unless constructed unchecked
+        Err(_) => return via_map("value", value, |map| map.remove("value")),
+    };
+    result.set_sensitive(value.is_sensitive());
+    result
+}
+
+fn to_bin(value: &http::HeaderValue) -> BinaryMetadataValue {
+    let mut result = match crate::base64_decode(value.as_bytes()) {
+        Some(decoded) => BinaryMetadataValue::from_bytes(&decoded),
+        //Binary value cannot be constructed from encoded bytes, other than by metadata map, which is the only way to keep invalid base64 as it is
+        None => return via_map(BIN_KEY, value, |map| map.remove_bin(BIN_KEY)),
+    };
+    result.set_sensitive(value.is_sensitive());
+    result
+}
+
+fn via_map<T, F: FnOnce(&mut MetadataMap) -> Option<T>>(key: &'static str, value: &http::HeaderValue, fun: F) -> T {
+    let mut single = http::HeaderMap::with_capacity(1);
+    single.insert(key, value.clone());
+    fun(&mut crate::compat::metadata_from_headers(single)).expect("value is present")
+}
+
+#[inline]
+fn from_value(value: &[u8], is_sensitive: bool) -> http::HeaderValue {
+    let mut result = http::HeaderValue::from_bytes(value).expect("metadata value is valid header value");
+    result.set_sensitive(is_sensitive);
+    result
+}
+
+#[derive(Clone, Debug)]
+///Key and value of `LazyMetadata` entry
+pub enum KeyAndValue<'a> {
+    ///ASCII entry
+    Ascii(&'a str, AsciiMetadataValue),
+    ///Binary entry
+    Binary(&'a str, BinaryMetadataValue),
+}
+
+#[derive(Debug)]
+///Metadata view over `http::HeaderMap`
+pub struct LazyMetadata<'a> {
+    headers: &'a mut http::HeaderMap,
+}
+
+impl<'a> LazyMetadata<'a> {
+    #[inline(always)]
+    ///Creates new instance
+    pub fn new(headers: &'a mut http::HeaderMap) -> Self {
+        Self {
+            headers
+        }
+    }
+
+    #[inline(always)]
+    ///Access underlying headers
+    pub fn headers(&self) -> &http::HeaderMap {
+        self.headers
+    }
+
+    #[inline(always)]
+    ///Access underlying headers mutably
+    pub fn headers_mut(&mut self) -> &mut http::HeaderMap {
+        self.headers
+    }
+
+    #[inline]
+    ///Runs `fun` with headers converted into `MetadataMap`
+    pub fn with_metadata<R, F: FnOnce(&mut MetadataMap) -> R>(&mut self, fun: F) -> R {
+        crate::raw::with_metadata(self.headers, fun)
+    }
+
+    #[inline(always)]
+    ///Returns number of values
+    pub fn len(&self) -> usize {
+        self.headers.len()
+    }
+
+    #[inline(always)]
+    ///Returns whether there are no values
+    pub fn is_empty(&self) -> bool {
+        self.headers.is_empty()
+    }
+
+    #[inline(always)]
+    ///Returns whether `key` is present, regardless of its encoding
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.headers.contains_key(key)
+    }
+
+    #[inline]
+    ///Gets first value of ASCII `key`
+    pub fn get(&self, key: &str) -> Option<AsciiMetadataValue> {
+        if is_bin(key) {
+            return None;
+        }
+        self.headers.get(key).map(to_ascii)
+    }
+
+    #[inline]
+    ///Gets first value of binary `key`
+    pub fn get_bin(&self, key: &str) -> Option<BinaryMetadataValue> {
+        if !is_bin(key) {
+            return None;
+        }
+        self.headers.get(key).map(to_bin)
+    }
+
+    #[inline]
+    ///Inserts ASCII `value`, replacing all existing values of `key` and returning first of them.
+    ///
+    ///Panics if `key` is not valid ASCII metadata key.
+    pub fn insert(&mut self, key: &'static str, value: AsciiMetadataValue) -> Option<AsciiMetadataValue> {
+        assert!(!is_bin(key), "ascii metadata key must not end with -bin");
+        let value = from_value(value.as_encoded_bytes(), value.is_sensitive());
+        self.headers.insert(key, value).as_ref().map(to_ascii)
+    }
+
+    #[inline]
+    ///Inserts binary `value`, replacing all existing values of `key` and returning first of them.
+    ///
+    ///Panics if `key` is not valid binary metadata key.
+    pub fn insert_bin(&mut self, key: &'static str, value: BinaryMetadataValue) -> Option<BinaryMetadataValue> {
+        assert!(is_bin(key), "binary metadata key must end with -bin");
+        let value = from_value(value.as_encoded_bytes(), value.is_sensitive());
+        self.headers.insert(key, value).as_ref().map(to_bin)
+    }
+
+    #[inline]
+    ///Removes all values of ASCII `key`, returning first of them
+    pub fn remove(&mut self, key: &str) -> Option<AsciiMetadataValue> {
+        if is_bin(key) {
+            return None;
+        }
+        self.headers.remove(key).as_ref().map(to_ascii)
+    }
+
+    #[inline]
+    ///Removes all values of binary `key`, returning first of them
+    pub fn remove_bin(&mut self, key: &str) -> Option<BinaryMetadataValue> {
+        if !is_bin(key) {
+            return None;
+        }
+        self.headers.remove(key).as_ref().map(to_bin)
+    }
+
+    #[inline]
+    ///Iterates over keys, yielding every key once
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.headers.keys().map(http::header::HeaderName::as_str)
+    }
+
+    #[inline]
+    ///Iterates over all entries, translating them as they are yielded
+    pub fn iter(&self) -> impl Iterator<Item = KeyAndValue<'_>> {
+        self.headers.iter().map(|(key, value)| {
+            let key = key.as_str();
+            match is_bin(key) {
+                true => KeyAndValue::Binary(key, to_bin(value)),
+                false => KeyAndValue::Ascii(key, to_ascii(value)),
+            }
+        })
+    }
+}
@@ -1,14 +1,253 @@
 //! Improved tonic interceptor
+//!
+//!## Tonic version
+//!
+//!Version of tonic, together with `http` and `http-body` it is built upon, is selected by one of mutually exclusive features:
+//!
+//!- `tonic011` - tonic 0.11, http 0.2 and http-body 0.4. Default.
+//!- `tonic012` - tonic 0.12, http 1 and http-body 1.
+//!
+//!Selected `tonic` and `http` are re-exported by this crate.
+//!
+//!Features built upon other crates keep their versions regardless:
+//!`tower-http` requires `tonic011`, while `prost` based features use prost 0.12.
+#![forbid(unsafe_code)]
 #![warn(missing_docs)]
-#![cfg_attr(feature = "cargo-clippy", allow(clippy::style))]
+#![allow(clippy::style, clippy::result_large_err)]
+
+#[cfg(all(feature = "tonic011", feature = "tonic012"))]
+compile_error!("Features `tonic011` and `tonic012` are mutually exclusive");
+#[cfg(not(any(feature = "tonic011", feature = "tonic012")))]
+compile_error!("One of features `tonic011` or `tonic012` must be enabled");
+#[cfg(all(feature = "tower-http", feature = "tonic012", not(feature = "tonic011")))]
+compile_error!("Feature `tower-http` is built upon tower-http 0.4, which requires `tonic011`");
+
+#[cfg(feature = "tonic011")]
+///Version of tonic selected by `tonic011` feature
+pub extern crate tonic;
+#[cfg(feature = "tonic011")]
+///Version of http selected by `tonic011` feature
+pub extern crate http;
+
+#[cfg(all(feature = "tonic012", not(feature = "tonic011")))]
+///Version of tonic selected by `tonic012` feature
+pub extern crate tonic012 as tonic;
+#[cfg(all(feature = "tonic012", not(feature = "tonic011")))]
+///Version of http selected by `tonic012` feature
+pub extern crate http1 as http;
+#[cfg(all(feature = "tonic012", not(feature = "tonic011")))]
+extern crate http_body1 as http_body;
 
 use core::task;
 use core::pin::Pin;
 use core::future::Future;
 
+#[cfg(any(feature = "tokio", feature = "wasm"))]
+mod proto;
+mod compat;
+pub mod timeout;
+pub mod matcher;
+pub mod raw;
+pub use raw::{RawInterceptor, Raw, raw_interceptor};
+pub mod deferred;
+pub use deferred::{DeferredInterceptor, Deferred, HeaderOps, deferred_interceptor};
+pub mod lazy;
+pub use lazy::LazyMetadata;
+pub mod metadata;
+pub use metadata::MetadataMapExt;
+pub mod ext;
+pub mod reject;
+pub mod asynchronous;
+pub mod builder;
+pub mod chain;
+pub mod control;
+pub mod observe;
+pub mod policy;
+pub mod headers;
+pub mod routing;
+pub mod redact;
+pub mod connection;
+pub mod limit;
+pub mod flags;
+pub mod profile;
+pub mod identity;
+pub mod auth;
+pub mod authz;
+pub mod tls;
+pub use chain::InterceptorChain;
+pub use builder::InterceptorBuilder;
+pub use asynchronous::{AsyncInterceptor, async_interceptor, from_fn, from_fn_with_state};
+pub mod client;
+#[cfg(feature = "tokio")]
+pub mod deadline;
+#[cfg(feature = "tokio")]
+pub mod propagation;
+#[cfg(feature = "tokio")]
+pub mod lifecycle;
+#[cfg(feature = "tokio")]
+pub mod reload;
+#[cfg(feature = "tokio")]
+pub mod shadow;
+pub mod net;
+pub mod response;
+pub mod debugging;
+pub mod timer;
+pub mod validate;
+pub mod body;
+#[cfg(feature = "opentelemetry")]
+pub mod otel;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(feature = "details")]
+pub mod details;
+#[cfg(feature = "prost-reflect")]
+pub mod descriptor;
+#[cfg(feature = "cache")]
+pub mod cache;
+#[cfg(feature = "serde")]
+pub mod config;
+#[cfg(feature = "diagnostics")]
+pub mod diagnostics;
+#[cfg(not(feature = "diagnostics"))]
+mod diagnostics;
+#[cfg(feature = "derive")]
+pub use tonic_interceptor_derive::Interceptor;
+
+#[cfg(feature = "derive")]
+#[doc(hidden)]
+pub mod __private {
+    pub use tonic;
+    pub use http;
+    pub use crate::__forward_poll_stats as forward_poll_stats;
+}
+
+#[cfg(all(feature = "derive", feature = "diagnostics"))]
+#[doc(hidden)]
+#[macro_export]
+//Defines `on_poll_stats` of derived interceptor, forwarding to every field
+macro_rules! __forward_poll_stats {
+    ($($ty:ty => $member:tt),*) => {
+        #[inline]
+        fn on_poll_stats(&self, stats: $crate::diagnostics::PollStats) {
+            $(<$ty as $crate::Interceptor>::on_poll_stats(&self.$member, stats);)*
+        }
+    };
+}
+
+#[cfg(all(feature = "derive", not(feature = "diagnostics")))]
+#[doc(hidden)]
+#[macro_export]
+//Without `diagnostics` there is no `on_poll_stats` to define
+macro_rules! __forward_poll_stats {
+    ($($tokens:tt)*) => {};
+}
+
 const GRPC_STATUS_HEADER_CODE: &str = "grpc-status";
+const GRPC_STATUS_MESSAGE_HEADER: &str = "grpc-message";
+const GRPC_CONTENT_TYPE: &str = "application/grpc";
+
+//Percent-encodes message as required by gRPC spec: everything outside of visible ASCII and space, and `%` itself
+fn encode_grpc_message(message: &str) -> http::HeaderValue {
+    const HEX: &[u8; 16] = b"0123456789ABCDEF";
+
+    let mut result = Vec::with_capacity(message.len());
+    for byte in message.bytes() {
+        match byte {
+            b'%' => result.extend_from_slice(b"%25"),
+            0x20..=0x7e => result.push(byte),
+            _ => result.extend_from_slice(&[b'%', HEX[(byte >> 4) as usize], HEX[(byte & 0xf) as usize]]),
+        }
+    }
+    http::HeaderValue::from_maybe_shared(bytes::Bytes::from(result)).expect("percent-encoded message is valid header value")
+}
+
+//Writes status into headers of rejection response
+//
+//Tonic doesn't escape `%` in message, hence it is always written by us.
+fn add_status_headers(status: &tonic::Status, headers: &mut http::HeaderMap) {
+    if compat::add_status_header(status, headers).is_err() {
+        //Should never happen, but status code must not be lost in any case
+        headers.insert(GRPC_STATUS_HEADER_CODE, http::HeaderValue::from(status.code() as i32));
+        headers.insert(GRPC_STATUS_MESSAGE_HEADER, http::HeaderValue::from_static("Invalid status"));
+        return;
+    }
+
+    if !status.message().is_empty() {
+        headers.insert(GRPC_STATUS_MESSAGE_HEADER, encode_grpc_message(status.message()));
+    }
+}
+
+//Returns whether content type is one of gRPC or gRPC-Web types
+pub(crate) fn is_grpc_content_type(value: &http::HeaderValue) -> bool {
+    let value = value.as_bytes();
+    if value.len() < GRPC_CONTENT_TYPE.len() || !value[..GRPC_CONTENT_TYPE.len()].eq_ignore_ascii_case(GRPC_CONTENT_TYPE.as_bytes()) {
+        return false;
+    }
+    match value.get(GRPC_CONTENT_TYPE.len()) {
+        None | Some(b'+') | Some(b'-') | Some(b';') => true,
+        Some(_) => false,
+    }
+}
+
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+//Encodes `value` as padded base64
+pub(crate) fn base64(value: &[u8]) -> String {
+    let mut result = String::with_capacity(value.len().div_ceil(3) * 4);
+    for chunk in value.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (idx, byte)| bits | (u32::from(*byte) << (16 - idx * 8)));
+        for idx in 0..4 {
+            match idx <= chunk.len() {
+                true => result.push(BASE64_ALPHABET[(bits >> (18 - idx * 6)) as usize & 0x3f] as char),
+                false => result.push('='),
+            }
+        }
+    }
+    result
+}
+
+//Decodes standard base64 `value` the same way as binary metadata does: padding is optional, and might be partial,
+//but cannot exceed that of padded encoding, while unused bits of last symbol must be zero
+pub(crate) fn base64_decode(value: &[u8]) -> Option<Vec<u8>> {
+    let padding = value.iter().rev().take_while(|symbol| **symbol == b'=').count();
+    let value = &value[..value.len() - padding];
+    match (value.len() % 4, padding) {
+        (0, 0) | (2, 0..=2) | (3, 0..=1) => (),
+        _ => return None,
+    }
+
+    let mut result = Vec::with_capacity(value.len() * 3 / 4);
+    for chunk in value.chunks(4) {
+        let mut bits = 0u32;
+        for (idx, symbol) in chunk.iter().enumerate() {
+            let sextet = match symbol {
+                b'A'..=b'Z' => symbol - b'A',
+                b'a'..=b'z' => symbol - b'a' + 26,
+                b'0'..=b'9' => symbol - b'0' + 52,
+                b'+' => 62,
+                b'/' => 63,
+                _ => return None,
+            };
+            bits |= u32::from(sextet) << (18 - idx * 6);
+        }
+        //Symbols carry 6 bits, of which only full bytes are kept
+        let len = chunk.len() * 6 / 8;
+        if bits & (0xff_ffff >> (len * 8)) != 0 {
+            return None;
+        }
+        result.extend_from_slice(&bits.to_be_bytes()[1..=len]);
+    }
+    Some(result)
+}
 
 ///Tonic interceptor
+///
+///Conversion of headers into `MetadataMap` and back is lossless, regardless of number of headers:
+///every value of every key is preserved in its original order, including keys that are not valid metadata keys
+///and `-bin` values that are not valid base64.
+///
+///Implemented for references and smart pointers to interceptors.
+///Closures are not interceptors on their own, wrap them into `InterceptorFn`, `OnResponse` or use `InterceptorBuilder`.
 pub trait Interceptor {
     ///Callback on incoming request, allowing you to modify headers or extensions
     ///
@@ -17,57 +256,492 @@ pub trait Interceptor {
     ///Returning status will preempt request handling and immediately returns status
     fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status>;
 
+    #[inline(always)]
+    ///Callback on incoming request, with access to its URI
+    ///
+    ///By default forwards to `on_request`.
+    ///Override it when interceptor depends on method path.
+    ///
+    ///Interceptors of this crate, which depend on method path, pass request through when called by `on_request` alone,
+    ///except for access control of `authz`, `auth::HmacSignature` and `config`'s `MethodAllowlist`, which rejects it with `INTERNAL`.
+    fn on_request_with_uri(&self, _uri: &http::Uri, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        self.on_request(headers, extensions)
+    }
+
+    #[inline(always)]
+    ///Callback on incoming request, with lazy metadata view over its headers
+    ///
+    ///By default converts headers for `on_request_with_uri`.
+    ///Override it to translate only accessed keys.
+    fn on_request_lazy(&self, uri: &http::Uri, headers: &mut LazyMetadata<'_>, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        headers.with_metadata(|metadata| self.on_request_with_uri(uri, metadata, extensions))
+    }
+
+    #[inline(always)]
+    ///Callback on incoming request's headers, before their conversion into `MetadataMap`
+    ///
+    ///This is what service calls, by default forwarding to `on_request_lazy`.
+    ///Override it to avoid conversion, see `RawInterceptor`.
+    fn on_request_headers(&self, uri: &http::Uri, headers: &mut http::HeaderMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        self.on_request_lazy(uri, &mut LazyMetadata::new(headers), extensions)
+    }
+
     ///Callback when response is being returned
     fn on_response(&self, status: tonic::Code, _headers: &mut http::HeaderMap, _extensions: &http::Extensions);
+
+    #[inline(always)]
+    ///Returns name of interceptor, used as label by `profile::Profiled`
+    ///
+    ///By default it is name of type.
+    fn name(&self) -> &'static str {
+        core::any::type_name::<Self>()
+    }
+
+    #[cfg(feature = "diagnostics")]
+    #[inline(always)]
+    ///Callback with statistics of polling `InterceptorFut`, once it completes or is dropped
+    ///
+    ///Called for every request of `InterceptorService`, including rejected ones when `on_response` is called for them.
+    fn on_poll_stats(&self, _stats: diagnostics::PollStats) {
+    }
+}
+
+macro_rules! impl_pointer {
+    ($($ty:ty),+) => {
+        $(
+            impl<I: Interceptor + ?Sized> Interceptor for $ty {
+                #[inline(always)]
+                fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+                    Interceptor::on_request(&**self, headers, extensions)
+                }
+
+                #[inline(always)]
+                fn on_request_with_uri(&self, uri: &http::Uri, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+                    Interceptor::on_request_with_uri(&**self, uri, headers, extensions)
+                }
+
+                #[inline(always)]
+                fn on_request_lazy(&self, uri: &http::Uri, headers: &mut LazyMetadata<'_>, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+                    Interceptor::on_request_lazy(&**self, uri, headers, extensions)
+                }
+
+                #[inline(always)]
+                fn on_request_headers(&self, uri: &http::Uri, headers: &mut http::HeaderMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+                    Interceptor::on_request_headers(&**self, uri, headers, extensions)
+                }
+
+                #[inline(always)]
+                fn on_response(&self, status: tonic::Code, headers: &mut http::HeaderMap, extensions: &http::Extensions) {
+                    Interceptor::on_response(&**self, status, headers, extensions)
+                }
+
+                #[inline(always)]
+                fn name(&self) -> &'static str {
+                    Interceptor::name(&**self)
+                }
+
+                #[cfg(feature = "diagnostics")]
+                #[inline(always)]
+                fn on_poll_stats(&self, stats: diagnostics::PollStats) {
+                    Interceptor::on_poll_stats(&**self, stats)
+                }
+            }
+        )+
+    };
+}
+
+//There is no blanket implementation for closures (see `InterceptorFn`, `OnResponse` and `InterceptorBuilder`),
+//which would overlap with these as `Fn` is implemented for references to closures.
+impl_pointer!(&I, &mut I, Box<I>, std::rc::Rc<I>, std::sync::Arc<I>);
+
+///Type erased interceptor, which is not required to be `Send` or `Sync`
+///
+///Suitable for single threaded runtime, as `InterceptorService` imposes no `Send` or `Sync` bounds on its own.
+pub type LocalBoxedInterceptor = std::rc::Rc<dyn Interceptor>;
+
+///Type erased interceptor, which can be shared between threads
+pub type BoxedInterceptor = std::sync::Arc<dyn Interceptor + Send + Sync>;
+
+///Body of response to rejected request
+///
+///Implemented for every `Default` body.
+///Note that `tonic::body::BoxBody::default()` boxes zero-sized body, hence it doesn't allocate.
+pub trait EmptyBody {
+    ///Creates empty body
+    fn empty() -> Self;
 }
 
-impl<I: Interceptor> Interceptor for std::sync::Arc<I> {
+impl<B: Default> EmptyBody for B {
     #[inline(always)]
-    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
-        Interceptor::on_request(self.as_ref(), headers, extensions)
+    fn empty() -> Self {
+        Self::default()
     }
+}
 
+///Factory of response body for rejected request
+///
+///Implemented for every `Fn() -> B`, which allows to use bodies that are not `EmptyBody`.
+pub trait BodyFactory<B> {
+    ///Creates body
+    fn create(&self) -> B;
+}
+
+impl<B, F: Fn() -> B> BodyFactory<B> for F {
     #[inline(always)]
-    fn on_response(&self, status: tonic::Code, headers: &mut http::HeaderMap, extensions: &http::Extensions) {
-        Interceptor::on_response(self.as_ref(), status, headers, extensions)
+    fn create(&self) -> B {
+        (self)()
+    }
+}
+
+#[derive(Clone, Copy, Default, Debug)]
+///Body factory, which uses `EmptyBody`
+pub struct DefaultBody;
+
+impl<B: EmptyBody> BodyFactory<B> for DefaultBody {
+    #[inline(always)]
+    fn create(&self) -> B {
+        B::empty()
     }
 }
 
 ///Layer
-#[derive(Clone)]
-#[repr(transparent)]
-pub struct InterceptorLayer<I>(I);
+#[derive(Clone, Copy)]
+pub struct InterceptorLayer<I, B = DefaultBody> {
+    interceptor: I,
+    on_rejection: bool,
+    capture: Option<ext::MetadataCapture>,
+    body: B,
+}
+
+impl<I> InterceptorLayer<I> {
+    #[inline(always)]
+    ///Creates new instance
+    pub const fn new(interceptor: I) -> Self {
+        Self {
+            interceptor,
+            on_rejection: false,
+            capture: None,
+            body: DefaultBody,
+        }
+    }
+}
+
+impl<I, B> InterceptorLayer<I, B> {
+    #[inline(always)]
+    ///Access interceptor
+    pub fn get_ref(&self) -> &I {
+        &self.interceptor
+    }
+
+    #[inline(always)]
+    ///Access interceptor mutably
+    pub fn get_mut(&mut self) -> &mut I {
+        &mut self.interceptor
+    }
+
+    #[inline(always)]
+    ///Returns interceptor
+    pub fn into_inner(self) -> I {
+        self.interceptor
+    }
+
+    #[inline(always)]
+    ///Sets whether `on_response` is called for requests rejected by `on_request`
+    ///
+    ///See `InterceptorService::call_on_response_for_rejections`.
+    pub fn call_on_response_for_rejections(mut self, value: bool) -> Self {
+        self.on_rejection = value;
+        self
+    }
+
+    #[inline(always)]
+    ///Sets which request metadata to capture for `on_response`
+    ///
+    ///See `InterceptorService::capture_request_metadata`.
+    pub fn capture_request_metadata(mut self, capture: impl Into<Option<ext::MetadataCapture>>) -> Self {
+        self.capture = capture.into();
+        self
+    }
+
+    #[inline]
+    ///Sets factory of rejection response body
+    ///
+    ///See `InterceptorService::with_body`.
+    pub fn with_body<B2>(self, body: B2) -> InterceptorLayer<I, B2> {
+        InterceptorLayer {
+            interceptor: self.interceptor,
+            on_rejection: self.on_rejection,
+            capture: self.capture,
+            body,
+        }
+    }
+}
+
+impl<I: core::fmt::Debug, B> core::fmt::Debug for InterceptorLayer<I, B> {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("InterceptorLayer").field("interceptor", &self.interceptor).field("on_rejection", &self.on_rejection).finish_non_exhaustive()
+    }
+}
+
+impl<S, I: Interceptor + Clone, B: Clone> tower_layer::Layer<S> for InterceptorLayer<I, B> {
+    type Service = InterceptorService<I, S, PropagateError, B>;
+
+    #[inline(always)]
+    fn layer(&self, inner: S) -> Self::Service {
+        InterceptorService::new(self.interceptor.clone(), inner).call_on_response_for_rejections(self.on_rejection).capture_request_metadata(self.capture).with_body(self.body.clone())
+    }
+}
+
+///Layer, which shares single interceptor between all services
+///
+///Unlike `InterceptorLayer`, it doesn't require interceptor to be `Clone`.
+pub struct ArcInterceptorLayer<I, B = DefaultBody> {
+    interceptor: std::sync::Arc<I>,
+    on_rejection: bool,
+    capture: Option<ext::MetadataCapture>,
+    body: B,
+}
+
+impl<I, B> ArcInterceptorLayer<I, B> {
+    #[inline(always)]
+    ///Access interceptor
+    pub fn get_ref(&self) -> &I {
+        &self.interceptor
+    }
+
+    #[inline(always)]
+    ///Access interceptor mutably, if it is not shared yet
+    pub fn get_mut(&mut self) -> Option<&mut I> {
+        std::sync::Arc::get_mut(&mut self.interceptor)
+    }
+
+    #[inline(always)]
+    ///Returns shared interceptor
+    pub fn into_inner(self) -> std::sync::Arc<I> {
+        self.interceptor
+    }
+
+    #[inline(always)]
+    ///Sets whether `on_response` is called for requests rejected by `on_request`
+    ///
+    ///See `InterceptorService::call_on_response_for_rejections`.
+    pub fn call_on_response_for_rejections(mut self, value: bool) -> Self {
+        self.on_rejection = value;
+        self
+    }
+
+    #[inline(always)]
+    ///Sets which request metadata to capture for `on_response`
+    ///
+    ///See `InterceptorService::capture_request_metadata`.
+    pub fn capture_request_metadata(mut self, capture: impl Into<Option<ext::MetadataCapture>>) -> Self {
+        self.capture = capture.into();
+        self
+    }
+
+    #[inline]
+    ///Sets factory of rejection response body
+    ///
+    ///See `InterceptorService::with_body`.
+    pub fn with_body<B2>(self, body: B2) -> ArcInterceptorLayer<I, B2> {
+        ArcInterceptorLayer {
+            interceptor: self.interceptor,
+            on_rejection: self.on_rejection,
+            capture: self.capture,
+            body,
+        }
+    }
+}
+
+impl<I, B: Clone> Clone for ArcInterceptorLayer<I, B> {
+    #[inline(always)]
+    fn clone(&self) -> Self {
+        Self {
+            interceptor: self.interceptor.clone(),
+            on_rejection: self.on_rejection,
+            capture: self.capture,
+            body: self.body.clone(),
+        }
+    }
+}
+
+impl<I: core::fmt::Debug, B> core::fmt::Debug for ArcInterceptorLayer<I, B> {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("ArcInterceptorLayer").field("interceptor", &self.interceptor).field("on_rejection", &self.on_rejection).finish_non_exhaustive()
+    }
+}
 
-impl<S, I: Interceptor + Clone> tower_layer::Layer<S> for InterceptorLayer<I> {
-    type Service = InterceptorService<I, S>;
+impl<S, I: Interceptor, B: Clone> tower_layer::Layer<S> for ArcInterceptorLayer<I, B> {
+    type Service = InterceptorService<std::sync::Arc<I>, S, PropagateError, B>;
 
     #[inline(always)]
     fn layer(&self, inner: S) -> Self::Service {
-        InterceptorService::new(self.0.clone(), inner)
+        InterceptorService::new(self.interceptor.clone(), inner).call_on_response_for_rejections(self.on_rejection).capture_request_metadata(self.capture).with_body(self.body.clone())
+    }
+}
+
+///Handler of inner service errors, see `InterceptorService::with_error_handler`
+pub trait ErrorHandler<E> {
+    ///Returns status to respond with, instead of propagating `error`
+    fn on_error(&self, error: &E) -> Option<tonic::Status>;
+}
+
+impl<E, F: Fn(&E) -> Option<tonic::Status>> ErrorHandler<E> for F {
+    #[inline(always)]
+    fn on_error(&self, error: &E) -> Option<tonic::Status> {
+        (self)(error)
+    }
+}
+
+#[derive(Clone, Copy, Default, Debug)]
+///Error handler, which propagates every error as it is
+pub struct PropagateError;
+
+impl<E> ErrorHandler<E> for PropagateError {
+    #[inline(always)]
+    fn on_error(&self, _: &E) -> Option<tonic::Status> {
+        None
     }
 }
 
 ///Service
-pub struct InterceptorService<I, S> {
+///
+///It is `Send` and `Sync` as long as interceptor and inner service are, and its future is `Send` as long as
+///interceptor and inner service's future are, which allows to use `!Send` interceptors on single threaded runtime.
+pub struct InterceptorService<I, S, H = PropagateError, B = DefaultBody> {
     interceptor: I,
+    on_rejection: bool,
+    capture: Option<ext::MetadataCapture>,
+    handler: H,
+    body: B,
     inner: S
 }
 
+impl<I: Clone, S: Clone, H: Clone, B: Clone> Clone for InterceptorService<I, S, H, B> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            interceptor: self.interceptor.clone(),
+            on_rejection: self.on_rejection,
+            capture: self.capture,
+            handler: self.handler.clone(),
+            body: self.body.clone(),
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<I: core::fmt::Debug, S: core::fmt::Debug, H, B> core::fmt::Debug for InterceptorService<I, S, H, B> {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("InterceptorService").field("interceptor", &self.interceptor).field("on_rejection", &self.on_rejection).field("inner", &self.inner).finish_non_exhaustive()
+    }
+}
+
 impl<I, S> InterceptorService<I, S> {
     #[inline(always)]
     ///Creates new instance
     pub fn new(interceptor: I, inner: S) -> Self {
         Self {
             interceptor,
+            on_rejection: false,
+            capture: None,
+            handler: PropagateError,
+            body: DefaultBody,
             inner
         }
     }
 }
 
-impl<ReqBody, ResBody: Default, S: tower_service::Service<http::Request<ReqBody>, Response = http::Response<ResBody>>, I: Interceptor + Clone> tower_service::Service<http::Request<ReqBody>> for InterceptorService<I, S> {
+impl<I, S, H, B> InterceptorService<I, S, H, B> {
+    #[inline(always)]
+    ///Access inner service
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    #[inline(always)]
+    ///Access inner service mutably
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    #[inline(always)]
+    ///Returns inner service
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    #[inline(always)]
+    ///Access interceptor
+    pub fn interceptor_ref(&self) -> &I {
+        &self.interceptor
+    }
+
+    #[inline(always)]
+    ///Access interceptor mutably
+    pub fn interceptor_mut(&mut self) -> &mut I {
+        &mut self.interceptor
+    }
+
+    #[inline(always)]
+    ///Sets whether `on_response` is called for requests rejected by `on_request`
+    ///
+    ///When enabled, `on_response` receives headers of rejection response and empty extensions.
+    ///Disabled by default, so that `on_response` is only called after inner service is called.
+    pub fn call_on_response_for_rejections(mut self, value: bool) -> Self {
+        self.on_rejection = value;
+        self
+    }
+
+    #[inline(always)]
+    ///Sets which request metadata to capture for `on_response`
+    ///
+    ///When set, `ext::RequestMetadataSnapshot` of request headers is taken before interceptor is called,
+    ///and carried to `on_response`, whether response is produced by inner service, rejection or error handler.
+    ///Disabled by default.
+    pub fn capture_request_metadata(mut self, capture: impl Into<Option<ext::MetadataCapture>>) -> Self {
+        self.capture = capture.into();
+        self
+    }
+
+    #[inline]
+    ///Sets handler of inner service errors
+    ///
+    ///When handler returns status, error is replaced with the same response as for rejected request,
+    ///which is passed to `on_response`.
+    ///Otherwise error is propagated, which is default behavior.
+    pub fn with_error_handler<H2>(self, handler: H2) -> InterceptorService<I, S, H2, B> {
+        InterceptorService {
+            interceptor: self.interceptor,
+            on_rejection: self.on_rejection,
+            capture: self.capture,
+            handler,
+            body: self.body,
+            inner: self.inner,
+        }
+    }
+
+    #[inline]
+    ///Sets factory of rejection response body
+    ///
+    ///By default body is created using `EmptyBody`.
+    ///Use it when service's body is not `Default` (e.g. under body-adapting layers).
+    pub fn with_body<B2>(self, body: B2) -> InterceptorService<I, S, H, B2> {
+        InterceptorService {
+            interceptor: self.interceptor,
+            on_rejection: self.on_rejection,
+            capture: self.capture,
+            handler: self.handler,
+            body,
+            inner: self.inner,
+        }
+    }
+}
+
+impl<ReqBody, ResBody, S: tower_service::Service<http::Request<ReqBody>, Response = http::Response<ResBody>>, I: Interceptor + Clone, H: ErrorHandler<S::Error> + Clone, B: BodyFactory<ResBody> + Clone> tower_service::Service<http::Request<ReqBody>> for InterceptorService<I, S, H, B> {
     type Response = S::Response;
     type Error = S::Error;
-    type Future = InterceptorFut<I, S::Future>;
+    type Future = InterceptorFut<I, S::Future, H, B>;
 
     #[inline(always)]
     fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
@@ -77,60 +751,174 @@ impl<ReqBody, ResBody: Default, S: tower_service::Service<http::Request<ReqBody>
     #[inline(always)]
     fn call(&mut self, mut req: http::Request<ReqBody>) -> Self::Future {
         let (mut parts, body) = req.into_parts();
+        let mut rejection = Rejection {
+            //Captured before interceptor has a chance to modify it
+            template: response::ResponseTemplate::from_parts(&parts.headers, parts.version),
+            body: self.body.clone(),
+            echoed: None,
+            carried: None,
+        };
+
+        if let Some(capture) = self.capture.as_ref() {
+            let snapshot = capture.capture(&parts.headers);
+            ext::Carried::of_request(&mut parts.extensions).insert(snapshot);
+        }
 
-        let mut headers = tonic::metadata::MetadataMap::from_headers(parts.headers);
-        match self.interceptor.on_request(&mut headers, &mut parts.extensions) {
+        let diagnostics = diagnostics::PollRecorder::new();
+        let intercepted = self.interceptor.on_request_headers(&parts.uri, &mut parts.headers, &mut parts.extensions);
+        rejection.echoed = parts.extensions.remove();
+        rejection.carried = parts.extensions.remove();
+        match intercepted {
             None => {
-                parts.headers = headers.into_headers();
                 req = http::Request::from_parts(parts, body);
-                InterceptorFut::fut(self.interceptor.clone(), self.inner.call(req))
+                InterceptorFut::fut(self.interceptor.clone(), self.handler.clone(), rejection, self.inner.call(req), diagnostics)
+            }
+            Some(status) => {
+                let interceptor = match self.on_rejection {
+                    true => Some(self.interceptor.clone()),
+                    false => None,
+                };
+                InterceptorFut::status(status, rejection, interceptor, diagnostics)
             }
-            Some(status) => InterceptorFut::status(self.interceptor.clone(), status),
         }
     }
 }
 
-///Interception service future
-pub struct InterceptorFut<I, F> {
-    interceptor: I,
-    inner: Result<F, tonic::Status>,
+impl<I, S: tonic::server::NamedService, H, B> tonic::server::NamedService for InterceptorService<I, S, H, B> {
+    const NAME: &'static str = S::NAME;
+}
+
+//Parameters of response, synthesized instead of inner service's one
+struct Rejection<B> {
+    template: response::ResponseTemplate,
+    body: B,
+    //Written onto any response, including inner service's one
+    echoed: Option<headers::Echoed>,
+    //Passed to `on_response` within response extensions
+    carried: Option<ext::Carried>,
+}
+
+impl<B> Rejection<B> {
+    fn respond<ResBody, I: Interceptor>(&mut self, status: &tonic::Status, interceptor: Option<&I>) -> http::Response<ResBody> where B: BodyFactory<ResBody> {
+        self.respond_with(status, |code, headers, extensions| if let Some(interceptor) = interceptor {
+            interceptor.on_response(code, headers, extensions);
+        })
+    }
+
+    fn respond_with<ResBody, F: FnOnce(tonic::Code, &mut http::HeaderMap, &http::Extensions)>(&mut self, status: &tonic::Status, on_response: F) -> http::Response<ResBody> where B: BodyFactory<ResBody> {
+        let (mut parts, body) = response::status_to_response_with(status, &self.template, self.body.create()).into_parts();
+        if let Some(echoed) = self.echoed.take() {
+            echoed.apply(&mut parts.headers);
+        }
+        if let Some(carried) = self.carried.take() {
+            parts.extensions.insert(carried);
+        }
+        on_response(status.code(), &mut parts.headers, &parts.extensions);
+        parts.extensions.remove::<ext::Carried>();
+        http::Response::from_parts(parts, body)
+    }
+}
+
+pin_project_lite::pin_project! {
+    #[project = InnerProj]
+    enum Inner<I, F, H, B> {
+        Fut {
+            interceptor: I,
+            handler: H,
+            rejection: Rejection<B>,
+            #[pin]
+            fut: F,
+        },
+        Status {
+            status: tonic::Status,
+            rejection: Rejection<B>,
+            interceptor: Option<I>,
+        },
+    }
+}
+
+pin_project_lite::pin_project! {
+    ///Interception service future
+    pub struct InterceptorFut<I, F, H = PropagateError, B = DefaultBody> {
+        #[pin]
+        inner: Inner<I, F, H, B>,
+        diagnostics: diagnostics::PollRecorder<I>,
+    }
+
+    impl<I, F, H, B> PinnedDrop for InterceptorFut<I, F, H, B> {
+        fn drop(this: Pin<&mut Self>) {
+            let this = this.project();
+            this.diagnostics.dropped(this.inner.interceptor());
+        }
+    }
 }
 
-impl<I, F> InterceptorFut<I, F> {
+impl<I, F, H, B> Inner<I, F, H, B> {
     #[inline(always)]
-    fn status(interceptor: I, status: tonic::Status) -> Self {
+    fn interceptor(self: Pin<&mut Self>) -> Option<&I> {
+        match self.project() {
+            InnerProj::Fut { interceptor, .. } => Some(interceptor),
+            InnerProj::Status { interceptor, .. } => interceptor.as_ref(),
+        }
+    }
+}
+
+impl<I, F, H, B> core::fmt::Debug for InterceptorFut<I, F, H, B> {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let state = match self.inner {
+            Inner::Fut { .. } => "Fut",
+            Inner::Status { .. } => "Status",
+        };
+        fmt.debug_struct("InterceptorFut").field("state", &state).finish_non_exhaustive()
+    }
+}
+
+impl<I, F, H, B> InterceptorFut<I, F, H, B> {
+    #[inline(always)]
+    fn status(status: tonic::Status, rejection: Rejection<B>, interceptor: Option<I>, diagnostics: diagnostics::PollRecorder<I>) -> Self {
         Self {
-            interceptor,
-            inner: Err(status),
+            inner: Inner::Status {
+                status,
+                rejection,
+                interceptor,
+            },
+            diagnostics,
         }
     }
 
     #[inline(always)]
-    fn fut(interceptor: I, fut: F) -> Self {
+    fn fut(interceptor: I, handler: H, rejection: Rejection<B>, fut: F, diagnostics: diagnostics::PollRecorder<I>) -> Self {
         Self {
-            interceptor,
-            inner: Ok(fut),
+            inner: Inner::Fut {
+                interceptor,
+                handler,
+                rejection,
+                fut,
+            },
+            diagnostics,
         }
     }
 }
 
-
-impl<ResBody: Default, E, I: Interceptor, F: Future<Output = Result<http::Response<ResBody>, E>>> Future for InterceptorFut<I, F> {
+impl<ResBody, E, I: Interceptor, F: Future<Output = Result<http::Response<ResBody>, E>>, H: ErrorHandler<E>, B: BodyFactory<ResBody>> Future for InterceptorFut<I, F, H, B> {
     type Output = F::Output;
 
     fn poll(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
-        let (intercepter, fut) = unsafe {
-            let this = self.get_unchecked_mut();
-            let fut = match this.inner.as_mut() {
-                Ok(fut) => Pin::new_unchecked(fut),
-                Err(status) => {
-                    let mut resp = http::Response::new(Default::default());
-                    resp.headers_mut().insert(http::header::CONTENT_TYPE, http::header::HeaderValue::from_static("application/grpc"));
-                    let _ = status.add_header(resp.headers_mut());
-                    return task::Poll::Ready(Ok(resp));
-                }
-            };
-            (&this.interceptor, fut)
+        let mut this = self.project();
+        let started = this.diagnostics.start();
+        let result = Self::poll_inner(this.inner.as_mut(), ctx);
+        this.diagnostics.finish(started, result.is_ready(), this.inner.interceptor());
+        result
+    }
+}
+
+impl<ResBody, E, I: Interceptor, F: Future<Output = Result<http::Response<ResBody>, E>>, H: ErrorHandler<E>, B: BodyFactory<ResBody>> InterceptorFut<I, F, H, B> {
+    fn poll_inner(inner: Pin<&mut Inner<I, F, H, B>>, ctx: &mut task::Context<'_>) -> task::Poll<F::Output> {
+        let (intercepter, handler, rejection, fut) = match inner.project() {
+            InnerProj::Fut { interceptor, handler, rejection, fut } => (interceptor, handler, rejection, fut),
+            InnerProj::Status { status, rejection, interceptor } => {
+                return task::Poll::Ready(Ok(rejection.respond(status, interceptor.as_ref())));
+            }
         };
         match Future::poll(fut, ctx) {
             task::Poll::Ready(Result::Ok(resp)) => {
@@ -138,10 +926,20 @@ impl<ResBody: Default, E, I: Interceptor, F: Future<Output = Result<http::Respon
 
                 let status = parts.headers.get(GRPC_STATUS_HEADER_CODE).map(|header| tonic::Code::from_bytes(header.as_bytes())).unwrap_or(tonic::Code::Unknown);
 
+                if let Some(echoed) = rejection.echoed.take() {
+                    echoed.apply(&mut parts.headers);
+                }
+                if let Some(carried) = rejection.carried.take() {
+                    parts.extensions.insert(carried);
+                }
                 intercepter.on_response(status, &mut parts.headers, &parts.extensions);
+                parts.extensions.remove::<ext::Carried>();
                 task::Poll::Ready(Ok(http::Response::from_parts(parts, body)))
             },
-            task::Poll::Ready(Result::Err(error)) => task::Poll::Ready(Err(error)),
+            task::Poll::Ready(Result::Err(error)) => match handler.on_error(&error) {
+                Some(status) => task::Poll::Ready(Ok(rejection.respond(&status, Some(&*intercepter)))),
+                None => task::Poll::Ready(Err(error)),
+            },
             task::Poll::Pending => task::Poll::Pending,
         }
     }
@@ -156,6 +954,13 @@ pub struct InterceptorFn<OnReq, OnResp> {
     pub on_response: OnResp,
 }
 
+impl<OnReq, OnResp> core::fmt::Debug for InterceptorFn<OnReq, OnResp> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("InterceptorFn").finish_non_exhaustive()
+    }
+}
+
 impl<OnReq: Fn(&mut tonic::metadata::MetadataMap, &mut http::Extensions) -> Option<tonic::Status>, OnResp: Fn(tonic::Code, &mut http::HeaderMap, &http::Extensions)> Interceptor for InterceptorFn<OnReq, OnResp> {
 
     #[inline(always)]
@@ -169,8 +974,67 @@ impl<OnReq: Fn(&mut tonic::metadata::MetadataMap, &mut http::Extensions) -> Opti
     }
 }
 
+#[derive(Clone, Copy)]
+#[repr(transparent)]
+///Interceptor, which only hooks into response using `Fn(tonic::Code, &mut http::HeaderMap, &http::Extensions)`
+///
+///Request is always passed through.
+pub struct OnResponse<F>(pub F);
+
+impl<F> core::fmt::Debug for OnResponse<F> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("OnResponse").finish_non_exhaustive()
+    }
+}
+
+impl<F: Fn(tonic::Code, &mut http::HeaderMap, &http::Extensions)> Interceptor for OnResponse<F> {
+    #[inline(always)]
+    fn on_request(&self, _: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+        None
+    }
+
+    #[inline(always)]
+    fn on_request_headers(&self, _: &http::Uri, _: &mut http::HeaderMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+        None
+    }
+
+    #[inline(always)]
+    fn on_response(&self, status: tonic::Code, headers: &mut http::HeaderMap, extensions: &http::Extensions) {
+        (self.0)(status, headers, extensions)
+    }
+}
+
+#[inline(always)]
+///Creates interceptor, which only hooks into response
+pub fn on_response<F: Fn(tonic::Code, &mut http::HeaderMap, &http::Extensions)>(fun: F) -> OnResponse<F> {
+    OnResponse(fun)
+}
+
 #[inline(always)]
 ///Creates interceptor layer
 pub fn interceptor<I: Interceptor>(interceptor: I) -> InterceptorLayer<I> {
-    InterceptorLayer(interceptor)
+    InterceptorLayer::new(interceptor)
+}
+
+#[inline(always)]
+///Creates interceptor layer, which uses `body` factory for rejection responses
+pub fn interceptor_with_body<I: Interceptor, B>(interceptor: I, body: B) -> InterceptorLayer<I, B> {
+    InterceptorLayer {
+        interceptor,
+        on_rejection: false,
+        capture: None,
+        body,
+    }
+}
+
+#[inline(always)]
+///Creates interceptor layer, sharing `interceptor` via `Arc`
+pub fn interceptor_arc<I: Interceptor>(interceptor: I) -> ArcInterceptorLayer<I> {
+    ArcInterceptorLayer {
+        interceptor: std::sync::Arc::new(interceptor),
+        on_rejection: false,
+        capture: None,
+        body: DefaultBody,
+    }
 }
@@ -0,0 +1,361 @@
+//! Server lifecycle
+//!
+//!`Drain` lets server finish in-flight calls on shutdown, while rejecting new ones:
+//!
+//!```rust,no_run
+//!use tonic_interceptor::lifecycle::Drain;
+//!
+//!use core::time::Duration;
+//!
+//!# async fn shutdown(server: impl core::future::Future<Output = ()>) {
+//!let drain = Drain::new(Duration::from_secs(30));
+//!let handle = drain.handle();
+//!//Pass `drain.layer()` to `Server::layer`, then on shutdown signal:
+//!handle.start_drain().await;
+//!# }
+//!```
+//!
+//!New calls are rejected with `UNAVAILABLE` and `x-server-draining: true` metadata, hinting client to re-resolve server.
+//!Call is in flight from `on_request` until its response body is complete or dropped,
+//!which is tracked by `InFlight` guard carried by request and then response.
+//!Drain timeout is measured by `Timer`, which is tokio's one by default.
+
+use crate::{Interceptor, InterceptorService};
+use crate::timer::{Timer, TokioTimer};
+use crate::compat;
+
+use core::task;
+use core::pin::{Pin, pin};
+use core::future::Future;
+use core::time::Duration;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+///Metadata key of rejection during drain
+pub const DRAINING_HEADER: &str = "x-server-draining";
+
+struct State {
+    draining: AtomicBool,
+    in_flight: AtomicUsize,
+    idle: tokio::sync::Notify,
+    timeout: Duration,
+    timer: Arc<dyn Timer + Send + Sync>,
+}
+
+//Call in flight, completed once dropped
+struct InFlightCall {
+    state: Arc<State>,
+}
+
+impl Drop for InFlightCall {
+    #[inline]
+    fn drop(&mut self) {
+        if self.state.in_flight.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.state.idle.notify_waiters();
+        }
+    }
+}
+
+#[derive(Clone)]
+///Guard of in-flight call, which completes call once it and all of its clones are dropped
+pub struct InFlight {
+    _call: Arc<InFlightCall>,
+}
+
+impl core::fmt::Debug for InFlight {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("InFlight").finish_non_exhaustive()
+    }
+}
+
+#[derive(Clone)]
+///Server interceptor which rejects new calls once drain is started
+///
+///Accepted call gets `InFlight` guard inserted into its request extensions.
+///Use `layer` to keep guard until response is complete, otherwise call completes once its request is dropped.
+pub struct Drain {
+    state: Arc<State>,
+}
+
+impl Drain {
+    #[inline]
+    ///Creates new instance, with drain waiting for in-flight calls up to `timeout`
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            state: Arc::new(State {
+                draining: AtomicBool::new(false),
+                in_flight: AtomicUsize::new(0),
+                idle: tokio::sync::Notify::new(),
+                timeout,
+                timer: Arc::new(TokioTimer),
+            }),
+        }
+    }
+
+    ///Sets timer, which measures drain timeout
+    ///
+    ///Timer must be set before instance is cloned or its handle is taken.
+    pub fn timer<T: Timer + Send + Sync + 'static>(mut self, timer: T) -> Self {
+        match Arc::get_mut(&mut self.state) {
+            Some(state) => state.timer = Arc::new(timer),
+            None => panic!("Drain::timer called after instance is shared"),
+        }
+        self
+    }
+
+    #[inline(always)]
+    ///Returns handle to start drain
+    pub fn handle(&self) -> DrainHandle {
+        DrainHandle {
+            state: self.state.clone(),
+        }
+    }
+
+    #[inline(always)]
+    ///Creates layer which tracks calls until their response is complete
+    pub fn layer(&self) -> DrainLayer {
+        DrainLayer {
+            drain: self.clone(),
+        }
+    }
+
+    #[inline(always)]
+    ///Returns number of in-flight calls
+    pub fn in_flight(&self) -> usize {
+        self.state.in_flight.load(Ordering::Acquire)
+    }
+
+    fn admit(&self, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        //Counted before checking drain, so that drain started in between cannot observe no calls in flight
+        let call = InFlightCall {
+            state: self.state.clone(),
+        };
+        self.state.in_flight.fetch_add(1, Ordering::SeqCst);
+        if self.state.draining.load(Ordering::SeqCst) {
+            drop(call);
+            let mut status = tonic::Status::unavailable("server is draining");
+            status.metadata_mut().insert(DRAINING_HEADER, tonic::metadata::AsciiMetadataValue::from_static("true"));
+            return Some(status);
+        }
+
+        extensions.insert(InFlight {
+            _call: Arc::new(call),
+        });
+        None
+    }
+}
+
+impl core::fmt::Debug for Drain {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("Drain").field("in_flight", &self.in_flight()).field("timeout", &self.state.timeout).finish_non_exhaustive()
+    }
+}
+
+impl Interceptor for Drain {
+    #[inline]
+    fn on_request(&self, _: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        self.admit(extensions)
+    }
+
+    #[inline]
+    fn on_request_headers(&self, _: &http::Uri, _: &mut http::HeaderMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        self.admit(extensions)
+    }
+
+    #[inline(always)]
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
+
+#[derive(Clone)]
+///Handle of `Drain`
+pub struct DrainHandle {
+    state: Arc<State>,
+}
+
+impl DrainHandle {
+    #[inline(always)]
+    ///Returns whether drain is started
+    pub fn is_draining(&self) -> bool {
+        self.state.draining.load(Ordering::Acquire)
+    }
+
+    #[inline(always)]
+    ///Returns number of in-flight calls
+    pub fn in_flight(&self) -> usize {
+        self.state.in_flight.load(Ordering::Acquire)
+    }
+
+    ///Starts drain, returning future which resolves once there are no in-flight calls or timeout elapses
+    ///
+    ///New calls are rejected immediately, without awaiting future.
+    pub fn start_drain(&self) -> impl Future<Output = ()> + Send + 'static {
+        self.state.draining.store(true, Ordering::SeqCst);
+
+        let state = self.state.clone();
+        async move {
+            let idle = async {
+                loop {
+                    let mut notified = pin!(state.idle.notified());
+                    //Register before checking, so that completion in between is not missed
+                    notified.as_mut().enable();
+                    if state.in_flight.load(Ordering::SeqCst) == 0 {
+                        break;
+                    }
+                    notified.await;
+                }
+            };
+            let _ = crate::timer::timeout(&*state.timer, state.timeout, idle).await;
+        }
+    }
+}
+
+impl core::fmt::Debug for DrainHandle {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("DrainHandle").field("is_draining", &self.is_draining()).field("in_flight", &self.in_flight()).finish()
+    }
+}
+
+#[derive(Clone, Debug)]
+///Layer of `Drain`, keeping call in flight until its response is complete
+pub struct DrainLayer {
+    drain: Drain,
+}
+
+impl<S> tower_layer::Layer<S> for DrainLayer {
+    type Service = InterceptorService<Drain, Track<S>>;
+
+    #[inline(always)]
+    fn layer(&self, inner: S) -> Self::Service {
+        InterceptorService::new(self.drain.clone(), Track {
+            inner,
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+///Service moving `InFlight` guard of request into its response
+pub struct Track<S> {
+    inner: S,
+}
+
+impl<S: tonic::server::NamedService> tonic::server::NamedService for Track<S> {
+    const NAME: &'static str = S::NAME;
+}
+
+impl<ReqBody, ResBody, S: tower_service::Service<http::Request<ReqBody>, Response = http::Response<ResBody>>> tower_service::Service<http::Request<ReqBody>> for Track<S> {
+    type Response = http::Response<TrackBody<ResBody>>;
+    type Error = S::Error;
+    type Future = TrackFut<S::Future>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    #[inline]
+    fn call(&mut self, mut req: http::Request<ReqBody>) -> Self::Future {
+        TrackFut {
+            guard: req.extensions_mut().remove(),
+            inner: self.inner.call(req),
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    ///Future of `Track`
+    pub struct TrackFut<F> {
+        guard: Option<InFlight>,
+        #[pin]
+        inner: F,
+    }
+}
+
+impl<F> core::fmt::Debug for TrackFut<F> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("TrackFut").finish_non_exhaustive()
+    }
+}
+
+impl<ResBody, E, F: Future<Output = Result<http::Response<ResBody>, E>>> Future for TrackFut<F> {
+    type Output = Result<http::Response<TrackBody<ResBody>>, E>;
+
+    #[inline]
+    fn poll(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let this = self.project();
+        match Future::poll(this.inner, ctx) {
+            task::Poll::Ready(Ok(response)) => {
+                let guard = this.guard.take();
+                task::Poll::Ready(Ok(response.map(|inner| TrackBody {
+                    guard,
+                    inner: compat::Incoming::new(inner),
+                })))
+            },
+            task::Poll::Ready(Err(error)) => {
+                *this.guard = None;
+                task::Poll::Ready(Err(error))
+            },
+            task::Poll::Pending => task::Poll::Pending,
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    ///Response body of `Track`, completing call once trailers are received or it is dropped
+    pub struct TrackBody<B> {
+        guard: Option<InFlight>,
+        #[pin]
+        inner: compat::Incoming<B>,
+    }
+}
+
+impl<B: Default> Default for TrackBody<B> {
+    #[inline(always)]
+    fn default() -> Self {
+        Self {
+            guard: None,
+            inner: Default::default(),
+        }
+    }
+}
+
+impl<B> core::fmt::Debug for TrackBody<B> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("TrackBody").field("is_complete", &self.guard.is_none()).finish_non_exhaustive()
+    }
+}
+
+compat::impl_body! {
+    [B: http_body::Body] TrackBody<B> {
+        type Data = B::Data;
+        type Error = B::Error;
+
+        #[inline(always)]
+        fn poll_data(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Option<Result<Self::Data, Self::Error>>> {
+            compat::Body::poll_data(self.project().inner, ctx)
+        }
+
+        fn poll_trailers(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Result<Option<http::HeaderMap>, Self::Error>> {
+            let this = self.project();
+
+            let result = compat::Body::poll_trailers(this.inner, ctx);
+            if result.is_ready() {
+                *this.guard = None;
+            }
+            result
+        }
+
+        #[inline(always)]
+        fn is_end_stream(&self) -> bool {
+            self.inner.is_end_stream()
+        }
+
+        #[inline(always)]
+        fn size_hint(&self) -> http_body::SizeHint {
+            self.inner.size_hint()
+        }
+    }
+}
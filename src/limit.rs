@@ -0,0 +1,944 @@
+//! Rate limit response headers
+//!
+//!Limiters describe their decision with `Headers`, so that clients can pace themselves before being rejected:
+//!
+//!- `x-ratelimit-limit` - number of requests allowed within window;
+//!- `x-ratelimit-remaining` - number of requests left within current window;
+//!- `x-ratelimit-reset` - seconds until current window ends, rounded up.
+//!
+//!Headers are written via `headers::Echoed`, hence they are present on every response of `InterceptorService`, including rejections.
+//!
+//!`AdaptiveShed` sheds load based on measured handling time instead of static limit:
+//!
+//!```rust
+//!use tonic_interceptor::limit::{AdaptiveShed, ShedController};
+//!
+//!use core::time::Duration;
+//!
+//!let shed = AdaptiveShed::new(ShedController::new(Duration::from_millis(50)));
+//!let handle = shed.handle();
+//!//Pass `shed.layer()` to `Server::layer`, while `handle` reports admission rate to dashboards
+//!# let _ = (shed.layer(), handle.admission_rate());
+//!```
+//!
+//!`RateLimitSet` enforces several keyed limits at once, e.g. per tenant and method alongside per API key:
+//!
+//!```rust
+//!use tonic_interceptor::limit::{KeyedLimit, RateLimitSet};
+//!
+//!use core::time::Duration;
+//!
+//!let limits = RateLimitSet::new().with(KeyedLimit::new("tenant-method", 100, Duration::from_secs(1), |metadata: &tonic::metadata::MetadataMap, _: &http::Extensions, method: &str| {
+//!    let tenant = metadata.get("x-tenant")?.to_str().ok()?;
+//!    Some(format!("{}{}", tenant, method))
+//!})).with(KeyedLimit::new("api-key", 1000, Duration::from_secs(60), |metadata: &tonic::metadata::MetadataMap, _: &http::Extensions, _: &str| {
+//!    metadata.get("x-api-key")?.to_str().ok().map(ToOwned::to_owned)
+//!}));
+//!```
+//!
+//!With `tokio` feature, `AdmissionQueue` lets requests over concurrency limit wait for a slot within bounded waiting room.
+//!
+//!`PerConnectionStreams` limits concurrent streams of every HTTP/2 connection, using interceptor per connection.
+//!
+//!With `gzip` feature, `CompressionGuard` limits compressed request messages, optionally decompressing them to reject decompression bombs.
+
+#[cfg(feature = "tokio")]
+mod admission;
+#[cfg(feature = "tokio")]
+pub use admission::{AdmissionQueue, AdmissionQueueLayer, AdmissionQueueService, AdmissionQueueFut, AdmittedBody};
+#[cfg(feature = "gzip")]
+mod compression;
+#[cfg(feature = "gzip")]
+pub use compression::{CompressionGuard, CompressionGuardLayer, CompressionGuardService, GuardedBody};
+
+use crate::{Interceptor, InterceptorService};
+use crate::connection::{MakeInterceptor, PerConnection, PerConnectionLayer};
+use crate::headers::Echoed;
+use crate::timer::{Clock, Instant, StdClock};
+use crate::compat;
+
+use core::task;
+use core::pin::Pin;
+use core::future::Future;
+use core::time::Duration;
+use core::convert::TryFrom;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+///Metadata key of number of requests allowed within window
+pub const RATELIMIT_LIMIT: &str = "x-ratelimit-limit";
+///Metadata key of number of requests left within current window
+pub const RATELIMIT_REMAINING: &str = "x-ratelimit-remaining";
+///Metadata key of seconds until current window ends
+pub const RATELIMIT_RESET: &str = "x-ratelimit-reset";
+///Metadata key of name of exceeded limit, included in rejection of `RateLimitSet`
+pub const RATELIMIT_NAME: &str = "x-ratelimit-name";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+///State of limit, as decided for single request
+pub struct Headers {
+    ///Number of requests allowed within window
+    pub limit: u64,
+    ///Number of requests left within current window
+    pub remaining: u64,
+    ///Time until current window ends
+    pub reset: Duration,
+}
+
+impl Headers {
+    #[inline(always)]
+    ///Creates new instance
+    pub const fn new(limit: u64, remaining: u64, reset: Duration) -> Self {
+        Self {
+            limit,
+            remaining,
+            reset,
+        }
+    }
+
+    #[inline]
+    fn reset_secs(&self) -> u64 {
+        let mut seconds = self.reset.as_secs();
+        if self.reset.subsec_nanos() > 0 {
+            seconds = seconds.saturating_add(1);
+        }
+        seconds
+    }
+
+    ///Writes headers, replacing existing ones
+    pub fn write(&self, headers: &mut http::HeaderMap) {
+        headers.insert(RATELIMIT_LIMIT, self.limit.into());
+        headers.insert(RATELIMIT_REMAINING, self.remaining.into());
+        headers.insert(RATELIMIT_RESET, self.reset_secs().into());
+    }
+
+    ///Stores itself in request `extensions`, scheduling headers to be written onto response
+    ///
+    ///When request is subject to multiple limits, one with the least remaining requests is kept.
+    ///Requires `InterceptorService`, which applies `headers::Echoed` to response.
+    pub fn echo(self, extensions: &mut http::Extensions) {
+        if extensions.get::<Self>().is_some_and(|current| current.remaining <= self.remaining) {
+            return;
+        }
+
+        let mut headers = http::HeaderMap::with_capacity(3);
+        self.write(&mut headers);
+
+        let mut echoed = extensions.remove::<Echoed>().unwrap_or_default();
+        for (key, value) in headers.into_iter() {
+            if let Some(key) = key {
+                echoed.insert(key, value);
+            }
+        }
+        extensions.insert(echoed);
+        extensions.insert(self);
+    }
+}
+
+#[derive(Clone, Debug)]
+///Controller of admission rate, driven by handling time of requests
+///
+///Latency is sampled in windows of fixed number of requests, and the least latency of window is compared against target.
+///Like in CoDel, the least latency exceeding target means that even the luckiest request was queued,
+///hence admission rate is decreased in proportion to latency inflation, but at most halved per window.
+///Otherwise admission rate is increased additively, until every request is admitted.
+///
+///Admission is deterministic: each request adds admission rate to credit, and is admitted once credit reaches one.
+pub struct ShedController {
+    target: Duration,
+    window: usize,
+    min_rate: f64,
+    increase: f64,
+    rate: f64,
+    credit: f64,
+    samples: usize,
+    window_min: Option<Duration>,
+    recent: Option<Duration>,
+}
+
+impl ShedController {
+    #[inline]
+    ///Creates new instance with `target` latency, admitting every request initially.
+    ///
+    ///Defaults: window of 20 samples, minimum admission rate of 5% and increase of 5% per window.
+    pub fn new(target: Duration) -> Self {
+        Self {
+            target,
+            window: 20,
+            min_rate: 0.05,
+            increase: 0.05,
+            rate: 1.0,
+            credit: 0.0,
+            samples: 0,
+            window_min: None,
+            recent: None,
+        }
+    }
+
+    #[inline]
+    ///Sets number of samples within window, which is at least one
+    pub fn window(mut self, window: usize) -> Self {
+        self.window = window.max(1);
+        self
+    }
+
+    #[inline]
+    ///Sets minimal admission rate, so that some requests keep being sampled under overload
+    pub fn min_rate(mut self, min_rate: f64) -> Self {
+        self.min_rate = min_rate.clamp(0.0, 1.0);
+        self
+    }
+
+    #[inline]
+    ///Sets increase of admission rate per window without latency inflation
+    pub fn increase(mut self, increase: f64) -> Self {
+        self.increase = increase.clamp(0.0, 1.0);
+        self
+    }
+
+    #[inline(always)]
+    ///Returns current admission rate within `0..=1`
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    #[inline(always)]
+    ///Returns the least latency of the last complete window
+    pub fn recent_latency(&self) -> Option<Duration> {
+        self.recent
+    }
+
+    ///Decides whether to admit next request
+    pub fn admit(&mut self) -> bool {
+        self.credit = (self.credit + self.rate).min(1.0);
+        if self.credit >= 1.0 {
+            self.credit -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    ///Records handling time of admitted request, adjusting admission rate once window is complete
+    pub fn record(&mut self, latency: Duration) {
+        let window_min = match self.window_min {
+            Some(window_min) => window_min.min(latency),
+            None => latency,
+        };
+        self.samples += 1;
+        if self.samples < self.window {
+            self.window_min = Some(window_min);
+            return;
+        }
+
+        self.samples = 0;
+        self.window_min = None;
+        self.recent = Some(window_min);
+        self.rate = if window_min > self.target {
+            let gradient = self.target.as_secs_f64() / window_min.as_secs_f64();
+            (self.rate * gradient.max(0.5)).max(self.min_rate)
+        } else {
+            (self.rate + self.increase).min(1.0)
+        };
+    }
+}
+
+pub(crate) fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    match mutex.lock() {
+        Ok(guard) => guard,
+        Err(error) => error.into_inner(),
+    }
+}
+
+#[derive(Clone, Debug)]
+///Server interceptor which sheds load, according to `ShedController`
+///
+///Requests, which are not admitted, are rejected with `RESOURCE_EXHAUSTED`.
+///Interceptor alone never samples latency, use `layer` to measure handling time of admitted requests.
+///Handling time is measured by `Clock`, which is `StdClock` by default.
+pub struct AdaptiveShed<C = StdClock> {
+    controller: Arc<Mutex<ShedController>>,
+    clock: C,
+}
+
+impl AdaptiveShed {
+    #[inline]
+    ///Creates new instance
+    pub fn new(controller: ShedController) -> Self {
+        Self {
+            controller: Arc::new(Mutex::new(controller)),
+            clock: StdClock,
+        }
+    }
+}
+
+impl<C> AdaptiveShed<C> {
+    #[inline]
+    ///Sets clock, which measures handling time
+    pub fn clock<C2: Clock>(self, clock: C2) -> AdaptiveShed<C2> {
+        AdaptiveShed {
+            controller: self.controller,
+            clock,
+        }
+    }
+
+    #[inline]
+    ///Returns handle to observe state of controller
+    pub fn handle(&self) -> ShedHandle {
+        ShedHandle {
+            controller: self.controller.clone(),
+        }
+    }
+
+    #[inline]
+    ///Returns layer, which samples handling time of inner service
+    pub fn layer(&self) -> AdaptiveShedLayer<C> where C: Clone {
+        AdaptiveShedLayer {
+            shed: self.clone(),
+        }
+    }
+}
+
+impl<C> Interceptor for AdaptiveShed<C> {
+    fn on_request(&self, _: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+        let mut controller = lock(&self.controller);
+        match controller.admit() {
+            true => None,
+            false => Some(tonic::Status::resource_exhausted(format!("server is overloaded, admitting {:.0}% of requests", controller.rate() * 100.0))),
+        }
+    }
+
+    #[inline(always)]
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
+
+#[derive(Clone, Debug)]
+///Handle to observe state of `AdaptiveShed`
+pub struct ShedHandle {
+    controller: Arc<Mutex<ShedController>>,
+}
+
+impl ShedHandle {
+    #[inline]
+    ///Returns current admission rate within `0..=1`
+    pub fn admission_rate(&self) -> f64 {
+        lock(&self.controller).rate()
+    }
+
+    #[inline]
+    ///Returns the least latency of the last complete window
+    pub fn recent_latency(&self) -> Option<Duration> {
+        lock(&self.controller).recent_latency()
+    }
+}
+
+#[derive(Clone, Debug)]
+///Layer of `AdaptiveShed`, sampling handling time of admitted requests
+pub struct AdaptiveShedLayer<C = StdClock> {
+    shed: AdaptiveShed<C>,
+}
+
+impl<S, C: Clone> tower_layer::Layer<S> for AdaptiveShedLayer<C> {
+    type Service = InterceptorService<AdaptiveShed<C>, Sample<S, C>>;
+
+    #[inline(always)]
+    fn layer(&self, inner: S) -> Self::Service {
+        InterceptorService::new(self.shed.clone(), Sample {
+            controller: self.shed.controller.clone(),
+            clock: self.shed.clock.clone(),
+            inner,
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+///Service recording time from call until response into `ShedController`
+///
+///Calls, which are dropped before response, are not recorded.
+pub struct Sample<S, C = StdClock> {
+    controller: Arc<Mutex<ShedController>>,
+    clock: C,
+    inner: S,
+}
+
+impl<S: tonic::server::NamedService, C> tonic::server::NamedService for Sample<S, C> {
+    const NAME: &'static str = S::NAME;
+}
+
+impl<Req, S: tower_service::Service<Req>, C: Clock + Clone> tower_service::Service<Req> for Sample<S, C> {
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = SampleFut<S::Future, C>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    #[inline]
+    fn call(&mut self, req: Req) -> Self::Future {
+        SampleFut {
+            controller: self.controller.clone(),
+            started: self.clock.now(),
+            clock: self.clock.clone(),
+            inner: self.inner.call(req),
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    ///Future of `Sample`
+    pub struct SampleFut<F, C = StdClock> {
+        controller: Arc<Mutex<ShedController>>,
+        started: Instant,
+        clock: C,
+        #[pin]
+        inner: F,
+    }
+}
+
+impl<F, C> core::fmt::Debug for SampleFut<F, C> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("SampleFut").field("started", &self.started).finish_non_exhaustive()
+    }
+}
+
+impl<F: Future, C: Clock> Future for SampleFut<F, C> {
+    type Output = F::Output;
+
+    #[inline]
+    fn poll(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let this = self.project();
+        let result = Future::poll(this.inner, ctx);
+        if result.is_ready() {
+            lock(this.controller).record(this.clock.now().saturating_duration_since(*this.started));
+        }
+        result
+    }
+}
+
+///Extractor of limit's key from request metadata, extensions and method path
+///
+///Limit does not apply to request without key.
+pub type KeyExtractor = dyn Fn(&tonic::metadata::MetadataMap, &http::Extensions, &str) -> Option<String> + Send + Sync;
+
+///Named limit of requests within fixed window, tracked separately for each key
+///
+///Number of tracked keys is bounded: once full, expired windows are evicted first, then the oldest ones.
+pub struct KeyedLimit {
+    name: String,
+    requests: u32,
+    period: Duration,
+    max_keys: usize,
+    extractor: Box<KeyExtractor>,
+    //Start of current window and number of requests within it, for each key
+    windows: Mutex<HashMap<String, (Instant, u32)>>,
+}
+
+impl KeyedLimit {
+    #[inline]
+    ///Creates new instance, allowing `requests` per every `period` for each key, tracking up to 10000 keys
+    pub fn new<F: Fn(&tonic::metadata::MetadataMap, &http::Extensions, &str) -> Option<String> + Send + Sync + 'static>(name: impl Into<String>, requests: u32, period: Duration, extractor: F) -> Self {
+        Self {
+            name: name.into(),
+            requests,
+            period,
+            max_keys: 10_000,
+            extractor: Box::new(extractor),
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    #[inline]
+    ///Sets maximum number of tracked keys, at least one
+    pub fn max_keys(mut self, max_keys: usize) -> Self {
+        self.max_keys = max_keys.max(1);
+        self
+    }
+
+    #[inline(always)]
+    ///Returns name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[inline]
+    ///Returns number of tracked keys
+    pub fn len(&self) -> usize {
+        lock(&self.windows).len()
+    }
+
+    #[inline]
+    ///Returns whether no keys are tracked
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    //Makes room for new key
+    fn evict(&self, windows: &mut HashMap<String, (Instant, u32)>, now: Instant) {
+        if windows.len() < self.max_keys {
+            return;
+        }
+
+        windows.retain(|_, (start, _)| now.duration_since(*start) < self.period);
+        while windows.len() >= self.max_keys {
+            let oldest = windows.iter().min_by_key(|(_, (start, _))| *start).map(|(key, _)| key.clone());
+            match oldest {
+                Some(oldest) => windows.remove(&oldest),
+                None => break,
+            };
+        }
+    }
+}
+
+impl core::fmt::Debug for KeyedLimit {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("KeyedLimit").field("name", &self.name).field("requests", &self.requests).field("period", &self.period).field("max_keys", &self.max_keys).finish_non_exhaustive()
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+///Server interceptor which enforces multiple `KeyedLimit` at once
+///
+///Request is admitted only when none of applicable limits is exceeded, in which case it is counted by each of them.
+///Otherwise the first exceeded limit, in order of addition, rejects request using `reject::rate_limited_with`,
+///naming itself in message and in `x-ratelimit-name` metadata.
+///With `headers` enabled, every response carries `Headers` of applicable limit with the least remaining requests.
+///
+///Windows are measured by `Clock`, which is `StdClock` by default.
+pub struct RateLimitSet<C = StdClock> {
+    limits: Arc<Vec<KeyedLimit>>,
+    headers: bool,
+    clock: C,
+}
+
+impl RateLimitSet {
+    #[inline]
+    ///Creates new instance without limits
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<C> RateLimitSet<C> {
+    #[inline]
+    ///Sets clock, which measures windows
+    pub fn clock<C2: Clock>(self, clock: C2) -> RateLimitSet<C2> {
+        RateLimitSet {
+            limits: self.limits,
+            headers: self.headers,
+            clock,
+        }
+    }
+
+    ///Adds `limit`
+    ///
+    ///Limits must be added before instance is cloned.
+    pub fn with(mut self, limit: KeyedLimit) -> Self {
+        match Arc::get_mut(&mut self.limits) {
+            Some(limits) => limits.push(limit),
+            None => panic!("RateLimitSet::with called after instance is cloned"),
+        }
+        self
+    }
+
+    #[inline]
+    ///Sets whether to write `x-ratelimit-*` headers onto every response
+    pub fn headers(mut self, headers: bool) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    #[inline]
+    ///Returns limit by `name`
+    pub fn get(&self, name: &str) -> Option<&KeyedLimit> {
+        self.limits.iter().find(|limit| limit.name == name)
+    }
+
+    ///Checks request of method `path`, counting it when admitted
+    pub fn check(&self, path: &str, metadata: &tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Result<(), tonic::Status> where C: Clock {
+        let now = self.clock.now();
+        //Locks are always taken in the same order, while all of them are held to count request atomically
+        let mut applicable = Vec::with_capacity(self.limits.len());
+        for limit in self.limits.iter() {
+            if let Some(key) = (limit.extractor)(metadata, extensions, path) {
+                applicable.push((limit, key, lock(&limit.windows)));
+            }
+        }
+
+        let mut exceeded = None;
+        let mut headers: Option<Headers> = None;
+        for (limit, key, windows) in applicable.iter_mut() {
+            let (start, count) = match windows.get(key.as_str()) {
+                Some((start, count)) if now.duration_since(*start) < limit.period => (*start, *count),
+                _ => (now, 0),
+            };
+            let reset = limit.period - now.duration_since(start);
+            if count >= limit.requests {
+                exceeded = Some((&limit.name, reset));
+                headers = Some(Headers::new(limit.requests.into(), 0, reset));
+                break;
+            }
+
+            let remaining = u64::from(limit.requests - count - 1);
+            if headers.is_none_or(|current| remaining < current.remaining) {
+                headers = Some(Headers::new(limit.requests.into(), remaining, reset));
+            }
+        }
+
+        if self.headers {
+            if let Some(headers) = headers {
+                headers.echo(extensions);
+            }
+        }
+
+        if let Some((name, reset)) = exceeded {
+            let mut status = crate::reject::rate_limited_with(format!("rate limit '{}' exceeded", name), reset);
+            if let Ok(name) = tonic::metadata::AsciiMetadataValue::try_from(name.as_str()) {
+                status.metadata_mut().insert(RATELIMIT_NAME, name);
+            }
+            return Err(status);
+        }
+
+        for (limit, key, windows) in applicable.iter_mut() {
+            match windows.get_mut(key.as_str()) {
+                Some(window) if now.duration_since(window.0) < limit.period => window.1 += 1,
+                Some(window) => *window = (now, 1),
+                None => {
+                    limit.evict(windows, now);
+                    windows.insert(core::mem::take(key), (now, 1));
+                },
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<C: Clock> Interceptor for RateLimitSet<C> {
+    #[inline(always)]
+    fn on_request(&self, _: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+        None
+    }
+
+    #[inline]
+    fn on_request_with_uri(&self, uri: &http::Uri, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        self.check(uri.path(), headers, extensions).err()
+    }
+
+    fn on_request_headers(&self, uri: &http::Uri, headers: &mut http::HeaderMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        let metadata = crate::compat::metadata_from_headers(core::mem::take(headers));
+        let result = self.check(uri.path(), &metadata, extensions).err();
+        *headers = crate::compat::metadata_into_headers(metadata);
+        result
+    }
+
+    #[inline(always)]
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+///Limit of streams on single HTTP/2 connection
+///
+///Connection can multiplex many concurrent streams, starving other clients even when per peer limits pass.
+///Every connection gets own `ConnectionStreams` interceptor via `connection::PerConnection`, which rejects:
+///
+///- Stream over `max_concurrent` in-flight streams with `RESOURCE_EXHAUSTED`;
+///- Any stream once connection served `max_requests`, if set, with `UNAVAILABLE`, so that client re-connects, possibly to another server.
+///
+///Stream is in flight from `on_request` until its response body is complete or dropped.
+///
+///```rust
+///use tonic_interceptor::limit::PerConnectionStreams;
+///
+///let limit = PerConnectionStreams::new(100).max_requests(10_000);
+/////Pass `limit.layer()` to `Server::layer`
+///# let _ = limit.layer();
+///```
+pub struct PerConnectionStreams {
+    max_concurrent: usize,
+    max_requests: Option<u64>,
+}
+
+impl PerConnectionStreams {
+    #[inline]
+    ///Creates new instance, limiting number of concurrent streams
+    pub const fn new(max_concurrent: usize) -> Self {
+        Self {
+            max_concurrent,
+            max_requests: None,
+        }
+    }
+
+    #[inline]
+    ///Sets limit of requests served over lifetime of connection
+    pub const fn max_requests(mut self, max_requests: u64) -> Self {
+        self.max_requests = Some(max_requests);
+        self
+    }
+
+    #[inline(always)]
+    ///Creates layer, which tracks streams until their response is complete
+    pub const fn layer(&self) -> PerConnectionStreamsLayer {
+        PerConnectionStreamsLayer {
+            limit: *self,
+        }
+    }
+}
+
+impl MakeInterceptor for PerConnectionStreams {
+    type Interceptor = ConnectionStreams;
+
+    #[inline]
+    fn make_interceptor(&self) -> Self::Interceptor {
+        ConnectionStreams {
+            limit: *self,
+            state: Arc::new(StreamsState {
+                in_flight: AtomicUsize::new(0),
+                requests: AtomicU64::new(0),
+            }),
+        }
+    }
+}
+
+struct StreamsState {
+    in_flight: AtomicUsize,
+    requests: AtomicU64,
+}
+
+//Stream in flight, completed once dropped
+struct InFlightStream {
+    state: Arc<StreamsState>,
+}
+
+impl Drop for InFlightStream {
+    #[inline]
+    fn drop(&mut self) {
+        self.state.in_flight.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+#[derive(Clone)]
+///Guard of in-flight stream, which completes stream once it and all of its clones are dropped
+pub struct ConnectionStream {
+    _stream: Arc<InFlightStream>,
+}
+
+impl core::fmt::Debug for ConnectionStream {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("ConnectionStream").finish_non_exhaustive()
+    }
+}
+
+#[derive(Clone)]
+///Interceptor of single connection, created by `PerConnectionStreams`
+///
+///Accepted stream gets `ConnectionStream` guard inserted into its request extensions.
+pub struct ConnectionStreams {
+    limit: PerConnectionStreams,
+    state: Arc<StreamsState>,
+}
+
+impl ConnectionStreams {
+    #[inline(always)]
+    ///Returns number of in-flight streams
+    pub fn in_flight(&self) -> usize {
+        self.state.in_flight.load(Ordering::Acquire)
+    }
+
+    #[inline(always)]
+    ///Returns number of accepted requests
+    pub fn requests(&self) -> u64 {
+        self.state.requests.load(Ordering::Acquire)
+    }
+
+    fn admit(&self, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        if let Some(max_requests) = self.limit.max_requests {
+            if self.state.requests.load(Ordering::Acquire) >= max_requests {
+                return Some(tonic::Status::unavailable(format!("connection exceeded limit of {} requests", max_requests)));
+            }
+        }
+
+        if self.state.in_flight.fetch_add(1, Ordering::AcqRel) >= self.limit.max_concurrent {
+            self.state.in_flight.fetch_sub(1, Ordering::AcqRel);
+            return Some(tonic::Status::resource_exhausted(format!("connection exceeded limit of {} concurrent streams", self.limit.max_concurrent)));
+        }
+        self.state.requests.fetch_add(1, Ordering::AcqRel);
+        extensions.insert(ConnectionStream {
+            _stream: Arc::new(InFlightStream {
+                state: self.state.clone(),
+            }),
+        });
+        None
+    }
+}
+
+impl core::fmt::Debug for ConnectionStreams {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("ConnectionStreams").field("limit", &self.limit).field("in_flight", &self.in_flight()).field("requests", &self.requests()).finish()
+    }
+}
+
+impl Interceptor for ConnectionStreams {
+    #[inline]
+    fn on_request(&self, _: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        self.admit(extensions)
+    }
+
+    #[inline]
+    fn on_request_headers(&self, _: &http::Uri, _: &mut http::HeaderMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        self.admit(extensions)
+    }
+
+    #[inline(always)]
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
+
+#[derive(Clone, Debug)]
+///Layer of `PerConnectionStreams`, keeping stream in flight until its response is complete
+pub struct PerConnectionStreamsLayer {
+    limit: PerConnectionStreams,
+}
+
+impl<S> tower_layer::Layer<S> for PerConnectionStreamsLayer {
+    type Service = PerConnection<PerConnectionStreams, TrackStream<S>>;
+
+    #[inline(always)]
+    fn layer(&self, inner: S) -> Self::Service {
+        PerConnectionLayer::new(self.limit).layer(TrackStream {
+            inner,
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+///Service moving `ConnectionStream` guard of request into its response
+pub struct TrackStream<S> {
+    inner: S,
+}
+
+impl<S: tonic::server::NamedService> tonic::server::NamedService for TrackStream<S> {
+    const NAME: &'static str = S::NAME;
+}
+
+impl<ReqBody, ResBody, S: tower_service::Service<http::Request<ReqBody>, Response = http::Response<ResBody>>> tower_service::Service<http::Request<ReqBody>> for TrackStream<S> {
+    type Response = http::Response<TrackStreamBody<ResBody>>;
+    type Error = S::Error;
+    type Future = TrackStreamFut<S::Future>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    #[inline]
+    fn call(&mut self, mut req: http::Request<ReqBody>) -> Self::Future {
+        TrackStreamFut {
+            guard: req.extensions_mut().remove(),
+            inner: self.inner.call(req),
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    ///Future of `TrackStream`
+    pub struct TrackStreamFut<F> {
+        guard: Option<ConnectionStream>,
+        #[pin]
+        inner: F,
+    }
+}
+
+impl<F> core::fmt::Debug for TrackStreamFut<F> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("TrackStreamFut").finish_non_exhaustive()
+    }
+}
+
+impl<ResBody, E, F: Future<Output = Result<http::Response<ResBody>, E>>> Future for TrackStreamFut<F> {
+    type Output = Result<http::Response<TrackStreamBody<ResBody>>, E>;
+
+    #[inline]
+    fn poll(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let this = self.project();
+        match Future::poll(this.inner, ctx) {
+            task::Poll::Ready(Ok(response)) => {
+                let guard = this.guard.take();
+                task::Poll::Ready(Ok(response.map(|inner| TrackStreamBody {
+                    guard,
+                    inner: compat::Incoming::new(inner),
+                })))
+            },
+            task::Poll::Ready(Err(error)) => {
+                *this.guard = None;
+                task::Poll::Ready(Err(error))
+            },
+            task::Poll::Pending => task::Poll::Pending,
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    ///Response body of `TrackStream`, completing stream once trailers are received or it is dropped
+    pub struct TrackStreamBody<B> {
+        guard: Option<ConnectionStream>,
+        #[pin]
+        inner: compat::Incoming<B>,
+    }
+}
+
+impl<B: Default> Default for TrackStreamBody<B> {
+    #[inline(always)]
+    fn default() -> Self {
+        Self {
+            guard: None,
+            inner: Default::default(),
+        }
+    }
+}
+
+impl<B> core::fmt::Debug for TrackStreamBody<B> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("TrackStreamBody").field("is_complete", &self.guard.is_none()).finish_non_exhaustive()
+    }
+}
+
+compat::impl_body! {
+    [B: http_body::Body] TrackStreamBody<B> {
+        type Data = B::Data;
+        type Error = B::Error;
+
+        #[inline(always)]
+        fn poll_data(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Option<Result<Self::Data, Self::Error>>> {
+            compat::Body::poll_data(self.project().inner, ctx)
+        }
+
+        fn poll_trailers(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Result<Option<http::HeaderMap>, Self::Error>> {
+            let this = self.project();
+
+            let result = compat::Body::poll_trailers(this.inner, ctx);
+            if result.is_ready() {
+                *this.guard = None;
+            }
+            result
+        }
+
+        #[inline(always)]
+        fn is_end_stream(&self) -> bool {
+            self.inner.is_end_stream()
+        }
+
+        #[inline(always)]
+        fn size_hint(&self) -> http_body::SizeHint {
+            self.inner.size_hint()
+        }
+    }
+}
@@ -0,0 +1,371 @@
+use crate::response::{self, ResponseTemplate};
+use crate::timer::{Timer, Timeout, TokioTimer};
+use crate::EmptyBody;
+use crate::compat;
+
+use core::fmt;
+use core::task;
+use core::pin::Pin;
+use core::future::Future;
+use core::time::Duration;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use tokio::sync::{AcquireError, OwnedSemaphorePermit, Semaphore};
+
+type Acquire = Timeout<Pin<Box<dyn Future<Output = Result<OwnedSemaphorePermit, AcquireError>> + Send>>>;
+
+struct Room {
+    semaphore: Arc<Semaphore>,
+    max_concurrent: usize,
+    waiting: AtomicUsize,
+    max_waiting: usize,
+    max_wait: Duration,
+}
+
+//Place in waiting room, given up on drop
+struct Waiting {
+    room: Arc<Room>,
+}
+
+impl Drop for Waiting {
+    #[inline]
+    fn drop(&mut self) {
+        self.room.waiting.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+//Outcome of admission
+enum Admission {
+    //Slot is taken
+    Admitted(OwnedSemaphorePermit),
+    //Request waits for slot
+    Wait(Waiting, Acquire),
+}
+
+#[derive(Clone)]
+///Concurrency limit with bounded waiting room
+///
+///Request, which finds all `max_concurrent` slots taken, waits up to `max_wait` for a slot, instead of being rejected immediately.
+///Only `max_waiting` requests may wait at once, while request beyond that is rejected with `RESOURCE_EXHAUSTED`.
+///Request, which does not get slot in time, is rejected with `RESOURCE_EXHAUSTED` too, but with different message.
+///
+///Slot is taken until response body is complete or dropped, while waiting request gives up its place once it is cancelled.
+///Waiting is bounded by `Timer`, which is tokio's one by default.
+///
+///```rust
+///use tonic_interceptor::limit::AdmissionQueue;
+///
+///use core::time::Duration;
+///
+///let queue = AdmissionQueue::new(64, 16, Duration::from_millis(50));
+/////Pass `queue.layer()` to `Server::layer`
+///# let _ = queue.layer();
+///```
+pub struct AdmissionQueue<T = TokioTimer> {
+    room: Arc<Room>,
+    timer: T,
+}
+
+impl AdmissionQueue {
+    ///Creates new instance, allowing `max_concurrent` requests, with up to `max_waiting` requests waiting for slot up to `max_wait`
+    pub fn new(max_concurrent: usize, max_waiting: usize, max_wait: Duration) -> Self {
+        Self {
+            room: Arc::new(Room {
+                semaphore: Arc::new(Semaphore::new(max_concurrent)),
+                max_concurrent,
+                waiting: AtomicUsize::new(0),
+                max_waiting,
+                max_wait,
+            }),
+            timer: TokioTimer,
+        }
+    }
+}
+
+impl<T> AdmissionQueue<T> {
+    #[inline]
+    ///Sets timer, which bounds waiting for slot
+    pub fn timer<T2: Timer>(self, timer: T2) -> AdmissionQueue<T2> {
+        AdmissionQueue {
+            room: self.room,
+            timer,
+        }
+    }
+
+    #[inline(always)]
+    ///Returns number of requests holding slot
+    pub fn in_flight(&self) -> usize {
+        self.room.max_concurrent - self.room.semaphore.available_permits()
+    }
+
+    #[inline(always)]
+    ///Returns number of requests waiting for slot
+    pub fn waiting(&self) -> usize {
+        self.room.waiting.load(Ordering::Acquire)
+    }
+
+    #[inline(always)]
+    ///Returns layer
+    pub fn layer(&self) -> AdmissionQueueLayer<T> where T: Clone {
+        AdmissionQueueLayer {
+            queue: self.clone(),
+        }
+    }
+
+    fn admit(&self) -> Result<Admission, tonic::Status> where T: Timer {
+        if let Ok(permit) = self.room.semaphore.clone().try_acquire_owned() {
+            return Ok(Admission::Admitted(permit));
+        }
+
+        if self.room.waiting.fetch_add(1, Ordering::AcqRel) >= self.room.max_waiting {
+            self.room.waiting.fetch_sub(1, Ordering::AcqRel);
+            return Err(tonic::Status::resource_exhausted("admission queue is full"));
+        }
+        let waiting = Waiting {
+            room: self.room.clone(),
+        };
+        let acquire = crate::timer::timeout(&self.timer, self.room.max_wait, Box::pin(self.room.semaphore.clone().acquire_owned()) as Pin<Box<_>>);
+        Ok(Admission::Wait(waiting, acquire))
+    }
+}
+
+impl<T> fmt::Debug for AdmissionQueue<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("AdmissionQueue").field("max_concurrent", &self.room.max_concurrent).field("in_flight", &self.in_flight())
+                                          .field("max_waiting", &self.room.max_waiting).field("waiting", &self.waiting())
+                                          .field("max_wait", &self.room.max_wait).finish()
+    }
+}
+
+#[derive(Clone, Debug)]
+///Layer of `AdmissionQueue`
+pub struct AdmissionQueueLayer<T = TokioTimer> {
+    queue: AdmissionQueue<T>,
+}
+
+impl<S, T: Clone> tower_layer::Layer<S> for AdmissionQueueLayer<T> {
+    type Service = AdmissionQueueService<S, T>;
+
+    #[inline(always)]
+    fn layer(&self, inner: S) -> Self::Service {
+        AdmissionQueueService {
+            queue: self.queue.clone(),
+            inner,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+///Service of `AdmissionQueue`
+pub struct AdmissionQueueService<S, T = TokioTimer> {
+    queue: AdmissionQueue<T>,
+    inner: S,
+}
+
+impl<S: tonic::server::NamedService, T> tonic::server::NamedService for AdmissionQueueService<S, T> {
+    const NAME: &'static str = S::NAME;
+}
+
+impl<ReqBody, ResBody, S, T: Timer> tower_service::Service<http::Request<ReqBody>> for AdmissionQueueService<S, T> where S: tower_service::Service<http::Request<ReqBody>, Response = http::Response<ResBody>> + Clone, ResBody: EmptyBody {
+    type Response = http::Response<AdmittedBody<ResBody>>;
+    type Error = S::Error;
+    type Future = AdmissionQueueFut<S, ReqBody>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        let state = match self.queue.admit() {
+            Ok(Admission::Admitted(permit)) => State::Call {
+                permit: Some(permit),
+                fut: self.inner.call(req),
+            },
+            Ok(Admission::Wait(waiting, acquire)) => {
+                //Inner service is ready, hence it is used once slot is taken
+                let inner = self.inner.clone();
+                let inner = core::mem::replace(&mut self.inner, inner);
+                State::Wait {
+                    waiting: Some(waiting),
+                    acquire,
+                    request: Some((inner, req)),
+                }
+            },
+            Err(status) => State::Rejected {
+                status: Some((status, ResponseTemplate::from_parts(req.headers(), req.version()))),
+            },
+        };
+        AdmissionQueueFut {
+            state,
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    #[project = StateProj]
+    enum State<S: tower_service::Service<http::Request<B>>, B> {
+        Wait {
+            waiting: Option<Waiting>,
+            acquire: Acquire,
+            request: Option<(S, http::Request<B>)>,
+        },
+        Call {
+            permit: Option<OwnedSemaphorePermit>,
+            #[pin]
+            fut: S::Future,
+        },
+        Rejected {
+            status: Option<(tonic::Status, ResponseTemplate)>,
+        },
+    }
+}
+
+pin_project_lite::pin_project! {
+    ///Future of `AdmissionQueueService`
+    ///
+    ///It waits for slot, if necessary, before calling inner service.
+    pub struct AdmissionQueueFut<S: tower_service::Service<http::Request<B>>, B> {
+        #[pin]
+        state: State<S, B>,
+    }
+}
+
+impl<S: tower_service::Service<http::Request<B>>, B> fmt::Debug for AdmissionQueueFut<S, B> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = match self.state {
+            State::Wait { .. } => "Wait",
+            State::Call { .. } => "Call",
+            State::Rejected { .. } => "Rejected",
+        };
+        fmt.debug_struct("AdmissionQueueFut").field("state", &state).finish_non_exhaustive()
+    }
+}
+
+impl<ResBody, B, S> Future for AdmissionQueueFut<S, B> where S: tower_service::Service<http::Request<B>, Response = http::Response<ResBody>>, ResBody: EmptyBody {
+    type Output = Result<http::Response<AdmittedBody<ResBody>>, S::Error>;
+
+    fn poll(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let mut this = self.project();
+        loop {
+            let next = match this.state.as_mut().project() {
+                StateProj::Call { permit, fut } => {
+                    return match fut.poll(ctx) {
+                        task::Poll::Ready(Ok(response)) => {
+                            let permit = permit.take();
+                            task::Poll::Ready(Ok(response.map(|inner| AdmittedBody {
+                                permit,
+                                inner: compat::Incoming::new(inner),
+                            })))
+                        },
+                        task::Poll::Ready(Err(error)) => {
+                            *permit = None;
+                            task::Poll::Ready(Err(error))
+                        },
+                        task::Poll::Pending => task::Poll::Pending,
+                    };
+                },
+                StateProj::Rejected { status } => {
+                    let (status, template) = status.take().expect("Future polled after completion");
+                    return task::Poll::Ready(Ok(response::status_to_response::<ResBody>(&status, &template).map(AdmittedBody::rejected)));
+                },
+                StateProj::Wait { waiting, acquire, request } => {
+                    let acquired = match Pin::new(acquire).poll(ctx) {
+                        task::Poll::Ready(acquired) => acquired,
+                        task::Poll::Pending => return task::Poll::Pending,
+                    };
+                    let waiting = waiting.take().expect("Future polled after completion");
+                    let (mut inner, req) = request.take().expect("Future polled after completion");
+                    match acquired {
+                        Some(Ok(permit)) => State::Call {
+                            permit: Some(permit),
+                            fut: inner.call(req),
+                        },
+                        Some(Err(_)) => State::Rejected {
+                            status: Some((tonic::Status::unavailable("admission queue is closed"), ResponseTemplate::from_parts(req.headers(), req.version()))),
+                        },
+                        None => {
+                            let message = format!("timed out waiting for admission after {}ms", waiting.room.max_wait.as_millis());
+                            State::Rejected {
+                                status: Some((tonic::Status::resource_exhausted(message), ResponseTemplate::from_parts(req.headers(), req.version()))),
+                            }
+                        },
+                    }
+                },
+            };
+            this.state.set(next);
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    ///Response body of `AdmissionQueueService`, releasing slot once trailers are received or it is dropped
+    pub struct AdmittedBody<B> {
+        permit: Option<OwnedSemaphorePermit>,
+        #[pin]
+        inner: compat::Incoming<B>,
+    }
+}
+
+impl<B> AdmittedBody<B> {
+    #[inline(always)]
+    fn rejected(inner: B) -> Self {
+        Self {
+            permit: None,
+            inner: compat::Incoming::new(inner),
+        }
+    }
+
+    #[inline(always)]
+    ///Returns whether body holds slot of `AdmissionQueue`
+    pub fn is_admitted(&self) -> bool {
+        self.permit.is_some()
+    }
+}
+
+impl<B: Default> Default for AdmittedBody<B> {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::rejected(B::default())
+    }
+}
+
+impl<B> fmt::Debug for AdmittedBody<B> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("AdmittedBody").field("is_admitted", &self.is_admitted()).finish_non_exhaustive()
+    }
+}
+
+compat::impl_body! {
+    [B: http_body::Body] AdmittedBody<B> {
+        type Data = B::Data;
+        type Error = B::Error;
+
+        #[inline(always)]
+        fn poll_data(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Option<Result<Self::Data, Self::Error>>> {
+            compat::Body::poll_data(self.project().inner, ctx)
+        }
+
+        fn poll_trailers(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Result<Option<http::HeaderMap>, Self::Error>> {
+            let this = self.project();
+
+            let result = compat::Body::poll_trailers(this.inner, ctx);
+            if result.is_ready() {
+                *this.permit = None;
+            }
+            result
+        }
+
+        #[inline(always)]
+        fn is_end_stream(&self) -> bool {
+            self.inner.is_end_stream()
+        }
+
+        #[inline(always)]
+        fn size_hint(&self) -> http_body::SizeHint {
+            self.inner.size_hint()
+        }
+    }
+}
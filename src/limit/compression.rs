@@ -0,0 +1,317 @@
+use crate::compat;
+
+use core::fmt;
+use core::task;
+use core::pin::Pin;
+use std::io::{self, Write};
+
+use bytes::{Buf, Bytes};
+
+//Compressed flag and length
+const FRAME_HEADER_SIZE: usize = 5;
+
+#[derive(Clone, Copy, Debug)]
+///Guard against decompression bombs in compressed request messages
+///
+///Since `tonic` decompresses messages after layers, expanded size of message cannot be known, unless guard decompresses it by itself:
+///
+///- Compressed message over `max_compressed_size` is rejected, using length of frame header;
+///- With `inspect`, `gzip` messages are decompressed as they are received, counting decompressed bytes without keeping them.
+///Message is rejected as soon as it expands over `max_decompressed_size` or over `max_ratio` times its compressed size.
+///
+///```rust
+///use tonic_interceptor::limit::CompressionGuard;
+///
+///let guard = CompressionGuard::new(64 * 1024).inspect(4 * 1024 * 1024).max_ratio(100);
+/////Pass `guard.layer()` to `Server::layer`
+///# let _ = guard.layer();
+///```
+///
+///Rejection is reported as `RESOURCE_EXHAUSTED` error of request body, which `tonic` returns as status of call.
+///Uncompressed messages are not guarded, as their size is limited by `max_decoding_message_size` of `tonic`.
+pub struct CompressionGuard {
+    max_compressed_size: usize,
+    max_decompressed_size: Option<usize>,
+    max_ratio: Option<usize>,
+}
+
+impl CompressionGuard {
+    #[inline]
+    ///Creates new instance, limiting size of compressed message
+    pub const fn new(max_compressed_size: usize) -> Self {
+        Self {
+            max_compressed_size,
+            max_decompressed_size: None,
+            max_ratio: None,
+        }
+    }
+
+    #[inline]
+    ///Enables decompression of `gzip` messages, limiting size of decompressed message
+    pub const fn inspect(mut self, max_decompressed_size: usize) -> Self {
+        self.max_decompressed_size = Some(max_decompressed_size);
+        self
+    }
+
+    #[inline]
+    ///Sets maximum ratio of decompressed size to compressed size, applied only with `inspect`
+    pub const fn max_ratio(mut self, max_ratio: usize) -> Self {
+        self.max_ratio = Some(max_ratio);
+        self
+    }
+
+    #[inline]
+    ///Returns layer
+    pub const fn layer(&self) -> CompressionGuardLayer {
+        CompressionGuardLayer {
+            guard: *self,
+        }
+    }
+
+    //Returns limit of decompressed message with compressed size `len`
+    fn decompressed_limit(&self, len: usize) -> Option<usize> {
+        let limit = self.max_decompressed_size?;
+        Some(match self.max_ratio {
+            Some(ratio) => limit.min(len.saturating_mul(ratio)),
+            None => limit,
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+///Layer of `CompressionGuard`
+pub struct CompressionGuardLayer {
+    guard: CompressionGuard,
+}
+
+impl<S> tower_layer::Layer<S> for CompressionGuardLayer {
+    type Service = CompressionGuardService<S>;
+
+    #[inline(always)]
+    fn layer(&self, inner: S) -> Self::Service {
+        CompressionGuardService {
+            guard: self.guard,
+            inner,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+///Service of `CompressionGuard`
+pub struct CompressionGuardService<S> {
+    guard: CompressionGuard,
+    inner: S,
+}
+
+impl<S: tonic::server::NamedService> tonic::server::NamedService for CompressionGuardService<S> {
+    const NAME: &'static str = S::NAME;
+}
+
+impl<ReqBody, S: tower_service::Service<http::Request<GuardedBody<ReqBody>>>> tower_service::Service<http::Request<ReqBody>> for CompressionGuardService<S> {
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        let is_gzip = req.headers().get("grpc-encoding").is_some_and(|encoding| encoding == "gzip");
+        let guard = self.guard;
+        self.inner.call(req.map(|body| GuardedBody::new(body, guard, is_gzip)))
+    }
+}
+
+//Output of decompression, which only counts bytes
+struct Counter {
+    written: usize,
+    limit: usize,
+    exceeded: bool,
+}
+
+impl Write for Counter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.written += buf.len();
+        if self.written > self.limit {
+            self.exceeded = true;
+            return Err(io::Error::new(io::ErrorKind::Other, "decompressed message exceeds limit"));
+        }
+        Ok(buf.len())
+    }
+
+    #[inline(always)]
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+type Decoder = flate2::write::GzDecoder<Counter>;
+
+///Request body passed to inner service by `CompressionGuardService`
+///
+///It yields data of request body as it is, failing with `RESOURCE_EXHAUSTED` once message exceeds limits of guard.
+pub struct GuardedBody<B> {
+    inner: compat::Incoming<B>,
+    guard: CompressionGuard,
+    is_gzip: bool,
+    header: [u8; FRAME_HEADER_SIZE],
+    header_len: usize,
+    //Remaining bytes of current message, once its header is complete
+    remaining: Option<usize>,
+    decoder: Option<Decoder>,
+    is_failed: bool,
+}
+
+impl<B> GuardedBody<B> {
+    fn new(inner: B, guard: CompressionGuard, is_gzip: bool) -> Self {
+        Self {
+            inner: compat::Incoming::new(inner),
+            guard,
+            is_gzip,
+            header: [0; FRAME_HEADER_SIZE],
+            header_len: 0,
+            remaining: None,
+            decoder: None,
+            is_failed: false,
+        }
+    }
+
+    fn start_message(&mut self) -> Result<(), tonic::Status> {
+        let len = (&self.header[1..]).get_u32() as usize;
+        self.header_len = 0;
+        self.remaining = Some(len);
+        if self.header[0] != 1 {
+            return Ok(());
+        }
+
+        if len > self.guard.max_compressed_size {
+            return Err(tonic::Status::resource_exhausted(format!("compressed message of {} bytes exceeds limit of {} bytes", len, self.guard.max_compressed_size)));
+        }
+        if self.is_gzip {
+            if let Some(limit) = self.guard.decompressed_limit(len) {
+                self.decoder = Some(Decoder::new(Counter {
+                    written: 0,
+                    limit,
+                    exceeded: false,
+                }));
+            }
+        }
+        Ok(())
+    }
+
+    fn decompress(&mut self, data: &[u8], is_end: bool) -> Result<(), tonic::Status> {
+        let decoder = match self.decoder.as_mut() {
+            Some(decoder) => decoder,
+            None => return Ok(()),
+        };
+        let result = match is_end {
+            false => decoder.write_all(data),
+            true => decoder.write_all(data).and_then(|_| decoder.try_finish()),
+        };
+        match result {
+            Ok(()) => Ok(()),
+            Err(_) if decoder.get_ref().exceeded => {
+                let limit = decoder.get_ref().limit;
+                Err(tonic::Status::resource_exhausted(format!("decompressed message exceeds limit of {} bytes", limit)))
+            },
+            Err(error) => Err(tonic::Status::internal(format!("failed to decompress message: {}", error))),
+        }
+    }
+
+    //Checks chunk of request body, which can contain any part of messages
+    fn check(&mut self, mut data: &[u8]) -> Result<(), tonic::Status> {
+        loop {
+            match self.remaining {
+                Some(remaining) => {
+                    let len = remaining.min(data.len());
+                    let remaining = remaining - len;
+                    self.decompress(&data[..len], remaining == 0)?;
+                    data = &data[len..];
+                    self.remaining = match remaining {
+                        0 => {
+                            self.decoder = None;
+                            None
+                        },
+                        remaining => Some(remaining),
+                    };
+                },
+                None if data.is_empty() => break Ok(()),
+                None => {
+                    let len = (FRAME_HEADER_SIZE - self.header_len).min(data.len());
+                    self.header[self.header_len..self.header_len + len].copy_from_slice(&data[..len]);
+                    self.header_len += len;
+                    data = &data[len..];
+                    if self.header_len == FRAME_HEADER_SIZE {
+                        self.start_message()?;
+                    }
+                },
+            }
+
+            if data.is_empty() && self.remaining != Some(0) {
+                break Ok(());
+            }
+        }
+    }
+}
+
+impl<B: Default> Default for GuardedBody<B> {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::new(B::default(), CompressionGuard::new(usize::MAX), false)
+    }
+}
+
+impl<B> fmt::Debug for GuardedBody<B> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("GuardedBody").field("guard", &self.guard).field("is_gzip", &self.is_gzip).field("is_failed", &self.is_failed).finish_non_exhaustive()
+    }
+}
+
+compat::impl_body! {
+    [B: http_body::Body<Data = Bytes> + Unpin] GuardedBody<B> where [B::Error: Into<crate::client::BoxError>] {
+        type Data = Bytes;
+        type Error = tonic::Status;
+
+        fn poll_data(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Option<Result<Self::Data, Self::Error>>> {
+            let this = self.get_mut();
+            if this.is_failed {
+                return task::Poll::Ready(None);
+            }
+            match Pin::new(&mut this.inner).poll_data(ctx) {
+                task::Poll::Ready(Some(Ok(data))) => match this.check(&data) {
+                    Ok(()) => task::Poll::Ready(Some(Ok(data))),
+                    Err(status) => {
+                        this.is_failed = true;
+                        this.decoder = None;
+                        task::Poll::Ready(Some(Err(status)))
+                    },
+                },
+                task::Poll::Ready(Some(Err(error))) => task::Poll::Ready(Some(Err(tonic::Status::from_error(error.into())))),
+                task::Poll::Ready(None) => task::Poll::Ready(None),
+                task::Poll::Pending => task::Poll::Pending,
+            }
+        }
+
+        fn poll_trailers(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Result<Option<http::HeaderMap>, Self::Error>> {
+            let this = self.get_mut();
+            if this.is_failed {
+                return task::Poll::Ready(Ok(None));
+            }
+            Pin::new(&mut this.inner).poll_trailers(ctx).map_err(|error| tonic::Status::from_error(error.into()))
+        }
+
+        #[inline]
+        fn is_end_stream(&self) -> bool {
+            self.is_failed || self.inner.is_end_stream()
+        }
+
+        #[inline(always)]
+        fn size_hint(&self) -> http_body::SizeHint {
+            self.inner.size_hint()
+        }
+    }
+}
+
@@ -0,0 +1,71 @@
+//! Method matching
+//!
+//!Patterns:
+//!
+//!- `/pkg.Service/Method` - exact method;
+//!- `/pkg.Service/*` - any method of service;
+//!- `*` - any method.
+
+use core::fmt;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+///Matcher of method path
+pub enum MethodMatcher {
+    ///Exact method path
+    Exact(String),
+    ///Any method of the service, with its path prefix including trailing `/`
+    Service(String),
+    ///Any method
+    Any,
+}
+
+impl MethodMatcher {
+    ///Parses pattern
+    pub fn new(pattern: &str) -> Self {
+        if pattern == "*" {
+            Self::Any
+        } else if let Some(prefix) = pattern.strip_suffix('*') {
+            Self::Service(prefix.to_owned())
+        } else {
+            Self::Exact(pattern.to_owned())
+        }
+    }
+
+    #[inline]
+    ///Returns whether `path` is matched
+    pub fn matches(&self, path: &str) -> bool {
+        match self {
+            Self::Exact(method) => method == path,
+            Self::Service(prefix) => path.starts_with(prefix.as_str()),
+            Self::Any => true,
+        }
+    }
+
+    #[inline]
+    ///Returns specificity of matcher, where exact method is the most specific.
+    pub fn specificity(&self) -> u8 {
+        match self {
+            Self::Exact(_) => 2,
+            Self::Service(_) => 1,
+            Self::Any => 0,
+        }
+    }
+}
+
+impl From<&str> for MethodMatcher {
+    #[inline(always)]
+    fn from(pattern: &str) -> Self {
+        Self::new(pattern)
+    }
+}
+
+impl fmt::Display for MethodMatcher {
+    #[inline]
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Exact(method) => fmt.write_str(method),
+            Self::Service(prefix) => write!(fmt, "{}*", prefix),
+            Self::Any => fmt.write_str("*"),
+        }
+    }
+}
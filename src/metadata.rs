@@ -0,0 +1,68 @@
+//! Metadata helpers
+//!
+//!`MetadataMapExt` covers common pattern of interceptor: get value of key, rejecting request if it is missing or malformed.
+//!Every error message includes name of the key.
+
+use tonic::metadata::{AsciiMetadataValue, BinaryMetadataValue, MetadataMap};
+
+use core::str::FromStr;
+
+#[inline(always)]
+fn missing(key: &str, code: tonic::Code) -> tonic::Status {
+    tonic::Status::new(code, format!("missing metadata '{}'", key))
+}
+
+#[inline(always)]
+fn invalid(key: &str, reason: &str) -> tonic::Status {
+    tonic::Status::invalid_argument(format!("metadata '{}' {}", key, reason))
+}
+
+///Extension of `MetadataMap`
+pub trait MetadataMapExt {
+    ///Gets ASCII value of `key`, rejecting with `code` if it is missing
+    ///
+    ///Typically `code` is `Unauthenticated` for credentials and `InvalidArgument` otherwise.
+    fn require(&self, key: &str, code: tonic::Code) -> Result<&AsciiMetadataValue, tonic::Status>;
+
+    ///Gets binary value of `key`, rejecting with `code` if it is missing
+    ///
+    ///Note that value is not decoded, hence it might not be valid base64.
+    fn require_bin(&self, key: &str, code: tonic::Code) -> Result<&BinaryMetadataValue, tonic::Status>;
+
+    ///Gets value of `key` as string, if it is present and consists of visible ASCII only
+    fn get_str(&self, key: &str) -> Option<&str>;
+
+    ///Parses value of `key`, if it is present
+    ///
+    ///Rejects with `InvalidArgument` if value is not visible ASCII or cannot be parsed.
+    fn parse<T: FromStr>(&self, key: &str) -> Result<Option<T>, tonic::Status>;
+}
+
+impl MetadataMapExt for MetadataMap {
+    #[inline]
+    fn require(&self, key: &str, code: tonic::Code) -> Result<&AsciiMetadataValue, tonic::Status> {
+        self.get(key).ok_or_else(|| missing(key, code))
+    }
+
+    #[inline]
+    fn require_bin(&self, key: &str, code: tonic::Code) -> Result<&BinaryMetadataValue, tonic::Status> {
+        self.get_bin(key).ok_or_else(|| missing(key, code))
+    }
+
+    #[inline]
+    fn get_str(&self, key: &str) -> Option<&str> {
+        self.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn parse<T: FromStr>(&self, key: &str) -> Result<Option<T>, tonic::Status> {
+        let value = match self.get(key) {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        let value = value.to_str().map_err(|_| invalid(key, "is not valid string"))?;
+        match value.parse() {
+            Ok(value) => Ok(Some(value)),
+            Err(_) => Err(invalid(key, "has invalid format")),
+        }
+    }
+}
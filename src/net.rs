@@ -0,0 +1,38 @@
+//! Connection level authentication
+//!
+//!`UdsPeer` authenticates callers over unix domain socket by credentials of peer process:
+//!
+//!```rust
+//!# #[cfg(all(unix, feature = "uds"))] {
+//!use tonic_interceptor::net::{UdsPeer, UidTable};
+//!
+//!let peer = UdsPeer::new(UidTable::new().uid(0, "root", vec!["admin"]).gid(100, vec!["reader"]));
+//!# }
+//!```
+//!
+//!Credentials are taken from `UdsConnectInfo`, which tonic inserts into request extensions when serving `tokio::net::UnixStream`.
+//!It requires `uds` feature on unix.
+//!
+//!`ResolveRealIp` determines address of client behind trusted proxies, making it available as `RealIp`:
+//!
+//!```rust
+//!use tonic_interceptor::net::{ForwardedHeader, ResolveRealIp};
+//!
+//!let resolve = ResolveRealIp::new().trust("10.0.0.0/8").expect("valid CIDR")
+//!                                  .trust("::1").expect("valid CIDR")
+//!                                  .headers(&[ForwardedHeader::Forwarded, ForwardedHeader::XForwardedFor]);
+//!```
+//!
+//!Address of peer connected to `tonic::transport::Server` is available with `transport` feature.
+//!
+//!`IpFilter` admits clients by their address, as well as built-in interceptors keyed by client, such as `config::RateLimit`
+//!with `per_client`, and observers, such as `observe::RejectionLog` and `config::Logging`, use `RealIp::client`.
+
+#[cfg(all(unix, feature = "uds"))]
+mod uds;
+#[cfg(all(unix, feature = "uds"))]
+pub use uds::{PeerResolver, UidTable, UdsPeer, UdsConnectInfo, UCred};
+mod real_ip;
+pub use real_ip::{Cidr, InvalidCidr, ForwardedHeader, RealIp, ResolveRealIp};
+mod ip_filter;
+pub use ip_filter::IpFilter;
@@ -0,0 +1,80 @@
+use crate::Interceptor;
+use super::{Cidr, InvalidCidr, RealIp};
+
+use std::net::IpAddr;
+
+#[derive(Clone, Debug, Default)]
+///Server interceptor which admits requests by address of client
+///
+///Address is taken from `RealIp::client`, hence place it after `ResolveRealIp` when served behind proxies.
+///Denied blocks take precedence over allowed ones, while without allowed blocks any address, which is not denied, is allowed.
+///Request with unknown address is allowed only when there are no allowed blocks.
+///
+///Rejection is `PERMISSION_DENIED`.
+pub struct IpFilter {
+    allow: Vec<Cidr>,
+    deny: Vec<Cidr>,
+}
+
+impl IpFilter {
+    #[inline]
+    ///Creates new instance, allowing every address
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    ///Adds block of allowed addresses, e.g. `10.0.0.0/8`
+    pub fn allow(mut self, cidr: &str) -> Result<Self, InvalidCidr> {
+        self.allow.push(cidr.parse()?);
+        Ok(self)
+    }
+
+    #[inline]
+    ///Adds block of allowed addresses
+    pub fn allow_cidr(mut self, cidr: Cidr) -> Self {
+        self.allow.push(cidr);
+        self
+    }
+
+    #[inline]
+    ///Adds block of denied addresses, e.g. `192.0.2.0/24`
+    pub fn deny(mut self, cidr: &str) -> Result<Self, InvalidCidr> {
+        self.deny.push(cidr.parse()?);
+        Ok(self)
+    }
+
+    #[inline]
+    ///Adds block of denied addresses
+    pub fn deny_cidr(mut self, cidr: Cidr) -> Self {
+        self.deny.push(cidr);
+        self
+    }
+
+    ///Returns whether client with `addr` is admitted
+    pub fn is_allowed(&self, addr: Option<IpAddr>) -> bool {
+        match addr {
+            Some(addr) => !self.deny.iter().any(|cidr| cidr.contains(addr)) && (self.allow.is_empty() || self.allow.iter().any(|cidr| cidr.contains(addr))),
+            None => self.allow.is_empty(),
+        }
+    }
+}
+
+impl Interceptor for IpFilter {
+    #[inline]
+    fn on_request(&self, _: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        match self.is_allowed(RealIp::client(extensions)) {
+            true => None,
+            false => Some(tonic::Status::permission_denied("client address is not allowed")),
+        }
+    }
+
+    #[inline]
+    fn on_request_headers(&self, _: &http::Uri, _: &mut http::HeaderMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        self.on_request(&mut tonic::metadata::MetadataMap::new(), extensions)
+    }
+
+    #[inline(always)]
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
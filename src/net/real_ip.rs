@@ -0,0 +1,421 @@
+use crate::Interceptor;
+
+use core::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+///Error of parsing `Cidr`
+pub struct InvalidCidr {
+    source: String,
+}
+
+impl fmt::Display for InvalidCidr {
+    #[inline]
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "invalid CIDR '{}'", self.source)
+    }
+}
+
+impl std::error::Error for InvalidCidr {
+}
+
+//Returns IPv4 address for IPv4-mapped IPv6 address
+#[inline]
+fn canonical(addr: IpAddr) -> IpAddr {
+    match addr {
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => IpAddr::V4(v4),
+            None => IpAddr::V6(v6),
+        },
+        addr => addr,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+///Block of IP addresses, e.g. `10.0.0.0/8` or `fd00::/8`
+///
+///Address without prefix length is block of single address.
+///IPv4-mapped IPv6 addresses are treated as IPv4 addresses.
+pub struct Cidr {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    ///Creates block of addresses sharing first `prefix` bits with `addr`
+    ///
+    ///Returns `None` if `prefix` exceeds number of bits of address.
+    pub fn new(addr: IpAddr, prefix: u8) -> Option<Self> {
+        let addr = canonical(addr);
+        match addr {
+            IpAddr::V4(v4) if prefix <= 32 => {
+                let mask = u32::MAX.checked_shl(32 - u32::from(prefix)).unwrap_or(0);
+                Some(Self {
+                    addr: IpAddr::V4(Ipv4Addr::from(u32::from(v4) & mask)),
+                    prefix,
+                })
+            },
+            IpAddr::V6(v6) if prefix <= 128 => {
+                let mask = u128::MAX.checked_shl(128 - u32::from(prefix)).unwrap_or(0);
+                Some(Self {
+                    addr: IpAddr::V6(Ipv6Addr::from(u128::from(v6) & mask)),
+                    prefix,
+                })
+            },
+            _ => None,
+        }
+    }
+
+    #[inline(always)]
+    ///Returns first address of block
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    #[inline(always)]
+    ///Returns prefix length
+    pub fn prefix(&self) -> u8 {
+        self.prefix
+    }
+
+    ///Returns whether `addr` belongs to block
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        match (self.addr, canonical(addr)) {
+            (IpAddr::V4(network), IpAddr::V4(addr)) => {
+                let mask = u32::MAX.checked_shl(32 - u32::from(self.prefix)).unwrap_or(0);
+                u32::from(addr) & mask == u32::from(network)
+            },
+            (IpAddr::V6(network), IpAddr::V6(addr)) => {
+                let mask = u128::MAX.checked_shl(128 - u32::from(self.prefix)).unwrap_or(0);
+                u128::from(addr) & mask == u128::from(network)
+            },
+            _ => false,
+        }
+    }
+}
+
+impl core::str::FromStr for Cidr {
+    type Err = InvalidCidr;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let error = || InvalidCidr {
+            source: source.to_owned(),
+        };
+        let (addr, prefix) = match source.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (source, None),
+        };
+        let addr = addr.parse::<IpAddr>().map_err(|_| error())?;
+        let prefix = match prefix {
+            Some(prefix) => prefix.parse::<u8>().map_err(|_| error())?,
+            None => match addr {
+                IpAddr::V4(_) => 32,
+                IpAddr::V6(_) => 128,
+            },
+        };
+        //Prefix of IPv4-mapped address applies to IPv6 form
+        let prefix = match (addr, canonical(addr)) {
+            (IpAddr::V6(_), IpAddr::V4(_)) => prefix.checked_sub(96).ok_or_else(error)?,
+            _ => prefix,
+        };
+        Self::new(addr, prefix).ok_or_else(error)
+    }
+}
+
+impl fmt::Display for Cidr {
+    #[inline]
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+///Header, which carries address of client as seen by proxy
+pub enum ForwardedHeader {
+    ///RFC 7239 `forwarded`, e.g. `for=192.0.2.60;proto=http, for="[2001:db8::1]:4711"`
+    Forwarded,
+    ///`x-forwarded-for`, list of addresses with client on the left, e.g. `192.0.2.60, 10.0.0.1`
+    XForwardedFor,
+    ///`x-real-ip`, single address of client
+    XRealIp,
+}
+
+impl ForwardedHeader {
+    #[inline(always)]
+    ///Returns name of header
+    pub const fn name(&self) -> &'static str {
+        match self {
+            ForwardedHeader::Forwarded => "forwarded",
+            ForwardedHeader::XForwardedFor => "x-forwarded-for",
+            ForwardedHeader::XRealIp => "x-real-ip",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+///Address of client, as resolved by `ResolveRealIp`
+pub struct RealIp(pub IpAddr);
+
+impl RealIp {
+    #[inline(always)]
+    ///Returns address of request's client, if resolved
+    pub fn from_extensions(extensions: &http::Extensions) -> Option<&Self> {
+        extensions.get()
+    }
+
+    ///Returns address of request's client, preferring `RealIp` over address of peer
+    ///
+    ///When `ResolveRealIp` fails to resolve client, nothing is returned, as peer is proxy rather than client.
+    pub fn client(extensions: &http::Extensions) -> Option<IpAddr> {
+        match Self::from_extensions(extensions) {
+            Some(addr) => Some(addr.0),
+            None if extensions.get::<Unresolved>().is_some() => None,
+            None => default_peer(extensions).map(canonical),
+        }
+    }
+
+    #[inline]
+    ///Returns address of `client` as string, suitable for `peer_with` of observers
+    pub fn peer(extensions: &http::Extensions) -> Option<String> {
+        Self::client(extensions).map(|addr| addr.to_string())
+    }
+
+    #[inline]
+    ///Returns address of `client` as key of `limit::KeyedLimit`, limiting requests of each client
+    pub fn rate_limit_key(_: &tonic::metadata::MetadataMap, extensions: &http::Extensions, _: &str) -> Option<String> {
+        Self::peer(extensions)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+//Marks request, which client `ResolveRealIp` failed to resolve
+struct Unresolved;
+
+//Parses address, optionally with port, e.g. `192.0.2.60:80` or `[2001:db8::1]:4711`
+fn parse_addr(value: &str) -> Option<IpAddr> {
+    let value = value.trim();
+    if let Ok(addr) = value.parse::<IpAddr>() {
+        return Some(canonical(addr));
+    }
+    if let Ok(addr) = value.parse::<SocketAddr>() {
+        return Some(canonical(addr.ip()));
+    }
+    value.strip_prefix('[').and_then(|value| value.strip_suffix(']')).and_then(|value| value.parse::<Ipv6Addr>().ok()).map(|addr| canonical(IpAddr::V6(addr)))
+}
+
+//Splits `value` by `separator`, outside of quoted strings
+fn split_unquoted(value: &str, separator: char) -> impl Iterator<Item = &str> {
+    let mut quoted = false;
+    let mut escaped = false;
+    value.split(move |ch: char| {
+        if escaped {
+            escaped = false;
+        } else if quoted && ch == '\\' {
+            escaped = true;
+        } else if ch == '"' {
+            quoted = !quoted;
+        } else if ch == separator && !quoted {
+            return true;
+        }
+        false
+    })
+}
+
+//Returns address of `for` parameter of `forwarded` element, or `None` if it is absent, obfuscated or malformed
+fn forwarded_for(element: &str) -> Option<IpAddr> {
+    let value = split_unquoted(element, ';').find_map(|pair| match pair.trim().split_once('=') {
+        Some((key, value)) if key.trim().eq_ignore_ascii_case("for") => Some(value.trim()),
+        _ => None,
+    })?;
+    let value = match value.strip_prefix('"') {
+        Some(value) => value.strip_suffix('"')?,
+        None => value,
+    };
+    parse_addr(value)
+}
+
+type PeerExtractor = dyn Fn(&http::Extensions) -> Option<IpAddr> + Send + Sync;
+
+//Returns address of TCP peer
+fn default_peer(extensions: &http::Extensions) -> Option<IpAddr> {
+    #[cfg(feature = "transport")]
+    if let Some(addr) = extensions.get::<tonic::transport::server::TcpConnectInfo>().and_then(|info| info.remote_addr()) {
+        return Some(addr.ip());
+    }
+    extensions.get::<SocketAddr>().map(|addr| addr.ip())
+}
+
+#[derive(Clone)]
+///Server interceptor which resolves address of client behind trusted proxies, inserting it as `RealIp` extension
+///
+///Headers are consulted only when connection comes from trusted proxy, in configured order of preference
+///(by default `forwarded`, `x-forwarded-for` and `x-real-ip`), using the first present one:
+///
+///- List headers are walked from the right, skipping trusted hops, and the first untrusted address is client;
+///- When every address is trusted, the leftmost one is client;
+///- Malformed, obfuscated or `unknown` entry stops the walk, as hops to the left of it cannot be verified,
+///  leaving the last trusted address as client. When it is the rightmost entry, client is unresolved;
+///- `x-real-ip` is taken as it is, using its last value, while malformed one leaves client unresolved.
+///
+///Headers after the first present one are ignored even when it is malformed, as client might control them.
+///Without headers, or when connection comes from untrusted address, peer address itself is client.
+///When peer address is unknown, headers cannot be trusted and client is unresolved as well.
+///
+///Resolved client is inserted as `RealIp`, while unresolved one makes `RealIp::client` return nothing,
+///so that consumers, such as `IpFilter`, do not mistake proxy for client.
+///
+///Peer address is taken from tonic's `TcpConnectInfo` with `transport` feature, or `std::net::SocketAddr` extension,
+///unless configured otherwise with `peer_with`.
+///Without `transport` feature, requests served by `tonic::transport::Server` carry no `SocketAddr`,
+///hence `peer_with` must be used to resolve anything.
+pub struct ResolveRealIp {
+    trusted: Vec<Cidr>,
+    headers: Vec<ForwardedHeader>,
+    peer: Option<Arc<PeerExtractor>>,
+}
+
+impl ResolveRealIp {
+    #[inline]
+    ///Creates new instance without trusted proxies
+    pub fn new() -> Self {
+        Self {
+            trusted: Vec::new(),
+            headers: vec![ForwardedHeader::Forwarded, ForwardedHeader::XForwardedFor, ForwardedHeader::XRealIp],
+            peer: None,
+        }
+    }
+
+    #[inline]
+    ///Adds block of trusted proxy addresses, e.g. `10.0.0.0/8`
+    pub fn trust(mut self, cidr: &str) -> Result<Self, InvalidCidr> {
+        self.trusted.push(cidr.parse()?);
+        Ok(self)
+    }
+
+    #[inline]
+    ///Adds block of trusted proxy addresses
+    pub fn trust_cidr(mut self, cidr: Cidr) -> Self {
+        self.trusted.push(cidr);
+        self
+    }
+
+    #[inline]
+    ///Sets headers to consult, in order of preference
+    pub fn headers(mut self, headers: &[ForwardedHeader]) -> Self {
+        self.headers = headers.to_vec();
+        self
+    }
+
+    #[inline]
+    ///Uses `extractor` to get address of peer, such as remote address from connection info
+    pub fn peer_with<F: Fn(&http::Extensions) -> Option<IpAddr> + Send + Sync + 'static>(mut self, extractor: F) -> Self {
+        self.peer = Some(Arc::new(extractor));
+        self
+    }
+
+    #[inline]
+    ///Returns whether `addr` is trusted proxy
+    pub fn is_trusted(&self, addr: IpAddr) -> bool {
+        self.trusted.iter().any(|cidr| cidr.contains(addr))
+    }
+
+    //Walks hops from the right, returning the first untrusted one
+    fn walk(&self, hops: Vec<Option<IpAddr>>) -> Option<IpAddr> {
+        let mut last = None;
+        for hop in hops.into_iter().rev() {
+            match hop {
+                Some(addr) if !self.is_trusted(addr) => return Some(addr),
+                Some(addr) => last = Some(addr),
+                None => return last,
+            }
+        }
+        last
+    }
+
+    ///Resolves address of client from `headers` of request, which came from `peer`
+    ///
+    ///Returns `None` when `peer` is unknown or the first present header is malformed.
+    pub fn resolve(&self, headers: &http::HeaderMap, peer: Option<IpAddr>) -> Option<IpAddr> {
+        let peer = canonical(peer?);
+        if !self.is_trusted(peer) {
+            return Some(peer);
+        }
+
+        for header in self.headers.iter() {
+            if !headers.contains_key(header.name()) {
+                continue;
+            }
+
+            let values = headers.get_all(header.name());
+            return match header {
+                ForwardedHeader::Forwarded => {
+                    let hops = values.iter().flat_map(|value| match value.to_str() {
+                        Ok(value) => split_unquoted(value, ',').map(forwarded_for).collect(),
+                        Err(_) => vec![None],
+                    }).collect();
+                    self.walk(hops)
+                },
+                ForwardedHeader::XForwardedFor => {
+                    let hops = values.iter().flat_map(|value| match value.to_str() {
+                        Ok(value) => value.split(',').map(parse_addr).collect(),
+                        Err(_) => vec![None],
+                    }).collect();
+                    self.walk(hops)
+                },
+                ForwardedHeader::XRealIp => values.iter().next_back().and_then(|value| value.to_str().ok()).and_then(parse_addr),
+            };
+        }
+
+        Some(peer)
+    }
+
+    #[inline]
+    fn apply(&self, headers: &http::HeaderMap, extensions: &mut http::Extensions) {
+        let peer = match &self.peer {
+            Some(peer) => peer(extensions),
+            None => default_peer(extensions),
+        };
+        match self.resolve(headers, peer) {
+            Some(addr) => {
+                extensions.insert(RealIp(addr));
+            },
+            None => {
+                extensions.insert(Unresolved);
+            },
+        }
+    }
+}
+
+impl Default for ResolveRealIp {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for ResolveRealIp {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("ResolveRealIp").field("trusted", &self.trusted).field("headers", &self.headers).finish_non_exhaustive()
+    }
+}
+
+impl Interceptor for ResolveRealIp {
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        let raw = crate::compat::metadata_into_headers(core::mem::take(headers));
+        self.apply(&raw, extensions);
+        *headers = crate::compat::metadata_from_headers(raw);
+        None
+    }
+
+    #[inline]
+    fn on_request_headers(&self, _: &http::Uri, headers: &mut http::HeaderMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        self.apply(headers, extensions);
+        None
+    }
+
+    #[inline(always)]
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
@@ -0,0 +1,120 @@
+use crate::Interceptor;
+use crate::identity::{Mechanism, PeerIdentity};
+
+use std::collections::HashMap;
+pub use tonic::transport::server::UdsConnectInfo;
+pub use tokio::net::unix::UCred;
+
+///Resolver of peer credentials into identity
+///
+///Implemented for every `Fn(&UCred) -> Option<PeerIdentity>`.
+pub trait PeerResolver {
+    ///Returns identity of peer, or `None` if peer is unknown
+    fn resolve(&self, cred: &UCred) -> Option<PeerIdentity>;
+}
+
+impl<F: Fn(&UCred) -> Option<PeerIdentity>> PeerResolver for F {
+    #[inline(always)]
+    fn resolve(&self, cred: &UCred) -> Option<PeerIdentity> {
+        (self)(cred)
+    }
+}
+
+#[derive(Clone, Default, Debug)]
+///Table of known users, resolving them into identity with `Mechanism::Uds`
+///
+///Subject and roles are taken from entry of user, while group of peer process grants additional roles.
+///Users absent from table are unknown, regardless of their group.
+pub struct UidTable {
+    users: HashMap<u32, (String, Vec<String>)>,
+    groups: HashMap<u32, Vec<String>>,
+}
+
+impl UidTable {
+    #[inline]
+    ///Creates empty table
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///Adds user `uid` with `subject` and `roles`
+    pub fn uid<R: Into<String>>(mut self, uid: u32, subject: impl Into<String>, roles: impl IntoIterator<Item = R>) -> Self {
+        self.users.insert(uid, (subject.into(), roles.into_iter().map(Into::into).collect()));
+        self
+    }
+
+    ///Grants `roles` to users with primary group `gid`
+    pub fn gid<R: Into<String>>(mut self, gid: u32, roles: impl IntoIterator<Item = R>) -> Self {
+        self.groups.entry(gid).or_default().extend(roles.into_iter().map(Into::into));
+        self
+    }
+}
+
+impl PeerResolver for UidTable {
+    fn resolve(&self, cred: &UCred) -> Option<PeerIdentity> {
+        let (subject, roles) = self.users.get(&cred.uid())?;
+        let mut identity = PeerIdentity::new(Mechanism::Uds).subject(subject.as_str());
+        identity.roles.extend(roles.iter().cloned());
+        if let Some(roles) = self.groups.get(&cred.gid()) {
+            identity.roles.extend(roles.iter().cloned());
+        }
+        Some(identity)
+    }
+}
+
+#[derive(Clone, Debug)]
+///Server interceptor which authenticates peer of unix domain socket
+///
+///Resolved identity is inserted as `PeerIdentity` with `UCred` in its `extra`.
+///Unknown peer and peer without credentials are rejected with `PERMISSION_DENIED`.
+///Requests, which are not made over unix domain socket, are passed through unless `require_uds` is set.
+pub struct UdsPeer<R> {
+    resolver: R,
+    require_uds: bool,
+}
+
+impl<R: PeerResolver> UdsPeer<R> {
+    #[inline]
+    ///Creates new instance
+    pub fn new(resolver: R) -> Self {
+        Self {
+            resolver,
+            require_uds: false,
+        }
+    }
+
+    #[inline]
+    ///Sets whether to reject requests, which are not made over unix domain socket
+    pub fn require_uds(mut self, require_uds: bool) -> Self {
+        self.require_uds = require_uds;
+        self
+    }
+}
+
+impl<R: PeerResolver> Interceptor for UdsPeer<R> {
+    fn on_request(&self, _: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        let info = match extensions.get::<UdsConnectInfo>() {
+            Some(info) => info,
+            None => return match self.require_uds {
+                true => Some(tonic::Status::permission_denied("connection is not unix domain socket")),
+                false => None,
+            },
+        };
+        let cred = match info.peer_cred {
+            Some(cred) => cred,
+            None => return Some(tonic::Status::permission_denied("peer credentials are unavailable")),
+        };
+
+        match self.resolver.resolve(&cred) {
+            Some(identity) => {
+                extensions.insert(identity.with_extra(cred));
+                None
+            },
+            None => Some(tonic::Status::permission_denied(format!("unknown peer uid {}", cred.uid()))),
+        }
+    }
+
+    #[inline(always)]
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
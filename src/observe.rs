@@ -0,0 +1,1308 @@
+//! Server metrics
+//!
+//!`MetricsFacade` is a layer, which reports every call to `MetricsSink`:
+//!
+//!- `<prefix>_handled_total` counter with `method` and `code` labels;
+//!- `<prefix>_handling_seconds` histogram with `method` and `code` labels;
+//!- `<prefix>_in_flight` gauge with `method` label.
+//!
+//!Prefix is `grpc_server` unless specified otherwise, while every metric additionally carries configured static labels.
+//!When enabled, `attempt` label separates first attempts from retries by `PreviousAttempts::bucket`.
+//!
+//!Call is complete once final status is known: either from response headers (trailers-only response) or from trailers.
+//!If response body is dropped before that, call is reported as `CANCELLED`.
+//!
+//!Methods are labeled by their path, as long as their number stays within limit, see `MethodLabels`.
+//!
+//!With `metrics` feature, `GlobalMetrics` sink emits via `metrics` crate into installed recorder:
+//!
+//!```rust
+//!# #[cfg(feature = "metrics")] {
+//!use tonic_interceptor::observe::{GlobalMetrics, MetricsFacade};
+//!
+//!let layer = MetricsFacade::builder(GlobalMetrics::new()).label("service", "users")
+//!                                                         .methods(["/pkg.Users/Get", "/pkg.Users/List"])
+//!                                                         .build();
+//!# }
+//!```
+//!
+//!With `prometheus` feature, `PrometheusMetrics` sink keeps metrics in memory, rendering them in Prometheus text format,
+//!while with `opentelemetry` feature, `OtelMetrics` sink records them into OpenTelemetry instruments.
+//!
+//!`InFlightRegistry` keeps live list of in-flight calls, which `InFlightHandle` exposes to admin endpoints.
+//!
+//!`RejectionLog` keeps the most recent rejections of wrapped interceptor, which `RejectionHandle` exposes the same way.
+
+use crate::{Interceptor, InterceptorService, LazyMetadata};
+use crate::identity::PeerIdentity;
+use crate::redact::Redactor;
+use crate::policy::PreviousAttempts;
+use crate::compat;
+
+use core::task;
+use core::pin::Pin;
+use core::future::Future;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, RwLock};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Instant, SystemTime};
+
+#[cfg(any(feature = "metrics", feature = "prometheus", feature = "opentelemetry"))]
+mod series;
+#[cfg(any(feature = "metrics", feature = "prometheus", feature = "opentelemetry"))]
+use series::SeriesCache;
+#[cfg(feature = "prometheus")]
+mod prometheus;
+#[cfg(feature = "prometheus")]
+pub use prometheus::PrometheusMetrics;
+#[cfg(feature = "opentelemetry")]
+mod otel;
+#[cfg(feature = "opentelemetry")]
+pub use otel::OtelMetrics;
+
+const GRPC_STATUS_HEADER_CODE: &str = "grpc-status";
+///Label of methods beyond cardinality limit
+pub const OTHER_METHOD: &str = "other";
+///Label of requests, which path is not gRPC method
+pub const UNKNOWN_METHOD: &str = "unknown";
+
+///Receiver of metrics
+pub trait MetricsSink {
+    ///Increments counter `name` by one
+    fn increment_counter(&self, name: &str, labels: &[(&str, &str)]);
+    ///Records `value` into histogram `name`
+    fn record_histogram(&self, name: &str, value: f64, labels: &[(&str, &str)]);
+    ///Increments gauge `name` by one
+    fn increment_gauge(&self, name: &str, labels: &[(&str, &str)]);
+    ///Decrements gauge `name` by one
+    fn decrement_gauge(&self, name: &str, labels: &[(&str, &str)]);
+}
+
+#[cfg(feature = "metrics")]
+#[derive(Clone, Default)]
+///Sink, which emits metrics via `metrics` crate into installed recorder
+///
+///Every distinct metric is registered once, with its key and labels built on first use only,
+///hence recorder must be installed before metrics are emitted.
+///Clones share registered metrics.
+pub struct GlobalMetrics {
+    handles: Arc<GlobalHandles>,
+}
+
+#[cfg(feature = "metrics")]
+impl GlobalMetrics {
+    #[inline]
+    ///Creates new instance without registered metrics
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl core::fmt::Debug for GlobalMetrics {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("GlobalMetrics").finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "metrics")]
+static METRICS_METADATA: metrics::Metadata<'static> = metrics::Metadata::new(module_path!(), metrics::Level::INFO, Some(module_path!()));
+
+#[cfg(feature = "metrics")]
+#[derive(Default)]
+struct GlobalHandles {
+    counters: SeriesCache<metrics::Counter>,
+    histograms: SeriesCache<metrics::Histogram>,
+    gauges: SeriesCache<metrics::Gauge>,
+}
+
+#[cfg(feature = "metrics")]
+#[inline]
+fn metric_key(name: &str, labels: &[(&str, &str)]) -> metrics::Key {
+    metrics::Key::from_parts(name.to_owned(), labels.iter().map(|(key, value)| metrics::Label::new(key.to_string(), value.to_string())).collect::<Vec<_>>())
+}
+
+#[cfg(feature = "metrics")]
+impl MetricsSink for GlobalMetrics {
+    #[inline]
+    fn increment_counter(&self, name: &str, labels: &[(&str, &str)]) {
+        self.handles.counters.get(name, labels, |name, labels| metrics::with_recorder(|recorder| recorder.register_counter(&metric_key(name, labels), &METRICS_METADATA))).increment(1);
+    }
+
+    #[inline]
+    fn record_histogram(&self, name: &str, value: f64, labels: &[(&str, &str)]) {
+        self.handles.histograms.get(name, labels, |name, labels| metrics::with_recorder(|recorder| recorder.register_histogram(&metric_key(name, labels), &METRICS_METADATA))).record(value);
+    }
+
+    #[inline]
+    fn increment_gauge(&self, name: &str, labels: &[(&str, &str)]) {
+        self.handles.gauges.get(name, labels, |name, labels| metrics::with_recorder(|recorder| recorder.register_gauge(&metric_key(name, labels), &METRICS_METADATA))).increment(1.0);
+    }
+
+    #[inline]
+    fn decrement_gauge(&self, name: &str, labels: &[(&str, &str)]) {
+        self.handles.gauges.get(name, labels, |name, labels| metrics::with_recorder(|recorder| recorder.register_gauge(&metric_key(name, labels), &METRICS_METADATA))).decrement(1.0);
+    }
+}
+
+impl<T: MetricsSink + ?Sized> MetricsSink for Arc<T> {
+    #[inline(always)]
+    fn increment_counter(&self, name: &str, labels: &[(&str, &str)]) {
+        T::increment_counter(self, name, labels)
+    }
+
+    #[inline(always)]
+    fn record_histogram(&self, name: &str, value: f64, labels: &[(&str, &str)]) {
+        T::record_histogram(self, name, value, labels)
+    }
+
+    #[inline(always)]
+    fn increment_gauge(&self, name: &str, labels: &[(&str, &str)]) {
+        T::increment_gauge(self, name, labels)
+    }
+
+    #[inline(always)]
+    fn decrement_gauge(&self, name: &str, labels: &[(&str, &str)]) {
+        T::decrement_gauge(self, name, labels)
+    }
+}
+
+///Returns canonical name of `code`, e.g. `DEADLINE_EXCEEDED`
+pub fn code_name(code: tonic::Code) -> &'static str {
+    match code {
+        tonic::Code::Ok => "OK",
+        tonic::Code::Cancelled => "CANCELLED",
+        tonic::Code::Unknown => "UNKNOWN",
+        tonic::Code::InvalidArgument => "INVALID_ARGUMENT",
+        tonic::Code::DeadlineExceeded => "DEADLINE_EXCEEDED",
+        tonic::Code::NotFound => "NOT_FOUND",
+        tonic::Code::AlreadyExists => "ALREADY_EXISTS",
+        tonic::Code::PermissionDenied => "PERMISSION_DENIED",
+        tonic::Code::ResourceExhausted => "RESOURCE_EXHAUSTED",
+        tonic::Code::FailedPrecondition => "FAILED_PRECONDITION",
+        tonic::Code::Aborted => "ABORTED",
+        tonic::Code::OutOfRange => "OUT_OF_RANGE",
+        tonic::Code::Unimplemented => "UNIMPLEMENTED",
+        tonic::Code::Internal => "INTERNAL",
+        tonic::Code::Unavailable => "UNAVAILABLE",
+        tonic::Code::DataLoss => "DATA_LOSS",
+        tonic::Code::Unauthenticated => "UNAUTHENTICATED",
+    }
+}
+
+//Returns whether path has form of `/<service>/<method>`
+fn is_method_path(path: &str) -> bool {
+    match path.strip_prefix('/').and_then(|path| path.split_once('/')) {
+        Some((service, method)) => !service.is_empty() && !method.is_empty() && !method.contains('/'),
+        None => false,
+    }
+}
+
+#[derive(Debug)]
+///Normalization of method label, limiting its cardinality
+///
+///Method is used as label once it is known, while the rest are reported as `other`.
+///Methods become known when seeded upfront, or when their call completes with status other than `UNIMPLEMENTED`,
+///as long as there are less than `max_methods` known methods.
+///Hence calls of unknown services or methods never take room of real methods.
+///Paths, which are not gRPC methods, are reported as `unknown`.
+pub struct MethodLabels {
+    max_methods: usize,
+    known: RwLock<HashSet<Arc<str>>>,
+    other: Arc<str>,
+    unknown: Arc<str>,
+}
+
+impl MethodLabels {
+    #[inline]
+    ///Creates new instance, allowing up to `max_methods` distinct labels
+    pub fn new(max_methods: usize) -> Self {
+        Self {
+            max_methods,
+            known: RwLock::new(HashSet::new()),
+            other: OTHER_METHOD.into(),
+            unknown: UNKNOWN_METHOD.into(),
+        }
+    }
+
+    #[inline]
+    ///Makes method `path`, e.g. `/package.Service/Method`, known regardless of limit
+    ///
+    ///Seeded methods count towards limit of methods, which become known later.
+    pub fn seed(self, path: &str) -> Self {
+        if is_method_path(path) {
+            write(&self.known).insert(path.into());
+        }
+        self
+    }
+
+    ///Returns label of `path` for call in progress
+    pub fn label(&self, path: &str) -> Arc<str> {
+        if !is_method_path(path) {
+            return self.unknown.clone();
+        }
+
+        match read(&self.known).get(path) {
+            Some(label) => label.clone(),
+            None => self.other.clone(),
+        }
+    }
+
+    ///Returns label of `path` for call completed with `code`, making method known if possible
+    pub fn complete(&self, path: &str, code: tonic::Code) -> Arc<str> {
+        if !is_method_path(path) {
+            return self.unknown.clone();
+        }
+
+        if let Some(label) = read(&self.known).get(path) {
+            return label.clone();
+        }
+        if code == tonic::Code::Unimplemented {
+            return self.other.clone();
+        }
+
+        let mut known = write(&self.known);
+        if let Some(label) = known.get(path) {
+            return label.clone();
+        }
+        if known.len() >= self.max_methods {
+            return self.other.clone();
+        }
+
+        let label: Arc<str> = path.into();
+        known.insert(label.clone());
+        label
+    }
+}
+
+#[inline(always)]
+fn read<T>(lock: &RwLock<T>) -> std::sync::RwLockReadGuard<'_, T> {
+    match lock.read() {
+        Ok(guard) => guard,
+        Err(error) => error.into_inner(),
+    }
+}
+
+#[inline(always)]
+fn write<T>(lock: &RwLock<T>) -> std::sync::RwLockWriteGuard<'_, T> {
+    match lock.write() {
+        Ok(guard) => guard,
+        Err(error) => error.into_inner(),
+    }
+}
+
+struct Config<M> {
+    sink: M,
+    handled: String,
+    handling: String,
+    in_flight: String,
+    labels: Vec<(String, String)>,
+    methods: MethodLabels,
+    attempt: bool,
+}
+
+impl<M: MetricsSink> Config<M> {
+    fn with_labels<R, F: FnOnce(&[(&str, &str)]) -> R>(&self, method: &str, attempt: Option<&str>, code: Option<tonic::Code>, fun: F) -> R {
+        let mut labels = Vec::with_capacity(self.labels.len() + 3);
+        labels.push(("method", method));
+        if let Some(attempt) = attempt {
+            labels.push(("attempt", attempt));
+        }
+        if let Some(code) = code {
+            labels.push(("code", code_name(code)));
+        }
+        labels.extend(self.labels.iter().map(|(key, value)| (key.as_str(), value.as_str())));
+        fun(&labels)
+    }
+}
+
+#[derive(Clone)]
+///Layer reporting server metrics
+pub struct MetricsFacade<M> {
+    config: Arc<Config<M>>,
+}
+
+impl<M> core::fmt::Debug for MetricsFacade<M> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("MetricsFacade").field("metric", &self.config.handled).field("labels", &self.config.labels).finish_non_exhaustive()
+    }
+}
+
+impl<M: MetricsSink> MetricsFacade<M> {
+    #[inline]
+    ///Creates new instance with `grpc_server` prefix, allowing up to 100 distinct methods
+    pub fn new(sink: M) -> Self {
+        Self::builder(sink).build()
+    }
+
+    #[inline]
+    ///Starts building instance
+    pub fn builder(sink: M) -> MetricsFacadeBuilder<M> {
+        MetricsFacadeBuilder {
+            sink,
+            prefix: "grpc_server".to_owned(),
+            labels: Vec::new(),
+            methods: Vec::new(),
+            max_methods: 100,
+            attempt: false,
+        }
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl MetricsFacade<GlobalMetrics> {
+    #[inline]
+    ///Creates new instance reporting to recorder of `metrics` crate, with `grpc_server` prefix, allowing up to 100 distinct methods
+    pub fn global() -> Self {
+        Self::new(GlobalMetrics::new())
+    }
+}
+
+///Builder of `MetricsFacade`
+pub struct MetricsFacadeBuilder<M> {
+    sink: M,
+    prefix: String,
+    labels: Vec<(String, String)>,
+    methods: Vec<String>,
+    max_methods: usize,
+    attempt: bool,
+}
+
+impl<M> core::fmt::Debug for MetricsFacadeBuilder<M> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("MetricsFacadeBuilder").field("prefix", &self.prefix).field("labels", &self.labels).field("methods", &self.methods).field("max_methods", &self.max_methods).field("attempt", &self.attempt).finish_non_exhaustive()
+    }
+}
+
+impl<M: MetricsSink> MetricsFacadeBuilder<M> {
+    #[inline]
+    ///Sets prefix of metric names
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    #[inline]
+    ///Adds static label to every metric
+    pub fn label(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.labels.push((key.into(), value.into()));
+        self
+    }
+
+    #[inline]
+    ///Reports method `path`, e.g. `/package.Service/Method`, under its own label from the first call
+    ///
+    ///Otherwise method is reported as `other` until its first call completes, see `MethodLabels`.
+    pub fn method(mut self, path: impl Into<String>) -> Self {
+        self.methods.push(path.into());
+        self
+    }
+
+    #[inline]
+    ///Reports every method of `paths` under its own label, see `method`
+    pub fn methods<P: Into<String>, I: IntoIterator<Item = P>>(mut self, paths: I) -> Self {
+        self.methods.extend(paths.into_iter().map(Into::into));
+        self
+    }
+
+    #[inline]
+    ///Sets limit of distinct method labels
+    pub fn max_methods(mut self, max_methods: usize) -> Self {
+        self.max_methods = max_methods;
+        self
+    }
+
+    #[inline]
+    ///Sets whether to add `attempt` label with bucket of `PreviousAttempts`, i.e. `0`, `1` or `2+`
+    ///
+    ///Attempts are taken from request's extension, set by `policy::RetryAttempts`, or from `grpc-previous-rpc-attempts` header.
+    ///Invalid header is counted as first attempt.
+    pub fn attempt_label(mut self, attempt: bool) -> Self {
+        self.attempt = attempt;
+        self
+    }
+
+    ///Builds layer
+    pub fn build(self) -> MetricsFacade<M> {
+        MetricsFacade {
+            config: Arc::new(Config {
+                sink: self.sink,
+                handled: format!("{}_handled_total", self.prefix),
+                handling: format!("{}_handling_seconds", self.prefix),
+                in_flight: format!("{}_in_flight", self.prefix),
+                labels: self.labels,
+                methods: self.methods.iter().fold(MethodLabels::new(self.max_methods), |methods, path| methods.seed(path)),
+                attempt: self.attempt,
+            }),
+        }
+    }
+}
+
+impl<M, S> tower_layer::Layer<S> for MetricsFacade<M> {
+    type Service = MetricsService<M, S>;
+
+    #[inline(always)]
+    fn layer(&self, inner: S) -> Self::Service {
+        MetricsService {
+            config: self.config.clone(),
+            inner,
+        }
+    }
+}
+
+///Service reporting server metrics
+pub struct MetricsService<M, S> {
+    config: Arc<Config<M>>,
+    inner: S,
+}
+
+impl<M, S: Clone> Clone for MetricsService<M, S> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            config: self.config.clone(),
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<M, S: core::fmt::Debug> core::fmt::Debug for MetricsService<M, S> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("MetricsService").field("inner", &self.inner).finish_non_exhaustive()
+    }
+}
+
+impl<M: MetricsSink, S: tonic::server::NamedService> tonic::server::NamedService for MetricsService<M, S> {
+    const NAME: &'static str = S::NAME;
+}
+
+impl<M: MetricsSink, ReqBody, ResBody, S: tower_service::Service<http::Request<ReqBody>, Response = http::Response<ResBody>>> tower_service::Service<http::Request<ReqBody>> for MetricsService<M, S> {
+    type Response = http::Response<MetricsBody<M, ResBody>>;
+    type Error = S::Error;
+    type Future = MetricsFut<M, S::Future>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        let path = req.uri().path();
+        let method = self.config.methods.label(path);
+        //Label of unknown method is decided once call completes
+        let path = match &*method == OTHER_METHOD {
+            true => Some(path.into()),
+            false => None,
+        };
+        let config = &self.config;
+        let attempt = match config.attempt {
+            true => {
+                let attempts = req.extensions().get::<PreviousAttempts>().copied().or_else(|| PreviousAttempts::from_headers(req.headers()));
+                Some(attempts.unwrap_or_default().bucket())
+            },
+            false => None,
+        };
+        config.with_labels(&method, attempt, None, |labels| config.sink.increment_gauge(&config.in_flight, labels));
+
+        MetricsFut {
+            completion: Some(Completion {
+                config: self.config.clone(),
+                method,
+                path,
+                attempt,
+                start: Instant::now(),
+            }),
+            inner: self.inner.call(req),
+        }
+    }
+}
+
+//Reports completion of call, which is assumed to be cancelled unless reported explicitly
+struct Completion<M: MetricsSink> {
+    config: Arc<Config<M>>,
+    //Label of in-flight gauge
+    method: Arc<str>,
+    //Path of method, which was not known when call started
+    path: Option<Box<str>>,
+    attempt: Option<&'static str>,
+    start: Instant,
+}
+
+impl<M: MetricsSink> Completion<M> {
+    fn complete(&self, code: tonic::Code) {
+        let config = &self.config;
+        let elapsed = self.start.elapsed().as_secs_f64();
+        let method = match &self.path {
+            Some(path) => config.methods.complete(path, code),
+            None => self.method.clone(),
+        };
+        config.with_labels(&method, self.attempt, Some(code), |labels| {
+            config.sink.increment_counter(&config.handled, labels);
+            config.sink.record_histogram(&config.handling, elapsed, labels);
+        });
+        config.with_labels(&self.method, self.attempt, None, |labels| config.sink.decrement_gauge(&config.in_flight, labels));
+    }
+}
+
+//Holds completion until final status is known
+struct Pending<M: MetricsSink>(Option<Completion<M>>);
+
+impl<M: MetricsSink> Pending<M> {
+    #[inline(always)]
+    fn complete(&mut self, code: tonic::Code) {
+        if let Some(completion) = self.0.take() {
+            completion.complete(code);
+        }
+    }
+}
+
+impl<M: MetricsSink> Drop for Pending<M> {
+    #[inline(always)]
+    fn drop(&mut self) {
+        self.complete(tonic::Code::Cancelled);
+    }
+}
+
+pin_project_lite::pin_project! {
+    ///Future of `MetricsService`
+    pub struct MetricsFut<M: MetricsSink, F> {
+        completion: Option<Completion<M>>,
+        #[pin]
+        inner: F,
+    }
+
+    impl<M: MetricsSink, F> PinnedDrop for MetricsFut<M, F> {
+        fn drop(this: Pin<&mut Self>) {
+            if let Some(completion) = this.project().completion.take() {
+                completion.complete(tonic::Code::Cancelled);
+            }
+        }
+    }
+}
+
+impl<M: MetricsSink, F> core::fmt::Debug for MetricsFut<M, F> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("MetricsFut").finish_non_exhaustive()
+    }
+}
+
+impl<M: MetricsSink, ResBody, E, F: Future<Output = Result<http::Response<ResBody>, E>>> Future for MetricsFut<M, F> {
+    type Output = Result<http::Response<MetricsBody<M, ResBody>>, E>;
+
+    fn poll(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let this = self.project();
+        let result = match Future::poll(this.inner, ctx) {
+            task::Poll::Ready(result) => result,
+            task::Poll::Pending => return task::Poll::Pending,
+        };
+
+        let completion = this.completion.take().expect("Future polled after completion");
+        match result {
+            Ok(response) => {
+                let mut pending = Pending(Some(completion));
+                if let Some(code) = response.headers().get(GRPC_STATUS_HEADER_CODE) {
+                    pending.complete(tonic::Code::from_bytes(code.as_bytes()));
+                }
+                task::Poll::Ready(Ok(response.map(|inner| MetricsBody {
+                    pending,
+                    inner: compat::Incoming::new(inner),
+                })))
+            },
+            Err(error) => {
+                completion.complete(tonic::Code::Unknown);
+                task::Poll::Ready(Err(error))
+            },
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    ///Response body of `MetricsService`, reporting completion once trailers are received
+    pub struct MetricsBody<M: MetricsSink, B> {
+        pending: Pending<M>,
+        #[pin]
+        inner: compat::Incoming<B>,
+    }
+}
+
+impl<M: MetricsSink, B: Default> Default for MetricsBody<M, B> {
+    #[inline(always)]
+    fn default() -> Self {
+        //Body of response created outside of service, such as rejection, reports nothing
+        Self {
+            pending: Pending(None),
+            inner: Default::default(),
+        }
+    }
+}
+
+impl<M: MetricsSink, B> core::fmt::Debug for MetricsBody<M, B> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("MetricsBody").field("is_complete", &self.pending.0.is_none()).finish_non_exhaustive()
+    }
+}
+
+compat::impl_body! {
+    [M: MetricsSink, B: http_body::Body] MetricsBody<M, B> {
+        type Data = B::Data;
+        type Error = B::Error;
+
+        fn poll_data(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Option<Result<Self::Data, Self::Error>>> {
+            let this = self.project();
+
+            let result = compat::Body::poll_data(this.inner, ctx);
+            if let task::Poll::Ready(Some(Err(_))) = result {
+                this.pending.complete(tonic::Code::Unknown);
+            }
+            result
+        }
+
+        fn poll_trailers(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Result<Option<http::HeaderMap>, Self::Error>> {
+            let this = self.project();
+
+            let result = compat::Body::poll_trailers(this.inner, ctx);
+            match &result {
+                task::Poll::Ready(Ok(trailers)) => {
+                    let code = trailers.as_ref().and_then(|trailers| trailers.get(GRPC_STATUS_HEADER_CODE));
+                    this.pending.complete(code.map_or(tonic::Code::Unknown, |code| tonic::Code::from_bytes(code.as_bytes())));
+                },
+                task::Poll::Ready(Err(_)) => this.pending.complete(tonic::Code::Unknown),
+                task::Poll::Pending => (),
+            }
+            result
+        }
+
+        #[inline(always)]
+        fn is_end_stream(&self) -> bool {
+            self.inner.is_end_stream()
+        }
+
+        #[inline(always)]
+        fn size_hint(&self) -> http_body::SizeHint {
+            self.inner.size_hint()
+        }
+    }
+}
+
+const REGISTRY_SHARDS: usize = 16;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+///Identifier of call within `InFlightRegistry`, inserted into request extensions
+pub struct CallId(pub u64);
+
+#[derive(Clone, Debug)]
+///Call, which is in flight
+pub struct InFlightCall {
+    ///Identifier of call
+    pub id: CallId,
+    ///Method path
+    pub method: String,
+    ///Caller, if known
+    pub peer: Option<String>,
+    ///Request id supplied by caller
+    pub request_id: Option<String>,
+    ///Time when call was registered
+    pub started: Instant,
+}
+
+#[derive(Default)]
+struct Registry {
+    next_id: AtomicU64,
+    shards: [Mutex<HashMap<u64, InFlightCall>>; REGISTRY_SHARDS],
+}
+
+impl Registry {
+    #[inline]
+    fn shard(&self, id: u64) -> std::sync::MutexGuard<'_, HashMap<u64, InFlightCall>> {
+        match self.shards[(id % REGISTRY_SHARDS as u64) as usize].lock() {
+            Ok(shard) => shard,
+            Err(error) => error.into_inner(),
+        }
+    }
+
+    fn snapshot(&self) -> Vec<InFlightCall> {
+        let mut result = Vec::new();
+        for id in 0..REGISTRY_SHARDS as u64 {
+            result.extend(self.shard(id).values().cloned());
+        }
+        result.sort_by_key(|call| call.id.0);
+        result
+    }
+}
+
+//Call within registry, removed once dropped
+struct Registered {
+    registry: Arc<Registry>,
+    id: CallId,
+}
+
+impl Drop for Registered {
+    #[inline]
+    fn drop(&mut self) {
+        self.registry.shard(self.id.0).remove(&self.id.0);
+    }
+}
+
+#[derive(Clone)]
+///Guard of registered call, which removes it from registry once it and all of its clones are dropped
+pub struct InFlightGuard {
+    call: Arc<Registered>,
+}
+
+impl core::fmt::Debug for InFlightGuard {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("InFlightGuard").field("id", &self.call.id).finish_non_exhaustive()
+    }
+}
+
+type PeerExtractor = dyn Fn(&http::Extensions) -> Option<String> + Send + Sync;
+
+#[derive(Clone)]
+///Server interceptor which registers every call reaching it as in flight
+///
+///Call gets `CallId` and `InFlightGuard` inserted into its request extensions, and stays registered until guard is dropped.
+///Use `layer` to keep guard until response is complete, otherwise call is unregistered once its request is dropped.
+///Rejection by preceding interceptor never registers call, while rejection by following one drops guard along with request.
+///
+///By default peer is subject of `PeerIdentity`, hence place it after authentication, and request id is taken from `x-request-id`.
+pub struct InFlightRegistry {
+    registry: Arc<Registry>,
+    request_id: &'static str,
+    peer: Option<Arc<PeerExtractor>>,
+}
+
+impl InFlightRegistry {
+    #[inline]
+    ///Creates new instance
+    pub fn new() -> Self {
+        Self {
+            registry: Arc::default(),
+            request_id: "x-request-id",
+            peer: None,
+        }
+    }
+
+    #[inline]
+    ///Sets metadata key of request id
+    pub fn request_id(mut self, key: &'static str) -> Self {
+        self.request_id = key;
+        self
+    }
+
+    #[inline]
+    ///Uses `extractor` to get peer of call, such as remote address from connection info
+    pub fn peer_with<F: Fn(&http::Extensions) -> Option<String> + Send + Sync + 'static>(mut self, extractor: F) -> Self {
+        self.peer = Some(Arc::new(extractor));
+        self
+    }
+
+    #[inline]
+    ///Returns handle to inspect in-flight calls
+    pub fn handle(&self) -> InFlightHandle {
+        InFlightHandle {
+            registry: self.registry.clone(),
+        }
+    }
+
+    #[inline]
+    ///Returns layer, which keeps call registered until its response is complete
+    pub fn layer(&self) -> InFlightLayer {
+        InFlightLayer {
+            registry: self.clone(),
+        }
+    }
+
+    fn register(&self, uri: &http::Uri, request_id: Option<&[u8]>, extensions: &mut http::Extensions) {
+        let peer = match self.peer.as_ref() {
+            Some(extractor) => extractor(extensions),
+            None => PeerIdentity::from_extensions(extensions).and_then(|identity| identity.subject.clone()),
+        };
+        let id = CallId(self.registry.next_id.fetch_add(1, Ordering::Relaxed));
+        self.registry.shard(id.0).insert(id.0, InFlightCall {
+            id,
+            method: uri.path().to_owned(),
+            peer,
+            request_id: request_id.and_then(|value| core::str::from_utf8(value).ok()).map(str::to_owned),
+            started: Instant::now(),
+        });
+        extensions.insert(id);
+        extensions.insert(InFlightGuard {
+            call: Arc::new(Registered {
+                registry: self.registry.clone(),
+                id,
+            }),
+        });
+    }
+}
+
+impl Default for InFlightRegistry {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl core::fmt::Debug for InFlightRegistry {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("InFlightRegistry").field("request_id", &self.request_id).finish_non_exhaustive()
+    }
+}
+
+impl Interceptor for InFlightRegistry {
+    #[inline]
+    fn on_request(&self, _: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+        None
+    }
+
+    #[inline]
+    fn on_request_with_uri(&self, uri: &http::Uri, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        self.register(uri, headers.get(self.request_id).map(|value| value.as_bytes()), extensions);
+        None
+    }
+
+    #[inline]
+    fn on_request_headers(&self, uri: &http::Uri, headers: &mut http::HeaderMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        self.register(uri, headers.get(self.request_id).map(http::HeaderValue::as_bytes), extensions);
+        None
+    }
+
+    #[inline(always)]
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
+
+#[derive(Clone)]
+///Handle to inspect calls of `InFlightRegistry`
+pub struct InFlightHandle {
+    registry: Arc<Registry>,
+}
+
+impl InFlightHandle {
+    #[inline]
+    ///Returns calls in flight, in order of registration
+    pub fn snapshot(&self) -> Vec<InFlightCall> {
+        self.registry.snapshot()
+    }
+
+    ///Returns number of calls in flight per method, ordered by method
+    pub fn counts(&self) -> Vec<(String, usize)> {
+        let mut counts = HashMap::new();
+        for id in 0..REGISTRY_SHARDS as u64 {
+            for call in self.registry.shard(id).values() {
+                *counts.entry(call.method.clone()).or_insert(0) += 1;
+            }
+        }
+        let mut counts: Vec<_> = counts.into_iter().collect();
+        counts.sort_unstable();
+        counts
+    }
+
+    ///Returns total number of calls in flight
+    pub fn len(&self) -> usize {
+        (0..REGISTRY_SHARDS as u64).map(|id| self.registry.shard(id).len()).sum()
+    }
+
+    #[inline]
+    ///Returns whether there is no call in flight
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl core::fmt::Debug for InFlightHandle {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("InFlightHandle").field("len", &self.len()).finish()
+    }
+}
+
+#[derive(Clone, Debug)]
+///Layer of `InFlightRegistry`, keeping call registered until its response is complete
+pub struct InFlightLayer {
+    registry: InFlightRegistry,
+}
+
+impl<S> tower_layer::Layer<S> for InFlightLayer {
+    type Service = InterceptorService<InFlightRegistry, TrackCall<S>>;
+
+    #[inline(always)]
+    fn layer(&self, inner: S) -> Self::Service {
+        InterceptorService::new(self.registry.clone(), TrackCall {
+            inner,
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+///Service moving `InFlightGuard` of request into its response
+pub struct TrackCall<S> {
+    inner: S,
+}
+
+impl<S: tonic::server::NamedService> tonic::server::NamedService for TrackCall<S> {
+    const NAME: &'static str = S::NAME;
+}
+
+impl<ReqBody, ResBody, S: tower_service::Service<http::Request<ReqBody>, Response = http::Response<ResBody>>> tower_service::Service<http::Request<ReqBody>> for TrackCall<S> {
+    type Response = http::Response<TrackCallBody<ResBody>>;
+    type Error = S::Error;
+    type Future = TrackCallFut<S::Future>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    #[inline]
+    fn call(&mut self, mut req: http::Request<ReqBody>) -> Self::Future {
+        TrackCallFut {
+            guard: req.extensions_mut().remove(),
+            inner: self.inner.call(req),
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    ///Future of `TrackCall`
+    pub struct TrackCallFut<F> {
+        guard: Option<InFlightGuard>,
+        #[pin]
+        inner: F,
+    }
+}
+
+impl<F> core::fmt::Debug for TrackCallFut<F> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("TrackCallFut").field("guard", &self.guard).finish_non_exhaustive()
+    }
+}
+
+impl<ResBody, E, F: Future<Output = Result<http::Response<ResBody>, E>>> Future for TrackCallFut<F> {
+    type Output = Result<http::Response<TrackCallBody<ResBody>>, E>;
+
+    #[inline]
+    fn poll(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let this = self.project();
+        match Future::poll(this.inner, ctx) {
+            task::Poll::Ready(Ok(response)) => {
+                //Trailers-only response is complete already
+                let guard = match response.headers().contains_key(GRPC_STATUS_HEADER_CODE) {
+                    true => {
+                        *this.guard = None;
+                        None
+                    },
+                    false => this.guard.take(),
+                };
+                task::Poll::Ready(Ok(response.map(|inner| TrackCallBody {
+                    guard,
+                    inner: compat::Incoming::new(inner),
+                })))
+            },
+            task::Poll::Ready(Err(error)) => {
+                *this.guard = None;
+                task::Poll::Ready(Err(error))
+            },
+            task::Poll::Pending => task::Poll::Pending,
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    ///Response body of `TrackCall`, unregistering call once trailers are received or it is dropped
+    pub struct TrackCallBody<B> {
+        guard: Option<InFlightGuard>,
+        #[pin]
+        inner: compat::Incoming<B>,
+    }
+}
+
+impl<B: Default> Default for TrackCallBody<B> {
+    #[inline(always)]
+    fn default() -> Self {
+        Self {
+            guard: None,
+            inner: Default::default(),
+        }
+    }
+}
+
+impl<B> core::fmt::Debug for TrackCallBody<B> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("TrackCallBody").field("is_complete", &self.guard.is_none()).finish_non_exhaustive()
+    }
+}
+
+compat::impl_body! {
+    [B: http_body::Body] TrackCallBody<B> {
+        type Data = B::Data;
+        type Error = B::Error;
+
+        fn poll_data(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Option<Result<Self::Data, Self::Error>>> {
+            let this = self.project();
+
+            let result = compat::Body::poll_data(this.inner, ctx);
+            if let task::Poll::Ready(Some(Err(_))) = result {
+                *this.guard = None;
+            }
+            result
+        }
+
+        fn poll_trailers(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Result<Option<http::HeaderMap>, Self::Error>> {
+            let this = self.project();
+
+            let result = compat::Body::poll_trailers(this.inner, ctx);
+            if result.is_ready() {
+                *this.guard = None;
+            }
+            result
+        }
+
+        #[inline(always)]
+        fn is_end_stream(&self) -> bool {
+            self.inner.is_end_stream()
+        }
+
+        #[inline(always)]
+        fn size_hint(&self) -> http_body::SizeHint {
+            self.inner.size_hint()
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+///Rejection recorded by `RejectionLog`
+pub struct RejectionRecord {
+    ///Time of rejection
+    pub time: SystemTime,
+    ///Method path, empty when request URI is not available
+    pub method: String,
+    ///Caller, if known
+    pub peer: Option<String>,
+    ///Status code
+    pub code: tonic::Code,
+    ///Status message
+    pub message: String,
+    ///Redacted metadata of request, as seen at the moment of rejection
+    pub metadata: Vec<(String, String)>,
+}
+
+//Sequence number of record with record itself
+type RejectionSlot = Option<(u64, RejectionRecord)>;
+
+//Fixed size ring, where writer locks only slot it is writing into
+struct RejectionRing {
+    next: AtomicU64,
+    slots: Box<[Mutex<RejectionSlot>]>,
+}
+
+impl RejectionRing {
+    fn new(capacity: usize) -> Self {
+        Self {
+            next: AtomicU64::new(0),
+            slots: (0..capacity.max(1)).map(|_| Mutex::new(None)).collect(),
+        }
+    }
+
+    #[inline]
+    fn slot(&self, idx: usize) -> std::sync::MutexGuard<'_, RejectionSlot> {
+        match self.slots[idx].lock() {
+            Ok(slot) => slot,
+            Err(error) => error.into_inner(),
+        }
+    }
+
+    fn push(&self, record: RejectionRecord) {
+        let seq = self.next.fetch_add(1, Ordering::Relaxed);
+        let mut slot = self.slot((seq % self.slots.len() as u64) as usize);
+        //Slower writer of previous lap must not overwrite newer record
+        if slot.as_ref().is_none_or(|(current, _)| *current < seq) {
+            *slot = Some((seq, record));
+        }
+    }
+
+    fn recent(&self) -> Vec<RejectionRecord> {
+        let mut records: Vec<_> = (0..self.slots.len()).filter_map(|idx| self.slot(idx).clone()).collect();
+        records.sort_unstable_by_key(|(seq, _)| *seq);
+        records.into_iter().map(|(_, record)| record).collect()
+    }
+}
+
+///Interceptor, which records rejections of wrapped interceptor into fixed size ring buffer
+///
+///Wrap the whole `InterceptorChain` to observe every rejection of server.
+///Metadata is redacted by `Redactor`, which masks `authorization` unless configured otherwise,
+///while peer is address of client, as returned by `net::RealIp::client`, falling back to subject of `PeerIdentity`,
+///unless extractor is set.
+pub struct RejectionLog<I> {
+    inner: I,
+    ring: Arc<RejectionRing>,
+    redactor: Arc<Redactor>,
+    peer: Option<Arc<PeerExtractor>>,
+}
+
+impl<I: Interceptor> RejectionLog<I> {
+    #[inline]
+    ///Creates new instance, keeping up to `capacity` most recent rejections of `inner`
+    pub fn new(inner: I, capacity: usize) -> Self {
+        Self {
+            inner,
+            ring: Arc::new(RejectionRing::new(capacity)),
+            redactor: Arc::new(Redactor::new().key("authorization")),
+            peer: None,
+        }
+    }
+
+    #[inline]
+    ///Sets redactor of metadata
+    pub fn redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = Arc::new(redactor);
+        self
+    }
+
+    #[inline]
+    ///Uses `extractor` to get peer of call, such as remote address from connection info
+    pub fn peer_with<F: Fn(&http::Extensions) -> Option<String> + Send + Sync + 'static>(mut self, extractor: F) -> Self {
+        self.peer = Some(Arc::new(extractor));
+        self
+    }
+
+    #[inline]
+    ///Returns handle to inspect recorded rejections
+    pub fn handle(&self) -> RejectionHandle {
+        RejectionHandle {
+            ring: self.ring.clone(),
+        }
+    }
+
+    #[inline(always)]
+    ///Access wrapped interceptor
+    pub fn inner(&self) -> &I {
+        &self.inner
+    }
+
+    fn record(&self, status: Option<tonic::Status>, method: &str, metadata: &tonic::metadata::MetadataMap, extensions: &http::Extensions) -> Option<tonic::Status> {
+        if let Some(status) = status.as_ref() {
+            let peer = match self.peer.as_ref() {
+                Some(extractor) => extractor(extensions),
+                None => crate::net::RealIp::peer(extensions).or_else(|| PeerIdentity::from_extensions(extensions).and_then(|identity| identity.subject.clone())),
+            };
+            self.ring.push(RejectionRecord {
+                time: SystemTime::now(),
+                method: method.to_owned(),
+                peer,
+                code: status.code(),
+                message: status.message().to_owned(),
+                metadata: self.redactor.snapshot(metadata),
+            });
+        }
+        status
+    }
+}
+
+impl<I: Clone> Clone for RejectionLog<I> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            ring: self.ring.clone(),
+            redactor: self.redactor.clone(),
+            peer: self.peer.clone(),
+        }
+    }
+}
+
+impl<I: core::fmt::Debug> core::fmt::Debug for RejectionLog<I> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("RejectionLog").field("inner", &self.inner).field("capacity", &self.ring.slots.len()).finish_non_exhaustive()
+    }
+}
+
+impl<I: Interceptor> Interceptor for RejectionLog<I> {
+    #[inline]
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        let status = self.inner.on_request(headers, extensions);
+        self.record(status, "", headers, extensions)
+    }
+
+    #[inline]
+    fn on_request_with_uri(&self, uri: &http::Uri, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        let status = self.inner.on_request_with_uri(uri, headers, extensions);
+        self.record(status, uri.path(), headers, extensions)
+    }
+
+    #[inline]
+    fn on_request_lazy(&self, uri: &http::Uri, headers: &mut LazyMetadata<'_>, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        match self.inner.on_request_lazy(uri, headers, extensions) {
+            None => None,
+            status => self.record(status, uri.path(), &crate::compat::metadata_from_headers(headers.headers().clone()), extensions),
+        }
+    }
+
+    #[inline]
+    fn on_request_headers(&self, uri: &http::Uri, headers: &mut http::HeaderMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        match self.inner.on_request_headers(uri, headers, extensions) {
+            None => None,
+            status => self.record(status, uri.path(), &crate::compat::metadata_from_headers(headers.clone()), extensions),
+        }
+    }
+
+    #[inline(always)]
+    fn on_response(&self, status: tonic::Code, headers: &mut http::HeaderMap, extensions: &http::Extensions) {
+        self.inner.on_response(status, headers, extensions)
+    }
+
+    #[inline(always)]
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    #[cfg(feature = "diagnostics")]
+    #[inline(always)]
+    fn on_poll_stats(&self, stats: crate::diagnostics::PollStats) {
+        self.inner.on_poll_stats(stats)
+    }
+}
+
+#[derive(Clone)]
+///Handle to inspect rejections of `RejectionLog`
+pub struct RejectionHandle {
+    ring: Arc<RejectionRing>,
+}
+
+impl RejectionHandle {
+    #[inline]
+    ///Returns the most recent rejections, oldest first
+    pub fn recent(&self) -> Vec<RejectionRecord> {
+        self.ring.recent()
+    }
+
+    #[inline(always)]
+    ///Returns maximum number of kept rejections
+    pub fn capacity(&self) -> usize {
+        self.ring.slots.len()
+    }
+
+    #[inline(always)]
+    ///Returns total number of rejections since creation, including ones no longer kept
+    pub fn total(&self) -> u64 {
+        self.ring.next.load(Ordering::Relaxed)
+    }
+}
+
+impl core::fmt::Debug for RejectionHandle {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("RejectionHandle").field("capacity", &self.capacity()).field("total", &self.total()).finish()
+    }
+}
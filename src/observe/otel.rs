@@ -0,0 +1,102 @@
+use super::{MetricsSink, SeriesCache};
+
+use opentelemetry::KeyValue;
+use opentelemetry::metrics::{Counter, Histogram, Meter, UpDownCounter};
+
+use core::fmt;
+use std::sync::Arc;
+
+//Instrument with attributes of series
+type Recorder<I> = (I, Arc<[KeyValue]>);
+
+struct Instruments {
+    meter: Meter,
+    counters: SeriesCache<Recorder<Counter<u64>>>,
+    histograms: SeriesCache<Recorder<Histogram<f64>>>,
+    gauges: SeriesCache<Recorder<UpDownCounter<i64>>>,
+}
+
+#[inline]
+fn attributes(labels: &[(&str, &str)]) -> Arc<[KeyValue]> {
+    labels.iter().map(|(key, value)| KeyValue::new(key.to_string(), value.to_string())).collect()
+}
+
+#[derive(Clone)]
+///Sink, which records metrics into OpenTelemetry instruments of `Meter`
+///
+///Counters are `u64` counters, histograms are `f64` histograms and gauges are `i64` up-down counters,
+///while labels are their attributes.
+///Instrument and attributes are created once per distinct metric.
+///Clones share instruments.
+///
+///```rust
+///use tonic_interceptor::observe::{MetricsFacade, OtelMetrics};
+///
+///let layer = MetricsFacade::builder(OtelMetrics::global()).attempt_label(true).build();
+///```
+pub struct OtelMetrics {
+    instruments: Arc<Instruments>,
+}
+
+impl OtelMetrics {
+    ///Creates new instance, which creates instruments with `meter`
+    pub fn new(meter: Meter) -> Self {
+        Self {
+            instruments: Arc::new(Instruments {
+                meter,
+                counters: SeriesCache::default(),
+                histograms: SeriesCache::default(),
+                gauges: SeriesCache::default(),
+            }),
+        }
+    }
+
+    #[inline]
+    ///Creates new instance with meter `tonic-interceptor` of global meter provider
+    ///
+    ///Meter provider must be set before.
+    pub fn global() -> Self {
+        Self::new(opentelemetry::global::meter("tonic-interceptor"))
+    }
+
+    #[inline(always)]
+    fn gauge(&self, name: &str, labels: &[(&str, &str)]) -> Recorder<UpDownCounter<i64>> {
+        let meter = &self.instruments.meter;
+        self.instruments.gauges.get(name, labels, |name, labels| (meter.i64_up_down_counter(name.to_owned()).init(), attributes(labels)))
+    }
+}
+
+impl fmt::Debug for OtelMetrics {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("OtelMetrics").field("meter", &self.instruments.meter).finish_non_exhaustive()
+    }
+}
+
+impl MetricsSink for OtelMetrics {
+    #[inline]
+    fn increment_counter(&self, name: &str, labels: &[(&str, &str)]) {
+        let meter = &self.instruments.meter;
+        let (counter, attributes) = self.instruments.counters.get(name, labels, |name, labels| (meter.u64_counter(name.to_owned()).init(), attributes(labels)));
+        counter.add(1, &attributes);
+    }
+
+    #[inline]
+    fn record_histogram(&self, name: &str, value: f64, labels: &[(&str, &str)]) {
+        let meter = &self.instruments.meter;
+        let (histogram, attributes) = self.instruments.histograms.get(name, labels, |name, labels| (meter.f64_histogram(name.to_owned()).init(), attributes(labels)));
+        histogram.record(value, &attributes);
+    }
+
+    #[inline]
+    fn increment_gauge(&self, name: &str, labels: &[(&str, &str)]) {
+        let (gauge, attributes) = self.gauge(name, labels);
+        gauge.add(1, &attributes);
+    }
+
+    #[inline]
+    fn decrement_gauge(&self, name: &str, labels: &[(&str, &str)]) {
+        let (gauge, attributes) = self.gauge(name, labels);
+        gauge.add(-1, &attributes);
+    }
+}
@@ -0,0 +1,197 @@
+use super::{MetricsSink, SeriesCache};
+
+use core::fmt::{self, Write};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+
+//Default buckets of Prometheus client libraries
+const DEFAULT_BUCKETS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+struct Histogram {
+    //Number of values within each bucket, but not within previous one
+    buckets: Box<[AtomicU64]>,
+    count: AtomicU64,
+    //Bits of `f64`
+    sum: AtomicU64,
+}
+
+impl Histogram {
+    fn new(buckets: usize) -> Self {
+        Self {
+            buckets: (0..buckets).map(|_| AtomicU64::new(0)).collect(),
+            count: AtomicU64::new(0),
+            sum: AtomicU64::new(0f64.to_bits()),
+        }
+    }
+}
+
+struct Registry {
+    buckets: Box<[f64]>,
+    counters: SeriesCache<Arc<AtomicU64>>,
+    gauges: SeriesCache<Arc<AtomicI64>>,
+    histograms: SeriesCache<Arc<Histogram>>,
+}
+
+#[derive(Clone)]
+///Sink, which keeps metrics in memory to be scraped by Prometheus
+///
+///`render` formats them in Prometheus text format, to be returned by metrics endpoint of server.
+///Clones share metrics.
+///
+///```rust
+///use tonic_interceptor::observe::{MetricsFacade, PrometheusMetrics};
+///
+///let metrics = PrometheusMetrics::new();
+///let layer = MetricsFacade::builder(metrics.clone()).attempt_label(true).build();
+///
+/////Within metrics endpoint
+///let body = metrics.render();
+///```
+pub struct PrometheusMetrics {
+    registry: Arc<Registry>,
+}
+
+impl PrometheusMetrics {
+    #[inline]
+    ///Creates new instance, which histograms have default buckets of Prometheus client libraries
+    pub fn new() -> Self {
+        Self::with_buckets(&DEFAULT_BUCKETS)
+    }
+
+    ///Creates new instance, which histograms have buckets with specified upper bounds
+    ///
+    ///Bounds are sorted, while `+Inf` bucket is always present.
+    pub fn with_buckets(buckets: &[f64]) -> Self {
+        let mut buckets: Vec<f64> = buckets.iter().copied().filter(|bound| bound.is_finite()).collect();
+        buckets.sort_by(|left, right| left.partial_cmp(right).expect("finite bounds"));
+        buckets.dedup();
+        Self {
+            registry: Arc::new(Registry {
+                buckets: buckets.into(),
+                counters: SeriesCache::default(),
+                gauges: SeriesCache::default(),
+                histograms: SeriesCache::default(),
+            }),
+        }
+    }
+
+    ///Formats metrics in Prometheus text format
+    ///
+    ///Metrics are ordered by name and labels.
+    pub fn render(&self) -> String {
+        enum Value {
+            Counter(u64),
+            Gauge(i64),
+            Histogram(Vec<u64>, u64, f64),
+        }
+
+        let registry = &self.registry;
+        let mut samples = Vec::new();
+        let mut collect = |series: &super::series::Series, value: Value| samples.push((series.name.clone(), series.labels.clone(), value));
+        registry.counters.for_each(|series, counter| collect(series, Value::Counter(counter.load(Ordering::Relaxed))));
+        registry.gauges.for_each(|series, gauge| collect(series, Value::Gauge(gauge.load(Ordering::Relaxed))));
+        registry.histograms.for_each(|series, histogram| {
+            let buckets = histogram.buckets.iter().map(|bucket| bucket.load(Ordering::Relaxed)).collect();
+            collect(series, Value::Histogram(buckets, histogram.count.load(Ordering::Relaxed), f64::from_bits(histogram.sum.load(Ordering::Relaxed))))
+        });
+        samples.sort_by(|left, right| (&left.0, &left.1).cmp(&(&right.0, &right.1)));
+
+        let mut result = String::new();
+        let mut previous = None;
+        for (name, labels, value) in samples.iter() {
+            if previous != Some(name) {
+                let kind = match value {
+                    Value::Counter(_) => "counter",
+                    Value::Gauge(_) => "gauge",
+                    Value::Histogram(..) => "histogram",
+                };
+                let _ = writeln!(result, "# TYPE {} {}", name, kind);
+                previous = Some(name);
+            }
+
+            let _ = match value {
+                Value::Counter(value) => writeln!(result, "{}{} {}", name, Labels(labels, None), value),
+                Value::Gauge(value) => writeln!(result, "{}{} {}", name, Labels(labels, None), value),
+                Value::Histogram(buckets, count, sum) => {
+                    let mut cumulative = 0;
+                    for (bound, bucket) in registry.buckets.iter().zip(buckets.iter()) {
+                        cumulative += bucket;
+                        let _ = writeln!(result, "{}_bucket{} {}", name, Labels(labels, Some(&bound.to_string())), cumulative);
+                    }
+                    let _ = writeln!(result, "{}_bucket{} {}", name, Labels(labels, Some("+Inf")), count);
+                    let _ = writeln!(result, "{}_sum{} {}", name, Labels(labels, None), sum);
+                    writeln!(result, "{}_count{} {}", name, Labels(labels, None), count)
+                },
+            };
+        }
+        result
+    }
+}
+
+impl Default for PrometheusMetrics {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for PrometheusMetrics {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("PrometheusMetrics").field("buckets", &self.registry.buckets).finish_non_exhaustive()
+    }
+}
+
+//Labels of sample, with optional `le` label of histogram bucket
+struct Labels<'a>(&'a [(Box<str>, Box<str>)], Option<&'a str>);
+
+impl fmt::Display for Labels<'_> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let labels = self.0.iter().map(|(key, value)| (&**key, &**value)).chain(self.1.map(|bound| ("le", bound)));
+        for (idx, (key, value)) in labels.enumerate() {
+            fmt.write_str(if idx == 0 { "{" } else { "," })?;
+            fmt.write_str(key)?;
+            fmt.write_str("=\"")?;
+            for ch in value.chars() {
+                match ch {
+                    '\\' => fmt.write_str("\\\\")?,
+                    '"' => fmt.write_str("\\\"")?,
+                    '\n' => fmt.write_str("\\n")?,
+                    ch => fmt.write_char(ch)?,
+                }
+            }
+            fmt.write_str("\"")?;
+        }
+        match self.0.is_empty() && self.1.is_none() {
+            true => Ok(()),
+            false => fmt.write_str("}"),
+        }
+    }
+}
+
+impl MetricsSink for PrometheusMetrics {
+    #[inline]
+    fn increment_counter(&self, name: &str, labels: &[(&str, &str)]) {
+        self.registry.counters.get(name, labels, |_, _| Default::default()).fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn record_histogram(&self, name: &str, value: f64, labels: &[(&str, &str)]) {
+        let buckets = &self.registry.buckets;
+        let histogram = self.registry.histograms.get(name, labels, |_, _| Arc::new(Histogram::new(buckets.len())));
+        if let Some(idx) = buckets.iter().position(|bound| value <= *bound) {
+            histogram.buckets[idx].fetch_add(1, Ordering::Relaxed);
+        }
+        let _ = histogram.sum.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |sum| Some((f64::from_bits(sum) + value).to_bits()));
+        histogram.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[inline]
+    fn increment_gauge(&self, name: &str, labels: &[(&str, &str)]) {
+        self.registry.gauges.get(name, labels, |_, _| Default::default()).fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[inline]
+    fn decrement_gauge(&self, name: &str, labels: &[(&str, &str)]) {
+        self.registry.gauges.get(name, labels, |_, _| Default::default()).fetch_sub(1, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+use std::collections::hash_map::RandomState;
+use std::sync::RwLock;
+use core::hash::{BuildHasher, Hash, Hasher};
+
+//Name and labels of metric
+pub(super) struct Series {
+    pub(super) name: Box<str>,
+    pub(super) labels: Box<[(Box<str>, Box<str>)]>,
+}
+
+impl Series {
+    #[inline]
+    fn is(&self, name: &str, labels: &[(&str, &str)]) -> bool {
+        &*self.name == name && self.labels.len() == labels.len() && self.labels.iter().zip(labels).all(|((key, value), (label, label_value))| &**key == *label && &**value == *label_value)
+    }
+}
+
+//Values of registered series, looked up by name and labels without allocation
+pub(super) struct SeriesCache<H> {
+    hasher: RandomState,
+    //Series by hash of name and labels
+    entries: RwLock<HashMap<u64, Vec<(Series, H)>>>,
+}
+
+impl<H> Default for SeriesCache<H> {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            hasher: Default::default(),
+            entries: RwLock::new(HashMap::new()),
+        }
+    }
+}
+
+impl<H: Clone> SeriesCache<H> {
+    //Returns value of series, registering it on first use
+    pub(super) fn get<F: FnOnce(&str, &[(&str, &str)]) -> H>(&self, name: &str, labels: &[(&str, &str)], register: F) -> H {
+        let mut hasher = self.hasher.build_hasher();
+        name.hash(&mut hasher);
+        labels.hash(&mut hasher);
+        let hash = hasher.finish();
+        let find = |entries: &[(Series, H)]| entries.iter().find(|(series, _)| series.is(name, labels)).map(|(_, value)| value.clone());
+
+        if let Some(value) = super::read(&self.entries).get(&hash).and_then(|entries| find(entries)) {
+            return value;
+        }
+        let mut entries = super::write(&self.entries);
+        let entries = entries.entry(hash).or_default();
+        if let Some(value) = find(entries) {
+            return value;
+        }
+        let value = register(name, labels);
+        let series = Series {
+            name: name.into(),
+            labels: labels.iter().map(|(key, value)| (Box::from(*key), Box::from(*value))).collect(),
+        };
+        entries.push((series, value.clone()));
+        value
+    }
+
+    #[cfg(feature = "prometheus")]
+    //Calls `fun` with every registered series
+    pub(super) fn for_each<F: FnMut(&Series, &H)>(&self, mut fun: F) {
+        for (series, value) in super::read(&self.entries).values().flatten() {
+            fun(series, value);
+        }
+    }
+}
@@ -0,0 +1,138 @@
+//! OpenTelemetry context propagation
+//!
+//!Server side `ExtractContext` makes context of incoming call available as `opentelemetry::Context` extension.
+//!Client side counterpart is `client::InjectContext`.
+//!
+//!Besides headers of configured propagator (`traceparent` and `tracestate` for W3C trace context),
+//!binary `grpc-trace-bin` header is supported.
+
+use crate::Interceptor;
+
+use opentelemetry::Context;
+use opentelemetry::propagation::TextMapPropagator;
+use opentelemetry::trace::{SpanContext, TraceContextExt, TraceId, SpanId, TraceFlags, TraceState};
+
+use std::sync::Arc;
+
+///Binary trace context header name
+pub const GRPC_TRACE_BIN: &str = "grpc-trace-bin";
+
+const TRACE_BIN_LEN: usize = 29;
+
+///Encodes span context in `grpc-trace-bin` format
+pub fn encode_trace_bin(span: &SpanContext) -> [u8; TRACE_BIN_LEN] {
+    let mut result = [0u8; TRACE_BIN_LEN];
+    //version 0, followed by fields trace id (0), span id (1) and trace options (2)
+    result[1] = 0;
+    result[2..18].copy_from_slice(&span.trace_id().to_bytes());
+    result[18] = 1;
+    result[19..27].copy_from_slice(&span.span_id().to_bytes());
+    result[27] = 2;
+    result[28] = span.trace_flags().to_u8();
+    result
+}
+
+///Decodes `grpc-trace-bin` value into remote span context, returning `None` if it is invalid.
+pub fn decode_trace_bin(value: &[u8]) -> Option<SpanContext> {
+    if value.len() < TRACE_BIN_LEN || value[0] != 0 || value[1] != 0 || value[18] != 1 || value[27] != 2 {
+        return None;
+    }
+
+    let mut trace_id = [0u8; 16];
+    trace_id.copy_from_slice(&value[2..18]);
+    let mut span_id = [0u8; 8];
+    span_id.copy_from_slice(&value[19..27]);
+
+    let span = SpanContext::new(TraceId::from_bytes(trace_id), SpanId::from_bytes(span_id), TraceFlags::new(value[28]), true, TraceState::default());
+    match span.is_valid() {
+        true => Some(span),
+        false => None,
+    }
+}
+
+#[derive(Clone, Default)]
+///Propagator to use, which is global one unless specified.
+pub(crate) struct Propagator(Option<Arc<dyn TextMapPropagator + Send + Sync>>);
+
+impl Propagator {
+    #[inline]
+    pub(crate) fn new<P: TextMapPropagator + Send + Sync + 'static>(propagator: P) -> Self {
+        Self(Some(Arc::new(propagator)))
+    }
+
+    #[inline]
+    pub(crate) fn with<R, F: FnMut(&dyn TextMapPropagator) -> R>(&self, mut fun: F) -> R {
+        match self.0.as_ref() {
+            Some(propagator) => fun(propagator.as_ref()),
+            None => opentelemetry::global::get_text_map_propagator(fun),
+        }
+    }
+}
+
+impl core::fmt::Debug for Propagator {
+    #[inline]
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.0.as_ref() {
+            Some(propagator) => core::fmt::Debug::fmt(propagator, fmt),
+            None => fmt.write_str("GlobalPropagator"),
+        }
+    }
+}
+
+struct MetadataExtractor<'a>(&'a tonic::metadata::MetadataMap);
+
+impl opentelemetry::propagation::Extractor for MetadataExtractor<'_> {
+    #[inline]
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    #[inline]
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|key| match key {
+            tonic::metadata::KeyRef::Ascii(key) => key.as_str(),
+            tonic::metadata::KeyRef::Binary(key) => key.as_str(),
+        }).collect()
+    }
+}
+
+#[derive(Clone, Default, Debug)]
+///Server interceptor which inserts `opentelemetry::Context` extracted from incoming request
+///
+///If propagator finds no remote span, `grpc-trace-bin` is used instead.
+///Context is only inserted when incoming request carries remote span.
+pub struct ExtractContext {
+    propagator: Propagator,
+}
+
+impl ExtractContext {
+    #[inline(always)]
+    ///Creates new instance using global propagator
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    ///Creates new instance using specified propagator
+    pub fn with_propagator<P: TextMapPropagator + Send + Sync + 'static>(propagator: P) -> Self {
+        Self {
+            propagator: Propagator::new(propagator),
+        }
+    }
+}
+
+impl Interceptor for ExtractContext {
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        let context = self.propagator.with(|propagator| propagator.extract_with_context(&Context::new(), &MetadataExtractor(headers)));
+        if context.span().span_context().is_valid() {
+            extensions.insert(context);
+        } else if let Some(span) = headers.get_bin(GRPC_TRACE_BIN).and_then(|value| value.to_bytes().ok()).and_then(|value| decode_trace_bin(&value)) {
+            extensions.insert(context.with_remote_span_context(span));
+        }
+        None
+    }
+
+    #[inline(always)]
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
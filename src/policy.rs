@@ -0,0 +1,1952 @@
+//! Declarative metadata policies
+//!
+//!`RequiredMetadata` validates request metadata against list of rules:
+//!
+//!```rust
+//!use tonic_interceptor::policy::{RequiredMetadata, Rule};
+//!
+//!let policy = RequiredMetadata::builder()
+//!    .rule(Rule::required("x-tenant").pattern("[a-z0-9-]{1,32}").expect("valid pattern"))
+//!    .rule(Rule::required("x-client-version"))
+//!    .rule(Rule::optional("x-request-id-bin").len(16, 16))
+//!    .build();
+//!```
+//!
+//!Whether value is ASCII or binary is determined by key, binary keys ending with `-bin`.
+//!Length of binary value is length of decoded bytes.
+//!
+//!`ValidateBinaryMetadata` checks that binary values can be decoded, before handler attempts it.
+//!
+//!`ValueHygiene` rejects or repairs ASCII values with whitespace, control characters or bytes, which `HeaderValue` permits, but downstream systems may not.
+//!
+//!`KnownMethods` rejects calls of unknown services before other interceptors run.
+//!
+//!`Freshness` rejects requests, which timestamp is stale or too far in future, regardless of authentication scheme.
+//!
+//!`RetryAttempts` makes `grpc-previous-rpc-attempts` of retried calls available as `PreviousAttempts`, optionally limiting it.
+//!
+//!`AuthorityCheck` rejects requests with unexpected `:authority`, such as ones of direct-to-IP scanners.
+//!
+//!`ContentTypeGate` restricts gRPC codecs accepted by each method, optionally answering non-gRPC requests with plain HTTP response.
+//!
+//!`KnownMethods`, `AuthorityCheck` and `ContentTypeGate` inspect request URI, hence they only act within `on_request_with_uri`,
+//!which `InterceptorService` calls, while passing request through when called by `on_request` alone.
+//!
+//!`RemapStatus` rewrites status codes of selected methods.
+
+use crate::Interceptor;
+use crate::auth::{Clock, SystemClock};
+use crate::matcher::MethodMatcher;
+use crate::compat;
+
+use core::fmt;
+use core::task;
+use core::convert::TryFrom;
+use core::pin::Pin;
+use core::future::Future;
+use core::time::Duration;
+use std::sync::Arc;
+
+use regex::bytes::Regex;
+
+#[derive(Clone, Debug, PartialEq)]
+///Error of setting pattern of value
+pub enum PatternError {
+    ///Pattern is not valid regular expression
+    Regex(regex::Error),
+    ///Binary value cannot have pattern
+    Binary,
+}
+
+impl fmt::Display for PatternError {
+    #[inline]
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatternError::Regex(error) => write!(fmt, "invalid pattern: {}", error),
+            PatternError::Binary => fmt.write_str("binary value cannot have pattern"),
+        }
+    }
+}
+
+impl std::error::Error for PatternError {
+    #[inline]
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PatternError::Regex(error) => Some(error),
+            PatternError::Binary => None,
+        }
+    }
+}
+
+impl From<regex::Error> for PatternError {
+    #[inline(always)]
+    fn from(error: regex::Error) -> Self {
+        PatternError::Regex(error)
+    }
+}
+
+enum Violation {
+    Missing,
+    Invalid(&'static str),
+}
+
+#[derive(Clone, Debug)]
+///Rule of single metadata key
+pub struct Rule {
+    key: String,
+    required: bool,
+    min_len: Option<usize>,
+    max_len: Option<usize>,
+    pattern: Option<Regex>,
+}
+
+impl Rule {
+    #[inline]
+    fn new(key: impl Into<String>, required: bool) -> Self {
+        Self {
+            key: key.into(),
+            required,
+            min_len: None,
+            max_len: None,
+            pattern: None,
+        }
+    }
+
+    #[inline]
+    ///Creates rule of key, which must be present
+    pub fn required(key: impl Into<String>) -> Self {
+        Self::new(key, true)
+    }
+
+    #[inline]
+    ///Creates rule of key, which is validated only when present
+    pub fn optional(key: impl Into<String>) -> Self {
+        Self::new(key, false)
+    }
+
+    #[inline(always)]
+    ///Returns key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    #[inline(always)]
+    ///Returns whether key is binary
+    pub fn is_bin(&self) -> bool {
+        self.key.ends_with("-bin")
+    }
+
+    #[inline]
+    ///Limits length of value, inclusive
+    pub fn len(mut self, min: usize, max: usize) -> Self {
+        self.min_len = Some(min);
+        self.max_len = Some(max);
+        self
+    }
+
+    #[inline]
+    ///Sets minimal length of value
+    pub fn min_len(mut self, min: usize) -> Self {
+        self.min_len = Some(min);
+        self
+    }
+
+    #[inline]
+    ///Sets maximal length of value
+    pub fn max_len(mut self, max: usize) -> Self {
+        self.max_len = Some(max);
+        self
+    }
+
+    #[inline]
+    ///Sets regular expression, which whole ASCII value must match
+    ///
+    ///Binary keys cannot have pattern, which is reported as error.
+    pub fn pattern(mut self, pattern: &str) -> Result<Self, PatternError> {
+        if self.is_bin() {
+            return Err(PatternError::Binary);
+        }
+        //Validated on its own, so that errors point into `pattern` rather than its anchored form
+        Regex::new(pattern)?;
+        self.pattern = Some(Regex::new(&format!("^(?:{})$", pattern))?);
+        Ok(self)
+    }
+
+    //Returns violation of rule, if any
+    fn check(&self, headers: &tonic::metadata::MetadataMap) -> Option<Violation> {
+        let len = match self.is_bin() {
+            true => match headers.get_bin(&self.key) {
+                None => return self.required.then_some(Violation::Missing),
+                Some(value) => match value.to_bytes() {
+                    Ok(value) => value.len(),
+                    Err(_) => return Some(Violation::Invalid("is not valid base64")),
+                },
+            },
+            false => match headers.get(&self.key) {
+                None => return self.required.then_some(Violation::Missing),
+                Some(value) => match value.to_str() {
+                    Ok(value) => {
+                        if let Some(pattern) = &self.pattern {
+                            if !pattern.is_match(value.as_bytes()) {
+                                return Some(Violation::Invalid("has invalid format"));
+                            }
+                        }
+                        value.len()
+                    },
+                    Err(_) => return Some(Violation::Invalid("is not valid string")),
+                },
+            },
+        };
+
+        if self.min_len.map_or(false, |min| len < min) || self.max_len.map_or(false, |max| len > max) {
+            return Some(Violation::Invalid("has invalid length"));
+        }
+        None
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+///Server interceptor which validates request metadata against rules
+///
+///Violations are rejected with `INVALID_ARGUMENT`, naming every failing key in order of rules,
+///e.g. `missing metadata 'x-tenant', metadata 'x-client-version' has invalid length`.
+pub struct RequiredMetadata {
+    rules: Vec<Rule>,
+}
+
+impl RequiredMetadata {
+    #[inline(always)]
+    ///Starts building policy
+    pub fn builder() -> RequiredMetadataBuilder {
+        RequiredMetadataBuilder {
+            rules: Vec::new(),
+        }
+    }
+
+    #[inline(always)]
+    ///Returns rules
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    ///Validates `headers`, returning status describing every violation
+    pub fn validate(&self, headers: &tonic::metadata::MetadataMap) -> Result<(), tonic::Status> {
+        let mut message = String::new();
+        for rule in self.rules.iter() {
+            if let Some(violation) = rule.check(headers) {
+                if !message.is_empty() {
+                    message.push_str(", ");
+                }
+                match violation {
+                    Violation::Missing => message.push_str(&format!("missing metadata '{}'", rule.key)),
+                    Violation::Invalid(reason) => message.push_str(&format!("metadata '{}' {}", rule.key, reason)),
+                }
+            }
+        }
+
+        match message.is_empty() {
+            true => Ok(()),
+            false => Err(tonic::Status::invalid_argument(message)),
+        }
+    }
+}
+
+impl Interceptor for RequiredMetadata {
+    #[inline]
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+        self.validate(headers).err()
+    }
+
+    #[inline(always)]
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
+
+#[derive(Debug)]
+///Builder of `RequiredMetadata`
+pub struct RequiredMetadataBuilder {
+    rules: Vec<Rule>,
+}
+
+impl RequiredMetadataBuilder {
+    #[inline]
+    ///Adds rule
+    pub fn rule(mut self, rule: Rule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    #[inline]
+    ///Adds rule of key, which must be present without further restrictions
+    pub fn required(self, key: impl Into<String>) -> Self {
+        self.rule(Rule::required(key))
+    }
+
+    #[inline(always)]
+    ///Builds policy
+    pub fn build(self) -> RequiredMetadata {
+        RequiredMetadata {
+            rules: self.rules,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+///Server interceptor which validates binary (`-bin`) metadata of request
+///
+///Every value of checked keys must be valid base64, optionally limited in decoded size.
+///Violations are rejected with `INVALID_ARGUMENT`, naming every failing key,
+///e.g. `metadata 'x-token-bin' is not valid base64`.
+///
+///In lenient mode invalid values are removed instead, leaving valid values of the same key intact.
+pub struct ValidateBinaryMetadata {
+    keys: Option<Vec<String>>,
+    max_size: Option<usize>,
+    lenient: bool,
+}
+
+impl ValidateBinaryMetadata {
+    #[inline(always)]
+    ///Creates new instance, checking every binary key
+    pub const fn new() -> Self {
+        Self {
+            keys: None,
+            max_size: None,
+            lenient: false,
+        }
+    }
+
+    #[inline]
+    ///Limits checks to `keys`
+    ///
+    ///Keys, which are not binary, are ignored.
+    pub fn keys<K: Into<String>, I: IntoIterator<Item = K>>(mut self, keys: I) -> Self {
+        let keys = keys.into_iter().map(Into::into).filter(|key: &String| key.ends_with("-bin")).collect();
+        self.keys = Some(keys);
+        self
+    }
+
+    #[inline(always)]
+    ///Sets maximal decoded size of single value
+    pub const fn max_size(mut self, max: usize) -> Self {
+        self.max_size = Some(max);
+        self
+    }
+
+    #[inline(always)]
+    ///Removes invalid values instead of rejecting request
+    pub const fn lenient(mut self) -> Self {
+        self.lenient = true;
+        self
+    }
+
+    //Returns reason why value is invalid, if any
+    fn check(&self, value: &tonic::metadata::BinaryMetadataValue) -> Option<&'static str> {
+        match value.to_bytes() {
+            Ok(value) if self.max_size.map_or(false, |max| value.len() > max) => Some("exceeds maximum size"),
+            Ok(_) => None,
+            Err(_) => Some("is not valid base64"),
+        }
+    }
+
+    ///Validates binary metadata of `headers`
+    ///
+    ///In lenient mode invalid values are removed from `headers` and it always succeeds.
+    pub fn validate(&self, headers: &mut tonic::metadata::MetadataMap) -> Result<(), tonic::Status> {
+        let keys = match &self.keys {
+            Some(keys) => keys.clone(),
+            None => headers.keys().filter_map(|key| match key {
+                tonic::metadata::KeyRef::Binary(key) => Some(key.as_str().to_owned()),
+                tonic::metadata::KeyRef::Ascii(_) => None,
+            }).collect(),
+        };
+
+        let mut message = String::new();
+        for key in keys.iter() {
+            let reason = match headers.get_all_bin(key.as_str()).iter().find_map(|value| self.check(value)) {
+                Some(reason) => reason,
+                None => continue,
+            };
+
+            if self.lenient {
+                let valid: Vec<_> = headers.get_all_bin(key.as_str()).iter().filter(|value| self.check(value).is_none()).cloned().collect();
+                let key = tonic::metadata::BinaryMetadataKey::from_bytes(key.as_bytes()).expect("valid binary metadata key");
+                headers.remove_bin(&key);
+                for value in valid {
+                    headers.append_bin(key.clone(), value);
+                }
+            } else {
+                if !message.is_empty() {
+                    message.push_str(", ");
+                }
+                message.push_str(&format!("metadata '{}' {}", key, reason));
+            }
+        }
+
+        match message.is_empty() {
+            true => Ok(()),
+            false => Err(tonic::Status::invalid_argument(message)),
+        }
+    }
+}
+
+impl Interceptor for ValidateBinaryMetadata {
+    #[inline]
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+        self.validate(headers).err()
+    }
+
+    #[inline(always)]
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+///Check of ASCII metadata value, with its repair in lenient mode
+pub enum ValuePolicy {
+    ///Value must not have leading or trailing whitespace, which is trimmed on repair
+    Trim,
+    ///Value must not contain control characters, such as tab, which are removed on repair
+    RejectControlChars,
+    ///Value must consist of visible ASCII characters and space, while other bytes are removed on repair
+    RequireVisibleAscii,
+    ///Value must not be longer than `n` bytes, which it is truncated to on repair
+    MaxLen(usize),
+}
+
+impl ValuePolicy {
+    //Returns reason why value is invalid, if any
+    fn check(&self, value: &[u8]) -> Option<String> {
+        match self {
+            ValuePolicy::Trim => match value.first().is_some_and(u8::is_ascii_whitespace) || value.last().is_some_and(u8::is_ascii_whitespace) {
+                true => Some("has leading or trailing whitespace".to_owned()),
+                false => None,
+            },
+            ValuePolicy::RejectControlChars => match value.iter().any(u8::is_ascii_control) {
+                true => Some("contains control characters".to_owned()),
+                false => None,
+            },
+            ValuePolicy::RequireVisibleAscii => match value.iter().all(|byte| (0x20..0x7f).contains(byte)) {
+                true => None,
+                false => Some("contains characters other than visible ASCII".to_owned()),
+            },
+            ValuePolicy::MaxLen(max) => match value.len() > *max {
+                true => Some(format!("exceeds {} bytes", max)),
+                false => None,
+            },
+        }
+    }
+
+    fn repair(&self, value: &[u8]) -> Vec<u8> {
+        match self {
+            ValuePolicy::Trim => value.trim_ascii().to_vec(),
+            ValuePolicy::RejectControlChars => value.iter().filter(|byte| !byte.is_ascii_control()).copied().collect(),
+            ValuePolicy::RequireVisibleAscii => value.iter().filter(|byte| (0x20..0x7f).contains(*byte)).copied().collect(),
+            ValuePolicy::MaxLen(max) => value[..value.len().min(*max)].to_vec(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+///Server interceptor which enforces hygiene of ASCII metadata values
+///
+///```rust
+///use tonic_interceptor::policy::{ValueHygiene, ValuePolicy};
+///
+///let hygiene = ValueHygiene::new().key("x-tenant", &[ValuePolicy::Trim, ValuePolicy::RequireVisibleAscii, ValuePolicy::MaxLen(32)])
+///                                 .key("x-forwarded-*", &[ValuePolicy::RejectControlChars]);
+///```
+///
+///Keys are matched exactly, or by prefix when pattern ends with `*`, while binary (`-bin`) keys are never checked,
+///see `ValidateBinaryMetadata` instead.
+///`HeaderValue` permits tabs and bytes above ASCII, which some downstream systems cannot handle.
+///
+///Violations are rejected with `INVALID_ARGUMENT`, naming every failing key, e.g. `metadata 'x-tenant' has leading or trailing whitespace`.
+///
+///In lenient mode invalid values are repaired instead, applying policies of every matching pattern in order.
+pub struct ValueHygiene {
+    rules: Vec<(String, Vec<ValuePolicy>)>,
+    lenient: bool,
+}
+
+impl ValueHygiene {
+    #[inline(always)]
+    ///Creates new instance without rules
+    pub const fn new() -> Self {
+        Self {
+            rules: Vec::new(),
+            lenient: false,
+        }
+    }
+
+    #[inline]
+    ///Adds `policies` of keys matching `pattern`
+    pub fn key(mut self, pattern: impl Into<String>, policies: &[ValuePolicy]) -> Self {
+        self.rules.push((pattern.into(), policies.to_vec()));
+        self
+    }
+
+    #[inline(always)]
+    ///Repairs invalid values instead of rejecting request
+    pub const fn lenient(mut self) -> Self {
+        self.lenient = true;
+        self
+    }
+
+    //Returns policies applicable to `key`
+    fn policies<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a ValuePolicy> + 'a {
+        self.rules.iter().filter(move |(pattern, _)| match pattern.strip_suffix('*') {
+            Some(prefix) => key.starts_with(prefix),
+            None => pattern == key,
+        }).flat_map(|(_, policies)| policies.iter())
+    }
+
+    ///Validates ASCII metadata of `headers`
+    ///
+    ///In lenient mode invalid values are repaired within `headers` and it always succeeds.
+    pub fn validate(&self, headers: &mut http::HeaderMap) -> Result<(), tonic::Status> {
+        let mut message = String::new();
+        let mut repaired = Vec::new();
+        for key in headers.keys() {
+            if key.as_str().ends_with("-bin") || self.policies(key.as_str()).next().is_none() {
+                continue;
+            }
+
+            let reason = headers.get_all(key).iter().find_map(|value| self.policies(key.as_str()).find_map(|policy| policy.check(value.as_bytes())));
+            let reason = match reason {
+                Some(reason) => reason,
+                None => continue,
+            };
+
+            if self.lenient {
+                let values: Vec<_> = headers.get_all(key).iter().filter_map(|value| {
+                    let value = self.policies(key.as_str()).fold(value.as_bytes().to_vec(), |value, policy| policy.repair(&value));
+                    http::HeaderValue::from_bytes(&value).ok()
+                }).collect();
+                repaired.push((key.clone(), values));
+            } else {
+                if !message.is_empty() {
+                    message.push_str(", ");
+                }
+                message.push_str(&format!("metadata '{}' {}", key, reason));
+            }
+        }
+
+        for (key, values) in repaired {
+            headers.remove(&key);
+            for value in values {
+                headers.append(key.clone(), value);
+            }
+        }
+
+        match message.is_empty() {
+            true => Ok(()),
+            false => Err(tonic::Status::invalid_argument(message)),
+        }
+    }
+}
+
+impl Interceptor for ValueHygiene {
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+        let mut raw = crate::compat::metadata_into_headers(core::mem::take(headers));
+        let result = self.validate(&mut raw);
+        *headers = crate::compat::metadata_from_headers(raw);
+        result.err()
+    }
+
+    #[inline]
+    fn on_request_headers(&self, _: &http::Uri, headers: &mut http::HeaderMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+        self.validate(headers).err()
+    }
+
+    #[inline(always)]
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+///Server interceptor which rejects calls of unknown services with `UNIMPLEMENTED`
+///
+///Service is the first segment of `/<service>/<method>` path, and is looked up among known services.
+///Generated servers expose it as `NamedService::NAME` constant:
+///
+///```rust,ignore
+///let known = KnownMethods::new([MyServiceServer::<MyService>::NAME]).allow_prefix("/grpc.health.v1.Health/");
+///```
+///
+///Paths, which are not of method form, are rejected as well unless allowed by prefix.
+///Place it before interceptors, which shouldn't run for such calls.
+pub struct KnownMethods {
+    services: std::collections::HashSet<String>,
+    prefixes: Vec<String>,
+}
+
+impl KnownMethods {
+    #[inline]
+    ///Creates new instance, accepting `services`
+    pub fn new<S: Into<String>, I: IntoIterator<Item = S>>(services: I) -> Self {
+        Self {
+            services: services.into_iter().map(Into::into).collect(),
+            prefixes: Vec::new(),
+        }
+    }
+
+    #[inline]
+    ///Accepts every path starting with `prefix`, e.g. `/grpc.reflection.`
+    pub fn allow_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefixes.push(prefix.into());
+        self
+    }
+
+    ///Checks method `path`
+    pub fn check(&self, path: &str) -> Result<(), tonic::Status> {
+        if self.prefixes.iter().any(|prefix| path.starts_with(prefix.as_str())) {
+            return Ok(());
+        }
+
+        match path.strip_prefix('/').and_then(|path| path.split_once('/')) {
+            Some((service, method)) if !service.is_empty() && !method.is_empty() && !method.contains('/') => match self.services.contains(service) {
+                true => Ok(()),
+                false => Err(tonic::Status::unimplemented(format!("unknown service '{}'", service))),
+            },
+            _ => Err(tonic::Status::unimplemented(format!("malformed method path '{}'", path))),
+        }
+    }
+}
+
+impl Interceptor for KnownMethods {
+    #[inline(always)]
+    fn on_request(&self, _: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+        None
+    }
+
+    #[inline]
+    fn on_request_with_uri(&self, uri: &http::Uri, _: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+        self.check(uri.path()).err()
+    }
+
+    #[inline]
+    fn on_request_headers(&self, uri: &http::Uri, _: &mut http::HeaderMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+        self.check(uri.path()).err()
+    }
+
+    #[inline(always)]
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
+
+///Metadata key of request timestamp, checked by `Freshness` by default
+pub const REQUEST_TIMESTAMP: &str = "x-request-timestamp";
+///Metadata key of server time as seconds since UNIX epoch, included in rejection of `Freshness`
+pub const SERVER_TIME: &str = "x-server-time";
+
+//Parses fixed number of ASCII digits
+fn parse_digits(value: &[u8]) -> Option<u64> {
+    match value.is_empty() {
+        true => None,
+        false => value.iter().try_fold(0u64, |acc, digit| match digit {
+            b'0'..=b'9' => acc.checked_mul(10)?.checked_add(u64::from(digit - b'0')),
+            _ => None,
+        }),
+    }
+}
+
+//Returns number of days since UNIX epoch of civil date
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+//Parses `YYYY-MM-DDTHH:MM:SS[.fraction](Z|+HH:MM|-HH:MM)` into milliseconds since UNIX epoch
+fn parse_rfc3339(value: &[u8]) -> Option<u64> {
+    if value.len() < 20 || value[4] != b'-' || value[7] != b'-' || !matches!(value[10], b'T' | b't' | b' ') || value[13] != b':' || value[16] != b':' {
+        return None;
+    }
+    let year = parse_digits(&value[0..4])? as i64;
+    let month = parse_digits(&value[5..7])? as i64;
+    let day = parse_digits(&value[8..10])? as i64;
+    let hour = parse_digits(&value[11..13])? as i64;
+    let minute = parse_digits(&value[14..16])? as i64;
+    let second = parse_digits(&value[17..19])? as i64;
+
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days_in_month = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap => 29,
+        2 => 28,
+        _ => return None,
+    };
+    //Leap second is allowed by RFC3339
+    if day == 0 || day > days_in_month || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+
+    let mut rest = &value[19..];
+    let mut millis = 0;
+    if let Some(fraction) = rest.strip_prefix(b".") {
+        let len = fraction.iter().take_while(|byte| byte.is_ascii_digit()).count();
+        if len == 0 {
+            return None;
+        }
+        for (idx, digit) in fraction[..len.min(3)].iter().enumerate() {
+            millis += i64::from(digit - b'0') * [100, 10, 1][idx];
+        }
+        rest = &fraction[len..];
+    }
+
+    let offset = match rest {
+        b"Z" | b"z" => 0,
+        [sign @ (b'+' | b'-'), hours @ .., b':', _, _] if hours.len() == 2 => {
+            let hours = parse_digits(hours)? as i64;
+            let minutes = parse_digits(&rest[4..6])? as i64;
+            if hours > 23 || minutes > 59 {
+                return None;
+            }
+            let offset = hours * 3600 + minutes * 60;
+            match sign {
+                b'+' => offset,
+                _ => -offset,
+            }
+        },
+        _ => return None,
+    };
+
+    let seconds = days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second - offset;
+    u64::try_from(seconds * 1000 + millis).ok()
+}
+
+///Parses timestamp into milliseconds since UNIX epoch
+///
+///Accepted formats:
+///
+///- RFC3339, e.g. `2024-01-02T03:04:05.678Z` or `2024-01-02T05:04:05+02:00`;
+///- UNIX seconds, e.g. `1704164645`;
+///- UNIX milliseconds, e.g. `1704164645678`, which is assumed for numbers of at least 12 digits.
+pub fn parse_timestamp(value: &str) -> Option<u64> {
+    //Seconds would be beyond year 5000
+    const MIN_MILLIS: u64 = 100_000_000_000;
+
+    let value = value.as_bytes();
+    if value.iter().all(u8::is_ascii_digit) {
+        let number = parse_digits(value)?;
+        match number >= MIN_MILLIS {
+            true => Some(number),
+            false => number.checked_mul(1000),
+        }
+    } else {
+        parse_rfc3339(value)
+    }
+}
+
+#[derive(Clone, Debug)]
+///Server interceptor which rejects requests with stale or future timestamp
+///
+///Timestamp is taken from `x-request-timestamp` unless configured otherwise, and parsed by `parse_timestamp`.
+///Request is accepted when it is at most `max_age` old and at most `max_skew` ahead of server's clock, both inclusive.
+///
+///Missing or malformed timestamp is rejected with `INVALID_ARGUMENT`, while stale or future timestamp with `PERMISSION_DENIED`.
+///Rejections carry `x-server-time` metadata, so that clients can correct their clock.
+pub struct Freshness<C = SystemClock> {
+    key: &'static str,
+    max_age: Duration,
+    max_skew: Duration,
+    clock: C,
+}
+
+impl Freshness {
+    #[inline]
+    ///Creates new instance, accepting timestamps within `max_age` in the past and `max_skew` in future
+    pub const fn new(max_age: Duration, max_skew: Duration) -> Self {
+        Self {
+            key: REQUEST_TIMESTAMP,
+            max_age,
+            max_skew,
+            clock: SystemClock,
+        }
+    }
+}
+
+impl<C: Clock> Freshness<C> {
+    #[inline]
+    ///Sets metadata key of timestamp, which must be lower case
+    pub fn key(mut self, key: &'static str) -> Self {
+        self.key = key;
+        self
+    }
+
+    #[inline]
+    ///Sets clock
+    pub fn clock<C2: Clock>(self, clock: C2) -> Freshness<C2> {
+        Freshness {
+            key: self.key,
+            max_age: self.max_age,
+            max_skew: self.max_skew,
+            clock,
+        }
+    }
+
+    ///Checks timestamp `value`
+    pub fn check(&self, value: Option<&[u8]>) -> Result<(), tonic::Status> {
+        let now = self.clock.now_millis();
+        let mut status = match value.map(|value| core::str::from_utf8(value).ok().and_then(parse_timestamp)) {
+            None => tonic::Status::invalid_argument(format!("missing request timestamp '{}'", self.key)),
+            Some(None) => tonic::Status::invalid_argument(format!("invalid request timestamp '{}'", self.key)),
+            Some(Some(timestamp)) => {
+                let max_age = self.max_age.as_millis() as u64;
+                let max_skew = self.max_skew.as_millis() as u64;
+                if timestamp.saturating_add(max_age) < now {
+                    tonic::Status::permission_denied("request timestamp is too old")
+                } else if timestamp > now.saturating_add(max_skew) {
+                    tonic::Status::permission_denied("request timestamp is too far in future")
+                } else {
+                    return Ok(());
+                }
+            },
+        };
+
+        status.metadata_mut().insert(SERVER_TIME, tonic::metadata::AsciiMetadataValue::from(now / 1000));
+        Err(status)
+    }
+}
+
+impl<C: Clock> Interceptor for Freshness<C> {
+    #[inline]
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+        self.check(headers.get(self.key).map(|value| value.as_encoded_bytes())).err()
+    }
+
+    #[inline]
+    fn on_request_headers(&self, _: &http::Uri, headers: &mut http::HeaderMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+        self.check(headers.get(self.key).map(http::HeaderValue::as_bytes)).err()
+    }
+
+    #[inline(always)]
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
+
+///Header with number of preceding attempts of retried call
+pub const GRPC_PREVIOUS_RPC_ATTEMPTS: &str = "grpc-previous-rpc-attempts";
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+///Number of attempts preceding current call, which is zero for first attempt
+pub struct PreviousAttempts(pub u32);
+
+impl PreviousAttempts {
+    ///Parses header value, which must be decimal number
+    pub fn parse(value: &[u8]) -> Option<Self> {
+        if value.is_empty() || !value.iter().all(u8::is_ascii_digit) {
+            return None;
+        }
+        core::str::from_utf8(value).ok().and_then(|value| value.parse().ok()).map(Self)
+    }
+
+    #[inline]
+    ///Extracts value of `grpc-previous-rpc-attempts`, which is zero when header is missing
+    ///
+    ///Returns `None` if value is invalid.
+    pub fn from_headers(headers: &http::HeaderMap) -> Option<Self> {
+        match headers.get(GRPC_PREVIOUS_RPC_ATTEMPTS) {
+            Some(value) => Self::parse(value.as_bytes()),
+            None => Some(Self(0)),
+        }
+    }
+
+    #[inline(always)]
+    ///Returns whether call is retry
+    pub const fn is_retry(&self) -> bool {
+        self.0 > 0
+    }
+
+    #[inline]
+    ///Returns label of low cardinality: `0`, `1` or `2+`
+    pub const fn bucket(&self) -> &'static str {
+        match self.0 {
+            0 => "0",
+            1 => "1",
+            _ => "2+",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+///Server interceptor which inserts `PreviousAttempts` extension from `grpc-previous-rpc-attempts` header
+///
+///Request without header is first attempt.
+///Invalid value is ignored, leaving request without extension, unless strict mode rejects it with `INVALID_ARGUMENT`.
+///When limit is set, request with more previous attempts is rejected with `RESOURCE_EXHAUSTED`.
+pub struct RetryAttempts {
+    max: Option<u32>,
+    strict: bool,
+}
+
+impl RetryAttempts {
+    #[inline(always)]
+    ///Creates new instance without limit
+    pub const fn new() -> Self {
+        Self {
+            max: None,
+            strict: false,
+        }
+    }
+
+    #[inline(always)]
+    ///Sets maximum number of previous attempts
+    pub const fn max(mut self, max: u32) -> Self {
+        self.max = Some(max);
+        self
+    }
+
+    #[inline(always)]
+    ///Sets whether invalid value is rejected
+    pub const fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    ///Checks header `value`, returning attempts to insert, if valid
+    pub fn check(&self, value: Option<&[u8]>) -> Result<Option<PreviousAttempts>, tonic::Status> {
+        let attempts = match value {
+            Some(value) => match PreviousAttempts::parse(value) {
+                Some(attempts) => attempts,
+                None if self.strict => return Err(tonic::Status::invalid_argument(format!("invalid '{}'", GRPC_PREVIOUS_RPC_ATTEMPTS))),
+                None => return Ok(None),
+            },
+            None => PreviousAttempts(0),
+        };
+
+        match self.max {
+            Some(max) if attempts.0 > max => Err(tonic::Status::resource_exhausted(format!("{} previous attempts exceed limit of {}", attempts.0, max))),
+            _ => Ok(Some(attempts)),
+        }
+    }
+
+    #[inline]
+    fn apply(&self, value: Option<&[u8]>, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        match self.check(value) {
+            Ok(attempts) => {
+                if let Some(attempts) = attempts {
+                    extensions.insert(attempts);
+                }
+                None
+            },
+            Err(status) => Some(status),
+        }
+    }
+}
+
+impl Interceptor for RetryAttempts {
+    #[inline]
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        self.apply(headers.get(GRPC_PREVIOUS_RPC_ATTEMPTS).map(|value| value.as_encoded_bytes()), extensions)
+    }
+
+    #[inline]
+    fn on_request_headers(&self, _: &http::Uri, headers: &mut http::HeaderMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        self.apply(headers.get(GRPC_PREVIOUS_RPC_ATTEMPTS).map(http::HeaderValue::as_bytes), extensions)
+    }
+
+    #[inline(always)]
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+///Authority of request, normalized by `AuthorityCheck`
+///
+///Host is lowercase without trailing dot, while IPv6 literal is kept in canonical form without brackets.
+pub struct Authority {
+    host: String,
+    port: Option<u16>,
+}
+
+impl Authority {
+    ///Parses `host[:port]`, where host is name, IPv4 or bracketed IPv6 literal
+    ///
+    ///Returns `None` for empty host, invalid port, user info or unbracketed IPv6 literal.
+    pub fn parse(value: &str) -> Option<Self> {
+        if value.contains('@') {
+            return None;
+        }
+
+        let (host, port) = match value.strip_prefix('[') {
+            Some(value) => {
+                let (host, rest) = value.split_once(']')?;
+                let host = host.parse::<std::net::Ipv6Addr>().ok()?.to_string();
+                match rest {
+                    "" => (host, None),
+                    rest => (host, Some(rest.strip_prefix(':')?)),
+                }
+            },
+            None => {
+                let (host, port) = match value.split_once(':') {
+                    Some((host, port)) => (host, Some(port)),
+                    None => (value, None),
+                };
+                let host = host.strip_suffix('.').unwrap_or(host);
+                if host.is_empty() || !host.bytes().all(|byte| byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'.' || byte == b'_') {
+                    return None;
+                }
+                (host.to_ascii_lowercase(), port)
+            },
+        };
+        let port = match port {
+            Some(port) if !port.is_empty() && port.bytes().all(|byte| byte.is_ascii_digit()) => Some(port.parse().ok()?),
+            Some(_) => return None,
+            None => None,
+        };
+
+        Some(Self {
+            host,
+            port,
+        })
+    }
+
+    #[inline(always)]
+    ///Returns host
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    #[inline(always)]
+    ///Returns port, if specified
+    pub fn port(&self) -> Option<u16> {
+        self.port
+    }
+
+    #[inline(always)]
+    ///Returns authority of request, if checked by `AuthorityCheck`
+    pub fn from_extensions(extensions: &http::Extensions) -> Option<&Self> {
+        extensions.get()
+    }
+}
+
+impl fmt::Display for Authority {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.host.contains(':') {
+            true => write!(fmt, "[{}]", self.host)?,
+            false => fmt.write_str(&self.host)?,
+        }
+        match self.port {
+            Some(port) => write!(fmt, ":{}", port),
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+///Error of parsing allowed authority of `AuthorityCheck`
+pub struct InvalidAuthority {
+    authority: String,
+}
+
+impl fmt::Display for InvalidAuthority {
+    #[inline]
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "invalid authority '{}'", self.authority)
+    }
+}
+
+impl std::error::Error for InvalidAuthority {
+}
+
+#[derive(Clone, Debug)]
+//Allowed authority, matching subdomains of host when wildcard
+struct AllowedAuthority {
+    wildcard: bool,
+    authority: Authority,
+}
+
+#[derive(Clone, Debug)]
+///Server interceptor which rejects requests with unexpected `:authority`, inserting normalized `Authority` extension otherwise
+///
+///Authority is taken from request URI, falling back to `host` header of HTTP/1.1 requests.
+///Request without valid authority is rejected.
+///
+///Allowed authorities are exact, e.g. `api.example.com` or `[::1]:8443`, or wildcard of subdomains, e.g. `*.example.com`,
+///which doesn't match `example.com` itself.
+///Allowed authority with port matches only that port, while one without port matches only requests without port,
+///unless port is ignored.
+///
+///Rejection is `PERMISSION_DENIED` by default.
+pub struct AuthorityCheck {
+    allowed: Vec<AllowedAuthority>,
+    ignore_port: bool,
+    code: tonic::Code,
+}
+
+impl AuthorityCheck {
+    #[inline]
+    ///Creates new instance, which allows no authority
+    pub fn new() -> Self {
+        Self {
+            allowed: Vec::new(),
+            ignore_port: false,
+            code: tonic::Code::PermissionDenied,
+        }
+    }
+
+    ///Allows `authority`, optionally prefixed with `*.` to allow its subdomains
+    pub fn allow(mut self, authority: &str) -> Result<Self, InvalidAuthority> {
+        let (wildcard, value) = match authority.strip_prefix("*.") {
+            Some(value) => (true, value),
+            None => (false, authority),
+        };
+        match Authority::parse(value) {
+            Some(parsed) => {
+                self.allowed.push(AllowedAuthority {
+                    wildcard,
+                    authority: parsed,
+                });
+                Ok(self)
+            },
+            None => Err(InvalidAuthority {
+                authority: authority.to_owned(),
+            }),
+        }
+    }
+
+    #[inline(always)]
+    ///Sets whether port is ignored when matching allowed authorities
+    pub fn ignore_port(mut self, ignore_port: bool) -> Self {
+        self.ignore_port = ignore_port;
+        self
+    }
+
+    #[inline(always)]
+    ///Sets code of rejection, such as `UNIMPLEMENTED` to pretend that nothing is served for unknown authority
+    pub fn code(mut self, code: tonic::Code) -> Self {
+        self.code = code;
+        self
+    }
+
+    ///Returns whether `authority` is allowed
+    pub fn is_allowed(&self, authority: &Authority) -> bool {
+        self.allowed.iter().any(|allowed| {
+            let host = match allowed.wildcard {
+                true => authority.host.strip_suffix(allowed.authority.host.as_str()).is_some_and(|prefix| prefix.len() > 1 && prefix.ends_with('.')),
+                false => authority.host == allowed.authority.host,
+            };
+            host && (self.ignore_port || authority.port == allowed.authority.port)
+        })
+    }
+
+    ///Checks authority of request, given by URI or `host` header value
+    pub fn check(&self, uri: Option<&str>, host: Option<&[u8]>) -> Result<Authority, tonic::Status> {
+        let value = match uri {
+            Some(value) => Some(value),
+            None => host.and_then(|host| core::str::from_utf8(host).ok()),
+        };
+        let value = match value {
+            Some(value) => value,
+            None => return Err(tonic::Status::new(self.code, "missing authority")),
+        };
+        match Authority::parse(value) {
+            Some(authority) if self.is_allowed(&authority) => Ok(authority),
+            Some(authority) => Err(tonic::Status::new(self.code, format!("unexpected authority '{}'", authority))),
+            None => Err(tonic::Status::new(self.code, "malformed authority")),
+        }
+    }
+
+    #[inline]
+    fn apply(&self, uri: &http::Uri, host: Option<&[u8]>, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        match self.check(uri.authority().map(http::uri::Authority::as_str), host) {
+            Ok(authority) => {
+                extensions.insert(authority);
+                None
+            },
+            Err(status) => Some(status),
+        }
+    }
+}
+
+impl Default for AuthorityCheck {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Interceptor for AuthorityCheck {
+    #[inline(always)]
+    fn on_request(&self, _: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+        None
+    }
+
+    #[inline]
+    fn on_request_with_uri(&self, uri: &http::Uri, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        self.apply(uri, headers.get(http::header::HOST.as_str()).map(|value| value.as_encoded_bytes()), extensions)
+    }
+
+    #[inline]
+    fn on_request_headers(&self, uri: &http::Uri, headers: &mut http::HeaderMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        self.apply(uri, headers.get(http::header::HOST).map(http::HeaderValue::as_bytes), extensions)
+    }
+
+    #[inline(always)]
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+///gRPC content type of request, with subtype normalized
+pub enum ContentType {
+    ///`application/grpc` or `application/grpc+proto`
+    Proto,
+    ///`application/grpc+json`
+    Json,
+    ///`application/grpc-web` or `application/grpc-web-text`, with any subtype
+    Web,
+}
+
+impl ContentType {
+    ///Parses content type, returning `None` if it is not supported gRPC content type
+    ///
+    ///Parameters, if any, are ignored.
+    pub fn parse(value: &[u8]) -> Option<Self> {
+        let value = match value.iter().position(|byte| *byte == b';') {
+            Some(end) => &value[..end],
+            None => value,
+        };
+        let value = value.trim_ascii().to_ascii_lowercase();
+        match value.as_slice() {
+            b"application/grpc" | b"application/grpc+proto" => Some(Self::Proto),
+            b"application/grpc+json" => Some(Self::Json),
+            value if value.starts_with(b"application/grpc-web") => Some(Self::Web),
+            _ => None,
+        }
+    }
+
+    #[inline(always)]
+    ///Returns canonical content type
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Proto => "application/grpc",
+            Self::Json => "application/grpc+json",
+            Self::Web => "application/grpc-web",
+        }
+    }
+}
+
+impl fmt::Display for ContentType {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.write_str(self.as_str())
+    }
+}
+
+#[derive(Clone, Debug)]
+///Plain HTTP response to non-gRPC request
+pub struct PlainResponse {
+    status: http::StatusCode,
+    headers: http::HeaderMap,
+    body: bytes::Bytes,
+}
+
+impl PlainResponse {
+    #[inline]
+    ///Creates new instance with empty body
+    pub fn new(status: http::StatusCode) -> Self {
+        Self {
+            status,
+            headers: http::HeaderMap::new(),
+            body: bytes::Bytes::new(),
+        }
+    }
+
+    #[inline]
+    ///Adds header
+    pub fn header(mut self, name: http::header::HeaderName, value: http::HeaderValue) -> Self {
+        self.headers.append(name, value);
+        self
+    }
+
+    #[inline]
+    ///Sets body
+    pub fn body(mut self, body: impl Into<bytes::Bytes>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    fn to_response<B>(&self) -> http::Response<ContentTypeGateBody<B>> {
+        let mut response = http::Response::new(ContentTypeGateBody::plain(self.body.clone()));
+        *response.status_mut() = self.status;
+        *response.headers_mut() = self.headers.clone();
+        response
+    }
+}
+
+#[derive(Clone, Debug)]
+///Server interceptor which restricts content types accepted by methods
+///
+///Accepted content types are taken from the most specific `MethodMatcher`, falling back to default ones, which are `ContentType::Proto` unless set otherwise.
+///Request with content type, which is not accepted, not supported or not gRPC at all, is rejected with `UNIMPLEMENTED`.
+///Accepted request gets `ContentType` extension, so that interceptors running after it need not to care about subtype spelling.
+///
+///Plain HTTP response to non-gRPC requests, such as health check of load balancer, requires `layer`.
+pub struct ContentTypeGate {
+    methods: Vec<(MethodMatcher, Vec<ContentType>)>,
+    default: Vec<ContentType>,
+    fallback: Option<Arc<PlainResponse>>,
+}
+
+impl ContentTypeGate {
+    #[inline]
+    ///Creates new instance, accepting only `ContentType::Proto` for every method
+    pub fn new() -> Self {
+        Self {
+            methods: Vec::new(),
+            default: vec![ContentType::Proto],
+            fallback: None,
+        }
+    }
+
+    #[inline]
+    ///Sets content types accepted by methods without own configuration
+    pub fn accept(mut self, accepted: &[ContentType]) -> Self {
+        self.default = accepted.to_vec();
+        self
+    }
+
+    #[inline]
+    ///Sets content types accepted by methods matched by `matcher`
+    ///
+    ///When several matchers have the same specificity, the first one wins.
+    pub fn method(mut self, matcher: impl Into<MethodMatcher>, accepted: &[ContentType]) -> Self {
+        self.methods.push((matcher.into(), accepted.to_vec()));
+        self
+    }
+
+    #[inline]
+    ///Sets plain HTTP response to non-gRPC requests, which is used by `layer`
+    pub fn fallback(mut self, response: PlainResponse) -> Self {
+        self.fallback = Some(Arc::new(response));
+        self
+    }
+
+    ///Returns content types accepted by method `path`
+    pub fn accepted_for(&self, path: &str) -> &[ContentType] {
+        let mut result: Option<&(MethodMatcher, Vec<ContentType>)> = None;
+        for method in self.methods.iter().filter(|(matcher, _)| matcher.matches(path)) {
+            if result.is_none_or(|(current, _)| method.0.specificity() > current.specificity()) {
+                result = Some(method);
+            }
+        }
+        result.map(|(_, accepted)| accepted.as_slice()).unwrap_or(&self.default)
+    }
+
+    ///Checks content type `value` of method `path`
+    pub fn check(&self, path: &str, value: Option<&[u8]>) -> Result<ContentType, tonic::Status> {
+        let value = match value {
+            Some(value) => value,
+            None => return Err(tonic::Status::unimplemented("missing content type")),
+        };
+        let content_type = match ContentType::parse(value) {
+            Some(content_type) => content_type,
+            None => {
+                let value = String::from_utf8_lossy(value);
+                return match value.trim().to_ascii_lowercase().starts_with(crate::GRPC_CONTENT_TYPE) {
+                    true => Err(tonic::Status::unimplemented(format!("content type '{}' is not supported", value))),
+                    false => Err(tonic::Status::unimplemented(format!("content type '{}' is not gRPC", value))),
+                };
+            },
+        };
+
+        let accepted = self.accepted_for(path);
+        match accepted.contains(&content_type) {
+            true => Ok(content_type),
+            false => {
+                let accepted = accepted.iter().map(ContentType::as_str).collect::<Vec<_>>().join(", ");
+                Err(tonic::Status::unimplemented(format!("method '{}' does not accept content type '{}', accepted are: {}", path, content_type, accepted)))
+            },
+        }
+    }
+
+    #[inline]
+    fn apply(&self, path: &str, value: Option<&[u8]>, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        match self.check(path, value) {
+            Ok(content_type) => {
+                extensions.insert(content_type);
+                None
+            },
+            Err(status) => Some(status),
+        }
+    }
+
+    #[inline]
+    ///Returns layer, which also responds to non-gRPC requests with fallback response, if set
+    pub fn layer(&self) -> ContentTypeGateLayer {
+        ContentTypeGateLayer {
+            gate: self.clone(),
+            body: crate::DefaultBody,
+        }
+    }
+}
+
+impl Default for ContentTypeGate {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Interceptor for ContentTypeGate {
+    #[inline(always)]
+    fn on_request(&self, _: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+        None
+    }
+
+    #[inline]
+    fn on_request_with_uri(&self, uri: &http::Uri, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        self.apply(uri.path(), headers.get(http::header::CONTENT_TYPE.as_str()).map(|value| value.as_encoded_bytes()), extensions)
+    }
+
+    #[inline]
+    fn on_request_headers(&self, uri: &http::Uri, headers: &mut http::HeaderMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        self.apply(uri.path(), headers.get(http::header::CONTENT_TYPE).map(http::HeaderValue::as_bytes), extensions)
+    }
+
+    #[inline(always)]
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
+
+#[derive(Clone, Debug)]
+///Layer of `ContentTypeGate`
+pub struct ContentTypeGateLayer<B = crate::DefaultBody> {
+    gate: ContentTypeGate,
+    body: B,
+}
+
+impl<B> ContentTypeGateLayer<B> {
+    #[inline]
+    ///Sets factory of rejection response body
+    ///
+    ///See `InterceptorService::with_body`.
+    pub fn with_body<B2>(self, body: B2) -> ContentTypeGateLayer<B2> {
+        ContentTypeGateLayer {
+            gate: self.gate,
+            body,
+        }
+    }
+}
+
+impl<S, B: Clone> tower_layer::Layer<S> for ContentTypeGateLayer<B> {
+    type Service = ContentTypeGateService<S, B>;
+
+    #[inline(always)]
+    fn layer(&self, inner: S) -> Self::Service {
+        ContentTypeGateService {
+            fallback: self.gate.fallback.clone(),
+            inner: crate::InterceptorService::new(self.gate.clone(), inner).with_body(self.body.clone()),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+///Service of `ContentTypeGate`, responding to non-gRPC requests with plain HTTP response
+pub struct ContentTypeGateService<S, B = crate::DefaultBody> {
+    fallback: Option<Arc<PlainResponse>>,
+    inner: crate::InterceptorService<ContentTypeGate, S, crate::PropagateError, B>,
+}
+
+impl<S, B> ContentTypeGateService<S, B> {
+    #[inline]
+    ///Sets factory of rejection response body
+    ///
+    ///See `InterceptorService::with_body`.
+    pub fn with_body<B2>(self, body: B2) -> ContentTypeGateService<S, B2> {
+        ContentTypeGateService {
+            fallback: self.fallback,
+            inner: self.inner.with_body(body),
+        }
+    }
+}
+
+impl<S: tonic::server::NamedService, B> tonic::server::NamedService for ContentTypeGateService<S, B> {
+    const NAME: &'static str = S::NAME;
+}
+
+impl<ReqBody, ResBody, S: tower_service::Service<http::Request<ReqBody>, Response = http::Response<ResBody>>, B: crate::BodyFactory<ResBody> + Clone> tower_service::Service<http::Request<ReqBody>> for ContentTypeGateService<S, B> {
+    type Response = http::Response<ContentTypeGateBody<ResBody>>;
+    type Error = S::Error;
+    type Future = ContentTypeGateFut<crate::InterceptorFut<ContentTypeGate, S::Future, crate::PropagateError, B>, ResBody>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        tower_service::Service::<http::Request<ReqBody>>::poll_ready(&mut self.inner, cx)
+    }
+
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        if let Some(fallback) = self.fallback.as_ref() {
+            if !req.headers().get(http::header::CONTENT_TYPE).is_some_and(crate::is_grpc_content_type) {
+                return ContentTypeGateFut {
+                    plain: Some(fallback.to_response()),
+                    inner: None,
+                };
+            }
+        }
+
+        ContentTypeGateFut {
+            plain: None,
+            inner: Some(self.inner.call(req)),
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    ///Future of `ContentTypeGateService`
+    pub struct ContentTypeGateFut<F, B> {
+        plain: Option<http::Response<ContentTypeGateBody<B>>>,
+        #[pin]
+        inner: Option<F>,
+    }
+}
+
+impl<F, B> fmt::Debug for ContentTypeGateFut<F, B> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("ContentTypeGateFut").field("is_plain", &self.inner.is_none()).finish_non_exhaustive()
+    }
+}
+
+impl<B, E, F: Future<Output = Result<http::Response<B>, E>>> Future for ContentTypeGateFut<F, B> {
+    type Output = Result<http::Response<ContentTypeGateBody<B>>, E>;
+
+    fn poll(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let this = self.project();
+        match this.inner.as_pin_mut() {
+            Some(inner) => match Future::poll(inner, ctx) {
+                task::Poll::Ready(result) => task::Poll::Ready(result.map(|response| response.map(ContentTypeGateBody::inner))),
+                task::Poll::Pending => task::Poll::Pending,
+            },
+            None => task::Poll::Ready(Ok(this.plain.take().expect("Future polled after completion"))),
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    #[project = GateBodyProj]
+    enum GateBody<B> {
+        Inner {
+            #[pin]
+            body: compat::Incoming<B>,
+        },
+        Plain {
+            body: Option<bytes::Bytes>,
+        },
+    }
+}
+
+pin_project_lite::pin_project! {
+    ///Response body of `ContentTypeGateService`, which is either inner service's body or plain HTTP response
+    pub struct ContentTypeGateBody<B> {
+        #[pin]
+        inner: GateBody<B>,
+    }
+}
+
+impl<B> ContentTypeGateBody<B> {
+    #[inline(always)]
+    fn inner(body: B) -> Self {
+        Self {
+            inner: GateBody::Inner {
+                body: compat::Incoming::new(body),
+            },
+        }
+    }
+
+    #[inline(always)]
+    fn plain(body: bytes::Bytes) -> Self {
+        Self {
+            inner: GateBody::Plain {
+                body: Some(body).filter(|body| !body.is_empty()),
+            },
+        }
+    }
+
+    #[inline(always)]
+    ///Returns whether it is body of plain HTTP response
+    pub fn is_plain(&self) -> bool {
+        matches!(self.inner, GateBody::Plain { .. })
+    }
+}
+
+impl<B: Default> Default for ContentTypeGateBody<B> {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::inner(B::default())
+    }
+}
+
+impl<B> fmt::Debug for ContentTypeGateBody<B> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("ContentTypeGateBody").field("is_plain", &self.is_plain()).finish_non_exhaustive()
+    }
+}
+
+compat::impl_body! {
+    [B: http_body::Body<Data = bytes::Bytes>] ContentTypeGateBody<B> {
+        type Data = bytes::Bytes;
+        type Error = B::Error;
+
+        #[inline]
+        fn poll_data(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Option<Result<Self::Data, Self::Error>>> {
+            match self.project().inner.project() {
+                GateBodyProj::Inner { body } => compat::Body::poll_data(body, ctx),
+                GateBodyProj::Plain { body } => task::Poll::Ready(body.take().map(Ok)),
+            }
+        }
+
+        #[inline]
+        fn poll_trailers(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Result<Option<http::HeaderMap>, Self::Error>> {
+            match self.project().inner.project() {
+                GateBodyProj::Inner { body } => compat::Body::poll_trailers(body, ctx),
+                GateBodyProj::Plain { .. } => task::Poll::Ready(Ok(None)),
+            }
+        }
+
+        #[inline]
+        fn is_end_stream(&self) -> bool {
+            match &self.inner {
+                GateBody::Inner { body } => body.is_end_stream(),
+                GateBody::Plain { body } => body.is_none(),
+            }
+        }
+
+        #[inline]
+        fn size_hint(&self) -> http_body::SizeHint {
+            match &self.inner {
+                GateBody::Inner { body } => body.size_hint(),
+                GateBody::Plain { body } => http_body::SizeHint::with_exact(body.as_ref().map_or(0, |body| body.len() as u64)),
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Remap {
+    matcher: MethodMatcher,
+    from: tonic::Code,
+    to: tonic::Code,
+    message: Option<http::HeaderValue>,
+}
+
+impl Remap {
+    //Rewrites status of `headers`
+    fn apply(&self, headers: &mut http::HeaderMap) {
+        if self.from != self.to {
+            headers.insert(GRPC_STATUS_HEADER_CODE, http::HeaderValue::from(self.to as i32));
+            //Details describe original status
+            headers.remove(GRPC_STATUS_DETAILS_HEADER);
+        }
+        if let Some(message) = &self.message {
+            headers.insert(GRPC_STATUS_MESSAGE_HEADER, message.clone());
+        }
+    }
+}
+
+const GRPC_STATUS_HEADER_CODE: &str = "grpc-status";
+const GRPC_STATUS_MESSAGE_HEADER: &str = "grpc-message";
+const GRPC_STATUS_DETAILS_HEADER: &str = "grpc-status-details-bin";
+
+#[derive(Clone, Debug, Default)]
+///Server layer which remaps status codes of matching methods
+///
+///```rust
+///use tonic_interceptor::policy::RemapStatus;
+///use tonic::Code;
+///
+///let remap = RemapStatus::new()
+///    .rule("/pkg.Search/Query", Code::NotFound, Code::Ok)
+///    .rule_with_message("/pkg.Legacy/*", Code::Internal, Code::Unavailable, "temporarily unavailable");
+///```
+///
+///Status is rewritten both in response headers (trailers-only response) and in trailers.
+///When code changes, `grpc-status-details-bin` is removed as it describes original status.
+///Message is kept unless rule provides replacement.
+///
+///Rule is selected by the most specific matching method, with earlier rule winning among equally specific ones.
+///It is a layer rather than interceptor, because `on_response` has no access to method of call.
+pub struct RemapStatus {
+    rules: Arc<Vec<Remap>>,
+}
+
+impl RemapStatus {
+    #[inline(always)]
+    ///Creates new instance without rules
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn push(mut self, matcher: MethodMatcher, from: tonic::Code, to: tonic::Code, message: Option<http::HeaderValue>) -> Self {
+        Arc::make_mut(&mut self.rules).push(Remap {
+            matcher,
+            from,
+            to,
+            message,
+        });
+        self
+    }
+
+    #[inline]
+    ///Adds rule, remapping `from` code of methods matching `matcher` to `to` code
+    pub fn rule(self, matcher: impl Into<MethodMatcher>, from: tonic::Code, to: tonic::Code) -> Self {
+        self.push(matcher.into(), from, to, None)
+    }
+
+    #[inline]
+    ///Adds rule, remapping `from` code of methods matching `matcher` to `to` code with replacement `message`
+    pub fn rule_with_message(self, matcher: impl Into<MethodMatcher>, from: tonic::Code, to: tonic::Code, message: &str) -> Self {
+        self.push(matcher.into(), from, to, Some(crate::encode_grpc_message(message)))
+    }
+}
+
+impl<S> tower_layer::Layer<S> for RemapStatus {
+    type Service = RemapStatusService<S>;
+
+    #[inline(always)]
+    fn layer(&self, inner: S) -> Self::Service {
+        RemapStatusService {
+            rules: self.rules.clone(),
+            inner,
+        }
+    }
+}
+
+//Rules applicable to single call
+#[derive(Default)]
+struct Selected {
+    rules: Arc<Vec<Remap>>,
+    indexes: Vec<usize>,
+}
+
+impl Selected {
+    fn apply(&self, headers: &mut http::HeaderMap) {
+        if self.indexes.is_empty() {
+            return;
+        }
+        let code = match headers.get(GRPC_STATUS_HEADER_CODE) {
+            Some(code) => tonic::Code::from_bytes(code.as_bytes()),
+            None => return,
+        };
+
+        let mut result: Option<&Remap> = None;
+        for rule in self.indexes.iter().map(|idx| &self.rules[*idx]).filter(|rule| rule.from == code) {
+            match result {
+                Some(current) if current.matcher.specificity() >= rule.matcher.specificity() => (),
+                _ => result = Some(rule),
+            }
+        }
+        if let Some(rule) = result {
+            rule.apply(headers);
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+///Service of `RemapStatus`
+pub struct RemapStatusService<S> {
+    rules: Arc<Vec<Remap>>,
+    inner: S,
+}
+
+impl<S: tonic::server::NamedService> tonic::server::NamedService for RemapStatusService<S> {
+    const NAME: &'static str = S::NAME;
+}
+
+impl<ReqBody, ResBody, S: tower_service::Service<http::Request<ReqBody>, Response = http::Response<ResBody>>> tower_service::Service<http::Request<ReqBody>> for RemapStatusService<S> {
+    type Response = http::Response<RemapStatusBody<ResBody>>;
+    type Error = S::Error;
+    type Future = RemapStatusFut<S::Future>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        let path = req.uri().path();
+        let selected = Selected {
+            indexes: self.rules.iter().enumerate().filter(|(_, rule)| rule.matcher.matches(path)).map(|(idx, _)| idx).collect(),
+            rules: self.rules.clone(),
+        };
+        RemapStatusFut {
+            selected: Some(selected),
+            inner: self.inner.call(req),
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    ///Future of `RemapStatusService`
+    pub struct RemapStatusFut<F> {
+        selected: Option<Selected>,
+        #[pin]
+        inner: F,
+    }
+}
+
+impl<F> fmt::Debug for RemapStatusFut<F> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("RemapStatusFut").finish_non_exhaustive()
+    }
+}
+
+impl<ResBody, E, F: Future<Output = Result<http::Response<ResBody>, E>>> Future for RemapStatusFut<F> {
+    type Output = Result<http::Response<RemapStatusBody<ResBody>>, E>;
+
+    fn poll(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let this = self.project();
+        match Future::poll(this.inner, ctx) {
+            task::Poll::Ready(Ok(response)) => {
+                let selected = this.selected.take().expect("Future polled after completion");
+                let (mut parts, inner) = response.into_parts();
+                selected.apply(&mut parts.headers);
+                task::Poll::Ready(Ok(http::Response::from_parts(parts, RemapStatusBody {
+                    selected,
+                    inner: compat::Incoming::new(inner),
+                })))
+            },
+            task::Poll::Ready(Err(error)) => task::Poll::Ready(Err(error)),
+            task::Poll::Pending => task::Poll::Pending,
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    ///Response body of `RemapStatusService`, remapping status of trailers
+    pub struct RemapStatusBody<B> {
+        selected: Selected,
+        #[pin]
+        inner: compat::Incoming<B>,
+    }
+}
+
+impl<B: Default> Default for RemapStatusBody<B> {
+    #[inline(always)]
+    fn default() -> Self {
+        Self {
+            selected: Selected::default(),
+            inner: Default::default(),
+        }
+    }
+}
+
+impl<B> fmt::Debug for RemapStatusBody<B> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("RemapStatusBody").finish_non_exhaustive()
+    }
+}
+
+compat::impl_body! {
+    [B: http_body::Body] RemapStatusBody<B> {
+        type Data = B::Data;
+        type Error = B::Error;
+
+        #[inline(always)]
+        fn poll_data(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Option<Result<Self::Data, Self::Error>>> {
+            compat::Body::poll_data(self.project().inner, ctx)
+        }
+
+        fn poll_trailers(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Result<Option<http::HeaderMap>, Self::Error>> {
+            let this = self.project();
+
+            let mut result = compat::Body::poll_trailers(this.inner, ctx);
+            if let task::Poll::Ready(Ok(Some(trailers))) = &mut result {
+                this.selected.apply(trailers);
+            }
+            result
+        }
+
+        #[inline(always)]
+        fn is_end_stream(&self) -> bool {
+            self.inner.is_end_stream()
+        }
+
+        #[inline(always)]
+        fn size_hint(&self) -> http_body::SizeHint {
+            self.inner.size_hint()
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+mod de {
+    use super::{Rule, RequiredMetadata};
+
+    use core::fmt;
+    use serde::de;
+
+    struct RuleVisitor;
+
+    impl<'de> de::Visitor<'de> for RuleVisitor {
+        type Value = Rule;
+
+        fn expecting(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+            fmt.write_str("metadata rule")
+        }
+
+        fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+            const FIELDS: &[&str] = &["key", "required", "min_len", "max_len", "pattern"];
+
+            let mut key: Option<String> = None;
+            let mut required = None;
+            let mut min_len = None;
+            let mut max_len = None;
+            let mut pattern: Option<String> = None;
+
+            while let Some(field) = map.next_key::<String>()? {
+                match field.as_str() {
+                    "key" if key.is_none() => key = Some(map.next_value()?),
+                    "required" if required.is_none() => required = Some(map.next_value()?),
+                    "min_len" if min_len.is_none() => min_len = Some(map.next_value()?),
+                    "max_len" if max_len.is_none() => max_len = Some(map.next_value()?),
+                    "pattern" if pattern.is_none() => pattern = Some(map.next_value()?),
+                    "key" => return Err(de::Error::duplicate_field("key")),
+                    "required" => return Err(de::Error::duplicate_field("required")),
+                    "min_len" => return Err(de::Error::duplicate_field("min_len")),
+                    "max_len" => return Err(de::Error::duplicate_field("max_len")),
+                    "pattern" => return Err(de::Error::duplicate_field("pattern")),
+                    _ => return Err(de::Error::unknown_field(&field, FIELDS)),
+                }
+            }
+
+            let key = key.ok_or_else(|| de::Error::missing_field("key"))?;
+            let mut rule = Rule::new(key, required.unwrap_or(true));
+            rule.min_len = min_len;
+            rule.max_len = max_len;
+            match pattern {
+                Some(pattern) => {
+                    let key = rule.key.clone();
+                    rule.pattern(&pattern).map_err(|error| de::Error::custom(format_args!("metadata '{}': {}", key, error)))
+                },
+                None => Ok(rule),
+            }
+        }
+    }
+
+    impl<'de> de::Deserialize<'de> for Rule {
+        #[inline(always)]
+        fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            deserializer.deserialize_map(RuleVisitor)
+        }
+    }
+
+    impl<'de> de::Deserialize<'de> for RequiredMetadata {
+        #[inline(always)]
+        fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            Ok(Self {
+                rules: de::Deserialize::deserialize(deserializer)?,
+            })
+        }
+    }
+}
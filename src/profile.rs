@@ -0,0 +1,254 @@
+//! Interceptor overhead profiling
+//!
+//!`Profiled` measures wall time spent inside interceptor's request and response callbacks, reporting it to `ProfileSink`:
+//!
+//!```rust
+//!use tonic_interceptor::InterceptorChain;
+//!use tonic_interceptor::profile::ProfileStats;
+//!
+//!let stats = ProfileStats::new();
+//!let chain = InterceptorChain::new().profile(stats.clone());
+//!//Interceptors pushed into chain are profiled individually.
+//!//Later, find the slowest interceptor
+//!let slowest = stats.snapshot().into_iter().next();
+//!```
+//!
+//!Interceptor is labeled by its `Interceptor::name`.
+//!Profiling is opt-in: interceptors, which are not wrapped, have no overhead.
+
+use crate::{Interceptor, LazyMetadata};
+use crate::observe::MetricsSink;
+
+use core::fmt;
+use core::time::Duration;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+///Name of histogram reported by `ProfileMetrics`
+pub const INTERCEPTOR_SECONDS: &str = "grpc_server_interceptor_seconds";
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+///Stage of call
+pub enum Stage {
+    ///Any of request callbacks
+    Request,
+    ///Response callback
+    Response,
+}
+
+impl Stage {
+    #[inline(always)]
+    ///Returns label of stage
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Request => "request",
+            Self::Response => "response",
+        }
+    }
+}
+
+///Receiver of measurements
+pub trait ProfileSink {
+    ///Records time `elapsed` inside interceptor `name` at `stage`
+    fn record(&self, name: &'static str, stage: Stage, elapsed: Duration);
+}
+
+impl<T: ProfileSink + ?Sized> ProfileSink for &T {
+    #[inline(always)]
+    fn record(&self, name: &'static str, stage: Stage, elapsed: Duration) {
+        T::record(self, name, stage, elapsed)
+    }
+}
+
+impl<T: ProfileSink + ?Sized> ProfileSink for Box<T> {
+    #[inline(always)]
+    fn record(&self, name: &'static str, stage: Stage, elapsed: Duration) {
+        T::record(self, name, stage, elapsed)
+    }
+}
+
+impl<T: ProfileSink + ?Sized> ProfileSink for Arc<T> {
+    #[inline(always)]
+    fn record(&self, name: &'static str, stage: Stage, elapsed: Duration) {
+        T::record(self, name, stage, elapsed)
+    }
+}
+
+#[derive(Clone, Copy)]
+///Sink, which calls closure with every measurement
+pub struct ProfileFn<F>(pub F);
+
+impl<F> fmt::Debug for ProfileFn<F> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("ProfileFn").finish_non_exhaustive()
+    }
+}
+
+impl<F: Fn(&'static str, Stage, Duration)> ProfileSink for ProfileFn<F> {
+    #[inline(always)]
+    fn record(&self, name: &'static str, stage: Stage, elapsed: Duration) {
+        (self.0)(name, stage, elapsed)
+    }
+}
+
+#[derive(Clone, Debug)]
+///Sink, which reports measurements as `grpc_server_interceptor_seconds` histogram with `interceptor` and `stage` labels
+pub struct ProfileMetrics<M> {
+    sink: M,
+}
+
+impl<M: MetricsSink> ProfileMetrics<M> {
+    #[inline(always)]
+    ///Creates new instance
+    pub fn new(sink: M) -> Self {
+        Self {
+            sink,
+        }
+    }
+}
+
+impl<M: MetricsSink> ProfileSink for ProfileMetrics<M> {
+    #[inline]
+    fn record(&self, name: &'static str, stage: Stage, elapsed: Duration) {
+        self.sink.record_histogram(INTERCEPTOR_SECONDS, elapsed.as_secs_f64(), &[("interceptor", name), ("stage", stage.as_str())]);
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+///Aggregated measurements of single interceptor's stage
+pub struct StageStats {
+    ///Number of calls
+    pub count: u64,
+    ///Total time of calls
+    pub total: Duration,
+    ///Time of the slowest call
+    pub max: Duration,
+}
+
+#[derive(Clone, Default)]
+///Sink, which aggregates measurements in memory
+///
+///Clones share the same measurements.
+pub struct ProfileStats {
+    stats: Arc<Mutex<HashMap<(&'static str, Stage), StageStats>>>,
+}
+
+impl ProfileStats {
+    #[inline(always)]
+    ///Creates new instance without measurements
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    ///Returns measurements of interceptor `name` at `stage`
+    pub fn get(&self, name: &str, stage: Stage) -> Option<StageStats> {
+        let stats = self.stats.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+        stats.iter().find(|((stat_name, stat_stage), _)| *stat_name == name && *stat_stage == stage).map(|(_, stats)| *stats)
+    }
+
+    ///Returns every measurement, starting with the largest total time
+    pub fn snapshot(&self) -> Vec<(&'static str, Stage, StageStats)> {
+        let stats = self.stats.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+        let mut result: Vec<_> = stats.iter().map(|((name, stage), stats)| (*name, *stage, *stats)).collect();
+        result.sort_by(|left, right| right.2.total.cmp(&left.2.total).then_with(|| left.0.cmp(right.0)));
+        result
+    }
+
+    #[inline]
+    ///Removes every measurement
+    pub fn clear(&self) {
+        self.stats.lock().unwrap_or_else(std::sync::PoisonError::into_inner).clear();
+    }
+}
+
+impl fmt::Debug for ProfileStats {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_list().entries(self.snapshot()).finish()
+    }
+}
+
+impl ProfileSink for ProfileStats {
+    fn record(&self, name: &'static str, stage: Stage, elapsed: Duration) {
+        let mut stats = self.stats.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+        let stats = stats.entry((name, stage)).or_default();
+        stats.count += 1;
+        stats.total += elapsed;
+        stats.max = stats.max.max(elapsed);
+    }
+}
+
+#[derive(Clone, Debug)]
+///Interceptor, which measures time spent inside wrapped interceptor
+///
+///Name of wrapped interceptor is used as its own.
+pub struct Profiled<I, S> {
+    inner: I,
+    sink: S,
+}
+
+impl<I: Interceptor, S: ProfileSink> Profiled<I, S> {
+    #[inline(always)]
+    ///Creates new instance, reporting measurements of `inner` to `sink`
+    pub fn new(inner: I, sink: S) -> Self {
+        Self {
+            inner,
+            sink,
+        }
+    }
+
+    #[inline(always)]
+    ///Access wrapped interceptor
+    pub fn inner(&self) -> &I {
+        &self.inner
+    }
+
+    #[inline(always)]
+    fn measure<R>(&self, stage: Stage, fun: impl FnOnce(&I) -> R) -> R {
+        let start = Instant::now();
+        let result = fun(&self.inner);
+        self.sink.record(self.inner.name(), stage, start.elapsed());
+        result
+    }
+}
+
+impl<I: Interceptor, S: ProfileSink> Interceptor for Profiled<I, S> {
+    #[inline]
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        self.measure(Stage::Request, |inner| inner.on_request(headers, extensions))
+    }
+
+    #[inline]
+    fn on_request_with_uri(&self, uri: &http::Uri, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        self.measure(Stage::Request, |inner| inner.on_request_with_uri(uri, headers, extensions))
+    }
+
+    #[inline]
+    fn on_request_lazy(&self, uri: &http::Uri, headers: &mut LazyMetadata<'_>, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        self.measure(Stage::Request, |inner| inner.on_request_lazy(uri, headers, extensions))
+    }
+
+    #[inline]
+    fn on_request_headers(&self, uri: &http::Uri, headers: &mut http::HeaderMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        self.measure(Stage::Request, |inner| inner.on_request_headers(uri, headers, extensions))
+    }
+
+    #[inline]
+    fn on_response(&self, status: tonic::Code, headers: &mut http::HeaderMap, extensions: &http::Extensions) {
+        self.measure(Stage::Response, |inner| inner.on_response(status, headers, extensions))
+    }
+
+    #[inline(always)]
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    #[cfg(feature = "diagnostics")]
+    #[inline(always)]
+    fn on_poll_stats(&self, stats: crate::diagnostics::PollStats) {
+        self.inner.on_poll_stats(stats)
+    }
+}
@@ -0,0 +1,198 @@
+//! Propagation of incoming call's metadata to outgoing calls
+//!
+//!Server side `Capture` layer stores configured keys of incoming request into `PropagatedMetadata`,
+//!which is available as tokio task-local while handler runs.
+//!Client side `Inject` interceptor copies the same keys from it onto outgoing requests.
+//!
+//!Both sides are configured with the same `Keys`, so they can share single constant:
+//!
+//!```rust
+//!use tonic_interceptor::propagation::{Keys, Capture, Inject};
+//!
+//!const PROPAGATED: Keys = Keys::new(&["x-request-id", "x-tenant"]);
+//!
+//!let server_layer = Capture::new(PROPAGATED);
+//!let client_interceptor = Inject::new(PROPAGATED);
+//!```
+//!
+//!Note that task-local is not inherited by spawned tasks, use `PropagatedMetadata::scope` to carry it over explicitly.
+//!Outside of the scope (e.g. background jobs) `Inject` does nothing.
+
+use crate::client::ClientInterceptor;
+
+use core::task;
+use core::future::Future;
+use std::sync::Arc;
+
+tokio::task_local! {
+    static CURRENT: PropagatedMetadata;
+}
+
+#[derive(Copy, Clone, Debug)]
+///List of metadata keys to propagate
+///
+///Keys must be lower case, as required by HTTP/2.
+pub struct Keys(&'static [&'static str]);
+
+impl Keys {
+    #[inline(always)]
+    ///Creates new instance
+    pub const fn new(keys: &'static [&'static str]) -> Self {
+        Self(keys)
+    }
+
+    #[inline(always)]
+    ///Access keys
+    pub const fn as_slice(&self) -> &'static [&'static str] {
+        self.0
+    }
+}
+
+#[derive(Clone, Default, Debug)]
+///Metadata captured from incoming call
+pub struct PropagatedMetadata {
+    headers: Arc<http::HeaderMap>,
+}
+
+impl PropagatedMetadata {
+    ///Captures `keys` from `headers`
+    pub fn capture(keys: Keys, headers: &http::HeaderMap) -> Self {
+        let mut captured = http::HeaderMap::new();
+        for key in keys.as_slice() {
+            for value in headers.get_all(*key) {
+                if let Ok(name) = http::header::HeaderName::from_bytes(key.as_bytes()) {
+                    captured.append(name, value.clone());
+                }
+            }
+        }
+
+        Self {
+            headers: Arc::new(captured)
+        }
+    }
+
+    #[inline(always)]
+    ///Returns captured headers
+    pub fn headers(&self) -> &http::HeaderMap {
+        &self.headers
+    }
+
+    #[inline(always)]
+    ///Returns whether nothing is captured
+    pub fn is_empty(&self) -> bool {
+        self.headers.is_empty()
+    }
+
+    #[inline]
+    ///Returns metadata of current task, if any
+    pub fn current() -> Option<Self> {
+        CURRENT.try_with(Clone::clone).ok()
+    }
+
+    #[inline(always)]
+    ///Runs `fut` with `self` as current metadata
+    pub fn scope<F: Future>(self, fut: F) -> tokio::task::futures::TaskLocalFuture<Self, F> {
+        CURRENT.scope(self, fut)
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+///Server layer, capturing metadata of incoming calls
+pub struct Capture {
+    keys: Keys,
+}
+
+impl Capture {
+    #[inline(always)]
+    ///Creates new instance
+    pub const fn new(keys: Keys) -> Self {
+        Self {
+            keys
+        }
+    }
+}
+
+impl<S> tower_layer::Layer<S> for Capture {
+    type Service = CaptureService<S>;
+
+    #[inline(always)]
+    fn layer(&self, inner: S) -> Self::Service {
+        CaptureService {
+            keys: self.keys,
+            inner,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+///Server service, running inner service within `PropagatedMetadata` scope
+pub struct CaptureService<S> {
+    keys: Keys,
+    inner: S,
+}
+
+impl<ReqBody, S: tower_service::Service<http::Request<ReqBody>>> tower_service::Service<http::Request<ReqBody>> for CaptureService<S> {
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = tokio::task::futures::TaskLocalFuture<PropagatedMetadata, S::Future>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    #[inline]
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        let metadata = PropagatedMetadata::capture(self.keys, req.headers());
+        //Handler may use task-local during creation of its future too
+        let fut = CURRENT.sync_scope(metadata.clone(), || self.inner.call(req));
+        metadata.scope(fut)
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+///Client interceptor, injecting metadata of current task into outgoing requests
+///
+///Keys already present in outgoing request are left as it is.
+pub struct Inject {
+    keys: Keys,
+}
+
+impl Inject {
+    #[inline(always)]
+    ///Creates new instance
+    pub const fn new(keys: Keys) -> Self {
+        Self {
+            keys
+        }
+    }
+}
+
+impl ClientInterceptor for Inject {
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+        let _ = CURRENT.try_with(|metadata| {
+            if metadata.is_empty() {
+                return;
+            }
+
+            let mut result = crate::compat::metadata_into_headers(core::mem::take(headers));
+            for key in self.keys.as_slice() {
+                if result.contains_key(*key) {
+                    continue;
+                }
+
+                for value in metadata.headers().get_all(*key) {
+                    if let Ok(name) = http::header::HeaderName::from_bytes(key.as_bytes()) {
+                        result.append(name, value.clone());
+                    }
+                }
+            }
+            *headers = crate::compat::metadata_from_headers(result);
+        });
+        None
+    }
+
+    #[inline(always)]
+    fn on_response(&self, _: &tonic::metadata::MetadataMap, _: &http::Extensions) {
+    }
+}
@@ -0,0 +1,137 @@
+//! Minimal protobuf wire format support for well known `google.rpc` types
+
+use core::time::Duration;
+use core::convert::TryFrom;
+
+const WIRE_VARINT: u64 = 0;
+const WIRE_FIXED64: u64 = 1;
+const WIRE_LEN: u64 = 2;
+const WIRE_FIXED32: u64 = 5;
+
+const RETRY_INFO_TYPE: &str = "type.googleapis.com/google.rpc.RetryInfo";
+
+///Field value
+pub(crate) enum Value<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    //Fixed size values are only skipped
+    Fixed,
+}
+
+///Reader over encoded message's fields
+///
+///Stops on malformed input.
+pub(crate) struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    #[inline(always)]
+    pub(crate) fn new(buf: &'a [u8]) -> Self {
+        Self {
+            buf
+        }
+    }
+
+    fn varint(&mut self) -> Option<u64> {
+        let mut result = 0u64;
+        for idx in 0..10 {
+            let byte = *self.buf.get(idx)?;
+            result |= ((byte & 0x7f) as u64) << (idx * 7);
+            if byte & 0x80 == 0 {
+                self.buf = &self.buf[idx + 1..];
+                return Some(result);
+            }
+        }
+        None
+    }
+
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.buf.len() < len {
+            return None;
+        }
+        let (result, rest) = self.buf.split_at(len);
+        self.buf = rest;
+        Some(result)
+    }
+}
+
+impl<'a> Iterator for Reader<'a> {
+    type Item = (u64, Value<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.buf.is_empty() {
+            return None;
+        }
+
+        let key = self.varint()?;
+        let value = match key & 0x7 {
+            WIRE_VARINT => Value::Varint(self.varint()?),
+            WIRE_LEN => {
+                let len = self.varint()?;
+                Value::Bytes(self.bytes(usize::try_from(len).ok()?)?)
+            },
+            WIRE_FIXED64 => {
+                self.bytes(8)?;
+                Value::Fixed
+            },
+            WIRE_FIXED32 => {
+                self.bytes(4)?;
+                Value::Fixed
+            },
+            _ => {
+                self.buf = &[];
+                return None;
+            }
+        };
+
+        Some((key >> 3, value))
+    }
+}
+
+///Iterates over `google.rpc.Status` details, returning `(type_url, value)` of each `Any`
+pub(crate) fn status_details(status: &[u8]) -> impl Iterator<Item = (&str, &[u8])> {
+    Reader::new(status).filter_map(|(field, value)| match (field, value) {
+        (3, Value::Bytes(any)) => {
+            let mut type_url = "";
+            let mut value: &[u8] = &[];
+            for field in Reader::new(any) {
+                match field {
+                    (1, Value::Bytes(bytes)) => type_url = core::str::from_utf8(bytes).ok()?,
+                    (2, Value::Bytes(bytes)) => value = bytes,
+                    _ => (),
+                }
+            }
+            Some((type_url, value))
+        },
+        _ => None,
+    })
+}
+
+///Decodes `google.protobuf.Duration`, ignoring negative values
+pub(crate) fn duration(message: &[u8]) -> Option<Duration> {
+    let mut seconds = 0i64;
+    let mut nanos = 0i32;
+    for field in Reader::new(message) {
+        match field {
+            (1, Value::Varint(value)) => seconds = value as i64,
+            (2, Value::Varint(value)) => nanos = value as i32,
+            _ => (),
+        }
+    }
+
+    if seconds < 0 || nanos < 0 {
+        None
+    } else {
+        Some(Duration::new(seconds as u64, nanos as u32))
+    }
+}
+
+///Extracts `retry_delay` from `google.rpc.RetryInfo` within encoded `google.rpc.Status`
+pub(crate) fn retry_delay(status: &[u8]) -> Option<Duration> {
+    let (_, retry_info) = status_details(status).find(|(type_url, _)| *type_url == RETRY_INFO_TYPE)?;
+    Reader::new(retry_info).find_map(|field| match field {
+        (1, Value::Bytes(delay)) => duration(delay),
+        _ => None,
+    })
+}
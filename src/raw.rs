@@ -0,0 +1,102 @@
+//! Raw header interceptor
+//!
+//!Conversion between `http::HeaderMap` and `tonic::metadata::MetadataMap` is done twice per request.
+//!`RawInterceptor` operates on `http::HeaderMap` directly, avoiding it when wrapped into `Raw`.
+//!
+//!Helpers in this module provide metadata semantics for individual keys when necessary.
+
+use crate::{Interceptor, InterceptorLayer};
+
+use core::convert::TryFrom;
+
+///Interceptor operating on raw headers
+pub trait RawInterceptor {
+    ///Callback on incoming request, allowing you to modify headers or extensions
+    ///
+    ///Returning status will preempt request handling and immediately returns status
+    fn on_request(&self, headers: &mut http::HeaderMap, extensions: &mut http::Extensions) -> Option<tonic::Status>;
+
+    ///Callback when response is being returned
+    fn on_response(&self, status: tonic::Code, headers: &mut http::HeaderMap, extensions: &http::Extensions);
+}
+
+impl<I: RawInterceptor> RawInterceptor for std::sync::Arc<I> {
+    #[inline(always)]
+    fn on_request(&self, headers: &mut http::HeaderMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        RawInterceptor::on_request(self.as_ref(), headers, extensions)
+    }
+
+    #[inline(always)]
+    fn on_response(&self, status: tonic::Code, headers: &mut http::HeaderMap, extensions: &http::Extensions) {
+        RawInterceptor::on_response(self.as_ref(), status, headers, extensions)
+    }
+}
+
+#[derive(Clone, Copy, Default, Debug)]
+#[repr(transparent)]
+///Adapter of `RawInterceptor` to `Interceptor`, which skips metadata conversion in `InterceptorService`
+pub struct Raw<I>(pub I);
+
+impl<I: RawInterceptor> Interceptor for Raw<I> {
+    #[inline]
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        let mut raw = crate::compat::metadata_into_headers(core::mem::take(headers));
+        let result = self.0.on_request(&mut raw, extensions);
+        *headers = crate::compat::metadata_from_headers(raw);
+        result
+    }
+
+    #[inline(always)]
+    fn on_request_headers(&self, _: &http::Uri, headers: &mut http::HeaderMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        self.0.on_request(headers, extensions)
+    }
+
+    #[inline(always)]
+    fn on_response(&self, status: tonic::Code, headers: &mut http::HeaderMap, extensions: &http::Extensions) {
+        self.0.on_response(status, headers, extensions)
+    }
+}
+
+#[inline(always)]
+///Creates interceptor layer for `RawInterceptor`
+pub fn raw_interceptor<I: RawInterceptor>(interceptor: I) -> InterceptorLayer<Raw<I>> {
+    crate::interceptor(Raw(interceptor))
+}
+
+#[inline]
+///Runs `fun` with `headers` converted into `MetadataMap`
+pub fn with_metadata<R, F: FnOnce(&mut tonic::metadata::MetadataMap) -> R>(headers: &mut http::HeaderMap, fun: F) -> R {
+    let mut metadata = crate::compat::metadata_from_headers(core::mem::take(headers));
+    let result = fun(&mut metadata);
+    *headers = crate::compat::metadata_into_headers(metadata);
+    result
+}
+
+#[inline]
+///Gets ASCII metadata value of `key`, if it is valid ASCII metadata
+pub fn get_ascii(headers: &http::HeaderMap, key: &str) -> Option<tonic::metadata::AsciiMetadataValue> {
+    if key.ends_with("-bin") {
+        return None;
+    }
+    headers.get(key).and_then(|value| tonic::metadata::AsciiMetadataValue::try_from(value.as_bytes()).ok())
+}
+
+#[inline]
+///Gets decoded value of binary metadata `key`, if it is valid base64
+pub fn get_bin(headers: &http::HeaderMap, key: &str) -> Option<bytes::Bytes> {
+    if !key.ends_with("-bin") {
+        return None;
+    }
+    headers.get(key).and_then(|value| crate::base64_decode(value.as_bytes())).map(bytes::Bytes::from)
+}
+
+#[inline]
+///Inserts binary metadata `key`, encoding `value` as base64.
+///
+///Panics if `key` is not valid binary metadata key.
+pub fn insert_bin(headers: &mut http::HeaderMap, key: &'static str, value: &[u8]) {
+    assert!(key.ends_with("-bin"), "binary metadata key must end with -bin");
+    let value = tonic::metadata::BinaryMetadataValue::from_bytes(value);
+    let value = http::HeaderValue::from_bytes(value.as_encoded_bytes()).expect("base64 is valid header value");
+    headers.insert(key, value);
+}
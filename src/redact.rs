@@ -0,0 +1,164 @@
+//! Metadata redaction
+//!
+//!`Redactor` produces printable snapshot of metadata with secrets masked, for use by logging and audit interceptors:
+//!
+//!```rust
+//!use tonic_interceptor::redact::Redactor;
+//!
+//!let redactor = Redactor::new()
+//!    .key("authorization")
+//!    .prefix("x-secret-")
+//!    .pattern("[A-Za-z0-9_-]{8,}[.][A-Za-z0-9_-]{8,}[.][A-Za-z0-9_-]{8,}").expect("valid pattern")
+//!    .keep(2);
+//!
+//!let mut metadata = tonic::metadata::MetadataMap::new();
+//!metadata.insert("authorization", "Bearer 0123456789".parse().unwrap());
+//!assert_eq!(redactor.display(&metadata).to_string(), "authorization: Be***89");
+//!```
+//!
+//!Value is masked when its key is configured, starts with configured prefix or any part of value matches configured regular expression.
+//!Binary values are printed base64 encoded, as they are sent.
+
+use regex::bytes::Regex;
+
+use core::fmt;
+use std::borrow::Cow;
+use std::collections::HashSet;
+
+///Replacement of masked value
+pub const MASK: &str = "***";
+
+#[derive(Clone, Debug, Default)]
+///Masks secrets of metadata
+pub struct Redactor {
+    keys: HashSet<String>,
+    prefixes: Vec<String>,
+    patterns: Vec<Regex>,
+    keep: usize,
+}
+
+impl Redactor {
+    #[inline(always)]
+    ///Creates new instance, which masks nothing
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    ///Masks value of `key`
+    pub fn key(mut self, key: impl Into<String>) -> Self {
+        self.keys.insert(key.into().to_ascii_lowercase());
+        self
+    }
+
+    #[inline]
+    ///Masks values of keys starting with `prefix`
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefixes.push(prefix.into().to_ascii_lowercase());
+        self
+    }
+
+    #[inline]
+    ///Masks values containing match of regular expression `pattern`
+    pub fn pattern(mut self, pattern: &str) -> Result<Self, regex::Error> {
+        self.patterns.push(Regex::new(pattern)?);
+        Ok(self)
+    }
+
+    #[inline]
+    ///Keeps first and last `count` characters of masked value
+    ///
+    ///They are kept only when at least half of value is masked, otherwise value is masked whole.
+    pub fn keep(mut self, count: usize) -> Self {
+        self.keep = count;
+        self
+    }
+
+    #[inline]
+    ///Returns whether value of `key` is masked regardless of value
+    pub fn is_secret_key(&self, key: &str) -> bool {
+        self.keys.contains(key) || self.prefixes.iter().any(|prefix| key.starts_with(prefix.as_str()))
+    }
+
+    ///Returns printable `value` of `key`, masked if necessary
+    pub fn redact<'a>(&self, key: &str, value: &'a str) -> Cow<'a, str> {
+        if !self.is_secret_key(key) && !self.patterns.iter().any(|pattern| pattern.is_match(value.as_bytes())) {
+            return Cow::Borrowed(value);
+        }
+
+        let len = value.chars().count();
+        match self.keep > 0 && len >= self.keep * 4 {
+            true => {
+                let head = value.chars().take(self.keep);
+                let tail = value.chars().skip(len - self.keep);
+                Cow::Owned(head.chain(MASK.chars()).chain(tail).collect())
+            },
+            false => Cow::Borrowed(MASK),
+        }
+    }
+
+    ///Returns printable snapshot of `metadata`, in order of its entries
+    pub fn snapshot(&self, metadata: &tonic::metadata::MetadataMap) -> Vec<(String, String)> {
+        let mut result = Vec::with_capacity(metadata.len());
+        for_each(metadata, |key, value| result.push((key.to_owned(), self.redact(key, value).into_owned())));
+        result
+    }
+
+    ///Writes printable snapshot of `metadata` as `key: value` pairs separated by `, `
+    pub fn write<W: fmt::Write>(&self, metadata: &tonic::metadata::MetadataMap, out: &mut W) -> fmt::Result {
+        let mut result = Ok(());
+        let mut first = true;
+        for_each(metadata, |key, value| {
+            if result.is_err() {
+                return;
+            }
+            if !first {
+                result = out.write_str(", ");
+            }
+            first = false;
+            result = result.and_then(|_| write!(out, "{}: {}", key, self.redact(key, value)));
+        });
+        result
+    }
+
+    #[inline(always)]
+    ///Returns `Display` of `metadata` snapshot, see `write`
+    pub fn display<'a>(&'a self, metadata: &'a tonic::metadata::MetadataMap) -> Redacted<'a> {
+        Redacted {
+            redactor: self,
+            metadata,
+        }
+    }
+}
+
+//Calls `fun` with every entry of `metadata` as string
+fn for_each<F: FnMut(&str, &str)>(metadata: &tonic::metadata::MetadataMap, mut fun: F) {
+    for entry in metadata.iter() {
+        let (key, value) = match entry {
+            tonic::metadata::KeyAndValueRef::Ascii(key, value) => (key.as_str(), value.as_encoded_bytes()),
+            tonic::metadata::KeyAndValueRef::Binary(key, value) => (key.as_str(), value.as_encoded_bytes()),
+        };
+        fun(key, &String::from_utf8_lossy(value));
+    }
+}
+
+#[derive(Clone, Copy)]
+///Printable snapshot of metadata, created by `Redactor::display`
+pub struct Redacted<'a> {
+    redactor: &'a Redactor,
+    metadata: &'a tonic::metadata::MetadataMap,
+}
+
+impl fmt::Display for Redacted<'_> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.redactor.write(self.metadata, fmt)
+    }
+}
+
+impl fmt::Debug for Redacted<'_> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, fmt)
+    }
+}
@@ -0,0 +1,72 @@
+//! Rejection constructors
+//!
+//!Helpers to create `tonic::Status` for common rejections, with metadata expected by clients.
+//!Status metadata is sent along with rejection response of `InterceptorService`.
+//!
+//!When `details` feature is enabled, statuses also carry corresponding standard error details.
+
+use core::convert::TryFrom;
+use core::time::Duration;
+
+///Metadata key of authentication challenge
+pub const WWW_AUTHENTICATE: &str = "www-authenticate";
+///Metadata key of delay in seconds, after which request can be retried
+pub const RETRY_AFTER: &str = "retry-after";
+
+//Escapes `value` as HTTP quoted-string content
+fn quote(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    for ch in value.chars() {
+        if ch == '"' || ch == '\\' {
+            result.push('\\');
+        }
+        result.push(ch);
+    }
+    result
+}
+
+///Creates `Unauthenticated` status with `www-authenticate: Bearer realm="<realm>"` challenge
+///
+///Challenge is omitted if `realm` contains characters that are not allowed in metadata.
+pub fn unauthenticated_with(realm: &str) -> tonic::Status {
+    let mut status = tonic::Status::unauthenticated("authentication required");
+    let challenge = format!("Bearer realm=\"{}\"", quote(realm));
+    if let Ok(challenge) = tonic::metadata::AsciiMetadataValue::try_from(challenge) {
+        status.metadata_mut().insert(WWW_AUTHENTICATE, challenge);
+    }
+    status
+}
+
+///Creates `ResourceExhausted` status with `retry-after` in whole seconds, rounded up
+///
+///With `details` feature, it also carries `RetryInfo` with exact delay.
+pub fn rate_limited(retry_after: Duration) -> tonic::Status {
+    rate_limited_with("rate limit exceeded", retry_after)
+}
+
+///Creates `ResourceExhausted` status with custom `message`, same as `rate_limited`
+pub fn rate_limited_with(message: impl Into<String>, retry_after: Duration) -> tonic::Status {
+    #[cfg(feature = "details")]
+    let mut status = crate::details::StatusDetails::new().retry_info(retry_after).into_status(tonic::Code::ResourceExhausted, message);
+    #[cfg(not(feature = "details"))]
+    let mut status = tonic::Status::resource_exhausted(message);
+
+    let mut seconds = retry_after.as_secs();
+    if retry_after.subsec_nanos() > 0 {
+        seconds = seconds.saturating_add(1);
+    }
+    status.metadata_mut().insert(RETRY_AFTER, tonic::metadata::AsciiMetadataValue::from(seconds));
+    status
+}
+
+///Creates `FailedPrecondition` status for `field`, which doesn't satisfy precondition described by `description`
+///
+///With `details` feature, it also carries `PreconditionFailure` with violation of type `FIELD` and `field` as subject.
+pub fn precondition(field: &str, description: &str) -> tonic::Status {
+    let message = format!("precondition failed for '{}': {}", field, description);
+
+    #[cfg(feature = "details")]
+    return crate::details::StatusDetails::new().precondition_failure("FIELD", field, description).into_status(tonic::Code::FailedPrecondition, message);
+    #[cfg(not(feature = "details"))]
+    return tonic::Status::failed_precondition(message);
+}
@@ -0,0 +1,156 @@
+//! Hot reload of interceptors
+//!
+//!`Watched` rebuilds interceptor out of configuration, whenever new one is sent through `tokio::sync::watch` channel:
+//!
+//!```rust
+//!use tonic_interceptor::reload::{FromConfig, Watched};
+//!
+//!struct MaxEntries(usize);
+//!
+//!impl FromConfig<usize> for MaxEntries {
+//!    type Error = &'static str;
+//!
+//!    fn from_config(config: &usize) -> Result<Self, Self::Error> {
+//!        match *config {
+//!            0 => Err("limit must be positive"),
+//!            limit => Ok(MaxEntries(limit)),
+//!        }
+//!    }
+//!}
+//!
+//!# impl tonic_interceptor::Interceptor for MaxEntries {
+//!#     fn on_request(&self, _: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<tonic::Status> { None }
+//!#     fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {}
+//!# }
+//!let (sender, receiver) = tokio::sync::watch::channel(16);
+//!let interceptor = Watched::<usize, MaxEntries>::new(receiver).expect("valid config");
+//!//Config file watcher pushes update, which is used by the next request
+//!sender.send(32).expect("interceptor is alive");
+//!```
+//!
+//!Change is detected on request, hence there is no background task.
+//!Invalid configuration is reported to `on_invalid` hook, while previous interceptor keeps serving requests.
+//!Built-in interceptors of `config` module are built out of `config::InterceptorConfig`, and whole chain out of `config::ChainConfig`.
+
+use crate::{Interceptor, LazyMetadata};
+
+use core::fmt;
+use std::sync::{Arc, Mutex};
+
+///Interceptor which can be built out of configuration `C`
+pub trait FromConfig<C>: Sized {
+    ///Error of invalid configuration
+    type Error;
+
+    ///Creates interceptor out of `config`
+    fn from_config(config: &C) -> Result<Self, Self::Error>;
+}
+
+struct State<C, I> {
+    receiver: tokio::sync::watch::Receiver<C>,
+    current: Arc<I>,
+}
+
+///Interceptor, which is rebuilt on every change of its configuration
+///
+///Rebuilt interceptor starts with fresh state (e.g. rate limit window).
+///Response is passed to interceptor, which is current at the time of response.
+///
+///Clones share the same interceptor.
+pub struct Watched<C, I: FromConfig<C>> {
+    state: Arc<Mutex<State<C, I>>>,
+    on_invalid: fn(&I::Error),
+}
+
+impl<C, I: FromConfig<C>> Watched<C, I> {
+    ///Creates new instance out of current configuration of `receiver`
+    pub fn new(mut receiver: tokio::sync::watch::Receiver<C>) -> Result<Self, I::Error> {
+        let current = I::from_config(&receiver.borrow_and_update())?;
+        Ok(Self {
+            state: Arc::new(Mutex::new(State {
+                receiver,
+                current: Arc::new(current),
+            })),
+            on_invalid: |_| {},
+        })
+    }
+
+    #[inline]
+    ///Sets hook, which is called with error of invalid configuration
+    pub fn on_invalid(mut self, on_invalid: fn(&I::Error)) -> Self {
+        self.on_invalid = on_invalid;
+        self
+    }
+
+    ///Returns interceptor built out of latest valid configuration
+    ///
+    ///Interceptor is rebuilt if configuration changed since last call.
+    pub fn current(&self) -> Arc<I> {
+        let mut state = self.state.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+        //Closed channel reports error instead of change, even if its last configuration is not seen yet
+        let changed = state.receiver.has_changed().unwrap_or_else(|_| state.receiver.borrow().has_changed());
+        if changed {
+            let result = I::from_config(&state.receiver.borrow_and_update());
+            match result {
+                Ok(current) => state.current = Arc::new(current),
+                Err(error) => (self.on_invalid)(&error),
+            }
+        }
+        state.current.clone()
+    }
+}
+
+impl<C, I: FromConfig<C>> Clone for Watched<C, I> {
+    #[inline(always)]
+    fn clone(&self) -> Self {
+        Self {
+            state: self.state.clone(),
+            on_invalid: self.on_invalid,
+        }
+    }
+}
+
+impl<C, I: FromConfig<C>> fmt::Debug for Watched<C, I> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("Watched").finish_non_exhaustive()
+    }
+}
+
+impl<C, I: FromConfig<C> + Interceptor> Interceptor for Watched<C, I> {
+    #[inline]
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        self.current().on_request(headers, extensions)
+    }
+
+    #[inline]
+    fn on_request_with_uri(&self, uri: &http::Uri, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        self.current().on_request_with_uri(uri, headers, extensions)
+    }
+
+    #[inline]
+    fn on_request_lazy(&self, uri: &http::Uri, headers: &mut LazyMetadata<'_>, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        self.current().on_request_lazy(uri, headers, extensions)
+    }
+
+    #[inline]
+    fn on_request_headers(&self, uri: &http::Uri, headers: &mut http::HeaderMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        self.current().on_request_headers(uri, headers, extensions)
+    }
+
+    #[inline]
+    fn on_response(&self, status: tonic::Code, headers: &mut http::HeaderMap, extensions: &http::Extensions) {
+        self.current().on_response(status, headers, extensions)
+    }
+
+    #[inline(always)]
+    fn name(&self) -> &'static str {
+        self.current().name()
+    }
+
+    #[cfg(feature = "diagnostics")]
+    #[inline(always)]
+    fn on_poll_stats(&self, stats: crate::diagnostics::PollStats) {
+        self.current().on_poll_stats(stats)
+    }
+}
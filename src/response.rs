@@ -0,0 +1,110 @@
+//! Construction of trailers-only responses
+//!
+//!Same construction is used by every rejection of this crate, which allows custom layers to reply consistently:
+//!
+//!```rust
+//!use tonic_interceptor::response::{status_to_response, ResponseTemplate};
+//!
+//!let request = http::Request::builder().header("content-type", "application/grpc+proto").body(()).unwrap();
+//!let template = ResponseTemplate::from_request(&request).header(http::header::HeaderName::from_static("x-served-by"), http::HeaderValue::from_static("edge"));
+//!let response: http::Response<()> = status_to_response(&tonic::Status::unavailable("draining"), &template);
+//!assert_eq!(response.headers()["content-type"], "application/grpc+proto");
+//!assert_eq!(response.headers()["grpc-status"], "14");
+//!```
+
+use crate::{EmptyBody, GRPC_CONTENT_TYPE, is_grpc_content_type, add_status_headers};
+
+#[derive(Clone, Debug)]
+///Parameters of trailers-only response
+pub struct ResponseTemplate {
+    content_type: Option<http::HeaderValue>,
+    version: http::Version,
+    headers: http::HeaderMap,
+}
+
+impl ResponseTemplate {
+    #[inline]
+    ///Creates new instance with `application/grpc` content type and default HTTP version
+    pub fn new() -> Self {
+        Self {
+            content_type: None,
+            version: http::Version::default(),
+            headers: http::HeaderMap::new(),
+        }
+    }
+
+    ///Creates new instance replying to `request`
+    ///
+    ///Content type of request is reused if it is gRPC or gRPC-Web one, as well as its HTTP version.
+    pub fn from_request<B>(request: &http::Request<B>) -> Self {
+        Self::from_parts(request.headers(), request.version())
+    }
+
+    pub(crate) fn from_parts(headers: &http::HeaderMap, version: http::Version) -> Self {
+        Self {
+            content_type: headers.get(http::header::CONTENT_TYPE).filter(|value| is_grpc_content_type(value)).cloned(),
+            version,
+            headers: http::HeaderMap::new(),
+        }
+    }
+
+    #[inline]
+    ///Sets content type
+    pub fn content_type(mut self, content_type: http::HeaderValue) -> Self {
+        self.content_type = Some(content_type);
+        self
+    }
+
+    #[inline]
+    ///Sets HTTP version
+    pub fn version(mut self, version: http::Version) -> Self {
+        self.version = version;
+        self
+    }
+
+    #[inline]
+    ///Adds extra header
+    ///
+    ///Extra headers never override content type or status headers.
+    pub fn header(mut self, name: http::header::HeaderName, value: http::HeaderValue) -> Self {
+        self.headers.append(name, value);
+        self
+    }
+
+    #[inline]
+    ///Adds extra headers
+    pub fn headers(mut self, headers: http::HeaderMap) -> Self {
+        for (name, value) in headers.iter() {
+            self.headers.append(name, value.clone());
+        }
+        self
+    }
+}
+
+impl Default for ResponseTemplate {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[inline]
+///Creates trailers-only response with `status`, using empty body
+///
+///Unlike `tonic::Status::to_http`, it preserves content type and HTTP version of template,
+///and always percent-encodes `%` of message.
+///Status metadata and details are written same as by tonic.
+pub fn status_to_response<B: EmptyBody>(status: &tonic::Status, template: &ResponseTemplate) -> http::Response<B> {
+    status_to_response_with(status, template, B::empty())
+}
+
+///Creates trailers-only response with `status`, using provided `body`
+pub fn status_to_response_with<B>(status: &tonic::Status, template: &ResponseTemplate, body: B) -> http::Response<B> {
+    let (mut parts, body) = http::Response::new(body).into_parts();
+    parts.version = template.version;
+    parts.headers = template.headers.clone();
+    let content_type = template.content_type.clone().unwrap_or_else(|| http::HeaderValue::from_static(GRPC_CONTENT_TYPE));
+    parts.headers.insert(http::header::CONTENT_TYPE, content_type);
+    add_status_headers(status, &mut parts.headers);
+    http::Response::from_parts(parts, body)
+}
@@ -0,0 +1,231 @@
+//! Canary routing
+//!
+//!Mesh routes call to canary deployment when it carries `x-canary: always`, and to stable one on `x-canary: never`.
+//!`Canary` assigns variant by hash of stable per-caller key, so that the same caller consistently hits the same variant:
+//!
+//!```rust
+//!use tonic_interceptor::routing::Canary;
+//!
+//!let canary = Canary::new(5).key("x-user-id");
+//!//Edge service stamps its responses
+//!let server = canary.clone().server();
+//!//Client stamps its requests
+//!let client = canary.client();
+//!```
+//!
+//!Hash is stable across processes and versions of the crate, hence every service using the same configuration agrees on variant.
+//!Calls without key are left unstamped.
+
+use crate::Interceptor;
+use crate::client::ClientInterceptor;
+use crate::headers::Echoed;
+
+use std::sync::Arc;
+
+///Default header
+pub const CANARY_HEADER: &str = "x-canary";
+
+const ALWAYS: &str = "always";
+const NEVER: &str = "never";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+///Variant of deployment
+pub enum Variant {
+    ///Canary deployment, stamped as `always`
+    Canary,
+    ///Stable deployment, stamped as `never`
+    Stable,
+}
+
+impl Variant {
+    #[inline(always)]
+    ///Returns header value of variant
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Canary => ALWAYS,
+            Self::Stable => NEVER,
+        }
+    }
+}
+
+type Extractor = dyn Fn(&tonic::metadata::MetadataMap) -> Option<String> + Send + Sync;
+
+#[derive(Clone)]
+enum Key {
+    Metadata(&'static str),
+    Custom(Arc<Extractor>),
+}
+
+#[derive(Clone)]
+///Canary assignment, shared by server and client interceptors
+pub struct Canary {
+    header: &'static str,
+    percent: u8,
+    key: Option<Key>,
+}
+
+impl Canary {
+    #[inline]
+    ///Creates new instance, assigning `percent` of keys to canary
+    ///
+    ///Percentage above 100 is treated as 100.
+    ///Without key extractor every call is left unstamped.
+    pub fn new(percent: u8) -> Self {
+        Self {
+            header: CANARY_HEADER,
+            percent: percent.min(100),
+            key: None,
+        }
+    }
+
+    #[inline]
+    ///Sets header to stamp, instead of `x-canary`
+    ///
+    ///Header must be lower case, as required by HTTP/2.
+    pub fn header(mut self, header: &'static str) -> Self {
+        self.header = header;
+        self
+    }
+
+    #[inline]
+    ///Uses value of metadata `key` as caller's key
+    pub fn key(mut self, key: &'static str) -> Self {
+        self.key = Some(Key::Metadata(key));
+        self
+    }
+
+    #[inline]
+    ///Uses `extractor` to get caller's key
+    pub fn key_with<F: Fn(&tonic::metadata::MetadataMap) -> Option<String> + Send + Sync + 'static>(mut self, extractor: F) -> Self {
+        self.key = Some(Key::Custom(Arc::new(extractor)));
+        self
+    }
+
+    #[inline(always)]
+    ///Returns percentage of canary
+    pub fn percent(&self) -> u8 {
+        self.percent
+    }
+
+    ///Returns variant of `key`
+    pub fn variant(&self, key: &[u8]) -> Variant {
+        match bucket(key) < u64::from(self.percent) {
+            true => Variant::Canary,
+            false => Variant::Stable,
+        }
+    }
+
+    ///Returns variant of call with `headers`, if key is present
+    pub fn variant_of(&self, headers: &tonic::metadata::MetadataMap) -> Option<Variant> {
+        match self.key.as_ref()? {
+            Key::Metadata(key) => headers.get(*key).map(|value| self.variant(value.as_bytes())),
+            Key::Custom(extractor) => extractor(headers).map(|key| self.variant(key.as_bytes())),
+        }
+    }
+
+    #[inline(always)]
+    ///Creates server interceptor, which stamps responses
+    pub fn server(self) -> CanaryServer {
+        CanaryServer {
+            canary: self,
+        }
+    }
+
+    #[inline(always)]
+    ///Creates client interceptor, which stamps requests
+    pub fn client(self) -> CanaryClient {
+        CanaryClient {
+            canary: self,
+        }
+    }
+}
+
+impl core::fmt::Debug for Canary {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("Canary").field("header", &self.header).field("percent", &self.percent).finish_non_exhaustive()
+    }
+}
+
+//Returns bucket of `key` in range `0..100`
+//
+//FNV-1a with final avalanche, as FNV alone distributes similar keys poorly in its low bits.
+fn bucket(key: &[u8]) -> u64 {
+    let mut hash = 0xcbf29ce484222325u64;
+    for byte in key {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51afd7ed558ccd);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xc4ceb9fe1a85ec53);
+    hash ^= hash >> 33;
+    hash % 100
+}
+
+#[derive(Clone, Debug)]
+///Server interceptor which stamps response with variant of caller
+///
+///Stamp is written via `Echoed`, hence it is present on rejection responses too.
+pub struct CanaryServer {
+    canary: Canary,
+}
+
+impl CanaryServer {
+    #[inline(always)]
+    ///Access configuration
+    pub fn canary(&self) -> &Canary {
+        &self.canary
+    }
+}
+
+impl Interceptor for CanaryServer {
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        if let Some(variant) = self.canary.variant_of(headers) {
+            if let Ok(header) = http::header::HeaderName::from_bytes(self.canary.header.as_bytes()) {
+                let mut echoed = extensions.remove::<Echoed>().unwrap_or_default();
+                echoed.append(header, http::HeaderValue::from_static(variant.as_str()));
+                extensions.insert(echoed);
+            }
+        }
+        None
+    }
+
+    #[inline(always)]
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
+
+#[derive(Clone, Debug)]
+///Client interceptor which stamps request with variant of caller
+///
+///Requests, which already carry header, are sent as they are.
+pub struct CanaryClient {
+    canary: Canary,
+}
+
+impl CanaryClient {
+    #[inline(always)]
+    ///Access configuration
+    pub fn canary(&self) -> &Canary {
+        &self.canary
+    }
+}
+
+impl ClientInterceptor for CanaryClient {
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+        if headers.contains_key(self.canary.header) {
+            return None;
+        }
+        if let Some(variant) = self.canary.variant_of(headers) {
+            if let Ok(key) = tonic::metadata::AsciiMetadataKey::from_bytes(self.canary.header.as_bytes()) {
+                headers.insert(key, tonic::metadata::AsciiMetadataValue::from_static(variant.as_str()));
+            }
+        }
+        None
+    }
+
+    #[inline(always)]
+    fn on_response(&self, _: &tonic::metadata::MetadataMap, _: &http::Extensions) {
+    }
+}
@@ -0,0 +1,806 @@
+//! Traffic mirroring
+//!
+//!`Mirror` copies sampled requests to secondary service, e.g. rewritten backend before cut over,
+//!while responses of secondary service are only compared against primary one:
+//!
+//!```rust,ignore
+//!use tonic_interceptor::shadow::Mirror;
+//!
+//!use core::time::Duration;
+//!
+//!let channel = tonic::transport::Channel::from_static("http://shadow:50051").connect_lazy();
+//!let mirror = Mirror::new(channel, 10).method("/pkg.Users/*").timeout(Duration::from_secs(2)).on_mismatch(|mismatch| {
+//!    eprintln!("{}: primary={:?} shadow={:?}", mismatch.method, mismatch.primary, mismatch.shadow);
+//!});
+//!
+//!tonic::transport::Server::builder().layer(mirror.layer());
+//!```
+//!
+//!`Compare` mirrors requests the same way, but reports outcome of every mirrored call, including whether response bodies are equal:
+//!
+//!```rust,ignore
+//!use tonic_interceptor::shadow::Compare;
+//!
+//!let (sender, mut reports) = tokio::sync::mpsc::channel(1024);
+//!let compare = Compare::new(channel, 10).budget(4 * 1024 * 1024).report_to(sender);
+//!
+//!tonic::transport::Server::builder().layer(compare.layer());
+//!```
+//!
+//!Copy is sent only after primary service read whole request body, which must fit within size limit,
+//!hence in practice only unary and small client streaming calls are mirrored.
+//!Copy is fired on spawned task with its own timeout, so that it never affects latency or outcome of primary call.
+//!Timeout is measured by `Timer`, which is tokio's one by default.
+//!Outside of tokio runtime nothing is mirrored.
+
+use crate::matcher::MethodMatcher;
+use crate::timer::{Timer, TokioTimer};
+use crate::compat::{self, Body as _};
+
+use core::task;
+use core::pin::Pin;
+use core::future::Future;
+use core::time::Duration;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+use bytes::{Buf, Bytes, BytesMut};
+use tokio::sync::oneshot;
+
+const GRPC_STATUS_HEADER_CODE: &str = "grpc-status";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+///Mismatch between outcomes of primary and shadow calls
+pub struct Mismatch {
+    ///Method path, e.g. `/pkg.Users/Get`
+    pub method: String,
+    ///Status of primary call
+    pub primary: tonic::Code,
+    ///Status of shadow call, or `None` if it failed to complete within timeout
+    pub shadow: Option<tonic::Code>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+///Comparison of primary and shadow calls
+pub struct ComparisonReport {
+    ///Method path, e.g. `/pkg.Users/Get`
+    pub method: String,
+    ///Status of primary call
+    pub primary_code: tonic::Code,
+    ///Status of shadow call, or `None` if it failed to complete within timeout
+    pub shadow_code: Option<tonic::Code>,
+    ///Whether response bodies are equal, or `None` if it cannot be determined
+    ///
+    ///Bodies are compared by hash of their encoded data, hence messages must be encoded and compressed the same way.
+    pub bodies_equal: Option<bool>,
+}
+
+impl ComparisonReport {
+    #[inline(always)]
+    ///Returns whether shadow call failed to complete within timeout
+    pub fn shadow_timeout(&self) -> bool {
+        self.shadow_code.is_none()
+    }
+
+    #[inline(always)]
+    ///Returns whether both status and body of shadow call are the same as of primary one
+    pub fn is_equal(&self) -> bool {
+        self.shadow_code == Some(self.primary_code) && self.bodies_equal == Some(true)
+    }
+}
+
+type OnMismatch = dyn Fn(&Mismatch) + Send + Sync;
+type OnReport = dyn Fn(&ComparisonReport) + Send + Sync;
+
+//Streaming FNV-1a, which does not depend on how data is split into chunks
+#[derive(Clone, Copy)]
+struct Digest(u64);
+
+impl Digest {
+    #[inline(always)]
+    const fn new() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+
+    #[inline]
+    fn update(&mut self, data: &[u8]) {
+        for byte in data {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    //Updates digest with `data`, returning `false` if it is not contiguous
+    #[inline]
+    fn update_buf<D: Buf>(&mut self, data: &D) -> bool {
+        let chunk = data.chunk();
+        if chunk.len() != data.remaining() {
+            return false;
+        }
+        self.update(chunk);
+        true
+    }
+}
+
+//Outcome of call
+struct Outcome {
+    code: tonic::Code,
+    digest: Option<u64>,
+}
+
+//Total size of request copies
+struct Budget {
+    limit: usize,
+    used: AtomicUsize,
+}
+
+impl Budget {
+    #[inline]
+    fn reserve(&self, size: usize) -> bool {
+        self.used.fetch_update(Ordering::AcqRel, Ordering::Acquire, |used| used.checked_add(size).filter(|used| *used <= self.limit)).is_ok()
+    }
+}
+
+//Part of budget, which is released on drop
+struct Reservation {
+    budget: Option<Arc<Budget>>,
+    size: usize,
+}
+
+impl Reservation {
+    #[inline]
+    fn grow(&mut self, size: usize) -> bool {
+        match &self.budget {
+            Some(budget) => if budget.reserve(size) {
+                self.size += size;
+                true
+            } else {
+                false
+            },
+            None => true,
+        }
+    }
+}
+
+impl Drop for Reservation {
+    #[inline]
+    fn drop(&mut self) {
+        if let Some(budget) = &self.budget {
+            budget.used.fetch_sub(self.size, Ordering::AcqRel);
+        }
+    }
+}
+
+#[derive(Clone)]
+enum Report {
+    Mismatch(Option<Arc<OnMismatch>>),
+    Compare(Option<Arc<OnReport>>, Arc<Budget>),
+}
+
+//Configuration shared by `Mirror` and `Compare`
+#[derive(Clone)]
+struct Settings<S2> {
+    shadow: S2,
+    percent: u8,
+    methods: Vec<MethodMatcher>,
+    max_body_size: usize,
+    timeout: Duration,
+    timer: Arc<dyn Timer + Send + Sync>,
+    counter: Arc<AtomicU64>,
+}
+
+impl<S2> Settings<S2> {
+    #[inline]
+    fn new(shadow: S2, percent: u8) -> Self {
+        Self {
+            shadow,
+            percent: percent.min(100),
+            methods: Vec::new(),
+            max_body_size: 64 * 1024,
+            timeout: Duration::from_secs(1),
+            timer: Arc::new(TokioTimer),
+            counter: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    fn is_sampled(&self, path: &str) -> bool {
+        if !self.methods.is_empty() && !self.methods.iter().any(|matcher| matcher.matches(path)) {
+            return false;
+        }
+
+        match self.percent {
+            0 => false,
+            100 => true,
+            percent => {
+                let percent = u64::from(percent);
+                let count = self.counter.fetch_add(1, Ordering::Relaxed);
+                count.wrapping_mul(percent) / 100 != count.wrapping_add(1).wrapping_mul(percent) / 100
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+///Layer mirroring sampled requests to shadow service `S2`, typically `tonic::transport::Channel`
+///
+///By default every method is mirrored, with request body of at most 64KiB and timeout of 1 second.
+pub struct Mirror<S2> {
+    settings: Settings<S2>,
+    on_mismatch: Option<Arc<OnMismatch>>,
+}
+
+impl<S2> Mirror<S2> {
+    #[inline]
+    ///Creates new instance, mirroring `percent` of requests to `shadow`
+    ///
+    ///Percentage above 100 is treated as 100.
+    ///Requests are sampled evenly, e.g. with 25% every fourth request is mirrored.
+    pub fn new(shadow: S2, percent: u8) -> Self {
+        Self {
+            settings: Settings::new(shadow, percent),
+            on_mismatch: None,
+        }
+    }
+
+    #[inline]
+    ///Restricts mirroring to methods matched by `matcher`, in addition to already added ones
+    pub fn method(mut self, matcher: impl Into<MethodMatcher>) -> Self {
+        self.settings.methods.push(matcher.into());
+        self
+    }
+
+    #[inline(always)]
+    ///Sets maximum size of request body, above which request is not mirrored
+    pub fn max_body_size(mut self, max_body_size: usize) -> Self {
+        self.settings.max_body_size = max_body_size;
+        self
+    }
+
+    #[inline(always)]
+    ///Sets timeout of shadow call, including reading of its response
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.settings.timeout = timeout;
+        self
+    }
+
+    #[inline]
+    ///Sets timer, which enforces timeout of shadow call
+    pub fn timer<T: Timer + Send + Sync + 'static>(mut self, timer: T) -> Self {
+        self.settings.timer = Arc::new(timer);
+        self
+    }
+
+    #[inline]
+    ///Sets callback, invoked on spawned task whenever status of shadow call differs from primary one
+    pub fn on_mismatch<F: Fn(&Mismatch) + Send + Sync + 'static>(mut self, on_mismatch: F) -> Self {
+        self.on_mismatch = Some(Arc::new(on_mismatch));
+        self
+    }
+
+    #[inline]
+    ///Returns layer
+    pub fn layer(&self) -> MirrorLayer<S2> where S2: Clone {
+        MirrorLayer {
+            settings: self.settings.clone(),
+            report: Report::Mismatch(self.on_mismatch.clone()),
+        }
+    }
+}
+
+impl<S2> core::fmt::Debug for Mirror<S2> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let settings = &self.settings;
+        fmt.debug_struct("Mirror").field("percent", &settings.percent).field("methods", &settings.methods).field("max_body_size", &settings.max_body_size).field("timeout", &settings.timeout).finish_non_exhaustive()
+    }
+}
+
+#[derive(Clone)]
+///Layer mirroring sampled requests to shadow service `S2` and comparing outcomes of both calls
+///
+///Responses are not kept, only hash of their bodies, while request copies are limited by total memory budget of 16MiB by default.
+///Requests, which would exceed budget, are not mirrored.
+///
+///Every mirrored call, which primary response completes, is reported, including shadow calls timed out.
+pub struct Compare<S2> {
+    settings: Settings<S2>,
+    on_report: Option<Arc<OnReport>>,
+    budget: Arc<Budget>,
+}
+
+impl<S2> Compare<S2> {
+    #[inline]
+    ///Creates new instance, mirroring `percent` of requests to `shadow`
+    ///
+    ///Percentage above 100 is treated as 100.
+    pub fn new(shadow: S2, percent: u8) -> Self {
+        Self {
+            settings: Settings::new(shadow, percent),
+            on_report: None,
+            budget: Arc::new(Budget {
+                limit: 16 * 1024 * 1024,
+                used: AtomicUsize::new(0),
+            }),
+        }
+    }
+
+    #[inline]
+    ///Restricts mirroring to methods matched by `matcher`, in addition to already added ones
+    pub fn method(mut self, matcher: impl Into<MethodMatcher>) -> Self {
+        self.settings.methods.push(matcher.into());
+        self
+    }
+
+    #[inline(always)]
+    ///Sets maximum size of request body, above which request is not mirrored
+    pub fn max_body_size(mut self, max_body_size: usize) -> Self {
+        self.settings.max_body_size = max_body_size;
+        self
+    }
+
+    #[inline(always)]
+    ///Sets timeout of shadow call, after which comparison is reported as `shadow_timeout`
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.settings.timeout = timeout;
+        self
+    }
+
+    #[inline]
+    ///Sets timer, which enforces timeout of shadow call
+    pub fn timer<T: Timer + Send + Sync + 'static>(mut self, timer: T) -> Self {
+        self.settings.timer = Arc::new(timer);
+        self
+    }
+
+    #[inline]
+    ///Sets total size of request copies, which are kept until shadow call completes
+    pub fn budget(mut self, budget: usize) -> Self {
+        self.budget = Arc::new(Budget {
+            limit: budget,
+            used: AtomicUsize::new(0),
+        });
+        self
+    }
+
+    #[inline]
+    ///Sets callback, invoked on spawned task with every comparison
+    pub fn on_report<F: Fn(&ComparisonReport) + Send + Sync + 'static>(mut self, on_report: F) -> Self {
+        self.on_report = Some(Arc::new(on_report));
+        self
+    }
+
+    #[inline]
+    ///Sends every comparison to `sender`, dropping it if channel is full
+    pub fn report_to(self, sender: tokio::sync::mpsc::Sender<ComparisonReport>) -> Self {
+        self.on_report(move |report| {
+            let _ = sender.try_send(report.clone());
+        })
+    }
+
+    #[inline]
+    ///Returns size of request copies currently kept
+    pub fn budget_used(&self) -> usize {
+        self.budget.used.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    ///Returns layer
+    pub fn layer(&self) -> MirrorLayer<S2> where S2: Clone {
+        MirrorLayer {
+            settings: self.settings.clone(),
+            report: Report::Compare(self.on_report.clone(), self.budget.clone()),
+        }
+    }
+}
+
+impl<S2> core::fmt::Debug for Compare<S2> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let settings = &self.settings;
+        fmt.debug_struct("Compare").field("percent", &settings.percent).field("methods", &settings.methods).field("max_body_size", &settings.max_body_size).field("timeout", &settings.timeout).field("budget", &self.budget.limit).finish_non_exhaustive()
+    }
+}
+
+#[derive(Clone)]
+///Layer of `Mirror` and `Compare`
+pub struct MirrorLayer<S2> {
+    settings: Settings<S2>,
+    report: Report,
+}
+
+impl<S2> core::fmt::Debug for MirrorLayer<S2> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("MirrorLayer").field("percent", &self.settings.percent).finish_non_exhaustive()
+    }
+}
+
+impl<S, S2: Clone> tower_layer::Layer<S> for MirrorLayer<S2> {
+    type Service = MirrorService<S, S2>;
+
+    #[inline(always)]
+    fn layer(&self, inner: S) -> Self::Service {
+        MirrorService {
+            settings: self.settings.clone(),
+            report: self.report.clone(),
+            inner,
+        }
+    }
+}
+
+#[derive(Clone)]
+///Service of `Mirror` and `Compare`
+pub struct MirrorService<S, S2> {
+    settings: Settings<S2>,
+    report: Report,
+    inner: S,
+}
+
+impl<S: core::fmt::Debug, S2> core::fmt::Debug for MirrorService<S, S2> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("MirrorService").field("percent", &self.settings.percent).field("inner", &self.inner).finish_non_exhaustive()
+    }
+}
+
+impl<S: tonic::server::NamedService, S2> tonic::server::NamedService for MirrorService<S, S2> {
+    const NAME: &'static str = S::NAME;
+}
+
+//Reads outcome of shadow response
+async fn shadow_outcome<B: http_body::Body + Unpin>(response: http::Response<B>, digest: bool) -> Outcome {
+    let mut digest = Some(Digest::new()).filter(|_| digest);
+    if let Some(code) = response.headers().get(GRPC_STATUS_HEADER_CODE) {
+        return Outcome {
+            code: tonic::Code::from_bytes(code.as_bytes()),
+            digest: digest.map(|digest| digest.0),
+        };
+    }
+
+    let mut body = compat::Incoming::new(response.into_body());
+    loop {
+        match core::future::poll_fn(|ctx| Pin::new(&mut body).poll_data(ctx)).await {
+            Some(Ok(data)) => if digest.as_mut().is_some_and(|digest| !digest.update_buf(&data)) {
+                digest = None;
+            },
+            Some(Err(_)) => return Outcome {
+                code: tonic::Code::Unknown,
+                digest: None,
+            },
+            None => break,
+        }
+    }
+    let code = match core::future::poll_fn(|ctx| Pin::new(&mut body).poll_trailers(ctx)).await {
+        Ok(Some(trailers)) => trailers.get(GRPC_STATUS_HEADER_CODE).map_or(tonic::Code::Unknown, |code| tonic::Code::from_bytes(code.as_bytes())),
+        _ => tonic::Code::Unknown,
+    };
+    Outcome {
+        code,
+        digest: digest.map(|digest| digest.0),
+    }
+}
+
+impl<ReqBody, ResBody, S, S2, ShadowBody> tower_service::Service<http::Request<ReqBody>> for MirrorService<S, S2>
+where
+    ReqBody: http_body::Body<Data = Bytes>,
+    S: tower_service::Service<http::Request<MirrorBody<ReqBody>>, Response = http::Response<ResBody>>,
+    S2: tower_service::Service<http::Request<crate::compat::BoxBody>, Response = http::Response<ShadowBody>> + Clone + Send + 'static,
+    S2::Future: Send,
+    S2::Error: Send,
+    ShadowBody: http_body::Body + Send + 'static,
+{
+    type Response = http::Response<MirrorResponseBody<ResBody>>;
+    type Error = S::Error;
+    type Future = MirrorFut<S::Future>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        if !self.settings.is_sampled(req.uri().path()) {
+            return MirrorFut {
+                status: None,
+                digest: None,
+                inner: self.inner.call(req.map(|body| MirrorBody::new(body, None))),
+            };
+        }
+
+        let (status, primary) = oneshot::channel::<Outcome>();
+        let method = req.method().clone();
+        let uri = req.uri().clone();
+        let version = req.version();
+        let headers = req.headers().clone();
+        let mut shadow = self.settings.shadow.clone();
+        let timeout = self.settings.timeout;
+        let timer = self.settings.timer.clone();
+        let report = self.report.clone();
+        let (digest, budget) = match &self.report {
+            Report::Mismatch(_) => (None, None),
+            Report::Compare(_, budget) => (Some(Digest::new()), Some(budget.clone())),
+        };
+        let fire = move |body: Bytes, reservation: Reservation| {
+            let runtime = match tokio::runtime::Handle::try_current() {
+                Ok(runtime) => runtime,
+                Err(_) => return,
+            };
+
+            let path = uri.path().to_owned();
+            let mut req = http::Request::new(compat::box_body(compat::full_body(body)));
+            *req.method_mut() = method;
+            *req.uri_mut() = uri;
+            *req.version_mut() = version;
+            *req.headers_mut() = headers;
+
+            runtime.spawn(async move {
+                let call = async {
+                    if core::future::poll_fn(|ctx| shadow.poll_ready(ctx)).await.is_err() {
+                        return Outcome {
+                            code: tonic::Code::Unavailable,
+                            digest: None,
+                        };
+                    }
+                    match shadow.call(req).await {
+                        Ok(response) => shadow_outcome(response.map(Box::pin), digest.is_some()).await,
+                        Err(_) => Outcome {
+                            code: tonic::Code::Unavailable,
+                            digest: None,
+                        },
+                    }
+                };
+                let shadow = crate::timer::timeout(&*timer, timeout, call).await;
+                drop(reservation);
+
+                //Primary call, which never completes, is not compared
+                match report {
+                    Report::Mismatch(Some(on_mismatch)) => if let Ok(primary) = primary.await {
+                        let shadow = shadow.map(|shadow| shadow.code);
+                        if shadow != Some(primary.code) {
+                            (on_mismatch)(&Mismatch {
+                                method: path,
+                                primary: primary.code,
+                                shadow,
+                            });
+                        }
+                    },
+                    Report::Compare(Some(on_report), _) => if let Ok(primary) = primary.await {
+                        let bodies_equal = match (primary.digest, shadow.as_ref().and_then(|shadow| shadow.digest)) {
+                            (Some(primary), Some(shadow)) => Some(primary == shadow),
+                            _ => None,
+                        };
+                        (on_report)(&ComparisonReport {
+                            method: path,
+                            primary_code: primary.code,
+                            shadow_code: shadow.map(|shadow| shadow.code),
+                            bodies_equal,
+                        });
+                    },
+                    Report::Mismatch(None) | Report::Compare(None, _) => (),
+                }
+            });
+        };
+
+        let tee = Tee {
+            buffer: BytesMut::new(),
+            limit: self.settings.max_body_size,
+            reservation: Reservation {
+                budget,
+                size: 0,
+            },
+            fire: Box::new(fire),
+        };
+        MirrorFut {
+            status: Some(status),
+            digest,
+            inner: self.inner.call(req.map(|body| MirrorBody::new(body, Some(tee)))),
+        }
+    }
+}
+
+//Copy of request body, which is fired once complete
+struct Tee {
+    buffer: BytesMut,
+    limit: usize,
+    reservation: Reservation,
+    fire: Box<dyn FnOnce(Bytes, Reservation) + Send>,
+}
+
+pin_project_lite::pin_project! {
+    ///Request body of `MirrorService`, which copies data of sampled requests
+    pub struct MirrorBody<B> {
+        #[pin]
+        inner: compat::Incoming<B>,
+        tee: Option<Tee>,
+    }
+}
+
+impl<B> MirrorBody<B> {
+    #[inline(always)]
+    fn new(inner: B, tee: Option<Tee>) -> Self {
+        Self {
+            inner: compat::Incoming::new(inner),
+            tee,
+        }
+    }
+
+    #[inline(always)]
+    ///Returns whether request is being mirrored
+    pub fn is_mirrored(&self) -> bool {
+        self.tee.is_some()
+    }
+}
+
+impl<B> core::fmt::Debug for MirrorBody<B> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("MirrorBody").field("is_mirrored", &self.is_mirrored()).finish_non_exhaustive()
+    }
+}
+
+compat::impl_body! {
+    [B: http_body::Body<Data = Bytes>] MirrorBody<B> {
+        type Data = Bytes;
+        type Error = B::Error;
+
+        fn poll_data(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Option<Result<Self::Data, Self::Error>>> {
+            let this = self.project();
+
+            let result = compat::Body::poll_data(this.inner, ctx);
+            match &result {
+                task::Poll::Ready(Some(Ok(data))) => if let Some(tee) = this.tee.as_mut() {
+                    if tee.buffer.len() + data.len() > tee.limit || !tee.reservation.grow(data.len()) {
+                        *this.tee = None;
+                    } else {
+                        tee.buffer.extend_from_slice(data);
+                    }
+                },
+                task::Poll::Ready(Some(Err(_))) => *this.tee = None,
+                task::Poll::Ready(None) => if let Some(tee) = this.tee.take() {
+                    (tee.fire)(tee.buffer.freeze(), tee.reservation);
+                },
+                task::Poll::Pending => (),
+            }
+            result
+        }
+
+        #[inline(always)]
+        fn poll_trailers(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Result<Option<http::HeaderMap>, Self::Error>> {
+            compat::Body::poll_trailers(self.project().inner, ctx)
+        }
+
+        #[inline(always)]
+        fn is_end_stream(&self) -> bool {
+            self.inner.is_end_stream()
+        }
+
+        #[inline(always)]
+        fn size_hint(&self) -> http_body::SizeHint {
+            self.inner.size_hint()
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    ///Future of `MirrorService`
+    pub struct MirrorFut<F> {
+        status: Option<oneshot::Sender<Outcome>>,
+        digest: Option<Digest>,
+        #[pin]
+        inner: F,
+    }
+}
+
+impl<F> core::fmt::Debug for MirrorFut<F> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("MirrorFut").field("is_mirrored", &self.status.is_some()).finish_non_exhaustive()
+    }
+}
+
+impl<B, E, F: Future<Output = Result<http::Response<B>, E>>> Future for MirrorFut<F> {
+    type Output = Result<http::Response<MirrorResponseBody<B>>, E>;
+
+    fn poll(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let this = self.project();
+
+        let response = match Future::poll(this.inner, ctx) {
+            task::Poll::Ready(Ok(response)) => response,
+            task::Poll::Ready(Err(error)) => return task::Poll::Ready(Err(error)),
+            task::Poll::Pending => return task::Poll::Pending,
+        };
+
+        let mut status = this.status.take();
+        let digest = this.digest.take();
+        //Trailers-only response
+        if let Some(code) = response.headers().get(GRPC_STATUS_HEADER_CODE) {
+            if let Some(status) = status.take() {
+                let _ = status.send(Outcome {
+                    code: tonic::Code::from_bytes(code.as_bytes()),
+                    digest: digest.map(|digest| digest.0),
+                });
+            }
+        }
+        task::Poll::Ready(Ok(response.map(|inner| MirrorResponseBody {
+            status,
+            digest,
+            inner: compat::Incoming::new(inner),
+        })))
+    }
+}
+
+pin_project_lite::pin_project! {
+    ///Response body of `MirrorService`, which reports outcome of primary call
+    pub struct MirrorResponseBody<B> {
+        status: Option<oneshot::Sender<Outcome>>,
+        digest: Option<Digest>,
+        #[pin]
+        inner: compat::Incoming<B>,
+    }
+}
+
+impl<B: Default> Default for MirrorResponseBody<B> {
+    #[inline(always)]
+    fn default() -> Self {
+        Self {
+            status: None,
+            digest: None,
+            inner: Default::default(),
+        }
+    }
+}
+
+impl<B> core::fmt::Debug for MirrorResponseBody<B> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("MirrorResponseBody").finish_non_exhaustive()
+    }
+}
+
+compat::impl_body! {
+    [B: http_body::Body] MirrorResponseBody<B> {
+        type Data = B::Data;
+        type Error = B::Error;
+
+        fn poll_data(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Option<Result<Self::Data, Self::Error>>> {
+            let this = self.project();
+
+            let result = compat::Body::poll_data(this.inner, ctx);
+            if let task::Poll::Ready(Some(Ok(data))) = &result {
+                if this.digest.as_mut().is_some_and(|digest| !digest.update_buf(data)) {
+                    *this.digest = None;
+                }
+            }
+            result
+        }
+
+        fn poll_trailers(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Result<Option<http::HeaderMap>, Self::Error>> {
+            let this = self.project();
+
+            let result = compat::Body::poll_trailers(this.inner, ctx);
+            if let task::Poll::Ready(result) = &result {
+                if let Some(status) = this.status.take() {
+                    let code = match result {
+                        Ok(Some(trailers)) => trailers.get(GRPC_STATUS_HEADER_CODE).map_or(tonic::Code::Unknown, |code| tonic::Code::from_bytes(code.as_bytes())),
+                        _ => tonic::Code::Unknown,
+                    };
+                    let _ = status.send(Outcome {
+                        code,
+                        digest: this.digest.take().map(|digest| digest.0),
+                    });
+                }
+            }
+            result
+        }
+
+        #[inline(always)]
+        fn is_end_stream(&self) -> bool {
+            self.inner.is_end_stream()
+        }
+
+        #[inline(always)]
+        fn size_hint(&self) -> http_body::SizeHint {
+            self.inner.size_hint()
+        }
+    }
+}
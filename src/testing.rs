@@ -0,0 +1,502 @@
+//! Testing utilities
+//!
+//!Allows to test interceptor without runtime, as long as inner service completes immediately:
+//!
+//!```rust
+//!use tonic_interceptor::{Interceptor, InterceptorService};
+//!use tonic_interceptor::testing::{poll_once, request, service_fn};
+//!use tower_service::Service;
+//!
+//!#[derive(Clone)]
+//!struct RequireUser;
+//!
+//!impl Interceptor for RequireUser {
+//!    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+//!        match headers.contains_key("x-user") {
+//!            true => None,
+//!            false => Some(tonic::Status::unauthenticated("no user")),
+//!        }
+//!    }
+//!
+//!    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+//!    }
+//!}
+//!
+//!let mut service = InterceptorService::new(RequireUser, service_fn(|_: http::Request<()>| Ok::<_, tonic::Status>(http::Response::new(()))));
+//!
+//!let response = poll_once(service.call(request().header("x-user", "admin").body(()).unwrap())).expect("success");
+//!assert!(response.headers().get("grpc-status").is_none());
+//!
+//!let response = poll_once(service.call(request().body(()).unwrap())).expect("success");
+//!assert_eq!(response.headers().get("grpc-status").unwrap(), "16");
+//!```
+
+use crate::Interceptor;
+use crate::client::ClientInterceptor;
+
+use core::task;
+use core::pin::pin;
+use core::future::{self, Future};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::collections::HashMap;
+
+struct NoopWaker;
+
+impl std::task::Wake for NoopWaker {
+    #[inline(always)]
+    fn wake(self: Arc<Self>) {
+    }
+
+    #[inline(always)]
+    fn wake_by_ref(self: &Arc<Self>) {
+    }
+}
+
+#[inline]
+///Creates waker, which does nothing
+pub fn noop_waker() -> task::Waker {
+    task::Waker::from(Arc::new(NoopWaker))
+}
+
+#[inline]
+///Runs `fun` with context of `noop_waker`
+pub fn with_noop_context<R, F: FnOnce(&mut task::Context<'_>) -> R>(fun: F) -> R {
+    let waker = noop_waker();
+    fun(&mut task::Context::from_waker(&waker))
+}
+
+#[track_caller]
+///Polls `fut` once with `noop_waker`, returning its output.
+///
+///Panics if future is pending.
+pub fn poll_once<F: Future>(fut: F) -> F::Output {
+    match with_noop_context(|ctx| Future::poll(pin!(fut), ctx)) {
+        task::Poll::Ready(result) => result,
+        task::Poll::Pending => panic!("Future is pending"),
+    }
+}
+
+#[inline]
+///Creates builder of gRPC request
+///
+///Preset with `POST` method, HTTP/2, `content-type: application/grpc` and `te: trailers`.
+pub fn request() -> http::request::Builder {
+    http::Request::builder().method(http::Method::POST)
+                            .version(http::Version::HTTP_2)
+                            .header(http::header::CONTENT_TYPE, "application/grpc")
+                            .header(http::header::TE, "trailers")
+}
+
+#[derive(Clone, Copy)]
+#[repr(transparent)]
+///Service, which completes immediately with result of `Fn(Request) -> Result<Response, Error>`
+pub struct ServiceFn<F>(pub F);
+
+impl<F> core::fmt::Debug for ServiceFn<F> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("ServiceFn").finish_non_exhaustive()
+    }
+}
+
+impl<Req, Resp, E, F: FnMut(Req) -> Result<Resp, E>> tower_service::Service<Req> for ServiceFn<F> {
+    type Response = Resp;
+    type Error = E;
+    type Future = future::Ready<Result<Resp, E>>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, _: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        task::Poll::Ready(Ok(()))
+    }
+
+    #[inline(always)]
+    fn call(&mut self, req: Req) -> Self::Future {
+        future::ready((self.0)(req))
+    }
+}
+
+#[inline(always)]
+///Creates `ServiceFn`
+pub fn service_fn<Req, Resp, E, F: FnMut(Req) -> Result<Resp, E>>(fun: F) -> ServiceFn<F> {
+    ServiceFn(fun)
+}
+
+#[derive(Clone, Debug)]
+///Outgoing request captured by `ClientRecorder`
+pub struct CapturedCall {
+    ///Request URI
+    pub uri: http::Uri,
+    ///Request metadata, as seen by recorder
+    pub metadata: tonic::metadata::MetadataMap,
+}
+
+impl CapturedCall {
+    #[inline(always)]
+    ///Returns method path
+    pub fn path(&self) -> &str {
+        self.uri.path()
+    }
+}
+
+#[derive(Clone, Default)]
+///Client interceptor which records every outgoing request
+///
+///By default requests are passed through to inner service, which can be real channel or dummy service.
+///Use `expect_status` to make calls to particular method fail without reaching inner service.
+///
+///Recorder is cheap to clone, with every clone sharing the same state.
+///Place it as the innermost layer in order to capture metadata set by other interceptors.
+pub struct ClientRecorder {
+    calls: Arc<Mutex<Vec<CapturedCall>>>,
+    statuses: Arc<Mutex<HashMap<String, tonic::Status>>>,
+}
+
+impl ClientRecorder {
+    #[inline(always)]
+    ///Creates new instance
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline(always)]
+    ///Returns shared storage of captured calls
+    pub fn handle(&self) -> Arc<Mutex<Vec<CapturedCall>>> {
+        self.calls.clone()
+    }
+
+    #[inline]
+    ///Makes every subsequent call to `path` fail with `status`
+    pub fn expect_status(&self, path: impl Into<String>, status: tonic::Status) -> &Self {
+        self.statuses.lock().unwrap().insert(path.into(), status);
+        self
+    }
+
+    #[inline]
+    ///Returns copy of all captured calls
+    pub fn calls(&self) -> Vec<CapturedCall> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    #[inline]
+    ///Returns copy of calls to `path`
+    pub fn calls_to(&self, path: &str) -> Vec<CapturedCall> {
+        self.calls.lock().unwrap().iter().filter(|call| call.path() == path).cloned().collect()
+    }
+
+    #[inline]
+    ///Returns copy of the last captured call
+    pub fn last(&self) -> Option<CapturedCall> {
+        self.calls.lock().unwrap().last().cloned()
+    }
+
+    #[inline]
+    ///Removes all captured calls, keeping expected statuses.
+    pub fn clear(&self) {
+        self.calls.lock().unwrap().clear();
+    }
+}
+
+impl ClientInterceptor for ClientRecorder {
+    #[inline(always)]
+    fn on_request(&self, _: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+        None
+    }
+
+    fn on_request_with_uri(&self, uri: &http::Uri, headers: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+        self.calls.lock().unwrap().push(CapturedCall {
+            uri: uri.clone(),
+            metadata: headers.clone(),
+        });
+        self.statuses.lock().unwrap().get(uri.path()).cloned()
+    }
+
+    #[inline(always)]
+    fn on_response(&self, _: &tonic::metadata::MetadataMap, _: &http::Extensions) {
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+///Kind of `Event`
+pub enum EventKind {
+    ///`on_request` is called
+    Request,
+    ///`on_response` is called with status code
+    Response(tonic::Code),
+}
+
+#[derive(Clone, Debug)]
+///Interaction recorded by `Recorder`
+pub struct Event {
+    ///Sequence number within shared log, starting from 0
+    pub seq: usize,
+    ///Name of recorder
+    pub name: &'static str,
+    ///Kind of interaction
+    pub kind: EventKind,
+    ///Snapshot of keys selected by `Recorder::with_keys`
+    pub headers: http::HeaderMap,
+}
+
+#[derive(Clone)]
+///Interceptor which records every interaction
+///
+///Recommended way to assert ordering of interceptors (e.g. that auth ran before logging):
+///put recorders next to interceptors under test, using `share` so that all of them write into the same log,
+///and compare `Event::name` and `Event::kind` of recorded events.
+///
+///Recorder is cheap to clone, with every clone sharing the same state.
+pub struct Recorder {
+    name: &'static str,
+    keys: Arc<[&'static str]>,
+    log: Arc<Mutex<Vec<Event>>>,
+    requests: Arc<AtomicUsize>,
+    rejections: Arc<Mutex<HashMap<usize, tonic::Status>>>,
+}
+
+impl core::fmt::Debug for Recorder {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("Recorder").field("name", &self.name).field("keys", &self.keys).finish_non_exhaustive()
+    }
+}
+
+impl Recorder {
+    #[inline]
+    ///Creates new instance with its own log
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            keys: Arc::new([]),
+            log: Arc::default(),
+            requests: Arc::default(),
+            rejections: Arc::default(),
+        }
+    }
+
+    #[inline]
+    ///Creates new instance, which writes into the same log as this one
+    ///
+    ///Selected keys are inherited, while request counter and rejections are not.
+    pub fn share(&self, name: &'static str) -> Self {
+        Self {
+            name,
+            keys: self.keys.clone(),
+            log: self.log.clone(),
+            requests: Arc::default(),
+            rejections: Arc::default(),
+        }
+    }
+
+    #[inline]
+    ///Sets keys, which values are captured by every event
+    pub fn with_keys(mut self, keys: &[&'static str]) -> Self {
+        self.keys = keys.into();
+        self
+    }
+
+    #[inline]
+    ///Makes `n`-th request, counting from 1, rejected with `status`
+    pub fn reject_nth(&self, n: usize, status: tonic::Status) -> &Self {
+        self.rejections.lock().unwrap().insert(n, status);
+        self
+    }
+
+    #[inline(always)]
+    ///Returns shared log
+    pub fn handle(&self) -> Arc<Mutex<Vec<Event>>> {
+        self.log.clone()
+    }
+
+    #[inline]
+    ///Returns copy of all recorded events
+    pub fn events(&self) -> Vec<Event> {
+        self.log.lock().unwrap().clone()
+    }
+
+    #[inline]
+    ///Returns `(name, kind)` of all recorded events in order
+    pub fn sequence(&self) -> Vec<(&'static str, EventKind)> {
+        self.log.lock().unwrap().iter().map(|event| (event.name, event.kind)).collect()
+    }
+
+    #[inline]
+    ///Removes all recorded events from shared log
+    pub fn clear(&self) {
+        self.log.lock().unwrap().clear();
+    }
+
+    fn record(&self, kind: EventKind, headers: &http::HeaderMap) {
+        let mut snapshot = http::HeaderMap::new();
+        for key in self.keys.iter() {
+            for value in headers.get_all(*key) {
+                snapshot.append(http::header::HeaderName::from_static(key), value.clone());
+            }
+        }
+
+        let mut log = self.log.lock().unwrap();
+        let seq = log.len();
+        log.push(Event {
+            seq,
+            name: self.name,
+            kind,
+            headers: snapshot,
+        });
+    }
+}
+
+impl Interceptor for Recorder {
+    #[inline]
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        let mut raw = crate::compat::metadata_into_headers(core::mem::take(headers));
+        let result = self.on_request_headers(&http::Uri::default(), &mut raw, extensions);
+        *headers = crate::compat::metadata_from_headers(raw);
+        result
+    }
+
+    fn on_request_headers(&self, _: &http::Uri, headers: &mut http::HeaderMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+        self.record(EventKind::Request, headers);
+        let n = self.requests.fetch_add(1, Ordering::SeqCst) + 1;
+        self.rejections.lock().unwrap().get(&n).cloned()
+    }
+
+    #[inline]
+    fn on_response(&self, status: tonic::Code, headers: &mut http::HeaderMap, _: &http::Extensions) {
+        self.record(EventKind::Response(status), headers);
+    }
+}
+
+#[derive(Default)]
+struct TestClock {
+    elapsed: core::time::Duration,
+    next_id: usize,
+    //Id of sleep, its end and waker
+    sleepers: Vec<(usize, core::time::Duration, task::Waker)>,
+}
+
+//Sleep of `TestTimer`
+struct TestSleep {
+    id: usize,
+    until: core::time::Duration,
+    clock: Arc<Mutex<TestClock>>,
+}
+
+impl Future for TestSleep {
+    type Output = ();
+
+    fn poll(self: core::pin::Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let mut clock = self.clock.lock().unwrap();
+        if clock.elapsed >= self.until {
+            return task::Poll::Ready(());
+        }
+        match clock.sleepers.iter_mut().find(|(id, _, _)| *id == self.id) {
+            Some((_, _, waker)) => waker.clone_from(ctx.waker()),
+            None => clock.sleepers.push((self.id, self.until, ctx.waker().clone())),
+        }
+        task::Poll::Pending
+    }
+}
+
+impl Drop for TestSleep {
+    fn drop(&mut self) {
+        if let Ok(mut clock) = self.clock.lock() {
+            clock.sleepers.retain(|(id, _, _)| *id != self.id);
+        }
+    }
+}
+
+#[derive(Clone)]
+///Timer, which time advances only when told so
+///
+///Sleeps complete once timer is advanced past their end, waking their tasks.
+pub struct TestTimer {
+    start: crate::timer::Instant,
+    clock: Arc<Mutex<TestClock>>,
+}
+
+impl TestTimer {
+    #[inline]
+    ///Creates new instance, starting at current time
+    pub fn new() -> Self {
+        Self {
+            start: crate::timer::Instant::now(),
+            clock: Arc::new(Mutex::new(TestClock::default())),
+        }
+    }
+
+    ///Advances time by `duration`, completing sleeps which end by then
+    pub fn advance(&self, duration: core::time::Duration) {
+        let wakers = {
+            let mut clock = self.clock.lock().unwrap();
+            clock.elapsed += duration;
+            let elapsed = clock.elapsed;
+            let (due, pending) = core::mem::take(&mut clock.sleepers).into_iter().partition::<Vec<_>, _>(|(_, until, _)| *until <= elapsed);
+            clock.sleepers = pending;
+            due
+        };
+        for (_, _, waker) in wakers {
+            waker.wake();
+        }
+    }
+
+    ///Advances timer to the end of the earliest pending sleep, returning whether there was one
+    ///
+    ///Only sleeps, which were polled, are considered.
+    pub fn advance_to_next(&self) -> bool {
+        let (elapsed, next) = {
+            let clock = self.clock.lock().unwrap();
+            (clock.elapsed, clock.sleepers.iter().map(|(_, until, _)| *until).min())
+        };
+        match next {
+            Some(until) => {
+                self.advance(until.saturating_sub(elapsed));
+                true
+            },
+            None => false,
+        }
+    }
+
+    #[inline]
+    ///Returns time elapsed since creation
+    pub fn elapsed(&self) -> core::time::Duration {
+        self.clock.lock().unwrap().elapsed
+    }
+
+    #[inline]
+    ///Returns number of pending sleeps, which were polled
+    pub fn sleepers(&self) -> usize {
+        self.clock.lock().unwrap().sleepers.len()
+    }
+}
+
+impl Default for TestTimer {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl core::fmt::Debug for TestTimer {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("TestTimer").field("elapsed", &self.elapsed()).finish_non_exhaustive()
+    }
+}
+
+impl crate::timer::Clock for TestTimer {
+    #[inline]
+    fn now(&self) -> crate::timer::Instant {
+        self.start + self.elapsed()
+    }
+}
+
+impl crate::timer::Timer for TestTimer {
+    fn sleep(&self, duration: core::time::Duration) -> crate::timer::BoxFuture<()> {
+        let mut clock = self.clock.lock().unwrap();
+        clock.next_id += 1;
+        Box::pin(TestSleep {
+            id: clock.next_id,
+            until: clock.elapsed + duration,
+            clock: self.clock.clone(),
+        })
+    }
+}
@@ -0,0 +1,89 @@
+//! `grpc-timeout` header support
+//!
+//!Grammar: `TimeoutValue TimeoutUnit`, where value is at most 8 ASCII digits and unit is one of:
+//!
+//!- `H` - Hour
+//!- `M` - Minute
+//!- `S` - Second
+//!- `m` - Millisecond
+//!- `u` - Microsecond
+//!- `n` - Nanosecond
+
+use core::time::Duration;
+
+///Header name
+pub const GRPC_TIMEOUT: &str = "grpc-timeout";
+
+const MAX_VALUE: u128 = 99_999_999;
+//Units in ascending order of size, with their length in nanoseconds
+const UNITS: [(u8, u128); 6] = [
+    (b'n', 1),
+    (b'u', 1_000),
+    (b'm', 1_000_000),
+    (b'S', 1_000_000_000),
+    (b'M', 60 * 1_000_000_000),
+    (b'H', 60 * 60 * 1_000_000_000),
+];
+
+///Parses value of `grpc-timeout`, returning `None` if it doesn't follow grammar.
+pub fn parse(value: &[u8]) -> Option<Duration> {
+    let (unit, digits) = value.split_last()?;
+    if digits.is_empty() || digits.len() > 8 || !digits.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+
+    let value = digits.iter().fold(0u64, |acc, digit| acc * 10 + (digit - b'0') as u64);
+    match unit {
+        b'H' => Some(Duration::from_secs(value * 60 * 60)),
+        b'M' => Some(Duration::from_secs(value * 60)),
+        b'S' => Some(Duration::from_secs(value)),
+        b'm' => Some(Duration::from_millis(value)),
+        b'u' => Some(Duration::from_micros(value)),
+        b'n' => Some(Duration::from_nanos(value)),
+        _ => None,
+    }
+}
+
+///Encodes duration as `grpc-timeout` value.
+///
+///Picks the largest unit which represents `timeout` within 8 digits, e.g. `2S` rather than `2000000u`.
+///When no unit represents it exactly, `timeout` is truncated to the most precise unit which value fits within 8 digits,
+///so that encoded timeout is never longer than remaining budget.
+///Durations beyond `99999999H` are capped at it.
+pub fn encode(timeout: Duration) -> http::HeaderValue {
+    let nanos = timeout.as_nanos();
+
+    let nanos = match UNITS.iter().find(|(_, size)| nanos / size <= MAX_VALUE) {
+        Some((_, size)) => nanos / size * size,
+        None => return format_value(MAX_VALUE, b'H'),
+    };
+    for (unit, size) in UNITS.iter().rev() {
+        if nanos % size == 0 && nanos / size <= MAX_VALUE {
+            return format_value(nanos / size, *unit);
+        }
+    }
+
+    unreachable!("truncated timeout fits its unit")
+}
+
+fn format_value(value: u128, unit: u8) -> http::HeaderValue {
+    let mut buf = [0u8; 9];
+    let mut len = 0;
+    let mut value = value;
+    loop {
+        buf[len] = b'0' + (value % 10) as u8;
+        len += 1;
+        value /= 10;
+        if value == 0 {
+            break;
+        }
+    }
+    buf[..len].reverse();
+    buf[len] = unit;
+    http::HeaderValue::from_bytes(&buf[..=len]).expect("valid grpc-timeout")
+}
+
+///Reads timeout from headers, ignoring invalid values.
+pub fn from_headers(headers: &http::HeaderMap) -> Option<Duration> {
+    headers.get(GRPC_TIMEOUT).and_then(|value| parse(value.as_bytes()))
+}
@@ -0,0 +1,178 @@
+//! Runtime-agnostic time
+//!
+//!Time-based interceptors take `Clock` or `Timer` as generic parameter, so that they can run on any runtime:
+//!
+//!- `Clock` only tells current time, which is enough for interceptors that measure time, such as rate limits and caches;
+//!- `Timer` additionally sleeps, which is required by interceptors that wait, such as timeouts, retries and admission queues.
+//!
+//!Provided implementations:
+//!
+//!- `TokioTimer` uses tokio's clock and sleep, requiring `tokio` feature. It is default of every interceptor that sleeps;
+//!- `WasmTimer` uses browser's `setTimeout`, requiring `wasm` feature. It is default of client interceptors on `wasm32`, see `DefaultTimer`;
+//!- `StdClock` uses system monotonic clock. It is default of interceptors that only measure time;
+//!- `testing::TestTimer` is driven manually by tests, requiring `testing` feature.
+//!
+//!Time is expressed as `Instant`, which is `std::time::Instant` unless `wasm` feature is enabled.
+//!With `wasm` feature it comes from `instant` crate, which uses `performance.now()` on `wasm32-unknown-unknown`,
+//!where `std::time::Instant` is not available, while being the same `std::time::Instant` on other targets.
+//!
+//!There is no timer without runtime, as sleeping would have to block thread.
+//!Other runtimes are supported by implementing `Timer`, e.g. for smol:
+//!
+//!```rust,ignore
+//!#[derive(Clone, Copy)]
+//!struct SmolTimer;
+//!
+//!impl Clock for SmolTimer {
+//!    fn now(&self) -> Instant {
+//!        Instant::now()
+//!    }
+//!}
+//!
+//!impl Timer for SmolTimer {
+//!    fn sleep(&self, duration: Duration) -> BoxFuture<()> {
+//!        Box::pin(async move {
+//!            async_io::Timer::after(duration).await;
+//!        })
+//!    }
+//!}
+//!```
+
+use core::task;
+use core::pin::Pin;
+use core::future::Future;
+use core::time::Duration;
+use std::sync::Arc;
+
+#[cfg(not(feature = "wasm"))]
+pub use std::time::Instant;
+#[cfg(feature = "wasm")]
+pub use instant::Instant;
+
+#[cfg(feature = "wasm")]
+mod wasm;
+#[cfg(feature = "wasm")]
+pub use wasm::WasmTimer;
+
+///Boxed future, returned by `Timer`
+pub type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+
+///Source of monotonic time
+pub trait Clock {
+    ///Returns current time
+    fn now(&self) -> Instant;
+}
+
+///Source of time and sleep
+pub trait Timer: Clock {
+    ///Returns future, which completes once `duration` elapses
+    fn sleep(&self, duration: Duration) -> BoxFuture<()>;
+}
+
+macro_rules! impl_pointer {
+    ($($ty:ty),+) => {
+        $(
+            impl<T: Clock + ?Sized> Clock for $ty {
+                #[inline(always)]
+                fn now(&self) -> Instant {
+                    T::now(self)
+                }
+            }
+
+            impl<T: Timer + ?Sized> Timer for $ty {
+                #[inline(always)]
+                fn sleep(&self, duration: Duration) -> BoxFuture<()> {
+                    T::sleep(self, duration)
+                }
+            }
+        )+
+    };
+}
+
+impl_pointer!(&T, Arc<T>);
+
+#[cfg(feature = "tokio")]
+#[derive(Copy, Clone, Default, Debug)]
+///Timer of tokio runtime
+///
+///It follows tokio's clock, hence it is paused and advanced along with it.
+pub struct TokioTimer;
+
+#[cfg(feature = "tokio")]
+impl Clock for TokioTimer {
+    #[inline(always)]
+    fn now(&self) -> Instant {
+        #[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+        {
+            tokio::time::Instant::now().into_std()
+        }
+        //tokio's clock is not available there
+        #[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+        {
+            Instant::now()
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Timer for TokioTimer {
+    #[inline]
+    fn sleep(&self, duration: Duration) -> BoxFuture<()> {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+#[cfg(all(feature = "tokio", not(all(feature = "wasm", target_arch = "wasm32"))))]
+///Timer of client interceptors, which is `TokioTimer` unless `WasmTimer` is required
+pub type DefaultTimer = TokioTimer;
+#[cfg(all(feature = "wasm", any(target_arch = "wasm32", not(feature = "tokio"))))]
+///Timer of client interceptors, which is `WasmTimer` on `wasm32` or when `tokio` feature is disabled
+pub type DefaultTimer = WasmTimer;
+
+#[derive(Copy, Clone, Default, Debug)]
+///Clock of `Instant`, which is system monotonic clock
+pub struct StdClock;
+
+impl Clock for StdClock {
+    #[inline(always)]
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+///Runs `fut` until `duration` elapses, according to `timer`
+pub fn timeout<T: Timer + ?Sized, F: Future>(timer: &T, duration: Duration, fut: F) -> Timeout<F> {
+    Timeout {
+        fut,
+        sleep: timer.sleep(duration),
+    }
+}
+
+pin_project_lite::pin_project! {
+    ///Future of `timeout`, resolving to `None` if future does not complete in time
+    pub struct Timeout<F> {
+        #[pin]
+        fut: F,
+        sleep: BoxFuture<()>,
+    }
+}
+
+impl<F> core::fmt::Debug for Timeout<F> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("Timeout").finish_non_exhaustive()
+    }
+}
+
+impl<F: Future> Future for Timeout<F> {
+    type Output = Option<F::Output>;
+
+    #[inline]
+    fn poll(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let this = self.project();
+        match this.fut.poll(ctx) {
+            task::Poll::Ready(output) => task::Poll::Ready(Some(output)),
+            task::Poll::Pending => this.sleep.as_mut().poll(ctx).map(|()| None),
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use super::{BoxFuture, Clock, Instant, Timer};
+
+use core::task;
+use core::pin::Pin;
+use core::future::Future;
+use core::time::Duration;
+use core::cell::{Cell, RefCell};
+use std::collections::HashMap;
+
+use gloo_timers::future::TimeoutFuture;
+
+//Maximum delay of `setTimeout`, longer delays fire immediately
+const MAX_DELAY_MS: u32 = i32::MAX as u32;
+
+std::thread_local! {
+    //JS timeouts are not `Send`, hence they are kept by thread, which created them, and sleep refers to them by id
+    static SLEEPS: RefCell<HashMap<u64, TimeoutFuture>> = RefCell::new(HashMap::new());
+    static NEXT_ID: Cell<u64> = const { Cell::new(0) };
+}
+
+#[derive(Copy, Clone, Default, Debug)]
+///Timer of browser, which sleeps using `setTimeout` and tells time using `performance.now()`
+///
+///It is only functional on `wasm32-unknown-unknown` within JS environment, where it is default timer of client interceptors.
+///Sleep must be polled on thread, which created it, which is always the case in single threaded environment.
+pub struct WasmTimer;
+
+impl Clock for WasmTimer {
+    #[inline(always)]
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+impl Timer for WasmTimer {
+    fn sleep(&self, duration: Duration) -> BoxFuture<()> {
+        //Round up, so that sleep is never shorter than requested
+        let millis = duration.as_nanos().div_ceil(1_000_000).min(MAX_DELAY_MS as u128) as u32;
+        let id = NEXT_ID.with(|next| {
+            let id = next.get();
+            next.set(id.wrapping_add(1));
+            id
+        });
+        SLEEPS.with(|sleeps| sleeps.borrow_mut().insert(id, TimeoutFuture::new(millis)));
+        Box::pin(WasmSleep {
+            id
+        })
+    }
+}
+
+//Sleep of `WasmTimer`, cancelling its timeout once dropped
+struct WasmSleep {
+    id: u64,
+}
+
+impl Future for WasmSleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        SLEEPS.with(|sleeps| {
+            let mut sleeps = sleeps.borrow_mut();
+            let timeout = sleeps.get_mut(&self.id).expect("WasmTimer sleep polled outside of thread, which created it");
+            match Future::poll(Pin::new(timeout), ctx) {
+                task::Poll::Ready(()) => {
+                    sleeps.remove(&self.id);
+                    task::Poll::Ready(())
+                },
+                task::Poll::Pending => task::Poll::Pending,
+            }
+        })
+    }
+}
+
+impl Drop for WasmSleep {
+    fn drop(&mut self) {
+        //Thread local may be already destroyed, in which case there is nothing to cancel
+        let _ = SLEEPS.try_with(|sleeps| {
+            //Drop timeout outside of borrow as it calls into JS
+            let timeout = sleeps.borrow_mut().remove(&self.id);
+            drop(timeout);
+        });
+    }
+}
@@ -0,0 +1,145 @@
+//! TLS connection info
+//!
+//!`ConnectionInfo` exposes TLS parameters of connection to interceptors as `TlsInfo`, optionally enforcing allowlist of server names (SNI).
+//!
+//!tonic's own `TlsConnectInfo` carries only peer certificates, hence `TlsInfo` is to be provided by TLS acceptor.
+//!The simplest way is to make it connection info of accepted stream, which tonic inserts into request extensions as it is.
+//!With `rustls` feature, `TlsInfo::from_rustls` fills it from server connection once handshake is complete:
+//!
+//!```rust
+//!# #[cfg(feature = "rustls")] {
+//!use tonic_interceptor::tls::TlsInfo;
+//!
+//!struct MyTlsStream {
+//!    inner: tokio_rustls::server::TlsStream<tokio::net::TcpStream>,
+//!}
+//!
+//!impl tonic::transport::server::Connected for MyTlsStream {
+//!    type ConnectInfo = TlsInfo;
+//!
+//!    fn connect_info(&self) -> Self::ConnectInfo {
+//!        let (_, connection) = self.inner.get_ref();
+//!        TlsInfo::from_rustls(connection)
+//!    }
+//!}
+//!# }
+//!```
+//!
+//!Otherwise use `ConnectionInfo::extractor` to dig it out of whatever connection info acceptor provides.
+
+use crate::Interceptor;
+
+use std::sync::Arc;
+
+#[derive(Clone, Default, PartialEq, Eq, Hash, Debug)]
+///TLS parameters of connection
+pub struct TlsInfo {
+    ///Server name, requested by client via SNI
+    pub sni: Option<String>,
+    ///Negotiated application protocol
+    pub alpn: Option<String>,
+    ///Negotiated cipher suite
+    pub cipher: Option<String>,
+}
+
+impl TlsInfo {
+    #[inline(always)]
+    ///Returns info of request's connection, if any
+    pub fn from_extensions(extensions: &http::Extensions) -> Option<&Self> {
+        extensions.get()
+    }
+
+    #[cfg(feature = "rustls")]
+    ///Creates info of `rustls` server connection, which completed handshake
+    pub fn from_rustls(connection: &rustls::ServerConnection) -> Self {
+        Self {
+            sni: connection.server_name().map(str::to_owned),
+            alpn: connection.alpn_protocol().map(|alpn| String::from_utf8_lossy(alpn).into_owned()),
+            cipher: connection.negotiated_cipher_suite().and_then(|suite| suite.suite().as_str()).map(str::to_owned),
+        }
+    }
+}
+
+type Extractor = dyn Fn(&http::Extensions) -> Option<TlsInfo> + Send + Sync;
+
+//Returns whether `name` is matched by `pattern`, where `*.` prefix matches exactly one label
+fn matches_sni(pattern: &str, name: &str) -> bool {
+    match pattern.strip_prefix("*.") {
+        Some(suffix) => match name.split_once('.') {
+            Some((label, rest)) => !label.is_empty() && rest.eq_ignore_ascii_case(suffix),
+            None => false,
+        },
+        None => pattern.eq_ignore_ascii_case(name),
+    }
+}
+
+#[derive(Clone, Default)]
+///Server interceptor which exposes `TlsInfo` of connection
+///
+///By default `TlsInfo` is expected in request extensions already, as connection info of accepted stream.
+///When extractor is set, its result is inserted into request extensions instead.
+///
+///With SNI allowlist, requests without server name or with server name outside of it are rejected with `PERMISSION_DENIED`.
+///Names are compared case-insensitively, while `*.example.com` matches any single label subdomain of `example.com`.
+pub struct ConnectionInfo {
+    extractor: Option<Arc<Extractor>>,
+    allowed_sni: Vec<String>,
+}
+
+impl ConnectionInfo {
+    #[inline]
+    ///Creates new instance
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    ///Uses `extractor` to get `TlsInfo` of request
+    pub fn extractor<F: Fn(&http::Extensions) -> Option<TlsInfo> + Send + Sync + 'static>(mut self, extractor: F) -> Self {
+        self.extractor = Some(Arc::new(extractor));
+        self
+    }
+
+    #[inline]
+    ///Adds server name `pattern` to allowlist
+    pub fn allow_sni(mut self, pattern: impl Into<String>) -> Self {
+        self.allowed_sni.push(pattern.into());
+        self
+    }
+
+    ///Checks server name of connection against allowlist
+    pub fn check(&self, info: Option<&TlsInfo>) -> Result<(), tonic::Status> {
+        if self.allowed_sni.is_empty() {
+            return Ok(());
+        }
+
+        match info.and_then(|info| info.sni.as_deref()) {
+            Some(sni) if self.allowed_sni.iter().any(|pattern| matches_sni(pattern, sni)) => Ok(()),
+            Some(sni) => Err(tonic::Status::permission_denied(format!("server name '{}' is not allowed", sni))),
+            None => Err(tonic::Status::permission_denied("server name is required")),
+        }
+    }
+}
+
+impl core::fmt::Debug for ConnectionInfo {
+    #[inline]
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("ConnectionInfo").field("allowed_sni", &self.allowed_sni).finish_non_exhaustive()
+    }
+}
+
+impl Interceptor for ConnectionInfo {
+    fn on_request(&self, _: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        if let Some(extractor) = self.extractor.as_ref() {
+            if let Some(info) = extractor(extensions) {
+                extensions.insert(info);
+            }
+        }
+
+        self.check(TlsInfo::from_extensions(extensions)).err()
+    }
+
+    #[inline(always)]
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
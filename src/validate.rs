@@ -0,0 +1,244 @@
+//! Migration from `tower_http::validate_request`
+//!
+//!`FromValidateRequest` runs validator of request head as interceptor, translating its rejection into `tonic::Status`:
+//!
+//!- `401 Unauthorized` into `UNAUTHENTICATED`;
+//!- `403 Forbidden` into `PERMISSION_DENIED`;
+//!- Anything else into `INTERNAL`.
+//!
+//!`ValidateRequest` mirrors trait of `tower_http`.
+//!With `tower-http` feature, every validator of `tower_http` implements it,
+//!while validators, which `tower_http` provides only as `ValidateRequestHeaderLayer`, are adapted by `from_layer`:
+//!
+//!```rust
+//!# #[cfg(feature = "tower-http")] {
+//!use tonic_interceptor::validate::FromValidateRequest;
+//!use tower_http::auth::require_authorization::Bearer;
+//!use tower_http::validate_request::ValidateRequestHeaderLayer;
+//!
+//!let interceptor = FromValidateRequest::from_layer(ValidateRequestHeaderLayer::<Bearer<tonic::body::BoxBody>>::bearer("secret"));
+//!# }
+//!```
+//!
+//!With `details` feature, `MessageValidator` validates request messages against rules registered per prost type.
+
+#[cfg(feature = "details")]
+mod message;
+#[cfg(feature = "details")]
+pub use message::{MessageValidator, Rules, Violations};
+
+use crate::Interceptor;
+
+use core::fmt;
+use std::sync::{Arc, Mutex, PoisonError};
+
+///Validator of request, same as `tower_http::validate_request::ValidateRequest`
+pub trait ValidateRequest<B> {
+    ///Body of rejection response
+    type ResponseBody;
+
+    ///Validates request, returning response on rejection
+    fn validate(&mut self, request: &mut http::Request<B>) -> Result<(), http::Response<Self::ResponseBody>>;
+}
+
+#[cfg(not(feature = "tower-http"))]
+impl<B, F: FnMut(&mut http::Request<B>) -> Result<(), http::Response<R>>, R> ValidateRequest<B> for F {
+    type ResponseBody = R;
+
+    #[inline(always)]
+    fn validate(&mut self, request: &mut http::Request<B>) -> Result<(), http::Response<Self::ResponseBody>> {
+        (self)(request)
+    }
+}
+
+#[cfg(feature = "tower-http")]
+//Includes closures, which `tower_http` implements it for
+impl<B, V: tower_http::validate_request::ValidateRequest<B>> ValidateRequest<B> for V {
+    type ResponseBody = V::ResponseBody;
+
+    #[inline(always)]
+    fn validate(&mut self, request: &mut http::Request<B>) -> Result<(), http::Response<Self::ResponseBody>> {
+        tower_http::validate_request::ValidateRequest::validate(self, request)
+    }
+}
+
+#[cfg(feature = "tower-http")]
+type AcceptedSlot = Arc<Mutex<Option<http::Request<()>>>>;
+
+#[cfg(feature = "tower-http")]
+//Innermost service of validation layer, which takes back accepted request
+struct Probe<R> {
+    accepted: AcceptedSlot,
+    _body: core::marker::PhantomData<fn() -> R>,
+}
+
+#[cfg(feature = "tower-http")]
+impl<R: Default> tower_service::Service<http::Request<()>> for Probe<R> {
+    type Response = http::Response<R>;
+    type Error = core::convert::Infallible;
+    type Future = core::future::Ready<Result<Self::Response, Self::Error>>;
+
+    #[inline(always)]
+    fn poll_ready(&mut self, _: &mut core::task::Context<'_>) -> core::task::Poll<Result<(), Self::Error>> {
+        core::task::Poll::Ready(Ok(()))
+    }
+
+    #[inline]
+    fn call(&mut self, request: http::Request<()>) -> Self::Future {
+        *self.accepted.lock().unwrap_or_else(PoisonError::into_inner) = Some(request);
+        core::future::ready(Ok(http::Response::new(R::default())))
+    }
+}
+
+#[cfg(feature = "tower-http")]
+///Validator of `tower_http::validate_request::ValidateRequestHeaderLayer`, created by `FromValidateRequest::from_layer`
+pub struct LayerValidator<V, R> {
+    service: tower_http::validate_request::ValidateRequestHeader<Probe<R>, V>,
+    accepted: AcceptedSlot,
+}
+
+#[cfg(feature = "tower-http")]
+impl<V: tower_http::validate_request::ValidateRequest<(), ResponseBody = R>, R: Default> ValidateRequest<()> for LayerValidator<V, R> {
+    type ResponseBody = R;
+
+    fn validate(&mut self, request: &mut http::Request<()>) -> Result<(), http::Response<Self::ResponseBody>> {
+        use core::future::Future;
+        use tower_service::Service;
+
+        let mut future = self.service.call(core::mem::replace(request, http::Request::new(())));
+        //Validator responds immediately, while probe is always ready
+        let response = match core::pin::Pin::new(&mut future).poll(&mut core::task::Context::from_waker(core::task::Waker::noop())) {
+            core::task::Poll::Ready(Ok(response)) => response,
+            core::task::Poll::Ready(Err(never)) => match never {},
+            core::task::Poll::Pending => unreachable!("validation layer is not immediately ready"),
+        };
+        match self.accepted.lock().unwrap_or_else(PoisonError::into_inner).take() {
+            Some(accepted) => {
+                *request = accepted;
+                Ok(())
+            },
+            None => Err(response),
+        }
+    }
+}
+
+///Returns status code, equivalent to HTTP `status` of rejection
+pub fn status_code(status: http::StatusCode) -> tonic::Code {
+    match status {
+        http::StatusCode::UNAUTHORIZED => tonic::Code::Unauthenticated,
+        http::StatusCode::FORBIDDEN => tonic::Code::PermissionDenied,
+        _ => tonic::Code::Internal,
+    }
+}
+
+///Interceptor, which validates request head using `ValidateRequest`
+///
+///Validator is given request with URI, headers and extensions of call, and empty body.
+///Changes it makes to headers and extensions are kept.
+///Validator is shared by clones of interceptor.
+///
+///Rejection is translated into status with code of `status_code` and canonical reason of HTTP status as message.
+///Headers of rejection, listed by `preserve` (`www-authenticate` by default), are added as status metadata.
+pub struct FromValidateRequest<V> {
+    validator: Arc<Mutex<V>>,
+    preserve: Vec<http::header::HeaderName>,
+}
+
+impl<V: ValidateRequest<()>> FromValidateRequest<V> {
+    #[inline]
+    ///Creates new instance
+    pub fn new(validator: V) -> Self {
+        Self {
+            validator: Arc::new(Mutex::new(validator)),
+            preserve: vec![http::header::WWW_AUTHENTICATE],
+        }
+    }
+
+    #[inline]
+    ///Adds header of rejection to preserve as status metadata
+    pub fn preserve(mut self, name: http::header::HeaderName) -> Self {
+        if !self.preserve.contains(&name) {
+            self.preserve.push(name);
+        }
+        self
+    }
+
+    fn validate(&self, uri: &http::Uri, headers: &mut http::HeaderMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        let mut request = http::Request::new(());
+        *request.uri_mut() = uri.clone();
+        *request.headers_mut() = core::mem::take(headers);
+        *request.extensions_mut() = core::mem::take(extensions);
+
+        let result = self.validator.lock().unwrap_or_else(PoisonError::into_inner).validate(&mut request);
+
+        let (mut parts, _) = request.into_parts();
+        *headers = core::mem::take(&mut parts.headers);
+        *extensions = core::mem::take(&mut parts.extensions);
+
+        let response = result.err()?;
+        let mut metadata = http::HeaderMap::new();
+        for name in self.preserve.iter() {
+            for value in response.headers().get_all(name) {
+                metadata.append(name.clone(), value.clone());
+            }
+        }
+        let message = response.status().canonical_reason().unwrap_or("Request is rejected");
+        Some(tonic::Status::with_metadata(status_code(response.status()), message, crate::compat::metadata_from_headers(metadata)))
+    }
+}
+
+#[cfg(feature = "tower-http")]
+impl<V: tower_http::validate_request::ValidateRequest<(), ResponseBody = R> + Clone, R: Default> FromValidateRequest<LayerValidator<V, R>> {
+    ///Creates new instance, validating requests same as `layer`
+    pub fn from_layer(layer: tower_http::validate_request::ValidateRequestHeaderLayer<V>) -> Self {
+        let accepted = AcceptedSlot::default();
+        let probe = Probe {
+            accepted: accepted.clone(),
+            _body: core::marker::PhantomData,
+        };
+        Self::new(LayerValidator {
+            service: tower_layer::Layer::layer(&layer, probe),
+            accepted,
+        })
+    }
+}
+
+impl<V> Clone for FromValidateRequest<V> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            validator: self.validator.clone(),
+            preserve: self.preserve.clone(),
+        }
+    }
+}
+
+impl<V> fmt::Debug for FromValidateRequest<V> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("FromValidateRequest").field("preserve", &self.preserve).finish_non_exhaustive()
+    }
+}
+
+impl<V: ValidateRequest<()>> Interceptor for FromValidateRequest<V> {
+    #[inline]
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        self.on_request_with_uri(&http::Uri::default(), headers, extensions)
+    }
+
+    fn on_request_with_uri(&self, uri: &http::Uri, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        let mut raw = crate::compat::metadata_into_headers(core::mem::take(headers));
+        let status = self.validate(uri, &mut raw, extensions);
+        *headers = crate::compat::metadata_from_headers(raw);
+        status
+    }
+
+    #[inline]
+    fn on_request_headers(&self, uri: &http::Uri, headers: &mut http::HeaderMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+        self.validate(uri, headers, extensions)
+    }
+
+    #[inline(always)]
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
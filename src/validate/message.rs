@@ -0,0 +1,220 @@
+use crate::body::UnaryInspector;
+use crate::details::{FieldViolation, StatusDetails};
+use crate::matcher::MethodMatcher;
+
+use core::fmt;
+use core::ops::RangeInclusive;
+use std::sync::Arc;
+
+///Field violations collected by `Rules`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Violations {
+    prefix: String,
+    violations: Vec<FieldViolation>,
+}
+
+impl Violations {
+    #[inline(always)]
+    ///Creates empty instance
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///Adds violation of `field`, relative to currently validated message
+    pub fn add<F: AsRef<str>, D: Into<String>>(&mut self, field: F, description: D) {
+        self.violations.push(FieldViolation {
+            field: format!("{}{}", self.prefix, field.as_ref()),
+            description: description.into(),
+        });
+    }
+
+    #[inline(always)]
+    ///Returns collected violations
+    pub fn as_slice(&self) -> &[FieldViolation] {
+        &self.violations
+    }
+
+    #[inline(always)]
+    ///Returns whether there are no violations
+    pub fn is_empty(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    ///Creates `INVALID_ARGUMENT` status with `BadRequest` details, listing every violation
+    pub fn into_status(self) -> tonic::Status {
+        let mut details = StatusDetails::new();
+        for violation in self.violations {
+            details = details.bad_request(violation.field, violation.description);
+        }
+        details.into_status(tonic::Code::InvalidArgument, "invalid request")
+    }
+}
+
+type Rule<M> = Box<dyn Fn(&M, &mut Violations) + Send + Sync>;
+
+///Validation rules of message `M`
+///
+///Every rule is evaluated, hence all violations are reported at once.
+pub struct Rules<M> {
+    rules: Vec<Rule<M>>,
+}
+
+impl<M: 'static> Rules<M> {
+    #[inline(always)]
+    ///Creates empty instance
+    pub fn new() -> Self {
+        Self {
+            rules: Vec::new(),
+        }
+    }
+
+    ///Requires optional `field` to be set
+    pub fn required<T, F: Fn(&M) -> Option<&T> + Send + Sync + 'static>(self, field: &'static str, get: F) -> Self {
+        self.custom(move |message, violations| if get(message).is_none() {
+            violations.add(field, "must be set");
+        })
+    }
+
+    ///Requires number of characters of string `field` to be within `range`
+    pub fn len<F: Fn(&M) -> &str + Send + Sync + 'static>(self, field: &'static str, get: F, range: RangeInclusive<usize>) -> Self {
+        self.custom(move |message, violations| {
+            let len = get(message).chars().count();
+            if !range.contains(&len) {
+                violations.add(field, format!("length must be between {} and {}", range.start(), range.end()));
+            }
+        })
+    }
+
+    ///Requires value of `field` to be within `range`
+    pub fn range<T: PartialOrd + fmt::Display + Send + Sync + 'static, F: Fn(&M) -> T + Send + Sync + 'static>(self, field: &'static str, get: F, range: RangeInclusive<T>) -> Self {
+        self.custom(move |message, violations| {
+            if !range.contains(&get(message)) {
+                violations.add(field, format!("must be between {} and {}", range.start(), range.end()));
+            }
+        })
+    }
+
+    ///Validates nested message of `field`, if it is set
+    ///
+    ///Violations of nested message are reported with path prefixed by `field`.
+    pub fn nested<N: 'static, F: Fn(&M) -> Option<&N> + Send + Sync + 'static>(self, field: &'static str, get: F, rules: Rules<N>) -> Self {
+        self.custom(move |message, violations| if let Some(nested) = get(message) {
+            let prefix_len = violations.prefix.len();
+            violations.prefix.push_str(field);
+            violations.prefix.push('.');
+            rules.check(nested, violations);
+            violations.prefix.truncate(prefix_len);
+        })
+    }
+
+    ///Adds custom rule, reporting violations into `Violations`
+    pub fn custom<F: Fn(&M, &mut Violations) + Send + Sync + 'static>(mut self, rule: F) -> Self {
+        self.rules.push(Box::new(rule));
+        self
+    }
+
+    ///Evaluates every rule, collecting violations
+    pub fn check(&self, message: &M, violations: &mut Violations) {
+        for rule in self.rules.iter() {
+            rule(message, violations);
+        }
+    }
+
+    ///Validates message, returning `INVALID_ARGUMENT` status with `BadRequest` details on violations
+    pub fn validate(&self, message: &M) -> Result<(), tonic::Status> {
+        let mut violations = Violations::new();
+        self.check(message, &mut violations);
+        match violations.is_empty() {
+            true => Ok(()),
+            false => Err(violations.into_status()),
+        }
+    }
+}
+
+impl<M: 'static> Default for Rules<M> {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<M> fmt::Debug for Rules<M> {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("Rules").field("len", &self.rules.len()).finish()
+    }
+}
+
+type Validate = dyn Fn(&[u8]) -> Result<(), tonic::Status> + Send + Sync;
+
+#[derive(Clone, Default)]
+///Registry of request message rules, validating messages of unary methods
+///
+///Rules are registered programmatically against prost types, without code generation:
+///
+///```rust
+///use tonic_interceptor::validate::{MessageValidator, Rules};
+///
+///#[derive(Clone, PartialEq, prost::Message)]
+///struct ListRequest {
+///    #[prost(string, tag = "1")]
+///    parent: String,
+///    #[prost(int32, tag = "2")]
+///    page_size: i32,
+///}
+///
+///let validator = MessageValidator::new().register::<ListRequest>("/pkg.Items/List", Rules::new().len("parent", |request: &ListRequest| request.parent.as_str(), 1..=64)
+///                                                                                               .range("page_size", |request: &ListRequest| request.page_size, 0..=1000));
+/////Pass `validator.inspector().layer()` to `Server::layer`
+///# let _ = validator.inspector().layer();
+///```
+///
+///Message is decoded and validated by `body::UnaryInspector`, returned by `inspector`.
+///Message, which cannot be decoded, is rejected with `INVALID_ARGUMENT` too.
+pub struct MessageValidator {
+    methods: Vec<(MethodMatcher, Arc<Validate>)>,
+}
+
+impl MessageValidator {
+    #[inline(always)]
+    ///Creates empty instance
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///Registers `rules` of message `M`, which is request of methods matched by `matcher`
+    ///
+    ///Method is validated by the first registration matching it.
+    pub fn register<M: prost::Message + Default + 'static>(mut self, matcher: impl Into<MethodMatcher>, rules: Rules<M>) -> Self {
+        let validate = move |message: &[u8]| match M::decode(message) {
+            Ok(message) => rules.validate(&message),
+            Err(error) => Err(tonic::Status::invalid_argument(format!("invalid message: {}", error))),
+        };
+        self.methods.push((matcher.into(), Arc::new(validate)));
+        self
+    }
+
+    ///Validates encoded `message` of method `path`
+    ///
+    ///Messages of methods without rules are always valid.
+    pub fn validate(&self, path: &str, message: &[u8]) -> Result<(), tonic::Status> {
+        match self.methods.iter().find(|(matcher, _)| matcher.matches(path)) {
+            Some((_, validate)) => validate(message),
+            None => Ok(()),
+        }
+    }
+
+    ///Returns inspector, validating request messages of registered methods
+    pub fn inspector(&self) -> UnaryInspector {
+        let matchers = self.methods.iter().map(|(matcher, _)| matcher.clone()).collect();
+        let validator = self.clone();
+        UnaryInspector::with_methods(matchers, Arc::new(move |path: &str, message: &[u8]| validator.validate(path, message)))
+    }
+}
+
+impl fmt::Debug for MessageValidator {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("MessageValidator").field("methods", &self.methods.iter().map(|(matcher, _)| matcher).collect::<Vec<_>>()).finish()
+    }
+}
@@ -0,0 +1,98 @@
+#![cfg(feature = "tonic011")]
+#![allow(clippy::result_large_err)]
+
+use tonic_interceptor::{EmptyBody, InterceptorFn, InterceptorService};
+
+use tonic::Status;
+use tower_service::Service;
+
+use core::task;
+use core::pin::pin;
+use core::future::{self, Future};
+use core::cell::Cell;
+use std::alloc::{GlobalAlloc, Layout, System};
+
+struct Counting;
+
+//Per thread to isolate concurrently running tests
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+const REJECTIONS: usize = 1_000_000;
+//Full rejection path is too slow in debug to run million times
+const REQUESTS: usize = 10_000;
+
+fn allocations<R>(fun: impl FnOnce() -> R) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    let result = fun();
+    let after = ALLOCATIONS.with(Cell::get);
+    drop(result);
+    after - before
+}
+
+//Returns allocations made by rejecting requests with body `B`
+fn reject<B: EmptyBody>() -> usize {
+    struct Noop;
+
+    impl std::task::Wake for Noop {
+        fn wake(self: std::sync::Arc<Self>) {
+        }
+    }
+
+    let waker = task::Waker::from(std::sync::Arc::new(Noop));
+    let mut ctx = task::Context::from_waker(&waker);
+
+    let svc = tower::service_fn(|_: http::Request<()>| future::ready(Ok::<_, Status>(http::Response::new(B::empty()))));
+    let interceptor = InterceptorFn {
+        on_request: |_: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions| {
+            Some(Status::permission_denied(""))
+        },
+        on_response: |_: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions| {
+        }
+    };
+    let mut service = InterceptorService::new(interceptor, svc);
+
+    allocations(|| {
+        for _ in 0..REQUESTS {
+            let res = pin!(service.call(http::Request::new(())));
+            match Future::poll(res, &mut ctx) {
+                task::Poll::Ready(result) => drop(result.expect("response")),
+                task::Poll::Pending => unreachable!(),
+            }
+        }
+    })
+}
+
+#[test]
+fn should_create_empty_box_body_without_allocation() {
+    let count = allocations(|| {
+        for _ in 0..REJECTIONS {
+            drop(<tonic::body::BoxBody as EmptyBody>::empty());
+        }
+    });
+    assert_eq!(count, 0);
+}
+
+#[test]
+fn should_not_allocate_body_on_rejection() {
+    //Warm up lazily initialized state
+    reject::<()>();
+
+    //Body contributes nothing on top of response headers
+    assert_eq!(reject::<tonic::body::BoxBody>(), reject::<()>());
+}
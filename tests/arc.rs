@@ -0,0 +1,55 @@
+#![cfg(feature = "tonic011")]
+mod common;
+
+use common::{EchoClient, user_request};
+
+use tonic_interceptor::Interceptor;
+
+use tonic::{Code, Status};
+use tower_layer::Layer;
+
+use std::sync::{Arc, Mutex};
+
+//Intentionally not `Clone`
+struct Allowlist {
+    users: Vec<String>,
+    seen: Arc<Mutex<Vec<String>>>,
+}
+
+impl Interceptor for Allowlist {
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<Status> {
+        let user = headers.get("x-user").and_then(|user| user.to_str().ok()).unwrap_or_default().to_owned();
+        let allowed = self.users.contains(&user);
+        self.seen.lock().unwrap().push(user);
+        match allowed {
+            true => None,
+            false => Some(Status::permission_denied("not allowed")),
+        }
+    }
+
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
+
+#[tokio::test]
+async fn should_share_non_clone_interceptor() {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let layer = tonic_interceptor::interceptor_arc(Allowlist {
+        users: vec!["admin".to_owned()],
+        seen: seen.clone(),
+    });
+    let service = common::EchoService::default();
+
+    //Every layered service, e.g. per connection, shares the same interceptor
+    let mut first = EchoClient::new(layer.layer(common::EchoServer::new(service.clone())));
+    let mut second = EchoClient::new(layer.clone().layer(common::EchoServer::new(service.clone())));
+
+    let response = first.unary(user_request("admin")).await.expect("success");
+    assert_eq!(response.get_ref().message, "hello");
+    let status = second.unary(user_request("guest")).await.expect_err("to fail");
+    assert_eq!(status.code(), Code::PermissionDenied);
+    assert_eq!(status.message(), "not allowed");
+
+    assert_eq!(service.calls(), 1);
+    assert_eq!(*seen.lock().unwrap(), ["admin", "guest"]);
+}
@@ -0,0 +1,83 @@
+#![cfg(feature = "tonic011")]
+use tonic_interceptor::auth::{TokenExtractor, TokenSource};
+
+use tonic::metadata::MetadataMap;
+
+fn metadata(entries: &[(&'static str, &'static str)]) -> MetadataMap {
+    let mut metadata = MetadataMap::new();
+    for (key, value) in entries {
+        metadata.append(*key, value.parse().unwrap());
+    }
+    metadata
+}
+
+fn extractor() -> TokenExtractor {
+    TokenExtractor::new().bearer().metadata("x-access-token").cookie("session")
+}
+
+fn extract(extractor: &TokenExtractor, entries: &[(&'static str, &'static str)]) -> Option<(String, TokenSource)> {
+    let metadata = metadata(entries);
+    extractor.extract(&metadata).map(|token| (token.value.to_owned(), token.source.clone()))
+}
+
+#[test]
+fn should_extract_from_every_source() {
+    let extractor = extractor();
+    assert_eq!(extract(&extractor, &[("authorization", "Bearer abc")]), Some(("abc".to_owned(), TokenSource::Bearer)));
+    assert_eq!(extract(&extractor, &[("authorization", "bearer  abc ")]), Some(("abc".to_owned(), TokenSource::Bearer)));
+    assert_eq!(extract(&extractor, &[("x-access-token", "abc")]), Some(("abc".to_owned(), TokenSource::Metadata("x-access-token".into()))));
+    assert_eq!(extract(&extractor, &[("cookie", "theme=dark; session=abc; lang=en")]), Some(("abc".to_owned(), TokenSource::Cookie("session".into()))));
+    assert_eq!(extract(&extractor, &[("cookie", "session=\"abc\"")]), Some(("abc".to_owned(), TokenSource::Cookie("session".into()))));
+    //HTTP/2 cookie crumbs
+    assert_eq!(extract(&extractor, &[("cookie", "theme=dark"), ("cookie", "session=abc")]), Some(("abc".to_owned(), TokenSource::Cookie("session".into()))));
+
+    assert_eq!(extract(&extractor, &[]), None);
+    assert_eq!(extract(&extractor, &[("authorization", "Basic abc")]), None);
+    assert_eq!(extract(&extractor, &[("cookie", "session_id=abc; xsession=abc")]), None);
+    assert_eq!(extract(&TokenExtractor::new(), &[("authorization", "Bearer abc")]), None);
+    assert_eq!(TokenExtractor::default().sources(), [TokenSource::Bearer]);
+}
+
+#[test]
+fn should_follow_precedence_of_sources() {
+    let all = [("cookie", "session=cookie"), ("x-access-token", "metadata"), ("authorization", "Bearer bearer")];
+    assert_eq!(extract(&extractor(), &all), Some(("bearer".to_owned(), TokenSource::Bearer)));
+
+    let reversed = TokenExtractor::new().cookie("session").metadata("x-access-token").bearer();
+    assert_eq!(extract(&reversed, &all), Some(("cookie".to_owned(), TokenSource::Cookie("session".into()))));
+    assert_eq!(extract(&reversed, &all[1..]), Some(("metadata".to_owned(), TokenSource::Metadata("x-access-token".into()))));
+
+    //The first bearer value is used, skipping other schemes
+    assert_eq!(extract(&extractor(), &[("authorization", "Basic abc"), ("authorization", "Bearer second"), ("authorization", "Bearer third")]), Some(("second".to_owned(), TokenSource::Bearer)));
+}
+
+#[test]
+fn should_skip_empty_values() {
+    let extractor = extractor();
+    assert_eq!(extract(&extractor, &[("authorization", "Bearer "), ("x-access-token", "abc")]), Some(("abc".to_owned(), TokenSource::Metadata("x-access-token".into()))));
+    assert_eq!(extract(&extractor, &[("x-access-token", ""), ("cookie", "session=abc")]), Some(("abc".to_owned(), TokenSource::Cookie("session".into()))));
+    assert_eq!(extract(&extractor, &[("x-access-token", " "), ("cookie", "session=; session=\"\"")]), None);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn should_record_source_of_accepted_token() {
+    use tonic_interceptor::Interceptor;
+    use tonic_interceptor::config::BearerAuth;
+
+    let auth = BearerAuth::new("secret").extractor(extractor());
+    let call = |entries: &[(&'static str, &'static str)]| {
+        let mut extensions = http::Extensions::new();
+        let result = auth.on_request(&mut metadata(entries), &mut extensions).map(|status| status.message().to_owned());
+        (result, extensions.get::<TokenSource>().cloned())
+    };
+
+    assert_eq!(call(&[("cookie", "session=secret")]), (None, Some(TokenSource::Cookie("session".into()))));
+    assert_eq!(call(&[("authorization", "Bearer secret")]), (None, Some(TokenSource::Bearer)));
+    //Precedence is not bypassed by valid token of later source
+    assert_eq!(call(&[("authorization", "Bearer wrong"), ("x-access-token", "secret")]), (Some("invalid bearer token".to_owned()), None));
+    assert_eq!(call(&[]), (Some("missing bearer token".to_owned()), None));
+
+    let default = BearerAuth::new("secret");
+    assert!(default.on_request(&mut metadata(&[("x-access-token", "secret")]), &mut http::Extensions::new()).is_some());
+}
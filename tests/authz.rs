@@ -0,0 +1,213 @@
+#![cfg(feature = "tonic011")]
+#![allow(clippy::result_large_err)]
+
+use tonic_interceptor::Interceptor;
+use tonic_interceptor::authz::{self, MethodPattern, Rbac, Roles};
+use tonic_interceptor::asynchronous::{AsyncInterceptor, RequestHead};
+use tonic_interceptor::testing::poll_once;
+
+use tonic::Code;
+
+fn call(rbac: &Rbac, path: &str, roles: Option<&[&str]>) -> Result<(), (Code, String)> {
+    let uri: http::Uri = path.parse().expect("valid uri");
+    let mut extensions = http::Extensions::new();
+    if let Some(roles) = roles {
+        extensions.insert(roles.iter().copied().collect::<Roles>());
+    }
+    match rbac.on_request_headers(&uri, &mut http::HeaderMap::new(), &mut extensions) {
+        None => Ok(()),
+        Some(status) => Err((status.code(), status.message().to_owned())),
+    }
+}
+
+fn denied(path: &str) -> Result<(), (Code, String)> {
+    Err((Code::PermissionDenied, format!("method '{}' is not allowed", path)))
+}
+
+#[test]
+fn should_match_wildcard_patterns() {
+    let cases = vec![
+        ("*", "/pkg.Echo/Unary", true),
+        ("/pkg.Echo/Unary", "/pkg.Echo/Unary", true),
+        ("pkg.Echo/Unary", "/pkg.Echo/Unary", true),
+        ("/pkg.Echo/Unary", "/pkg.Echo/UnaryStream", false),
+        ("Echo/*", "/pkg.Echo/Unary", true),
+        ("Echo/*", "/pkg.v1.Echo/Unary", true),
+        ("Echo/*", "/pkg.EchoAdmin/Unary", false),
+        ("other.Echo/*", "/pkg.Echo/Unary", false),
+        ("pkg.*/*", "/pkg.Echo/Unary", true),
+        ("*/Get*", "/pkg.Users/GetUser", true),
+        ("*/Get*", "/pkg.Users/Get", true),
+        ("*/Get*", "/pkg.Users/ForgetUser", false),
+        ("*/*User", "/pkg.Users/GetUser", true),
+        ("*/G*t*r", "/pkg.Users/GetUser", true),
+        ("*/G*t*r", "/pkg.Users/GetUsers", false),
+        ("Get*", "/pkg.Users/GetUser", true),
+        ("Get*", "/pkg.Users/ListUsers", false),
+    ];
+    for (pattern, path, expected) in cases {
+        assert_eq!(MethodPattern::new(pattern).matches(path), expected, "'{}' against '{}'", pattern, path);
+    }
+    assert_eq!(MethodPattern::new("/pkg.Echo/*").to_string(), "pkg.Echo/*");
+}
+
+#[test]
+fn should_evaluate_rules_by_roles() {
+    let rbac = Rbac::builder().allow("admin", "AdminService/*").allow("reader", "*/Get*").build();
+    assert_eq!(rbac.rules().len(), 2);
+
+    assert_eq!(call(&rbac, "/pkg.AdminService/Reset", Some(&["admin"])), Ok(()));
+    assert_eq!(call(&rbac, "/pkg.AdminService/Reset", Some(&["reader"])), denied("/pkg.AdminService/Reset"));
+    assert_eq!(call(&rbac, "/pkg.AdminService/GetStats", Some(&["reader"])), Ok(()));
+    assert_eq!(call(&rbac, "/pkg.Users/GetUser", Some(&["reader"])), Ok(()));
+    assert_eq!(call(&rbac, "/pkg.Users/DeleteUser", Some(&["reader"])), denied("/pkg.Users/DeleteUser"));
+    assert_eq!(call(&rbac, "/pkg.Users/DeleteUser", Some(&["admin", "reader"])), denied("/pkg.Users/DeleteUser"));
+    assert_eq!(call(&rbac, "/pkg.Users/GetUser", Some(&["admin", "reader"])), Ok(()));
+    //Deny by default
+    assert_eq!(call(&rbac, "/pkg.Users/GetUser", Some(&[])), denied("/pkg.Users/GetUser"));
+    assert_eq!(call(&Rbac::builder().build(), "/pkg.Users/GetUser", Some(&["admin"])), denied("/pkg.Users/GetUser"));
+}
+
+#[test]
+fn should_give_precedence_to_deny() {
+    let rbac = Rbac::builder().deny("reader", "*/GetSecret").allow("reader", "*/Get*").allow("auditor", "*").build();
+
+    assert_eq!(call(&rbac, "/pkg.Vault/GetSecret", Some(&["reader"])), denied("/pkg.Vault/GetSecret"));
+    assert_eq!(call(&rbac, "/pkg.Vault/GetStatus", Some(&["reader"])), Ok(()));
+    //Deny of one role revokes access granted by other role
+    assert_eq!(call(&rbac, "/pkg.Vault/GetSecret", Some(&["auditor", "reader"])), denied("/pkg.Vault/GetSecret"));
+    assert_eq!(call(&rbac, "/pkg.Vault/GetSecret", Some(&["auditor"])), Ok(()));
+
+    //Order of rules is irrelevant
+    let rbac = Rbac::builder().allow("reader", "*/Get*").deny("reader", "*/GetSecret").build();
+    assert_eq!(call(&rbac, "/pkg.Vault/GetSecret", Some(&["reader"])), denied("/pkg.Vault/GetSecret"));
+}
+
+#[test]
+fn should_require_identity() {
+    let rbac = Rbac::builder().allow("admin", "*").build();
+    assert_eq!(call(&rbac, "/pkg.Echo/Unary", None), Err((Code::Unauthenticated, "method '/pkg.Echo/Unary' requires authentication".to_owned())));
+
+    let mut metadata = tonic::metadata::MetadataMap::new();
+    let mut extensions = http::Extensions::new();
+    extensions.insert(Roles(vec!["admin".to_owned()]));
+    let uri = http::Uri::from_static("/pkg.Echo/Unary");
+    assert!(rbac.on_request_with_uri(&uri, &mut metadata, &mut extensions).is_none());
+    assert_eq!(rbac.on_request(&mut metadata, &mut extensions).expect("to fail").code(), Code::Internal);
+}
+
+#[test]
+fn should_allow_unauthenticated_methods() {
+    let rbac = Rbac::builder().deny("guest", "*").allow_unauthenticated("/grpc.health.v1.Health/*").allow_unauthenticated("Login").build();
+
+    assert_eq!(call(&rbac, "/grpc.health.v1.Health/Check", None), Ok(()));
+    assert_eq!(call(&rbac, "/pkg.Auth/Login", None), Ok(()));
+    //Public methods are not subject to rules
+    assert_eq!(call(&rbac, "/pkg.Auth/Login", Some(&["guest"])), Ok(()));
+    assert_eq!(call(&rbac, "/pkg.Auth/Logout", None).unwrap_err().0, Code::Unauthenticated);
+    assert_eq!(call(&rbac, "/pkg.Auth/Logout", Some(&["guest"])), denied("/pkg.Auth/Logout"));
+}
+
+//Tenant of caller, as established by authentication
+struct TenantId(String);
+
+//Callers may only access their own tenant
+fn own_tenant(tenant: Option<&TenantId>, method: &str, metadata: &tonic::metadata::MetadataMap) -> Result<(), tonic::Status> {
+    let tenant = tenant.ok_or_else(|| tonic::Status::unauthenticated("missing tenant"))?;
+    match metadata.get("x-tenant") {
+        Some(requested) if requested == tenant.0.as_str() => Ok(()),
+        _ => Err(tonic::Status::permission_denied(format!("method '{}' is not allowed for tenant '{}'", method, tenant.0))),
+    }
+}
+
+fn tenant_request(tenant: Option<&str>, requested: Option<&'static str>) -> (http::HeaderMap, http::Extensions) {
+    let mut headers = http::HeaderMap::new();
+    if let Some(requested) = requested {
+        headers.insert("x-tenant", http::HeaderValue::from_static(requested));
+    }
+    let mut extensions = http::Extensions::new();
+    if let Some(tenant) = tenant {
+        extensions.insert(TenantId(tenant.to_owned()));
+    }
+    (headers, extensions)
+}
+
+#[test]
+fn should_authorize_by_closure() {
+    let interceptor = authz::authorize(own_tenant);
+    let call = |tenant, requested| {
+        let (mut headers, mut extensions) = tenant_request(tenant, requested);
+        let uri = http::Uri::from_static("/pkg.Orders/List");
+        interceptor.on_request_headers(&uri, &mut headers, &mut extensions).map(|status| (status.code(), status.message().to_owned()))
+    };
+
+    assert_eq!(call(Some("acme"), Some("acme")), None);
+    assert_eq!(call(Some("acme"), Some("globex")), Some((Code::PermissionDenied, "method '/pkg.Orders/List' is not allowed for tenant 'acme'".to_owned())));
+    assert_eq!(call(Some("acme"), None), Some((Code::PermissionDenied, "method '/pkg.Orders/List' is not allowed for tenant 'acme'".to_owned())));
+    assert_eq!(call(None, Some("acme")), Some((Code::Unauthenticated, "missing tenant".to_owned())));
+
+    let (_, mut extensions) = tenant_request(Some("acme"), None);
+    assert_eq!(interceptor.on_request(&mut tonic::metadata::MetadataMap::new(), &mut extensions).expect("to fail").code(), Code::Internal);
+}
+
+#[test]
+fn should_authorize_by_async_closure() {
+    let interceptor = authz::authorize_async(|tenant: Option<&TenantId>, method: &str, metadata: &tonic::metadata::MetadataMap| {
+        //Policy engine would be consulted with owned input
+        let verdict = own_tenant(tenant, method, metadata);
+        async move {
+            verdict
+        }
+    });
+    let call = |tenant, requested| {
+        let (headers, extensions) = tenant_request(tenant, requested);
+        let request = RequestHead::new(http::Uri::from_static("/pkg.Orders/List"), tonic::metadata::MetadataMap::from_headers(headers), extensions);
+        poll_once(interceptor.on_request(request))
+    };
+
+    let head = call(Some("acme"), Some("acme")).expect("to pass");
+    assert_eq!(head.extensions.get::<TenantId>().unwrap().0, "acme");
+    assert_eq!(head.headers.get("x-tenant").unwrap(), "acme");
+    assert_eq!(call(Some("acme"), Some("globex")).unwrap_err().code(), Code::PermissionDenied);
+    assert_eq!(call(None, None).unwrap_err().code(), Code::Unauthenticated);
+
+    let roles = authz::authorize_async(|roles: Option<&Roles>, _: &str, _: &tonic::metadata::MetadataMap| {
+        let rejection = match roles {
+            Some(roles) if roles.has("admin") => None,
+            _ => Some(tonic::Status::permission_denied("admin only")),
+        };
+        async move {
+            rejection
+        }
+    });
+    let request = RequestHead::new(http::Uri::from_static("/pkg.Orders/List"), tonic::metadata::MetadataMap::new(), http::Extensions::new());
+    assert_eq!(poll_once(roles.on_request(request)).unwrap_err().message(), "admin only");
+}
+
+#[cfg(feature = "serde")]
+mod common;
+
+#[cfg(feature = "serde")]
+#[test]
+fn should_deserialize_rules() {
+    use common::value::Value;
+    use serde::Deserialize;
+
+    let rbac = Rbac::deserialize(Value::Map(vec![
+        ("rules", Value::List(vec![
+            Value::Map(vec![("role", Value::Str("admin")), ("allow", Value::List(vec![Value::Str("AdminService/*")]))]),
+            Value::Map(vec![("role", Value::Str("reader")), ("allow", Value::List(vec![Value::Str("*/Get*")])), ("deny", Value::List(vec![Value::Str("*/GetSecret")]))]),
+        ])),
+        ("allow_unauthenticated", Value::List(vec![Value::Str("/grpc.health.v1.Health/*")])),
+    ])).expect("valid rbac");
+    assert_eq!(rbac.rules().len(), 3);
+    assert_eq!(call(&rbac, "/pkg.AdminService/Reset", Some(&["admin"])), Ok(()));
+    assert_eq!(call(&rbac, "/pkg.Users/GetUser", Some(&["reader"])), Ok(()));
+    assert_eq!(call(&rbac, "/pkg.Vault/GetSecret", Some(&["reader"])), denied("/pkg.Vault/GetSecret"));
+    assert_eq!(call(&rbac, "/grpc.health.v1.Health/Check", None), Ok(()));
+
+    let error = Rbac::deserialize(Value::Map(vec![("rules", Value::List(vec![Value::Map(vec![("allow", Value::List(vec![]))])]))])).unwrap_err();
+    assert_eq!(error.to_string(), "missing field `role`");
+    let error = Rbac::deserialize(Value::Map(vec![("roles", Value::List(vec![]))])).unwrap_err();
+    assert_eq!(error.to_string(), "unknown field `roles`, expected `rules` or `allow_unauthenticated`");
+}
@@ -0,0 +1,78 @@
+#![cfg(feature = "tonic011")]
+#![allow(clippy::result_large_err)]
+
+use tonic_interceptor::{InterceptorFn, InterceptorService};
+use tonic_interceptor::testing::{poll_once, service_fn};
+
+use tonic::Status;
+use tower::{ServiceBuilder, ServiceExt};
+use tower_service::Service;
+
+use core::task;
+use core::pin::Pin;
+
+//Body of body-adapting layer (e.g. compression), which cannot be created out of nothing
+struct Compressed<B> {
+    inner: B,
+    level: u8,
+}
+
+impl<B: http_body::Body + Unpin> http_body::Body for Compressed<B> {
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_data(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Option<Result<Self::Data, Self::Error>>> {
+        Pin::new(&mut self.inner).poll_data(cx)
+    }
+
+    fn poll_trailers(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Result<Option<http::HeaderMap>, Self::Error>> {
+        Pin::new(&mut self.inner).poll_trailers(cx)
+    }
+}
+
+fn compress(response: http::Response<tonic::body::BoxBody>) -> http::Response<Compressed<tonic::body::BoxBody>> {
+    response.map(|inner| Compressed {
+        inner,
+        level: 6,
+    })
+}
+
+fn empty() -> Compressed<tonic::body::BoxBody> {
+    Compressed {
+        inner: tonic::body::empty_body(),
+        level: 0,
+    }
+}
+
+#[test]
+fn should_compose_over_layer_with_non_default_body() {
+    let interceptor = InterceptorFn {
+        on_request: |headers: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions| match headers.contains_key("x-reject") {
+            true => Some(Status::permission_denied("BAD")),
+            false => None,
+        },
+        on_response: |_: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions| {
+        }
+    };
+
+    let svc = service_fn(|_: http::Request<()>| Ok::<_, Status>(http::Response::new(tonic::body::empty_body())));
+    let mut service = ServiceBuilder::new().layer(tonic_interceptor::interceptor_with_body(interceptor.clone(), empty)).map_response(compress).service(svc);
+
+    let response = poll_once(service.call(http::Request::new(()))).expect("response");
+    assert!(response.headers().get("grpc-status").is_none());
+    assert_eq!(response.body().level, 6);
+
+    let request = http::Request::builder().header("x-reject", "1").body(()).unwrap();
+    let response = poll_once(service.call(request)).expect("response");
+    assert_eq!(response.headers().get("grpc-status").unwrap(), "7");
+    assert_eq!(response.body().level, 0);
+
+    let mut service = InterceptorService::new(interceptor.clone(), svc.map_response(compress)).with_body(empty);
+    let request = http::Request::builder().header("x-reject", "1").body(()).unwrap();
+    let response = poll_once(service.call(request)).expect("response");
+    assert_eq!(response.headers().get("grpc-status").unwrap(), "7");
+
+    let mut service = ServiceBuilder::new().layer(tonic_interceptor::interceptor_arc(interceptor).with_body(empty)).map_response(compress).service(svc);
+    let response = poll_once(service.call(http::Request::new(()))).expect("response");
+    assert_eq!(response.body().level, 6);
+}
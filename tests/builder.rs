@@ -0,0 +1,109 @@
+#![cfg(feature = "tonic011")]
+#![allow(clippy::result_large_err)]
+
+use tonic_interceptor::{ErrorHandler, Interceptor, InterceptorBuilder, InterceptorService};
+use tonic_interceptor::builder::{Built, Noop};
+
+use tonic::Status;
+use tower::ServiceExt;
+
+use core::future;
+
+fn on_request(headers: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<Status> {
+    headers.insert("x-request", "1".parse().unwrap());
+    None
+}
+
+fn on_response(_: tonic::Code, headers: &mut http::HeaderMap, _: &http::Extensions) {
+    headers.insert("x-response", http::HeaderValue::from_static("1"));
+}
+
+#[derive(Debug, PartialEq)]
+struct Boom;
+
+fn on_error(_: &Boom) -> Option<Status> {
+    Some(Status::unavailable("boom"))
+}
+
+async fn check<I: Interceptor + ErrorHandler<Boom> + Clone>(interceptor: I, has_request: bool, has_response: bool, has_error: bool) {
+    let svc = tower::service_fn(|request: http::Request<()>| {
+        let result = match request.headers().get("x-fail") {
+            Some(_) => Err(Boom),
+            None => {
+                let mut response = http::Response::builder().header("grpc-status", "0");
+                if let Some(value) = request.headers().get("x-request") {
+                    response = response.header("x-seen", value);
+                }
+                Ok(response.body(()).unwrap())
+            }
+        };
+        future::ready(result)
+    });
+    let service = InterceptorService::new(interceptor.clone(), svc).with_error_handler(interceptor);
+
+    let response = service.clone().oneshot(http::Request::new(())).await.expect("success");
+    assert_eq!(response.headers().get("grpc-status").unwrap(), "0");
+    assert_eq!(response.headers().contains_key("x-seen"), has_request);
+    assert_eq!(response.headers().contains_key("x-response"), has_response);
+
+    let request = http::Request::builder().header("x-fail", "1").body(()).unwrap();
+    match service.oneshot(request).await {
+        Ok(response) => {
+            assert!(has_error);
+            assert_eq!(response.headers().get("grpc-status").unwrap(), "14");
+            assert_eq!(response.headers().get("grpc-message").unwrap(), "boom");
+            assert_eq!(response.headers().contains_key("x-response"), has_response);
+        },
+        Err(error) => {
+            assert!(!has_error);
+            assert_eq!(error, Boom);
+        }
+    }
+}
+
+#[test]
+fn should_build_noop_zero_sized_interceptor() {
+    let interceptor: Built = InterceptorBuilder::new().build();
+    assert_eq!(core::mem::size_of_val(&interceptor), 0);
+
+    let copy = interceptor;
+    let mut headers = tonic::metadata::MetadataMap::new();
+    assert!(copy.on_request(&mut headers, &mut http::Extensions::new()).is_none());
+    assert!(headers.is_empty());
+    assert!(ErrorHandler::<Boom>::on_error(&interceptor, &Boom).is_none());
+
+    let interceptor: Built<Noop, _> = InterceptorBuilder::new().on_response(|_: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions| ()).build();
+    assert_eq!(core::mem::size_of_val(&interceptor), 0);
+}
+
+#[tokio::test]
+async fn should_build_every_subset_of_callbacks() {
+    check(InterceptorBuilder::new().build(), false, false, false).await;
+    check(InterceptorBuilder::new().on_request(on_request).build(), true, false, false).await;
+    check(InterceptorBuilder::new().on_response(on_response).build(), false, true, false).await;
+    check(InterceptorBuilder::new().on_error(on_error).build(), false, false, true).await;
+    check(InterceptorBuilder::new().on_request(on_request).on_response(on_response).build(), true, true, false).await;
+    check(InterceptorBuilder::new().on_request(on_request).on_error(on_error).build(), true, false, true).await;
+    check(InterceptorBuilder::new().on_response(on_response).on_error(on_error).build(), false, true, true).await;
+    check(InterceptorBuilder::new().on_error(on_error).on_response(on_response).on_request(on_request).build(), true, true, true).await;
+}
+
+#[tokio::test]
+async fn should_capture_state_in_closures() {
+    let name = http::HeaderValue::from_static("builder");
+    let interceptor = InterceptorBuilder::new().on_request(|headers, _| match headers.contains_key("x-reject") {
+        true => Some(Status::permission_denied("rejected")),
+        false => None,
+    }).on_response(move |_, headers, _| {
+        headers.insert("x-name", name.clone());
+    }).build();
+
+    let svc = tower::service_fn(|_: http::Request<()>| future::ready(Ok::<_, Status>(http::Response::new(()))));
+    let service = tonic_interceptor::interceptor(interceptor).call_on_response_for_rejections(true);
+    let service = tower_layer::Layer::layer(&service, svc);
+
+    let request = http::Request::builder().header("x-reject", "1").body(()).unwrap();
+    let response = service.oneshot(request).await.expect("success");
+    assert_eq!(response.headers().get("grpc-status").unwrap(), "7");
+    assert_eq!(response.headers().get("x-name").unwrap(), "builder");
+}
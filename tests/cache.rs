@@ -0,0 +1,176 @@
+#![cfg(all(feature = "tonic011", feature = "cache"))]
+
+use tonic_interceptor::cache::UnaryCache;
+use tonic_interceptor::testing::{poll_once, service_fn, TestTimer};
+
+use tower_layer::Layer;
+use tower_service::Service;
+use http_body::Body;
+
+use core::pin::Pin;
+use core::time::Duration;
+use core::convert::Infallible;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+//Body with data chunks, which ends with trailers
+#[derive(Default)]
+struct Reply {
+    data: Vec<bytes::Bytes>,
+    trailers: Option<http::HeaderMap>,
+}
+
+impl Body for Reply {
+    type Data = bytes::Bytes;
+    type Error = Infallible;
+
+    fn poll_data(mut self: Pin<&mut Self>, _: &mut core::task::Context<'_>) -> core::task::Poll<Option<Result<Self::Data, Self::Error>>> {
+        match self.data.is_empty() {
+            true => core::task::Poll::Ready(None),
+            false => core::task::Poll::Ready(Some(Ok(self.data.remove(0)))),
+        }
+    }
+
+    fn poll_trailers(mut self: Pin<&mut Self>, _: &mut core::task::Context<'_>) -> core::task::Poll<Result<Option<http::HeaderMap>, Self::Error>> {
+        core::task::Poll::Ready(Ok(self.trailers.take()))
+    }
+}
+
+//Returns data and trailers
+fn collect<B: Body<Data = bytes::Bytes> + Unpin>(mut body: B) -> (Vec<u8>, Option<http::HeaderMap>) {
+    let mut data = Vec::new();
+    while let Some(chunk) = poll_once(core::future::poll_fn(|ctx| Pin::new(&mut body).poll_data(ctx))) {
+        data.extend_from_slice(&chunk.ok().unwrap());
+    }
+    let trailers = poll_once(core::future::poll_fn(|ctx| Pin::new(&mut body).poll_trailers(ctx))).ok().unwrap();
+    (data, trailers)
+}
+
+//Service replying with its call number as body, split into two chunks
+fn service(calls: Arc<AtomicUsize>, code: &'static str) -> impl Service<http::Request<()>, Response = http::Response<Reply>, Error = Infallible> {
+    service_fn(move |_: http::Request<()>| {
+        let call = calls.fetch_add(1, Ordering::SeqCst);
+        let mut trailers = http::HeaderMap::new();
+        trailers.insert("grpc-status", http::HeaderValue::from_static(code));
+        let body = Reply {
+            data: vec![bytes::Bytes::from_static(b"call-"), bytes::Bytes::from(call.to_string())],
+            trailers: Some(trailers),
+        };
+        Ok(http::Response::builder().header("content-type", "application/grpc").header("x-served", call.to_string()).body(body).unwrap())
+    })
+}
+
+fn request(path: &str, tenant: Option<&'static str>) -> http::Request<()> {
+    let mut request = http::Request::builder().uri(path);
+    if let Some(tenant) = tenant {
+        request = request.header("x-tenant", tenant);
+    }
+    request.body(()).unwrap()
+}
+
+//Returns body and whether it was replayed from cache
+fn call<S: Service<http::Request<()>, Response = http::Response<tonic_interceptor::cache::CachedBody<Reply>>, Error = Infallible>>(svc: &mut S, path: &str, tenant: Option<&'static str>) -> (String, bool) {
+    let response = poll_once(svc.call(request(path, tenant))).unwrap();
+    let is_cached = response.body().is_cached();
+    let served = response.headers().get("x-served").unwrap().to_str().unwrap().to_owned();
+    let (data, trailers) = collect(response.into_body());
+    let data = String::from_utf8(data).unwrap();
+    assert_eq!(data, format!("call-{}", served));
+    assert!(trailers.unwrap().contains_key("grpc-status"));
+    (data, is_cached)
+}
+
+#[test]
+fn should_replay_cached_response() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let cache = UnaryCache::new("/pkg.Config/*", Duration::from_secs(60));
+    let mut svc = cache.layer().layer(service(calls.clone(), "0"));
+
+    assert_eq!(call(&mut svc, "/pkg.Config/Get", None), ("call-0".to_owned(), false));
+    assert_eq!(call(&mut svc, "/pkg.Config/Get", None), ("call-0".to_owned(), true));
+    assert_eq!(call(&mut svc, "/pkg.Config/List", None), ("call-1".to_owned(), false));
+    assert_eq!(call(&mut svc, "/pkg.Config/List", None), ("call-1".to_owned(), true));
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+    assert_eq!(cache.len(), 2);
+
+    //Other methods are not cached
+    assert_eq!(call(&mut svc, "/pkg.Users/Get", None), ("call-2".to_owned(), false));
+    assert_eq!(call(&mut svc, "/pkg.Users/Get", None), ("call-3".to_owned(), false));
+    assert_eq!(cache.len(), 2);
+
+    cache.clear();
+    assert!(cache.is_empty());
+    assert_eq!(call(&mut svc, "/pkg.Config/Get", None), ("call-4".to_owned(), false));
+}
+
+#[test]
+fn should_vary_by_metadata() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let cache = UnaryCache::new("/pkg.Config/Get", Duration::from_secs(60)).vary("x-tenant");
+    let mut svc = cache.layer().layer(service(calls.clone(), "0"));
+
+    assert_eq!(call(&mut svc, "/pkg.Config/Get", Some("a")), ("call-0".to_owned(), false));
+    assert_eq!(call(&mut svc, "/pkg.Config/Get", Some("b")), ("call-1".to_owned(), false));
+    assert_eq!(call(&mut svc, "/pkg.Config/Get", None), ("call-2".to_owned(), false));
+    assert_eq!(call(&mut svc, "/pkg.Config/Get", Some("a")), ("call-0".to_owned(), true));
+    assert_eq!(call(&mut svc, "/pkg.Config/Get", Some("b")), ("call-1".to_owned(), true));
+    assert_eq!(call(&mut svc, "/pkg.Config/Get", None), ("call-2".to_owned(), true));
+    assert_eq!(cache.len(), 3);
+}
+
+#[test]
+fn should_expire_and_evict_entries() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let timer = TestTimer::new();
+    let cache = UnaryCache::new("*", Duration::from_millis(50)).vary("x-tenant").max_entries(2).clock(timer.clone());
+    let mut svc = cache.layer().layer(service(calls.clone(), "0"));
+
+    assert_eq!(call(&mut svc, "/pkg.Config/Get", Some("a")), ("call-0".to_owned(), false));
+    timer.advance(Duration::from_millis(5));
+    assert_eq!(call(&mut svc, "/pkg.Config/Get", Some("b")), ("call-1".to_owned(), false));
+    //Entry closest to expiration is evicted
+    assert_eq!(call(&mut svc, "/pkg.Config/Get", Some("c")), ("call-2".to_owned(), false));
+    assert_eq!(cache.len(), 2);
+    assert_eq!(call(&mut svc, "/pkg.Config/Get", Some("b")), ("call-1".to_owned(), true));
+    assert_eq!(call(&mut svc, "/pkg.Config/Get", Some("c")), ("call-2".to_owned(), true));
+    assert_eq!(call(&mut svc, "/pkg.Config/Get", Some("a")), ("call-3".to_owned(), false));
+
+    //Entry expires exactly after its time to live
+    timer.advance(Duration::from_millis(49));
+    assert_eq!(call(&mut svc, "/pkg.Config/Get", Some("a")), ("call-3".to_owned(), true));
+    timer.advance(Duration::from_millis(1));
+    assert_eq!(call(&mut svc, "/pkg.Config/Get", Some("a")), ("call-4".to_owned(), false));
+    assert_eq!(cache.len(), 2);
+}
+
+#[test]
+fn should_not_cache_large_or_failed_responses() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let cache = UnaryCache::new("*", Duration::from_secs(60)).max_entry_size(5);
+    let mut svc = cache.layer().layer(service(calls.clone(), "0"));
+
+    assert_eq!(call(&mut svc, "/pkg.Config/Get", None), ("call-0".to_owned(), false));
+    assert_eq!(call(&mut svc, "/pkg.Config/Get", None), ("call-1".to_owned(), false));
+    assert!(cache.is_empty());
+
+    //Body of exactly maximum size is cached
+    let cache = UnaryCache::new("*", Duration::from_secs(60)).max_entry_size(6);
+    let mut svc = cache.layer().layer(service(Arc::new(AtomicUsize::new(0)), "0"));
+    assert_eq!(call(&mut svc, "/pkg.Config/Get", None), ("call-0".to_owned(), false));
+    assert_eq!(call(&mut svc, "/pkg.Config/Get", None), ("call-0".to_owned(), true));
+
+    let cache = UnaryCache::new("*", Duration::from_secs(60));
+    let mut svc = cache.layer().layer(service(Arc::new(AtomicUsize::new(0)), "14"));
+    assert_eq!(call(&mut svc, "/pkg.Config/Get", None), ("call-0".to_owned(), false));
+    assert_eq!(call(&mut svc, "/pkg.Config/Get", None), ("call-1".to_owned(), false));
+    assert!(cache.is_empty());
+
+    //Trailers-only response is passed through
+    let cache = UnaryCache::new("*", Duration::from_secs(60));
+    let mut svc = cache.layer().layer(service_fn(|_: http::Request<()>| {
+        Ok::<_, Infallible>(http::Response::builder().header("grpc-status", "0").body(Reply::default()).unwrap())
+    }));
+    let response = poll_once(svc.call(request("/pkg.Config/Get", None))).unwrap();
+    assert_eq!(collect(response.into_body()), (Vec::new(), None));
+    assert!(cache.is_empty());
+}
@@ -0,0 +1,95 @@
+#![cfg(feature = "tonic011")]
+#![allow(clippy::result_large_err)]
+
+use tonic_interceptor::InterceptorService;
+use tonic_interceptor::client::ClientInterceptor;
+use tonic_interceptor::routing::{Canary, Variant};
+use tonic_interceptor::testing::{poll_once, service_fn};
+
+use tonic::Status;
+use tower_service::Service;
+
+const KEYS: usize = 100_000;
+
+fn key(idx: usize) -> String {
+    format!("user-{}", idx)
+}
+
+#[test]
+fn should_distribute_keys_by_percentage() {
+    for percent in [0u8, 1, 5, 10, 25, 50, 90, 100] {
+        let canary = Canary::new(percent);
+        let count = (0..KEYS).filter(|idx| canary.variant(key(*idx).as_bytes()) == Variant::Canary).count();
+        let expected = KEYS * usize::from(percent) / 100;
+        assert!(count.abs_diff(expected) <= KEYS / 200, "{}%: {} of {}", percent, count, KEYS);
+    }
+    assert_eq!(Canary::new(200).percent(), 100);
+}
+
+#[test]
+fn should_keep_key_sticky() {
+    let canary = Canary::new(10);
+    let other = Canary::new(10).header("x-variant");
+    let wider = Canary::new(20);
+    for idx in 0..KEYS {
+        let key = key(idx);
+        let variant = canary.variant(key.as_bytes());
+        assert_eq!(variant, canary.variant(key.as_bytes()));
+        assert_eq!(variant, other.variant(key.as_bytes()));
+        //Growing canary only moves stable keys to canary
+        if variant == Variant::Canary {
+            assert_eq!(wider.variant(key.as_bytes()), Variant::Canary);
+        }
+    }
+
+    //Hash is stable across processes and versions, hence pinned by percentage at which key becomes canary
+    let thresholds: Vec<_> = (0..8).map(|idx| (0..=100).find(|percent| Canary::new(*percent).variant(key(idx).as_bytes()) == Variant::Canary).unwrap()).collect();
+    assert_eq!(thresholds, [51, 78, 36, 37, 59, 72, 95, 56]);
+}
+
+//Returns stamp of request and response for caller `user`
+fn stamp(canary: Canary, user: Option<&'static str>) -> (Option<String>, Option<String>) {
+    let mut metadata = tonic::metadata::MetadataMap::new();
+    if let Some(user) = user {
+        metadata.insert("x-user-id", user.parse().unwrap());
+    }
+    assert!(canary.clone().client().on_request(&mut metadata, &mut http::Extensions::new()).is_none());
+    let request = metadata.get("x-canary").map(|value| value.to_str().unwrap().to_owned());
+
+    let mut service = InterceptorService::new(canary.server(), service_fn(|_: http::Request<()>| Ok::<_, Status>(http::Response::new(()))));
+    let mut req = http::Request::new(());
+    if let Some(user) = user {
+        req.headers_mut().insert("x-user-id", http::HeaderValue::from_static(user));
+    }
+    let response = poll_once(service.call(req)).expect("response");
+    (request, response.headers().get("x-canary").map(|value| value.to_str().unwrap().to_owned()))
+}
+
+#[test]
+fn should_stamp_request_and_response_consistently() {
+    let canary = Canary::new(50).key("x-user-id");
+    let mut seen = [false; 2];
+    for user in ["alice", "bob", "carol", "dave", "eve", "mallory", "trent", "victor"] {
+        let expected = canary.variant(user.as_bytes());
+        seen[(expected == Variant::Canary) as usize] = true;
+        let (request, response) = stamp(canary.clone(), Some(user));
+        assert_eq!(request.as_deref(), Some(expected.as_str()));
+        assert_eq!(response.as_deref(), Some(expected.as_str()));
+    }
+    assert_eq!(seen, [true, true]);
+
+    assert_eq!(stamp(canary, None), (None, None));
+    assert_eq!(stamp(Canary::new(100), Some("alice")), (None, None));
+    assert_eq!(stamp(Canary::new(100).key("x-user-id"), Some("alice")), (Some("always".to_owned()), Some("always".to_owned())));
+    assert_eq!(stamp(Canary::new(0).key_with(|metadata| metadata.get("x-user-id").map(|_| "fixed".to_owned())), Some("alice")), (Some("never".to_owned()), Some("never".to_owned())));
+}
+
+#[test]
+fn should_keep_explicit_request_stamp() {
+    let client = Canary::new(100).key("x-user-id").client();
+    let mut metadata = tonic::metadata::MetadataMap::new();
+    metadata.insert("x-user-id", "alice".parse().unwrap());
+    metadata.insert("x-canary", "never".parse().unwrap());
+    assert!(client.on_request(&mut metadata, &mut http::Extensions::new()).is_none());
+    assert_eq!(metadata.get("x-canary").unwrap(), "never");
+}
@@ -0,0 +1,46 @@
+#![cfg(feature = "tonic011")]
+#![allow(clippy::result_large_err)]
+
+use tonic_interceptor::{Interceptor, InterceptorChain, BoxedInterceptor};
+use tonic_interceptor::testing::{EventKind, Recorder};
+
+use tonic::{Code, Status};
+
+#[test]
+fn should_run_requests_in_order_and_responses_in_reverse() {
+    let first = Recorder::new("first");
+    let second = first.share("second");
+    let chain = InterceptorChain::new().with(first.clone()).with(second.clone());
+    assert_eq!(chain.len(), 2);
+
+    let mut headers = tonic::metadata::MetadataMap::new();
+    assert!(chain.on_request(&mut headers, &mut http::Extensions::new()).is_none());
+    chain.on_response(Code::Ok, &mut http::HeaderMap::new(), &http::Extensions::new());
+    assert_eq!(first.sequence(), [
+        ("first", EventKind::Request),
+        ("second", EventKind::Request),
+        ("second", EventKind::Response(Code::Ok)),
+        ("first", EventKind::Response(Code::Ok)),
+    ]);
+}
+
+#[test]
+fn should_stop_at_first_rejection() {
+    let first = Recorder::new("first");
+    let second = first.share("second");
+    first.reject_nth(1, Status::permission_denied("first"));
+
+    let chain = vec![first.clone(), second].into_iter().map(|recorder| std::sync::Arc::new(recorder) as BoxedInterceptor).collect::<InterceptorChain>();
+    let uri = http::Uri::from_static("http://localhost/pkg.Service/Method");
+    let mut headers = http::HeaderMap::new();
+    let status = chain.on_request_headers(&uri, &mut headers, &mut http::Extensions::new()).expect("to reject");
+    assert_eq!(status.message(), "first");
+    assert_eq!(first.sequence(), [("first", EventKind::Request)]);
+}
+
+#[test]
+fn should_accept_everything_when_empty() {
+    let chain = InterceptorChain::default();
+    assert!(chain.is_empty());
+    assert!(chain.on_request(&mut tonic::metadata::MetadataMap::new(), &mut http::Extensions::new()).is_none());
+}
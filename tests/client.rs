@@ -0,0 +1,213 @@
+#![cfg(feature = "tonic011")]
+mod common;
+
+use common::EchoClient;
+use common::echo::EchoRequest;
+
+use tonic_interceptor::client::{self, ClientInterceptor};
+
+use tonic::Status;
+use tonic::metadata::MetadataMap;
+
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone, Default)]
+struct Tagger {
+    reject: bool,
+    seen: Arc<Mutex<Vec<String>>>,
+}
+
+impl ClientInterceptor for Tagger {
+    fn on_request(&self, headers: &mut MetadataMap, _: &mut http::Extensions) -> Option<Status> {
+        if self.reject {
+            return Some(Status::permission_denied("local rejection"));
+        }
+        headers.insert("x-tag", "client".parse().unwrap());
+        None
+    }
+
+    fn on_response(&self, headers: &MetadataMap, _: &http::Extensions) {
+        if let Some(tag) = headers.get("x-tag") {
+            self.seen.lock().unwrap().push(tag.to_str().unwrap().to_owned());
+        }
+    }
+}
+
+#[tokio::test]
+async fn should_modify_outgoing_metadata_and_observe_response() {
+    let (_, channel) = common::spawn_echo().await;
+    let interceptor = Tagger::default();
+
+    let channel = tower::ServiceBuilder::new().layer(client::interceptor(interceptor.clone())).service(channel);
+    let mut client = EchoClient::new(channel);
+
+    let response = client.unary(EchoRequest { message: "hello".to_owned() }).await.expect("success");
+    assert_eq!(response.get_ref().message, "hello");
+    assert_eq!(response.metadata().get("x-tag").expect("to have x-tag"), "client");
+    assert_eq!(*interceptor.seen.lock().unwrap(), ["client"]);
+}
+
+#[tokio::test]
+async fn should_return_rejection_as_call_status() {
+    let (service, channel) = common::spawn_echo().await;
+    let interceptor = Tagger {
+        reject: true,
+        ..Default::default()
+    };
+
+    let channel = tower::ServiceBuilder::new().layer(client::interceptor(interceptor.clone())).service(channel);
+    let mut client = EchoClient::new(channel);
+
+    let status = client.unary(EchoRequest { message: "hello".to_owned() }).await.expect_err("to reject");
+    assert_eq!(status.code(), tonic::Code::PermissionDenied);
+    assert_eq!(status.message(), "local rejection");
+    assert_eq!(service.calls(), 0);
+    assert!(interceptor.seen.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn should_propagate_server_status() {
+    let (_, channel) = common::spawn_echo().await;
+    let interceptor = Tagger::default();
+
+    let channel = client::ClientInterceptorService::new(interceptor.clone(), channel);
+    let mut client = EchoClient::new(channel);
+
+    let status = client.unary(EchoRequest { message: "error:5".to_owned() }).await.expect_err("to fail");
+    assert_eq!(status.code(), tonic::Code::NotFound);
+}
+
+#[derive(Clone, Default)]
+struct Completion {
+    events: Arc<Mutex<Vec<String>>>,
+}
+
+impl Completion {
+    fn events(&self) -> Vec<String> {
+        self.events.lock().unwrap().clone()
+    }
+}
+
+impl ClientInterceptor for Completion {
+    fn on_request(&self, _: &mut MetadataMap, _: &mut http::Extensions) -> Option<Status> {
+        None
+    }
+
+    fn on_response(&self, _: &MetadataMap, _: &http::Extensions) {
+        self.events.lock().unwrap().push("response".to_owned());
+    }
+
+    fn on_trailers(&self, trailers: &MetadataMap) {
+        let status = trailers.get("grpc-status").expect("to have grpc-status").to_str().unwrap();
+        self.events.lock().unwrap().push(format!("trailers:{}", status));
+    }
+
+    fn on_complete(&self, code: tonic::Code) {
+        self.events.lock().unwrap().push(format!("complete:{:?}", code));
+    }
+}
+
+#[tokio::test]
+async fn should_complete_unary_with_trailers() {
+    let (_, channel) = common::spawn_echo().await;
+    let interceptor = Completion::default();
+    let mut client = EchoClient::new(client::ClientInterceptorService::new(interceptor.clone(), channel));
+
+    client.unary(EchoRequest { message: "hello".to_owned() }).await.expect("success");
+    assert_eq!(interceptor.events(), ["response", "trailers:0", "complete:Ok"]);
+}
+
+#[tokio::test]
+async fn should_complete_unary_error_from_trailers_only_response() {
+    let (_, channel) = common::spawn_echo().await;
+    let interceptor = Completion::default();
+    let mut client = EchoClient::new(client::ClientInterceptorService::new(interceptor.clone(), channel));
+
+    let status = client.unary(EchoRequest { message: "error:3".to_owned() }).await.expect_err("to fail");
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    assert_eq!(interceptor.events(), ["response", "complete:InvalidArgument"]);
+}
+
+#[tokio::test]
+async fn should_complete_stream_with_trailers() {
+    let (_, channel) = common::spawn_echo().await;
+    let interceptor = Completion::default();
+    let mut client = EchoClient::new(client::ClientInterceptorService::new(interceptor.clone(), channel));
+
+    let mut stream = client.stream(EchoRequest { message: "a b c".to_owned() }).await.expect("success").into_inner();
+    let mut items = Vec::new();
+    while let Some(item) = stream.message().await.expect("message") {
+        items.push(item.message);
+    }
+    assert_eq!(items, ["a", "b", "c"]);
+    assert_eq!(interceptor.events(), ["response", "trailers:0", "complete:Ok"]);
+}
+
+#[tokio::test]
+async fn should_complete_stream_with_error_trailers() {
+    let (_, channel) = common::spawn_echo().await;
+    let interceptor = Completion::default();
+    let mut client = EchoClient::new(client::ClientInterceptorService::new(interceptor.clone(), channel));
+
+    let mut stream = client.stream(EchoRequest { message: "a error:14".to_owned() }).await.expect("success").into_inner();
+    let status = loop {
+        match stream.message().await {
+            Ok(Some(_)) => continue,
+            Ok(None) => panic!("stream should fail"),
+            Err(status) => break status,
+        }
+    };
+    assert_eq!(status.code(), tonic::Code::Unavailable);
+    assert_eq!(interceptor.events(), ["response", "trailers:14", "complete:Unavailable"]);
+}
+
+#[tokio::test]
+async fn should_complete_compressed_stream() {
+    use tonic::codec::CompressionEncoding;
+
+    let (_, channel) = common::spawn_echo_with(|server| server.send_compressed(CompressionEncoding::Gzip).accept_compressed(CompressionEncoding::Gzip)).await;
+    let interceptor = Completion::default();
+    let channel = client::ClientInterceptorService::new(interceptor.clone(), channel);
+    let mut client = EchoClient::new(channel).send_compressed(CompressionEncoding::Gzip).accept_compressed(CompressionEncoding::Gzip);
+
+    let mut stream = client.stream(EchoRequest { message: "a b".to_owned() }).await.expect("success").into_inner();
+    assert_eq!(stream.message().await.expect("message").expect("item").message, "a");
+    assert_eq!(stream.message().await.expect("message").expect("item").message, "b");
+    assert!(stream.message().await.expect("message").is_none());
+    assert_eq!(interceptor.events(), ["response", "trailers:0", "complete:Ok"]);
+}
+
+#[tokio::test]
+async fn should_complete_rejected_call() {
+    let interceptor = Tagger {
+        reject: true,
+        ..Default::default()
+    };
+    let completion = Completion::default();
+    let (service, channel) = common::spawn_echo().await;
+    let channel = tower::ServiceBuilder::new().layer(client::interceptor(completion.clone())).layer(client::interceptor(interceptor)).service(channel);
+    let mut client = EchoClient::new(channel);
+
+    let status = client.unary(EchoRequest { message: "hello".to_owned() }).await.expect_err("to reject");
+    assert_eq!(status.code(), tonic::Code::PermissionDenied);
+    assert_eq!(service.calls(), 0);
+    assert_eq!(completion.events(), ["complete:PermissionDenied"]);
+}
+
+#[test]
+fn should_complete_call_dropped_before_response() {
+    use tonic_interceptor::testing::with_noop_context;
+    use tower::Service;
+    use core::future::Future;
+
+    let completion = Completion::default();
+    let mut service = client::ClientInterceptorService::new(completion.clone(), tower::service_fn(|_: http::Request<()>| {
+        core::future::pending::<Result<http::Response<tonic::body::BoxBody>, core::convert::Infallible>>()
+    }));
+
+    let mut call = Box::pin(service.call(http::Request::new(())));
+    assert!(with_noop_context(|ctx| call.as_mut().poll(ctx)).is_pending());
+    assert!(completion.events().is_empty());
+    drop(call);
+    assert_eq!(completion.events(), ["complete:Cancelled"]);
+}
@@ -0,0 +1,331 @@
+//Hand written equivalent of `tonic-build` output for the following proto:
+//
+//```proto
+//syntax = "proto3";
+//package test;
+//
+//message EchoRequest { string message = 1; }
+//message EchoResponse { string message = 1; }
+//
+//service Echo {
+//    rpc Unary(EchoRequest) returns (EchoResponse);
+//    rpc Stream(EchoRequest) returns (stream EchoResponse);
+//}
+//```
+#![allow(dead_code, clippy::result_large_err)]
+
+#[cfg(feature = "serde")]
+pub mod value;
+
+pub mod echo {
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct EchoRequest {
+        #[prost(string, tag = "1")]
+        pub message: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct EchoResponse {
+        #[prost(string, tag = "1")]
+        pub message: String,
+    }
+
+    pub mod echo_client {
+        use tonic::codegen::*;
+
+        #[derive(Debug, Clone)]
+        pub struct EchoClient<T> {
+            inner: tonic::client::Grpc<T>,
+        }
+
+        impl<T> EchoClient<T> where T: tonic::client::GrpcService<tonic::body::BoxBody>, T::Error: Into<StdError>, T::ResponseBody: Body<Data = Bytes> + Send + 'static, <T::ResponseBody as Body>::Error: Into<StdError> + Send {
+            pub fn new(inner: T) -> Self {
+                Self {
+                    inner: tonic::client::Grpc::new(inner),
+                }
+            }
+
+            pub fn send_compressed(mut self, encoding: CompressionEncoding) -> Self {
+                self.inner = self.inner.send_compressed(encoding);
+                self
+            }
+
+            pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
+                self.inner = self.inner.accept_compressed(encoding);
+                self
+            }
+
+            async fn ready(&mut self) -> Result<(), tonic::Status> {
+                self.inner.ready().await.map_err(|error| tonic::Status::new(tonic::Code::Unknown, format!("Service was not ready: {}", error.into())))
+            }
+
+            pub async fn unary(&mut self, request: impl tonic::IntoRequest<super::EchoRequest>) -> Result<tonic::Response<super::EchoResponse>, tonic::Status> {
+                self.ready().await?;
+                let codec = tonic::codec::ProstCodec::default();
+                let path = http::uri::PathAndQuery::from_static("/test.Echo/Unary");
+                let mut req = request.into_request();
+                req.extensions_mut().insert(GrpcMethod::new("test.Echo", "Unary"));
+                self.inner.unary(req, path, codec).await
+            }
+
+            pub async fn stream(&mut self, request: impl tonic::IntoRequest<super::EchoRequest>) -> Result<tonic::Response<tonic::codec::Streaming<super::EchoResponse>>, tonic::Status> {
+                self.ready().await?;
+                let codec = tonic::codec::ProstCodec::default();
+                let path = http::uri::PathAndQuery::from_static("/test.Echo/Stream");
+                let mut req = request.into_request();
+                req.extensions_mut().insert(GrpcMethod::new("test.Echo", "Stream"));
+                self.inner.server_streaming(req, path, codec).await
+            }
+        }
+    }
+
+    pub mod echo_server {
+        use tonic::codegen::*;
+
+        #[async_trait]
+        pub trait Echo: Send + Sync + 'static {
+            async fn unary(&self, request: tonic::Request<super::EchoRequest>) -> Result<tonic::Response<super::EchoResponse>, tonic::Status>;
+
+            type StreamStream: tokio_stream::Stream<Item = Result<super::EchoResponse, tonic::Status>> + Send + 'static;
+
+            async fn stream(&self, request: tonic::Request<super::EchoRequest>) -> Result<tonic::Response<Self::StreamStream>, tonic::Status>;
+        }
+
+        #[derive(Debug)]
+        pub struct EchoServer<T: Echo> {
+            inner: Arc<T>,
+            accept_compression_encodings: EnabledCompressionEncodings,
+            send_compression_encodings: EnabledCompressionEncodings,
+        }
+
+        impl<T: Echo> EchoServer<T> {
+            pub fn new(inner: T) -> Self {
+                Self {
+                    inner: Arc::new(inner),
+                    accept_compression_encodings: Default::default(),
+                    send_compression_encodings: Default::default(),
+                }
+            }
+
+            pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
+                self.accept_compression_encodings.enable(encoding);
+                self
+            }
+
+            pub fn send_compressed(mut self, encoding: CompressionEncoding) -> Self {
+                self.send_compression_encodings.enable(encoding);
+                self
+            }
+        }
+
+        impl<T: Echo> Clone for EchoServer<T> {
+            fn clone(&self) -> Self {
+                Self {
+                    inner: self.inner.clone(),
+                    accept_compression_encodings: self.accept_compression_encodings,
+                    send_compression_encodings: self.send_compression_encodings,
+                }
+            }
+        }
+
+        impl<T: Echo> tonic::server::NamedService for EchoServer<T> {
+            const NAME: &'static str = "test.Echo";
+        }
+
+        impl<T, B> Service<http::Request<B>> for EchoServer<T> where T: Echo, B: Body + Send + 'static, B::Error: Into<StdError> + Send + 'static {
+            type Response = http::Response<tonic::body::BoxBody>;
+            type Error = std::convert::Infallible;
+            type Future = BoxFuture<Self::Response, Self::Error>;
+
+            fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+                Poll::Ready(Ok(()))
+            }
+
+            fn call(&mut self, req: http::Request<B>) -> Self::Future {
+                let inner = self.inner.clone();
+                let accept_compression_encodings = self.accept_compression_encodings;
+                let send_compression_encodings = self.send_compression_encodings;
+                match req.uri().path() {
+                    "/test.Echo/Unary" => {
+                        struct UnarySvc<T: Echo>(Arc<T>);
+                        impl<T: Echo> tonic::server::UnaryService<super::EchoRequest> for UnarySvc<T> {
+                            type Response = super::EchoResponse;
+                            type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+
+                            fn call(&mut self, request: tonic::Request<super::EchoRequest>) -> Self::Future {
+                                let inner = self.0.clone();
+                                Box::pin(async move { inner.unary(request).await })
+                            }
+                        }
+
+                        Box::pin(async move {
+                            let codec = tonic::codec::ProstCodec::default();
+                            let mut grpc = tonic::server::Grpc::new(codec).apply_compression_config(accept_compression_encodings, send_compression_encodings);
+                            Ok(grpc.unary(UnarySvc(inner), req).await)
+                        })
+                    },
+                    "/test.Echo/Stream" => {
+                        struct StreamSvc<T: Echo>(Arc<T>);
+                        impl<T: Echo> tonic::server::ServerStreamingService<super::EchoRequest> for StreamSvc<T> {
+                            type Response = super::EchoResponse;
+                            type ResponseStream = T::StreamStream;
+                            type Future = BoxFuture<tonic::Response<Self::ResponseStream>, tonic::Status>;
+
+                            fn call(&mut self, request: tonic::Request<super::EchoRequest>) -> Self::Future {
+                                let inner = self.0.clone();
+                                Box::pin(async move { inner.stream(request).await })
+                            }
+                        }
+
+                        Box::pin(async move {
+                            let codec = tonic::codec::ProstCodec::default();
+                            let mut grpc = tonic::server::Grpc::new(codec).apply_compression_config(accept_compression_encodings, send_compression_encodings);
+                            Ok(grpc.server_streaming(StreamSvc(inner), req).await)
+                        })
+                    },
+                    _ => Box::pin(async move {
+                        Ok(http::Response::builder()
+                            .status(200)
+                            .header("grpc-status", "12")
+                            .header("content-type", "application/grpc")
+                            .body(empty_body())
+                            .unwrap())
+                    }),
+                }
+            }
+        }
+    }
+}
+
+use echo::{EchoRequest, EchoResponse};
+
+use tonic::{Request, Response, Status};
+
+use core::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+///Echo implementation used by tests.
+///
+///- `Unary` returns the request message and copies every `x-*` request metadata onto response metadata.
+///  Message `error:<code>` makes it fail with the corresponding code instead.
+///- `Stream` returns one item per whitespace separated word of the message.
+///  Word `error:<code>` makes the stream fail with corresponding code at that point.
+///
+///Every call records its metadata and time of arrival.
+///Statuses pushed via `fail_next` are returned by subsequent calls instead of normal response.
+#[derive(Clone, Default)]
+pub struct EchoService {
+    pub calls: Arc<AtomicUsize>,
+    pub requests: Arc<Mutex<Vec<(tokio::time::Instant, tonic::metadata::MetadataMap)>>>,
+    pub failures: Arc<Mutex<VecDeque<Status>>>,
+}
+
+impl EchoService {
+    pub fn calls(&self) -> usize {
+        self.calls.load(Ordering::SeqCst)
+    }
+
+    pub fn fail_next(&self, status: Status) {
+        self.failures.lock().unwrap().push_back(status);
+    }
+
+    pub fn requests(&self) -> Vec<(tokio::time::Instant, tonic::metadata::MetadataMap)> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    fn record(&self, metadata: &tonic::metadata::MetadataMap) -> Result<(), Status> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        self.requests.lock().unwrap().push((tokio::time::Instant::now(), metadata.clone()));
+        match self.failures.lock().unwrap().pop_front() {
+            Some(status) => Err(status),
+            None => Ok(()),
+        }
+    }
+}
+
+fn parse_error(word: &str) -> Option<Status> {
+    let code = word.strip_prefix("error:")?;
+    let code = tonic::Code::from_i32(code.parse().expect("valid code"));
+    Some(Status::new(code, word))
+}
+
+#[tonic::async_trait]
+impl echo::echo_server::Echo for EchoService {
+    async fn unary(&self, request: Request<EchoRequest>) -> Result<Response<EchoResponse>, Status> {
+        self.record(request.metadata())?;
+
+        let (metadata, _, request) = request.into_parts();
+        if let Some(status) = parse_error(&request.message) {
+            return Err(status);
+        }
+
+        let mut response = Response::new(EchoResponse {
+            message: request.message,
+        });
+        for entry in metadata.iter() {
+            match entry {
+                tonic::metadata::KeyAndValueRef::Ascii(key, value) if key.as_str().starts_with("x-") => {
+                    response.metadata_mut().append(key.clone(), value.clone());
+                },
+                tonic::metadata::KeyAndValueRef::Binary(key, value) if key.as_str().starts_with("x-") => {
+                    response.metadata_mut().append_bin(key.clone(), value.clone());
+                },
+                _ => (),
+            }
+        }
+        Ok(response)
+    }
+
+    type StreamStream = Pin<Box<dyn tokio_stream::Stream<Item = Result<EchoResponse, Status>> + Send>>;
+
+    async fn stream(&self, request: Request<EchoRequest>) -> Result<Response<Self::StreamStream>, Status> {
+        self.record(request.metadata())?;
+
+        let items: Vec<_> = request.into_inner().message.split_whitespace().map(|word| match parse_error(word) {
+            Some(status) => Err(status),
+            None => Ok(EchoResponse {
+                message: word.to_owned(),
+            }),
+        }).collect();
+        Ok(Response::new(Box::pin(tokio_stream::iter(items))))
+    }
+}
+
+///Creates unary request with `hello` message on behalf of `user` given via `x-user` metadata
+pub fn user_request(user: &str) -> tonic::Request<EchoRequest> {
+    let mut request = tonic::Request::new(EchoRequest {
+        message: "hello".to_owned(),
+    });
+    request.metadata_mut().insert("x-user", user.parse().unwrap());
+    request
+}
+
+pub type EchoServer = echo::echo_server::EchoServer<EchoService>;
+pub type EchoClient<T> = echo::echo_client::EchoClient<T>;
+
+///Binds listener on random local port, returning incoming connections and URL to connect to
+pub async fn listen() -> (tokio_stream::wrappers::TcpListenerStream, String) {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.expect("bind");
+    let addr = format!("http://{}", listener.local_addr().expect("local addr"));
+    (tokio_stream::wrappers::TcpListenerStream::new(listener), addr)
+}
+
+///Starts echo server without any layers, returning its service state and channel to it
+pub async fn spawn_echo() -> (EchoService, tonic::transport::Channel) {
+    spawn_echo_with(|server| server).await
+}
+
+///Starts echo server configured by `configure`, returning its service state and channel to it
+pub async fn spawn_echo_with(configure: impl FnOnce(EchoServer) -> EchoServer) -> (EchoService, tonic::transport::Channel) {
+    let service = EchoService::default();
+    let (incoming, addr) = listen().await;
+    let server = tonic::transport::Server::builder().add_service(configure(EchoServer::new(service.clone())));
+    tokio::spawn(server.serve_with_incoming(incoming));
+    (service, connect(addr).await)
+}
+
+pub async fn connect(addr: String) -> tonic::transport::Channel {
+    tonic::transport::Endpoint::from_shared(addr).expect("valid url").connect().await.expect("connect")
+}
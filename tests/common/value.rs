@@ -0,0 +1,39 @@
+use serde::de::{self, IntoDeserializer};
+use serde::de::value::{Error, MapDeserializer, SeqDeserializer};
+
+//Document in the shape produced by TOML and JSON deserializers
+pub enum Value {
+    Bool(bool),
+    Int(i64),
+    Str(&'static str),
+    List(Vec<Value>),
+    Map(Vec<(&'static str, Value)>),
+}
+
+impl<'de> de::Deserializer<'de> for Value {
+    type Error = Error;
+
+    fn deserialize_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Value::Bool(value) => visitor.visit_bool(value),
+            Value::Int(value) => visitor.visit_i64(value),
+            Value::Str(value) => visitor.visit_str(value),
+            Value::List(values) => visitor.visit_seq(SeqDeserializer::new(values.into_iter())),
+            Value::Map(entries) => visitor.visit_map(MapDeserializer::new(entries.into_iter())),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Value {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
@@ -0,0 +1,135 @@
+//Bodies of crate over http-body selected by `tonic011` or `tonic012` feature
+//
+//Run with `cargo test --no-default-features --features tonic012 --test compat` to check tonic 0.12
+use tonic_interceptor::policy::RemapStatus;
+use tonic_interceptor::testing::{poll_once, service_fn};
+use tonic_interceptor::{http, tonic};
+use tonic_interceptor::tonic::Code;
+
+#[cfg(feature = "tonic012")]
+extern crate http_body1 as http_body;
+
+use tower_layer::Layer;
+use tower_service::Service;
+
+use core::task;
+use core::pin::Pin;
+use core::convert::Infallible;
+use std::collections::VecDeque;
+
+use bytes::Bytes;
+
+//Body of data chunks, which ends with trailers
+#[derive(Default)]
+struct Chunks {
+    data: VecDeque<Bytes>,
+    trailers: Option<http::HeaderMap>,
+}
+
+#[cfg(feature = "tonic011")]
+impl http_body::Body for Chunks {
+    type Data = Bytes;
+    type Error = Infallible;
+
+    fn poll_data(mut self: Pin<&mut Self>, _: &mut task::Context<'_>) -> task::Poll<Option<Result<Self::Data, Self::Error>>> {
+        task::Poll::Ready(self.data.pop_front().map(Ok))
+    }
+
+    fn poll_trailers(mut self: Pin<&mut Self>, _: &mut task::Context<'_>) -> task::Poll<Result<Option<http::HeaderMap>, Self::Error>> {
+        task::Poll::Ready(Ok(self.trailers.take()))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.data.is_empty() && self.trailers.is_none()
+    }
+}
+
+#[cfg(feature = "tonic012")]
+impl http_body::Body for Chunks {
+    type Data = Bytes;
+    type Error = Infallible;
+
+    fn poll_frame(mut self: Pin<&mut Self>, _: &mut task::Context<'_>) -> task::Poll<Option<Result<http_body::Frame<Self::Data>, Self::Error>>> {
+        let frame = match self.data.pop_front() {
+            Some(data) => Some(http_body::Frame::data(data)),
+            None => self.trailers.take().map(http_body::Frame::trailers),
+        };
+        task::Poll::Ready(frame.map(Ok))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.data.is_empty() && self.trailers.is_none()
+    }
+}
+
+//Reads data and trailers of body
+#[cfg(feature = "tonic011")]
+fn collect<B: http_body::Body<Data = Bytes> + Unpin>(mut body: B) -> (Vec<Bytes>, Option<http::HeaderMap>) where B::Error: core::fmt::Debug {
+    let mut data = Vec::new();
+    while let Some(chunk) = poll_once(core::future::poll_fn(|ctx| Pin::new(&mut body).poll_data(ctx))) {
+        data.push(chunk.expect("data"));
+    }
+    let trailers = poll_once(core::future::poll_fn(|ctx| Pin::new(&mut body).poll_trailers(ctx))).expect("trailers");
+    assert!(body.is_end_stream());
+    (data, trailers)
+}
+
+//Reads data and trailers of body
+#[cfg(feature = "tonic012")]
+fn collect<B: http_body::Body<Data = Bytes> + Unpin>(mut body: B) -> (Vec<Bytes>, Option<http::HeaderMap>) where B::Error: core::fmt::Debug {
+    let mut data = Vec::new();
+    let mut trailers = None;
+    while let Some(frame) = poll_once(core::future::poll_fn(|ctx| Pin::new(&mut body).poll_frame(ctx))) {
+        match frame.expect("frame").into_data() {
+            Ok(chunk) => data.push(chunk),
+            Err(frame) => trailers = Some(frame.into_trailers().expect("trailers")),
+        }
+    }
+    assert!(body.is_end_stream());
+    (data, trailers)
+}
+
+fn call(data: &[&'static str], trailers: Option<http::HeaderMap>) -> (Vec<Bytes>, Option<http::HeaderMap>) {
+    let body = Chunks {
+        data: data.iter().map(|chunk| Bytes::from_static(chunk.as_bytes())).collect(),
+        trailers,
+    };
+    let mut body = Some(body);
+    let mut service = RemapStatus::new().rule("*", Code::DataLoss, Code::Internal).layer(service_fn(move |_: http::Request<()>| {
+        Ok::<_, Infallible>(http::Response::new(body.take().expect("single call")))
+    }));
+    let request = http::Request::builder().uri("/pkg.Search/Query").body(()).unwrap();
+    let response = poll_once(service.call(request)).expect("response");
+    collect(response.into_body())
+}
+
+#[test]
+fn should_pass_data_followed_by_trailers() {
+    let mut trailers = http::HeaderMap::new();
+    trailers.insert("grpc-status", http::HeaderValue::from(Code::DataLoss as i32));
+
+    let (data, trailers) = call(&["first", "second"], Some(trailers));
+    assert_eq!(data, ["first", "second"]);
+    assert_eq!(trailers.expect("trailers").get("grpc-status").unwrap(), "13");
+}
+
+#[test]
+fn should_pass_data_without_trailers() {
+    let (data, trailers) = call(&["only"], None);
+    assert_eq!(data, ["only"]);
+    assert!(trailers.is_none());
+
+    let (data, trailers) = call(&[], None);
+    assert!(data.is_empty());
+    assert!(trailers.is_none());
+}
+
+#[test]
+fn should_pass_trailers_only() {
+    let mut trailers = http::HeaderMap::new();
+    trailers.insert("grpc-status", http::HeaderValue::from(tonic::Code::Ok as i32));
+
+    let (data, trailers) = call(&[], Some(trailers));
+    assert!(data.is_empty());
+    assert_eq!(trailers.expect("trailers").get("grpc-status").unwrap(), "0");
+}
@@ -0,0 +1,162 @@
+#![cfg(feature = "tonic011")]
+#![allow(clippy::result_large_err)]
+
+//Composition with `tower_http` layers, which wrap response body, around tonic routes
+
+mod common;
+
+use common::{EchoClient, EchoServer, EchoService};
+use common::echo::EchoRequest;
+
+use tonic::{Code, Status};
+use tonic::codec::CompressionEncoding;
+use tonic_interceptor::Interceptor;
+use tower::ServiceBuilder;
+use tower_http::classify::{GrpcErrorsAsFailures, SharedClassifier};
+use tower_http::compression::CompressionLayer;
+use tower_http::compression::predicate::SizeAbove;
+use tower_http::decompression::DecompressionLayer;
+use tower_http::trace::TraceLayer;
+
+use std::sync::{Arc, Mutex, PoisonError};
+
+#[derive(Clone, Default)]
+//`grpc-status` of every response, which reached trace layer, if it is known from headers
+struct Traced(Arc<Mutex<Vec<Option<String>>>>);
+
+impl<B> tower_http::trace::OnResponse<B> for Traced {
+    fn on_response(self, response: &http::Response<B>, _: core::time::Duration, _: &tracing::Span) {
+        let status = response.headers().get("grpc-status").map(|status| status.to_str().unwrap().to_owned());
+        self.0.lock().unwrap_or_else(PoisonError::into_inner).push(status);
+    }
+}
+
+impl Traced {
+    fn layer(&self) -> TraceLayer<SharedClassifier<GrpcErrorsAsFailures>, tower_http::trace::DefaultMakeSpan, tower_http::trace::DefaultOnRequest, Self> {
+        TraceLayer::new_for_grpc().on_response(self.clone())
+    }
+
+    fn get(&self) -> Vec<Option<String>> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+}
+
+fn compression() -> CompressionLayer<SizeAbove> {
+    //Default predicate never compresses gRPC
+    CompressionLayer::new().compress_when(SizeAbove::new(1))
+}
+
+#[derive(Clone)]
+struct Observer;
+
+impl Interceptor for Observer {
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<Status> {
+        if headers.contains_key("x-reject") {
+            return Some(Status::permission_denied("rejected"));
+        }
+
+        if let Some(encoding) = headers.get("grpc-encoding").cloned() {
+            headers.insert("x-seen-encoding", encoding);
+        }
+        None
+    }
+
+    fn on_response(&self, _: Code, headers: &mut http::HeaderMap, _: &http::Extensions) {
+        //Visible only when compression runs after interceptor
+        let encoding = headers.get(http::header::CONTENT_ENCODING).cloned().unwrap_or_else(|| http::HeaderValue::from_static("none"));
+        headers.insert("x-seen-compressed", encoding);
+    }
+}
+
+fn request(message: &str, reject: bool) -> tonic::Request<EchoRequest> {
+    let mut request = tonic::Request::new(EchoRequest {
+        message: message.to_owned(),
+    });
+    if reject {
+        request.metadata_mut().insert("x-reject", "1".parse().unwrap());
+    }
+    request
+}
+
+//Starts server with `layer` over its routes, returning service state and client, which compresses messages and decompresses responses
+macro_rules! spawn {
+    ($layer:expr) => {{
+        let service = EchoService::default();
+        let (incoming, addr) = common::listen().await;
+        let server = EchoServer::new(service.clone()).accept_compressed(CompressionEncoding::Gzip);
+        let router = tonic::transport::Server::builder().layer($layer).add_service(server);
+        tokio::spawn(router.serve_with_incoming(incoming));
+        let channel = ServiceBuilder::new().layer(DecompressionLayer::new()).service(common::connect(addr).await);
+        let client = EchoClient::new(channel).send_compressed(CompressionEncoding::Gzip);
+        (service, client)
+    }};
+}
+
+#[tokio::test]
+async fn should_compose_inside_tower_http_layers() {
+    let traced = Traced::default();
+    let layer = ServiceBuilder::new().layer(traced.layer())
+                                     .layer(compression())
+                                     .layer(tonic_interceptor::interceptor(Observer))
+                                     .into_inner();
+    let (service, mut client) = spawn!(layer);
+
+    let response = client.unary(request("hello", false)).await.expect("success");
+    assert_eq!(response.get_ref().message, "hello");
+    let metadata = response.metadata();
+    assert_eq!(metadata.get("x-seen-encoding").unwrap(), "gzip");
+    assert_eq!(metadata.get("x-seen-compressed").unwrap(), "none");
+
+    let status = client.unary(request("hello", true)).await.expect_err("to reject");
+    assert_eq!(status.code(), Code::PermissionDenied);
+    assert_eq!(status.message(), "rejected");
+    //Rejection is traced as response with status in headers
+    assert_eq!(traced.get(), [None, Some("7".to_owned())]);
+    assert_eq!(service.calls(), 1);
+}
+
+#[tokio::test]
+async fn should_compose_outside_tower_http_layers() {
+    let traced = Traced::default();
+    //Bodies of both layers are `Default`, so rejection needs no body factory
+    let layer = ServiceBuilder::new().layer(tonic_interceptor::interceptor(Observer).call_on_response_for_rejections(true))
+                                     .layer(traced.layer())
+                                     .layer(compression())
+                                     .into_inner();
+    let (service, mut client) = spawn!(layer);
+
+    let response = client.unary(request("hello", false)).await.expect("success");
+    assert_eq!(response.get_ref().message, "hello");
+    let metadata = response.metadata();
+    assert_eq!(metadata.get("x-seen-encoding").unwrap(), "gzip");
+    assert_eq!(metadata.get("x-seen-compressed").unwrap(), "gzip");
+
+    let status = client.unary(request("hello", true)).await.expect_err("to reject");
+    assert_eq!(status.code(), Code::PermissionDenied);
+    assert_eq!(status.message(), "rejected");
+    assert_eq!(status.metadata().get("x-seen-compressed").unwrap(), "none");
+    //Rejected request never reaches trace layer
+    assert_eq!(traced.get(), [None]);
+    assert_eq!(service.calls(), 1);
+}
+
+#[tokio::test]
+async fn should_compose_between_tower_http_layers() {
+    let traced = Traced::default();
+    let layer = ServiceBuilder::new().layer(traced.layer())
+                                     .layer(tonic_interceptor::interceptor(Observer))
+                                     .layer(compression())
+                                     .into_inner();
+    let (service, mut client) = spawn!(layer);
+
+    let response = client.unary(request("hello", false)).await.expect("success");
+    assert_eq!(response.get_ref().message, "hello");
+    let metadata = response.metadata();
+    assert_eq!(metadata.get("x-seen-encoding").unwrap(), "gzip");
+    assert_eq!(metadata.get("x-seen-compressed").unwrap(), "gzip");
+
+    let status = client.unary(request("hello", true)).await.expect_err("to reject");
+    assert_eq!(status.code(), Code::PermissionDenied);
+    assert_eq!(traced.get(), [None, Some("7".to_owned())]);
+    assert_eq!(service.calls(), 1);
+}
@@ -0,0 +1,189 @@
+#![cfg(all(feature = "tonic011", feature = "serde"))]
+#![allow(clippy::result_large_err)]
+
+mod common;
+
+use common::value::Value;
+
+use tonic_interceptor::config::{build_chain, ChainConfig, InterceptorConfig, Param};
+
+use serde::Deserialize;
+use serde::de::value::Error;
+use tonic::Code;
+
+fn parse(value: Value) -> Result<ChainConfig, Error> {
+    ChainConfig::deserialize(value)
+}
+
+fn chain(interceptors: Vec<Value>) -> Value {
+    Value::Map(vec![("interceptors", Value::List(interceptors))])
+}
+
+fn call(interceptor: &dyn tonic_interceptor::Interceptor, path: &str, token: Option<&str>) -> Option<tonic::Status> {
+    let uri = format!("http://localhost{}", path).parse::<http::Uri>().unwrap();
+    let mut headers = tonic::metadata::MetadataMap::new();
+    if let Some(token) = token {
+        headers.insert("authorization", format!("Bearer {}", token).parse().unwrap());
+    }
+    interceptor.on_request_with_uri(&uri, &mut headers, &mut http::Extensions::new())
+}
+
+const TOML: &str = include_str!("data/chain.toml");
+const JSON: &str = include_str!("data/chain.json");
+
+#[test]
+fn should_build_chain_in_configured_order() {
+    std::env::set_var("TONIC_INTERCEPTOR_CONFIG_TOKEN", "secret");
+    let config = toml::from_str::<ChainConfig>(TOML).expect("valid toml");
+    assert_eq!(serde_json::from_str::<ChainConfig>(JSON).expect("valid json"), config);
+    assert_eq!(config.interceptors.len(), 5);
+    assert_eq!(config.interceptors[1], InterceptorConfig::new("method_allowlist").param("methods", Param::List(vec![Param::Str("/pkg.Users/*".to_owned())])));
+    assert_eq!(config.interceptors[3], InterceptorConfig::new("rate_limit").param("requests", Param::Int(2)).param("period_secs", Param::Int(60)));
+    assert!(!config.interceptors[4].enabled);
+
+    let interceptor = build_chain(config).expect("valid chain");
+    //Authentication comes before allowlist
+    assert_eq!(call(&*interceptor, "/pkg.Orders/List", None).unwrap().code(), Code::Unauthenticated);
+    assert_eq!(call(&*interceptor, "/pkg.Orders/List", Some("wrong")).unwrap().code(), Code::Unauthenticated);
+    assert_eq!(call(&*interceptor, "/pkg.Orders/List", Some("secret")).unwrap().code(), Code::PermissionDenied);
+    //Rejected requests do not count toward rate limit
+    assert!(call(&*interceptor, "/pkg.Users/Get", Some("secret")).is_none());
+    assert!(call(&*interceptor, "/pkg.Users/Get", Some("secret")).is_none());
+    let status = call(&*interceptor, "/pkg.Users/Get", Some("secret")).expect("to reject");
+    assert_eq!(status.code(), Code::ResourceExhausted);
+    assert!(status.metadata().get("retry-after").is_some());
+}
+
+#[test]
+fn should_build_logging_with_tracing_feature() {
+    let config = toml::from_str::<ChainConfig>("[[interceptors]]\nname = \"logging\"\nlevel = \"debug\"\nredact = [\"authorization\"]\n").expect("valid toml");
+    let result = build_chain(config);
+    #[cfg(feature = "tracing")]
+    assert!(call(&*result.expect("valid chain"), "/pkg.Users/Get", Some("secret")).is_none());
+    #[cfg(not(feature = "tracing"))]
+    assert_eq!(result.err().expect("invalid config").to_string(), "interceptors[0].name: 'logging' requires tracing feature");
+}
+
+#[test]
+fn should_build_empty_chain() {
+    let interceptor = build_chain(parse(Value::Map(Vec::new())).expect("valid config")).expect("valid chain");
+    assert!(call(&*interceptor, "/pkg.Users/Get", None).is_none());
+}
+
+#[test]
+fn should_reject_malformed_document() {
+    let error = parse(chain(vec![Value::Map(vec![("requests", Value::Int(1))])])).expect_err("missing name");
+    assert_eq!(error.to_string(), "missing field `name`");
+
+    let error = parse(Value::Map(vec![("interceptor", Value::List(Vec::new()))])).expect_err("unknown key");
+    assert_eq!(error.to_string(), "unknown field `interceptor`, expected `interceptors`");
+
+    let error = parse(chain(vec![Value::Map(vec![("name", Value::Str("logging")), ("level", Value::Map(Vec::new()))])])).expect_err("nested table");
+    assert_eq!(error.to_string(), "invalid type: map, expected boolean, number, string or list");
+}
+
+#[test]
+fn should_report_invalid_config_with_path() {
+    let error = |interceptors: Vec<Value>| match build_chain(parse(chain(interceptors)).expect("valid document")) {
+        Ok(_) => panic!("invalid config is accepted"),
+        Err(error) => error.to_string(),
+    };
+
+    assert_eq!(error(vec![
+        Value::Map(vec![("name", Value::Str("method_allowlist")), ("methods", Value::List(vec![Value::Str("*")]))]),
+        Value::Map(vec![("name", Value::Str("rate_limiter")), ("requests", Value::Int(1))]),
+    ]), "interceptors[1].name: unknown interceptor 'rate_limiter'");
+    assert_eq!(error(vec![Value::Map(vec![("name", Value::Str("rate_limit")), ("requests", Value::Int(-1))])]), "interceptors[0].requests: expected positive integer, found -1");
+    assert_eq!(error(vec![Value::Map(vec![("name", Value::Str("rate_limit")), ("requests", Value::Str("10"))])]), "interceptors[0].requests: expected positive integer, found string");
+    assert_eq!(error(vec![Value::Map(vec![("name", Value::Str("rate_limit"))])]), "interceptors[0].requests: missing parameter of 'rate_limit'");
+    assert_eq!(error(vec![Value::Map(vec![("name", Value::Str("rate_limit")), ("requests", Value::Int(1)), ("burst", Value::Int(2))])]), "interceptors[0].burst: unknown parameter of 'rate_limit'");
+    assert_eq!(error(vec![Value::Map(vec![("name", Value::Str("rate_limit")), ("requests", Value::Int(1)), ("headers", Value::Str("yes"))])]), "interceptors[0].headers: expected boolean, found string");
+    assert_eq!(error(vec![Value::Map(vec![("name", Value::Str("metadata_limit"))])]), "interceptors[0].max_entries: 'metadata_limit' requires max_entries or max_bytes");
+    assert_eq!(error(vec![
+        Value::Map(vec![("name", Value::Str("method_allowlist")), ("methods", Value::List(vec![Value::Str("*"), Value::Int(1)]))]),
+    ]), "interceptors[0].methods[1]: expected string, found integer");
+    assert_eq!(error(vec![
+        Value::Map(vec![("name", Value::Str("method_allowlist")), ("methods", Value::List(vec![Value::Str("pkg.Users/Get")]))]),
+    ]), "interceptors[0].methods[0]: expected method pattern, found 'pkg.Users/Get'");
+    #[cfg(feature = "tracing")]
+    assert_eq!(error(vec![Value::Map(vec![("name", Value::Str("logging")), ("level", Value::Str("verbose"))])]), "interceptors[0].level: expected one of error, warn, info, debug, trace, found 'verbose'");
+    assert_eq!(error(vec![
+        Value::Map(vec![("name", Value::Str("bearer_auth")), ("token_env", Value::Str("TONIC_INTERCEPTOR_CONFIG_MISSING"))]),
+    ]), "interceptors[0].token_env: environment variable 'TONIC_INTERCEPTOR_CONFIG_MISSING' is not set");
+
+    let error = build_chain(ChainConfig {
+        interceptors: vec![InterceptorConfig::new("rate_limit")],
+    }).err().expect("invalid config");
+    assert_eq!(error.path(), "interceptors[0].requests");
+    assert_eq!(error.message(), "missing parameter of 'rate_limit'");
+}
+
+fn limit_check(limit: &tonic_interceptor::config::MetadataLimit, entries: &[(&'static str, &'static str)]) -> Option<String> {
+    use tonic_interceptor::Interceptor;
+
+    let mut headers = http::HeaderMap::new();
+    for (key, value) in entries {
+        headers.append(*key, http::HeaderValue::from_static(value));
+    }
+    let mut metadata = tonic::metadata::MetadataMap::from_headers(headers.clone());
+    let result = limit.on_request_headers(&http::Uri::from_static("/pkg.Users/Get"), &mut headers, &mut http::Extensions::new());
+    let metadata_result = limit.on_request(&mut metadata, &mut http::Extensions::new());
+    assert_eq!(result.as_ref().map(|status| status.message()), metadata_result.as_ref().map(|status| status.message()));
+    result.map(|status| {
+        assert_eq!(status.code(), Code::ResourceExhausted);
+        status.message().to_owned()
+    })
+}
+
+#[test]
+fn should_limit_metadata_entries_by_kind() {
+    use tonic_interceptor::config::MetadataLimit;
+
+    let limit = MetadataLimit::new().max_ascii_entries(2).max_bin_entries(1);
+    assert_eq!(limit_check(&limit, &[("x-a", "1"), ("x-b", "2"), ("x-c-bin", "AQ")]), None);
+    assert_eq!(limit_check(&limit, &[("x-a", "1"), ("x-b", "2"), ("x-c", "3")]).unwrap(), "metadata exceeds limit of 2 ASCII entries");
+    assert_eq!(limit_check(&limit, &[("x-a", "1"), ("x-c-bin", "AQ"), ("x-d-bin", "Ag")]).unwrap(), "metadata exceeds limit of 1 binary entries");
+    //Values of the same key count as separate entries
+    assert_eq!(limit_check(&limit, &[("x-c-bin", "AQ"), ("x-c-bin", "Ag")]).unwrap(), "metadata exceeds limit of 1 binary entries");
+}
+
+#[test]
+fn should_limit_values_per_key() {
+    use tonic_interceptor::config::MetadataLimit;
+
+    let limit = MetadataLimit::new().max_values_per_key(2);
+    assert_eq!(limit_check(&limit, &[("x-a", "1"), ("x-a", "2"), ("x-b", "1"), ("x-b", "2"), ("x-c-bin", "AQ"), ("x-c-bin", "Ag")]), None);
+    assert_eq!(limit_check(&limit, &[("x-a", "1"), ("x-b", "1"), ("x-a", "2"), ("x-a", "3")]).unwrap(), "metadata 'x-a' exceeds limit of 2 values");
+    assert_eq!(limit_check(&limit, &[("x-c-bin", "AQ"), ("x-c-bin", "Ag"), ("x-c-bin", "Aw")]).unwrap(), "metadata 'x-c-bin' exceeds limit of 2 values");
+}
+
+#[test]
+fn should_trip_count_within_combined_limits() {
+    use tonic_interceptor::config::MetadataLimit;
+
+    const TINY: [(&str, &str); 6] = [("a", ""), ("b", ""), ("c", ""), ("d", ""), ("e", ""), ("f", "")];
+
+    //Tiny entries are well within byte limit, but not within count limit
+    let limit = MetadataLimit::new().max_entries(100).max_bytes(1024).max_ascii_entries(5).max_values_per_key(4);
+    assert_eq!(limit_check(&limit, &TINY[..5]), None);
+    assert_eq!(limit_check(&limit, &TINY).unwrap(), "metadata exceeds limit of 5 ASCII entries");
+
+    let limit = MetadataLimit::new().max_bytes(6).max_ascii_entries(10);
+    assert_eq!(limit_check(&limit, &TINY), None);
+    assert_eq!(limit_check(&limit, &[("abcd", "12"), ("x", "")]).unwrap(), "metadata exceeds limit of 6 bytes");
+}
+
+#[test]
+fn should_build_metadata_limit_with_entry_counts() {
+    let config = parse(chain(vec![
+        Value::Map(vec![("name", Value::Str("metadata_limit")), ("max_bin_entries", Value::Int(1)), ("max_values_per_key", Value::Int(3))]),
+    ])).expect("valid config");
+    let interceptor = build_chain(config).expect("valid chain");
+
+    let mut headers = tonic::metadata::MetadataMap::new();
+    headers.insert_bin("x-a-bin", tonic::metadata::MetadataValue::from_bytes(b"1"));
+    headers.insert_bin("x-b-bin", tonic::metadata::MetadataValue::from_bytes(b"2"));
+    let status = interceptor.on_request(&mut headers, &mut http::Extensions::new()).expect("to reject");
+    assert_eq!(status.code(), Code::ResourceExhausted);
+    assert_eq!(status.message(), "metadata exceeds limit of 1 binary entries");
+}
@@ -0,0 +1,176 @@
+#![cfg(feature = "tonic011")]
+#![allow(clippy::result_large_err)]
+
+mod common;
+
+use common::{EchoClient, EchoServer, EchoService};
+use common::echo::EchoRequest;
+
+use tonic_interceptor::connection::per_connection;
+use tonic_interceptor::limit::PerConnectionStreams;
+use tonic_interceptor::testing::{poll_once, service_fn};
+use tonic_interceptor::InterceptorFn;
+
+use tonic::Code;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use core::task;
+use core::convert::Infallible;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+fn ok(_: http::Request<()>) -> Result<http::Response<()>, Infallible> {
+    Ok(http::Response::new(()))
+}
+
+fn call<S: Service<http::Request<()>, Response = http::Response<B>>, B>(svc: &mut S) -> http::Response<B> where S::Error: core::fmt::Debug {
+    poll_once(svc.call(http::Request::builder().uri("/test.Echo/Unary").body(()).unwrap())).unwrap()
+}
+
+#[test]
+fn should_create_interceptor_per_clone() {
+    let connections = Arc::new(AtomicUsize::new(0));
+    let layer = {
+        let connections = connections.clone();
+        per_connection(move || {
+            let connection = connections.fetch_add(1, Ordering::SeqCst);
+            let requests = Arc::new(AtomicUsize::new(0));
+            InterceptorFn {
+                on_request: move |_: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions| {
+                    requests.fetch_add(1, Ordering::SeqCst);
+                    None
+                },
+                on_response: move |_, headers: &mut http::HeaderMap, _: &http::Extensions| {
+                    headers.insert("x-connection", connection.into());
+                },
+            }
+        })
+    };
+
+    let mut first = layer.layer(service_fn(ok));
+    let mut second = first.clone();
+    assert_eq!(connections.load(Ordering::SeqCst), 2);
+    assert_eq!(call(&mut first).headers()["x-connection"], "0");
+    assert_eq!(call(&mut second).headers()["x-connection"], "1");
+    assert_eq!(call(&mut first).headers()["x-connection"], "0");
+}
+
+#[test]
+fn should_limit_streams_per_connection() {
+    let mut first = PerConnectionStreams::new(2).layer().layer(service_fn(ok));
+    let mut second = first.clone();
+
+    let accepted = [call(&mut first), call(&mut first)];
+    assert!(accepted.iter().all(|response| !response.headers().contains_key("grpc-status")));
+    assert_eq!(first.interceptor().in_flight(), 2);
+    assert_eq!(call(&mut first).headers()["grpc-status"], "8");
+    //Other connection is not affected
+    assert!(!call(&mut second).headers().contains_key("grpc-status"));
+    assert_eq!(second.interceptor().in_flight(), 0);
+
+    //Stream completes once its response is dropped
+    drop(accepted);
+    assert_eq!(first.interceptor().in_flight(), 0);
+    assert!(!call(&mut first).headers().contains_key("grpc-status"));
+    assert_eq!(first.interceptor().requests(), 3);
+}
+
+#[test]
+fn should_limit_requests_per_connection() {
+    let mut first = PerConnectionStreams::new(2).max_requests(2).layer().layer(service_fn(ok));
+    let mut second = first.clone();
+
+    for _ in 0..2 {
+        assert!(!call(&mut first).headers().contains_key("grpc-status"));
+    }
+    assert_eq!(call(&mut first).headers()["grpc-status"], "14");
+    assert!(!call(&mut second).headers().contains_key("grpc-status"));
+}
+
+//Holds calls with `x-block` metadata until it is opened
+#[derive(Clone)]
+struct Gate {
+    blocked: Arc<AtomicUsize>,
+    open: Arc<tokio::sync::Semaphore>,
+    inner: EchoServer,
+}
+
+impl tonic::server::NamedService for Gate {
+    const NAME: &'static str = "test.Echo";
+}
+
+impl Service<http::Request<tonic::transport::Body>> for Gate {
+    type Response = http::Response<tonic::body::BoxBody>;
+    type Error = Infallible;
+    type Future = std::pin::Pin<Box<dyn core::future::Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        Service::<http::Request<tonic::transport::Body>>::poll_ready(&mut self.inner, cx)
+    }
+
+    fn call(&mut self, req: http::Request<tonic::transport::Body>) -> Self::Future {
+        let is_blocked = req.headers().contains_key("x-block");
+        let blocked = self.blocked.clone();
+        let open = self.open.clone();
+        let fut = self.inner.call(req);
+        Box::pin(async move {
+            if is_blocked {
+                blocked.fetch_add(1, Ordering::SeqCst);
+                let _ = open.acquire().await.expect("open");
+            }
+            fut.await
+        })
+    }
+}
+
+fn request(is_blocked: bool) -> tonic::Request<EchoRequest> {
+    let mut request = tonic::Request::new(EchoRequest {
+        message: "hello".to_owned(),
+    });
+    if is_blocked {
+        request.metadata_mut().insert("x-block", "1".parse().unwrap());
+    }
+    request
+}
+
+#[tokio::test]
+async fn should_reject_flood_of_single_channel() {
+    let gate = Gate {
+        blocked: Arc::new(AtomicUsize::new(0)),
+        open: Arc::new(tokio::sync::Semaphore::new(0)),
+        inner: EchoServer::new(EchoService::default()),
+    };
+    let (incoming, addr) = common::listen().await;
+    let router = tonic::transport::Server::builder().layer(PerConnectionStreams::new(4).layer()).add_service(gate.clone());
+    tokio::spawn(router.serve_with_incoming(incoming));
+
+    let flooded = EchoClient::new(common::connect(addr.clone()).await);
+    let calls: Vec<_> = (0..10).map(|_| {
+        let mut client = flooded.clone();
+        tokio::spawn(async move {
+            client.unary(request(true)).await
+        })
+    }).collect();
+
+    //Streams over limit are rejected while the rest are held
+    while gate.blocked.load(Ordering::SeqCst) < 4 || calls.iter().filter(|call| call.is_finished()).count() < 6 {
+        tokio::time::sleep(core::time::Duration::from_millis(5)).await;
+    }
+    assert_eq!(gate.blocked.load(Ordering::SeqCst), 4);
+    assert_eq!(flooded.clone().unary(request(false)).await.unwrap_err().code(), Code::ResourceExhausted);
+    //Another connection is served
+    let mut other = EchoClient::new(common::connect(addr).await);
+    assert_eq!(other.unary(request(false)).await.unwrap().get_ref().message, "hello");
+
+    gate.open.add_permits(10);
+    let mut codes = Vec::new();
+    for call in calls {
+        codes.push(call.await.unwrap().map_or_else(|status| status.code(), |_| Code::Ok));
+    }
+    codes.sort_by_key(|code| *code as i32);
+    assert_eq!(codes, [[Code::Ok; 4].as_slice(), [Code::ResourceExhausted; 6].as_slice()].concat());
+
+    //Completed streams free their slots
+    assert_eq!(flooded.clone().unary(request(false)).await.unwrap().get_ref().message, "hello");
+}
@@ -0,0 +1,144 @@
+#![cfg(feature = "tonic011")]
+#![allow(clippy::result_large_err)]
+
+use tonic_interceptor::{Interceptor, InterceptorChain, InterceptorService};
+use tonic_interceptor::control::{ToggleHandle, Toggle};
+use tonic_interceptor::testing::{poll_once, service_fn};
+
+use tonic::{Code, Status};
+use tower_service::Service;
+
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone, Default)]
+struct Recorder {
+    name: &'static str,
+    reject: bool,
+    events: Arc<Mutex<Vec<String>>>,
+}
+
+impl Interceptor for Recorder {
+    fn on_request(&self, _: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<Status> {
+        self.events.lock().unwrap().push(format!("{}:request", self.name));
+        match self.reject {
+            true => Some(Status::permission_denied("rejected")),
+            false => None,
+        }
+    }
+
+    fn on_response(&self, _: Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+        self.events.lock().unwrap().push(format!("{}:response", self.name));
+    }
+}
+
+fn take(events: &Arc<Mutex<Vec<String>>>) -> Vec<String> {
+    core::mem::take(&mut *events.lock().unwrap())
+}
+
+//Calls service, running `during` while request is handled by inner service
+fn call<I: Interceptor + Clone>(interceptor: I, during: impl Fn()) -> http::Response<()> {
+    let svc = service_fn(move |_: http::Request<()>| {
+        during();
+        Ok::<_, Status>(http::Response::new(()))
+    });
+    let mut service = InterceptorService::new(interceptor, svc).call_on_response_for_rejections(true);
+    poll_once(service.call(http::Request::new(()))).expect("response")
+}
+
+#[test]
+fn should_skip_disabled_interceptor() {
+    let recorder = Recorder {
+        name: "a",
+        reject: true,
+        ..Recorder::default()
+    };
+    let toggle = Arc::new(Toggle::new(recorder.clone()));
+    let handle = toggle.handle();
+    assert!(handle.is_enabled());
+
+    let response = call(toggle.clone(), || ());
+    assert_eq!(response.headers().get("grpc-status").unwrap(), "7");
+    assert_eq!(take(&recorder.events), ["a:request", "a:response"]);
+
+    handle.disable();
+    assert!(!handle.is_enabled());
+    let response = call(toggle.clone(), || ());
+    assert!(response.headers().get("grpc-status").is_none());
+    assert!(take(&recorder.events).is_empty());
+
+    //Handles are shared
+    let other: ToggleHandle = handle.clone();
+    other.enable();
+    assert!(handle.is_enabled());
+    call(toggle, || ());
+    assert_eq!(take(&recorder.events), ["a:request", "a:response"]);
+}
+
+#[test]
+fn should_keep_decision_for_the_whole_request() {
+    let recorder = Recorder {
+        name: "a",
+        ..Recorder::default()
+    };
+    let toggle = Arc::new(Toggle::new(recorder.clone()));
+    let handle = toggle.handle();
+
+    //Started enabled, gets its response
+    {
+        let handle = handle.clone();
+        call(toggle.clone(), move || handle.disable());
+    }
+    assert!(!handle.is_enabled());
+    assert_eq!(take(&recorder.events), ["a:request", "a:response"]);
+
+    //Started disabled, never sees response
+    {
+        let handle = handle.clone();
+        call(toggle.clone(), move || handle.enable());
+    }
+    assert!(handle.is_enabled());
+    assert!(take(&recorder.events).is_empty());
+
+    //Outside of service, current state is used
+    handle.disable();
+    toggle.on_response(Code::Ok, &mut http::HeaderMap::new(), &http::Extensions::new());
+    assert!(take(&recorder.events).is_empty());
+}
+
+#[test]
+fn should_control_chain_toggles_by_name() {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let recorder = |name| Recorder {
+        name,
+        events: events.clone(),
+        ..Recorder::default()
+    };
+    let chain = InterceptorChain::new().with_toggle("a", recorder("a")).with(recorder("b")).with_toggle("c", recorder("c"));
+    let control = chain.control();
+    assert_eq!(control.names().collect::<Vec<_>>(), ["a", "c"]);
+    assert_eq!(control.is_enabled("a"), Some(true));
+    assert_eq!(control.is_enabled("b"), None);
+    let chain = Arc::new(chain);
+
+    call(chain.clone(), || ());
+    assert_eq!(take(&events), ["a:request", "b:request", "c:request", "c:response", "b:response", "a:response"]);
+
+    assert!(control.disable("a"));
+    assert!(!control.disable("b"));
+    call(chain.clone(), || ());
+    assert_eq!(take(&events), ["b:request", "c:request", "c:response", "b:response"]);
+
+    //Decisions of toggles within the same request are independent
+    {
+        let control = control.clone();
+        call(chain.clone(), move || {
+            control.enable("a");
+            control.disable("c");
+        });
+    }
+    assert_eq!(take(&events), ["b:request", "c:request", "c:response", "b:response"]);
+    assert_eq!(control.is_enabled("a"), Some(true));
+    assert_eq!(control.get("c").map(ToggleHandle::is_enabled), Some(false));
+    call(chain, || ());
+    assert_eq!(take(&events), ["a:request", "b:request", "b:response", "a:response"]);
+}
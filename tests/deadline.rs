@@ -0,0 +1,368 @@
+#![cfg(all(feature = "tonic011", feature = "tokio"))]
+
+use tonic_interceptor::{Interceptor, InterceptorChain, InterceptorService};
+use tonic_interceptor::client::{ClientInterceptorService, PropagateDeadline};
+use tonic_interceptor::deadline::{Clamp, Deadline, DeadlineContext, InsertDeadline, MethodTimeout, OriginalTimeout, PerMethodTimeout, TaskLocalDeadline};
+use tonic_interceptor::testing::{with_noop_context, TestTimer};
+use tonic_interceptor::timer::Timer;
+
+use tonic::Status;
+use tower::ServiceExt;
+
+use core::convert::Infallible;
+use std::sync::{Arc, Mutex};
+use core::time::Duration;
+
+type Headers = Option<http::HeaderValue>;
+
+async fn call(interceptor: PropagateDeadline<TestTimer>, request: http::Request<()>) -> Result<Headers, Status> {
+    let timeout = Arc::new(Mutex::new(None));
+    let service = {
+        let timeout = timeout.clone();
+        tower::service_fn(move |req: http::Request<()>| {
+            *timeout.lock().unwrap() = req.headers().get("grpc-timeout").cloned();
+            async move {
+                Ok::<_, Infallible>(http::Response::new(()))
+            }
+        })
+    };
+    let service = ClientInterceptorService::new(interceptor, service);
+    match service.oneshot(request).await {
+        Ok(_) => Ok(timeout.lock().unwrap().take()),
+        Err(error) => Err(Status::from_error(error)),
+    }
+}
+
+fn request_with_deadline(timer: &TestTimer, timeout: Duration) -> http::Request<()> {
+    let mut request = http::Request::new(());
+    request.extensions_mut().insert(Deadline::after_with(timer, timeout));
+    request
+}
+
+#[tokio::test]
+async fn should_not_set_timeout_without_deadline() {
+    let timer = TestTimer::new();
+    let timeout = call(PropagateDeadline::new().clock(timer), http::Request::new(())).await.expect("success");
+    assert_eq!(timeout, None);
+}
+
+#[tokio::test]
+async fn should_propagate_deadline_from_extension() {
+    let timer = TestTimer::new();
+    let timeout = call(PropagateDeadline::new().clock(timer.clone()), request_with_deadline(&timer, Duration::from_secs(2))).await.expect("success");
+    assert_eq!(timeout.expect("to have grpc-timeout"), "2S");
+}
+
+#[tokio::test]
+async fn should_propagate_deadline_from_context() {
+    let timer = TestTimer::new();
+    let deadline = Deadline::after_with(&timer, Duration::from_millis(1500));
+    timer.advance(Duration::from_millis(500));
+
+    let timeout = DeadlineContext::scope(deadline, call(PropagateDeadline::new().clock(timer), http::Request::new(()))).await.expect("success");
+    assert_eq!(timeout.expect("to have grpc-timeout"), "1S");
+    assert_eq!(DeadlineContext::current(), None);
+}
+
+#[tokio::test]
+async fn should_prefer_extension_over_context() {
+    let timer = TestTimer::new();
+    let deadline = Deadline::after_with(&timer, Duration::from_secs(10));
+    let timeout = DeadlineContext::scope(deadline, call(PropagateDeadline::new().clock(timer.clone()), request_with_deadline(&timer, Duration::from_secs(1)))).await.expect("success");
+    assert_eq!(timeout.expect("to have grpc-timeout"), "1S");
+}
+
+#[tokio::test]
+async fn should_subtract_margin() {
+    let timer = TestTimer::new();
+    let interceptor = PropagateDeadline::with_margin(Duration::from_millis(100)).clock(timer.clone());
+    let timeout = call(interceptor, request_with_deadline(&timer, Duration::from_secs(1))).await.expect("success");
+    assert_eq!(timeout.expect("to have grpc-timeout"), "900m");
+}
+
+#[tokio::test]
+async fn should_keep_shorter_explicit_timeout() {
+    let timer = TestTimer::new();
+    let mut request = request_with_deadline(&timer, Duration::from_secs(1));
+    request.headers_mut().insert("grpc-timeout", http::HeaderValue::from_static("10m"));
+    let timeout = call(PropagateDeadline::new().clock(timer.clone()), request).await.expect("success");
+    assert_eq!(timeout.expect("to have grpc-timeout"), "10m");
+
+    let mut request = request_with_deadline(&timer, Duration::from_secs(1));
+    request.headers_mut().insert("grpc-timeout", http::HeaderValue::from_static("1H"));
+    let timeout = call(PropagateDeadline::new().clock(timer), request).await.expect("success");
+    assert_eq!(timeout.expect("to have grpc-timeout"), "1S");
+}
+
+#[tokio::test]
+async fn should_reject_expired_deadline() {
+    let timer = TestTimer::new();
+    let request = request_with_deadline(&timer, Duration::from_millis(10));
+    timer.advance(Duration::from_millis(10));
+    let status = call(PropagateDeadline::new().clock(timer.clone()), request).await.expect_err("to reject");
+    assert_eq!(status.code(), tonic::Code::DeadlineExceeded);
+
+    let interceptor = PropagateDeadline::with_margin(Duration::from_millis(100)).clock(timer.clone());
+    let status = call(interceptor, request_with_deadline(&timer, Duration::from_millis(100))).await.expect_err("to reject");
+    assert_eq!(status.code(), tonic::Code::DeadlineExceeded);
+}
+
+#[tokio::test]
+async fn should_insert_deadline_from_incoming_timeout() {
+    let timer = TestTimer::new();
+    let service = tower::service_fn(|req: http::Request<()>| async move {
+        Ok::<_, Infallible>(http::Response::new(req.extensions().get::<Deadline>().copied()))
+    });
+
+    let request = http::Request::builder().header("grpc-timeout", "3S").body(()).unwrap();
+    let deadline = InterceptorService::new(InsertDeadline::new().clock(timer.clone()), service).oneshot(request).await.expect("success").into_body().expect("to have deadline");
+    assert_eq!(deadline.remaining_with(&timer), Duration::from_secs(3));
+    timer.advance(Duration::from_secs(1));
+    assert_eq!(deadline.remaining_with(&timer), Duration::from_secs(2));
+
+    let deadline = InterceptorService::new(InsertDeadline::new(), service).oneshot(http::Request::new(())).await.expect("success").into_body();
+    assert_eq!(deadline, None);
+
+    let mut extensions = http::Extensions::new();
+    let mut metadata = tonic::metadata::MetadataMap::new();
+    metadata.insert("grpc-timeout", "invalid".parse().unwrap());
+    assert!(InsertDeadline::new().on_request(&mut metadata, &mut extensions).is_none());
+    assert!(extensions.get::<Deadline>().is_none());
+}
+
+fn clamp(clamp: &Clamp, timeout: Option<&'static str>) -> (Option<http::HeaderValue>, Option<OriginalTimeout>) {
+    let mut headers = http::HeaderMap::new();
+    if let Some(timeout) = timeout {
+        headers.insert("grpc-timeout", http::HeaderValue::from_static(timeout));
+    }
+    let mut extensions = http::Extensions::new();
+    let uri = http::Uri::from_static("/pkg.Service/Method");
+    assert!(clamp.on_request_headers(&uri, &mut headers, &mut extensions).is_none());
+    (headers.get("grpc-timeout").cloned(), extensions.get::<OriginalTimeout>().copied())
+}
+
+#[test]
+fn should_clamp_timeout() {
+    let interceptor = Clamp::new(Duration::from_secs(30), Duration::from_secs(5));
+
+    //Acceptable values are left byte-identical, even if they have different encoding
+    for value in ["00000030S", "30000m", "0n", "29999999u", "1m"].iter() {
+        let (timeout, original) = clamp(&interceptor, Some(value));
+        assert_eq!(timeout.unwrap(), *value);
+        assert_eq!(original, None);
+    }
+
+    let (timeout, original) = clamp(&interceptor, Some("1H"));
+    assert_eq!(timeout.unwrap(), "30S");
+    assert_eq!(original, Some(OriginalTimeout(Some(Duration::from_secs(60 * 60)))));
+
+    let (timeout, original) = clamp(&interceptor, Some("31S"));
+    assert_eq!(timeout.unwrap(), "30S");
+    assert_eq!(original, Some(OriginalTimeout(Some(Duration::from_secs(31)))));
+
+    let (timeout, original) = clamp(&interceptor, None);
+    assert_eq!(timeout.unwrap(), "5S");
+    assert_eq!(original, Some(OriginalTimeout(None)));
+
+    let (timeout, original) = clamp(&interceptor, Some("5s"));
+    assert_eq!(timeout.unwrap(), "5S");
+    assert_eq!(original, Some(OriginalTimeout(None)));
+
+    //Default is capped by maximum
+    let (timeout, _) = clamp(&Clamp::new(Duration::from_millis(1500), Duration::from_secs(5)), None);
+    assert_eq!(timeout.unwrap(), "1500m");
+}
+
+#[test]
+fn should_clamp_timeout_in_metadata() {
+    let interceptor = Clamp::new(Duration::from_secs(30), Duration::from_secs(5));
+    let mut metadata = tonic::metadata::MetadataMap::new();
+    metadata.insert("grpc-timeout", "2H".parse().unwrap());
+    let mut extensions = http::Extensions::new();
+    assert!(interceptor.on_request(&mut metadata, &mut extensions).is_none());
+    assert_eq!(metadata.get("grpc-timeout").unwrap(), "30S");
+    assert_eq!(extensions.get::<OriginalTimeout>(), Some(&OriginalTimeout(Some(Duration::from_secs(2 * 60 * 60)))));
+}
+
+#[tokio::test]
+async fn should_derive_deadline_from_clamped_timeout() {
+    let timer = TestTimer::new();
+    let interceptor = InterceptorChain::new().with(Clamp::new(Duration::from_secs(30), Duration::from_secs(5))).with(InsertDeadline::new().clock(timer.clone()));
+    let service = tower::service_fn(|req: http::Request<()>| async move {
+        Ok::<_, Infallible>(http::Response::new(req.extensions().get::<Deadline>().copied()))
+    });
+    let service = InterceptorService::new(interceptor, service);
+    let request = http::Request::builder().header("grpc-timeout", "1H").body(()).unwrap();
+    let deadline = service.oneshot(request).await.expect("response").into_body().expect("deadline");
+    assert_eq!(deadline.remaining_with(&timer), Duration::from_secs(30));
+}
+
+#[test]
+fn should_pick_most_specific_method_timeout() {
+    let timeout = PerMethodTimeout::new(Duration::from_secs(30)).method("/pkg.Search/*", Duration::from_secs(10)).method("/pkg.Search/Query", Duration::from_secs(2)).method("/pkg.Search/Query", Duration::from_secs(5));
+    assert_eq!(timeout.timeout_for("/pkg.Search/Query"), Duration::from_secs(2));
+    assert_eq!(timeout.timeout_for("/pkg.Search/Suggest"), Duration::from_secs(10));
+    assert_eq!(timeout.timeout_for("/pkg.Other/Query"), Duration::from_secs(30));
+}
+
+//Runs `fut` to completion, advancing `timer` in steps of 100ms
+fn run<F: core::future::Future>(timer: &TestTimer, fut: F) -> F::Output {
+    let mut fut = core::pin::pin!(fut);
+    loop {
+        if let core::task::Poll::Ready(output) = with_noop_context(|ctx| fut.as_mut().poll(ctx)) {
+            return output;
+        }
+        timer.advance(Duration::from_millis(100));
+    }
+}
+
+fn call_with_timeout(timeout: &PerMethodTimeout, path: &'static str, grpc_timeout: Option<&'static str>, work: Duration) -> (http::HeaderMap, Option<http::HeaderValue>, Duration) {
+    use tower::Layer;
+
+    let timer = TestTimer::new();
+    let seen = Arc::new(Mutex::new(None));
+    let service = {
+        let seen = seen.clone();
+        let timer = timer.clone();
+        tower::service_fn(move |req: http::Request<()>| {
+            *seen.lock().unwrap() = req.headers().get("grpc-timeout").cloned();
+            let sleep = timer.sleep(work);
+            async move {
+                sleep.await;
+                Ok::<_, Infallible>(http::Response::new(()))
+            }
+        })
+    };
+    let mut request = http::Request::builder().uri(path);
+    if let Some(grpc_timeout) = grpc_timeout {
+        request = request.header("grpc-timeout", grpc_timeout);
+    }
+
+    let service = timeout.clone().timer(timer.clone()).layer().layer(service);
+    let response = run(&timer, service.oneshot(request.body(()).unwrap())).expect("response");
+    let seen = seen.lock().unwrap().take();
+    (response.headers().clone(), seen, timer.elapsed())
+}
+
+#[test]
+fn should_enforce_method_timeout() {
+    let timeout = PerMethodTimeout::new(Duration::from_secs(30)).method("/pkg.Search/Query", Duration::from_secs(2));
+
+    let (headers, grpc_timeout, elapsed) = call_with_timeout(&timeout, "/pkg.Search/Query", None, Duration::from_secs(5));
+    assert_eq!(headers.get("grpc-status").unwrap(), "4");
+    assert_eq!(headers.get("grpc-message").unwrap(), "method timeout of 2s exceeded");
+    assert_eq!(headers.get("content-type").unwrap(), "application/grpc");
+    assert_eq!(grpc_timeout.unwrap(), "2S");
+    assert_eq!(elapsed, Duration::from_secs(2));
+
+    //Default applies to other methods
+    let (headers, grpc_timeout, elapsed) = call_with_timeout(&timeout, "/pkg.Search/Suggest", None, Duration::from_secs(5));
+    assert_eq!(headers.get("grpc-status"), None);
+    assert_eq!(grpc_timeout.unwrap(), "30S");
+    assert_eq!(elapsed, Duration::from_secs(5));
+}
+
+#[test]
+fn should_use_lesser_of_method_and_client_timeouts() {
+    let timeout = PerMethodTimeout::new(Duration::from_secs(30)).method("/pkg.Search/Query", Duration::from_secs(2));
+
+    //Client asked for less, its timeout is kept as it is
+    let (headers, grpc_timeout, elapsed) = call_with_timeout(&timeout, "/pkg.Search/Query", Some("500m"), Duration::from_secs(5));
+    assert_eq!(headers.get("grpc-status").unwrap(), "4");
+    assert_eq!(headers.get("grpc-message").unwrap(), "client deadline of 500ms exceeded (method timeout is 2s)");
+    assert_eq!(grpc_timeout.unwrap(), "500m");
+    assert_eq!(elapsed, Duration::from_millis(500));
+
+    //Client asked for more, method's timeout wins
+    let (headers, grpc_timeout, elapsed) = call_with_timeout(&timeout, "/pkg.Search/Query", Some("1M"), Duration::from_secs(5));
+    assert_eq!(headers.get("grpc-message").unwrap(), "method timeout of 2s exceeded");
+    assert_eq!(grpc_timeout.unwrap(), "2S");
+    assert_eq!(elapsed, Duration::from_secs(2));
+
+    //Call completing in time is not affected
+    let (headers, _, elapsed) = call_with_timeout(&timeout, "/pkg.Search/Query", Some("1S"), Duration::from_millis(900));
+    assert_eq!(headers.get("grpc-status"), None);
+    assert_eq!(elapsed, Duration::from_millis(900));
+}
+
+#[test]
+fn should_insert_method_timeout() {
+    let timer = TestTimer::new();
+    let timeout = PerMethodTimeout::new(Duration::from_secs(30)).timer(timer.clone());
+    let mut headers = http::HeaderMap::new();
+    headers.insert("grpc-timeout", http::HeaderValue::from_static("10S"));
+    let mut extensions = http::Extensions::new();
+    assert!(timeout.on_request_headers(&http::Uri::from_static("/pkg.Search/Query"), &mut headers, &mut extensions).is_none());
+
+    let method = extensions.get::<MethodTimeout>().copied().expect("method timeout");
+    assert_eq!(method.limit, Duration::from_secs(30));
+    assert_eq!(method.client, Some(Duration::from_secs(10)));
+    assert_eq!(method.deadline.remaining_with(&timer), Duration::from_secs(10));
+    assert_eq!(extensions.get::<Deadline>(), Some(&method.deadline));
+
+    //Without URI request is passed through
+    let mut extensions = http::Extensions::new();
+    assert!(timeout.on_request(&mut tonic::metadata::MetadataMap::new(), &mut extensions).is_none());
+    assert!(extensions.get::<MethodTimeout>().is_none());
+}
+
+#[test]
+fn should_set_task_local_deadline_for_handler() {
+    use tonic_interceptor::deadline::remaining_with;
+    use tower::Layer;
+
+    let timer = TestTimer::new();
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let service = {
+        let seen = seen.clone();
+        let timer = timer.clone();
+        tower::service_fn(move |_: http::Request<()>| {
+            seen.lock().unwrap().push(remaining_with(&timer));
+            let seen = seen.clone();
+            let timer = timer.clone();
+            async move {
+                timer.sleep(Duration::from_secs(1)).await;
+                seen.lock().unwrap().push(remaining_with(&timer));
+                Ok::<_, Infallible>(http::Response::new(()))
+            }
+        })
+    };
+    let service = TaskLocalDeadline::new().clock(timer.clone()).layer().layer(service);
+
+    let request = http::Request::builder().header("grpc-timeout", "5S").body(()).unwrap();
+    run(&timer, service.clone().oneshot(request)).expect("response");
+    assert_eq!(*seen.lock().unwrap(), [Some(Duration::from_secs(5)), Some(Duration::from_secs(4))]);
+    assert_eq!(remaining_with(&timer), None);
+
+    //Without timeout there is no deadline
+    seen.lock().unwrap().clear();
+    run(&timer, service.oneshot(http::Request::new(()))).expect("response");
+    assert_eq!(*seen.lock().unwrap(), [None, None]);
+}
+
+#[tokio::test]
+async fn should_keep_outer_deadline_context() {
+    use tonic_interceptor::deadline::remaining_with;
+    use tower::Layer;
+
+    let timer = TestTimer::new();
+    let service = {
+        let timer = timer.clone();
+        tower::service_fn(move |_: http::Request<()>| {
+            let remaining = remaining_with(&timer);
+            async move {
+                Ok::<_, Infallible>(http::Response::new(remaining))
+            }
+        })
+    };
+    let service = TaskLocalDeadline::new().clock(timer.clone()).layer().layer(service);
+
+    let request = http::Request::builder().header("grpc-timeout", "2S").body(()).unwrap();
+    let outer = Deadline::after_with(&timer, Duration::from_secs(10));
+    let response = DeadlineContext::scope(outer, async move {
+        let response = service.oneshot(request).await.expect("response");
+        assert_eq!(remaining_with(&timer), Some(Duration::from_secs(10)));
+        response
+    }).await;
+    assert_eq!(*response.body(), Some(Duration::from_secs(2)));
+}
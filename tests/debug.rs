@@ -0,0 +1,94 @@
+#![cfg(feature = "tonic011")]
+#![allow(clippy::result_large_err)]
+
+use tonic_interceptor::{InterceptorFn, InterceptorService};
+
+use tonic::Status;
+use tower_service::Service;
+
+use core::future;
+
+#[derive(Clone, Debug)]
+struct Tag;
+
+impl tonic_interceptor::client::ClientInterceptor for Tag {
+    fn on_request(&self, _: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<Status> {
+        None
+    }
+
+    fn on_response(&self, _: &tonic::metadata::MetadataMap, _: &http::Extensions) {
+    }
+}
+
+#[derive(Debug)]
+struct Inner;
+
+fn on_request(headers: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<Status> {
+    match headers.contains_key("x-reject") {
+        true => Some(Status::permission_denied("BAD")),
+        false => None,
+    }
+}
+
+fn on_response(_: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+}
+
+type Callbacks = InterceptorFn<fn(&mut tonic::metadata::MetadataMap, &mut http::Extensions) -> Option<Status>, fn(tonic::Code, &mut http::HeaderMap, &http::Extensions)>;
+
+fn interceptor() -> Callbacks {
+    InterceptorFn {
+        on_request,
+        on_response,
+    }
+}
+
+#[test]
+fn should_format_closure_wrappers_without_debug_closures() {
+    let closures = InterceptorFn {
+        on_request: |_: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions| None,
+        on_response: |_: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions| {
+        }
+    };
+    assert_eq!(format!("{:?}", closures), "InterceptorFn { .. }");
+    assert_eq!(format!("{:?}", tonic_interceptor::interceptor(closures)), "InterceptorLayer { interceptor: InterceptorFn { .. }, on_rejection: false, .. }");
+    assert_eq!(format!("{:?}", interceptor()), "InterceptorFn { .. }");
+    assert_eq!(format!("{:?}", tonic_interceptor::interceptor(interceptor())), "InterceptorLayer { interceptor: InterceptorFn { .. }, on_rejection: false, .. }");
+    assert_eq!(format!("{:?}", tonic_interceptor::interceptor_arc(interceptor()).call_on_response_for_rejections(true)), "ArcInterceptorLayer { interceptor: InterceptorFn { .. }, on_rejection: true, .. }");
+}
+
+#[test]
+fn should_format_service_with_inner_values() {
+    assert_eq!(format!("{:?}", InterceptorService::new(Tag, Inner)), "InterceptorService { interceptor: Tag, on_rejection: false, inner: Inner, .. }");
+    let service = InterceptorService::new(Tag, Inner).with_error_handler(|_: &Status| None::<Status>).with_body(tonic::body::empty_body);
+    assert_eq!(format!("{:?}", service), "InterceptorService { interceptor: Tag, on_rejection: false, inner: Inner, .. }");
+
+    assert_eq!(format!("{:?}", tonic_interceptor::client::interceptor(Tag)), "ClientInterceptorLayer(Tag)");
+    assert_eq!(format!("{:?}", tonic_interceptor::client::ClientInterceptorService::new(Tag, Inner)), "ClientInterceptorService { interceptor: Tag, inner: Inner }");
+}
+
+#[test]
+fn should_format_future_without_debug_generics() {
+    let svc = tower::service_fn(|_: http::Request<()>| future::ready(Ok::<_, Status>(http::Response::new(()))));
+    let mut service = InterceptorService::new(interceptor(), svc);
+
+    let fut = service.call(http::Request::new(()));
+    assert_eq!(format!("{:?}", fut), "InterceptorFut { state: \"Fut\", .. }");
+
+    let request = http::Request::builder().header("x-reject", "1").body(()).unwrap();
+    let fut = service.call(request);
+    assert_eq!(format!("{:?}", fut), "InterceptorFut { state: \"Status\", .. }");
+}
+
+#[test]
+fn should_allow_derive_on_struct_storing_stack() {
+    #[derive(Debug)]
+    #[allow(dead_code)]
+    struct State<S> {
+        service: S,
+    }
+
+    let state = State {
+        service: tonic_interceptor::interceptor(interceptor()),
+    };
+    assert!(format!("{:?}", state).starts_with("State { service: InterceptorLayer {"));
+}
@@ -0,0 +1,245 @@
+#![cfg(feature = "tonic011")]
+use tonic_interceptor::{Interceptor, InterceptorFn, InterceptorService};
+use tonic_interceptor::debugging::{DiffLogger, MetadataDiff, Phase};
+use tonic_interceptor::redact::Redactor;
+use tonic_interceptor::testing::{poll_once, service_fn};
+
+use tonic::Status;
+use tonic::metadata::{MetadataMap, MetadataValue};
+use tower_service::Service;
+
+use core::convert::Infallible;
+use std::sync::{Arc, Mutex};
+
+type Callbacks = InterceptorFn<fn(&mut MetadataMap, &mut http::Extensions) -> Option<Status>, fn(tonic::Code, &mut http::HeaderMap, &http::Extensions)>;
+
+fn on_request(headers: &mut MetadataMap, _: &mut http::Extensions) -> Option<Status> {
+    headers.insert("x-added", "1".parse().unwrap());
+    headers.insert("x-tenant", "other".parse().unwrap());
+    headers.remove("x-removed");
+    headers.insert_bin("x-trace-bin", MetadataValue::from_bytes(b"\x01\x02"));
+    headers.insert("authorization", "Bearer rotated".parse().unwrap());
+    None
+}
+
+fn on_response(_: tonic::Code, headers: &mut http::HeaderMap, _: &http::Extensions) {
+    headers.insert("x-served-by", http::HeaderValue::from_static("edge"));
+    headers.remove("x-internal");
+}
+
+fn interceptor() -> Callbacks {
+    InterceptorFn {
+        on_request,
+        on_response,
+    }
+}
+
+fn logger() -> (DiffLogger<Callbacks>, Arc<Mutex<Vec<MetadataDiff>>>) {
+    let diffs = Arc::new(Mutex::new(Vec::new()));
+    let reported = diffs.clone();
+    let logger = DiffLogger::new(interceptor(), move |diff| reported.lock().unwrap().push(diff.clone())).activate_on("x-debug-diff");
+    (logger, diffs)
+}
+
+fn metadata(entries: &[(&'static str, &'static str)]) -> MetadataMap {
+    let mut metadata = MetadataMap::new();
+    for (key, value) in entries {
+        metadata.append(*key, value.parse().unwrap());
+    }
+    metadata
+}
+
+fn entry(key: &str, value: &str) -> (String, String) {
+    (key.to_owned(), value.to_owned())
+}
+
+#[test]
+fn should_report_request_diff() {
+    let (logger, diffs) = logger();
+    let mut headers = metadata(&[("x-debug-diff", "1"), ("x-tenant", "acme"), ("x-removed", "a"), ("x-removed", "b"), ("authorization", "Bearer secret")]);
+    headers.insert_bin("x-trace-bin", MetadataValue::from_bytes(b"\x01"));
+    assert!(logger.on_request(&mut headers, &mut http::Extensions::new()).is_none());
+
+    let diffs = diffs.lock().unwrap();
+    assert_eq!(diffs.len(), 1);
+    let diff = &diffs[0];
+    assert_eq!(diff.interceptor, logger.name());
+    assert_eq!(diff.phase, Phase::Request);
+    assert_eq!(diff.added, [entry("x-added", "1")]);
+    assert_eq!(diff.removed, [entry("x-removed", "a"), entry("x-removed", "b")]);
+    //Keys follow order of metadata map
+    let mut changed = diff.changed.clone();
+    changed.sort();
+    assert_eq!(changed, [
+        //Secret is redacted, but its change is reported
+        ("authorization".to_owned(), "***".to_owned(), "***".to_owned()),
+        ("x-tenant".to_owned(), "acme".to_owned(), "other".to_owned()),
+        //Binary values are base64 encoded
+        ("x-trace-bin".to_owned(), "AQ".to_owned(), "AQI".to_owned()),
+    ]);
+    assert!(diff.to_string().contains(" +x-added: 1;"));
+}
+
+#[test]
+fn should_skip_inactive_requests() {
+    let (logger, diffs) = logger();
+    let mut headers = metadata(&[("x-tenant", "acme")]);
+    logger.on_request(&mut headers, &mut http::Extensions::new());
+    //Interceptor is called regardless
+    assert_eq!(headers.get("x-tenant").unwrap(), "other");
+    let mut response = http::HeaderMap::new();
+    logger.on_response(tonic::Code::Ok, &mut response, &http::Extensions::new());
+    assert!(response.contains_key("x-served-by"));
+    assert!(diffs.lock().unwrap().is_empty());
+
+    //Global toggle
+    let toggle = logger.toggle();
+    toggle.enable();
+    logger.clone().on_request(&mut metadata(&[]), &mut http::Extensions::new());
+    logger.on_response(tonic::Code::Ok, &mut http::HeaderMap::new(), &http::Extensions::new());
+    toggle.disable();
+    logger.on_request(&mut metadata(&[]), &mut http::Extensions::new());
+
+    let diffs = diffs.lock().unwrap();
+    assert_eq!(diffs.iter().map(|diff| diff.phase).collect::<Vec<_>>(), [Phase::Request, Phase::Response]);
+    assert_eq!(diffs[1].added, [entry("x-served-by", "edge")]);
+}
+
+#[test]
+fn should_report_response_diff_of_activated_call() {
+    let (logger, diffs) = logger();
+    let logger = logger.redactor(Redactor::new().prefix("x-served"));
+    let mut svc = InterceptorService::new(logger, service_fn(|_: http::Request<()>| {
+        Ok::<_, Infallible>(http::Response::builder().header("x-internal", "node-1").header("grpc-status", "0").body(()).unwrap())
+    }));
+
+    let request = http::Request::builder().uri("/pkg.Svc/Call").header("x-debug-diff", "1").body(()).unwrap();
+    let response = poll_once(svc.call(request)).unwrap();
+    assert_eq!(response.headers()["x-served-by"], "edge");
+
+    {
+        let diffs = diffs.lock().unwrap();
+        assert_eq!(diffs.len(), 2);
+        assert_eq!(diffs[0].method, "/pkg.Svc/Call");
+        assert_eq!(diffs[1].method, "/pkg.Svc/Call");
+        assert_eq!(diffs[1].phase, Phase::Response);
+        assert_eq!(diffs[1].added, [entry("x-served-by", "***")]);
+        assert_eq!(diffs[1].removed, [entry("x-internal", "node-1")]);
+        assert!(diffs[1].changed.is_empty());
+    }
+
+    //Call without activation metadata
+    poll_once(svc.call(http::Request::builder().uri("/pkg.Svc/Call").body(()).unwrap())).unwrap();
+    assert_eq!(diffs.lock().unwrap().len(), 2);
+}
+
+mod payload {
+    use tonic_interceptor::debugging::{Direction, Payload, PayloadBody, PayloadLog};
+    use tonic_interceptor::testing::{poll_once, service_fn, with_noop_context};
+
+    use tower_layer::Layer;
+    use tower_service::Service;
+
+    use core::pin::Pin;
+    use core::convert::Infallible;
+    use std::sync::{Arc, Mutex};
+
+    use bytes::Bytes;
+
+    type Body = http_body::Full<Bytes>;
+
+    fn drain<B: http_body::Body<Data = Bytes> + Unpin>(mut body: B) -> Vec<u8> {
+        let mut data = Vec::new();
+        with_noop_context(|ctx| while let core::task::Poll::Ready(Some(Ok(chunk))) = http_body::Body::poll_data(Pin::new(&mut body), ctx) {
+            data.extend_from_slice(&chunk);
+        });
+        data
+    }
+
+    //Calls service with `path`, optionally activating logging, and drains both bodies
+    fn call(log: &PayloadLog, path: &str, activate: bool, request: &'static [u8], response: &'static [u8]) {
+        let svc = service_fn(move |req: http::Request<PayloadBody<Body>>| {
+            drain(req.into_body());
+            Ok::<_, Infallible>(http::Response::new(Body::from(response)))
+        });
+        let mut svc = log.layer().layer(svc);
+        let mut req = http::Request::builder().uri(path);
+        if activate {
+            req = req.header("x-debug-payload", "1");
+        }
+        let response = poll_once(svc.call(req.body(Body::from(request)).unwrap())).unwrap();
+        drain(response.into_body());
+    }
+
+    fn payload(method: &str, direction: Direction, bytes: &'static [u8], truncated: bool) -> Payload {
+        Payload {
+            method: method.to_owned(),
+            direction,
+            bytes: Bytes::from_static(bytes),
+            truncated,
+        }
+    }
+
+    fn configured<F: FnOnce(PayloadLog) -> PayloadLog>(configure: F) -> (PayloadLog, Arc<Mutex<Vec<Payload>>>) {
+        let payloads = Arc::new(Mutex::new(Vec::new()));
+        let sink = payloads.clone();
+        (configure(PayloadLog::new(move |payload| sink.lock().unwrap().push(payload.clone()))), payloads)
+    }
+
+    #[test]
+    fn should_capture_both_directions_up_to_limit() {
+        let (log, payloads) = configured(|log| log.max_bytes(4));
+
+        call(&log, "/pkg.Svc/Call", true, b"\x00\x01\x02\x03", b"\x00\x01\x02\x03\x04\x05");
+        assert_eq!(*payloads.lock().unwrap(), [
+            payload("/pkg.Svc/Call", Direction::Request, b"\x00\x01\x02\x03", false),
+            payload("/pkg.Svc/Call", Direction::Response, b"\x00\x01\x02\x03", true),
+        ]);
+        let payloads = payloads.lock().unwrap();
+        assert_eq!(payloads[1].hex(), "00010203");
+        assert_eq!(payloads[1].base64(), "AAECAw==");
+        assert_eq!(payloads[1].to_string(), "/pkg.Svc/Call response payload: 00010203...");
+    }
+
+    #[test]
+    fn should_log_only_activated_or_sampled_calls() {
+        let (log, payloads) = configured(|log| log);
+        call(&log, "/pkg.Svc/Call", false, b"req", b"resp");
+        assert!(payloads.lock().unwrap().is_empty());
+
+        let (log, payloads) = configured(|log| log.sample(50));
+        for _ in 0..4 {
+            call(&log, "/pkg.Svc/Call", false, b"req", b"resp");
+        }
+        assert_eq!(payloads.lock().unwrap().len(), 4);
+
+        //Sampling is restricted by allow and deny lists
+        let (log, payloads) = configured(|log| log.sample(100).allow("/pkg.Svc/*").deny("/pkg.Svc/Upload"));
+        call(&log, "/pkg.Other/Call", false, b"req", b"resp");
+        call(&log, "/pkg.Svc/Upload", false, b"req", b"resp");
+        assert!(payloads.lock().unwrap().is_empty());
+        call(&log, "/pkg.Svc/Call", false, b"req", b"resp");
+        assert_eq!(payloads.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn should_never_log_sensitive_methods() {
+        let (log, payloads) = configured(|log| log.sample(100).deny("/pkg.Svc/Upload").sensitive("/pkg.Auth/*"));
+
+        //Activation overrides deny list
+        call(&log, "/pkg.Svc/Upload", true, b"req", b"resp");
+        assert_eq!(payloads.lock().unwrap().iter().map(|payload| payload.direction).collect::<Vec<_>>(), [Direction::Request, Direction::Response]);
+        payloads.lock().unwrap().clear();
+
+        //Sensitive method overrides both activation and sampling
+        call(&log, "/pkg.Auth/Login", true, b"password", b"token");
+        call(&log, "/pkg.Auth/Login", false, b"password", b"token");
+        assert!(payloads.lock().unwrap().is_empty());
+        assert!(!log.is_logged("/pkg.Auth/Login", &http::HeaderMap::new()));
+
+        //Activation can be disabled
+        let (log, payloads) = configured(|log| log.activate_on(None));
+        call(&log, "/pkg.Svc/Call", true, b"req", b"resp");
+        assert!(payloads.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,119 @@
+#![cfg(feature = "tonic011")]
+#![allow(clippy::result_large_err)]
+
+use tonic_interceptor::{Deferred, DeferredInterceptor, HeaderOps, InterceptorService};
+use tonic_interceptor::testing::{poll_once, service_fn};
+
+use tonic::Status;
+use tower_service::Service;
+
+fn value(value: &'static str) -> http::HeaderValue {
+    http::HeaderValue::from_static(value)
+}
+
+fn values(headers: &http::HeaderMap, key: &str) -> Vec<String> {
+    headers.get_all(key).iter().map(|value| value.to_str().unwrap().to_owned()).collect()
+}
+
+#[test]
+fn should_apply_operations_in_order() {
+    let mut headers = http::HeaderMap::new();
+    headers.insert("x-replaced", value("old"));
+    headers.insert("x-appended", value("old"));
+    headers.insert("x-removed", value("old"));
+    headers.insert("x-removed-then-added", value("old"));
+    headers.insert("x-untouched", value("old"));
+
+    let mut ops = HeaderOps::new();
+    ops.append("x-replaced", value("1"));
+    ops.insert("x-replaced", value("2"));
+    ops.append("x-replaced", value("3"));
+    ops.append("x-appended", value("1"));
+    ops.append("x-appended", value("2"));
+    ops.insert("x-removed", value("1"));
+    ops.remove("x-removed");
+    ops.remove("x-removed-then-added");
+    ops.append("x-removed-then-added", value("1"));
+    ops.append("x-new", value("1"));
+    ops.remove("x-missing");
+    assert_eq!(ops.len(), 11);
+
+    let mut expected = headers.clone();
+    expected.insert("x-replaced", value("2"));
+    expected.append("x-replaced", value("3"));
+    expected.append("x-appended", value("1"));
+    expected.append("x-appended", value("2"));
+    expected.remove("x-removed");
+    expected.insert("x-removed-then-added", value("1"));
+    expected.append("x-new", value("1"));
+
+    ops.apply(&mut headers);
+    assert!(ops.is_empty());
+    assert_eq!(headers, expected);
+    assert_eq!(values(&headers, "x-replaced"), ["2", "3"]);
+    assert_eq!(values(&headers, "x-appended"), ["old", "1", "2"]);
+}
+
+struct Add(&'static str);
+
+impl DeferredInterceptor for Add {
+    fn on_request(&self, headers: &http::HeaderMap, ops: &mut HeaderOps, _: &mut http::Extensions) -> Option<Status> {
+        //Sees original headers only
+        assert!(!headers.contains_key("x-chain"));
+        ops.append("x-chain", value(self.0));
+        None
+    }
+
+    fn on_response(&self, _: tonic::Code, headers: &mut http::HeaderMap, _: &http::Extensions) {
+        headers.append("x-response", value(self.0));
+    }
+}
+
+struct Reject;
+
+impl DeferredInterceptor for Reject {
+    fn on_request(&self, headers: &http::HeaderMap, _: &mut HeaderOps, _: &mut http::Extensions) -> Option<Status> {
+        match headers.contains_key("x-reject") {
+            true => Some(Status::permission_denied("rejected")),
+            false => None,
+        }
+    }
+
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
+
+fn call<I: tonic_interceptor::Interceptor + Clone>(interceptor: I, request: http::Request<()>) -> (Option<http::HeaderMap>, http::Response<()>) {
+    let mut seen = None;
+    let response = {
+        let svc = service_fn(|request: http::Request<()>| {
+            seen = Some(request.headers().clone());
+            Ok::<_, Status>(http::Response::new(()))
+        });
+        let mut service = InterceptorService::new(interceptor, svc);
+        poll_once(service.call(request)).expect("response")
+    };
+    (seen, response)
+}
+
+#[test]
+fn should_apply_chain_once() {
+    let chain = std::sync::Arc::new(Deferred((Add("1"), Reject, Add("2"), Add("3"))));
+
+    let (seen, response) = call(chain.clone(), http::Request::new(()));
+    assert_eq!(values(&seen.expect("to call service"), "x-chain"), ["1", "2", "3"]);
+    assert_eq!(values(response.headers(), "x-response"), ["3", "2", "1"]);
+
+    let request = http::Request::builder().header("x-reject", "1").body(()).unwrap();
+    let (seen, response) = call(chain, request);
+    assert!(seen.is_none());
+    assert_eq!(response.headers().get("grpc-status").unwrap(), "7");
+}
+
+#[test]
+fn should_work_as_metadata_interceptor() {
+    let mut metadata = tonic::metadata::MetadataMap::new();
+    let result = tonic_interceptor::Interceptor::on_request(&Deferred((Add("1"), Add("2"))), &mut metadata, &mut http::Extensions::new());
+    assert!(result.is_none());
+    assert_eq!(metadata.get_all("x-chain").iter().count(), 2);
+}
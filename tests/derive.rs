@@ -0,0 +1,156 @@
+#![cfg(all(feature = "tonic011", feature = "derive"))]
+#![allow(clippy::result_large_err)]
+
+use tonic_interceptor::{Interceptor, InterceptorService, Raw, RawInterceptor};
+use tonic_interceptor::testing::{EventKind, Recorder};
+
+use tonic::Status;
+use tower::ServiceExt;
+
+use core::future;
+use core::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+
+type Log = Arc<Mutex<Vec<String>>>;
+
+struct Step {
+    name: &'static str,
+    log: Log,
+}
+
+impl Step {
+    fn new(name: &'static str, log: &Log) -> Self {
+        Self {
+            name,
+            log: log.clone(),
+        }
+    }
+
+}
+
+impl Interceptor for Step {
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<Status> {
+        self.log.lock().unwrap().push(format!("request:{}", self.name));
+        headers.append("x-steps", self.name.parse().unwrap());
+        None
+    }
+
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+        self.log.lock().unwrap().push(format!("response:{}", self.name));
+    }
+}
+
+struct Header;
+
+impl RawInterceptor for Header {
+    fn on_request(&self, headers: &mut http::HeaderMap, _: &mut http::Extensions) -> Option<Status> {
+        headers.insert("x-raw", http::HeaderValue::from_static("1"));
+        None
+    }
+
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
+
+#[derive(Interceptor)]
+struct Derived {
+    first: Step,
+    #[interceptor(skip)]
+    _name: &'static str,
+    raw: Raw<Header>,
+    second: Step,
+}
+
+#[derive(Interceptor)]
+struct Tuple(Recorder, #[interceptor(skip)] PhantomData<u8>, Recorder);
+
+#[derive(Interceptor)]
+struct Generic<A, B> {
+    first: A,
+    second: B,
+}
+
+struct HandWritten {
+    first: Step,
+    raw: Raw<Header>,
+    second: Step,
+}
+
+impl Interceptor for HandWritten {
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<Status> {
+        self.first.on_request(headers, extensions).or_else(|| self.raw.on_request(headers, extensions)).or_else(|| self.second.on_request(headers, extensions))
+    }
+
+    fn on_response(&self, status: tonic::Code, headers: &mut http::HeaderMap, extensions: &http::Extensions) {
+        self.second.on_response(status, headers, extensions);
+        self.raw.on_response(status, headers, extensions);
+        self.first.on_response(status, headers, extensions);
+    }
+}
+
+async fn run<I: Interceptor + Clone>(interceptor: I) -> (String, Option<String>) {
+    let svc = tower::service_fn(|request: http::Request<()>| {
+        let steps = request.headers().get_all("x-steps").iter().map(|step| step.to_str().unwrap().to_owned()).collect::<Vec<_>>().join(",");
+        assert_eq!(request.headers().get("x-raw").unwrap(), "1");
+        let response = http::Response::builder().header("grpc-status", "0").header("x-steps", steps).body(()).unwrap();
+        future::ready(Ok::<_, Status>(response))
+    });
+    let service = InterceptorService::new(interceptor, svc);
+    let response = service.oneshot(http::Request::new(())).await.expect("success");
+    let status = response.headers().get("grpc-status").unwrap().to_str().unwrap().to_owned();
+    let steps = response.headers().get("x-steps").map(|steps| steps.to_str().unwrap().to_owned());
+    (status, steps)
+}
+
+#[tokio::test]
+async fn should_match_hand_written_interceptor() {
+    let derived_log = Log::default();
+    let derived = Arc::new(Derived {
+        first: Step::new("first", &derived_log),
+        _name: "derived",
+        raw: Raw(Header),
+        second: Step::new("second", &derived_log),
+    });
+    let hand_log = Log::default();
+    let hand = Arc::new(HandWritten {
+        first: Step::new("first", &hand_log),
+        raw: Raw(Header),
+        second: Step::new("second", &hand_log),
+    });
+
+    let derived = run(derived).await;
+    let hand = run(hand).await;
+    assert_eq!(derived, hand);
+    assert_eq!(derived, ("0".to_owned(), Some("first,second".to_owned())));
+    assert_eq!(*derived_log.lock().unwrap(), *hand_log.lock().unwrap());
+    assert_eq!(*derived_log.lock().unwrap(), ["request:first", "request:second", "response:second", "response:first"]);
+}
+
+#[test]
+fn should_stop_at_first_rejection() {
+    let first = Recorder::new("first");
+    let second = first.share("second");
+    first.reject_nth(1, Status::permission_denied("first"));
+    let tuple = Tuple(first.clone(), PhantomData, second.clone());
+
+    let mut headers = tonic::metadata::MetadataMap::new();
+    let status = tuple.on_request(&mut headers, &mut http::Extensions::new()).expect("to reject");
+    assert_eq!(status.message(), "first");
+    assert_eq!(first.sequence(), [("first", EventKind::Request)]);
+
+    first.clear();
+    second.reject_nth(1, Status::permission_denied("second"));
+    let generic = Generic {
+        first,
+        second,
+    };
+    let status = generic.on_request_headers(&http::Uri::default(), &mut http::HeaderMap::new(), &mut http::Extensions::new()).expect("to reject");
+    assert_eq!(status.message(), "second");
+    generic.on_response(tonic::Code::Ok, &mut http::HeaderMap::new(), &http::Extensions::new());
+    assert_eq!(generic.first.sequence(), [
+        ("first", EventKind::Request),
+        ("second", EventKind::Request),
+        ("second", EventKind::Response(tonic::Code::Ok)),
+        ("first", EventKind::Response(tonic::Code::Ok)),
+    ]);
+}
@@ -0,0 +1,82 @@
+#![cfg(all(feature = "tonic011", feature = "prost-reflect"))]
+#![allow(clippy::result_large_err)]
+
+use tonic_interceptor::Interceptor;
+use tonic_interceptor::descriptor::{Descriptors, MethodInfo, MethodInfoInterceptor};
+
+use tonic::Code;
+
+//FileDescriptorSet of `data/descriptor.proto`
+const DESCRIPTOR: &[u8] = include_bytes!("data/descriptor.bin");
+
+fn lookup(interceptor: &MethodInfoInterceptor, path: &'static str) -> Result<Option<MethodInfo>, (Code, String)> {
+    let mut extensions = http::Extensions::new();
+    match interceptor.on_request_headers(&http::Uri::from_static(path), &mut http::HeaderMap::new(), &mut extensions) {
+        Some(status) => Err((status.code(), status.message().to_owned())),
+        None => Ok(MethodInfo::from_extensions(&extensions).cloned()),
+    }
+}
+
+#[test]
+fn should_decode_descriptor_set() {
+    let descriptors = Descriptors::decode(DESCRIPTOR).expect("valid descriptor");
+    assert_eq!(descriptors.len(), 6);
+    assert!(descriptors.pool().get_message_by_name("pkg.v1.Request").is_some());
+
+    let get = descriptors.method("/pkg.v1.Users/Get").expect("method");
+    assert_eq!(&*get.service, "pkg.v1.Users");
+    assert_eq!(&*get.method, "Get");
+    assert!(get.idempotent);
+    assert!(get.is_unary());
+
+    let expected = [
+        ("/pkg.v1.Users/Put", false, false, true),
+        ("/pkg.v1.Users/Delete", false, false, false),
+        ("/pkg.v1.Users/Watch", false, true, false),
+        ("/pkg.v1.Users/Upload", true, false, false),
+        ("/pkg.v1.Users/Chat", true, true, false),
+    ];
+    for (path, client_streaming, server_streaming, idempotent) in expected.iter() {
+        let info = descriptors.method(path).expect("method");
+        assert_eq!(info.client_streaming, *client_streaming, "{}", path);
+        assert_eq!(info.server_streaming, *server_streaming, "{}", path);
+        assert_eq!(info.idempotent, *idempotent, "{}", path);
+    }
+
+    assert!(descriptors.method("/pkg.v1.Users/Missing").is_none());
+    assert!(descriptors.method("/Users/Get").is_none());
+    assert!(Descriptors::decode(&DESCRIPTOR[..DESCRIPTOR.len() - 1]).is_err());
+    assert!(Descriptors::decode(&[]).expect("empty set").is_empty());
+}
+
+#[test]
+fn should_insert_method_info() {
+    let interceptor = MethodInfoInterceptor::new(DESCRIPTOR).expect("valid descriptor");
+
+    let info = lookup(&interceptor, "/pkg.v1.Users/Watch").expect("accepted").expect("method info");
+    assert_eq!(&*info.method, "Watch");
+    assert!(info.server_streaming);
+
+    //Unknown methods are passed through
+    assert_eq!(lookup(&interceptor, "/pkg.v1.Users/Missing"), Ok(None));
+    assert_eq!(lookup(&interceptor, "/grpc.health.v1.Health/Check"), Ok(None));
+
+    let mut extensions = http::Extensions::new();
+    let uri = http::Uri::from_static("/pkg.v1.Users/Put");
+    assert!(interceptor.on_request_with_uri(&uri, &mut tonic::metadata::MetadataMap::new(), &mut extensions).is_none());
+    assert!(MethodInfo::from_extensions(&extensions).expect("method info").idempotent);
+
+    //Without URI request is passed through
+    let mut extensions = http::Extensions::new();
+    assert!(interceptor.on_request(&mut tonic::metadata::MetadataMap::new(), &mut extensions).is_none());
+    assert!(MethodInfo::from_extensions(&extensions).is_none());
+}
+
+#[test]
+fn should_reject_unknown_methods() {
+    let interceptor = MethodInfoInterceptor::new(DESCRIPTOR).expect("valid descriptor").reject_unknown(true);
+
+    assert!(lookup(&interceptor, "/pkg.v1.Users/Get").expect("accepted").is_some());
+    assert_eq!(lookup(&interceptor, "/pkg.v1.Users/Missing"), Err((Code::Unimplemented, "unknown method '/pkg.v1.Users/Missing'".to_owned())));
+    assert_eq!(lookup(&interceptor, "/pkg.v1.Other/Get"), Err((Code::Unimplemented, "unknown method '/pkg.v1.Other/Get'".to_owned())));
+}
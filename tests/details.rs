@@ -0,0 +1,69 @@
+#![cfg(all(feature = "tonic011", feature = "details"))]
+#![allow(clippy::result_large_err)]
+
+use tonic_interceptor::{InterceptorFn, InterceptorService};
+use tonic_interceptor::details::StatusDetails;
+use tonic_interceptor::testing::{poll_once, service_fn};
+
+use tonic::Status;
+use tower_service::Service;
+
+//Rejects with `status` and returns status decoded from rejection response
+fn reject(status: Status) -> Status {
+    let interceptor = InterceptorFn {
+        on_request: move |_: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions| Some(status.clone()),
+        on_response: |_: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions| {
+        }
+    };
+    let svc = service_fn(|_: http::Request<()>| Ok::<_, Status>(http::Response::new(())));
+    let mut service = InterceptorService::new(interceptor, svc);
+
+    let response = poll_once(service.call(http::Request::new(()))).expect("response");
+
+    let details = response.headers().get("grpc-status-details-bin").expect("details header");
+    assert!(!details.as_bytes().contains(&b'='), "details must be base64 without padding");
+    Status::from_header_map(response.headers()).expect("status")
+}
+
+fn assert_round_trip(details: StatusDetails) {
+    let status = reject(details.clone().into_status(tonic::Code::InvalidArgument, "invalid request"));
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    assert_eq!(status.message(), "invalid request");
+    assert_eq!(StatusDetails::from_status(&status).expect("to decode"), details);
+}
+
+#[test]
+fn should_round_trip_bad_request() {
+    assert_round_trip(StatusDetails::new().bad_request("name", "must not be empty").bad_request("items[0].id", "unknown id"));
+}
+
+#[test]
+fn should_round_trip_error_info() {
+    assert_round_trip(StatusDetails::new().error_info("API_DISABLED", "example.com", [("service", "echo"), ("region", "eu")]));
+}
+
+#[test]
+fn should_round_trip_precondition_failure() {
+    assert_round_trip(StatusDetails::new().precondition_failure("TOS", "user:1", "terms of service not accepted"));
+}
+
+#[test]
+fn should_round_trip_quota_failure() {
+    assert_round_trip(StatusDetails::new().quota_failure("project:1", "daily limit exceeded"));
+}
+
+#[test]
+fn should_round_trip_combined_details() {
+    let details = StatusDetails::new().bad_request("name", "too long")
+                                      .error_info("INVALID", "example.com", Vec::<(String, String)>::new())
+                                      .precondition_failure("STATE", "order:1", "already shipped")
+                                      .quota_failure("user:1", "too many requests");
+    assert!(!details.is_empty());
+    assert_round_trip(details);
+}
+
+#[test]
+fn should_decode_status_without_details() {
+    let details = StatusDetails::from_status(&Status::internal("no details")).expect("to decode");
+    assert!(details.is_empty());
+}
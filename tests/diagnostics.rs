@@ -0,0 +1,207 @@
+#![cfg(all(feature = "tonic011", feature = "diagnostics"))]
+
+use tonic_interceptor::{Interceptor, InterceptorChain, InterceptorService};
+use tonic_interceptor::diagnostics::PollStats;
+use tonic_interceptor::testing::with_noop_context;
+
+use core::future::Future;
+use core::pin::Pin;
+use core::task;
+use std::sync::{Arc, Mutex};
+use tower::Service;
+
+#[derive(Clone, Default)]
+struct Collect {
+    reject: bool,
+    stats: Arc<Mutex<Vec<PollStats>>>,
+}
+
+impl Interceptor for Collect {
+    fn on_request(&self, _: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+        match self.reject {
+            true => Some(tonic::Status::permission_denied("rejected")),
+            false => None,
+        }
+    }
+
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+
+    fn on_poll_stats(&self, stats: PollStats) {
+        self.stats.lock().unwrap().push(stats);
+    }
+}
+
+//Future, which is pending given number of times before completion
+struct Pending {
+    remaining: usize,
+}
+
+impl Future for Pending {
+    type Output = Result<http::Response<()>, &'static str>;
+
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        match self.remaining {
+            0 => task::Poll::Ready(Ok(http::Response::new(()))),
+            _ => {
+                self.remaining -= 1;
+                std::thread::sleep(core::time::Duration::from_millis(1));
+                ctx.waker().wake_by_ref();
+                task::Poll::Pending
+            },
+        }
+    }
+}
+
+#[derive(Clone)]
+struct PendingService(usize);
+
+impl Service<http::Request<()>> for PendingService {
+    type Response = http::Response<()>;
+    type Error = &'static str;
+    type Future = Pending;
+
+    fn poll_ready(&mut self, _: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        task::Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _: http::Request<()>) -> Self::Future {
+        Pending {
+            remaining: self.0,
+        }
+    }
+}
+
+//Polls future `polls` times at most, returning whether it completed
+fn drive<F: Future>(fut: F, polls: usize) -> bool {
+    let mut fut = Box::pin(fut);
+    with_noop_context(|ctx| (0..polls).any(|_| fut.as_mut().poll(ctx).is_ready()))
+}
+
+#[test]
+fn should_report_completed_future() {
+    let interceptor = Collect::default();
+    let mut service = InterceptorService::new(interceptor.clone(), PendingService(3));
+
+    assert!(drive(service.call(http::Request::new(())), 10));
+    let stats = interceptor.stats.lock().unwrap().clone();
+    assert_eq!(stats.len(), 1);
+    assert_eq!(stats[0].polls, 4);
+    assert!(stats[0].completed);
+    assert!(stats[0].busy >= core::time::Duration::from_millis(3), "{:?}", stats[0]);
+    assert!(stats[0].elapsed >= stats[0].busy, "{:?}", stats[0]);
+}
+
+#[test]
+fn should_report_dropped_future() {
+    let interceptor = Collect::default();
+    let mut service = InterceptorService::new(interceptor.clone(), PendingService(5));
+
+    assert!(!drive(service.call(http::Request::new(())), 2));
+    let stats = interceptor.stats.lock().unwrap().clone();
+    assert_eq!(stats.len(), 1);
+    assert_eq!(stats[0].polls, 2);
+    assert!(!stats[0].completed);
+
+    //Future, which is never polled, has nothing to report
+    drop(service.call(http::Request::new(())));
+    assert_eq!(interceptor.stats.lock().unwrap().len(), 1);
+}
+
+#[test]
+fn should_report_rejected_request() {
+    let interceptor = Collect {
+        reject: true,
+        ..Collect::default()
+    };
+    //Future of rejected request holds interceptor only to call `on_response`
+    let mut service = InterceptorService::new(interceptor.clone(), PendingService(5));
+    assert!(drive(service.call(http::Request::new(())), 1));
+    assert!(interceptor.stats.lock().unwrap().is_empty());
+
+    let mut service = service.call_on_response_for_rejections(true);
+    assert!(drive(service.call(http::Request::new(())), 1));
+    let stats = interceptor.stats.lock().unwrap().clone();
+    assert_eq!(stats.len(), 1);
+    assert_eq!(stats[0].polls, 1);
+    assert!(stats[0].completed);
+}
+
+#[test]
+fn should_not_clone_interceptor() {
+    #[derive(Default)]
+    struct Counted {
+        clones: Arc<std::sync::atomic::AtomicUsize>,
+        stats: Collect,
+    }
+
+    impl Clone for Counted {
+        fn clone(&self) -> Self {
+            self.clones.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Self {
+                clones: self.clones.clone(),
+                stats: self.stats.clone(),
+            }
+        }
+    }
+
+    impl Interceptor for Counted {
+        fn on_request(&self, _: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+            None
+        }
+
+        fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+        }
+
+        fn on_poll_stats(&self, stats: PollStats) {
+            self.stats.on_poll_stats(stats)
+        }
+    }
+
+    let interceptor = Counted::default();
+    let stats = interceptor.stats.stats.clone();
+    let clones = interceptor.clones.clone();
+    let mut service = InterceptorService::new(interceptor, PendingService(1));
+    assert!(drive(service.call(http::Request::new(())), 10));
+    assert!(!drive(service.call(http::Request::new(())), 1));
+    //Single clone is held by future to call `on_response`
+    assert_eq!(clones.load(std::sync::atomic::Ordering::SeqCst), 2);
+    let stats = stats.lock().unwrap();
+    assert_eq!(stats.len(), 2);
+    assert!(stats[0].completed);
+    assert!(!stats[1].completed);
+}
+
+#[cfg(feature = "derive")]
+#[test]
+fn should_forward_stats_through_derive() {
+    #[derive(Interceptor)]
+    struct Derived(Collect, Arc<Collect>);
+
+    let first = Collect::default();
+    let second = Arc::new(Collect::default());
+    let derived = Arc::new(Derived(first.clone(), second.clone()));
+    let mut service = InterceptorService::new(derived, PendingService(1));
+
+    assert!(drive(service.call(http::Request::new(())), 10));
+    for stats in [&first.stats, &second.stats].iter() {
+        let stats = stats.lock().unwrap();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].polls, 2);
+    }
+}
+
+#[test]
+fn should_forward_stats_through_wrappers() {
+    let first = Collect::default();
+    let second = Collect::default();
+    let chain = Arc::new(InterceptorChain::new().with(first.clone()).with(Arc::new(second.clone())));
+    let mut service = InterceptorService::new(chain, PendingService(1));
+
+    assert!(drive(service.call(http::Request::new(())), 10));
+    for interceptor in [first, second].iter() {
+        let stats = interceptor.stats.lock().unwrap();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].polls, 2);
+    }
+}
@@ -0,0 +1,150 @@
+#![cfg(feature = "tonic011")]
+#![allow(clippy::result_large_err)]
+
+//Interceptor applied via `Server::layer` over generated echo service, called by real client
+
+mod common;
+
+use common::{EchoClient, EchoServer, EchoService};
+use common::echo::EchoRequest;
+
+use tonic_interceptor::Interceptor;
+
+use tonic::{Code, Status};
+use tonic::codec::CompressionEncoding;
+use tonic::transport::Channel;
+
+#[derive(Clone)]
+struct Gateway;
+
+impl Interceptor for Gateway {
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<Status> {
+        if let Some(reason) = headers.get("x-deny").cloned() {
+            let mut status = Status::permission_denied("доступ запрещён: 100%");
+            status.metadata_mut().insert("x-deny-reason", reason);
+            return Some(status);
+        }
+
+        headers.remove("x-internal");
+        let user = headers.get("authorization").and_then(|value| value.to_str().ok()).and_then(|value| value.strip_prefix("Bearer ")).map(str::to_owned);
+        if let Some(user) = user {
+            headers.insert("x-user", user.parse().unwrap());
+        }
+        None
+    }
+
+    fn on_response(&self, status: Code, headers: &mut http::HeaderMap, _: &http::Extensions) {
+        headers.insert("x-served-by", http::HeaderValue::from_static("gateway"));
+        headers.insert("x-intercepted-code", http::HeaderValue::from(status as i32));
+    }
+}
+
+async fn spawn() -> (EchoService, EchoClient<Channel>) {
+    let service = EchoService::default();
+    let (incoming, addr) = common::listen().await;
+    let layer = tonic_interceptor::interceptor(Gateway).call_on_response_for_rejections(true);
+    let server = EchoServer::new(service.clone()).accept_compressed(CompressionEncoding::Gzip).send_compressed(CompressionEncoding::Gzip);
+    let router = tonic::transport::Server::builder().layer(layer).add_service(server);
+    tokio::spawn(router.serve_with_incoming(incoming));
+    (service, EchoClient::new(common::connect(addr).await))
+}
+
+fn request(message: &str) -> tonic::Request<EchoRequest> {
+    tonic::Request::new(EchoRequest {
+        message: message.to_owned(),
+    })
+}
+
+#[tokio::test]
+async fn should_expose_modified_metadata_to_handler() {
+    let (service, mut client) = spawn().await;
+
+    let mut request = request("hello");
+    request.metadata_mut().insert("authorization", "Bearer alice".parse().unwrap());
+    request.metadata_mut().insert("x-internal", "secret".parse().unwrap());
+    request.metadata_mut().insert_bin("x-trace-bin", tonic::metadata::MetadataValue::from_bytes(b"\x00\x01"));
+    let response = client.unary(request).await.expect("success");
+    assert_eq!(response.get_ref().message, "hello");
+
+    let requests = service.requests();
+    assert_eq!(requests.len(), 1);
+    let metadata = &requests[0].1;
+    assert_eq!(metadata.get("x-user").unwrap(), "alice");
+    assert!(metadata.get("x-internal").is_none());
+    assert_eq!(metadata.get_bin("x-trace-bin").unwrap().to_bytes().unwrap().as_ref(), b"\x00\x01");
+    //Echoed by handler
+    assert_eq!(response.metadata().get("x-user").unwrap(), "alice");
+}
+
+#[tokio::test]
+async fn should_surface_rejection_with_metadata() {
+    let (service, mut client) = spawn().await;
+
+    let mut unary = request("hello");
+    unary.metadata_mut().insert("x-deny", "banned".parse().unwrap());
+    let status = client.unary(unary).await.expect_err("to reject");
+    assert_eq!(status.code(), Code::PermissionDenied);
+    assert_eq!(status.message(), "доступ запрещён: 100%");
+    assert_eq!(status.metadata().get("x-deny-reason").unwrap(), "banned");
+    assert_eq!(status.metadata().get("x-served-by").unwrap(), "gateway");
+    assert_eq!(status.metadata().get("x-intercepted-code").unwrap(), "7");
+    assert_eq!(service.calls(), 0);
+
+    //Streaming call is rejected the same way
+    let mut stream = request("a b");
+    stream.metadata_mut().insert("x-deny", "banned".parse().unwrap());
+    let status = client.stream(stream).await.expect_err("to reject");
+    assert_eq!(status.code(), Code::PermissionDenied);
+    assert_eq!(service.calls(), 0);
+}
+
+#[tokio::test]
+async fn should_deliver_response_headers_to_client() {
+    let (_, mut client) = spawn().await;
+
+    let response = client.unary(request("hello")).await.expect("success");
+    assert_eq!(response.metadata().get("x-served-by").unwrap(), "gateway");
+    //Status of successful call is sent in trailers, hence it is not known yet
+    assert_eq!(response.metadata().get("x-intercepted-code").unwrap(), "2");
+
+    //Handler error is returned as trailers-only response
+    let status = client.unary(request("error:5")).await.expect_err("to fail");
+    assert_eq!(status.code(), Code::NotFound);
+    assert_eq!(status.metadata().get("x-served-by").unwrap(), "gateway");
+    assert_eq!(status.metadata().get("x-intercepted-code").unwrap(), "5");
+}
+
+#[tokio::test]
+async fn should_keep_streaming_trailers() {
+    let (_, mut client) = spawn().await;
+
+    let response = client.stream(request("a b c")).await.expect("success");
+    assert_eq!(response.metadata().get("x-served-by").unwrap(), "gateway");
+    let mut stream = response.into_inner();
+    let mut messages = Vec::new();
+    while let Some(item) = stream.message().await.expect("item") {
+        messages.push(item.message);
+    }
+    assert_eq!(messages, ["a", "b", "c"]);
+
+    let mut stream = client.stream(request("a error:14")).await.expect("success").into_inner();
+    let status = loop {
+        match stream.message().await {
+            Ok(Some(_)) => continue,
+            Ok(None) => panic!("stream should fail"),
+            Err(status) => break status,
+        }
+    };
+    assert_eq!(status.code(), Code::Unavailable);
+}
+
+#[tokio::test]
+async fn should_pass_compressed_messages() {
+    let (service, client) = spawn().await;
+    let mut client = client.send_compressed(CompressionEncoding::Gzip).accept_compressed(CompressionEncoding::Gzip);
+
+    let message = "compressible ".repeat(64);
+    let response = client.unary(request(&message)).await.expect("success");
+    assert_eq!(response.get_ref().message, message);
+    assert_eq!(service.requests()[0].1.get("grpc-encoding").unwrap(), "gzip");
+}
@@ -0,0 +1,198 @@
+#![cfg(feature = "tonic011")]
+#![allow(clippy::result_large_err)]
+
+use tonic_interceptor::{Interceptor, ext};
+use tonic_interceptor::ext::Scoped;
+use tonic_interceptor::testing::{poll_once, service_fn};
+
+use tonic::{Code, Status};
+use tower_service::Service;
+
+#[derive(Debug, PartialEq)]
+struct User(&'static str);
+
+#[derive(Clone)]
+struct Auth(&'static str);
+
+impl Interceptor for Auth {
+    fn on_request(&self, _: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<Status> {
+        ext::insert_unique(extensions, User(self.0)).err()
+    }
+
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
+
+#[test]
+fn should_get_or_reject() {
+    let mut extensions = http::Extensions::new();
+    let status = ext::get_or_reject::<User>(&extensions).expect_err("to be missing");
+    assert_eq!(status.code(), Code::Internal);
+    assert_eq!(status.message(), "missing extension 'ext::User'");
+
+    extensions.insert(User("admin"));
+    assert_eq!(ext::get_or_reject::<User>(&extensions).unwrap(), &User("admin"));
+    ext::get_mut_or_reject::<User>(&mut extensions).unwrap().0 = "guest";
+    assert_eq!(extensions.get::<User>().unwrap(), &User("guest"));
+}
+
+#[test]
+fn should_detect_double_insert() {
+    let mut extensions = http::Extensions::new();
+    ext::insert_unique(&mut extensions, User("admin")).expect("to insert");
+
+    let status = ext::insert_unique(&mut extensions, User("guest")).expect_err("to detect duplicate");
+    assert_eq!(status.code(), Code::Internal);
+    assert_eq!(status.message(), "duplicate extension 'ext::User'");
+    //First value is kept
+    assert_eq!(extensions.get::<User>().unwrap(), &User("admin"));
+
+    //Scoped is distinct type
+    ext::insert_unique(&mut extensions, Scoped(User("scoped"))).expect("to insert");
+    assert_eq!(extensions.get::<Scoped<User>>().unwrap().0, User("scoped"));
+    assert_eq!(ext::get_or_reject::<User>(&extensions).unwrap(), &User("admin"));
+}
+
+#[test]
+fn should_reject_chain_with_two_auth_interceptors() {
+    let svc = service_fn(|request: http::Request<()>| {
+        let user = ext::get_or_reject::<User>(request.extensions()).map(|user| user.0);
+        user.map(|user| http::Response::builder().header("x-user", user).body(()).unwrap())
+    });
+
+    let mut service = tower::ServiceBuilder::new().layer(tonic_interceptor::interceptor(Auth("first"))).service(svc);
+    let response = poll_once(service.call(http::Request::new(()))).expect("success");
+    assert_eq!(response.headers().get("x-user").unwrap(), "first");
+
+    let mut service = tower::ServiceBuilder::new().layer(tonic_interceptor::interceptor(Auth("first"))).layer(tonic_interceptor::interceptor(Auth("second"))).service(svc);
+    let response = poll_once(service.call(http::Request::new(()))).expect("success");
+    assert_eq!(response.headers().get("grpc-status").unwrap(), "13");
+    assert!(response.headers().get("x-user").is_none());
+}
+
+#[test]
+fn should_carry_extensions_to_on_response() {
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Debug, PartialEq)]
+    struct Started(u32);
+
+    #[derive(Clone, Default)]
+    struct Carrier(Arc<Mutex<Vec<Option<u32>>>>);
+
+    impl Interceptor for Carrier {
+        fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<Status> {
+            ext::Carried::of_request(extensions).insert(Started(42));
+            match headers.contains_key("x-reject") {
+                true => Some(Status::permission_denied("rejected")),
+                false => None,
+            }
+        }
+
+        fn on_response(&self, _: Code, _: &mut http::HeaderMap, extensions: &http::Extensions) {
+            self.0.lock().unwrap().push(ext::Carried::get_from::<Started>(extensions).map(|started| started.0));
+        }
+    }
+
+    let carrier = Carrier::default();
+    let svc = service_fn(|req: http::Request<()>| {
+        assert!(req.extensions().get::<ext::Carried>().is_none());
+        Ok::<_, Status>(http::Response::new(()))
+    });
+    let mut service = tonic_interceptor::InterceptorService::new(carrier.clone(), svc).call_on_response_for_rejections(true);
+
+    let response = poll_once(service.call(http::Request::new(()))).expect("response");
+    assert!(response.extensions().get::<ext::Carried>().is_none());
+    let request = http::Request::builder().header("x-reject", "1").body(()).unwrap();
+    let response = poll_once(service.call(request)).expect("response");
+    assert!(response.extensions().get::<ext::Carried>().is_none());
+    assert_eq!(*carrier.0.lock().unwrap(), [Some(42), Some(42)]);
+}
+
+#[derive(Clone, Default)]
+struct SnapshotRecorder(std::sync::Arc<std::sync::Mutex<Vec<Option<ext::RequestMetadataSnapshot>>>>);
+
+impl Interceptor for SnapshotRecorder {
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<Status> {
+        //Snapshot is taken before interceptor
+        headers.remove("x-api-key");
+        match headers.contains_key("x-reject") {
+            true => Some(Status::permission_denied("rejected")),
+            false => None,
+        }
+    }
+
+    fn on_response(&self, _: Code, _: &mut http::HeaderMap, extensions: &http::Extensions) {
+        self.0.lock().unwrap().push(ext::RequestMetadataSnapshot::from_extensions(extensions).cloned());
+    }
+}
+
+fn snapshot_request(entries: &[(&'static str, &'static str)]) -> http::Request<()> {
+    let mut request = http::Request::new(());
+    for (key, value) in entries {
+        request.headers_mut().append(*key, http::HeaderValue::from_static(value));
+    }
+    request
+}
+
+#[test]
+fn should_capture_listed_request_metadata() {
+    use tower_layer::Layer;
+
+    let recorder = SnapshotRecorder::default();
+    let svc = service_fn(|req: http::Request<()>| {
+        assert!(!req.headers().contains_key("x-api-key"));
+        match req.headers().contains_key("x-fail") {
+            true => Err("failed"),
+            false => Ok(http::Response::new(())),
+        }
+    });
+    let layer = tonic_interceptor::interceptor(recorder.clone()).call_on_response_for_rejections(true).capture_request_metadata(ext::MetadataCapture::keys(&["x-api-key", "X-Page-Size"]));
+    let mut service = layer.layer(svc).with_error_handler(|_: &&'static str| Some(Status::unavailable("mapped")));
+
+    let entries = [("x-api-key", "key-1"), ("x-page-size", "50"), ("x-page-size", "100"), ("x-other", "1")];
+    poll_once(service.call(snapshot_request(&entries))).expect("response");
+    let mut rejected = entries.to_vec();
+    rejected.push(("x-reject", "1"));
+    let response = poll_once(service.call(snapshot_request(&rejected))).expect("response");
+    assert_eq!(response.headers()["grpc-status"], "7");
+    let mut failed = entries.to_vec();
+    failed.push(("x-fail", "1"));
+    let response = poll_once(service.call(snapshot_request(&failed))).expect("response");
+    assert_eq!(response.headers()["grpc-status"], "14");
+
+    let snapshots = recorder.0.lock().unwrap();
+    assert_eq!(snapshots.len(), 3);
+    for snapshot in snapshots.iter() {
+        let snapshot = snapshot.as_ref().expect("snapshot");
+        assert_eq!(snapshot.get("x-api-key").unwrap(), "key-1");
+        assert_eq!(snapshot.get_all("x-page-size").collect::<Vec<_>>(), ["50", "100"]);
+        assert!(!snapshot.contains_key("x-other"));
+        assert!(!snapshot.contains_key("x-reject"));
+        assert_eq!(snapshot.len(), 3);
+        assert!(!snapshot.is_truncated());
+    }
+}
+
+#[test]
+fn should_capture_all_request_metadata_up_to_limit() {
+    let recorder = SnapshotRecorder::default();
+    let svc = service_fn(|_: http::Request<()>| Ok::<_, Status>(http::Response::new(())));
+    let mut service = tonic_interceptor::InterceptorService::new(recorder.clone(), svc).capture_request_metadata(ext::MetadataCapture::all().max_bytes(20));
+
+    //9 + 5, then 11 + 2 exceeds limit
+    poll_once(service.call(snapshot_request(&[("x-api-key", "key-1"), ("x-page-size", "50")]))).expect("response");
+    poll_once(service.call(snapshot_request(&[("x-a", "1"), ("x-b", "2")]))).expect("response");
+    //Disabled
+    let mut service = service.capture_request_metadata(None);
+    poll_once(service.call(snapshot_request(&[("x-a", "1")]))).expect("response");
+
+    let snapshots = recorder.0.lock().unwrap();
+    let snapshot = snapshots[0].as_ref().expect("snapshot");
+    assert_eq!(snapshot.len(), 1);
+    assert!(snapshot.is_truncated());
+    let snapshot = snapshots[1].as_ref().expect("snapshot");
+    assert_eq!(snapshot.iter().map(|(key, value)| (key.as_str(), value.to_str().unwrap())).collect::<std::collections::BTreeSet<_>>(), [("x-a", "1"), ("x-b", "2")].iter().copied().collect());
+    assert!(!snapshot.is_truncated());
+    assert!(snapshots[2].is_none());
+}
@@ -0,0 +1,120 @@
+#![cfg(feature = "tonic011")]
+#![allow(clippy::result_large_err)]
+
+use tonic_interceptor::{Interceptor, InterceptorService};
+use tonic_interceptor::flags::{DynamicFlags, FlagContext, FlagProvider, Flagged, Identity, StaticFlags, Tenant};
+use tonic_interceptor::testing::{poll_once, service_fn};
+
+use tonic::Status;
+use tower_service::Service;
+
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+struct Maintenance {
+    responses: AtomicUsize,
+}
+
+impl Interceptor for Maintenance {
+    fn on_request(&self, _: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<Status> {
+        Some(Status::unavailable("maintenance"))
+    }
+
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+        self.responses.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+fn maintenance() -> Maintenance {
+    Maintenance {
+        responses: AtomicUsize::new(0),
+    }
+}
+
+//Flag, method, tenant and identity of evaluation
+type Evaluation = (String, String, Option<String>, Option<String>);
+
+//Provider, which is switched by test and records every evaluation
+#[derive(Default)]
+struct Scripted {
+    enabled: AtomicBool,
+    seen: Mutex<Vec<Evaluation>>,
+}
+
+impl FlagProvider for Scripted {
+    fn is_enabled(&self, flag: &str, ctx: &FlagContext<'_>) -> bool {
+        self.seen.lock().unwrap().push((flag.to_owned(), ctx.method().to_owned(), ctx.tenant().map(str::to_owned), ctx.identity().map(str::to_owned)));
+        self.enabled.load(Ordering::SeqCst)
+    }
+}
+
+fn call<B, S: Service<http::Request<()>, Response = http::Response<B>>>(service: &mut S) -> &'static str where S::Error: core::fmt::Debug {
+    let mut request = http::Request::builder().uri("http://localhost/pkg.Echo/Unary").body(()).unwrap();
+    request.extensions_mut().insert(Tenant("acme".to_owned()));
+    request.extensions_mut().insert(Identity("alice".to_owned()));
+    let response = poll_once(service.call(request)).expect("response");
+    match response.headers().get("grpc-status") {
+        Some(_) => "rejected",
+        None => "passed",
+    }
+}
+
+#[test]
+fn should_toggle_interceptor_per_request() {
+    let provider = Arc::new(Scripted::default());
+    let mut service = InterceptorService::new(Flagged::new("maintenance", provider.clone(), Arc::new(maintenance())), service_fn(|_: http::Request<()>| Ok::<_, Status>(http::Response::new(()))));
+
+    assert_eq!(call(&mut service), "passed");
+    provider.enabled.store(true, Ordering::SeqCst);
+    assert_eq!(call(&mut service), "rejected");
+    assert_eq!(call(&mut service), "rejected");
+    provider.enabled.store(false, Ordering::SeqCst);
+    assert_eq!(call(&mut service), "passed");
+
+    let seen = provider.seen.lock().unwrap();
+    assert_eq!(seen.len(), 4);
+    for entry in seen.iter() {
+        assert_eq!(entry, &("maintenance".to_owned(), "/pkg.Echo/Unary".to_owned(), Some("acme".to_owned()), Some("alice".to_owned())));
+    }
+}
+
+#[test]
+fn should_pass_every_response_to_interceptor() {
+    let flagged = Flagged::new("maintenance", StaticFlags::new(), maintenance());
+    let mut metadata = tonic::metadata::MetadataMap::new();
+    assert!(flagged.on_request(&mut metadata, &mut http::Extensions::new()).is_none());
+    flagged.on_response(tonic::Code::Ok, &mut http::HeaderMap::new(), &http::Extensions::new());
+    assert_eq!(flagged.inner().responses.load(Ordering::SeqCst), 1);
+    assert_eq!(flagged.flag(), "maintenance");
+}
+
+#[test]
+fn should_read_static_flags() {
+    let flags: StaticFlags = vec![("maintenance", true), ("chaos", false)].into_iter().collect();
+    let extensions = http::Extensions::new();
+    let ctx = FlagContext::new("", &extensions);
+    assert!(flags.is_enabled("maintenance", &ctx));
+    assert!(!flags.is_enabled("chaos", &ctx));
+    assert!(!flags.is_enabled("unknown", &ctx));
+    assert_eq!(flags, StaticFlags::new().set("maintenance", true).set("chaos", false));
+    assert_eq!(ctx.tenant(), None);
+    assert_eq!(ctx.identity(), None);
+}
+
+#[test]
+fn should_update_dynamic_flags() {
+    let flags = DynamicFlags::with(StaticFlags::new().set("chaos", true));
+    let mut service = InterceptorService::new(Flagged::new("maintenance", flags.clone(), Arc::new(maintenance())), service_fn(|_: http::Request<()>| Ok::<_, Status>(http::Response::new(()))));
+    assert_eq!(call(&mut service), "passed");
+
+    let snapshot = flags.load();
+    flags.set("maintenance", true);
+    assert_eq!(call(&mut service), "rejected");
+    //Snapshot is not affected by later updates
+    assert!(!snapshot.get("maintenance"));
+    assert!(flags.load().get("chaos"));
+
+    flags.store(StaticFlags::new());
+    assert_eq!(call(&mut service), "passed");
+    assert!(!flags.load().get("chaos"));
+}
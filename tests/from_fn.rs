@@ -0,0 +1,119 @@
+#![cfg(feature = "tonic011")]
+#![allow(clippy::result_large_err)]
+
+mod common;
+
+use common::{EchoClient, EchoServer, EchoService, user_request};
+
+use tonic_interceptor::asynchronous::{AsyncInterceptorService, RequestHead};
+use tonic_interceptor::AsyncInterceptor;
+
+use tonic::{Code, Status};
+use tower::ServiceExt;
+use tower_layer::Layer;
+
+use core::future;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Clone, Debug, PartialEq)]
+struct User(String);
+
+#[tokio::test]
+async fn should_reject_from_stateless_fn() {
+    let layer = tonic_interceptor::async_interceptor(tonic_interceptor::from_fn(|headers, _| {
+        let user = headers.get("x-user").cloned();
+        async move {
+            //Pretend to ask remote service
+            tokio::time::sleep(Duration::from_millis(1)).await;
+            match user {
+                Some(user) if user == "admin" => Ok(()),
+                _ => Err(Status::permission_denied("not admin")),
+            }
+        }
+    }));
+
+    let service = EchoService::default();
+    let (incoming, addr) = common::listen().await;
+    let server = tonic::transport::Server::builder().add_service(layer.layer(EchoServer::new(service.clone())));
+    tokio::spawn(server.serve_with_incoming(incoming));
+    let mut client = EchoClient::new(common::connect(addr).await);
+
+    let response = client.unary(user_request("admin")).await.expect("success");
+    assert_eq!(response.get_ref().message, "hello");
+
+    let status = client.unary(user_request("guest")).await.expect_err("to reject");
+    assert_eq!(status.code(), Code::PermissionDenied);
+    assert_eq!(status.message(), "not admin");
+    assert_eq!(service.calls(), 1);
+}
+
+#[tokio::test]
+async fn should_pass_state_to_fn() {
+    let users = Arc::new(HashMap::from([("token".to_owned(), "admin".to_owned())]));
+    let interceptor = tonic_interceptor::from_fn_with_state(users, |users: &Arc<HashMap<String, String>>, headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions| {
+        let user = headers.get("x-token").and_then(|token| token.to_str().ok()).and_then(|token| users.get(token)).cloned();
+        headers.remove("x-token");
+        let verdict = match user {
+            Some(user) => {
+                extensions.insert(User(user));
+                None
+            },
+            None => Some(Status::unauthenticated("unknown token")),
+        };
+        future::ready(verdict)
+    });
+
+    let svc = tower::service_fn(|request: http::Request<()>| {
+        assert!(request.headers().get("x-token").is_none());
+        let user = request.extensions().get::<User>().cloned();
+        future::ready(Ok::<_, Status>(http::Response::new(user)))
+    });
+    let service = AsyncInterceptorService::new(interceptor, svc);
+
+    let request = http::Request::builder().header("x-token", "token").body(()).unwrap();
+    let response = service.clone().oneshot(request).await.expect("success");
+    assert_eq!(response.into_body(), Some(User("admin".to_owned())));
+
+    let request = http::Request::builder().header("x-token", "guest").header("content-type", "application/grpc+proto").body(()).unwrap();
+    let response = service.oneshot(request).await.expect("success");
+    assert_eq!(response.headers().get("grpc-status").unwrap(), "16");
+    assert_eq!(response.headers().get("content-type").unwrap(), "application/grpc+proto");
+    assert_eq!(response.into_body(), None);
+}
+
+#[derive(Clone)]
+struct Lookup;
+
+impl AsyncInterceptor for Lookup {
+    type Future = core::pin::Pin<Box<dyn core::future::Future<Output = Result<RequestHead, Status>> + Send>>;
+
+    fn on_request(&self, mut request: RequestHead) -> Self::Future {
+        Box::pin(async move {
+            tokio::task::yield_now().await;
+            let user = request.uri().path().to_owned();
+            request.extensions.insert(User(user));
+            Ok(request)
+        })
+    }
+
+    fn on_response(&self, status: Code, headers: &mut http::HeaderMap, _: &http::Extensions) {
+        headers.insert("x-status", http::HeaderValue::from(status as i32));
+    }
+}
+
+#[tokio::test]
+async fn should_modify_request_after_await() {
+    let svc = tower::service_fn(|request: http::Request<()>| {
+        let user = request.extensions().get::<User>().cloned().expect("user");
+        let response = http::Response::builder().header("grpc-status", "0").body(Some(user)).unwrap();
+        future::ready(Ok::<_, Status>(response))
+    });
+    let service = tonic_interceptor::async_interceptor(Lookup).layer(svc);
+
+    let request = http::Request::builder().uri("/test.Echo/Unary").body(()).unwrap();
+    let response = service.oneshot(request).await.expect("success");
+    assert_eq!(response.headers().get("x-status").unwrap(), "0");
+    assert_eq!(response.into_body(), Some(User("/test.Echo/Unary".to_owned())));
+}
@@ -0,0 +1,297 @@
+#![cfg(feature = "tonic011")]
+#![allow(clippy::result_large_err)]
+
+use tonic_interceptor::{Interceptor, InterceptorService};
+use tonic_interceptor::headers::{EchoRequestHeader, Echoed, Rule, Transform};
+use tonic_interceptor::testing::{poll_once, service_fn};
+
+use tonic::Status;
+use tower_service::Service;
+
+use std::sync::{Arc, Mutex};
+
+//Calls service through `transform`, returning request headers seen by service and response headers
+fn call(transform: Transform, request: &[(&'static str, &'static str)], response: &[(&'static str, &'static str)]) -> (http::HeaderMap, http::HeaderMap) {
+    let seen = Arc::new(Mutex::new(http::HeaderMap::new()));
+    let inner = {
+        let seen = seen.clone();
+        let response = response.to_vec();
+        service_fn(move |req: http::Request<()>| {
+            *seen.lock().unwrap() = req.headers().clone();
+            let mut resp = http::Response::new(());
+            for (key, value) in response.iter() {
+                resp.headers_mut().append(*key, http::HeaderValue::from_static(value));
+            }
+            Ok::<_, Status>(resp)
+        })
+    };
+    let mut service = InterceptorService::new(transform, inner);
+    let mut req = http::Request::new(());
+    for (key, value) in request {
+        req.headers_mut().append(*key, http::HeaderValue::from_static(value));
+    }
+    let resp = poll_once(service.call(req)).expect("response");
+    let seen = seen.lock().unwrap().clone();
+    (seen, resp.headers().clone())
+}
+
+fn values(headers: &http::HeaderMap, key: &str) -> Vec<String> {
+    headers.get_all(key).iter().map(|value| value.to_str().unwrap().to_owned()).collect()
+}
+
+#[test]
+fn should_normalize_request_key() {
+    let transform = || Transform::builder().request(Rule::copy_if_absent("x-old-tenant", "x-tenant").unwrap())
+                                           .request(Rule::remove("x-old-tenant").unwrap())
+                                           .build();
+
+    let (seen, _) = call(transform(), &[("x-old-tenant", "old")], &[]);
+    assert_eq!(values(&seen, "x-tenant"), ["old"]);
+    assert!(!seen.contains_key("x-old-tenant"));
+
+    let (seen, _) = call(transform(), &[("x-old-tenant", "old"), ("x-tenant", "new")], &[]);
+    assert_eq!(values(&seen, "x-tenant"), ["new"]);
+    assert!(!seen.contains_key("x-old-tenant"));
+
+    let (seen, _) = call(transform(), &[("x-tenant", "a"), ("x-tenant", "b")], &[]);
+    assert_eq!(values(&seen, "x-tenant"), ["a", "b"]);
+}
+
+#[test]
+fn should_apply_rules_in_order() {
+    //Removing old key first leaves nothing to copy
+    let transform = Transform::builder().request(Rule::remove("x-old-tenant").unwrap())
+                                        .request(Rule::copy_if_absent("x-old-tenant", "x-tenant").unwrap())
+                                        .build();
+    let (seen, _) = call(transform, &[("x-old-tenant", "old")], &[]);
+    assert!(!seen.contains_key("x-tenant"));
+
+    //Default applies only when neither key is present
+    let transform = || Transform::builder().request(Rule::rename("x-old-tenant", "x-tenant").unwrap())
+                                           .request(Rule::set_if_absent("x-tenant", "default").unwrap())
+                                           .build();
+    let (seen, _) = call(transform(), &[("x-old-tenant", "o1"), ("x-old-tenant", "o2"), ("x-tenant", "new")], &[]);
+    assert_eq!(values(&seen, "x-tenant"), ["o1", "o2"]);
+    assert!(!seen.contains_key("x-old-tenant"));
+    let (seen, _) = call(transform(), &[], &[]);
+    assert_eq!(values(&seen, "x-tenant"), ["default"]);
+
+    //Default set first is then overwritten by rename
+    let transform = Transform::builder().request(Rule::set_if_absent("x-tenant", "default").unwrap())
+                                        .request(Rule::rename("x-old-tenant", "x-tenant").unwrap())
+                                        .build();
+    let (seen, _) = call(transform, &[("x-old-tenant", "old")], &[]);
+    assert_eq!(values(&seen, "x-tenant"), ["old"]);
+}
+
+#[test]
+fn should_transform_response_separately() {
+    let transform = Transform::builder().request(Rule::remove("x-tenant").unwrap())
+                                        .response(Rule::copy_if_absent("x-tenant", "x-old-tenant").unwrap())
+                                        .response(Rule::set_if_absent("x-api-version", "2").unwrap())
+                                        .build();
+    let (seen, response) = call(transform, &[("x-tenant", "req")], &[("x-tenant", "resp")]);
+    assert!(!seen.contains_key("x-tenant"));
+    assert_eq!(values(&response, "x-tenant"), ["resp"]);
+    assert_eq!(values(&response, "x-old-tenant"), ["resp"]);
+    assert_eq!(values(&response, "x-api-version"), ["2"]);
+}
+
+#[test]
+fn should_handle_bin_keys() {
+    let transform = Transform::builder().request(Rule::rename("x-old-id-bin", "x-id-bin").unwrap())
+                                        .request(Rule::set_bin_if_absent("x-trace-bin", &[1, 2, 3]).unwrap())
+                                        .build();
+    let (seen, _) = call(transform.clone(), &[("x-old-id-bin", "AQI")], &[]);
+    let seen = tonic::metadata::MetadataMap::from_headers(seen);
+    assert_eq!(seen.get_bin("x-id-bin").unwrap().to_bytes().unwrap().as_ref(), [1, 2]);
+    assert_eq!(seen.get_bin("x-trace-bin").unwrap().to_bytes().unwrap().as_ref(), [1, 2, 3]);
+
+    //Same result through metadata conversion
+    let mut metadata = tonic::metadata::MetadataMap::new();
+    metadata.insert_bin("x-old-id-bin", tonic::metadata::BinaryMetadataValue::from_bytes(&[1, 2]));
+    assert!(transform.on_request(&mut metadata, &mut http::Extensions::new()).is_none());
+    assert!(metadata.get_bin("x-old-id-bin").is_none());
+    assert_eq!(metadata.get_bin("x-id-bin").unwrap().to_bytes().unwrap().as_ref(), [1, 2]);
+    assert_eq!(metadata.get_bin("x-trace-bin").unwrap().to_bytes().unwrap().as_ref(), [1, 2, 3]);
+
+    assert_eq!(Rule::copy_if_absent("x-id-bin", "x-id").unwrap_err().to_string(), "cannot move metadata 'x-id-bin' to 'x-id' of different type");
+    assert_eq!(Rule::rename("x-id", "x-id-bin").unwrap_err().to_string(), "cannot move metadata 'x-id' to 'x-id-bin' of different type");
+    assert_eq!(Rule::set_if_absent("x-id-bin", "AQI").unwrap_err().to_string(), "metadata 'x-id-bin' requires binary value");
+    assert_eq!(Rule::set_bin_if_absent("x-id", &[1]).unwrap_err().to_string(), "metadata 'x-id' requires ASCII value");
+    assert_eq!(Rule::remove("X-Id").unwrap_err().to_string(), "invalid metadata key 'X-Id'");
+    assert_eq!(Rule::set_if_absent("x-id", "new\nline").unwrap_err().to_string(), "metadata 'x-id' has invalid value");
+}
+
+const ECHO: EchoRequestHeader = EchoRequestHeader::new(&["x-request-id", "x-trace-bin"]);
+
+fn echo_call<I: Interceptor + Clone>(interceptor: I, request: &[(&'static str, &'static str)], response: Result<&[(&'static str, &'static str)], &'static str>) -> Result<http::HeaderMap, &'static str> {
+    let response: Result<Vec<_>, _> = response.map(|response| response.to_vec());
+    let inner = service_fn(move |_: http::Request<()>| {
+        let response = response.clone()?;
+        let mut resp = http::Response::new(());
+        for (key, value) in response.iter() {
+            resp.headers_mut().append(*key, http::HeaderValue::from_static(value));
+        }
+        Ok::<_, &'static str>(resp)
+    });
+    let mut service = InterceptorService::new(interceptor, inner).with_error_handler(|error: &&'static str| match *error {
+        "mapped" => Some(Status::unavailable("mapped")),
+        _ => None,
+    });
+    let mut req = http::Request::new(());
+    for (key, value) in request {
+        req.headers_mut().append(*key, http::HeaderValue::from_static(value));
+    }
+    poll_once(service.call(req)).map(|resp| resp.headers().clone())
+}
+
+#[test]
+fn should_echo_request_header_on_success() {
+    let headers = echo_call(ECHO, &[("x-request-id", "id-1"), ("x-trace-bin", "AQI"), ("x-other", "o")], Ok(&[])).expect("response");
+    assert_eq!(values(&headers, "x-request-id"), ["id-1"]);
+    assert_eq!(values(&headers, "x-trace-bin"), ["AQI"]);
+    assert!(!headers.contains_key("x-other"));
+
+    //Value set by service is kept
+    let headers = echo_call(ECHO, &[("x-request-id", "id-1"), ("x-request-id", "id-2")], Ok(&[("x-request-id", "service")])).expect("response");
+    assert_eq!(values(&headers, "x-request-id"), ["service"]);
+    let headers = echo_call(ECHO, &[("x-request-id", "id-1"), ("x-request-id", "id-2")], Ok(&[])).expect("response");
+    assert_eq!(values(&headers, "x-request-id"), ["id-1", "id-2"]);
+}
+
+#[test]
+fn should_echo_request_header_on_rejection() {
+    let reject = tonic_interceptor::InterceptorBuilder::new().on_request(|headers, _| match headers.contains_key("x-deny") {
+        true => Some(Status::permission_denied("denied")),
+        false => None,
+    }).build();
+    let chain = tonic_interceptor::InterceptorChain::new().with(ECHO).with(reject);
+
+    let headers = echo_call(chain.clone(), &[("x-request-id", "id-1"), ("x-deny", "1")], Ok(&[])).expect("response");
+    assert_eq!(headers.get("grpc-status").unwrap(), "7");
+    assert_eq!(values(&headers, "x-request-id"), ["id-1"]);
+
+    //Echoed by outer service, when rejected by inner one
+    let inner = InterceptorService::new(reject, service_fn(|_: http::Request<()>| Ok::<_, &'static str>(http::Response::new(()))));
+    let mut service = InterceptorService::new(ECHO, inner);
+    let mut req = http::Request::new(());
+    req.headers_mut().insert("x-request-id", http::HeaderValue::from_static("id-2"));
+    req.headers_mut().insert("x-deny", http::HeaderValue::from_static("1"));
+    let headers = poll_once(service.call(req)).expect("response").headers().clone();
+    assert_eq!(headers.get("grpc-status").unwrap(), "7");
+    assert_eq!(values(&headers, "x-request-id"), ["id-2"]);
+}
+
+#[test]
+fn should_echo_request_header_on_mapped_error() {
+    let headers = echo_call(ECHO, &[("x-request-id", "id-1")], Err("mapped")).expect("response");
+    assert_eq!(headers.get("grpc-status").unwrap(), "14");
+    assert_eq!(values(&headers, "x-request-id"), ["id-1"]);
+
+    assert_eq!(echo_call(ECHO, &[("x-request-id", "id-1")], Err("propagated")).unwrap_err(), "propagated");
+}
+
+#[test]
+fn should_not_echo_missing_request_header() {
+    let headers = echo_call(ECHO, &[("x-other", "o")], Ok(&[])).expect("response");
+    assert!(!headers.contains_key("x-request-id"));
+    assert!(!headers.contains_key("x-trace-bin"));
+
+    let mut metadata = tonic::metadata::MetadataMap::new();
+    let mut extensions = http::Extensions::new();
+    assert!(ECHO.on_request(&mut metadata, &mut extensions).is_none());
+    assert!(extensions.get::<Echoed>().is_none());
+
+    metadata.insert("x-request-id", "id-1".parse().unwrap());
+    metadata.insert_bin("x-trace-bin", tonic::metadata::BinaryMetadataValue::from_bytes(&[1, 2]));
+    assert!(ECHO.on_request(&mut metadata, &mut extensions).is_none());
+    let echoed = extensions.get::<Echoed>().expect("echoed");
+    assert_eq!(values(echoed.headers(), "x-request-id"), ["id-1"]);
+    assert_eq!(values(echoed.headers(), "x-trace-bin"), ["AQI"]);
+}
+
+fn normalize(normalize: &tonic_interceptor::headers::Normalize, request: &[(&'static str, &'static str)]) -> Result<http::HeaderMap, (tonic::Code, String)> {
+    let mut headers = http::HeaderMap::new();
+    for (key, value) in request {
+        headers.append(*key, http::HeaderValue::from_static(value));
+    }
+    let mut metadata = tonic::metadata::MetadataMap::from_headers(headers.clone());
+    let mut extensions = http::Extensions::new();
+
+    let result = normalize.on_request_headers(&http::Uri::from_static("/pkg.Users/Get"), &mut headers, &mut extensions);
+    //Both paths must agree
+    let metadata_result = normalize.on_request(&mut metadata, &mut extensions);
+    assert_eq!(result.as_ref().map(|status| status.message()), metadata_result.as_ref().map(|status| status.message()));
+    match result {
+        Some(status) => Err((status.code(), status.message().to_owned())),
+        None => {
+            assert_eq!(metadata.into_headers(), headers);
+            Ok(headers)
+        },
+    }
+}
+
+#[test]
+fn should_resolve_duplicates_by_policy() {
+    use tonic_interceptor::headers::{DuplicatePolicy, Normalize};
+
+    let policy = Normalize::new().key("x-first", DuplicatePolicy::First).unwrap()
+                                 .key("x-last", DuplicatePolicy::Last).unwrap()
+                                 .key("x-join", DuplicatePolicy::Join(", ")).unwrap()
+                                 .key("x-tenant", DuplicatePolicy::Reject).unwrap();
+    assert_eq!(policy.policy("x-last"), Some(&DuplicatePolicy::Last));
+    assert_eq!(policy.policy("x-other"), None);
+
+    let headers = normalize(&policy, &[("x-first", "a"), ("x-first", "b"), ("x-last", "a"), ("x-last", "b"), ("x-join", "a"), ("x-join", "b"), ("x-join", "c"), ("x-tenant", "t"), ("x-tenant", "t"), ("x-other", "a"), ("x-other", "b")]).unwrap();
+    assert_eq!(values(&headers, "x-first"), ["a"]);
+    assert_eq!(values(&headers, "x-last"), ["b"]);
+    assert_eq!(values(&headers, "x-join"), ["a, b, c"]);
+    assert_eq!(values(&headers, "x-tenant"), ["t"]);
+    //Keys without policy are left alone
+    assert_eq!(values(&headers, "x-other"), ["a", "b"]);
+
+    assert_eq!(normalize(&policy, &[("x-tenant", "a"), ("x-tenant", "b")]), Err((tonic::Code::InvalidArgument, "metadata 'x-tenant' has conflicting values".to_owned())));
+    assert_eq!(values(&normalize(&policy, &[("x-tenant", "a")]).unwrap(), "x-tenant"), ["a"]);
+
+    //Default policy applies to every other key
+    let policy = policy.key("x-first", DuplicatePolicy::Last).unwrap().default_policy(DuplicatePolicy::First);
+    let headers = normalize(&policy, &[("x-first", "a"), ("x-first", "b"), ("x-other", "a"), ("x-other", "b")]).unwrap();
+    assert_eq!(values(&headers, "x-first"), ["b"]);
+    assert_eq!(values(&headers, "x-other"), ["a"]);
+}
+
+#[test]
+fn should_normalize_bin_keys() {
+    use tonic_interceptor::headers::{DuplicatePolicy, Normalize};
+
+    let policy = Normalize::new().key("x-token-bin", DuplicatePolicy::Reject).unwrap()
+                                 .key("x-trace-bin", DuplicatePolicy::Join(",")).unwrap()
+                                 .key("x-last-bin", DuplicatePolicy::Last).unwrap();
+
+    //Padding is not significant
+    let headers = normalize(&policy, &[("x-token-bin", "AQI="), ("x-token-bin", "AQI"), ("x-trace-bin", "AQ"), ("x-trace-bin", "Ag=="), ("x-last-bin", "AQ"), ("x-last-bin", "Ag")]).unwrap();
+    assert_eq!(values(&headers, "x-token-bin"), ["AQI="]);
+    assert_eq!(values(&headers, "x-trace-bin"), ["AQ,Ag=="]);
+    assert_eq!(values(&headers, "x-last-bin"), ["Ag"]);
+
+    assert_eq!(normalize(&policy, &[("x-token-bin", "AQI="), ("x-token-bin", "AQM=")]).unwrap_err().0, tonic::Code::InvalidArgument);
+    assert!(Normalize::new().key("X-Tenant", DuplicatePolicy::First).is_err());
+}
+
+#[test]
+fn should_treat_blank_values_as_absent() {
+    use tonic_interceptor::headers::{DuplicatePolicy, Normalize};
+
+    let policy = Normalize::new().key("x-tenant", DuplicatePolicy::Reject).unwrap();
+    assert_eq!(normalize(&policy, &[("x-tenant", "a"), ("x-tenant", " ")]).unwrap_err().0, tonic::Code::InvalidArgument);
+    assert_eq!(values(&normalize(&policy, &[("x-tenant", "")]).unwrap(), "x-tenant"), [""]);
+
+    let policy = policy.blank_as_absent(true);
+    assert_eq!(values(&normalize(&policy, &[("x-tenant", "a"), ("x-tenant", " ")]).unwrap(), "x-tenant"), ["a"]);
+    assert_eq!(values(&normalize(&policy, &[("x-tenant", " "), ("x-tenant", "a")]).unwrap(), "x-tenant"), ["a"]);
+    assert!(!normalize(&policy, &[("x-tenant", "")]).unwrap().contains_key("x-tenant"));
+    assert!(!normalize(&policy, &[("x-tenant", " \t"), ("x-tenant", "")]).unwrap().contains_key("x-tenant"));
+    //Only normalized keys are affected
+    assert_eq!(values(&normalize(&policy, &[("x-other", " ")]).unwrap(), "x-other"), [" "]);
+}
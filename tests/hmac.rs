@@ -0,0 +1,122 @@
+#![cfg(all(feature = "tonic011", feature = "hmac"))]
+
+mod common;
+
+use common::EchoClient;
+use common::echo::EchoRequest;
+
+use tonic_interceptor::Interceptor;
+use tonic_interceptor::auth::{HmacSignature, KEY_ID, SIGNATURE_TIMESTAMP, SIGNATURE};
+use tonic_interceptor::client::{self, ClientInterceptor, HmacSign};
+
+use tonic::{Code, Request};
+
+const NOW: u64 = 1_700_000_000;
+const SECRET: &[u8] = b"secret";
+
+fn request(message: &str) -> EchoRequest {
+    EchoRequest {
+        message: message.to_owned(),
+    }
+}
+
+fn signer(now: u64) -> HmacSign<impl Fn() -> u64 + Clone> {
+    HmacSign::new("key-1".parse().unwrap(), SECRET).header("x-tenant").clock(move || now)
+}
+
+fn validator() -> HmacSignature<impl Fn() -> u64 + Clone> {
+    HmacSignature::new().key("key-1", SECRET).header("x-tenant").clock(|| NOW)
+}
+
+type Server = tonic_interceptor::InterceptorService<HmacSignature<fn() -> u64>, common::EchoServer>;
+type Client = EchoClient<client::ClientInterceptorService<HmacSign<fn() -> u64>, Server>>;
+
+//Client and server interceptors wired in-process around generated server
+fn signed_echo(signer: HmacSign<fn() -> u64>) -> (common::EchoService, Client) {
+    let service = common::EchoService::default();
+    let validator = HmacSignature::new().key("key-1", SECRET).header("x-tenant").clock((|| NOW) as fn() -> u64);
+    let server = tonic_interceptor::InterceptorService::new(validator, common::EchoServer::new(service.clone()));
+    (service, EchoClient::new(client::ClientInterceptorService::new(signer, server)))
+}
+
+fn signed(path: &str, tenant: &str) -> tonic::metadata::MetadataMap {
+    let mut metadata = tonic::metadata::MetadataMap::new();
+    metadata.insert("x-tenant", tenant.parse().unwrap());
+    let uri = http::Uri::from_maybe_shared(format!("http://localhost{}", path)).unwrap();
+    assert!(signer(NOW).on_request_with_uri(&uri, &mut metadata, &mut http::Extensions::new()).is_none());
+    metadata
+}
+
+fn validate(path: &str, mut metadata: tonic::metadata::MetadataMap) -> Option<tonic::Status> {
+    let uri = path.parse::<http::Uri>().unwrap();
+    validator().on_request_with_uri(&uri, &mut metadata, &mut http::Extensions::new())
+}
+
+#[tokio::test]
+async fn should_accept_signed_call() {
+    let (service, mut client) = signed_echo(HmacSign::new("key-1".parse().unwrap(), SECRET).header("x-tenant").clock((|| NOW - 10) as fn() -> u64));
+
+    let mut request = Request::new(request("hello"));
+    request.metadata_mut().insert("x-tenant", "acme".parse().unwrap());
+    let response = client.unary(request).await.expect("success");
+    assert_eq!(response.get_ref().message, "hello");
+
+    let (_, metadata) = service.requests().pop().expect("to have request");
+    assert_eq!(metadata.get(KEY_ID).expect(KEY_ID), "key-1");
+    assert_eq!(metadata.get(SIGNATURE_TIMESTAMP).expect(SIGNATURE_TIMESTAMP), (NOW - 10).to_string().as_str());
+    assert_eq!(metadata.get(SIGNATURE).expect(SIGNATURE).len(), 64);
+}
+
+#[tokio::test]
+async fn should_reject_stale_signature() {
+    let (service, mut client) = signed_echo(HmacSign::new("key-1".parse().unwrap(), SECRET).header("x-tenant").clock((|| NOW - 10 * 60) as fn() -> u64));
+
+    let status = client.unary(request("hello")).await.expect_err("to fail");
+    assert_eq!(status.code(), Code::Unauthenticated);
+    assert_eq!(status.message(), "Signature timestamp is out of range");
+    assert_eq!(service.calls(), 0);
+}
+
+#[test]
+fn should_reject_tampered_request() {
+    assert!(validate("/test.Echo/Unary", signed("/test.Echo/Unary", "acme")).is_none());
+
+    let mut metadata = signed("/test.Echo/Unary", "acme");
+    metadata.insert("x-tenant", "evil".parse().unwrap());
+    let status = validate("/test.Echo/Unary", metadata).expect("to reject");
+    assert_eq!(status.code(), Code::Unauthenticated);
+    assert_eq!(status.message(), "Invalid signature");
+
+    let status = validate("/test.Echo/Stream", signed("/test.Echo/Unary", "acme")).expect("to reject");
+    assert_eq!(status.message(), "Invalid signature");
+
+    let mut metadata = signed("/test.Echo/Unary", "acme");
+    metadata.insert(SIGNATURE, "not hex".parse().unwrap());
+    let status = validate("/test.Echo/Unary", metadata).expect("to reject");
+    assert_eq!(status.message(), "Invalid signature");
+}
+
+#[test]
+fn should_reject_unknown_key_and_missing_signature() {
+    let mut metadata = signed("/test.Echo/Unary", "acme");
+    metadata.insert(KEY_ID, "key-2".parse().unwrap());
+    let status = validate("/test.Echo/Unary", metadata).expect("to reject");
+    assert_eq!(status.code(), Code::Unauthenticated);
+    assert_eq!(status.message(), "Unknown key id");
+
+    let mut metadata = signed("/test.Echo/Unary", "acme");
+    metadata.remove(SIGNATURE);
+    let status = validate("/test.Echo/Unary", metadata).expect("to reject");
+    assert_eq!(status.message(), "Missing request signature");
+}
+
+#[test]
+fn should_require_uri() {
+    let mut metadata = tonic::metadata::MetadataMap::new();
+    //Signer passes request through unsigned, while validator rejects it
+    assert!(ClientInterceptor::on_request(&signer(NOW), &mut metadata, &mut http::Extensions::new()).is_none());
+    assert!(metadata.is_empty());
+
+    let status = Interceptor::on_request(&validator(), &mut metadata, &mut http::Extensions::new()).expect("to reject");
+    assert_eq!(status.code(), Code::Internal);
+}
@@ -0,0 +1,130 @@
+#![cfg(feature = "tonic011")]
+#![allow(clippy::result_large_err)]
+
+use tonic_interceptor::Interceptor;
+use tonic_interceptor::authz::{Rbac, Roles};
+use tonic_interceptor::identity::{Mechanism, PeerIdentity};
+
+use tonic::Code;
+
+const PATH: &str = "/pkg.Echo/Unary";
+
+#[cfg(any(feature = "serde", feature = "hmac"))]
+fn call(interceptor: &dyn Interceptor, metadata: &[(&'static str, &str)]) -> Result<http::Extensions, tonic::Status> {
+    let mut headers = http::HeaderMap::new();
+    for (key, value) in metadata {
+        headers.append(*key, value.parse().unwrap());
+    }
+    let mut extensions = http::Extensions::new();
+    match interceptor.on_request_headers(&http::Uri::from_static(PATH), &mut headers, &mut extensions) {
+        None => Ok(extensions),
+        Some(status) => Err(status),
+    }
+}
+
+#[test]
+fn should_build_identity() {
+    let identity = PeerIdentity::new(Mechanism::Jwt).subject("alice").issuer("https://idp").role("admin").scope("read").with_extra(42u32);
+    assert_eq!(identity.subject.as_deref(), Some("alice"));
+    assert_eq!(identity.issuer.as_deref(), Some("https://idp"));
+    assert!(identity.has_role("admin"));
+    assert!(!identity.has_role("reader"));
+    assert!(identity.has_scope("read"));
+    assert_eq!(identity.extra.get::<u32>(), Some(&42));
+    assert_eq!(format!("{:?}", identity), "PeerIdentity { subject: Some(\"alice\"), issuer: Some(\"https://idp\"), mechanism: Jwt, roles: [\"admin\"], scopes: [\"read\"], .. }");
+
+    let mechanisms = [Mechanism::Bearer, Mechanism::Jwt, Mechanism::Mtls, Mechanism::ApiKey, Mechanism::Signature, Mechanism::Uds, Mechanism::Other("kerberos")];
+    let names: Vec<_> = mechanisms.iter().map(ToString::to_string).collect();
+    assert_eq!(names, ["bearer", "jwt", "mtls", "api-key", "signature", "uds", "kerberos"]);
+
+    let mut extensions = http::Extensions::new();
+    assert!(PeerIdentity::from_extensions(&extensions).is_none());
+    extensions.insert(identity);
+    assert_eq!(PeerIdentity::from_extensions(&extensions).unwrap().mechanism, Mechanism::Jwt);
+}
+
+#[test]
+fn should_authorize_by_identity_roles() {
+    let rbac = Rbac::builder().allow("admin", "*").build();
+    let check = |extensions: &mut http::Extensions| rbac.on_request_headers(&http::Uri::from_static(PATH), &mut http::HeaderMap::new(), extensions).map(|status| status.code());
+
+    let mut extensions = http::Extensions::new();
+    extensions.insert(PeerIdentity::new(Mechanism::Mtls).subject("node-1").role("admin"));
+    assert_eq!(check(&mut extensions), None);
+
+    //Legacy roles are used only without identity
+    extensions.insert(Roles(vec!["reader".to_owned()]));
+    assert_eq!(check(&mut extensions), None);
+    let mut extensions = http::Extensions::new();
+    extensions.insert(Roles(vec!["admin".to_owned()]));
+    assert_eq!(check(&mut extensions), None);
+    extensions.insert(PeerIdentity::new(Mechanism::Bearer));
+    assert_eq!(check(&mut extensions), Some(Code::PermissionDenied));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn should_identify_bearer_auth() {
+    use tonic_interceptor::auth::TokenSource;
+    use tonic_interceptor::config::BearerAuth;
+
+    let extensions = call(&BearerAuth::new("secret"), &[("authorization", "Bearer secret")]).expect("accepted");
+    let identity = PeerIdentity::from_extensions(&extensions).unwrap();
+    assert_eq!(identity.mechanism, Mechanism::Bearer);
+    assert_eq!(identity.subject, None);
+    assert!(identity.roles.is_empty());
+    assert_eq!(identity.extra.get::<TokenSource>(), Some(&TokenSource::Bearer));
+    assert_eq!(extensions.get::<TokenSource>(), Some(&TokenSource::Bearer));
+
+    let error = call(&BearerAuth::new("secret"), &[("authorization", "Bearer wrong")]).unwrap_err();
+    assert_eq!(error.code(), Code::Unauthenticated);
+}
+
+#[cfg(feature = "hmac")]
+#[test]
+fn should_identify_hmac_signature() {
+    use tonic_interceptor::auth::{canonical_string, sign, HmacSignature, KEY_ID, SIGNATURE, SIGNATURE_TIMESTAMP};
+
+    const NOW: u64 = 1_700_000_000;
+    let validator = HmacSignature::new().key("key-1", "secret").clock(|| NOW);
+    let timestamp = NOW.to_string();
+    let signature = sign(b"secret", &canonical_string::<&str>(PATH, &timestamp, &[], &tonic::metadata::MetadataMap::new()));
+
+    let extensions = call(&validator, &[(KEY_ID, "key-1"), (SIGNATURE_TIMESTAMP, &timestamp), (SIGNATURE, &signature)]).expect("accepted");
+    let identity = PeerIdentity::from_extensions(&extensions).unwrap();
+    assert_eq!(identity.mechanism, Mechanism::Signature);
+    assert_eq!(identity.subject.as_deref(), Some("key-1"));
+
+    let error = call(&validator, &[(KEY_ID, "key-1"), (SIGNATURE_TIMESTAMP, &timestamp), (SIGNATURE, "00")]).unwrap_err();
+    assert_eq!(error.code(), Code::Unauthenticated);
+}
+
+#[cfg(feature = "oauth2")]
+#[tokio::test]
+async fn should_identify_introspected_token() {
+    use tonic_interceptor::asynchronous::{AsyncInterceptor, RequestHead};
+    use tonic_interceptor::auth::{HttpClient, Introspection, OAuthIdentity, TokenSource};
+    use tonic_interceptor::client::BoxError;
+
+    struct Active;
+
+    impl HttpClient for Active {
+        async fn send(&self, _: http::Request<bytes::Bytes>) -> Result<http::Response<bytes::Bytes>, BoxError> {
+            Ok(http::Response::new(bytes::Bytes::from_static(br#"{"active":true,"sub":"alice","scope":"read write"}"#)))
+        }
+    }
+
+    let interceptor = Introspection::builder(Active, http::Uri::from_static("http://idp/introspect"), "id", "secret").build();
+    let mut metadata = tonic::metadata::MetadataMap::new();
+    metadata.insert("authorization", "Bearer abc".parse().unwrap());
+    let head = interceptor.on_request(RequestHead::new(http::Uri::from_static(PATH), metadata, http::Extensions::new())).await.expect("accepted");
+
+    let identity = PeerIdentity::from_extensions(&head.extensions).unwrap();
+    assert_eq!(identity.mechanism, Mechanism::Bearer);
+    assert_eq!(identity.subject.as_deref(), Some("alice"));
+    assert_eq!(identity.scopes, ["read", "write"]);
+    assert_eq!(identity.extra.get::<TokenSource>(), Some(&TokenSource::Bearer));
+    //Legacy type is still inserted
+    assert_eq!(identity.extra.get::<OAuthIdentity>(), head.extensions.get::<OAuthIdentity>());
+    assert!(head.extensions.get::<OAuthIdentity>().is_some());
+}
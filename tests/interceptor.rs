@@ -1,55 +1,24 @@
-use tonic_interceptor::{InterceptorFn, InterceptorService};
+#![allow(clippy::result_large_err)]
 
-use tonic::Status;
-use tonic::metadata::{MetadataValue, MetadataMap};
+use tonic_interceptor::{InterceptorFn, InterceptorService};
+//Re-exported by crate, so that test runs against version of tonic selected by feature
+use tonic_interceptor::{http, tonic};
+use tonic_interceptor::tonic::Status;
+use tonic_interceptor::tonic::metadata::{MetadataValue, MetadataMap};
+use tonic_interceptor::testing::{ServiceFn, poll_once, with_noop_context};
 use tower_service::Service;
 
 use core::task;
 use core::pin::pin;
-use core::future::{self, Future};
-
-mod noop {
-    use core::{ptr, task};
-
-    const VTABLE: task::RawWakerVTable = task::RawWakerVTable::new(clone, action, action, action);
-    const WAKER: task::RawWaker = task::RawWaker::new(ptr::null(), &VTABLE);
-
-    fn clone(_: *const()) -> task::RawWaker {
-        WAKER
-    }
-
-    fn action(_: *const ()) {
-    }
-
-    #[inline(always)]
-    pub fn waker() -> task::Waker {
-        unsafe {
-            task::Waker::from_raw(WAKER)
-        }
-    }
-}
-
-#[derive(Copy, Clone)]
-pub struct ServiceFn<T>(T);
-
-impl<Request, R, E, T: FnMut(Request) -> Result<R, E>> Service<Request> for ServiceFn<T> {
-    type Response = R;
-    type Error = E;
-    type Future = future::Ready<Result<R, E>>;
-
-    fn poll_ready(&mut self, _: &mut task::Context<'_>) -> task::Poll<Result<(), E>> {
-        Ok(()).into()
-    }
-
-    fn call(&mut self, req: Request) -> Self::Future {
-        future::ready((self.0)(req))
-    }
-}
+use core::future::Future;
 
 #[test]
 fn should_propagate_status_on_request() {
     const MSG: &str = "BAD";
+    #[cfg(feature = "tonic011")]
     let expected = Status::permission_denied(MSG).to_http();
+    #[cfg(feature = "tonic012")]
+    let expected = Status::permission_denied(MSG).into_http();
 
     let svc = ServiceFn(|_: http::Request<()>| {
         Ok::<_, Status>(http::Response::new(()))
@@ -65,15 +34,7 @@ fn should_propagate_status_on_request() {
 
     let mut service = InterceptorService::new(interceptor, svc);
     let request = http::Request::builder().body(()).unwrap();
-    let res = pin!(service.call(request));
-
-    let waker = noop::waker();
-    let mut ctx = task::Context::from_waker(&waker);
-
-    let response = match Future::poll(res, &mut ctx) {
-        task::Poll::Ready(result) => result.expect("Response"),
-        task::Poll::Pending => unreachable!(),
-    };
+    let response = poll_once(service.call(request)).expect("Response");
 
     assert_eq!(expected.status(), response.status());
     assert_eq!(expected.version(), response.version());
@@ -82,6 +43,7 @@ fn should_propagate_status_on_request() {
 
 #[test]
 fn should_modify_request_parts() {
+    #[derive(Clone)]
     struct Dummy(&'static str);
 
     const MSG: &str = "BAD";
@@ -119,17 +81,349 @@ fn should_modify_request_parts() {
 
     let mut service = InterceptorService::new(interceptor, svc);
     let request = http::Request::builder().body(()).unwrap();
-    let res = pin!(service.call(request));
-
-    let waker = noop::waker();
-    let mut ctx = task::Context::from_waker(&waker);
-
-    let response = match Future::poll(res, &mut ctx) {
-        task::Poll::Ready(result) => result.expect("Response"),
-        task::Poll::Pending => unreachable!(),
-    };
+    let response = poll_once(service.call(request)).expect("Response");
 
     assert_eq!(expected.status(), response.status());
     assert_eq!(expected.version(), response.version());
     assert_eq!(expected.headers(), response.headers());
 }
+
+#[test]
+fn should_not_clone_interceptor_on_rejection() {
+    use std::sync::Arc;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    struct Counted {
+        clones: Arc<AtomicUsize>,
+        reject: bool,
+    }
+
+    impl Clone for Counted {
+        fn clone(&self) -> Self {
+            self.clones.fetch_add(1, Ordering::SeqCst);
+            Self {
+                clones: self.clones.clone(),
+                reject: self.reject,
+            }
+        }
+    }
+
+    impl tonic_interceptor::Interceptor for Counted {
+        fn on_request(&self, _: &mut MetadataMap, _: &mut http::Extensions) -> Option<Status> {
+            match self.reject {
+                true => Some(Status::permission_denied("rejected")),
+                false => None,
+            }
+        }
+
+        fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+        }
+    }
+
+    let clones = Arc::new(AtomicUsize::new(0));
+
+    for reject in [true, false] {
+        let svc = ServiceFn(|_: http::Request<()>| {
+            Ok::<_, Status>(http::Response::new(()))
+        });
+        let interceptor = Counted {
+            clones: clones.clone(),
+            reject,
+        };
+        let mut service = InterceptorService::new(interceptor, svc);
+
+        clones.store(0, Ordering::SeqCst);
+        poll_once(service.call(http::Request::new(()))).expect("Response");
+
+        let expected = match reject {
+            true => 0,
+            false => 1,
+        };
+        assert_eq!(clones.load(Ordering::SeqCst), expected);
+    }
+}
+
+#[test]
+fn should_poll_not_unpin_future() {
+    use core::marker::PhantomPinned;
+
+    //Returns `Pending` once before completing, and must not be moved in between
+    struct NotUnpin {
+        polled: bool,
+        _pin: PhantomPinned,
+    }
+
+    impl Future for NotUnpin {
+        type Output = Result<http::Response<()>, Status>;
+
+        fn poll(self: core::pin::Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+            //Fields are not structurally pinned
+            let this = unsafe {
+                self.get_unchecked_mut()
+            };
+            if this.polled {
+                let mut response = http::Response::new(());
+                response.headers_mut().insert("grpc-status", http::HeaderValue::from_static("0"));
+                task::Poll::Ready(Ok(response))
+            } else {
+                this.polled = true;
+                ctx.waker().wake_by_ref();
+                task::Poll::Pending
+            }
+        }
+    }
+
+    struct NotUnpinService;
+
+    impl Service<http::Request<()>> for NotUnpinService {
+        type Response = http::Response<()>;
+        type Error = Status;
+        type Future = NotUnpin;
+
+        fn poll_ready(&mut self, _: &mut task::Context<'_>) -> task::Poll<Result<(), Status>> {
+            Ok(()).into()
+        }
+
+        fn call(&mut self, _: http::Request<()>) -> Self::Future {
+            NotUnpin {
+                polled: false,
+                _pin: PhantomPinned,
+            }
+        }
+    }
+
+    let interceptor = InterceptorFn {
+        on_request: |_: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions| {
+            None
+        },
+        on_response: |status: tonic::Code, headers: &mut http::HeaderMap, _: &http::Extensions| {
+            assert_eq!(status, tonic::Code::Ok);
+            headers.insert("x-intercepted", http::HeaderValue::from_static("1"));
+        }
+    };
+
+    let mut service = InterceptorService::new(interceptor, NotUnpinService);
+    let mut res = pin!(service.call(http::Request::new(())));
+
+    assert!(with_noop_context(|ctx| Future::poll(res.as_mut(), ctx)).is_pending());
+    let response = poll_once(res).expect("Response");
+    assert_eq!(response.headers().get("x-intercepted").unwrap(), "1");
+}
+
+#[test]
+fn should_preserve_grpc_content_type_on_rejection() {
+    const CASES: &[(Option<&str>, &str)] = &[
+        (None, "application/grpc"),
+        (Some("application/grpc"), "application/grpc"),
+        (Some("application/grpc+proto"), "application/grpc+proto"),
+        (Some("application/grpc-web+proto"), "application/grpc-web+proto"),
+        (Some("application/grpc-web-text"), "application/grpc-web-text"),
+        (Some("application/grpc-web-text+proto"), "application/grpc-web-text+proto"),
+        (Some("application/json"), "application/grpc"),
+        (Some("application/grpcx"), "application/grpc"),
+    ];
+
+    let svc = ServiceFn(|_: http::Request<()>| {
+        Ok::<_, Status>(http::Response::new(()))
+    });
+
+    let interceptor = InterceptorFn {
+        on_request: |headers: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions| {
+            //Modification by interceptor must not affect rejection
+            headers.remove("content-type");
+            Some(Status::permission_denied("BAD"))
+        },
+        on_response: |_: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions| {
+        }
+    };
+
+    let mut service = InterceptorService::new(interceptor, svc);
+
+    for (content_type, expected) in CASES {
+        let mut request = http::Request::builder();
+        if let Some(content_type) = content_type {
+            request = request.header("content-type", *content_type);
+        }
+        let response = poll_once(service.call(request.body(()).unwrap())).expect("Response");
+
+        assert_eq!(response.headers().get("content-type").unwrap(), *expected, "content-type {:?}", content_type);
+        assert_eq!(response.headers().get("grpc-status").unwrap(), "7");
+        assert_eq!(response.headers().get("grpc-message").unwrap(), "BAD");
+    }
+}
+
+#[test]
+fn should_match_request_version_on_rejection() {
+    const CASES: &[(http::Version, &str)] = &[
+        (http::Version::HTTP_2, "application/grpc"),
+        (http::Version::HTTP_11, "application/grpc-web+proto"),
+    ];
+
+    let svc = ServiceFn(|_: http::Request<()>| {
+        Ok::<_, Status>(http::Response::new(()))
+    });
+
+    let interceptor = InterceptorFn {
+        on_request: |_: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions| {
+            Some(Status::permission_denied("BAD"))
+        },
+        on_response: |_: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions| {
+        }
+    };
+
+    let mut service = InterceptorService::new(interceptor, svc);
+
+    for (version, content_type) in CASES {
+        let request = http::Request::builder().version(*version).header("content-type", *content_type).body(()).unwrap();
+        let response = poll_once(service.call(request)).expect("Response");
+
+        assert_eq!(response.version(), *version);
+        assert_eq!(response.headers().get("content-type").unwrap(), *content_type);
+    }
+}
+
+#[test]
+fn should_percent_encode_message_on_rejection() {
+    const CASES: &[(&str, &str)] = &[
+        ("поле обязательно", "%D0%BF%D0%BE%D0%BB%D0%B5 %D0%BE%D0%B1%D1%8F%D0%B7%D0%B0%D1%82%D0%B5%D0%BB%D1%8C%D0%BD%D0%BE"),
+        ("100% done", "100%25 done"),
+        ("%D0%BF", "%25D0%25BF"),
+        ("line\r\nbreak\ttab", "line%0D%0Abreak%09tab"),
+        ("plain {\"json\": 1}?", "plain {\"json\": 1}?"),
+    ];
+
+    let svc = ServiceFn(|_: http::Request<()>| {
+        Ok::<_, Status>(http::Response::new(()))
+    });
+
+    for (message, encoded) in CASES {
+        let interceptor = InterceptorFn {
+            on_request: |_: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions| {
+                Some(Status::invalid_argument(*message))
+            },
+            on_response: |_: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions| {
+            }
+        };
+
+        let mut service = InterceptorService::new(interceptor, svc);
+        let response = poll_once(service.call(http::Request::new(()))).expect("Response");
+
+        assert_eq!(response.headers().get("grpc-status").unwrap(), "3");
+        assert_eq!(response.headers().get("grpc-message").unwrap(), *encoded);
+        let status = Status::from_header_map(response.headers()).expect("status");
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        assert_eq!(status.message(), *message);
+    }
+}
+
+#[test]
+fn should_call_on_response_for_rejections_only_when_enabled() {
+    use tower_layer::Layer;
+
+    let svc = ServiceFn(|_: http::Request<()>| {
+        Ok::<_, Status>(http::Response::new(()))
+    });
+
+    let interceptor = InterceptorFn {
+        on_request: |_: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions| {
+            Some(Status::permission_denied("BAD"))
+        },
+        on_response: |code: tonic::Code, headers: &mut http::HeaderMap, extensions: &http::Extensions| {
+            assert_eq!(code, tonic::Code::PermissionDenied);
+            assert_eq!(headers.get("grpc-message").unwrap(), "BAD");
+            assert!(extensions.is_empty());
+            headers.insert("x-response", http::HeaderValue::from_static("1"));
+        }
+    };
+
+    let services = [
+        (InterceptorService::new(interceptor.clone(), svc), false),
+        (InterceptorService::new(interceptor.clone(), svc).call_on_response_for_rejections(true), true),
+        (tonic_interceptor::interceptor(interceptor.clone()).layer(svc), false),
+        (tonic_interceptor::interceptor(interceptor.clone()).call_on_response_for_rejections(true).layer(svc), true),
+    ];
+
+    for (mut service, expected) in services {
+        let response = poll_once(service.call(http::Request::new(()))).expect("Response");
+
+        assert_eq!(response.headers().get("grpc-status").unwrap(), "7");
+        assert_eq!(response.headers().contains_key("x-response"), expected);
+    }
+
+    let layer = tonic_interceptor::interceptor_arc(interceptor).call_on_response_for_rejections(true);
+    let mut service = layer.clone().layer(svc);
+    let response = poll_once(service.call(http::Request::new(()))).expect("Response");
+    assert_eq!(response.headers().get("x-response").unwrap(), "1");
+}
+
+#[test]
+fn should_map_inner_error_with_error_handler() {
+    type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+    let svc = ServiceFn(|request: http::Request<()>| {
+        match request.headers().contains_key("x-fail") {
+            true => Err(BoxError::from("stream reset")),
+            false => Ok(http::Response::new(())),
+        }
+    });
+
+    let interceptor = InterceptorFn {
+        on_request: |_: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions| {
+            None
+        },
+        on_response: |_: tonic::Code, headers: &mut http::HeaderMap, _: &http::Extensions| {
+            headers.insert("x-response", http::HeaderValue::from_static("1"));
+        }
+    };
+
+    let mut service = InterceptorService::new(interceptor.clone(), svc).with_error_handler(|error: &BoxError| match error.to_string().as_str() {
+        "stream reset" => Some(Status::unavailable("inner failure")),
+        _ => None,
+    });
+
+    let request = http::Request::builder().version(http::Version::HTTP_2).header("x-fail", "1").header("content-type", "application/grpc+proto").body(()).unwrap();
+    let response = poll_once(service.call(request)).expect("Response");
+    assert_eq!(response.version(), http::Version::HTTP_2);
+    assert_eq!(response.headers().get("content-type").unwrap(), "application/grpc+proto");
+    assert_eq!(response.headers().get("grpc-status").unwrap(), "14");
+    assert_eq!(response.headers().get("grpc-message").unwrap(), "inner failure");
+    assert_eq!(response.headers().get("x-response").unwrap(), "1");
+
+    let response = poll_once(service.call(http::Request::new(()))).expect("Response");
+    assert!(response.headers().get("grpc-status").is_none());
+    assert_eq!(response.headers().get("x-response").unwrap(), "1");
+
+    let mut service = InterceptorService::new(interceptor, svc).with_error_handler(|_: &BoxError| None);
+    let request = http::Request::builder().header("x-fail", "1").body(()).unwrap();
+    assert_eq!(poll_once(service.call(request)).unwrap_err().to_string(), "stream reset");
+}
+
+#[test]
+fn should_clone_service_within_stack() {
+    fn assert_clone<T: Clone>(value: &T) -> T {
+        value.clone()
+    }
+
+    let svc = ServiceFn(|request: http::Request<()>| {
+        assert_eq!(request.headers().get("x-mapped").unwrap(), "1");
+        Ok::<_, Status>(http::Response::new(()))
+    });
+
+    let interceptor = InterceptorFn {
+        on_request: |_: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions| {
+            None
+        },
+        on_response: |_: tonic::Code, headers: &mut http::HeaderMap, _: &http::Extensions| {
+            headers.insert("x-response", http::HeaderValue::from_static("1"));
+        }
+    };
+
+    let service = tower::ServiceBuilder::new().layer(tonic_interceptor::interceptor(interceptor)).map_request(|mut request: http::Request<()>| {
+        request.headers_mut().insert("x-mapped", http::HeaderValue::from_static("1"));
+        request
+    }).service(svc);
+    let mut cloned = assert_clone(&service);
+
+    let response = poll_once(cloned.call(http::Request::new(()))).expect("Response");
+    assert_eq!(response.headers().get("x-response").unwrap(), "1");
+}
@@ -0,0 +1,98 @@
+#![cfg(feature = "tonic011")]
+mod common;
+
+use common::{EchoClient, EchoServer, EchoService, user_request};
+
+use tonic_interceptor::{Interceptor, InterceptorLayer};
+
+use tonic::{Code, Status};
+use tower::{ServiceBuilder, ServiceExt};
+use tower_layer::Layer;
+
+use core::future;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Limit {
+    max_len: usize,
+}
+
+impl Interceptor for Limit {
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<Status> {
+        let user = headers.get("x-user").and_then(|user| user.to_str().ok()).unwrap_or_default();
+        match user.len() > self.max_len {
+            true => Some(Status::invalid_argument("user is too long")),
+            false => None,
+        }
+    }
+
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
+
+const LAYER: InterceptorLayer<Limit> = InterceptorLayer::new(Limit { max_len: 16 });
+
+#[test]
+fn should_access_interceptor() {
+    let mut layer = LAYER;
+    assert_eq!(*layer.get_ref(), Limit { max_len: 16 });
+    layer.get_mut().max_len = 4;
+    assert_eq!(layer.into_inner(), Limit { max_len: 4 });
+
+    //Copy, hence constant is intact
+    assert_eq!(*LAYER.get_ref(), Limit { max_len: 16 });
+
+    let mut layer = tonic_interceptor::interceptor_arc(Limit { max_len: 16 });
+    layer.get_mut().expect("not shared").max_len = 4;
+    let _shared = layer.clone();
+    assert!(layer.get_mut().is_none());
+    assert_eq!(*layer.get_ref(), Limit { max_len: 4 });
+    assert_eq!(*layer.into_inner(), Limit { max_len: 4 });
+
+    let mut layer = tonic_interceptor::client::ClientInterceptorLayer::new(4u8);
+    *layer.get_mut() += 1;
+    assert_eq!(*layer.get_ref(), 5);
+    assert_eq!(layer.into_inner(), 5);
+}
+
+#[tokio::test]
+async fn should_configure_interceptor_before_serving() {
+    let mut layer = LAYER.call_on_response_for_rejections(true);
+    layer.get_mut().max_len = 5;
+
+    let service = EchoService::default();
+    let (incoming, addr) = common::listen().await;
+    let server = tonic::transport::Server::builder().add_service(layer.layer(EchoServer::new(service.clone())));
+    tokio::spawn(server.serve_with_incoming(incoming));
+    let mut client = EchoClient::new(common::connect(addr).await);
+
+    let response = client.unary(user_request("admin")).await.expect("success");
+    assert_eq!(response.get_ref().message, "hello");
+
+    let status = client.unary(user_request("administrator")).await.expect_err("to reject");
+    assert_eq!(status.code(), Code::InvalidArgument);
+    assert_eq!(service.calls(), 1);
+}
+
+#[tokio::test]
+async fn should_unwrap_layered_service() {
+    let svc = tower::service_fn(|request: http::Request<()>| {
+        let user = request.headers().get("x-user").cloned();
+        future::ready(Ok::<_, Status>(http::Response::new(user)))
+    });
+    let mut service = ServiceBuilder::new().layer(LAYER).layer(tonic_interceptor::interceptor(Limit { max_len: 8 })).service(svc);
+
+    assert_eq!(*service.interceptor_ref(), Limit { max_len: 16 });
+    service.interceptor_mut().max_len = 4;
+    assert_eq!(service.get_ref().interceptor_ref().max_len, 8);
+    service.get_mut().interceptor_mut().max_len = 2;
+
+    let request = http::Request::builder().header("x-user", "admin").body(()).unwrap();
+    let response = service.clone().oneshot(request).await.expect("success");
+    assert_eq!(response.headers().get("grpc-status").unwrap(), "3");
+
+    //Back to original `ServiceFn`
+    let svc = service.into_inner().into_inner();
+    let request = http::Request::builder().header("x-user", "admin").body(()).unwrap();
+    let response = svc.oneshot(request).await.expect("success");
+    assert_eq!(response.into_body().unwrap(), "admin");
+}
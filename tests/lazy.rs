@@ -0,0 +1,150 @@
+#![cfg(feature = "tonic011")]
+#![allow(clippy::result_large_err)]
+
+use tonic_interceptor::{Interceptor, InterceptorService, LazyMetadata, lazy::KeyAndValue};
+use tonic_interceptor::testing::{poll_once, service_fn};
+
+use tonic::Status;
+use tonic::metadata::{KeyAndValueRef, MetadataMap};
+use tower_service::Service;
+
+fn corpus() -> Vec<http::HeaderMap> {
+    let mut result = Vec::new();
+    result.push(http::HeaderMap::new());
+
+    let mut headers = http::HeaderMap::new();
+    headers.insert("x-user", http::HeaderValue::from_static("user"));
+    headers.append("x-user", http::HeaderValue::from_static("other"));
+    headers.insert("x-opaque", http::HeaderValue::from_bytes(b"hello\xfa").unwrap());
+    headers.insert("x-trace-bin", http::HeaderValue::from_static("AAEC"));
+    headers.insert("x-invalid-bin", http::HeaderValue::from_static("not base64!"));
+    result.push(headers);
+
+    let mut headers = http::HeaderMap::new();
+    let mut secret = http::HeaderValue::from_static("secret");
+    secret.set_sensitive(true);
+    headers.insert("authorization", secret);
+    headers.insert("x-empty", http::HeaderValue::from_static(""));
+    headers.insert("x-padded-bin", http::HeaderValue::from_static("AA=="));
+    headers.append("x-padded-bin", http::HeaderValue::from_static("AQ"));
+    result.push(headers);
+
+    result
+}
+
+const KEYS: &[&str] = &["x-user", "X-User", "x-opaque", "x-trace-bin", "x-invalid-bin", "authorization", "x-empty", "x-padded-bin", "x-missing", "x-missing-bin", "invalid key"];
+
+fn ascii(value: Option<&tonic::metadata::AsciiMetadataValue>) -> Option<(Vec<u8>, bool, Option<String>)> {
+    value.map(|value| (value.as_encoded_bytes().to_vec(), value.is_sensitive(), value.to_str().ok().map(ToOwned::to_owned)))
+}
+
+//Valid values are compared decoded, as lazy view does not keep their padding
+fn binary(value: Option<&tonic::metadata::BinaryMetadataValue>) -> Option<(Option<Vec<u8>>, bool, Option<bytes::Bytes>)> {
+    value.map(|value| match value.to_bytes() {
+        Ok(decoded) => (None, value.is_sensitive(), Some(decoded)),
+        Err(_) => (Some(value.as_encoded_bytes().to_vec()), value.is_sensitive(), None),
+    })
+}
+
+#[test]
+fn should_get_same_values_as_metadata_map() {
+    for mut headers in corpus() {
+        let metadata = MetadataMap::from_headers(headers.clone());
+        let lazy = LazyMetadata::new(&mut headers);
+
+        assert_eq!(metadata.len(), lazy.len());
+        assert_eq!(metadata.is_empty(), lazy.is_empty());
+        for key in KEYS {
+            assert_eq!(ascii(metadata.get(*key)), ascii(lazy.get(key).as_ref()), "get({})", key);
+            assert_eq!(binary(metadata.get_bin(*key)), binary(lazy.get_bin(key).as_ref()), "get_bin({})", key);
+            assert_eq!(metadata.contains_key(*key), lazy.contains_key(key), "contains_key({})", key);
+        }
+    }
+}
+
+#[test]
+fn should_iterate_same_as_metadata_map() {
+    for mut headers in corpus() {
+        let metadata = MetadataMap::from_headers(headers.clone());
+        let lazy = LazyMetadata::new(&mut headers);
+
+        let expected = metadata.iter().map(|entry| match entry {
+            KeyAndValueRef::Ascii(key, value) => (key.as_str().to_owned(), ascii(Some(value)), None),
+            KeyAndValueRef::Binary(key, value) => (key.as_str().to_owned(), None, binary(Some(value))),
+        }).collect::<Vec<_>>();
+        let actual = lazy.iter().map(|entry| match entry {
+            KeyAndValue::Ascii(key, value) => (key.to_owned(), ascii(Some(&value)), None),
+            KeyAndValue::Binary(key, value) => (key.to_owned(), None, binary(Some(&value))),
+        }).collect::<Vec<_>>();
+        assert_eq!(expected, actual);
+
+        let expected = metadata.keys().map(|key| match key {
+            tonic::metadata::KeyRef::Ascii(key) => key.as_str(),
+            tonic::metadata::KeyRef::Binary(key) => key.as_str(),
+        }).collect::<Vec<_>>();
+        assert_eq!(expected, lazy.keys().collect::<Vec<_>>());
+    }
+}
+
+#[test]
+fn should_modify_same_as_metadata_map() {
+    for mut headers in corpus() {
+        let mut metadata = MetadataMap::from_headers(headers.clone());
+        let mut lazy = LazyMetadata::new(&mut headers);
+
+        let mut secret = tonic::metadata::AsciiMetadataValue::from_static("new");
+        secret.set_sensitive(true);
+        assert_eq!(ascii(metadata.insert("authorization", secret.clone()).as_ref()), ascii(lazy.insert("authorization", secret).as_ref()));
+        assert_eq!(ascii(metadata.insert("x-user", "replaced".parse().unwrap()).as_ref()), ascii(lazy.insert("x-user", "replaced".parse().unwrap()).as_ref()));
+
+        let value = tonic::metadata::BinaryMetadataValue::from_bytes(b"\x00binary");
+        assert_eq!(binary(metadata.insert_bin("x-trace-bin", value.clone()).as_ref()), binary(lazy.insert_bin("x-trace-bin", value).as_ref()));
+
+        for key in KEYS {
+            assert_eq!(ascii(metadata.remove(*key).as_ref()), ascii(lazy.remove(key).as_ref()), "remove({})", key);
+            assert_eq!(binary(metadata.remove_bin(*key).as_ref()), binary(lazy.remove_bin(key).as_ref()), "remove_bin({})", key);
+        }
+
+        let metadata = metadata.into_headers();
+        assert_eq!(metadata, headers);
+        for (key, value) in metadata.iter() {
+            assert_eq!(value.is_sensitive(), headers.get(key).unwrap().is_sensitive());
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Lazy;
+
+impl Interceptor for Lazy {
+    fn on_request(&self, _: &mut MetadataMap, _: &mut http::Extensions) -> Option<Status> {
+        unreachable!()
+    }
+
+    fn on_request_lazy(&self, _: &http::Uri, headers: &mut LazyMetadata<'_>, _: &mut http::Extensions) -> Option<Status> {
+        if headers.get("x-reject").is_some() {
+            return Some(Status::permission_denied("rejected"));
+        }
+        headers.insert("x-added", "1".parse().unwrap());
+        None
+    }
+
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
+
+#[test]
+fn should_prefer_lazy_callback_in_service() {
+    let mut service = InterceptorService::new(std::sync::Arc::new(Lazy), service_fn(|request: http::Request<()>| {
+        assert_eq!(request.headers().get("x-added").unwrap(), "1");
+        Ok::<_, Status>(http::Response::new(()))
+    }));
+
+    let response = poll_once(service.call(http::Request::new(()))).unwrap();
+    assert!(response.headers().get("grpc-status").is_none());
+
+    let mut request = http::Request::new(());
+    request.headers_mut().insert("x-reject", http::HeaderValue::from_static("1"));
+    let response = poll_once(service.call(request)).unwrap();
+    assert_eq!(response.headers().get("grpc-status").unwrap(), "7");
+}
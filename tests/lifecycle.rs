@@ -0,0 +1,147 @@
+#![cfg(all(feature = "tonic011", feature = "tokio"))]
+
+use tonic_interceptor::lifecycle::{Drain, DRAINING_HEADER};
+
+use tower::{Layer, ServiceExt};
+use tokio::sync::oneshot;
+
+use core::convert::Infallible;
+use core::time::Duration;
+
+type Body = http_body::Empty<bytes::Bytes>;
+type Response = http::Response<tonic_interceptor::lifecycle::TrackBody<Body>>;
+
+//Starts call, which responds once sender is used or dropped
+fn call(drain: &Drain) -> (oneshot::Sender<()>, tokio::task::JoinHandle<Result<Response, Infallible>>) {
+    let (sender, receiver) = oneshot::channel::<()>();
+    let receiver = std::sync::Mutex::new(Some(receiver));
+    let service = drain.layer().layer(tower::service_fn(move |_: http::Request<()>| {
+        let receiver = receiver.lock().unwrap().take().expect("single call");
+        async move {
+            let _ = receiver.await;
+            Ok::<_, Infallible>(http::Response::new(Body::new()))
+        }
+    }));
+    (sender, tokio::spawn(service.oneshot(http::Request::new(()))))
+}
+
+async fn settle() {
+    for _ in 0..10 {
+        tokio::task::yield_now().await;
+    }
+}
+
+#[tokio::test]
+async fn should_resolve_once_in_flight_calls_complete() {
+    let drain = Drain::new(Duration::from_secs(3600));
+    let handle = drain.handle();
+
+    let (complete, completed) = call(&drain);
+    let (respond, streaming) = call(&drain);
+    let (_cancel, cancelled) = call(&drain);
+    settle().await;
+    assert_eq!(handle.in_flight(), 3);
+
+    let drained = tokio::spawn(handle.start_drain());
+    assert!(handle.is_draining());
+
+    //New calls are rejected without being counted
+    let (_, rejected) = call(&drain);
+    let rejected = rejected.await.unwrap().unwrap();
+    assert_eq!(rejected.headers().get("grpc-status").unwrap(), "14");
+    assert_eq!(rejected.headers().get(DRAINING_HEADER).unwrap(), "true");
+    assert_eq!(handle.in_flight(), 3);
+
+    complete.send(()).unwrap();
+    drop(completed.await.unwrap().unwrap());
+    settle().await;
+    assert_eq!(handle.in_flight(), 2);
+    assert!(!drained.is_finished());
+
+    //Response is ready, but its body is still being sent
+    respond.send(()).unwrap();
+    let body = streaming.await.unwrap().unwrap();
+    settle().await;
+    assert_eq!(handle.in_flight(), 2);
+    assert!(!drained.is_finished());
+    drop(body);
+    settle().await;
+    assert_eq!(handle.in_flight(), 1);
+    assert!(!drained.is_finished());
+
+    cancelled.abort();
+    tokio::time::timeout(Duration::from_secs(5), drained).await.expect("to drain").unwrap();
+    assert_eq!(handle.in_flight(), 0);
+}
+
+#[tokio::test]
+async fn should_resolve_immediately_without_in_flight_calls() {
+    let drain = Drain::new(Duration::from_secs(3600));
+    let (complete, completed) = call(&drain);
+    complete.send(()).unwrap();
+    drop(completed.await.unwrap().unwrap());
+
+    tokio::time::timeout(Duration::from_secs(5), drain.handle().start_drain()).await.expect("to drain");
+}
+
+#[tokio::test]
+async fn should_resolve_on_timeout() {
+    use tonic_interceptor::testing::{with_noop_context, TestTimer};
+    use core::future::Future;
+
+    let timer = TestTimer::new();
+    let drain = Drain::new(Duration::from_secs(30)).timer(timer.clone());
+    let handle = drain.handle();
+    let (_stuck, _call) = call(&drain);
+    settle().await;
+
+    let mut drained = core::pin::pin!(handle.start_drain());
+    assert!(with_noop_context(|ctx| drained.as_mut().poll(ctx)).is_pending());
+    timer.advance(Duration::from_secs(29));
+    assert!(with_noop_context(|ctx| drained.as_mut().poll(ctx)).is_pending());
+    timer.advance(Duration::from_secs(1));
+    assert!(with_noop_context(|ctx| drained.as_mut().poll(ctx)).is_ready());
+    assert_eq!(handle.in_flight(), 1);
+}
+
+#[test]
+fn should_not_admit_call_after_drain_resolved() {
+    use tonic_interceptor::Interceptor;
+    use tonic_interceptor::testing::{with_noop_context, TestTimer};
+    use core::future::Future;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    for _ in 0..200 {
+        let drain = Drain::new(Duration::from_secs(30)).timer(TestTimer::new());
+        let handle = drain.handle();
+        let started = Arc::new(AtomicBool::new(false));
+        let resolved = Arc::new(AtomicBool::new(false));
+        //Admits calls while drain is being started, until rejected
+        let admitting = std::thread::spawn({
+            let started = started.clone();
+            let resolved = resolved.clone();
+            move || loop {
+                let mut extensions = http::Extensions::new();
+                match drain.on_request(&mut tonic::metadata::MetadataMap::new(), &mut extensions) {
+                    Some(status) => break status,
+                    None => assert!(!resolved.load(Ordering::SeqCst), "call is admitted after drain resolved"),
+                }
+                started.store(true, Ordering::SeqCst);
+            }
+        });
+
+        while !started.load(Ordering::SeqCst) {
+            std::thread::yield_now();
+        }
+        let mut drained = core::pin::pin!(handle.start_drain());
+        while with_noop_context(|ctx| drained.as_mut().poll(ctx)).is_pending() {
+            std::thread::yield_now();
+        }
+        resolved.store(true, Ordering::SeqCst);
+
+        let rejected = admitting.join().unwrap();
+        assert_eq!(rejected.code(), tonic::Code::Unavailable);
+        assert_eq!(handle.in_flight(), 0);
+    }
+}
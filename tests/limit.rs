@@ -0,0 +1,582 @@
+#![cfg(feature = "tonic011")]
+use tonic_interceptor::headers::Echoed;
+use tonic_interceptor::limit::{AdaptiveShed, Headers, RateLimitSet, ShedController, RATELIMIT_LIMIT, RATELIMIT_REMAINING, RATELIMIT_RESET};
+use tonic_interceptor::testing::TestTimer;
+
+use core::time::Duration;
+
+fn values(headers: &http::HeaderMap) -> [&str; 3] {
+    let value = |key| headers.get(key).map(|value: &http::HeaderValue| value.to_str().unwrap()).unwrap_or("-");
+    [value(RATELIMIT_LIMIT), value(RATELIMIT_REMAINING), value(RATELIMIT_RESET)]
+}
+
+#[test]
+fn should_write_headers() {
+    let mut headers = http::HeaderMap::new();
+    Headers::new(100, 42, Duration::from_millis(1500)).write(&mut headers);
+    assert_eq!(values(&headers), ["100", "42", "2"]);
+
+    Headers::new(10, 0, Duration::from_secs(3)).write(&mut headers);
+    assert_eq!(values(&headers), ["10", "0", "3"]);
+    assert_eq!(headers.len(), 3);
+}
+
+#[test]
+fn should_keep_most_restrictive_limit() {
+    let mut extensions = http::Extensions::new();
+    Headers::new(100, 42, Duration::from_secs(1)).echo(&mut extensions);
+    Headers::new(10, 5, Duration::from_secs(60)).echo(&mut extensions);
+    Headers::new(1000, 999, Duration::from_secs(1)).echo(&mut extensions);
+
+    assert_eq!(extensions.get::<Headers>(), Some(&Headers::new(10, 5, Duration::from_secs(60))));
+    let echoed = extensions.get::<Echoed>().unwrap();
+    assert_eq!(values(echoed.headers()), ["10", "5", "60"]);
+    assert_eq!(echoed.headers().len(), 3);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn should_write_headers_of_rate_limit_on_every_response() {
+    use tonic_interceptor::InterceptorService;
+    use tonic_interceptor::config::RateLimit;
+    use tonic_interceptor::testing::{poll_once, service_fn};
+
+    use std::sync::{Arc, Mutex};
+    use tower::Service;
+
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let inner = {
+        let seen = seen.clone();
+        service_fn(move |req: http::Request<()>| {
+            seen.lock().unwrap().push(req.extensions().get::<Headers>().map(|headers| headers.remaining));
+            Ok::<_, &'static str>(http::Response::new(()))
+        })
+    };
+    let limit = Arc::new(RateLimit::new(2, Duration::from_secs(60)).headers(true));
+    let mut service = InterceptorService::new(limit, inner);
+
+    let mut call = || poll_once(service.call(http::Request::new(()))).expect("response").headers().clone();
+    let first = call();
+    assert_eq!(values(&first), ["2", "1", "60"]);
+    assert!(!first.contains_key("grpc-status"));
+    assert_eq!(values(&call()), ["2", "0", "60"]);
+
+    //Rejection carries the same headers as decided for it
+    let rejected = call();
+    assert_eq!(rejected.get("grpc-status").unwrap(), "8");
+    assert_eq!(rejected.get("retry-after").unwrap(), "60");
+    assert_eq!(values(&rejected), ["2", "0", "60"]);
+
+    //Inner service observes decision of its own request
+    assert_eq!(*seen.lock().unwrap(), [Some(1), Some(0)]);
+
+    //Disabled by default
+    let mut service = InterceptorService::new(Arc::new(RateLimit::new(1, Duration::from_secs(60))), service_fn(|_: http::Request<()>| Ok::<_, &'static str>(http::Response::new(()))));
+    let headers = poll_once(service.call(http::Request::new(()))).expect("response").headers().clone();
+    assert_eq!(values(&headers), ["-", "-", "-"]);
+}
+
+fn assert_rate(controller: &ShedController, expected: f64) {
+    assert!((controller.rate() - expected).abs() < 1e-9, "rate {} != {}", controller.rate(), expected);
+}
+
+fn record_window(controller: &mut ShedController, latencies: &[u64]) {
+    for latency in latencies {
+        controller.record(Duration::from_millis(*latency));
+    }
+}
+
+#[test]
+fn should_decrease_admission_rate_on_latency_inflation() {
+    let mut controller = ShedController::new(Duration::from_millis(10)).window(4);
+    assert_rate(&controller, 1.0);
+    assert!((0..10).all(|_| controller.admit()));
+
+    //Incomplete window changes nothing
+    record_window(&mut controller, &[40, 40, 40]);
+    assert_rate(&controller, 1.0);
+    assert_eq!(controller.recent_latency(), None);
+
+    //Decrease is at most half per window
+    record_window(&mut controller, &[40]);
+    assert_rate(&controller, 0.5);
+    assert_eq!(controller.recent_latency(), Some(Duration::from_millis(40)));
+    assert_eq!((0..10).filter(|_| controller.admit()).count(), 5);
+
+    //Otherwise it is proportional to inflation
+    record_window(&mut controller, &[20, 16, 40, 30]);
+    assert_rate(&controller, 0.5 * 10.0 / 16.0);
+    assert_eq!(controller.recent_latency(), Some(Duration::from_millis(16)));
+
+    //Rate never goes below minimum
+    for _ in 0..10 {
+        record_window(&mut controller, &[100, 100, 100, 100]);
+    }
+    assert_rate(&controller, 0.05);
+    assert_eq!((0..100).filter(|_| controller.admit()).count(), 5);
+}
+
+#[test]
+fn should_recover_admission_rate_without_latency_inflation() {
+    let mut controller = ShedController::new(Duration::from_millis(10)).window(2).min_rate(0.1).increase(0.25);
+    for _ in 0..10 {
+        record_window(&mut controller, &[50, 50]);
+    }
+    assert_rate(&controller, 0.1);
+
+    //The least latency within window is what counts
+    record_window(&mut controller, &[50, 10]);
+    assert_rate(&controller, 0.35);
+    record_window(&mut controller, &[1, 1]);
+    assert_rate(&controller, 0.6);
+    assert_eq!(controller.recent_latency(), Some(Duration::from_millis(1)));
+    record_window(&mut controller, &[1, 1]);
+    record_window(&mut controller, &[1, 1]);
+    assert_rate(&controller, 1.0);
+    assert!((0..10).all(|_| controller.admit()));
+
+    //Window has at least one sample
+    let mut controller = ShedController::new(Duration::from_millis(10)).window(0);
+    controller.record(Duration::from_millis(20));
+    assert_rate(&controller, 0.5);
+}
+
+#[test]
+fn should_shed_load_of_slow_service() {
+    use tonic_interceptor::testing::{poll_once, service_fn};
+
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use tower::{Layer, Service};
+
+    let slow = Arc::new(AtomicBool::new(true));
+    let inner = {
+        let slow = slow.clone();
+        service_fn(move |_: http::Request<()>| {
+            if slow.load(Ordering::SeqCst) {
+                std::thread::sleep(Duration::from_millis(40));
+            }
+            Ok::<_, &'static str>(http::Response::new(()))
+        })
+    };
+    let shed = AdaptiveShed::new(ShedController::new(Duration::from_millis(20)).window(2).increase(0.5));
+    let handle = shed.handle();
+    let mut service = shed.layer().layer(inner);
+    let mut call = || poll_once(service.call(http::Request::new(()))).expect("response").headers().get("grpc-status").map(|code| code.to_str().unwrap().to_owned());
+
+    assert_eq!(call(), None);
+    assert_eq!(call(), None);
+    assert_eq!(handle.admission_rate(), 0.5);
+    assert!(handle.recent_latency().unwrap() >= Duration::from_millis(40));
+
+    //Half of requests are rejected, while rejected ones are not sampled
+    let codes: Vec<_> = (0..4).map(|_| call()).collect();
+    assert_eq!(codes, [Some("8".to_owned()), None, Some("8".to_owned()), None]);
+    assert_eq!(handle.admission_rate(), 0.25);
+
+    //Once service is fast again, admission recovers
+    slow.store(false, Ordering::SeqCst);
+    let admitted = (0..16).filter(|_| call().is_none()).count();
+    assert!(admitted >= 6, "admitted {}", admitted);
+    assert_eq!(handle.admission_rate(), 1.0);
+}
+
+fn limits(timer: &TestTimer) -> RateLimitSet<TestTimer> {
+    use tonic_interceptor::limit::KeyedLimit;
+
+    let tenant_method = KeyedLimit::new("tenant-method", 10, Duration::from_secs(60), |metadata: &tonic::metadata::MetadataMap, _: &http::Extensions, method: &str| {
+        let tenant = metadata.get("x-tenant")?.to_str().ok()?;
+        Some(format!("{}{}", tenant, method))
+    });
+    let api_key = KeyedLimit::new("api-key", 2, Duration::from_secs(60), |metadata: &tonic::metadata::MetadataMap, _: &http::Extensions, _: &str| {
+        metadata.get("x-api-key")?.to_str().ok().map(ToOwned::to_owned)
+    }).max_keys(2);
+    RateLimitSet::new().with(tenant_method).with(api_key).clock(timer.clone())
+}
+
+fn limited(limits: &RateLimitSet<TestTimer>, path: &'static str, tenant: &'static str, api_key: Option<&'static str>) -> (Option<tonic::Status>, http::Extensions) {
+    use tonic_interceptor::Interceptor;
+
+    let mut headers = http::HeaderMap::new();
+    headers.insert("x-tenant", http::HeaderValue::from_static(tenant));
+    if let Some(api_key) = api_key {
+        headers.insert("x-api-key", http::HeaderValue::from_static(api_key));
+    }
+    let mut extensions = http::Extensions::new();
+    let status = limits.on_request_headers(&http::Uri::from_static(path), &mut headers, &mut extensions);
+    assert_eq!(headers.get("x-tenant").unwrap(), tenant);
+    (status, extensions)
+}
+
+#[test]
+fn should_reject_by_first_exceeded_limit() {
+    use tonic_interceptor::limit::RATELIMIT_NAME;
+
+    let limits = limits(&TestTimer::new());
+    assert!(limited(&limits, "/pkg.Users/Get", "acme", Some("key-1")).0.is_none());
+    assert!(limited(&limits, "/pkg.Users/List", "acme", Some("key-1")).0.is_none());
+
+    //Only API key limit trips
+    let status = limited(&limits, "/pkg.Users/Get", "acme", Some("key-1")).0.expect("rejection");
+    assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+    assert_eq!(status.message(), "rate limit 'api-key' exceeded");
+    assert_eq!(status.metadata().get(RATELIMIT_NAME).unwrap(), "api-key");
+    assert_eq!(status.metadata().get("retry-after").unwrap(), "60");
+
+    //Rejected request is not counted by other limits
+    let tenant_method = limits.get("tenant-method").expect("limit");
+    assert_eq!(tenant_method.len(), 2);
+    for _ in 0..9 {
+        assert!(limited(&limits, "/pkg.Users/Get", "acme", None).0.is_none());
+    }
+    let status = limited(&limits, "/pkg.Users/Get", "acme", None).0.expect("rejection");
+    assert_eq!(status.message(), "rate limit 'tenant-method' exceeded");
+    assert_eq!(status.metadata().get(RATELIMIT_NAME).unwrap(), "tenant-method");
+
+    //Other keys are not affected
+    assert!(limited(&limits, "/pkg.Users/Get", "other", Some("key-2")).0.is_none());
+    assert!(limited(&limits, "/pkg.Users/List", "acme", Some("key-3")).0.is_none());
+
+    //Without URI request is passed through
+    use tonic_interceptor::Interceptor;
+    assert!(limits.on_request(&mut tonic::metadata::MetadataMap::new(), &mut http::Extensions::new()).is_none());
+}
+
+#[test]
+fn should_bound_keys_of_each_limit() {
+    let timer = TestTimer::new();
+    let limits = limits(&timer);
+    for (idx, api_key) in ["key-1", "key-2", "key-3", "key-4"].iter().enumerate() {
+        assert!(limited(&limits, "/pkg.Users/Get", "acme", Some(api_key)).0.is_none(), "{}", idx);
+        timer.advance(Duration::from_millis(1));
+    }
+    assert_eq!(limits.get("api-key").expect("limit").len(), 2);
+    assert_eq!(limits.get("tenant-method").expect("limit").len(), 1);
+
+    //The oldest key was evicted, hence it starts with fresh window
+    assert!(limited(&limits, "/pkg.Users/Get", "acme", Some("key-1")).0.is_none());
+    assert!(limited(&limits, "/pkg.Users/Get", "acme", Some("key-1")).0.is_none());
+    assert!(limited(&limits, "/pkg.Users/Get", "acme", Some("key-1")).0.is_some());
+    assert_eq!(limits.get("api-key").expect("limit").len(), 2);
+}
+
+#[test]
+fn should_echo_headers_of_most_restrictive_applicable_limit() {
+    let timer = TestTimer::new();
+    let limits = limits(&timer).headers(true);
+
+    let (_, mut extensions) = limited(&limits, "/pkg.Users/Get", "acme", Some("key-1"));
+    let mut headers = http::HeaderMap::new();
+    extensions.remove::<Echoed>().expect("echoed").apply(&mut headers);
+    assert_eq!(values(&headers), ["2", "1", "60"]);
+
+    //Without API key only tenant limit applies
+    let (_, mut extensions) = limited(&limits, "/pkg.Users/Get", "acme", None);
+    let mut headers = http::HeaderMap::new();
+    extensions.remove::<Echoed>().expect("echoed").apply(&mut headers);
+    assert_eq!(values(&headers), ["10", "8", "60"]);
+
+    limited(&limits, "/pkg.Users/Get", "acme", Some("key-1"));
+    let (status, mut extensions) = limited(&limits, "/pkg.Users/Get", "acme", Some("key-1"));
+    assert!(status.is_some());
+    let mut headers = http::HeaderMap::new();
+    extensions.remove::<Echoed>().expect("echoed").apply(&mut headers);
+    assert_eq!(values(&headers), ["2", "0", "60"]);
+
+    timer.advance(Duration::from_millis(59_500));
+    let (status, mut extensions) = limited(&limits, "/pkg.Users/Get", "acme", Some("key-1"));
+    assert!(status.is_some());
+    let mut headers = http::HeaderMap::new();
+    extensions.remove::<Echoed>().expect("echoed").apply(&mut headers);
+    assert_eq!(values(&headers), ["2", "0", "1"]);
+
+    //New window starts once period elapses
+    timer.advance(Duration::from_millis(500));
+    let (status, mut extensions) = limited(&limits, "/pkg.Users/Get", "acme", Some("key-1"));
+    assert!(status.is_none());
+    let mut headers = http::HeaderMap::new();
+    extensions.remove::<Echoed>().expect("echoed").apply(&mut headers);
+    assert_eq!(values(&headers), ["2", "1", "60"]);
+}
+
+#[cfg(feature = "gzip")]
+mod compression {
+    use tonic_interceptor::limit::{CompressionGuard, GuardedBody};
+    use tonic_interceptor::testing::{poll_once, service_fn, with_noop_context};
+
+    use tower_layer::Layer;
+    use tower_service::Service;
+
+    use core::task;
+    use core::pin::Pin;
+    use core::convert::Infallible;
+    use std::collections::VecDeque;
+    use std::io::Write;
+
+    use bytes::Bytes;
+
+    //Request body, which chunks are always ready
+    struct Chunks(VecDeque<Bytes>);
+
+    impl http_body::Body for Chunks {
+        type Data = Bytes;
+        type Error = Infallible;
+
+        fn poll_data(mut self: Pin<&mut Self>, _: &mut task::Context<'_>) -> task::Poll<Option<Result<Self::Data, Self::Error>>> {
+            task::Poll::Ready(self.0.pop_front().map(Ok))
+        }
+
+        fn poll_trailers(self: Pin<&mut Self>, _: &mut task::Context<'_>) -> task::Poll<Result<Option<http::HeaderMap>, Self::Error>> {
+            task::Poll::Ready(Ok(None))
+        }
+
+        fn is_end_stream(&self) -> bool {
+            self.0.is_empty()
+        }
+    }
+
+    fn gzip(message: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+        encoder.write_all(message).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn frame(compressed: bool, message: &[u8]) -> Vec<u8> {
+        let mut frame = vec![compressed as u8];
+        frame.extend_from_slice(&(message.len() as u32).to_be_bytes());
+        frame.extend_from_slice(message);
+        frame
+    }
+
+    //Passes request through guard, returning data received by inner service and error of body
+    fn call(guard: &CompressionGuard, encoding: &'static str, body: &[u8], chunk_size: usize) -> (Vec<u8>, Option<tonic::Status>) {
+        let svc = service_fn(|req: http::Request<GuardedBody<Chunks>>| {
+            let mut body = req.into_body();
+            let mut data = Vec::new();
+            let mut error = None;
+            with_noop_context(|ctx| while let task::Poll::Ready(Some(chunk)) = http_body::Body::poll_data(Pin::new(&mut body), ctx) {
+                match chunk {
+                    Ok(chunk) => data.extend_from_slice(&chunk),
+                    Err(status) => error = Some(status),
+                }
+            });
+            Ok::<_, Infallible>((data, error))
+        });
+        let mut svc = guard.layer().layer(svc);
+        let chunks = body.chunks(chunk_size).map(Bytes::copy_from_slice).collect();
+        let req = http::Request::builder().uri("/pkg.Upload/Put").header("grpc-encoding", encoding).body(Chunks(chunks)).unwrap();
+        poll_once(svc.call(req)).unwrap()
+    }
+
+    #[test]
+    fn should_limit_compressed_size() {
+        let guard = CompressionGuard::new(64);
+
+        let compressed = frame(true, &[0; 65]);
+        let (data, error) = call(&guard, "gzip", &compressed, 3);
+        assert_eq!(error.unwrap().code(), tonic::Code::ResourceExhausted);
+        //Only header is needed to reject
+        assert_eq!(data, compressed[..3]);
+
+        //Any compression is limited, while uncompressed messages are not
+        let (_, error) = call(&guard, "deflate", &compressed, 1024);
+        assert_eq!(error.unwrap().code(), tonic::Code::ResourceExhausted);
+        let uncompressed = frame(false, &[0; 65]);
+        let (data, error) = call(&guard, "gzip", &uncompressed, 7);
+        assert!(error.is_none());
+        assert_eq!(data, uncompressed);
+    }
+
+    #[test]
+    fn should_reject_decompression_bomb() {
+        let bomb = gzip(&vec![0; 16 * 1024 * 1024]);
+        assert!(bomb.len() < 32 * 1024);
+        let bomb = frame(true, &bomb);
+        let guard = CompressionGuard::new(64 * 1024);
+
+        //Without inspection, bomb fits compressed limit
+        let (data, error) = call(&guard, "gzip", &bomb, 1024);
+        assert!(error.is_none());
+        assert_eq!(data, bomb);
+
+        let guard = guard.inspect(1024 * 1024);
+        let (data, error) = call(&guard, "gzip", &bomb, 1024);
+        let error = error.unwrap();
+        assert_eq!(error.code(), tonic::Code::ResourceExhausted);
+        assert_eq!(error.message(), "decompressed message exceeds limit of 1048576 bytes");
+        //Rejected long before whole bomb is received
+        assert!(data.len() < bomb.len() / 2);
+        assert_eq!(data, bomb[..data.len()]);
+
+        //Messages within limit are passed as they are
+        let mut messages = frame(true, &gzip(&[1; 1024 * 1024]));
+        messages.extend_from_slice(&frame(false, b"query"));
+        messages.extend_from_slice(&frame(true, &gzip(b"query")));
+        let (data, error) = call(&guard, "gzip", &messages, 7);
+        assert!(error.is_none());
+        assert_eq!(data, messages);
+
+        //Subsequent message of stream is guarded too
+        messages.extend_from_slice(&bomb);
+        let (data, error) = call(&guard, "gzip", &messages, 1024);
+        assert_eq!(error.unwrap().code(), tonic::Code::ResourceExhausted);
+        assert!(data.len() < messages.len());
+    }
+
+    #[test]
+    fn should_limit_ratio() {
+        let message = frame(true, &gzip(&[0; 64 * 1024]));
+        let guard = CompressionGuard::new(64 * 1024).inspect(1024 * 1024);
+
+        let (_, error) = call(&guard, "gzip", &message, 16);
+        assert!(error.is_none());
+        let (_, error) = call(&guard.max_ratio(10), "gzip", &message, 16);
+        assert_eq!(error.unwrap().code(), tonic::Code::ResourceExhausted);
+    }
+
+    #[test]
+    fn should_reject_invalid_gzip() {
+        let guard = CompressionGuard::new(64 * 1024).inspect(1024);
+        let (_, error) = call(&guard, "gzip", &frame(true, b"query"), 16);
+        assert_eq!(error.unwrap().code(), tonic::Code::Internal);
+
+        //Other compressions cannot be inspected
+        let (_, error) = call(&guard, "deflate", &frame(true, b"query"), 16);
+        assert!(error.is_none());
+    }
+}
+
+#[cfg(feature = "tokio")]
+mod admission {
+    use tonic_interceptor::limit::{AdmissionQueue, AdmissionQueueService, AdmittedBody};
+    use tonic_interceptor::testing::{service_fn, ServiceFn, with_noop_context, TestTimer};
+
+    use tower_layer::Layer;
+    use tower_service::Service;
+
+    use core::pin::pin;
+    use core::future::Future;
+    use core::time::Duration;
+    use core::convert::Infallible;
+
+    type Svc = AdmissionQueueService<ServiceFn<fn(http::Request<()>) -> Result<http::Response<()>, Infallible>>, TestTimer>;
+
+    fn ok(_: http::Request<()>) -> Result<http::Response<()>, Infallible> {
+        Ok(http::Response::new(()))
+    }
+
+    fn queue(timer: &TestTimer) -> AdmissionQueue<TestTimer> {
+        AdmissionQueue::new(1, 1, Duration::from_millis(100)).timer(timer.clone())
+    }
+
+    fn service(queue: &AdmissionQueue<TestTimer>) -> Svc {
+        queue.layer().layer(service_fn(ok as fn(_) -> _))
+    }
+
+    fn call(svc: &mut Svc) -> impl Future<Output = http::Response<AdmittedBody<()>>> {
+        let fut = svc.call(http::Request::new(()));
+        async move {
+            fut.await.unwrap()
+        }
+    }
+
+    fn is_pending<F: Future>(fut: core::pin::Pin<&mut F>) -> bool {
+        with_noop_context(|ctx| fut.poll(ctx).is_pending())
+    }
+
+    //Runs `fut` to completion, advancing `timer` in steps of 10ms
+    fn run<F: Future>(timer: &TestTimer, fut: F) -> F::Output {
+        let mut fut = pin!(fut);
+        loop {
+            if let core::task::Poll::Ready(output) = with_noop_context(|ctx| fut.as_mut().poll(ctx)) {
+                return output;
+            }
+            timer.advance(Duration::from_millis(10));
+        }
+    }
+
+    fn message(response: &http::Response<AdmittedBody<()>>) -> &str {
+        assert_eq!(response.headers()["grpc-status"], "8");
+        assert!(!response.body().is_admitted());
+        response.headers()["grpc-message"].to_str().unwrap()
+    }
+
+    #[test]
+    fn should_admit_waiting_request_once_slot_is_free() {
+        let timer = TestTimer::new();
+        let queue = queue(&timer);
+        let mut svc = service(&queue);
+
+        let first = run(&timer, call(&mut svc));
+        assert!(first.body().is_admitted());
+        assert_eq!(queue.in_flight(), 1);
+
+        let mut second = pin!(call(&mut svc));
+        assert!(is_pending(second.as_mut()));
+        assert_eq!(queue.waiting(), 1);
+
+        timer.advance(Duration::from_millis(50));
+        assert!(is_pending(second.as_mut()));
+        drop(first);
+        let second = run(&timer, second);
+        assert!(second.body().is_admitted());
+        assert_eq!(timer.elapsed(), Duration::from_millis(50));
+        assert_eq!(queue.waiting(), 0);
+        assert_eq!(queue.in_flight(), 1);
+        drop(second);
+        assert_eq!(queue.in_flight(), 0);
+    }
+
+    #[test]
+    fn should_reject_when_queue_is_full() {
+        let timer = TestTimer::new();
+        let queue = queue(&timer);
+        let mut svc = service(&queue);
+
+        let _first = run(&timer, call(&mut svc));
+        let mut second = pin!(call(&mut svc));
+        assert!(is_pending(second.as_mut()));
+
+        let third = run(&timer, call(&mut svc));
+        assert_eq!(message(&third), "admission queue is full");
+        assert_eq!(timer.elapsed(), Duration::ZERO);
+        assert_eq!(queue.waiting(), 1);
+    }
+
+    #[test]
+    fn should_reject_when_wait_times_out() {
+        let timer = TestTimer::new();
+        let queue = queue(&timer);
+        let mut svc = service(&queue);
+
+        let _first = run(&timer, call(&mut svc));
+        let second = run(&timer, call(&mut svc));
+        assert_eq!(message(&second), "timed out waiting for admission after 100ms");
+        assert_eq!(timer.elapsed(), Duration::from_millis(100));
+        assert_eq!(queue.waiting(), 0);
+        assert_eq!(queue.in_flight(), 1);
+    }
+
+    #[test]
+    fn should_give_up_place_when_cancelled() {
+        let timer = TestTimer::new();
+        let queue = queue(&timer);
+        let mut svc = service(&queue);
+
+        let first = run(&timer, call(&mut svc));
+        {
+            let mut cancelled = pin!(call(&mut svc));
+            assert!(is_pending(cancelled.as_mut()));
+            assert_eq!(queue.waiting(), 1);
+        }
+        assert_eq!(queue.waiting(), 0);
+        assert_eq!(timer.sleepers(), 0);
+
+        //Freed slot goes to the next request, not cancelled one
+        let mut next = pin!(call(&mut svc));
+        assert!(is_pending(next.as_mut()));
+        drop(first);
+        assert!(run(&timer, next).body().is_admitted());
+        assert_eq!(queue.waiting(), 0);
+    }
+}
@@ -0,0 +1,110 @@
+#![cfg(feature = "tonic011")]
+#![allow(clippy::result_large_err)]
+
+use tonic_interceptor::{Interceptor, InterceptorService, LocalBoxedInterceptor};
+
+use tonic::Status;
+use tower_service::Service;
+
+use core::future::{self, Future};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+//Intentionally `!Send` and `!Sync`
+#[derive(Clone, Default)]
+struct Cache {
+    users: Rc<RefCell<HashMap<String, usize>>>,
+}
+
+impl Interceptor for Cache {
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<Status> {
+        let user = match headers.get("x-user").and_then(|user| user.to_str().ok()) {
+            Some(user) => user.to_owned(),
+            None => return Some(Status::unauthenticated("missing user")),
+        };
+        let mut users = self.users.borrow_mut();
+        let count = users.entry(user).or_default();
+        *count += 1;
+        headers.insert("x-count", count.to_string().parse().unwrap());
+        None
+    }
+
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
+
+fn request(user: Option<&str>) -> http::Request<()> {
+    let mut request = http::Request::builder();
+    if let Some(user) = user {
+        request = request.header("x-user", user);
+    }
+    request.body(()).unwrap()
+}
+
+async fn call<S: Service<http::Request<()>, Response = http::Response<()>, Error = Status>>(service: &mut S, user: Option<&str>) -> http::Response<()> {
+    future::poll_fn(|ctx| service.poll_ready(ctx)).await.expect("ready");
+    service.call(request(user)).await.expect("response")
+}
+
+fn echo_count() -> impl Service<http::Request<()>, Response = http::Response<()>, Error = Status, Future = impl Future> + Clone {
+    tower::service_fn(|request: http::Request<()>| {
+        let mut response = http::Response::new(());
+        if let Some(count) = request.headers().get("x-count") {
+            response.headers_mut().insert("x-count", count.clone());
+        }
+        future::ready(Ok::<_, Status>(response))
+    })
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn should_run_not_send_interceptor_on_local_set() {
+    let cache = Cache::default();
+    let mut service = InterceptorService::new(cache.clone(), echo_count());
+
+    let local = tokio::task::LocalSet::new();
+    local.run_until(async move {
+        let handle = tokio::task::spawn_local(async move {
+            let response = call(&mut service, Some("user")).await;
+            assert_eq!(response.headers().get("x-count").unwrap(), "1");
+            let response = call(&mut service, Some("user")).await;
+            assert_eq!(response.headers().get("x-count").unwrap(), "2");
+            let response = call(&mut service, None).await;
+            assert_eq!(response.headers().get("grpc-status").unwrap(), "16");
+        });
+        handle.await.expect("to complete");
+    }).await;
+
+    assert_eq!(cache.users.borrow().get("user"), Some(&2));
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn should_support_rc_and_boxed_local_interceptor() {
+    let cache = Cache::default();
+
+    let mut service = InterceptorService::new(Rc::new(cache.clone()), echo_count());
+    let response = call(&mut service, Some("user")).await;
+    assert_eq!(response.headers().get("x-count").unwrap(), "1");
+
+    let boxed: LocalBoxedInterceptor = Rc::new(cache.clone());
+    let mut service = InterceptorService::new(boxed, echo_count());
+    let response = call(&mut service, Some("user")).await;
+    assert_eq!(response.headers().get("x-count").unwrap(), "2");
+}
+
+#[test]
+fn should_not_impose_send_bounds() {
+    fn assert_send<T: Send>(_: &T) {
+    }
+
+    //Send is inherited from interceptor and inner service only
+    let service = InterceptorService::new(std::sync::Arc::new(tonic_interceptor::InterceptorFn {
+        on_request: |_: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions| None::<Status>,
+        on_response: |_: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions| {
+        }
+    }), echo_count());
+    assert_send(&service);
+
+    let mut service = InterceptorService::new(Cache::default(), echo_count());
+    let _: tonic_interceptor::InterceptorFut<Cache, _> = service.call(request(Some("user")));
+}
@@ -0,0 +1,88 @@
+#![cfg(feature = "tonic011")]
+#![allow(clippy::result_large_err)]
+
+//Client stack over transport, which is neither `Send` nor driven by runtime (e.g. browser's fetch)
+
+use tonic_interceptor::client::{ClientInterceptor, ClientInterceptorService, LocalBearerAuth, LocalTokenSource};
+use tonic_interceptor::testing::poll_once;
+
+use tonic::Status;
+use tonic::metadata::{Ascii, MetadataValue};
+use tower::ServiceBuilder;
+use tower_service::Service;
+
+use core::cell::{Cell, RefCell};
+use core::future;
+use std::rc::Rc;
+
+type Sent = Rc<RefCell<Vec<http::HeaderMap>>>;
+
+#[derive(Clone)]
+struct Transport(Sent);
+
+impl Service<http::Request<()>> for Transport {
+    type Response = http::Response<tonic::body::BoxBody>;
+    type Error = Status;
+    type Future = future::Ready<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _: &mut core::task::Context<'_>) -> core::task::Poll<Result<(), Self::Error>> {
+        core::task::Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<()>) -> Self::Future {
+        self.0.borrow_mut().push(request.headers().clone());
+        future::ready(Ok(http::Response::builder().header("grpc-status", "0").body(tonic::body::empty_body()).unwrap()))
+    }
+}
+
+#[derive(Clone)]
+struct RequestId(Rc<Cell<u64>>);
+
+impl ClientInterceptor for RequestId {
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<Status> {
+        let id = self.0.get() + 1;
+        self.0.set(id);
+        headers.insert("x-request-id", id.into());
+        None
+    }
+
+    fn on_response(&self, _: &tonic::metadata::MetadataMap, _: &http::Extensions) {
+    }
+}
+
+//Token kept in browser storage
+struct Storage(Rc<RefCell<Option<&'static str>>>);
+
+impl LocalTokenSource for Storage {
+    async fn token(&self) -> Result<MetadataValue<Ascii>, Status> {
+        match *self.0.borrow() {
+            Some(token) => Ok(MetadataValue::from_static(token)),
+            None => Err(Status::unauthenticated("not logged in")),
+        }
+    }
+}
+
+#[test]
+fn should_intercept_over_local_transport() {
+    let sent = Sent::default();
+    let token = Rc::new(RefCell::new(Some("secret")));
+    let interceptor = RequestId(Rc::new(Cell::new(0)));
+    let mut service = ServiceBuilder::new().layer(LocalBearerAuth::new(Storage(token.clone())))
+                                           .layer_fn(|inner| ClientInterceptorService::new(interceptor.clone(), inner))
+                                           .service(Transport(sent.clone()));
+
+    poll_once(service.call(http::Request::new(()))).expect("success");
+    poll_once(service.call(http::Request::new(()))).expect("success");
+    {
+        let sent = sent.borrow();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[0].get("authorization").unwrap(), "Bearer secret");
+        assert_eq!(sent[0].get("x-request-id").unwrap(), "1");
+        assert_eq!(sent[1].get("x-request-id").unwrap(), "2");
+    }
+
+    token.borrow_mut().take();
+    let error = poll_once(service.call(http::Request::new(()))).expect_err("to fail");
+    assert_eq!(Status::from_error(error).code(), tonic::Code::Unauthenticated);
+    assert_eq!(sent.borrow().len(), 2);
+}
@@ -0,0 +1,73 @@
+#![cfg(feature = "tonic011")]
+#![allow(clippy::result_large_err)]
+
+use tonic_interceptor::MetadataMapExt;
+
+use tonic::Code;
+use tonic::metadata::{AsciiMetadataValue, BinaryMetadataValue, MetadataMap};
+
+use core::convert::TryFrom;
+
+fn metadata() -> MetadataMap {
+    let mut metadata = MetadataMap::new();
+    metadata.insert("x-user", "admin".parse().unwrap());
+    metadata.insert("x-limit", "10".parse().unwrap());
+    metadata.insert("x-opaque", AsciiMetadataValue::try_from(&b"caf\xe9"[..]).unwrap());
+    metadata.insert_bin("x-token-bin", BinaryMetadataValue::from_bytes(b"\x00\x01"));
+    metadata
+}
+
+#[test]
+fn should_require_key() {
+    let metadata = metadata();
+    assert_eq!(metadata.require("x-user", Code::Unauthenticated).unwrap(), "admin");
+
+    let status = metadata.require("authorization", Code::Unauthenticated).expect_err("to be missing");
+    assert_eq!(status.code(), Code::Unauthenticated);
+    assert_eq!(status.message(), "missing metadata 'authorization'");
+
+    let status = metadata.require("x-tenant", Code::InvalidArgument).expect_err("to be missing");
+    assert_eq!(status.code(), Code::InvalidArgument);
+    assert_eq!(status.message(), "missing metadata 'x-tenant'");
+
+    //Binary key is not ASCII key
+    let status = metadata.require("x-token-bin", Code::InvalidArgument).expect_err("to be missing");
+    assert_eq!(status.message(), "missing metadata 'x-token-bin'");
+}
+
+#[test]
+fn should_require_bin_key() {
+    let metadata = metadata();
+    assert_eq!(metadata.require_bin("x-token-bin", Code::Unauthenticated).unwrap().to_bytes().unwrap().as_ref(), b"\x00\x01");
+
+    let status = metadata.require_bin("x-missing-bin", Code::Unauthenticated).expect_err("to be missing");
+    assert_eq!(status.code(), Code::Unauthenticated);
+    assert_eq!(status.message(), "missing metadata 'x-missing-bin'");
+
+    let status = metadata.require_bin("x-user", Code::InvalidArgument).expect_err("to be missing");
+    assert_eq!(status.message(), "missing metadata 'x-user'");
+}
+
+#[test]
+fn should_get_str() {
+    let metadata = metadata();
+    assert_eq!(metadata.get_str("x-user"), Some("admin"));
+    assert_eq!(metadata.get_str("x-missing"), None);
+    assert_eq!(metadata.get_str("x-opaque"), None);
+    assert!(metadata.get("x-opaque").is_some());
+}
+
+#[test]
+fn should_parse() {
+    let metadata = metadata();
+    assert_eq!(metadata.parse::<u32>("x-limit").unwrap(), Some(10));
+    assert_eq!(metadata.parse::<u32>("x-missing").unwrap(), None);
+
+    let status = metadata.parse::<u32>("x-user").expect_err("to fail");
+    assert_eq!(status.code(), Code::InvalidArgument);
+    assert_eq!(status.message(), "metadata 'x-user' has invalid format");
+
+    let status = metadata.parse::<String>("x-opaque").expect_err("to fail");
+    assert_eq!(status.code(), Code::InvalidArgument);
+    assert_eq!(status.message(), "metadata 'x-opaque' is not valid string");
+}
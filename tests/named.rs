@@ -0,0 +1,54 @@
+#![cfg(feature = "tonic011")]
+#![allow(clippy::result_large_err)]
+
+mod common;
+
+use common::{EchoClient, EchoServer, EchoService};
+use common::echo::EchoRequest;
+
+use tonic_interceptor::{Interceptor, InterceptorService};
+
+use tonic::Status;
+use tonic::server::NamedService;
+
+#[derive(Clone)]
+struct Reject;
+
+impl Interceptor for Reject {
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<Status> {
+        match headers.contains_key("x-reject") {
+            true => Some(Status::permission_denied("rejected")),
+            false => None,
+        }
+    }
+
+    fn on_response(&self, _: tonic::Code, headers: &mut http::HeaderMap, _: &http::Extensions) {
+        headers.insert("x-intercepted", http::HeaderValue::from_static("1"));
+    }
+}
+
+#[test]
+fn should_forward_service_name() {
+    assert_eq!(<InterceptorService<Reject, EchoServer> as NamedService>::NAME, EchoServer::NAME);
+}
+
+#[tokio::test]
+async fn should_add_wrapped_service_to_server() {
+    let service = EchoService::default();
+    let wrapped = InterceptorService::new(Reject, EchoServer::new(service.clone()));
+
+    let (incoming, addr) = common::listen().await;
+    let server = tonic::transport::Server::builder().add_service(wrapped);
+    tokio::spawn(server.serve_with_incoming(incoming));
+    let mut client = EchoClient::new(common::connect(addr).await);
+
+    let response = client.unary(EchoRequest { message: "hello".to_owned() }).await.expect("success");
+    assert_eq!(response.get_ref().message, "hello");
+    assert_eq!(response.metadata().get("x-intercepted").unwrap(), "1");
+
+    let mut request = tonic::Request::new(EchoRequest { message: "hello".to_owned() });
+    request.metadata_mut().insert("x-reject", "1".parse().unwrap());
+    let status = client.unary(request).await.expect_err("to reject");
+    assert_eq!(status.code(), tonic::Code::PermissionDenied);
+    assert_eq!(service.calls(), 1);
+}
@@ -0,0 +1,100 @@
+#![cfg(all(feature = "tonic011", unix, feature = "uds"))]
+#![allow(clippy::result_large_err)]
+
+mod common;
+
+use common::{EchoClient, EchoServer, EchoService};
+use common::echo::EchoRequest;
+
+use tonic_interceptor::{Interceptor, InterceptorChain, InterceptorFn};
+use tonic_interceptor::identity::{Mechanism, PeerIdentity};
+use tonic_interceptor::net::{PeerResolver, UCred, UdsConnectInfo, UdsPeer, UidTable};
+
+use tonic::{Code, Status};
+use tonic::transport::Channel;
+
+use std::os::unix::fs::MetadataExt;
+use std::path::PathBuf;
+
+//Writes identity of peer into metadata, which is echoed back by service
+fn expose_identity(headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<Status> {
+    let identity = PeerIdentity::from_extensions(extensions).expect("identity");
+    assert_eq!(identity.mechanism, Mechanism::Uds);
+    assert!(identity.extra.get::<UCred>().is_some());
+    headers.insert("x-subject", identity.subject.as_deref().unwrap_or("-").parse().unwrap());
+    headers.insert("x-roles", identity.roles.join(",").parse().unwrap());
+    None
+}
+
+//Starts echo server over unix domain socket, returning client and uid, which owns socket
+async fn spawn<R: PeerResolver + Clone + Send + Sync + 'static>(name: &str, peer: UdsPeer<R>) -> (EchoService, EchoClient<Channel>, u32) {
+    let path = std::env::temp_dir().join(format!("tonic-interceptor-{}-{}.sock", std::process::id(), name));
+    let _ = std::fs::remove_file(&path);
+    let listener = tokio::net::UnixListener::bind(&path).expect("bind");
+    let uid = std::fs::metadata(&path).expect("socket metadata").uid();
+
+    let service = EchoService::default();
+    let interceptor = InterceptorChain::new().with(peer).with(InterceptorFn {
+        on_request: expose_identity,
+        on_response: |_: Code, _: &mut http::HeaderMap, _: &http::Extensions| {},
+    });
+    let router = tonic::transport::Server::builder().layer(tonic_interceptor::interceptor(interceptor)).add_service(EchoServer::new(service.clone()));
+    tokio::spawn(router.serve_with_incoming(tokio_stream::wrappers::UnixListenerStream::new(listener)));
+
+    let channel = tonic::transport::Endpoint::from_static("http://localhost").connect_with_connector(tower::service_fn(move |_: http::Uri| {
+        let path: PathBuf = path.clone();
+        tokio::net::UnixStream::connect(path)
+    })).await.expect("connect");
+    (service, EchoClient::new(channel), uid)
+}
+
+fn request() -> tonic::Request<EchoRequest> {
+    tonic::Request::new(EchoRequest {
+        message: "hello".to_owned(),
+    })
+}
+
+#[tokio::test]
+async fn should_identify_known_peer() {
+    let (_, mut probe, uid) = spawn("probe", UdsPeer::new(|_: &UCred| None)).await;
+    let status = probe.unary(request()).await.unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+    assert_eq!(status.message(), format!("unknown peer uid {}", uid));
+
+    let table = UidTable::new().uid(uid, "local", vec!["admin"]).uid(uid.wrapping_add(1), "other", vec!["never"]);
+    let (service, mut client, _) = spawn("known", UdsPeer::new(table)).await;
+    let response = client.unary(request()).await.expect("success");
+    assert_eq!(response.metadata().get("x-subject").unwrap(), "local");
+    assert_eq!(response.metadata().get("x-roles").unwrap(), "admin");
+    assert_eq!(service.calls(), 1);
+}
+
+#[tokio::test]
+async fn should_grant_roles_of_peer_group() {
+    let resolve = |cred: &UCred| UidTable::new().uid(cred.uid(), "local", Vec::<String>::new()).gid(cred.gid(), vec!["reader", "writer"]).resolve(cred);
+    let (_, mut client, _) = spawn("group", UdsPeer::new(resolve)).await;
+    let response = client.unary(request()).await.expect("success");
+    assert_eq!(response.metadata().get("x-roles").unwrap(), "reader,writer");
+}
+
+#[test]
+fn should_handle_connections_without_credentials() {
+    let peer = UdsPeer::new(UidTable::new());
+    let mut metadata = tonic::metadata::MetadataMap::new();
+
+    //Not unix domain socket
+    assert!(peer.on_request(&mut metadata, &mut http::Extensions::new()).is_none());
+    let status = peer.clone().require_uds(true).on_request(&mut metadata, &mut http::Extensions::new()).expect("rejection");
+    assert_eq!(status.code(), Code::PermissionDenied);
+    assert_eq!(status.message(), "connection is not unix domain socket");
+
+    let mut extensions = http::Extensions::new();
+    extensions.insert(UdsConnectInfo {
+        peer_addr: None,
+        peer_cred: None,
+    });
+    let status = peer.on_request(&mut metadata, &mut extensions).expect("rejection");
+    assert_eq!(status.code(), Code::PermissionDenied);
+    assert_eq!(status.message(), "peer credentials are unavailable");
+    assert!(PeerIdentity::from_extensions(&extensions).is_none());
+}
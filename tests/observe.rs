@@ -0,0 +1,728 @@
+#![cfg(feature = "tonic011")]
+#![allow(clippy::result_large_err)]
+
+mod common;
+
+use common::{EchoClient, EchoServer, EchoService};
+use common::echo::EchoRequest;
+
+use tonic_interceptor::observe::{CallId, InFlightRegistry, MetricsFacade, MetricsSink, MethodLabels};
+use tonic_interceptor::testing::{poll_once, service_fn, with_noop_context};
+
+use tonic::{Code, Status};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use core::future::Future;
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone, Debug, PartialEq)]
+enum Kind {
+    Counter,
+    Histogram,
+    GaugeUp,
+    GaugeDown,
+}
+
+type Metric = (Kind, String, Vec<(String, String)>);
+
+#[derive(Clone, Default)]
+struct Sink(Arc<Mutex<Vec<Metric>>>);
+
+impl Sink {
+    fn push(&self, kind: Kind, name: &str, labels: &[(&str, &str)]) {
+        let labels = labels.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect();
+        self.0.lock().unwrap().push((kind, name.to_owned(), labels));
+    }
+
+    fn take(&self) -> Vec<Metric> {
+        core::mem::take(&mut *self.0.lock().unwrap())
+    }
+}
+
+impl MetricsSink for Sink {
+    fn increment_counter(&self, name: &str, labels: &[(&str, &str)]) {
+        self.push(Kind::Counter, name, labels)
+    }
+
+    fn record_histogram(&self, name: &str, value: f64, labels: &[(&str, &str)]) {
+        assert!(value >= 0.0);
+        self.push(Kind::Histogram, name, labels)
+    }
+
+    fn increment_gauge(&self, name: &str, labels: &[(&str, &str)]) {
+        self.push(Kind::GaugeUp, name, labels)
+    }
+
+    fn decrement_gauge(&self, name: &str, labels: &[(&str, &str)]) {
+        self.push(Kind::GaugeDown, name, labels)
+    }
+}
+
+fn labels(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+    pairs.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect()
+}
+
+fn expected(prefix: &str, method: &str, code: &str, extra: &[(&str, &str)]) -> Vec<Metric> {
+    let mut with_code = vec![("method", method), ("code", code)];
+    with_code.extend_from_slice(extra);
+    let mut without_code = vec![("method", method)];
+    without_code.extend_from_slice(extra);
+    vec![
+        (Kind::GaugeUp, format!("{}_in_flight", prefix), labels(&without_code)),
+        (Kind::Counter, format!("{}_handled_total", prefix), labels(&with_code)),
+        (Kind::Histogram, format!("{}_handling_seconds", prefix), labels(&with_code)),
+        (Kind::GaugeDown, format!("{}_in_flight", prefix), labels(&without_code)),
+    ]
+}
+
+fn request(path: &str) -> http::Request<()> {
+    http::Request::builder().uri(path).body(()).unwrap()
+}
+
+#[test]
+fn should_report_trailers_only_response() {
+    let sink = Sink::default();
+    let svc = service_fn(|_: http::Request<()>| {
+        Ok::<_, Status>(http::Response::builder().header("grpc-status", "7").body(tonic::body::empty_body()).unwrap())
+    });
+    let mut service = MetricsFacade::builder(sink.clone()).method("/pkg.Users/Get").build().layer(svc);
+
+    let response = poll_once(service.call(request("/pkg.Users/Get"))).expect("response");
+    assert_eq!(sink.take(), expected("grpc_server", "/pkg.Users/Get", "PERMISSION_DENIED", &[]));
+    drop(response);
+    assert!(sink.take().is_empty());
+}
+
+#[test]
+fn should_report_cancelled_calls() {
+    let sink = Sink::default();
+    let svc = service_fn(|_: http::Request<()>| Ok::<_, Status>(http::Response::new(tonic::body::empty_body())));
+    let mut service = MetricsFacade::builder(sink.clone()).method("/pkg.Users/Get").build().layer(svc);
+
+    //Response body is dropped without trailers
+    drop(poll_once(service.call(request("/pkg.Users/Get"))));
+    assert_eq!(sink.take(), expected("grpc_server", "/pkg.Users/Get", "CANCELLED", &[]));
+
+    //Future is dropped without polling
+    drop(service.call(request("/pkg.Users/Get")));
+    assert_eq!(sink.take(), expected("grpc_server", "/pkg.Users/Get", "CANCELLED", &[]));
+
+    let mut service = MetricsFacade::builder(sink.clone()).method("/pkg.Users/Get").build().layer(service_fn(|_: http::Request<()>| Err::<http::Response<()>, _>(Status::internal("boom"))));
+    poll_once(service.call(request("/pkg.Users/Get"))).expect_err("error");
+    assert_eq!(sink.take(), expected("grpc_server", "/pkg.Users/Get", "UNKNOWN", &[]));
+}
+
+#[test]
+fn should_cap_method_cardinality() {
+    let methods = MethodLabels::new(2).seed("/pkg.Users/Get");
+    assert_eq!(&*methods.label("/pkg.Users/Get"), "/pkg.Users/Get");
+    assert_eq!(&*methods.label("/pkg.Users/List"), "other");
+    //Unknown methods never become known
+    assert_eq!(&*methods.complete("/pkg.Users/Bogus", Code::Unimplemented), "other");
+    assert_eq!(&*methods.label("/pkg.Users/Bogus"), "other");
+    assert_eq!(&*methods.complete("/pkg.Users/List", Code::NotFound), "/pkg.Users/List");
+    assert_eq!(&*methods.label("/pkg.Users/List"), "/pkg.Users/List");
+    assert_eq!(&*methods.complete("/pkg.Users/Delete", Code::Ok), "other");
+    assert_eq!(&*methods.label("/health"), "unknown");
+    assert_eq!(&*methods.complete("/health", Code::Ok), "unknown");
+    assert_eq!(&*methods.label("/pkg.Users/Get/extra"), "unknown");
+    assert_eq!(&*methods.label("//Get"), "unknown");
+
+    let sink = Sink::default();
+    let svc = service_fn(|request: http::Request<()>| {
+        let code = match request.uri().path().starts_with("/x/") {
+            true => "12",
+            false => "0",
+        };
+        Ok::<_, Status>(http::Response::builder().header("grpc-status", code).body(()).unwrap())
+    });
+    let mut service = MetricsFacade::builder(sink.clone()).max_methods(1).build().layer(svc);
+    //Calls of unknown services do not take room of real methods
+    for idx in 0..10 {
+        poll_once(service.call(request(&format!("/x/{}", idx)))).expect("response");
+        assert_eq!(sink.take(), expected("grpc_server", "other", "UNIMPLEMENTED", &[]));
+    }
+    //Method is known once its first call completes
+    poll_once(service.call(request("/pkg.Users/Get"))).expect("response");
+    let mut metrics = expected("grpc_server", "other", "OK", &[]);
+    metrics[1].2 = labels(&[("method", "/pkg.Users/Get"), ("code", "OK")]);
+    metrics[2].2 = labels(&[("method", "/pkg.Users/Get"), ("code", "OK")]);
+    assert_eq!(sink.take(), metrics);
+    poll_once(service.call(request("/pkg.Users/Get"))).expect("response");
+    poll_once(service.call(request("/pkg.Users/List"))).expect("response");
+    let mut metrics = expected("grpc_server", "/pkg.Users/Get", "OK", &[]);
+    metrics.extend(expected("grpc_server", "other", "OK", &[]));
+    assert_eq!(sink.take(), metrics);
+}
+
+#[tokio::test]
+async fn should_report_status_from_trailers() {
+    let sink = Sink::default();
+    let layer = MetricsFacade::builder(sink.clone()).prefix("echo").label("service", "test").methods(["/test.Echo/Unary", "/test.Echo/Stream"]).build();
+
+    let service = EchoService::default();
+    let (incoming, addr) = common::listen().await;
+    let server = tonic::transport::Server::builder().layer(layer).add_service(EchoServer::new(service.clone()));
+    tokio::spawn(server.serve_with_incoming(incoming));
+    let mut client = EchoClient::new(common::connect(addr).await);
+
+    let response = client.unary(EchoRequest { message: "hello".to_owned() }).await.expect("success");
+    assert_eq!(response.get_ref().message, "hello");
+    assert_eq!(sink.take(), expected("echo", "/test.Echo/Unary", "OK", &[("service", "test")]));
+
+    let mut stream = client.stream(EchoRequest { message: "a error:5".to_owned() }).await.expect("success").into_inner();
+    let status = loop {
+        match stream.message().await {
+            Ok(Some(_)) => continue,
+            Ok(None) => panic!("stream should fail"),
+            Err(status) => break status,
+        }
+    };
+    assert_eq!(status.code(), Code::NotFound);
+    assert_eq!(sink.take(), expected("echo", "/test.Echo/Stream", "NOT_FOUND", &[("service", "test")]));
+}
+
+fn request_with_id(path: &str, id: &str) -> http::Request<()> {
+    http::Request::builder().uri(path).header("x-request-id", id).body(()).unwrap()
+}
+
+#[test]
+fn should_register_in_flight_calls() {
+    use tonic_interceptor::{InterceptorChain, InterceptorFn};
+    use tonic_interceptor::identity::{Mechanism, PeerIdentity};
+
+    let registry = InFlightRegistry::new();
+    let handle = registry.handle();
+    let authenticate = InterceptorFn {
+        on_request: |_: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions| {
+            extensions.insert(PeerIdentity::new(Mechanism::Bearer).subject("alice"));
+            None
+        },
+        on_response: |_: Code, _: &mut http::HeaderMap, _: &http::Extensions| {},
+    };
+    let svc = service_fn(|req: http::Request<()>| {
+        assert!(req.extensions().get::<CallId>().is_some());
+        let mut response = http::Response::new(tonic::body::empty_body());
+        if req.uri().path().ends_with("Fail") {
+            response.headers_mut().insert("grpc-status", http::HeaderValue::from_static("13"));
+        }
+        Ok::<_, Status>(response)
+    });
+    let mut service = tonic_interceptor::InterceptorService::new(InterceptorChain::new().with(authenticate).with(registry.clone()), registry.layer().layer(svc).into_inner());
+
+    let first = poll_once(service.call(request_with_id("/pkg.Users/Get", "req-1"))).expect("response");
+    let second = poll_once(service.call(request("/pkg.Users/Get"))).expect("response");
+    let third = poll_once(service.call(request("/pkg.Users/List"))).expect("response");
+    let calls = handle.snapshot();
+    assert_eq!(calls.len(), 3);
+    assert_eq!(calls[0].method, "/pkg.Users/Get");
+    assert_eq!(calls[0].peer.as_deref(), Some("alice"));
+    assert_eq!(calls[0].request_id.as_deref(), Some("req-1"));
+    assert_eq!(calls[1].request_id, None);
+    assert!(calls[0].id.0 < calls[1].id.0 && calls[1].id.0 < calls[2].id.0);
+    assert!(calls[0].started <= calls[2].started);
+    assert_eq!(handle.counts(), [("/pkg.Users/Get".to_owned(), 2), ("/pkg.Users/List".to_owned(), 1)]);
+
+    //Call completes along with its response
+    drop(second);
+    assert_eq!(handle.counts(), [("/pkg.Users/Get".to_owned(), 1), ("/pkg.Users/List".to_owned(), 1)]);
+    drop((first, third));
+    assert!(handle.is_empty());
+
+    //Trailers-only response is complete right away
+    let response = poll_once(service.call(request("/pkg.Users/Fail"))).expect("response");
+    assert!(handle.is_empty());
+    drop(response);
+}
+
+#[test]
+fn should_unregister_calls_on_every_exit_path() {
+    use tonic_interceptor::{InterceptorChain, InterceptorFn};
+
+    let registry = InFlightRegistry::new().peer_with(|extensions| extensions.get::<&'static str>().map(|peer| peer.to_string()));
+    let handle = registry.handle();
+    let reject = |path: &'static str| InterceptorFn {
+        on_request: move |headers: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions| match headers.get("x-reject").map(|value| value == path) {
+            Some(true) => Some(Status::permission_denied("rejected")),
+            _ => None,
+        },
+        on_response: |_: Code, _: &mut http::HeaderMap, _: &http::Extensions| {},
+    };
+    let svc = service_fn(|req: http::Request<()>| match req.uri().path() {
+        "/pkg.Users/Error" => Err(Status::internal("boom")),
+        _ => Ok(http::Response::new(tonic::body::empty_body())),
+    });
+    let chain = InterceptorChain::new().with(reject("before")).with(registry.clone()).with(reject("after"));
+    let mut service = tonic_interceptor::InterceptorService::new(chain, registry.layer().layer(svc).into_inner());
+
+    poll_once(service.call(request("/pkg.Users/Error"))).expect_err("error");
+    assert!(handle.is_empty());
+
+    for reject in ["before", "after"].iter() {
+        let request = http::Request::builder().uri("/pkg.Users/Get").header("x-reject", *reject).body(()).unwrap();
+        let response = poll_once(service.call(request)).expect("response");
+        assert_eq!(response.headers().get("grpc-status").unwrap(), "7");
+        assert!(handle.is_empty());
+    }
+
+    //Peer is taken from custom extractor
+    let mut request = request("/pkg.Users/Get");
+    request.extensions_mut().insert("10.0.0.1:5000");
+    let response = poll_once(service.call(request)).expect("response");
+    assert_eq!(handle.snapshot()[0].peer.as_deref(), Some("10.0.0.1:5000"));
+    drop(response);
+    assert!(handle.is_empty());
+}
+
+#[test]
+fn should_not_leak_cancelled_calls() {
+    let registry = InFlightRegistry::new();
+    let handle = registry.handle();
+    let svc = tower::service_fn(|_: http::Request<()>| core::future::pending::<Result<http::Response<()>, Status>>());
+    let mut service = registry.layer().layer(svc);
+
+    let mut calls = Vec::new();
+    for idx in 0..1000 {
+        let mut call = Box::pin(service.call(request(if idx % 2 == 0 { "/pkg.Users/Get" } else { "/pkg.Users/List" })));
+        assert!(with_noop_context(|ctx| call.as_mut().poll(ctx)).is_pending());
+        calls.push(call);
+    }
+    assert_eq!(handle.len(), 1000);
+    assert_eq!(handle.counts(), [("/pkg.Users/Get".to_owned(), 500), ("/pkg.Users/List".to_owned(), 500)]);
+
+    calls.truncate(10);
+    assert_eq!(handle.len(), 10);
+    drop(calls);
+    assert!(handle.is_empty());
+}
+
+#[test]
+fn should_register_calls_concurrently() {
+    let registry = InFlightRegistry::new();
+    let handle = registry.handle();
+    let svc = service_fn(|_: http::Request<()>| Ok::<_, Status>(http::Response::new(tonic::body::empty_body())));
+    let service = registry.layer().layer(svc);
+
+    let threads: Vec<_> = (0..8).map(|thread| {
+        let mut service = service.clone();
+        let handle = handle.clone();
+        std::thread::spawn(move || {
+            let mut responses = Vec::new();
+            for idx in 0..500 {
+                responses.push(poll_once(service.call(request_with_id("/pkg.Users/Get", &format!("{}-{}", thread, idx)))).expect("response"));
+                if idx % 3 == 0 {
+                    responses.remove(0);
+                }
+                assert!(handle.len() >= responses.len());
+            }
+        })
+    }).collect();
+    for thread in threads {
+        thread.join().expect("thread");
+    }
+    assert!(handle.is_empty());
+    assert!(handle.counts().is_empty());
+}
+
+#[derive(Clone)]
+struct RejectAll;
+
+impl tonic_interceptor::Interceptor for RejectAll {
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<Status> {
+        let attempt = headers.get("x-attempt").and_then(|value| value.to_str().ok()).unwrap_or("").to_owned();
+        Some(Status::permission_denied(format!("rejected {}", attempt)))
+    }
+
+    fn on_response(&self, _: Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
+
+fn reject<I: tonic_interceptor::Interceptor>(interceptor: &I, path: &str, attempt: usize) -> Option<Status> {
+    let uri = path.parse::<http::Uri>().unwrap();
+    let mut headers = tonic::metadata::MetadataMap::new();
+    headers.insert("x-attempt", attempt.to_string().parse().unwrap());
+    headers.insert("authorization", "Bearer secret".parse().unwrap());
+    interceptor.on_request_with_uri(&uri, &mut headers, &mut http::Extensions::new())
+}
+
+#[test]
+fn should_keep_most_recent_rejections() {
+    use tonic_interceptor::observe::RejectionLog;
+
+    let log = RejectionLog::new(RejectAll, 3);
+    let handle = log.handle();
+    assert_eq!(handle.capacity(), 3);
+    assert!(handle.recent().is_empty());
+
+    for attempt in 0..5 {
+        let status = reject(&log, "/pkg.Users/Get", attempt).expect("to reject");
+        assert_eq!(status.message(), format!("rejected {}", attempt));
+    }
+
+    let recent = handle.recent();
+    assert_eq!(handle.total(), 5);
+    assert_eq!(recent.len(), 3);
+    for (record, attempt) in recent.iter().zip(2..) {
+        assert_eq!(record.method, "/pkg.Users/Get");
+        assert_eq!(record.code, Code::PermissionDenied);
+        assert_eq!(record.message, format!("rejected {}", attempt));
+        assert_eq!(record.peer, None);
+    }
+    assert!(recent[0].time <= recent[2].time);
+    assert!(recent[0].metadata.contains(&("authorization".to_owned(), tonic_interceptor::redact::MASK.to_owned())));
+    assert!(recent[0].metadata.contains(&("x-attempt".to_owned(), "2".to_owned())));
+}
+
+#[test]
+fn should_not_record_accepted_requests() {
+    use tonic_interceptor::InterceptorFn;
+    use tonic_interceptor::identity::{Mechanism, PeerIdentity};
+    use tonic_interceptor::observe::RejectionLog;
+    use tonic_interceptor::redact::Redactor;
+
+    let accept = InterceptorFn {
+        on_request: |headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions| {
+            extensions.insert(PeerIdentity::new(Mechanism::Bearer).subject("alice"));
+            match headers.get("x-attempt").map(|value| value == "1") {
+                Some(true) => Some(Status::unauthenticated("bad attempt")),
+                _ => None,
+            }
+        },
+        on_response: |_: Code, _: &mut http::HeaderMap, _: &http::Extensions| {},
+    };
+    let log = RejectionLog::new(accept, 4).redactor(Redactor::new().key("x-attempt"));
+    let handle = log.handle();
+
+    assert!(reject(&log, "/pkg.Users/Get", 0).is_none());
+    assert!(reject(&log, "/pkg.Users/List", 1).is_some());
+    assert!(reject(&log, "/pkg.Users/Get", 2).is_none());
+
+    let recent = handle.recent();
+    assert_eq!(recent.len(), 1);
+    assert_eq!(recent[0].method, "/pkg.Users/List");
+    assert_eq!(recent[0].code, Code::Unauthenticated);
+    assert_eq!(recent[0].peer.as_deref(), Some("alice"));
+    assert!(recent[0].metadata.contains(&("x-attempt".to_owned(), tonic_interceptor::redact::MASK.to_owned())));
+    assert!(recent[0].metadata.contains(&("authorization".to_owned(), "Bearer secret".to_owned())));
+}
+
+#[test]
+fn should_record_rejections_from_concurrent_writers() {
+    use tonic_interceptor::observe::RejectionLog;
+
+    const THREADS: usize = 8;
+    const PER_THREAD: usize = 50;
+
+    let log = RejectionLog::new(RejectAll, 16).peer_with(|_: &http::Extensions| Some("worker".to_owned()));
+    let handle = log.handle();
+    let workers: Vec<_> = (0..THREADS).map(|thread| {
+        let log = log.clone();
+        std::thread::spawn(move || {
+            for idx in 0..PER_THREAD {
+                reject(&log, "/pkg.Users/Get", thread * PER_THREAD + idx);
+            }
+        })
+    }).collect();
+    for worker in workers {
+        worker.join().expect("not to panic");
+    }
+
+    let recent = handle.recent();
+    assert_eq!(handle.total(), (THREADS * PER_THREAD) as u64);
+    assert_eq!(recent.len(), 16);
+    let mut messages: Vec<_> = recent.iter().map(|record| record.message.clone()).collect();
+    messages.sort();
+    messages.dedup();
+    assert_eq!(messages.len(), 16);
+    assert!(recent.iter().all(|record| record.peer.as_deref() == Some("worker")));
+}
+
+#[test]
+fn should_label_attempts() {
+    use tonic_interceptor::policy::{PreviousAttempts, RetryAttempts};
+
+    fn expected_attempt(method: &str, attempt: &str) -> Vec<Metric> {
+        let with_code = [("method", method), ("attempt", attempt), ("code", "OK")];
+        let without_code = [("method", method), ("attempt", attempt)];
+        vec![
+            (Kind::GaugeUp, "grpc_server_in_flight".to_owned(), labels(&without_code)),
+            (Kind::Counter, "grpc_server_handled_total".to_owned(), labels(&with_code)),
+            (Kind::Histogram, "grpc_server_handling_seconds".to_owned(), labels(&with_code)),
+            (Kind::GaugeDown, "grpc_server_in_flight".to_owned(), labels(&without_code)),
+        ]
+    }
+
+    let sink = Sink::default();
+    let svc = service_fn(|_: http::Request<()>| {
+        Ok::<_, Status>(http::Response::builder().header("grpc-status", "0").body(()).unwrap())
+    });
+    let mut service = MetricsFacade::builder(sink.clone()).method("/pkg.Users/Get").attempt_label(true).build().layer(svc);
+    let attempt = |value: &'static str| http::Request::builder().uri("/pkg.Users/Get").header("grpc-previous-rpc-attempts", value).body(()).unwrap();
+
+    poll_once(service.call(request("/pkg.Users/Get"))).expect("response");
+    assert_eq!(sink.take(), expected_attempt("/pkg.Users/Get", "0"));
+    poll_once(service.call(attempt("1"))).expect("response");
+    assert_eq!(sink.take(), expected_attempt("/pkg.Users/Get", "1"));
+    poll_once(service.call(attempt("5"))).expect("response");
+    assert_eq!(sink.take(), expected_attempt("/pkg.Users/Get", "2+"));
+    poll_once(service.call(attempt("garbage"))).expect("response");
+    assert_eq!(sink.take(), expected_attempt("/pkg.Users/Get", "0"));
+
+    //Extension takes precedence over header
+    let mut request = attempt("0");
+    request.extensions_mut().insert(PreviousAttempts(2));
+    poll_once(service.call(request)).expect("response");
+    assert_eq!(sink.take(), expected_attempt("/pkg.Users/Get", "2+"));
+
+    //Extension inserted by interceptor running within the layer
+    let svc = service_fn(|_: http::Request<()>| {
+        Ok::<_, Status>(http::Response::builder().header("grpc-status", "0").body(tonic::body::empty_body()).unwrap())
+    });
+    let mut service = tonic_interceptor::InterceptorService::new(RetryAttempts::new(), MetricsFacade::builder(sink.clone()).method("/pkg.Users/Get").attempt_label(true).build().layer(svc));
+    poll_once(service.call(attempt("1"))).expect("response");
+    assert_eq!(sink.take(), expected_attempt("/pkg.Users/Get", "1"));
+}
+
+#[cfg(feature = "metrics")]
+#[test]
+fn should_emit_through_metrics_recorder() {
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+    use metrics_util::MetricKind;
+    use tonic_interceptor::observe::GlobalMetrics;
+
+    let recorder = DebuggingRecorder::new();
+    let snapshotter = recorder.snapshotter();
+    let svc = service_fn(|_: http::Request<()>| {
+        Ok::<_, Status>(http::Response::builder().header("grpc-status", "7").body(()).unwrap())
+    });
+    let mut service = MetricsFacade::builder(GlobalMetrics::new()).prefix("users").label("service", "test").method("/pkg.Users/Get").build().layer(svc);
+    metrics::with_local_recorder(&recorder, || {
+        poll_once(service.call(request("/pkg.Users/Get"))).expect("response");
+        poll_once(service.call(request("/pkg.Users/Get"))).expect("response");
+    });
+
+    let mut metrics: Vec<_> = snapshotter.snapshot().into_vec().into_iter().map(|(key, _, _, value)| {
+        let labels: Vec<_> = key.key().labels().map(|label| (label.key().to_owned(), label.value().to_owned())).collect();
+        (key.kind(), key.key().name().to_owned(), labels, value)
+    }).collect();
+    metrics.sort_by(|left, right| left.1.cmp(&right.1));
+    assert_eq!(metrics.len(), 3);
+
+    let with_code = labels(&[("method", "/pkg.Users/Get"), ("code", "PERMISSION_DENIED"), ("service", "test")]);
+    assert_eq!((metrics[0].0, metrics[0].1.as_str(), &metrics[0].2), (MetricKind::Counter, "users_handled_total", &with_code));
+    assert_eq!(metrics[0].3, DebugValue::Counter(2));
+    assert_eq!((metrics[1].0, metrics[1].1.as_str(), &metrics[1].2), (MetricKind::Histogram, "users_handling_seconds", &with_code));
+    match &metrics[1].3 {
+        DebugValue::Histogram(values) => assert_eq!(values.len(), 2),
+        value => panic!("unexpected value {:?}", value),
+    }
+    let without_code = labels(&[("method", "/pkg.Users/Get"), ("service", "test")]);
+    assert_eq!((metrics[2].0, metrics[2].1.as_str(), &metrics[2].2), (MetricKind::Gauge, "users_in_flight", &without_code));
+    assert_eq!(metrics[2].3, DebugValue::Gauge(0.0.into()));
+}
+
+#[cfg(feature = "metrics")]
+#[test]
+fn should_register_global_metric_once() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tonic_interceptor::observe::GlobalMetrics;
+
+    #[derive(Default)]
+    struct Registrations(AtomicUsize);
+
+    impl metrics::Recorder for Registrations {
+        fn describe_counter(&self, _: metrics::KeyName, _: Option<metrics::Unit>, _: metrics::SharedString) {}
+        fn describe_gauge(&self, _: metrics::KeyName, _: Option<metrics::Unit>, _: metrics::SharedString) {}
+        fn describe_histogram(&self, _: metrics::KeyName, _: Option<metrics::Unit>, _: metrics::SharedString) {}
+
+        fn register_counter(&self, _: &metrics::Key, _: &metrics::Metadata<'_>) -> metrics::Counter {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            metrics::Counter::noop()
+        }
+
+        fn register_gauge(&self, _: &metrics::Key, _: &metrics::Metadata<'_>) -> metrics::Gauge {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            metrics::Gauge::noop()
+        }
+
+        fn register_histogram(&self, _: &metrics::Key, _: &metrics::Metadata<'_>) -> metrics::Histogram {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            metrics::Histogram::noop()
+        }
+    }
+
+    let recorder = Registrations::default();
+    let sink = GlobalMetrics::new();
+    metrics::with_local_recorder(&recorder, || {
+        for _ in 0..3 {
+            sink.increment_counter("calls", &[("method", "/pkg.Users/Get"), ("code", "OK")]);
+            sink.increment_gauge("calls", &[("method", "/pkg.Users/Get"), ("code", "OK")]);
+            sink.decrement_gauge("calls", &[("method", "/pkg.Users/Get"), ("code", "OK")]);
+        }
+        assert_eq!(recorder.0.load(Ordering::SeqCst), 2);
+
+        //Metric is distinguished by name, label keys and values
+        sink.increment_counter("calls", &[("method", "/pkg.Users/Get"), ("code", "NOT_FOUND")]);
+        sink.increment_counter("calls", &[("method", "/pkg.Users/Get"), ("status", "OK")]);
+        sink.increment_counter("calls", &[("method", "/pkg.Users/Get")]);
+        sink.increment_counter("other_calls", &[("method", "/pkg.Users/Get"), ("code", "OK")]);
+        assert_eq!(recorder.0.load(Ordering::SeqCst), 6);
+
+        //Clones share registered metrics
+        sink.clone().increment_counter("calls", &[("method", "/pkg.Users/Get"), ("code", "OK")]);
+        sink.record_histogram("calls", 1.0, &[("method", "/pkg.Users/Get"), ("code", "OK")]);
+        assert_eq!(recorder.0.load(Ordering::SeqCst), 7);
+    });
+}
+
+#[cfg(feature = "prometheus")]
+#[test]
+fn should_render_prometheus_metrics() {
+    use tonic_interceptor::observe::PrometheusMetrics;
+
+    let metrics = PrometheusMetrics::with_buckets(&[1.0, 0.5, 10.0, f64::INFINITY]);
+    metrics.increment_counter("calls", &[]);
+    metrics.increment_counter("calls", &[]);
+    metrics.increment_counter("calls", &[("service", "a\"b\\c\nd")]);
+    metrics.record_histogram("latency", 0.5, &[]);
+    metrics.record_histogram("latency", 0.7, &[]);
+    metrics.record_histogram("latency", 20.0, &[]);
+    metrics.increment_gauge("active", &[("pool", "main")]);
+    metrics.increment_gauge("active", &[("pool", "main")]);
+    metrics.decrement_gauge("active", &[("pool", "main")]);
+    metrics.decrement_gauge("active", &[("pool", "backup")]);
+    assert_eq!(metrics.render(), [
+        "# TYPE active gauge",
+        "active{pool=\"backup\"} -1",
+        "active{pool=\"main\"} 1",
+        "# TYPE calls counter",
+        "calls 2",
+        "calls{service=\"a\\\"b\\\\c\\nd\"} 1",
+        "# TYPE latency histogram",
+        "latency_bucket{le=\"0.5\"} 1",
+        "latency_bucket{le=\"1\"} 2",
+        "latency_bucket{le=\"10\"} 2",
+        "latency_bucket{le=\"+Inf\"} 3",
+        "latency_sum 21.2",
+        "latency_count 3",
+        "",
+    ].join("\n"));
+
+    //Attempt bucket is label of every metric
+    let metrics = PrometheusMetrics::new();
+    let svc = service_fn(|_: http::Request<()>| {
+        Ok::<_, Status>(http::Response::builder().header("grpc-status", "0").body(()).unwrap())
+    });
+    let mut service = MetricsFacade::builder(metrics.clone()).method("/pkg.Users/Get").attempt_label(true).build().layer(svc);
+    poll_once(service.call(request("/pkg.Users/Get"))).expect("response");
+    let retry = http::Request::builder().uri("/pkg.Users/Get").header("grpc-previous-rpc-attempts", "3").body(()).unwrap();
+    poll_once(service.call(retry)).expect("response");
+
+    let rendered = metrics.render();
+    let lines: Vec<_> = rendered.lines().filter(|line| line.starts_with("# TYPE") || line.contains("_total{") || line.contains("_count{") || line.contains("_in_flight{")).collect();
+    assert_eq!(lines, [
+        "# TYPE grpc_server_handled_total counter",
+        "grpc_server_handled_total{method=\"/pkg.Users/Get\",attempt=\"0\",code=\"OK\"} 1",
+        "grpc_server_handled_total{method=\"/pkg.Users/Get\",attempt=\"2+\",code=\"OK\"} 1",
+        "# TYPE grpc_server_handling_seconds histogram",
+        "grpc_server_handling_seconds_count{method=\"/pkg.Users/Get\",attempt=\"0\",code=\"OK\"} 1",
+        "grpc_server_handling_seconds_count{method=\"/pkg.Users/Get\",attempt=\"2+\",code=\"OK\"} 1",
+        "# TYPE grpc_server_in_flight gauge",
+        "grpc_server_in_flight{method=\"/pkg.Users/Get\",attempt=\"0\"} 0",
+        "grpc_server_in_flight{method=\"/pkg.Users/Get\",attempt=\"2+\"} 0",
+    ]);
+    assert_eq!(rendered.lines().filter(|line| line.starts_with("grpc_server_handling_seconds_bucket{")).count(), 2 * 12);
+}
+
+#[cfg(feature = "opentelemetry")]
+#[test]
+fn should_record_into_opentelemetry_instruments() {
+    use opentelemetry::KeyValue;
+    use opentelemetry::metrics::{Counter, Histogram, InstrumentProvider, Meter, SyncCounter, SyncHistogram, SyncUpDownCounter, Unit, UpDownCounter};
+    use tonic_interceptor::observe::OtelMetrics;
+    use std::borrow::Cow;
+
+    type Records = Arc<Mutex<Vec<(String, String, Vec<(String, String)>)>>>;
+
+    //Instrument, which records name, value and attributes
+    struct Instrument(String, Records);
+
+    impl Instrument {
+        fn record(&self, value: String, attributes: &[KeyValue]) {
+            let attributes = attributes.iter().map(|attribute| (attribute.key.to_string(), attribute.value.to_string())).collect();
+            self.1.lock().unwrap().push((self.0.clone(), value, attributes));
+        }
+    }
+
+    impl SyncCounter<u64> for Instrument {
+        fn add(&self, value: u64, attributes: &[KeyValue]) {
+            self.record(format!("+{}", value), attributes)
+        }
+    }
+
+    impl SyncUpDownCounter<i64> for Instrument {
+        fn add(&self, value: i64, attributes: &[KeyValue]) {
+            self.record(format!("{:+}", value), attributes)
+        }
+    }
+
+    impl SyncHistogram<f64> for Instrument {
+        fn record(&self, _: f64, attributes: &[KeyValue]) {
+            Instrument::record(self, "histogram".to_owned(), attributes)
+        }
+    }
+
+    #[derive(Default)]
+    struct Provider {
+        created: Mutex<Vec<String>>,
+        records: Records,
+    }
+
+    impl Provider {
+        fn instrument(&self, name: Cow<'static, str>) -> Arc<Instrument> {
+            self.created.lock().unwrap().push(name.to_string());
+            Arc::new(Instrument(name.into_owned(), self.records.clone()))
+        }
+    }
+
+    impl InstrumentProvider for Provider {
+        fn u64_counter(&self, name: Cow<'static, str>, _: Option<Cow<'static, str>>, _: Option<Unit>) -> opentelemetry::metrics::Result<Counter<u64>> {
+            Ok(Counter::new(self.instrument(name)))
+        }
+
+        fn i64_up_down_counter(&self, name: Cow<'static, str>, _: Option<Cow<'static, str>>, _: Option<Unit>) -> opentelemetry::metrics::Result<UpDownCounter<i64>> {
+            Ok(UpDownCounter::new(self.instrument(name)))
+        }
+
+        fn f64_histogram(&self, name: Cow<'static, str>, _: Option<Cow<'static, str>>, _: Option<Unit>) -> opentelemetry::metrics::Result<Histogram<f64>> {
+            Ok(Histogram::new(self.instrument(name)))
+        }
+
+        fn register_callback(&self, _: &[Arc<dyn std::any::Any>], _: Box<dyn Fn(&dyn opentelemetry::metrics::Observer) + Send + Sync>) -> opentelemetry::metrics::Result<Box<dyn opentelemetry::metrics::CallbackRegistration>> {
+            Err(opentelemetry::metrics::MetricsError::Other("callbacks are not supported".to_owned()))
+        }
+    }
+
+    let provider = Arc::new(Provider::default());
+    let metrics = OtelMetrics::new(Meter::new(provider.clone()));
+    let svc = service_fn(|_: http::Request<()>| {
+        Ok::<_, Status>(http::Response::builder().header("grpc-status", "5").body(()).unwrap())
+    });
+    let mut service = MetricsFacade::builder(metrics).label("service", "users").method("/pkg.Users/Get").attempt_label(true).build().layer(svc);
+    let retry = || http::Request::builder().uri("/pkg.Users/Get").header("grpc-previous-rpc-attempts", "1").body(()).unwrap();
+    poll_once(service.call(retry())).expect("response");
+    poll_once(service.call(retry())).expect("response");
+
+    let with_code = labels(&[("method", "/pkg.Users/Get"), ("attempt", "1"), ("code", "NOT_FOUND"), ("service", "users")]);
+    let without_code = labels(&[("method", "/pkg.Users/Get"), ("attempt", "1"), ("service", "users")]);
+    let call = vec![
+        ("grpc_server_in_flight".to_owned(), "+1".to_owned(), without_code.clone()),
+        ("grpc_server_handled_total".to_owned(), "+1".to_owned(), with_code.clone()),
+        ("grpc_server_handling_seconds".to_owned(), "histogram".to_owned(), with_code),
+        ("grpc_server_in_flight".to_owned(), "-1".to_owned(), without_code),
+    ];
+    assert_eq!(*provider.records.lock().unwrap(), [call.clone(), call].concat());
+    //Instruments are created once per metric
+    assert_eq!(*provider.created.lock().unwrap(), ["grpc_server_in_flight", "grpc_server_handled_total", "grpc_server_handling_seconds"]);
+}
@@ -0,0 +1,55 @@
+#![cfg(feature = "tonic011")]
+#![allow(clippy::result_large_err)]
+
+use tonic_interceptor::{InterceptorFn, OnResponse};
+
+use tonic::Status;
+use tower::{ServiceBuilder, ServiceExt};
+
+use core::future;
+
+fn service() -> impl tower_service::Service<http::Request<()>, Response = http::Response<()>, Error = Status, Future = impl Send> + Clone {
+    let request_only = InterceptorFn {
+        on_request: |headers: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions| match headers.contains_key("x-reject") {
+            true => Some(Status::permission_denied("rejected")),
+            false => {
+                headers.insert("x-user", "admin".parse().unwrap());
+                None
+            }
+        },
+        on_response: |_: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions| {
+        }
+    };
+    let response_only = tonic_interceptor::on_response(|status: tonic::Code, headers: &mut http::HeaderMap, _: &http::Extensions| {
+        headers.insert("x-status", http::HeaderValue::from(status as i32));
+    });
+
+    let svc = tower::service_fn(|request: http::Request<()>| {
+        assert_eq!(request.headers().get("x-user").unwrap(), "admin");
+        let response = http::Response::builder().header("grpc-status", "0").body(()).unwrap();
+        future::ready(Ok::<_, Status>(response))
+    });
+    ServiceBuilder::new().layer(tonic_interceptor::interceptor(response_only)).layer(tonic_interceptor::interceptor(request_only)).service(svc)
+}
+
+#[test]
+fn should_be_clone() {
+    let name = "x-name".to_owned();
+    let interceptor = OnResponse(move |_: tonic::Code, headers: &mut http::HeaderMap, _: &http::Extensions| {
+        headers.remove(name.as_str());
+    });
+    let _ = interceptor.clone();
+    assert_eq!(format!("{:?}", interceptor), "OnResponse { .. }");
+}
+
+#[tokio::test]
+async fn should_compose_with_request_only_interceptor() {
+    let response = service().oneshot(http::Request::new(())).await.expect("success");
+    assert_eq!(response.headers().get("x-status").unwrap(), "0");
+
+    //Rejection of inner interceptor is a response for outer one
+    let request = http::Request::builder().header("x-reject", "1").body(()).unwrap();
+    let response = service().oneshot(request).await.expect("success");
+    assert_eq!(response.headers().get("grpc-status").unwrap(), "7");
+    assert_eq!(response.headers().get("x-status").unwrap(), "7");
+}
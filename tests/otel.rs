@@ -0,0 +1,137 @@
+#![cfg(all(feature = "tonic011", feature = "opentelemetry"))]
+
+use tonic_interceptor::InterceptorService;
+use tonic_interceptor::client::{ClientInterceptor, ClientInterceptorService, InjectContext};
+use tonic_interceptor::otel::{ExtractContext, GRPC_TRACE_BIN, encode_trace_bin, decode_trace_bin};
+
+use opentelemetry::Context;
+use opentelemetry::trace::{SpanContext, TraceContextExt, TraceId, SpanId, TraceFlags, TraceState};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use tower::ServiceExt;
+
+use core::convert::Infallible;
+use std::sync::{Arc, Mutex};
+
+fn span_context() -> SpanContext {
+    let state = "vendor=value".parse::<TraceState>().expect("valid trace state");
+    SpanContext::new(TraceId::from(0x4bf92f3577b34da6a3ce929d0e0e4736), SpanId::from(0x00f067aa0ba902b7), TraceFlags::SAMPLED, true, state)
+}
+
+fn context() -> Context {
+    Context::new().with_remote_span_context(span_context())
+}
+
+fn inject(request: &mut http::Request<()>) -> tonic::metadata::MetadataMap {
+    let mut metadata = tonic::metadata::MetadataMap::from_headers(core::mem::take(request.headers_mut()));
+    InjectContext::with_propagator(TraceContextPropagator::new()).on_request(&mut metadata, request.extensions_mut());
+    metadata
+}
+
+//Client interceptor calling server interceptor in-process
+async fn round_trip(request: http::Request<()>) -> (http::HeaderMap, Option<Context>) {
+    let received = Arc::new(Mutex::new(None));
+    let service = {
+        let received = received.clone();
+        tower::service_fn(move |request: http::Request<()>| {
+            *received.lock().unwrap() = Some((request.headers().clone(), request.extensions().get::<Context>().cloned()));
+            async move {
+                Ok::<_, Infallible>(http::Response::new(tonic::body::empty_body()))
+            }
+        })
+    };
+    let service = InterceptorService::new(ExtractContext::with_propagator(TraceContextPropagator::new()), service);
+    let service = ClientInterceptorService::new(InjectContext::with_propagator(TraceContextPropagator::new()), service);
+    service.oneshot(request).await.expect("success");
+
+    let result = received.lock().unwrap().take();
+    result.expect("to receive request")
+}
+
+fn assert_same_span(context: &Context, expected: &SpanContext) {
+    let span = context.span();
+    let span = span.span_context();
+    assert_eq!(span.trace_id(), expected.trace_id());
+    assert_eq!(span.span_id(), expected.span_id());
+    assert_eq!(span.trace_flags(), expected.trace_flags());
+    assert!(span.is_remote());
+}
+
+#[tokio::test]
+async fn should_round_trip_explicit_context() {
+    let mut request = http::Request::new(());
+    request.extensions_mut().insert(context());
+
+    let (headers, received) = round_trip(request).await;
+    assert_eq!(headers.get("traceparent").expect("traceparent"), "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01");
+    assert_eq!(headers.get("tracestate").expect("tracestate"), "vendor=value");
+    assert!(headers.contains_key(GRPC_TRACE_BIN));
+
+    let received = received.expect("context to be extracted");
+    assert_same_span(&received, &span_context());
+    assert_eq!(received.span().span_context().trace_state().header(), "vendor=value");
+}
+
+#[tokio::test]
+async fn should_round_trip_current_context() {
+    let _guard = context().attach();
+
+    let (headers, received) = round_trip(http::Request::new(())).await;
+    assert!(headers.contains_key("traceparent"));
+    assert_same_span(&received.expect("context to be extracted"), &span_context());
+}
+
+#[tokio::test]
+async fn should_extract_trace_bin_only() {
+    let mut request = http::Request::new(());
+    request.extensions_mut().insert(context());
+    let mut metadata = inject(&mut request);
+    metadata.remove("traceparent");
+    metadata.remove("tracestate");
+
+    let mut extensions = http::Extensions::new();
+    tonic_interceptor::Interceptor::on_request(&ExtractContext::with_propagator(TraceContextPropagator::new()), &mut metadata, &mut extensions);
+    assert_same_span(extensions.get::<Context>().expect("context to be extracted"), &span_context());
+}
+
+#[test]
+fn should_encode_trace_bin() {
+    let encoded = encode_trace_bin(&span_context());
+    assert_eq!(encoded, [
+        0,
+        0, 0x4b, 0xf9, 0x2f, 0x35, 0x77, 0xb3, 0x4d, 0xa6, 0xa3, 0xce, 0x92, 0x9d, 0x0e, 0x0e, 0x47, 0x36,
+        1, 0x00, 0xf0, 0x67, 0xaa, 0x0b, 0xa9, 0x02, 0xb7,
+        2, 1,
+    ]);
+
+    let decoded = decode_trace_bin(&encoded).expect("to decode");
+    assert_eq!(decoded.trace_id(), span_context().trace_id());
+    assert_eq!(decoded.span_id(), span_context().span_id());
+    assert_eq!(decoded.trace_flags(), TraceFlags::SAMPLED);
+
+    assert!(decode_trace_bin(&encoded[..28]).is_none());
+    assert!(decode_trace_bin(&encode_trace_bin(&SpanContext::empty_context())).is_none());
+}
+
+#[test]
+fn should_not_overwrite_explicit_headers() {
+    let mut request = http::Request::new(());
+    request.headers_mut().insert("traceparent", "00-11111111111111111111111111111111-2222222222222222-00".parse().unwrap());
+    request.headers_mut().insert(GRPC_TRACE_BIN, "AA".parse().unwrap());
+    request.extensions_mut().insert(context());
+
+    let metadata = inject(&mut request);
+    assert_eq!(metadata.get("traceparent").expect("traceparent"), "00-11111111111111111111111111111111-2222222222222222-00");
+    assert_eq!(metadata.get_bin(GRPC_TRACE_BIN).expect(GRPC_TRACE_BIN).as_encoded_bytes(), b"AA");
+    assert_eq!(metadata.get("tracestate").expect("tracestate"), "vendor=value");
+}
+
+#[test]
+fn should_do_nothing_without_active_span() {
+    let mut request = http::Request::new(());
+    let metadata = inject(&mut request);
+    assert!(metadata.is_empty());
+
+    request.extensions_mut().insert(Context::new());
+    let metadata = inject(&mut request);
+    assert!(metadata.is_empty());
+}
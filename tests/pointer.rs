@@ -0,0 +1,73 @@
+#![cfg(feature = "tonic011")]
+#![allow(clippy::result_large_err)]
+
+use tonic_interceptor::{Interceptor, InterceptorService};
+
+use tonic::Status;
+use tower::ServiceExt;
+
+use core::future;
+use std::rc::Rc;
+use std::sync::Arc;
+
+struct Reject;
+
+impl Interceptor for Reject {
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<Status> {
+        match headers.contains_key("x-reject") {
+            true => Some(Status::permission_denied("rejected")),
+            false => None,
+        }
+    }
+
+    fn on_response(&self, _: tonic::Code, headers: &mut http::HeaderMap, _: &http::Extensions) {
+        headers.insert("x-intercepted", http::HeaderValue::from_static("1"));
+    }
+}
+
+fn svc() -> impl tower_service::Service<http::Request<()>, Response = http::Response<()>, Error = Status, Future = impl Send> + Clone {
+    tower::service_fn(|_: http::Request<()>| {
+        let response = http::Response::builder().header("grpc-status", "0").body(()).unwrap();
+        future::ready(Ok::<_, Status>(response))
+    })
+}
+
+async fn check<I: Interceptor + Clone>(interceptor: I) {
+    let service = InterceptorService::new(interceptor, svc());
+    let response = service.clone().oneshot(http::Request::new(())).await.expect("success");
+    assert_eq!(response.headers().get("x-intercepted").unwrap(), "1");
+
+    let request = http::Request::builder().header("x-reject", "1").body(()).unwrap();
+    let response = service.oneshot(request).await.expect("success");
+    assert_eq!(response.headers().get("grpc-status").unwrap(), "7");
+}
+
+#[tokio::test]
+async fn should_forward_through_pointers() {
+    let interceptor = Reject;
+    check(&interceptor).await;
+    check(&&interceptor).await;
+    check(Arc::new(Reject)).await;
+    check(Rc::new(Reject)).await;
+
+    //Box is only `Clone` for `Clone` interceptors
+    let boxed: Box<dyn Interceptor> = Box::new(Reject);
+    check(&boxed).await;
+    let boxed: Arc<Box<dyn Interceptor + Send + Sync>> = Arc::new(Box::new(Reject));
+    check(boxed).await;
+}
+
+#[test]
+fn should_forward_through_mutable_reference() {
+    let mut interceptor = Reject;
+    let service = InterceptorService::new(&mut interceptor, svc());
+
+    let mut headers = http::HeaderMap::new();
+    headers.insert("x-reject", http::HeaderValue::from_static("1"));
+    let status = service.interceptor_ref().on_request_headers(&http::Uri::default(), &mut headers, &mut http::Extensions::new()).expect("to reject");
+    assert_eq!(status.code(), tonic::Code::PermissionDenied);
+
+    let mut headers = http::HeaderMap::new();
+    service.interceptor_ref().on_response(tonic::Code::Ok, &mut headers, &http::Extensions::new());
+    assert_eq!(headers.get("x-intercepted").unwrap(), "1");
+}
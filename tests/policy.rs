@@ -0,0 +1,663 @@
+#![cfg(feature = "tonic011")]
+#![allow(clippy::result_large_err)]
+
+use tonic_interceptor::Interceptor;
+use tonic_interceptor::policy::{Authority, AuthorityCheck, KnownMethods, PatternError, RequiredMetadata, Rule, ValidateBinaryMetadata};
+
+use tonic::Code;
+use tonic::metadata::{MetadataMap, MetadataValue};
+
+use core::time::Duration;
+
+fn policy() -> RequiredMetadata {
+    RequiredMetadata::builder().rule(Rule::required("x-tenant").pattern("[a-z0-9-]{1,32}").expect("valid pattern"))
+                               .rule(Rule::required("x-client-version").max_len(16))
+                               .rule(Rule::optional("x-request-id-bin").len(16, 16))
+                               .build()
+}
+
+fn validate(policy: &RequiredMetadata, entries: &[(&'static str, &'static str)], bin: Option<&[u8]>) -> Result<(), String> {
+    let mut headers = MetadataMap::new();
+    for (key, value) in entries {
+        headers.insert(*key, value.parse().unwrap());
+    }
+    if let Some(bin) = bin {
+        headers.insert_bin("x-request-id-bin", MetadataValue::from_bytes(bin));
+    }
+    match policy.on_request(&mut headers, &mut http::Extensions::new()) {
+        None => Ok(()),
+        Some(status) => {
+            assert_eq!(status.code(), Code::InvalidArgument);
+            Err(status.message().to_owned())
+        }
+    }
+}
+
+#[test]
+fn should_accept_valid_metadata() {
+    let policy = policy();
+    assert_eq!(validate(&policy, &[("x-tenant", "acme-1"), ("x-client-version", "1.2.3")], None), Ok(()));
+    assert_eq!(validate(&policy, &[("x-tenant", "acme-1"), ("x-client-version", "1.2.3")], Some(&[7; 16])), Ok(()));
+}
+
+#[test]
+fn should_name_every_failing_key() {
+    let policy = policy();
+    assert_eq!(validate(&policy, &[], None).unwrap_err(), "missing metadata 'x-tenant', missing metadata 'x-client-version'");
+    assert_eq!(validate(&policy, &[("x-tenant", "ACME"), ("x-client-version", "1.2.3-beta.1+build.42")], Some(&[0; 4])).unwrap_err(),
+               "metadata 'x-tenant' has invalid format, metadata 'x-client-version' has invalid length, metadata 'x-request-id-bin' has invalid length");
+    assert_eq!(validate(&policy, &[("x-client-version", "1")], None).unwrap_err(), "missing metadata 'x-tenant'");
+}
+
+#[test]
+fn should_check_decoded_length_of_bin_value() {
+    let policy = RequiredMetadata::builder().rule(Rule::required("x-id-bin").min_len(2).max_len(4)).build();
+    let check = |value: &[u8]| {
+        let mut headers = MetadataMap::new();
+        headers.insert_bin("x-id-bin", MetadataValue::from_bytes(value));
+        policy.validate(&headers).map_err(|status| status.message().to_owned())
+    };
+    //Encoded value of 4 bytes is 6 characters long
+    assert_eq!(check(&[1, 2, 3, 4]), Ok(()));
+    assert_eq!(check(&[1, 2]), Ok(()));
+    assert_eq!(check(&[1]).unwrap_err(), "metadata 'x-id-bin' has invalid length");
+    assert_eq!(check(&[1, 2, 3, 4, 5]).unwrap_err(), "metadata 'x-id-bin' has invalid length");
+
+    let mut headers = http::HeaderMap::new();
+    headers.insert("x-id-bin", http::HeaderValue::from_static("!!!"));
+    let headers = MetadataMap::from_headers(headers);
+    assert_eq!(policy.validate(&headers).unwrap_err().message(), "metadata 'x-id-bin' is not valid base64");
+
+    assert!(Rule::required("x-id-bin").pattern("[a-z]").is_err());
+}
+
+#[test]
+fn should_match_whole_value_against_pattern() {
+    let check = |pattern: &str, value: &'static str| {
+        let policy = RequiredMetadata::builder().rule(Rule::required("x-value").pattern(pattern).unwrap()).build();
+        let mut headers = MetadataMap::new();
+        headers.insert("x-value", value.parse().unwrap());
+        policy.validate(&headers).is_ok()
+    };
+
+    assert!(check("[a-z0-9-]{1,32}", "acme-1"));
+    assert!(!check("[a-z0-9-]{1,32}", "Acme"));
+    assert!(!check("[a-z0-9-]{1,32}", "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"));
+    //Alternation is anchored as a whole
+    assert!(check("v1|v2", "v2"));
+    assert!(!check("v1|v2", "v10"));
+    assert!(!check("v1|v2", "xv2"));
+
+    let jwt = r"[\w-]+\.[\w-]+\.[\w-]+";
+    assert!(check(jwt, "eyJhbGciOi.eyJzdWIiOiIx.SflKxw"));
+    assert!(!check(jwt, "eyJhbGciOi.eyJzdWIiOiIx"));
+    assert!(!check(jwt, "Bearer eyJhbGciOi.eyJzdWIiOiIx.SflKxw"));
+
+    assert!(matches!(Rule::required("x-value").pattern("[a-z"), Err(PatternError::Regex(_))));
+    assert!(Rule::required("x-value").pattern("[a-z").unwrap_err().to_string().starts_with("invalid pattern: "));
+    assert_eq!(Rule::required("x-id-bin").pattern("[a-z]").unwrap_err(), PatternError::Binary);
+}
+
+//Creates metadata out of raw, not yet decoded, values
+fn raw_metadata(entries: &[(&'static str, &'static str)]) -> MetadataMap {
+    let mut headers = http::HeaderMap::new();
+    for (key, value) in entries {
+        headers.append(*key, http::HeaderValue::from_static(value));
+    }
+    MetadataMap::from_headers(headers)
+}
+
+#[test]
+fn should_accept_padded_and_unpadded_bin_values() {
+    let validator = ValidateBinaryMetadata::new();
+    for value in ["AQI", "AQI=", "AQID", "", "AQIDBA", "AQIDBA=="] {
+        let mut headers = raw_metadata(&[("x-token-bin", value), ("x-tenant", "!!!")]);
+        assert!(validator.on_request(&mut headers, &mut http::Extensions::new()).is_none(), "value '{}'", value);
+        assert!(headers.get_bin("x-token-bin").is_some());
+    }
+}
+
+#[test]
+fn should_reject_corrupted_bin_values() {
+    let validator = ValidateBinaryMetadata::new();
+    let mut headers = raw_metadata(&[("x-token-bin", "AQI"), ("x-trace-bin", "AQ!D"), ("x-id-bin", "A"), ("x-id-bin", "AQI")]);
+    let status = validator.on_request(&mut headers, &mut http::Extensions::new()).expect("to reject");
+    assert_eq!(status.code(), Code::InvalidArgument);
+    let mut reasons: Vec<_> = status.message().split(", ").collect();
+    reasons.sort_unstable();
+    assert_eq!(reasons, ["metadata 'x-id-bin' is not valid base64", "metadata 'x-trace-bin' is not valid base64"]);
+
+    //Only configured keys are checked
+    let validator = ValidateBinaryMetadata::new().keys(["x-token-bin", "x-tenant"]);
+    let mut headers = raw_metadata(&[("x-token-bin", "AQI"), ("x-trace-bin", "AQ!D")]);
+    assert!(validator.on_request(&mut headers, &mut http::Extensions::new()).is_none());
+    let mut headers = raw_metadata(&[("x-token-bin", "AQI==")]);
+    assert_eq!(validator.on_request(&mut headers, &mut http::Extensions::new()).expect("to reject").message(), "metadata 'x-token-bin' is not valid base64");
+}
+
+#[test]
+fn should_limit_decoded_size_of_bin_values() {
+    let validator = ValidateBinaryMetadata::new().max_size(2);
+    let mut headers = raw_metadata(&[("x-token-bin", "AQI")]);
+    assert!(validator.on_request(&mut headers, &mut http::Extensions::new()).is_none());
+    let mut headers = raw_metadata(&[("x-token-bin", "AQID")]);
+    assert_eq!(validator.on_request(&mut headers, &mut http::Extensions::new()).expect("to reject").message(), "metadata 'x-token-bin' exceeds maximum size");
+}
+
+#[test]
+fn should_strip_invalid_bin_values_in_lenient_mode() {
+    let validator = ValidateBinaryMetadata::new().max_size(2).lenient();
+    let mut headers = raw_metadata(&[("x-id-bin", "A"), ("x-id-bin", "AQI"), ("x-id-bin", "AQID"), ("x-trace-bin", "!!!"), ("x-token-bin", "AQ"), ("x-tenant", "acme")]);
+    assert!(validator.on_request(&mut headers, &mut http::Extensions::new()).is_none());
+
+    let ids: Vec<_> = headers.get_all_bin("x-id-bin").iter().map(|value| value.to_bytes().unwrap()).collect();
+    assert_eq!(ids, [vec![1u8, 2]]);
+    assert!(headers.get_bin("x-trace-bin").is_none());
+    assert_eq!(headers.get_bin("x-token-bin").unwrap().to_bytes().unwrap().as_ref(), [1]);
+    assert_eq!(headers.get("x-tenant").unwrap(), "acme");
+}
+
+#[cfg(feature = "serde")]
+mod common;
+
+#[cfg(feature = "serde")]
+#[test]
+fn should_deserialize_rules() {
+    use common::value::Value;
+    use serde::Deserialize;
+
+    let policy = RequiredMetadata::deserialize(Value::List(vec![
+        Value::Map(vec![("key", Value::Str("x-tenant")), ("pattern", Value::Str("[a-z0-9-]{1,32}"))]),
+        Value::Map(vec![("key", Value::Str("x-client-version")), ("max_len", Value::Int(16))]),
+        Value::Map(vec![("key", Value::Str("x-request-id-bin")), ("required", Value::Bool(false)), ("min_len", Value::Int(16)), ("max_len", Value::Int(16))]),
+    ])).expect("valid policy");
+    assert_eq!(policy.rules().len(), 3);
+    assert_eq!(validate(&policy, &[("x-tenant", "ACME"), ("x-client-version", "1.2.3")], Some(&[0; 4])).unwrap_err(),
+               "metadata 'x-tenant' has invalid format, metadata 'x-request-id-bin' has invalid length");
+
+    let error = RequiredMetadata::deserialize(Value::List(vec![Value::Map(vec![("key", Value::Str("x-tenant")), ("pattern", Value::Str("[a-z"))])])).unwrap_err();
+    assert!(error.to_string().starts_with("metadata 'x-tenant': invalid pattern: regex parse error:\n    [a-z\n"), "{}", error);
+    let error = RequiredMetadata::deserialize(Value::List(vec![Value::Map(vec![("key", Value::Str("x-tenant")), ("regex", Value::Str("[a-z]"))])])).unwrap_err();
+    assert_eq!(error.to_string(), "unknown field `regex`, expected one of `key`, `required`, `min_len`, `max_len`, `pattern`");
+}
+
+fn check_path(known: &KnownMethods, path: &str) -> Result<(), (Code, String)> {
+    let uri: http::Uri = path.parse().expect("valid uri");
+    match known.on_request_headers(&uri, &mut http::HeaderMap::new(), &mut http::Extensions::new()) {
+        None => Ok(()),
+        Some(status) => Err((status.code(), status.message().to_owned())),
+    }
+}
+
+#[test]
+fn should_accept_known_services() {
+    let known = KnownMethods::new(["test.Echo", "test.Other"]).allow_prefix("/grpc.health.v1.Health/").allow_prefix("/grpc.reflection.");
+    assert_eq!(check_path(&known, "/test.Echo/Unary"), Ok(()));
+    assert_eq!(check_path(&known, "/test.Other/Any?query=1"), Ok(()));
+    assert_eq!(check_path(&known, "/grpc.health.v1.Health/Check"), Ok(()));
+    assert_eq!(check_path(&known, "/grpc.reflection.v1alpha.ServerReflection/ServerReflectionInfo"), Ok(()));
+
+    let mut metadata = MetadataMap::new();
+    let uri = http::Uri::from_static("/test.Echo/Unary");
+    assert!(known.on_request_with_uri(&uri, &mut metadata, &mut http::Extensions::new()).is_none());
+    //Without URI request is passed through
+    assert!(known.on_request(&mut metadata, &mut http::Extensions::new()).is_none());
+}
+
+#[test]
+fn should_reject_unknown_services() {
+    let known = KnownMethods::new(["test.Echo"]).allow_prefix("/grpc.health.v1.Health/");
+    assert_eq!(check_path(&known, "/test.Echo2/Unary"), Err((Code::Unimplemented, "unknown service 'test.Echo2'".to_owned())));
+    assert_eq!(check_path(&known, "/wp-admin/index.php"), Err((Code::Unimplemented, "unknown service 'wp-admin'".to_owned())));
+    assert_eq!(check_path(&known, "/grpc.health.v1.Health"), Err((Code::Unimplemented, "malformed method path '/grpc.health.v1.Health'".to_owned())));
+    assert_eq!(KnownMethods::default().check("/test.Echo/Unary").unwrap_err().code(), Code::Unimplemented);
+}
+
+#[test]
+fn should_reject_malformed_paths() {
+    let known = KnownMethods::new(["test.Echo"]);
+    for path in ["/", "/test.Echo", "/test.Echo/", "/test.Echo/Unary/extra"] {
+        let (code, message) = check_path(&known, path).unwrap_err();
+        assert_eq!(code, Code::Unimplemented, "path '{}'", path);
+        assert_eq!(message, format!("malformed method path '{}'", path));
+    }
+    assert_eq!(known.check("test.Echo/Unary").unwrap_err().message(), "malformed method path 'test.Echo/Unary'");
+    assert_eq!(known.check("//Unary").unwrap_err().message(), "malformed method path '//Unary'");
+}
+
+#[test]
+fn should_parse_timestamps() {
+    use tonic_interceptor::policy::parse_timestamp;
+
+    let cases = vec![
+        ("1704164645", Some(1_704_164_645_000)),
+        ("1704164645678", Some(1_704_164_645_678)),
+        ("0", Some(0)),
+        ("99999999999", Some(99_999_999_999_000)),
+        ("100000000000", Some(100_000_000_000)),
+        ("2024-01-02T03:04:05Z", Some(1_704_164_645_000)),
+        ("2024-01-02t03:04:05z", Some(1_704_164_645_000)),
+        ("2024-01-02 03:04:05Z", Some(1_704_164_645_000)),
+        ("2024-01-02T03:04:05.678Z", Some(1_704_164_645_678)),
+        ("2024-01-02T03:04:05.6Z", Some(1_704_164_645_600)),
+        ("2024-01-02T03:04:05.678901Z", Some(1_704_164_645_678)),
+        ("2024-01-02T05:04:05+02:00", Some(1_704_164_645_000)),
+        ("2024-01-01T22:34:05-04:30", Some(1_704_164_645_000)),
+        ("1970-01-01T00:00:00Z", Some(0)),
+        ("2024-02-29T00:00:00Z", Some(1_709_164_800_000)),
+        ("2000-03-01T00:00:00Z", Some(951_868_800_000)),
+        ("2016-12-31T23:59:60Z", Some(1_483_228_800_000)),
+        ("", None),
+        ("-1", None),
+        ("12.5", None),
+        ("99999999999999999999", None),
+        ("1969-12-31T23:59:59Z", None),
+        ("2023-02-29T00:00:00Z", None),
+        ("2024-13-01T00:00:00Z", None),
+        ("2024-01-00T00:00:00Z", None),
+        ("2024-01-02T24:00:00Z", None),
+        ("2024-01-02T03:04:05", None),
+        ("2024-01-02T03:04:05.Z", None),
+        ("2024-01-02T03:04:05+0200", None),
+        ("2024-01-02T03:04:05+24:00", None),
+        ("2024-01-02X03:04:05Z", None),
+        ("2024-1-02T03:04:05Z", None),
+    ];
+    for (value, expected) in cases {
+        assert_eq!(parse_timestamp(value), expected, "'{}'", value);
+    }
+}
+
+fn freshness_call(freshness: &tonic_interceptor::policy::Freshness<impl tonic_interceptor::auth::Clock>, timestamp: Option<&str>) -> Result<(), (Code, String, String)> {
+    let mut headers = http::HeaderMap::new();
+    if let Some(timestamp) = timestamp {
+        headers.insert("x-request-timestamp", timestamp.parse().unwrap());
+    }
+    let uri = http::Uri::from_static("/test.Echo/Unary");
+    match freshness.on_request_headers(&uri, &mut headers, &mut http::Extensions::new()) {
+        None => Ok(()),
+        Some(status) => Err((status.code(), status.message().to_owned(), status.metadata().get("x-server-time").unwrap().to_str().unwrap().to_owned())),
+    }
+}
+
+#[test]
+fn should_accept_fresh_timestamp_within_boundaries() {
+    use tonic_interceptor::policy::Freshness;
+
+    const NOW: u64 = 1_704_164_645;
+    let freshness = Freshness::new(Duration::from_secs(300), Duration::from_secs(30)).clock(|| NOW);
+
+    assert_eq!(freshness_call(&freshness, Some("1704164645")), Ok(()));
+    //Exactly at max age and max skew
+    assert_eq!(freshness_call(&freshness, Some(&(NOW - 300).to_string())), Ok(()));
+    assert_eq!(freshness_call(&freshness, Some(&((NOW - 300) * 1000).to_string())), Ok(()));
+    assert_eq!(freshness_call(&freshness, Some("2024-01-02T02:59:05Z")), Ok(()));
+    assert_eq!(freshness_call(&freshness, Some(&(NOW + 30).to_string())), Ok(()));
+    //Just beyond them
+    let stale = Err((Code::PermissionDenied, "request timestamp is too old".to_owned(), NOW.to_string()));
+    assert_eq!(freshness_call(&freshness, Some(&((NOW - 300) * 1000 - 1).to_string())), stale);
+    assert_eq!(freshness_call(&freshness, Some("2024-01-02T02:59:04.999Z")), stale);
+    let future = Err((Code::PermissionDenied, "request timestamp is too far in future".to_owned(), NOW.to_string()));
+    assert_eq!(freshness_call(&freshness, Some(&((NOW + 30) * 1000 + 1).to_string())), future);
+    assert_eq!(freshness_call(&freshness, Some(&(NOW + 31).to_string())), future);
+}
+
+#[test]
+fn should_reject_missing_or_malformed_timestamp() {
+    use tonic_interceptor::policy::Freshness;
+
+    let freshness = Freshness::new(Duration::from_secs(300), Duration::ZERO).clock(|| 1000);
+    assert_eq!(freshness_call(&freshness, None), Err((Code::InvalidArgument, "missing request timestamp 'x-request-timestamp'".to_owned(), "1000".to_owned())));
+    assert_eq!(freshness_call(&freshness, Some("yesterday")), Err((Code::InvalidArgument, "invalid request timestamp 'x-request-timestamp'".to_owned(), "1000".to_owned())));
+
+    //Custom key via metadata
+    let freshness = freshness.key("x-signature-timestamp");
+    let mut metadata = MetadataMap::new();
+    metadata.insert("x-signature-timestamp", "1000".parse().unwrap());
+    assert!(freshness.on_request(&mut metadata, &mut http::Extensions::new()).is_none());
+    metadata.insert("x-signature-timestamp", "1001".parse().unwrap());
+    assert_eq!(freshness.on_request(&mut metadata, &mut http::Extensions::new()).unwrap().code(), Code::PermissionDenied);
+}
+
+fn retry_attempts(policy: &tonic_interceptor::policy::RetryAttempts, value: Option<&'static str>) -> Result<Option<u32>, (Code, String)> {
+    use tonic_interceptor::policy::PreviousAttempts;
+
+    let mut headers = MetadataMap::new();
+    if let Some(value) = value {
+        headers.insert("grpc-previous-rpc-attempts", value.parse().unwrap());
+    }
+    let mut extensions = http::Extensions::new();
+    match policy.on_request(&mut headers, &mut extensions) {
+        Some(status) => Err((status.code(), status.message().to_owned())),
+        None => Ok(extensions.get::<PreviousAttempts>().map(|attempts| attempts.0)),
+    }
+}
+
+#[test]
+fn should_insert_previous_attempts() {
+    use tonic_interceptor::policy::RetryAttempts;
+
+    let policy = RetryAttempts::new();
+    assert_eq!(retry_attempts(&policy, None), Ok(Some(0)));
+    assert_eq!(retry_attempts(&policy, Some("0")), Ok(Some(0)));
+    assert_eq!(retry_attempts(&policy, Some("3")), Ok(Some(3)));
+    assert_eq!(retry_attempts(&policy, Some("4294967295")), Ok(Some(u32::MAX)));
+    //Garbage is ignored
+    assert_eq!(retry_attempts(&policy, Some("-1")), Ok(None));
+    assert_eq!(retry_attempts(&policy, Some("+1")), Ok(None));
+    assert_eq!(retry_attempts(&policy, Some("one")), Ok(None));
+    assert_eq!(retry_attempts(&policy, Some("4294967296")), Ok(None));
+    assert_eq!(retry_attempts(&policy, Some("")), Ok(None));
+
+    let mut headers = http::HeaderMap::new();
+    headers.insert("grpc-previous-rpc-attempts", http::HeaderValue::from_static("2"));
+    let mut extensions = http::Extensions::new();
+    assert!(policy.on_request_headers(&http::Uri::from_static("/pkg.Users/Get"), &mut headers, &mut extensions).is_none());
+    assert_eq!(extensions.get(), Some(&tonic_interceptor::policy::PreviousAttempts(2)));
+}
+
+#[test]
+fn should_limit_previous_attempts() {
+    use tonic_interceptor::policy::RetryAttempts;
+
+    let policy = RetryAttempts::new().max(2);
+    assert_eq!(retry_attempts(&policy, None), Ok(Some(0)));
+    assert_eq!(retry_attempts(&policy, Some("2")), Ok(Some(2)));
+    assert_eq!(retry_attempts(&policy, Some("3")), Err((Code::ResourceExhausted, "3 previous attempts exceed limit of 2".to_owned())));
+    //Garbage is still not rejected
+    assert_eq!(retry_attempts(&policy, Some("many")), Ok(None));
+}
+
+#[test]
+fn should_reject_invalid_previous_attempts_in_strict_mode() {
+    use tonic_interceptor::policy::RetryAttempts;
+
+    let policy = RetryAttempts::new().strict(true);
+    assert_eq!(retry_attempts(&policy, None), Ok(Some(0)));
+    assert_eq!(retry_attempts(&policy, Some("1")), Ok(Some(1)));
+    assert_eq!(retry_attempts(&policy, Some("many")), Err((Code::InvalidArgument, "invalid 'grpc-previous-rpc-attempts'".to_owned())));
+    assert_eq!(retry_attempts(&policy, Some("")), Err((Code::InvalidArgument, "invalid 'grpc-previous-rpc-attempts'".to_owned())));
+}
+
+#[test]
+fn should_bucket_previous_attempts() {
+    use tonic_interceptor::policy::PreviousAttempts;
+
+    assert_eq!(PreviousAttempts(0).bucket(), "0");
+    assert!(!PreviousAttempts(0).is_retry());
+    assert_eq!(PreviousAttempts(1).bucket(), "1");
+    assert!(PreviousAttempts(1).is_retry());
+    assert_eq!(PreviousAttempts(2).bucket(), "2+");
+    assert_eq!(PreviousAttempts(100).bucket(), "2+");
+}
+
+fn gate_check(gate: &tonic_interceptor::policy::ContentTypeGate, path: &'static str, content_type: Option<&'static str>) -> Result<tonic_interceptor::policy::ContentType, (Code, String)> {
+    let mut headers = http::HeaderMap::new();
+    if let Some(content_type) = content_type {
+        headers.insert(http::header::CONTENT_TYPE, http::HeaderValue::from_static(content_type));
+    }
+    let mut extensions = http::Extensions::new();
+    match gate.on_request_headers(&http::Uri::from_static(path), &mut headers, &mut extensions) {
+        Some(status) => Err((status.code(), status.message().to_owned())),
+        None => Ok(*extensions.get().expect("content type")),
+    }
+}
+
+#[test]
+fn should_parse_content_types() {
+    use tonic_interceptor::policy::ContentType;
+
+    assert_eq!(ContentType::parse(b"application/grpc"), Some(ContentType::Proto));
+    assert_eq!(ContentType::parse(b"application/grpc+proto"), Some(ContentType::Proto));
+    assert_eq!(ContentType::parse(b"Application/GRPC+Proto; charset=utf-8"), Some(ContentType::Proto));
+    assert_eq!(ContentType::parse(b"application/grpc+json"), Some(ContentType::Json));
+    assert_eq!(ContentType::parse(b"application/grpc-web"), Some(ContentType::Web));
+    assert_eq!(ContentType::parse(b"application/grpc-web+proto"), Some(ContentType::Web));
+    assert_eq!(ContentType::parse(b"application/grpc-web-text"), Some(ContentType::Web));
+    assert_eq!(ContentType::parse(b"application/grpc+thrift"), None);
+    assert_eq!(ContentType::parse(b"application/json"), None);
+    assert_eq!(ContentType::parse(b"text/html"), None);
+    assert_eq!(ContentType::Proto.as_str(), "application/grpc");
+}
+
+#[test]
+fn should_gate_content_types_per_method() {
+    use tonic_interceptor::policy::{ContentType, ContentTypeGate};
+
+    let gate = ContentTypeGate::new().method("/pkg.Public/*", &[ContentType::Proto, ContentType::Json])
+                                     .method("/pkg.Public/Upload", &[ContentType::Proto])
+                                     .method("/pkg.Browser/*", &[ContentType::Web]);
+
+    //Subtype is normalized
+    assert_eq!(gate_check(&gate, "/pkg.Private/Get", Some("application/grpc")), Ok(ContentType::Proto));
+    assert_eq!(gate_check(&gate, "/pkg.Private/Get", Some("application/grpc+proto")), Ok(ContentType::Proto));
+    assert_eq!(gate_check(&gate, "/pkg.Private/Get", Some("application/grpc+json")), Err((Code::Unimplemented, "method '/pkg.Private/Get' does not accept content type 'application/grpc+json', accepted are: application/grpc".to_owned())));
+
+    assert_eq!(gate_check(&gate, "/pkg.Public/Get", Some("application/grpc+json")), Ok(ContentType::Json));
+    assert_eq!(gate_check(&gate, "/pkg.Public/Get", Some("application/grpc")), Ok(ContentType::Proto));
+    assert_eq!(gate_check(&gate, "/pkg.Public/Upload", Some("application/grpc+json")).unwrap_err().0, Code::Unimplemented);
+
+    assert_eq!(gate_check(&gate, "/pkg.Browser/Get", Some("application/grpc-web-text")), Ok(ContentType::Web));
+    assert_eq!(gate_check(&gate, "/pkg.Browser/Get", Some("application/grpc+proto")), Err((Code::Unimplemented, "method '/pkg.Browser/Get' does not accept content type 'application/grpc', accepted are: application/grpc-web".to_owned())));
+
+    assert_eq!(gate_check(&gate, "/pkg.Public/Get", Some("application/grpc+thrift")), Err((Code::Unimplemented, "content type 'application/grpc+thrift' is not supported".to_owned())));
+    assert_eq!(gate_check(&gate, "/pkg.Public/Get", Some("text/html")), Err((Code::Unimplemented, "content type 'text/html' is not gRPC".to_owned())));
+    assert_eq!(gate_check(&gate, "/pkg.Public/Get", None), Err((Code::Unimplemented, "missing content type".to_owned())));
+
+    //Default can be changed
+    let gate = ContentTypeGate::new().accept(&[ContentType::Json]);
+    assert_eq!(gate_check(&gate, "/pkg.Any/Get", Some("application/grpc+json")), Ok(ContentType::Json));
+    assert_eq!(gate_check(&gate, "/pkg.Any/Get", Some("application/grpc")).unwrap_err().0, Code::Unimplemented);
+
+    let mut metadata = MetadataMap::new();
+    metadata.insert("content-type", "application/grpc+json".parse().unwrap());
+    let mut extensions = http::Extensions::new();
+    assert!(gate.on_request_with_uri(&http::Uri::from_static("/pkg.Any/Get"), &mut metadata, &mut extensions).is_none());
+    assert_eq!(extensions.get(), Some(&ContentType::Json));
+}
+
+#[test]
+fn should_answer_non_grpc_requests_with_fallback() {
+    use tonic_interceptor::policy::{ContentType, ContentTypeGate, PlainResponse};
+    use tonic_interceptor::testing::{poll_once, service_fn};
+    use tower_layer::Layer;
+    use tower_service::Service;
+
+    use http_body::Body;
+
+    let fallback = PlainResponse::new(http::StatusCode::OK).header(http::header::CONTENT_TYPE, http::HeaderValue::from_static("text/plain")).body("ok");
+    let gate = ContentTypeGate::new().accept(&[ContentType::Proto, ContentType::Json]).fallback(fallback);
+    let svc = service_fn(|req: http::Request<()>| {
+        assert!(req.extensions().get::<ContentType>().is_some());
+        Ok::<_, core::convert::Infallible>(http::Response::builder().header("grpc-status", "0").body(tonic::body::empty_body()).unwrap())
+    });
+    let mut service = gate.layer().layer(svc);
+    let request = |content_type: Option<&'static str>| {
+        let mut request = http::Request::builder().uri("/pkg.Users/Get");
+        if let Some(content_type) = content_type {
+            request = request.header(http::header::CONTENT_TYPE, content_type);
+        }
+        request.body(()).unwrap()
+    };
+
+    //Browser or health check of load balancer
+    for content_type in [None, Some("text/html")].iter() {
+        let mut response = poll_once(service.call(request(*content_type))).expect("response");
+        assert_eq!(response.status(), http::StatusCode::OK);
+        assert_eq!(response.headers().get(http::header::CONTENT_TYPE).unwrap(), "text/plain");
+        assert_eq!(response.headers().get("grpc-status"), None);
+        assert!(response.body().is_plain());
+        assert_eq!(response.body().size_hint().exact(), Some(2));
+        let data = poll_once(core::future::poll_fn(|ctx| core::pin::Pin::new(response.body_mut()).poll_data(ctx))).expect("data").expect("ok");
+        assert_eq!(&data[..], b"ok");
+        assert!(response.body().is_end_stream());
+    }
+
+    //gRPC requests go to the gate
+    let response = poll_once(service.call(request(Some("application/grpc+json")))).expect("response");
+    assert_eq!(response.headers().get("grpc-status").unwrap(), "0");
+    assert!(!response.body().is_plain());
+    let response = poll_once(service.call(request(Some("application/grpc-web")))).expect("response");
+    assert_eq!(response.headers().get("grpc-status").unwrap(), "12");
+    assert_eq!(response.headers().get(http::header::CONTENT_TYPE).unwrap(), "application/grpc-web");
+
+    //Without fallback non-gRPC requests are rejected
+    let mut service = ContentTypeGate::new().layer().layer(service_fn(|_: http::Request<()>| Ok::<_, core::convert::Infallible>(http::Response::new(tonic::body::empty_body()))));
+    let response = poll_once(service.call(request(Some("text/html")))).expect("response");
+    assert_eq!(response.headers().get("grpc-status").unwrap(), "12");
+    assert!(!response.body().is_plain());
+
+    //Body of rejection comes from factory, when service's body is not `Default`
+    struct Text;
+    let mut service = ContentTypeGate::new().layer().with_body(|| Text).layer(service_fn(|_: http::Request<()>| Ok::<_, core::convert::Infallible>(http::Response::new(Text))));
+    let response = poll_once(service.call(request(Some("text/html")))).expect("response");
+    assert_eq!(response.headers().get("grpc-status").unwrap(), "12");
+    let response = poll_once(service.call(request(Some("application/grpc")))).expect("response");
+    assert!(response.headers().get("grpc-status").is_none());
+}
+
+//Runs `hygiene` over raw values, returning resulting values of `key` or rejection message
+fn hygiene(hygiene: &tonic_interceptor::policy::ValueHygiene, entries: &[(&'static str, &'static [u8])], key: &str) -> Result<Vec<Vec<u8>>, String> {
+    let mut headers = http::HeaderMap::new();
+    for (name, value) in entries {
+        headers.append(*name, http::HeaderValue::from_bytes(value).expect("valid header value"));
+    }
+    let mut metadata = MetadataMap::from_headers(headers.clone());
+
+    let result = hygiene.on_request_headers(&http::Uri::from_static("/pkg.Users/Get"), &mut headers, &mut http::Extensions::new());
+    let metadata_result = hygiene.on_request(&mut metadata, &mut http::Extensions::new());
+    assert_eq!(result.as_ref().map(|status| status.message()), metadata_result.as_ref().map(|status| status.message()));
+    match result {
+        Some(status) => {
+            assert_eq!(status.code(), Code::InvalidArgument);
+            Err(status.message().to_owned())
+        },
+        None => {
+            assert_eq!(metadata.into_headers(), headers);
+            Ok(headers.get_all(key).iter().map(|value| value.as_bytes().to_vec()).collect())
+        }
+    }
+}
+
+#[test]
+fn should_reject_unhygienic_values() {
+    use tonic_interceptor::policy::{ValueHygiene, ValuePolicy};
+
+    let policy = ValueHygiene::new().key("x-tenant", &[ValuePolicy::Trim, ValuePolicy::MaxLen(8)])
+                                    .key("x-forwarded-*", &[ValuePolicy::RejectControlChars])
+                                    .key("x-name", &[ValuePolicy::RequireVisibleAscii]);
+
+    assert_eq!(hygiene(&policy, &[("x-tenant", b"acme")], "x-tenant"), Ok(vec![b"acme".to_vec()]));
+    assert_eq!(hygiene(&policy, &[("x-tenant", b" acme")], "x-tenant").unwrap_err(), "metadata 'x-tenant' has leading or trailing whitespace");
+    assert_eq!(hygiene(&policy, &[("x-tenant", b"acme\t")], "x-tenant").unwrap_err(), "metadata 'x-tenant' has leading or trailing whitespace");
+    assert_eq!(hygiene(&policy, &[("x-tenant", b"acme-corp")], "x-tenant").unwrap_err(), "metadata 'x-tenant' exceeds 8 bytes");
+    //Every value of key is checked
+    assert_eq!(hygiene(&policy, &[("x-tenant", b"acme"), ("x-tenant", b"acme ")], "x-tenant").unwrap_err(), "metadata 'x-tenant' has leading or trailing whitespace");
+
+    assert_eq!(hygiene(&policy, &[("x-forwarded-for", b"a\tb")], "x-forwarded-for").unwrap_err(), "metadata 'x-forwarded-for' contains control characters");
+    assert_eq!(hygiene(&policy, &[("x-forwarded-host", b"\tab")], "x-forwarded-host").unwrap_err(), "metadata 'x-forwarded-host' contains control characters");
+    assert_eq!(hygiene(&policy, &[("x-forwarded-host", b"a b\xff")], "x-forwarded-host"), Ok(vec![b"a b\xff".to_vec()]));
+
+    assert_eq!(hygiene(&policy, &[("x-name", b"J\xc3\xbcrgen")], "x-name").unwrap_err(), "metadata 'x-name' contains characters other than visible ASCII");
+    assert_eq!(hygiene(&policy, &[("x-name", b"a\tb")], "x-name").unwrap_err(), "metadata 'x-name' contains characters other than visible ASCII");
+    assert_eq!(hygiene(&policy, &[("x-name", b"John Smith ~")], "x-name"), Ok(vec![b"John Smith ~".to_vec()]));
+
+    //Failing keys are reported together, while keys without rules and binary keys are left alone
+    assert_eq!(hygiene(&policy, &[("x-tenant", b" a"), ("x-name", b"\x80"), ("x-other", b" \t"), ("x-tenant-bin", b" AQ")], "x-other").unwrap_err(), "metadata 'x-tenant' has leading or trailing whitespace, metadata 'x-name' contains characters other than visible ASCII");
+    let policy = policy.key("*", &[ValuePolicy::Trim]);
+    assert_eq!(hygiene(&policy, &[("x-tenant-bin", b"AQ ")], "x-tenant-bin"), Ok(vec![b"AQ ".to_vec()]));
+    assert!(hygiene(&policy, &[("x-other", b"a ")], "x-other").is_err());
+}
+
+#[test]
+fn should_repair_unhygienic_values_in_lenient_mode() {
+    use tonic_interceptor::policy::{ValueHygiene, ValuePolicy};
+
+    let policy = ValueHygiene::new().key("x-tenant", &[ValuePolicy::Trim, ValuePolicy::RequireVisibleAscii, ValuePolicy::MaxLen(4)])
+                                    .key("x-*", &[ValuePolicy::RejectControlChars])
+                                    .lenient();
+
+    assert_eq!(hygiene(&policy, &[("x-tenant", b" \tacme\t ")], "x-tenant"), Ok(vec![b"acme".to_vec()]));
+    assert_eq!(hygiene(&policy, &[("x-tenant", b"\xffacme-corp")], "x-tenant"), Ok(vec![b"acme".to_vec()]));
+    assert_eq!(hygiene(&policy, &[("x-tenant", b" a "), ("x-tenant", b"b\x80")], "x-tenant"), Ok(vec![b"a".to_vec(), b"b".to_vec()]));
+    //Value, which repairs to nothing, is kept empty
+    assert_eq!(hygiene(&policy, &[("x-tenant", b"\xc3\xbc")], "x-tenant"), Ok(vec![Vec::new()]));
+    assert_eq!(hygiene(&policy, &[("x-trace", b"a\tb\t")], "x-trace"), Ok(vec![b"ab".to_vec()]));
+    assert_eq!(hygiene(&policy, &[("x-trace", b"ab")], "x-trace"), Ok(vec![b"ab".to_vec()]));
+}
+
+fn check_authority(check: &AuthorityCheck, uri: &str, host: Option<&'static str>) -> Result<String, tonic::Status> {
+    let mut headers = http::HeaderMap::new();
+    if let Some(host) = host {
+        headers.insert(http::header::HOST, http::HeaderValue::from_static(host));
+    }
+    let mut extensions = http::Extensions::new();
+    match check.on_request_headers(&uri.parse().unwrap(), &mut headers, &mut extensions) {
+        None => Ok(Authority::from_extensions(&extensions).unwrap().to_string()),
+        Some(status) => Err(status),
+    }
+}
+
+#[test]
+fn should_check_authority() {
+    let check = AuthorityCheck::new().allow("api.example.com").unwrap()
+                                     .allow("*.internal.example.com").unwrap()
+                                     .allow("[::1]:8443").unwrap()
+                                     .allow("10.0.0.1:50051").unwrap();
+
+    assert_eq!(check_authority(&check, "http://API.Example.com./pkg.Svc/Call", None).unwrap(), "api.example.com");
+    assert_eq!(check_authority(&check, "http://a.b.internal.example.com/pkg.Svc/Call", None).unwrap(), "a.b.internal.example.com");
+    assert_eq!(check_authority(&check, "http://[0:0::1]:8443/pkg.Svc/Call", None).unwrap(), "[::1]:8443");
+    assert_eq!(check_authority(&check, "http://10.0.0.1:50051/pkg.Svc/Call", None).unwrap(), "10.0.0.1:50051");
+
+    //Wildcard doesn't match domain itself
+    let status = check_authority(&check, "http://internal.example.com/pkg.Svc/Call", None).unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+    assert_eq!(status.message(), "unexpected authority 'internal.example.com'");
+    assert!(check_authority(&check, "http://evilinternal.example.com/pkg.Svc/Call", None).is_err());
+    //Port must match
+    assert!(check_authority(&check, "http://api.example.com:443/pkg.Svc/Call", None).is_err());
+    assert!(check_authority(&check, "http://[::1]/pkg.Svc/Call", None).is_err());
+    //Direct-to-IP
+    assert_eq!(check_authority(&check, "http://192.0.2.1:50051/pkg.Svc/Call", None).unwrap_err().message(), "unexpected authority '192.0.2.1:50051'");
+    assert_eq!(check_authority(&check, "http://user@api.example.com/pkg.Svc/Call", None).unwrap_err().message(), "malformed authority");
+
+    let check = check.ignore_port(true).code(Code::Unimplemented);
+    assert_eq!(check_authority(&check, "http://api.example.com:443/pkg.Svc/Call", None).unwrap(), "api.example.com:443");
+    assert_eq!(check_authority(&check, "http://[::1]/pkg.Svc/Call", None).unwrap(), "[::1]");
+    assert_eq!(check_authority(&check, "http://example.com/pkg.Svc/Call", None).unwrap_err().code(), Code::Unimplemented);
+}
+
+#[test]
+fn should_fallback_to_host_header() {
+    let check = AuthorityCheck::new().allow("api.example.com").unwrap();
+    assert_eq!(check_authority(&check, "/pkg.Svc/Call", Some("api.example.com")).unwrap(), "api.example.com");
+    //URI takes precedence
+    assert!(check_authority(&check, "http://other.example.com/pkg.Svc/Call", Some("api.example.com")).is_err());
+    assert_eq!(check_authority(&check, "/pkg.Svc/Call", Some("other.example.com")).unwrap_err().message(), "unexpected authority 'other.example.com'");
+    assert_eq!(check_authority(&check, "/pkg.Svc/Call", Some("::1")).unwrap_err().message(), "malformed authority");
+    assert_eq!(check_authority(&check, "/pkg.Svc/Call", None).unwrap_err().message(), "missing authority");
+
+    let mut headers = MetadataMap::new();
+    headers.insert("host", "api.example.com".parse().unwrap());
+    let mut extensions = http::Extensions::new();
+    assert!(check.on_request_with_uri(&"/pkg.Svc/Call".parse().unwrap(), &mut headers, &mut extensions).is_none());
+    assert_eq!(Authority::from_extensions(&extensions).map(Authority::host), Some("api.example.com"));
+    assert!(check.on_request(&mut headers, &mut http::Extensions::new()).is_none());
+}
+
+#[test]
+fn should_parse_authority() {
+    let authority = Authority::parse("[2001:DB8::1]:443").unwrap();
+    assert_eq!(authority.host(), "2001:db8::1");
+    assert_eq!(authority.port(), Some(443));
+    assert_eq!(authority.to_string(), "[2001:db8::1]:443");
+
+    assert!(Authority::parse("").is_none());
+    assert!(Authority::parse("2001:db8::1").is_none());
+    assert!(Authority::parse("[not-ip]").is_none());
+    assert!(Authority::parse("example.com:").is_none());
+    assert!(Authority::parse("example.com:70000").is_none());
+    assert!(Authority::parse("exa mple.com").is_none());
+    assert_eq!(AuthorityCheck::new().allow("*.").unwrap_err().to_string(), "invalid authority '*.'");
+}
@@ -0,0 +1,155 @@
+#![cfg(feature = "tonic011")]
+use tonic_interceptor::{Interceptor, InterceptorChain};
+use tonic_interceptor::observe::MetricsSink;
+use tonic_interceptor::profile::{ProfileFn, ProfileMetrics, ProfileStats, Profiled, Stage, INTERCEPTOR_SECONDS};
+
+use core::time::Duration;
+use std::sync::{Arc, Mutex};
+
+const SLOW: Duration = Duration::from_millis(30);
+
+//Sleeps within configured stages
+struct Slow {
+    request: bool,
+    response: bool,
+}
+
+impl Interceptor for Slow {
+    fn on_request(&self, _: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+        if self.request {
+            std::thread::sleep(SLOW);
+        }
+        None
+    }
+
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+        if self.response {
+            std::thread::sleep(SLOW);
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match (self.request, self.response) {
+            (true, false) => "slow_request",
+            (false, true) => "slow_response",
+            _ => "slow",
+        }
+    }
+}
+
+struct Fast;
+
+impl Interceptor for Fast {
+    fn on_request(&self, _: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+        None
+    }
+
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
+
+fn call(interceptor: &dyn Interceptor) {
+    let uri = http::Uri::from_static("http://localhost/pkg.Echo/Unary");
+    let mut extensions = http::Extensions::new();
+    assert!(interceptor.on_request_headers(&uri, &mut http::HeaderMap::new(), &mut extensions).is_none());
+    interceptor.on_response(tonic::Code::Ok, &mut http::HeaderMap::new(), &extensions);
+}
+
+#[test]
+fn should_use_type_name_by_default() {
+    assert_eq!(Fast.name(), "profile::Fast");
+    assert_eq!(Arc::new(Fast).name(), "profile::Fast");
+    let boxed: tonic_interceptor::BoxedInterceptor = Arc::new(Fast);
+    assert_eq!(boxed.name(), "profile::Fast");
+    assert_eq!(Profiled::new(Fast, ProfileStats::new()).name(), "profile::Fast");
+}
+
+#[test]
+fn should_attribute_time_to_stage_of_interceptor() {
+    let stats = ProfileStats::new();
+    let chain = InterceptorChain::new().with(Fast).profile(stats.clone()).with(Slow {
+        request: true,
+        response: false,
+    }).with(Slow {
+        request: false,
+        response: true,
+    });
+    assert_eq!(format!("{:?}", chain), "InterceptorChain { len: 3, profiled: true }");
+    assert_eq!(format!("{:?}", InterceptorChain::new().with(Fast)), "InterceptorChain { len: 1, profiled: false }");
+
+    call(&chain);
+    call(&chain);
+
+    let request = stats.get("slow_request", Stage::Request).unwrap();
+    assert_eq!(request.count, 2);
+    assert!(request.total >= SLOW * 2, "{:?}", request);
+    assert!(request.max >= SLOW, "{:?}", request);
+    let response = stats.get("slow_request", Stage::Response).unwrap();
+    assert_eq!(response.count, 2);
+    assert!(response.max < SLOW, "{:?}", response);
+
+    let response = stats.get("slow_response", Stage::Response).unwrap();
+    assert!(response.total >= SLOW * 2, "{:?}", response);
+    assert!(stats.get("slow_response", Stage::Request).unwrap().max < SLOW);
+
+    assert!(stats.get("profile::Fast", Stage::Request).unwrap().max < SLOW);
+
+    let snapshot = stats.snapshot();
+    assert_eq!(snapshot.len(), 6);
+    let mut slowest: Vec<_> = snapshot[..2].iter().map(|(name, stage, _)| (*name, *stage)).collect();
+    slowest.sort_by_key(|(name, _)| *name);
+    assert_eq!(slowest, [("slow_request", Stage::Request), ("slow_response", Stage::Response)]);
+
+    stats.clear();
+    assert!(stats.snapshot().is_empty());
+}
+
+#[test]
+fn should_report_to_callback() {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let sink = {
+        let events = events.clone();
+        ProfileFn(move |name, stage, elapsed: Duration| events.lock().unwrap().push((name, stage, elapsed >= SLOW)))
+    };
+    call(&Profiled::new(Slow {
+        request: true,
+        response: false,
+    }, sink));
+    assert_eq!(*events.lock().unwrap(), [("slow_request", Stage::Request, true), ("slow_request", Stage::Response, false)]);
+}
+
+#[derive(Default)]
+struct Histograms {
+    records: Mutex<Vec<String>>,
+}
+
+impl MetricsSink for Histograms {
+    fn increment_counter(&self, _: &str, _: &[(&str, &str)]) {
+        unreachable!();
+    }
+
+    fn record_histogram(&self, name: &str, _: f64, labels: &[(&str, &str)]) {
+        let labels: Vec<_> = labels.iter().map(|(key, value)| format!("{}={}", key, value)).collect();
+        self.records.lock().unwrap().push(format!("{}{{{}}}", name, labels.join(",")));
+    }
+
+    fn increment_gauge(&self, _: &str, _: &[(&str, &str)]) {
+        unreachable!();
+    }
+
+    fn decrement_gauge(&self, _: &str, _: &[(&str, &str)]) {
+        unreachable!();
+    }
+}
+
+#[test]
+fn should_report_to_metrics() {
+    let metrics = Arc::new(Histograms::default());
+    call(&Profiled::new(Fast, ProfileMetrics::new(metrics.clone())));
+
+    let records = metrics.records.lock().unwrap();
+    assert_eq!(*records, [
+        format!("{}{{interceptor=profile::Fast,stage=request}}", INTERCEPTOR_SECONDS),
+        format!("{}{{interceptor=profile::Fast,stage=response}}", INTERCEPTOR_SECONDS),
+    ]);
+}
@@ -0,0 +1,121 @@
+#![cfg(all(feature = "tonic011", feature = "tokio"))]
+
+mod common;
+
+use common::EchoClient;
+use common::echo::{EchoRequest, EchoResponse};
+use common::echo::echo_server::{Echo, EchoServer};
+
+use tonic_interceptor::client;
+use tonic_interceptor::propagation::{Keys, Capture, Inject, PropagatedMetadata};
+
+use tonic::{Request, Response, Status};
+
+use core::pin::Pin;
+
+const PROPAGATED: Keys = Keys::new(&["x-request-id"]);
+
+fn request(message: &str) -> EchoRequest {
+    EchoRequest {
+        message: message.to_owned(),
+    }
+}
+
+type RelayChannel = client::ClientInterceptorService<Inject, tonic::transport::Channel>;
+
+///Calls itself with message `nested`, which responds with `x-request-id` it received
+struct Relay {
+    channel: RelayChannel,
+}
+
+#[tonic::async_trait]
+impl Echo for Relay {
+    async fn unary(&self, request: Request<EchoRequest>) -> Result<Response<EchoResponse>, Status> {
+        if request.get_ref().message == "nested" {
+            let request_id = match request.metadata().get("x-request-id") {
+                Some(value) => value.to_str().expect("ascii").to_owned(),
+                None => "none".to_owned(),
+            };
+            return Ok(Response::new(EchoResponse {
+                message: request_id,
+            }));
+        }
+
+        let mut client = EchoClient::new(self.channel.clone());
+        client.unary(self::request("nested")).await
+    }
+
+    type StreamStream = Pin<Box<dyn tokio_stream::Stream<Item = Result<EchoResponse, Status>> + Send>>;
+
+    async fn stream(&self, _: Request<EchoRequest>) -> Result<Response<Self::StreamStream>, Status> {
+        Err(Status::unimplemented("stream"))
+    }
+}
+
+async fn spawn_relay() -> RelayChannel {
+    let (incoming, addr) = common::listen().await;
+    let channel = tonic::transport::Endpoint::from_shared(addr).expect("valid url").connect_lazy();
+    let channel = tower::ServiceBuilder::new().layer(client::interceptor(Inject::new(PROPAGATED))).service(channel);
+
+    let server = tonic::transport::Server::builder().layer(Capture::new(PROPAGATED)).add_service(EchoServer::new(Relay {
+        channel: channel.clone(),
+    }));
+    tokio::spawn(server.serve_with_incoming(incoming));
+    channel
+}
+
+#[tokio::test]
+async fn should_propagate_request_id_to_nested_call() {
+    let channel = spawn_relay().await;
+    let mut client = EchoClient::new(channel);
+
+    let mut request = Request::new(request("relay"));
+    request.metadata_mut().insert("x-request-id", "req-1".parse().unwrap());
+    request.metadata_mut().insert("x-other", "ignored".parse().unwrap());
+    let response = client.unary(request).await.expect("success");
+    assert_eq!(response.get_ref().message, "req-1");
+}
+
+#[tokio::test]
+async fn should_ignore_missing_keys() {
+    let channel = spawn_relay().await;
+    let mut client = EchoClient::new(channel);
+
+    let response = client.unary(request("relay")).await.expect("success");
+    assert_eq!(response.get_ref().message, "none");
+}
+
+#[tokio::test]
+async fn should_not_inject_outside_of_scope() {
+    use tonic_interceptor::client::ClientInterceptor;
+
+    let inject = Inject::new(PROPAGATED);
+    let mut metadata = tonic::metadata::MetadataMap::new();
+    assert!(inject.on_request(&mut metadata, &mut http::Extensions::new()).is_none());
+    assert!(metadata.is_empty());
+}
+
+#[tokio::test]
+async fn should_keep_explicit_value() {
+    use tonic_interceptor::client::ClientInterceptor;
+
+    let mut headers = http::HeaderMap::new();
+    headers.insert("x-request-id", "propagated".parse().unwrap());
+    headers.insert("x-other", "ignored".parse().unwrap());
+    let propagated = PropagatedMetadata::capture(PROPAGATED, &headers);
+    assert_eq!(propagated.headers().len(), 1);
+
+    let inject = Inject::new(PROPAGATED);
+    propagated.clone().scope(async move {
+        let mut metadata = tonic::metadata::MetadataMap::new();
+        inject.on_request(&mut metadata, &mut http::Extensions::new());
+        assert_eq!(metadata.get("x-request-id").expect("injected"), "propagated");
+
+        let mut metadata = tonic::metadata::MetadataMap::new();
+        metadata.insert("x-request-id", "explicit".parse().unwrap());
+        inject.on_request(&mut metadata, &mut http::Extensions::new());
+        assert_eq!(metadata.get("x-request-id").expect("kept"), "explicit");
+    }).await;
+
+    assert!(PropagatedMetadata::current().is_none());
+}
@@ -0,0 +1,132 @@
+#![cfg(feature = "tonic011")]
+#![allow(clippy::result_large_err)]
+
+use tonic_interceptor::{Interceptor, InterceptorService, RawInterceptor, Raw, raw};
+use tonic_interceptor::testing::{poll_once, service_fn};
+
+use tonic::Status;
+use tower_service::Service;
+
+#[derive(Clone)]
+struct Metadata;
+
+impl Interceptor for Metadata {
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<Status> {
+        if headers.get("x-reject").is_some() {
+            return Some(Status::permission_denied("rejected"));
+        }
+        headers.insert("x-added", "1".parse().unwrap());
+        None
+    }
+
+    fn on_response(&self, _: tonic::Code, headers: &mut http::HeaderMap, _: &http::Extensions) {
+        headers.insert("x-response", http::HeaderValue::from_static("1"));
+    }
+}
+
+#[derive(Clone)]
+struct Headers;
+
+impl RawInterceptor for Headers {
+    fn on_request(&self, headers: &mut http::HeaderMap, _: &mut http::Extensions) -> Option<Status> {
+        if headers.contains_key("x-reject") {
+            return Some(Status::permission_denied("rejected"));
+        }
+        headers.insert("x-added", http::HeaderValue::from_static("1"));
+        None
+    }
+
+    fn on_response(&self, _: tonic::Code, headers: &mut http::HeaderMap, _: &http::Extensions) {
+        headers.insert("x-response", http::HeaderValue::from_static("1"));
+    }
+}
+
+//Returns headers seen by service and response headers
+fn call<I: Interceptor + Clone>(interceptor: I, headers: &http::HeaderMap) -> (Option<http::HeaderMap>, http::HeaderMap) {
+    let mut seen = None;
+    let response = {
+        let service = service_fn(|request: http::Request<()>| {
+            seen = Some(request.headers().clone());
+            Ok::<_, Status>(http::Response::new(()))
+        });
+        let mut service = InterceptorService::new(interceptor, service);
+        let mut request = http::Request::new(());
+        *request.headers_mut() = headers.clone();
+        poll_once(service.call(request)).expect("response")
+    };
+    (seen, response.headers().clone())
+}
+
+fn corpus() -> Vec<http::HeaderMap> {
+    let mut result = Vec::new();
+    result.push(http::HeaderMap::new());
+
+    let mut headers = http::HeaderMap::new();
+    headers.insert("x-user", http::HeaderValue::from_static("user"));
+    headers.append("x-user", http::HeaderValue::from_static("other"));
+    headers.insert("x-trace-bin", http::HeaderValue::from_static("not base64!"));
+    headers.insert("x-opaque", http::HeaderValue::from_bytes(b"\xfa\xfb").unwrap());
+    result.push(headers);
+
+    let mut headers = http::HeaderMap::new();
+    headers.insert("x-reject", http::HeaderValue::from_static("1"));
+    result.push(headers);
+
+    result
+}
+
+#[test]
+fn should_behave_same_as_metadata_interceptor() {
+    for headers in corpus() {
+        let (metadata_seen, metadata_response) = call(Metadata, &headers);
+        let (raw_seen, raw_response) = call(Raw(Headers), &headers);
+        assert_eq!(metadata_seen, raw_seen);
+        assert_eq!(metadata_response, raw_response);
+    }
+}
+
+#[test]
+fn should_convert_when_called_as_metadata_interceptor() {
+    let mut metadata = tonic::metadata::MetadataMap::new();
+    metadata.insert("x-user", "user".parse().unwrap());
+    assert!(Interceptor::on_request(&Raw(Headers), &mut metadata, &mut http::Extensions::new()).is_none());
+    assert_eq!(metadata.get("x-added").unwrap(), "1");
+    assert_eq!(metadata.get("x-user").unwrap(), "user");
+}
+
+#[test]
+fn should_decode_binary_same_as_metadata_map() {
+    const VALUES: &[&str] = &[
+        "", "AA", "AA==", "AA=", "AAE", "AAE=", "AAEC", "AAECAA=", "AQID/+8", "3q2+7w", "3q2+7w==",
+        "A", "=", "==", "A===", "AA===", "AAE==", "AAEC=", "AAECA===", "AB", "AAF", "A=A=", "AA=A", "AA AA", "AA-_", "not base64!",
+    ];
+    for value in VALUES {
+        let mut headers = http::HeaderMap::new();
+        headers.insert("x-value-bin", http::HeaderValue::from_static(value));
+        let expected = tonic::metadata::MetadataMap::from_headers(headers.clone()).get_bin("x-value-bin").unwrap().to_bytes().ok();
+        assert_eq!(raw::get_bin(&headers, "x-value-bin"), expected, "{}", value);
+    }
+}
+
+#[test]
+fn should_provide_metadata_helpers() {
+    let mut headers = http::HeaderMap::new();
+    raw::insert_bin(&mut headers, "x-trace-bin", b"\x00\x01binary");
+    headers.insert("x-user", http::HeaderValue::from_static("user"));
+    headers.insert("x-invalid-bin", http::HeaderValue::from_static("not base64!"));
+
+    assert_eq!(raw::get_bin(&headers, "x-trace-bin").unwrap().as_ref(), b"\x00\x01binary");
+    assert!(raw::get_bin(&headers, "x-invalid-bin").is_none());
+    assert!(raw::get_bin(&headers, "x-user").is_none());
+    assert_eq!(raw::get_ascii(&headers, "x-user").unwrap(), "user");
+    assert!(raw::get_ascii(&headers, "x-trace-bin").is_none());
+    assert!(raw::get_ascii(&headers, "x-missing").is_none());
+
+    let value = raw::with_metadata(&mut headers, |metadata| {
+        metadata.insert("x-added", "1".parse().unwrap());
+        metadata.get_bin("x-trace-bin").unwrap().to_bytes().unwrap()
+    });
+    assert_eq!(value.as_ref(), b"\x00\x01binary");
+    assert_eq!(headers.get("x-added").unwrap(), "1");
+    assert_eq!(headers.len(), 4);
+}
@@ -0,0 +1,259 @@
+#![cfg(feature = "tonic011")]
+#![allow(clippy::result_large_err)]
+
+#[cfg(feature = "transport")]
+mod common;
+
+use tonic_interceptor::Interceptor;
+use tonic_interceptor::net::{Cidr, ForwardedHeader, IpFilter, RealIp, ResolveRealIp};
+
+use tonic::metadata::MetadataMap;
+
+use std::net::{IpAddr, SocketAddr};
+
+fn resolver() -> ResolveRealIp {
+    ResolveRealIp::new().trust("10.0.0.0/8").expect("valid CIDR")
+                        .trust("2001:db8:ffff::/48").expect("valid CIDR")
+}
+
+fn ip(addr: &str) -> IpAddr {
+    addr.parse().unwrap()
+}
+
+//Resolves request from `peer`, returning its extensions
+fn resolve_extensions(resolver: &ResolveRealIp, peer: &str, entries: &[(&'static str, &'static str)]) -> http::Extensions {
+    let mut headers = MetadataMap::new();
+    for (key, value) in entries {
+        headers.append(*key, value.parse().unwrap());
+    }
+    let mut extensions = http::Extensions::new();
+    extensions.insert(SocketAddr::new(ip(peer), 50051));
+    assert!(resolver.on_request(&mut headers, &mut extensions).is_none());
+    //Metadata is left intact
+    assert_eq!(headers.len(), entries.len());
+    extensions
+}
+
+fn resolve(resolver: &ResolveRealIp, peer: &str, entries: &[(&'static str, &'static str)]) -> Option<IpAddr> {
+    let extensions = resolve_extensions(resolver, peer, entries);
+    let resolved = RealIp::from_extensions(&extensions).map(|addr| addr.0);
+    //Unresolved client is not mistaken for peer
+    assert_eq!(RealIp::client(&extensions), resolved);
+    resolved
+}
+
+#[test]
+fn should_parse_cidr() {
+    let cidr: Cidr = "10.1.2.3/8".parse().unwrap();
+    assert_eq!(cidr.to_string(), "10.0.0.0/8");
+    assert!(cidr.contains(ip("10.255.0.1")));
+    assert!(cidr.contains(ip("::ffff:10.0.0.1")));
+    assert!(!cidr.contains(ip("11.0.0.1")));
+    assert!(!cidr.contains(ip("::1")));
+
+    let cidr: Cidr = "::1".parse().unwrap();
+    assert_eq!(cidr.prefix(), 128);
+    assert!(cidr.contains(ip("::1")));
+    assert!(!cidr.contains(ip("::2")));
+
+    let cidr: Cidr = "::ffff:192.168.0.0/112".parse().unwrap();
+    assert_eq!(cidr.to_string(), "192.168.0.0/16");
+
+    assert!("0.0.0.0/0".parse::<Cidr>().unwrap().contains(ip("8.8.8.8")));
+    assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+    assert!("10.0.0/8".parse::<Cidr>().is_err());
+    assert_eq!("10.0.0.0/x".parse::<Cidr>().unwrap_err().to_string(), "invalid CIDR '10.0.0.0/x'");
+    assert!(ResolveRealIp::new().trust("localhost").is_err());
+}
+
+#[test]
+fn should_use_peer_unless_trusted() {
+    let resolver = resolver();
+    //Headers of untrusted peer are spoofable
+    assert_eq!(resolve(&resolver, "192.0.2.1", &[("x-forwarded-for", "198.51.100.7")]), Some(ip("192.0.2.1")));
+    assert_eq!(resolve(&resolver, "192.0.2.1", &[]), Some(ip("192.0.2.1")));
+    //Trusted proxy without headers
+    assert_eq!(resolve(&resolver, "10.0.0.1", &[]), Some(ip("10.0.0.1")));
+
+    //Unknown peer
+    let mut extensions = http::Extensions::new();
+    let mut headers = MetadataMap::new();
+    headers.insert("x-forwarded-for", "198.51.100.7".parse().unwrap());
+    assert!(resolver.on_request(&mut headers, &mut extensions).is_none());
+    assert!(RealIp::from_extensions(&extensions).is_none());
+
+    //Custom peer extractor
+    let resolver = resolver.peer_with(|_| Some("10.0.0.2".parse().unwrap()));
+    resolver.on_request(&mut headers, &mut extensions);
+    assert_eq!(RealIp::from_extensions(&extensions), Some(&RealIp(ip("198.51.100.7"))));
+}
+
+#[test]
+fn should_walk_x_forwarded_for_from_the_right() {
+    let resolver = resolver();
+    assert_eq!(resolve(&resolver, "10.0.0.1", &[("x-forwarded-for", "198.51.100.7")]), Some(ip("198.51.100.7")));
+    assert_eq!(resolve(&resolver, "10.0.0.1", &[("x-forwarded-for", "203.0.113.9, 198.51.100.7, 10.0.0.3, 10.0.0.2")]), Some(ip("198.51.100.7")));
+    //Multiple headers form single list
+    assert_eq!(resolve(&resolver, "10.0.0.1", &[("x-forwarded-for", "198.51.100.7"), ("x-forwarded-for", "10.0.0.3")]), Some(ip("198.51.100.7")));
+    //Ports and brackets
+    assert_eq!(resolve(&resolver, "10.0.0.1", &[("x-forwarded-for", "198.51.100.7:4711, 10.0.0.3:80")]), Some(ip("198.51.100.7")));
+    assert_eq!(resolve(&resolver, "10.0.0.1", &[("x-forwarded-for", "[2001:db8::7]:4711, 2001:db8:ffff::1")]), Some(ip("2001:db8::7")));
+    assert_eq!(resolve(&resolver, "::ffff:10.0.0.1", &[("x-forwarded-for", "::ffff:198.51.100.7")]), Some(ip("198.51.100.7")));
+}
+
+#[test]
+fn should_fallback_on_malformed_or_trusted_chain() {
+    let resolver = resolver();
+    //All hops are trusted: leftmost is client
+    assert_eq!(resolve(&resolver, "10.0.0.1", &[("x-forwarded-for", "10.0.0.5, 10.0.0.3")]), Some(ip("10.0.0.5")));
+    //Malformed entry stops walk at the last trusted hop
+    assert_eq!(resolve(&resolver, "10.0.0.1", &[("x-forwarded-for", "198.51.100.7, garbage, 10.0.0.3")]), Some(ip("10.0.0.3")));
+    //Malformed rightmost entry leaves client unresolved, without falling back to the next header
+    assert_eq!(resolve(&resolver, "10.0.0.1", &[("x-forwarded-for", "198.51.100.7, garbage")]), None);
+    assert_eq!(resolve(&resolver, "10.0.0.1", &[("x-forwarded-for", "198.51.100.7,"), ("x-real-ip", "203.0.113.9")]), None);
+    assert_eq!(resolve(&resolver, "10.0.0.1", &[("x-real-ip", "unknown")]), None);
+}
+
+#[test]
+fn should_parse_forwarded() {
+    let resolver = resolver();
+    assert_eq!(resolve(&resolver, "10.0.0.1", &[("forwarded", "for=198.51.100.7;proto=https;by=10.0.0.1")]), Some(ip("198.51.100.7")));
+    assert_eq!(resolve(&resolver, "10.0.0.1", &[("forwarded", "For=\"[2001:db8::7]:4711\", for=10.0.0.3")]), Some(ip("2001:db8::7")));
+    assert_eq!(resolve(&resolver, "10.0.0.1", &[("forwarded", "for=203.0.113.9, for=\"198.51.100.7:80\";host=\"a;b,c\"")]), Some(ip("198.51.100.7")));
+    //Obfuscated and unknown nodes are malformed
+    assert_eq!(resolve(&resolver, "10.0.0.1", &[("forwarded", "for=198.51.100.7, for=unknown, for=10.0.0.3")]), Some(ip("10.0.0.3")));
+    //Client controls headers to the left of proxy, which might not set preferred one
+    assert_eq!(resolve(&resolver, "10.0.0.1", &[("forwarded", "for=_hidden"), ("x-forwarded-for", "203.0.113.9")]), None);
+    assert_eq!(resolve(&resolver, "10.0.0.1", &[("forwarded", "proto=https"), ("x-forwarded-for", "203.0.113.9")]), None);
+}
+
+#[test]
+fn should_follow_header_preference() {
+    let entries = [("x-real-ip", "192.0.2.9"), ("x-forwarded-for", "203.0.113.9"), ("forwarded", "for=198.51.100.7")];
+    assert_eq!(resolve(&resolver(), "10.0.0.1", &entries), Some(ip("198.51.100.7")));
+
+    let resolver = resolver().headers(&[ForwardedHeader::XRealIp, ForwardedHeader::Forwarded]);
+    assert_eq!(resolve(&resolver, "10.0.0.1", &entries), Some(ip("192.0.2.9")));
+
+    let resolver = ResolveRealIp::new().trust("10.0.0.0/8").unwrap().headers(&[ForwardedHeader::XRealIp]);
+    assert_eq!(resolve(&resolver, "10.0.0.1", &entries[1..]), Some(ip("10.0.0.1")));
+}
+
+#[test]
+fn should_resolve_from_http_headers() {
+    let resolver = resolver();
+    let mut headers = http::HeaderMap::new();
+    headers.insert("x-forwarded-for", http::HeaderValue::from_static("198.51.100.7, 10.0.0.3"));
+    let mut extensions = http::Extensions::new();
+    extensions.insert(SocketAddr::new(ip("10.0.0.1"), 50051));
+    assert!(resolver.on_request_headers(&"/pkg.Svc/Call".parse().unwrap(), &mut headers, &mut extensions).is_none());
+    assert_eq!(RealIp::peer(&extensions).as_deref(), Some("198.51.100.7"));
+    assert_eq!(RealIp::rate_limit_key(&MetadataMap::new(), &extensions, "/pkg.Svc/Call").as_deref(), Some("198.51.100.7"));
+}
+
+#[test]
+fn should_prefer_real_ip_over_peer() {
+    let mut extensions = http::Extensions::new();
+    assert_eq!(RealIp::client(&extensions), None);
+    extensions.insert(SocketAddr::new(ip("::ffff:192.0.2.1"), 50051));
+    assert_eq!(RealIp::client(&extensions), Some(ip("192.0.2.1")));
+    extensions.insert(RealIp(ip("198.51.100.7")));
+    assert_eq!(RealIp::client(&extensions), Some(ip("198.51.100.7")));
+    assert_eq!(RealIp::peer(&extensions).as_deref(), Some("198.51.100.7"));
+}
+
+#[test]
+fn should_filter_by_client_address() {
+    let filter = IpFilter::new().allow("198.51.100.0/24").unwrap().deny("198.51.100.66").unwrap();
+    let admit = |filter: &IpFilter, extensions: &mut http::Extensions| match filter.on_request(&mut MetadataMap::new(), extensions) {
+        None => true,
+        Some(status) => {
+            assert_eq!(status.code(), tonic::Code::PermissionDenied);
+            false
+        },
+    };
+
+    //Address of proxy is not one of client
+    let resolver = resolver();
+    assert!(admit(&filter, &mut resolve_extensions(&resolver, "10.0.0.1", &[("x-forwarded-for", "198.51.100.7")])));
+    assert!(!admit(&filter, &mut resolve_extensions(&resolver, "10.0.0.1", &[("x-forwarded-for", "198.51.100.66")])));
+    assert!(!admit(&filter, &mut resolve_extensions(&resolver, "10.0.0.1", &[("x-forwarded-for", "203.0.113.9")])));
+    assert!(!admit(&filter, &mut resolve_extensions(&resolver, "10.0.0.1", &[])));
+    assert!(!admit(&filter, &mut resolve_extensions(&resolver, "10.0.0.1", &[("x-forwarded-for", "garbage")])));
+
+    //Without resolver peer is client
+    let mut extensions = http::Extensions::new();
+    assert!(!admit(&filter, &mut extensions));
+    extensions.insert(SocketAddr::new(ip("198.51.100.1"), 50051));
+    assert!(admit(&filter, &mut extensions));
+
+    //Only denied blocks
+    let filter = IpFilter::new().deny("10.0.0.0/8").unwrap();
+    assert!(admit(&filter, &mut http::Extensions::new()));
+    assert!(!admit(&filter, &mut resolve_extensions(&resolver, "10.0.0.1", &[])));
+    assert!(admit(&filter, &mut resolve_extensions(&resolver, "10.0.0.1", &[("x-forwarded-for", "198.51.100.7")])));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn should_rate_limit_per_client() {
+    use tonic_interceptor::config::RateLimit;
+    use core::time::Duration;
+
+    let resolver = resolver();
+    let limit = RateLimit::new(1, Duration::from_secs(60)).per_client(true);
+    let call = |forwarded_for: &'static str| limit.on_request(&mut MetadataMap::new(), &mut resolve_extensions(&resolver, "10.0.0.1", &[("x-forwarded-for", forwarded_for)])).map(|status| status.code());
+    assert_eq!(call("198.51.100.7"), None);
+    assert_eq!(call("198.51.100.8"), None);
+    assert_eq!(call("198.51.100.7"), Some(tonic::Code::ResourceExhausted));
+    //Unresolved requests share window
+    assert_eq!(call("garbage"), None);
+    assert_eq!(call("_hidden"), Some(tonic::Code::ResourceExhausted));
+}
+
+#[test]
+fn should_record_client_address_of_rejection() {
+    use tonic_interceptor::observe::RejectionLog;
+
+    let log = RejectionLog::new(IpFilter::new().deny("198.51.100.7").unwrap(), 1);
+    let mut extensions = resolve_extensions(&resolver(), "10.0.0.1", &[("x-forwarded-for", "198.51.100.7")]);
+    assert!(log.on_request_with_uri(&"/pkg.Svc/Call".parse().unwrap(), &mut MetadataMap::new(), &mut extensions).is_some());
+    assert_eq!(log.handle().recent()[0].peer.as_deref(), Some("198.51.100.7"));
+}
+
+#[cfg(feature = "transport")]
+#[tokio::test]
+async fn should_resolve_peer_of_server_connection() {
+    use common::{EchoClient, EchoServer, EchoService, user_request};
+
+    //Exposes resolved address to echo handler
+    #[derive(Clone)]
+    struct Expose;
+
+    impl Interceptor for Expose {
+        fn on_request(&self, headers: &mut MetadataMap, extensions: &mut http::Extensions) -> Option<tonic::Status> {
+            let addr = RealIp::peer(extensions).unwrap_or_else(|| "none".to_owned());
+            headers.insert("x-real-ip", addr.parse().unwrap());
+            None
+        }
+
+        fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+        }
+    }
+
+    let chain = tonic_interceptor::InterceptorChain::new().with(ResolveRealIp::new().trust("127.0.0.1").unwrap()).with(Expose);
+    let service = EchoService::default();
+    let (incoming, addr) = common::listen().await;
+    let router = tonic::transport::Server::builder().layer(tonic_interceptor::interceptor(chain)).add_service(EchoServer::new(service.clone()));
+    tokio::spawn(router.serve_with_incoming(incoming));
+    let mut client = EchoClient::new(common::connect(addr).await);
+
+    let response = client.unary(user_request("alice")).await.expect("success");
+    assert_eq!(response.metadata().get("x-real-ip").unwrap(), "127.0.0.1");
+
+    //Connection comes from trusted proxy
+    let mut request = user_request("alice");
+    request.metadata_mut().insert("x-forwarded-for", "198.51.100.7".parse().unwrap());
+    let response = client.unary(request).await.expect("success");
+    assert_eq!(response.metadata().get("x-real-ip").unwrap(), "198.51.100.7");
+}
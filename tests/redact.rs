@@ -0,0 +1,103 @@
+#![cfg(feature = "tonic011")]
+use tonic_interceptor::redact::{Redactor, MASK};
+
+use tonic::metadata::{MetadataMap, BinaryMetadataValue};
+
+const JWT: &str = "[A-Za-z0-9_-]{8,}[.][A-Za-z0-9_-]{8,}[.][A-Za-z0-9_-]{8,}";
+
+fn redactor() -> Redactor {
+    Redactor::new().key("authorization").key("Cookie").prefix("x-secret-").pattern(JWT).expect("valid pattern")
+}
+
+//Deterministic pseudo-random generator, so that failures are reproducible
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        self.0 >> 33
+    }
+
+    fn string(&mut self, alphabet: &[u8], min: usize, max: usize) -> String {
+        let len = min + self.next() as usize % (max - min);
+        (0..len).map(|_| alphabet[self.next() as usize % alphabet.len()] as char).collect()
+    }
+}
+
+const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789_-";
+
+#[test]
+fn should_mask_configured_values() {
+    let mut metadata = MetadataMap::new();
+    metadata.insert("authorization", "Bearer 0123456789".parse().unwrap());
+    metadata.insert("cookie", "session=abc".parse().unwrap());
+    metadata.insert("x-secret-token", "s3cr3t".parse().unwrap());
+    metadata.insert("x-request-id", "req-1".parse().unwrap());
+    metadata.insert("x-forwarded", "eyJhbGciOi.eyJzdWIiOiIx.SflKxwRJSMeK".parse().unwrap());
+    metadata.insert("x-note", "a.b.c".parse().unwrap());
+    metadata.insert_bin("x-secret-bin", BinaryMetadataValue::from_bytes(&[1, 2]));
+    metadata.insert_bin("x-trace-bin", BinaryMetadataValue::from_bytes(&[1, 2]));
+
+    let snapshot = redactor().snapshot(&metadata);
+    let expected = [
+        ("authorization", MASK),
+        ("cookie", MASK),
+        ("x-secret-token", MASK),
+        ("x-request-id", "req-1"),
+        ("x-forwarded", MASK),
+        ("x-note", "a.b.c"),
+        ("x-secret-bin", MASK),
+        ("x-trace-bin", "AQI"),
+    ];
+    assert_eq!(snapshot.len(), expected.len());
+    for (key, value) in expected {
+        assert!(snapshot.contains(&(key.to_owned(), value.to_owned())), "{}: {}", key, value);
+    }
+
+    let mut metadata = MetadataMap::new();
+    metadata.insert("authorization", "Bearer 0123456789".parse().unwrap());
+    metadata.insert("x-request-id", "req-1".parse().unwrap());
+    assert_eq!(redactor().display(&metadata).to_string(), "authorization: ***, x-request-id: req-1");
+    assert_eq!(redactor().keep(2).display(&metadata).to_string(), "authorization: Be***89, x-request-id: req-1");
+    //Short value is masked whole
+    assert_eq!(redactor().keep(5).redact("authorization", "Bearer 0123456789"), "***");
+    assert_eq!(format!("{:?}", redactor().display(&MetadataMap::new())), "");
+}
+
+#[test]
+fn should_never_print_secret_value() {
+    let mut rng = Rng(42);
+    for keep in [0, 1, 3] {
+        let redactor = redactor().keep(keep);
+        for _ in 0..500 {
+            let mut secrets = Vec::new();
+            let mut metadata = MetadataMap::new();
+
+            let token = format!("Bearer {}", rng.string(ALPHABET, 12, 52));
+            metadata.insert("authorization", token.parse().unwrap());
+            secrets.push(token);
+
+            let key = format!("x-secret-{}", rng.string(b"abcdefghijklmnopqrstuvwxyz", 1, 8));
+            let value = rng.string(ALPHABET, 8, 24);
+            metadata.insert(tonic::metadata::AsciiMetadataKey::from_bytes(key.as_bytes()).unwrap(), value.parse().unwrap());
+            secrets.push(value);
+
+            let jwt = [rng.string(ALPHABET, 10, 40), rng.string(ALPHABET, 10, 40), rng.string(ALPHABET, 10, 40)].join(".");
+            let forwarded = format!("for=1.2.3.4; token={}", jwt);
+            metadata.insert("x-forwarded", forwarded.parse().unwrap());
+            secrets.push(jwt);
+
+            let public = rng.string(b"abcdefghijklmnopqrstuvwxyz", 12, 13);
+            metadata.insert("x-request-id", public.parse().unwrap());
+
+            let mut written = String::new();
+            redactor.write(&metadata, &mut written).unwrap();
+            let snapshot = redactor.snapshot(&metadata);
+            for secret in secrets.iter() {
+                assert!(!written.contains(secret.as_str()), "'{}' in '{}'", secret, written);
+                assert!(snapshot.iter().all(|(_, value)| !value.contains(secret.as_str())), "'{}' in {:?}", secret, snapshot);
+            }
+            assert!(written.contains(&public));
+        }
+    }
+}
@@ -0,0 +1,84 @@
+#![cfg(feature = "tonic011")]
+#![allow(clippy::result_large_err)]
+
+use tonic_interceptor::{InterceptorFn, InterceptorService, reject};
+use tonic_interceptor::testing::{poll_once, service_fn};
+
+use tonic::{Code, Status};
+use tower_service::Service;
+
+use core::time::Duration;
+
+//Returns headers of rejection response for `status`
+fn respond(status: Status) -> http::HeaderMap {
+    let interceptor = InterceptorFn {
+        on_request: move |_: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions| Some(status.clone()),
+        on_response: |_: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions| {
+        }
+    };
+    let mut service = InterceptorService::new(interceptor, service_fn(|_: http::Request<()>| Ok::<_, Status>(http::Response::new(()))));
+    poll_once(service.call(http::Request::new(()))).expect("response").headers().clone()
+}
+
+#[test]
+fn should_attach_authentication_challenge() {
+    let status = reject::unauthenticated_with("api");
+    assert_eq!(status.code(), Code::Unauthenticated);
+    assert_eq!(status.metadata().get("www-authenticate").unwrap(), "Bearer realm=\"api\"");
+
+    let headers = respond(status);
+    assert_eq!(headers.get("grpc-status").unwrap(), "16");
+    assert_eq!(headers.get("www-authenticate").unwrap(), "Bearer realm=\"api\"");
+
+    let status = reject::unauthenticated_with("my \"quoted\\\" realm");
+    assert_eq!(status.metadata().get("www-authenticate").unwrap(), "Bearer realm=\"my \\\"quoted\\\\\\\" realm\"");
+
+    let status = reject::unauthenticated_with("new\nline");
+    assert_eq!(status.code(), Code::Unauthenticated);
+    assert!(status.metadata().get("www-authenticate").is_none());
+}
+
+#[test]
+fn should_attach_retry_after() {
+    let status = reject::rate_limited(Duration::from_millis(1500));
+    assert_eq!(status.code(), Code::ResourceExhausted);
+    assert_eq!(status.message(), "rate limit exceeded");
+    assert_eq!(status.metadata().get("retry-after").unwrap(), "2");
+    assert_eq!(reject::rate_limited(Duration::from_secs(3)).metadata().get("retry-after").unwrap(), "3");
+    assert_eq!(reject::rate_limited(Duration::ZERO).metadata().get("retry-after").unwrap(), "0");
+
+    let headers = respond(status.clone());
+    assert_eq!(headers.get("grpc-status").unwrap(), "8");
+    assert_eq!(headers.get("retry-after").unwrap(), "2");
+
+    #[cfg(feature = "details")]
+    {
+        let details = tonic_interceptor::details::StatusDetails::from_status(&status).expect("details");
+        let delay = details.retry_info.expect("retry info").retry_delay.expect("delay");
+        assert_eq!((delay.seconds, delay.nanos), (1, 500_000_000));
+        assert!(headers.contains_key("grpc-status-details-bin"));
+    }
+    #[cfg(not(feature = "details"))]
+    assert!(status.details().is_empty());
+}
+
+#[test]
+fn should_describe_precondition() {
+    let status = reject::precondition("x-tenant", "must match token tenant");
+    assert_eq!(status.code(), Code::FailedPrecondition);
+    assert_eq!(status.message(), "precondition failed for 'x-tenant': must match token tenant");
+
+    let headers = respond(status.clone());
+    assert_eq!(headers.get("grpc-status").unwrap(), "9");
+
+    #[cfg(feature = "details")]
+    {
+        let details = tonic_interceptor::details::StatusDetails::from_status(&status).expect("details");
+        let violation = &details.precondition_failure.expect("precondition failure").violations[0];
+        assert_eq!(violation.r#type, "FIELD");
+        assert_eq!(violation.subject, "x-tenant");
+        assert_eq!(violation.description, "must match token tenant");
+    }
+    #[cfg(not(feature = "details"))]
+    assert!(status.details().is_empty());
+}
@@ -0,0 +1,155 @@
+#![cfg(all(feature = "tonic011", feature = "tokio"))]
+#![allow(clippy::result_large_err)]
+
+use tonic_interceptor::Interceptor;
+use tonic_interceptor::reload::{FromConfig, Watched};
+
+use tokio::sync::watch;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+//Rejects requests with more than configured number of entries
+struct MaxEntries(usize);
+
+impl FromConfig<usize> for MaxEntries {
+    type Error = &'static str;
+
+    fn from_config(config: &usize) -> Result<Self, Self::Error> {
+        match *config {
+            0 => Err("limit must be positive"),
+            limit => Ok(MaxEntries(limit)),
+        }
+    }
+}
+
+impl Interceptor for MaxEntries {
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<tonic::Status> {
+        match headers.len() > self.0 {
+            true => Some(tonic::Status::resource_exhausted("too many entries")),
+            false => None,
+        }
+    }
+
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
+
+fn entries(interceptor: &impl Interceptor, count: usize) -> Option<tonic::Code> {
+    let mut headers = tonic::metadata::MetadataMap::new();
+    for idx in 0..count {
+        headers.insert(tonic::metadata::AsciiMetadataKey::from_bytes(format!("x-entry-{}", idx).as_bytes()).unwrap(), "1".parse().unwrap());
+    }
+    interceptor.on_request(&mut headers, &mut http::Extensions::new()).map(|status| status.code())
+}
+
+static INVALID: AtomicUsize = AtomicUsize::new(0);
+
+#[test]
+fn should_rebuild_interceptor_on_change() {
+    let (sender, receiver) = watch::channel(2);
+    let interceptor = Watched::<usize, MaxEntries>::new(receiver).expect("valid config").on_invalid(|error| {
+        assert_eq!(*error, "limit must be positive");
+        INVALID.fetch_add(1, Ordering::SeqCst);
+    });
+    let clone = interceptor.clone();
+    assert_eq!(entries(&interceptor, 3), Some(tonic::Code::ResourceExhausted));
+
+    sender.send(3).unwrap();
+    assert_eq!(entries(&interceptor, 3), None);
+    assert_eq!(entries(&interceptor, 4), Some(tonic::Code::ResourceExhausted));
+    //Unchanged configuration keeps the same interceptor
+    assert!(std::sync::Arc::ptr_eq(&interceptor.current(), &clone.current()));
+
+    //Invalid configuration keeps previous interceptor
+    sender.send(0).unwrap();
+    assert_eq!(entries(&clone, 3), None);
+    assert_eq!(entries(&interceptor, 3), None);
+    assert_eq!(INVALID.load(Ordering::SeqCst), 1);
+
+    sender.send(1).unwrap();
+    drop(sender);
+    assert_eq!(entries(&interceptor, 2), Some(tonic::Code::ResourceExhausted));
+    assert_eq!(interceptor.current().0, 1);
+    assert_eq!(INVALID.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn should_reject_invalid_initial_config() {
+    let (_sender, receiver) = watch::channel(0);
+    assert_eq!(Watched::<usize, MaxEntries>::new(receiver).unwrap_err(), "limit must be positive");
+}
+
+#[cfg(feature = "serde")]
+mod builtin {
+    use super::*;
+
+    use tonic_interceptor::BoxedInterceptor;
+    use tonic_interceptor::config::{ChainConfig, InterceptorConfig, MethodAllowlist, Param, RateLimit};
+
+    fn call(interceptor: &impl Interceptor, path: &str) -> Option<tonic::Code> {
+        let uri = format!("http://localhost{}", path).parse::<http::Uri>().unwrap();
+        interceptor.on_request_with_uri(&uri, &mut tonic::metadata::MetadataMap::new(), &mut http::Extensions::new()).map(|status| status.code())
+    }
+
+    fn allowlist(methods: &[&str]) -> InterceptorConfig {
+        InterceptorConfig::new("method_allowlist").param("methods", Param::List(methods.iter().map(|method| Param::Str(method.to_string())).collect()))
+    }
+
+    #[test]
+    fn should_reload_builtin_interceptor() {
+        let (sender, receiver) = watch::channel(allowlist(&["/pkg.Users/*"]));
+        let interceptor = Watched::<_, MethodAllowlist>::new(receiver).expect("valid config");
+        assert_eq!(call(&interceptor, "/pkg.Users/Get"), None);
+        assert_eq!(call(&interceptor, "/pkg.Orders/List"), Some(tonic::Code::PermissionDenied));
+
+        sender.send(allowlist(&["/pkg.Users/*", "/pkg.Orders/List"])).unwrap();
+        assert_eq!(call(&interceptor, "/pkg.Orders/List"), None);
+
+        sender.send(allowlist(&[])).unwrap();
+        assert_eq!(call(&interceptor, "/pkg.Orders/List"), None);
+    }
+
+    #[test]
+    fn should_reset_rate_limit_on_reload() {
+        let config = |requests| InterceptorConfig::new("rate_limit").param("requests", Param::Int(requests)).param("period_secs", Param::Int(3600));
+        let (sender, receiver) = watch::channel(config(1));
+        let interceptor = Watched::<_, RateLimit>::new(receiver).expect("valid config");
+        assert_eq!(call(&interceptor, "/pkg.Users/Get"), None);
+        assert_eq!(call(&interceptor, "/pkg.Users/Get"), Some(tonic::Code::ResourceExhausted));
+
+        sender.send(config(2)).unwrap();
+        assert_eq!(call(&interceptor, "/pkg.Users/Get"), None);
+        assert_eq!(call(&interceptor, "/pkg.Users/Get"), None);
+        assert_eq!(call(&interceptor, "/pkg.Users/Get"), Some(tonic::Code::ResourceExhausted));
+    }
+
+    #[test]
+    fn should_report_errors_of_builtin_config() {
+        let error = RateLimit::from_config(&InterceptorConfig::new("rate_limit")).unwrap_err();
+        assert_eq!(error.to_string(), "rate_limit.requests: missing parameter of 'rate_limit'");
+        let error = RateLimit::from_config(&allowlist(&["/pkg.Users/*"])).unwrap_err();
+        assert_eq!(error.to_string(), "method_allowlist.name: expected 'rate_limit', found 'method_allowlist'");
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn should_report_errors_of_logging_config() {
+        use tonic_interceptor::config::Logging;
+
+        let error = Logging::from_config(&InterceptorConfig::new("logging").param("redact", Param::Str("authorization".to_owned()))).unwrap_err();
+        assert_eq!(error.to_string(), "logging.redact: expected list of strings, found string");
+        assert!(Logging::from_config(&InterceptorConfig::new("logging").param("redact", Param::List(vec![Param::Str("authorization".to_owned())]))).is_ok());
+    }
+
+    #[test]
+    fn should_reload_chain() {
+        let (sender, receiver) = watch::channel(ChainConfig::default());
+        let interceptor = Watched::<_, BoxedInterceptor>::new(receiver).expect("valid config");
+        assert_eq!(call(&interceptor, "/pkg.Orders/List"), None);
+
+        sender.send(ChainConfig {
+            interceptors: vec![allowlist(&["/pkg.Users/*"])],
+        }).unwrap();
+        assert_eq!(call(&interceptor, "/pkg.Orders/List"), Some(tonic::Code::PermissionDenied));
+    }
+}
@@ -0,0 +1,112 @@
+#![cfg(feature = "tonic011")]
+use tonic_interceptor::policy::RemapStatus;
+use tonic_interceptor::testing::{poll_once, service_fn};
+
+use tonic::Code;
+use tower_layer::Layer;
+use tower_service::Service;
+use http_body::Body;
+
+use core::pin::Pin;
+use core::convert::Infallible;
+
+//Body without data, which ends with trailers
+#[derive(Default)]
+struct Trailers(Option<http::HeaderMap>);
+
+impl Body for Trailers {
+    type Data = bytes::Bytes;
+    type Error = Infallible;
+
+    fn poll_data(self: Pin<&mut Self>, _: &mut core::task::Context<'_>) -> core::task::Poll<Option<Result<Self::Data, Self::Error>>> {
+        core::task::Poll::Ready(None)
+    }
+
+    fn poll_trailers(mut self: Pin<&mut Self>, _: &mut core::task::Context<'_>) -> core::task::Poll<Result<Option<http::HeaderMap>, Self::Error>> {
+        core::task::Poll::Ready(Ok(self.0.take()))
+    }
+}
+
+fn status(code: Code, message: &'static str, details: bool) -> http::HeaderMap {
+    let mut headers = http::HeaderMap::new();
+    headers.insert("grpc-status", http::HeaderValue::from(code as i32));
+    if !message.is_empty() {
+        headers.insert("grpc-message", http::HeaderValue::from_static(message));
+    }
+    if details {
+        headers.insert("grpc-status-details-bin", http::HeaderValue::from_static("AQI"));
+    }
+    headers
+}
+
+fn remap() -> RemapStatus {
+    RemapStatus::new().rule("/pkg.Search/Query", Code::NotFound, Code::Ok)
+                      .rule_with_message("/pkg.Legacy/*", Code::Internal, Code::Unavailable, "temporarily unavailable: 100%")
+                      .rule_with_message("/pkg.Legacy/Debug", Code::Internal, Code::Internal, "redacted")
+                      .rule("*", Code::DataLoss, Code::Internal)
+}
+
+//Returns response headers and trailers of call to `path`
+fn call(path: &str, headers: http::HeaderMap, trailers: Option<http::HeaderMap>) -> (http::HeaderMap, Option<http::HeaderMap>) {
+    let mut service = remap().layer(service_fn(move |_: http::Request<()>| {
+        let mut response = http::Response::new(Trailers(trailers.clone()));
+        *response.headers_mut() = headers.clone();
+        Ok::<_, Infallible>(response)
+    }));
+    let request = http::Request::builder().uri(path).body(()).unwrap();
+    let response = poll_once(service.call(request)).expect("response");
+    let (parts, mut body) = response.into_parts();
+    let trailers = poll_once(core::future::poll_fn(|ctx| Pin::new(&mut body).poll_trailers(ctx))).expect("trailers");
+    (parts.headers, trailers)
+}
+
+#[test]
+fn should_remap_code_of_exact_method() {
+    let (headers, _) = call("/pkg.Search/Query", status(Code::NotFound, "no results", true), None);
+    assert_eq!(headers.get("grpc-status").unwrap(), "0");
+    assert_eq!(headers.get("grpc-message").unwrap(), "no results");
+    assert!(headers.get("grpc-status-details-bin").is_none());
+
+    let (headers, _) = call("/pkg.Search/QueryAll", status(Code::NotFound, "no results", true), None);
+    assert_eq!(headers, status(Code::NotFound, "no results", true));
+}
+
+#[test]
+fn should_remap_code_of_service_with_message() {
+    let (headers, _) = call("/pkg.Legacy/Get", status(Code::Internal, "db exploded", true), None);
+    assert_eq!(headers.get("grpc-status").unwrap(), "14");
+    assert_eq!(headers.get("grpc-message").unwrap(), "temporarily unavailable: 100%25");
+    assert!(headers.get("grpc-status-details-bin").is_none());
+
+    //More specific rule wins, replacing only message
+    let (headers, _) = call("/pkg.Legacy/Debug", status(Code::Internal, "stack trace", true), None);
+    assert_eq!(headers.get("grpc-status").unwrap(), "13");
+    assert_eq!(headers.get("grpc-message").unwrap(), "redacted");
+    assert_eq!(headers.get("grpc-status-details-bin").unwrap(), "AQI");
+}
+
+#[test]
+fn should_remap_code_of_trailers() {
+    let (headers, trailers) = call("/pkg.Other/Stream", http::HeaderMap::new(), Some(status(Code::DataLoss, "", false)));
+    assert!(headers.is_empty());
+    assert_eq!(trailers.expect("trailers").get("grpc-status").unwrap(), "13");
+
+    let (_, trailers) = call("/pkg.Legacy/Stream", http::HeaderMap::new(), Some(status(Code::Internal, "", true)));
+    let trailers = trailers.expect("trailers");
+    assert_eq!(trailers.get("grpc-status").unwrap(), "14");
+    assert_eq!(trailers.get("grpc-message").unwrap(), "temporarily unavailable: 100%25");
+    assert!(trailers.get("grpc-status-details-bin").is_none());
+}
+
+#[test]
+fn should_pass_through_unmatched_status() {
+    for (path, code) in [("/pkg.Search/Query", Code::Internal), ("/pkg.Legacy/Get", Code::NotFound), ("/pkg.Search/Query", Code::Ok)] {
+        let (headers, trailers) = call(path, status(code, "as is", true), Some(status(code, "as is", true)));
+        assert_eq!(headers, status(code, "as is", true));
+        assert_eq!(trailers, Some(status(code, "as is", true)));
+    }
+
+    let (headers, trailers) = call("/pkg.Search/Query", http::HeaderMap::new(), None);
+    assert!(headers.is_empty());
+    assert!(trailers.is_none());
+}
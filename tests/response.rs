@@ -0,0 +1,61 @@
+#![cfg(feature = "tonic011")]
+use tonic_interceptor::response::{status_to_response, status_to_response_with, ResponseTemplate};
+
+use tonic::{Code, Status};
+use tonic::metadata::MetadataMap;
+
+fn status() -> Status {
+    let mut metadata = MetadataMap::new();
+    metadata.insert("x-retry-after", "5".parse().unwrap());
+    Status::with_details_and_metadata(Code::ResourceExhausted, "quota exceeded", bytes::Bytes::from_static(b"\x08\x01"), metadata)
+}
+
+#[test]
+fn should_match_tonic_response() {
+    let mut expected = status().to_http();
+    let mut response: http::Response<()> = status_to_response(&status(), &ResponseTemplate::new());
+
+    assert_eq!(response.status(), expected.status());
+    assert_eq!(response.version(), expected.version());
+    //Space is visible ASCII, hence it is not percent-encoded, but message is decoded the same
+    assert_eq!(response.headers()["grpc-message"], "quota exceeded");
+    assert_eq!(Status::from_header_map(response.headers()).unwrap().message(), "quota exceeded");
+    response.headers_mut().remove("grpc-message");
+    expected.headers_mut().remove("grpc-message");
+    assert_eq!(response.headers(), expected.headers());
+    assert_eq!(response.headers()["grpc-status"], "8");
+    assert_eq!(response.headers()["x-retry-after"], "5");
+    assert!(response.headers().contains_key("grpc-status-details-bin"));
+
+    let expected = Status::ok("").to_http();
+    let response: http::Response<tonic::body::BoxBody> = status_to_response(&Status::ok(""), &ResponseTemplate::default());
+    assert_eq!(response.headers(), expected.headers());
+    assert!(!response.headers().contains_key("grpc-message"));
+}
+
+#[test]
+fn should_apply_template() {
+    let request = http::Request::builder().version(http::Version::HTTP_2).header("content-type", "application/grpc-web+proto").body(()).unwrap();
+    let template = ResponseTemplate::from_request(&request).header(http::header::HeaderName::from_static("x-served-by"), http::HeaderValue::from_static("edge"))
+                                                           .header(http::header::CONTENT_TYPE, http::HeaderValue::from_static("text/plain"))
+                                                           .header(http::header::HeaderName::from_static("grpc-status"), http::HeaderValue::from_static("0"));
+    let response = status_to_response_with(&Status::unavailable("100% drained"), &template, "body");
+
+    assert_eq!(response.version(), http::Version::HTTP_2);
+    assert_eq!(response.headers()["content-type"], "application/grpc-web+proto");
+    assert_eq!(response.headers()["x-served-by"], "edge");
+    assert_eq!(response.headers().get_all("grpc-status").iter().collect::<Vec<_>>(), ["14"]);
+    //`%` is always escaped
+    assert_eq!(response.headers()["grpc-message"], "100%25 drained");
+    assert_eq!(*response.body(), "body");
+
+    //Non-gRPC content type of request is not reused
+    let request = http::Request::builder().header("content-type", "application/json").body(()).unwrap();
+    let response: http::Response<()> = status_to_response(&Status::internal(""), &ResponseTemplate::from_request(&request));
+    assert_eq!(response.headers()["content-type"], "application/grpc");
+
+    let template = ResponseTemplate::new().content_type(http::HeaderValue::from_static("application/grpc+json")).version(http::Version::HTTP_11);
+    let response: http::Response<()> = status_to_response(&Status::internal(""), &template);
+    assert_eq!(response.headers()["content-type"], "application/grpc+json");
+    assert_eq!(response.version(), http::Version::HTTP_11);
+}
@@ -0,0 +1,146 @@
+#![cfg(feature = "tonic011")]
+#![allow(clippy::result_large_err)]
+
+//Handler sets `ResponseMeta`, which is written into response metadata by `ApplyResponseMeta` layer
+
+mod common;
+
+use common::{EchoClient, EchoService};
+use common::echo::{EchoRequest, EchoResponse};
+use common::echo::echo_server::{Echo, EchoServer};
+
+use tonic_interceptor::headers::{ApplyResponseMeta, ResponseMeta};
+
+use tonic::{Request, Response, Status};
+use tonic::transport::Channel;
+
+use std::sync::{Arc, Mutex};
+
+//Echo, which reports cache state of every response via `ResponseMeta`
+struct CachingEcho(EchoService);
+
+#[tonic::async_trait]
+impl Echo for CachingEcho {
+    async fn unary(&self, request: Request<EchoRequest>) -> Result<Response<EchoResponse>, Status> {
+        let mut response = self.0.unary(request).await?;
+        let meta = match response.get_ref().message.as_str() {
+            "hit" => ResponseMeta::set("x-cache", "hit").with("x-cache-age", "10"),
+            "invalid" => {
+                let mut meta = ResponseMeta::set("X-Cache", "hit").with("grpc-status", "0").with("x-cache", "new\nline").with("x-cache-bin", "hit");
+                meta.insert_bin("x-cache-key", vec![1, 2]);
+                meta.insert_bin("x-cache-key-bin", vec![1, 2]);
+                meta.insert("x-cache", "miss");
+                meta
+            },
+            _ => ResponseMeta::new(),
+        };
+        response.extensions_mut().insert(meta);
+        Ok(response)
+    }
+
+    type StreamStream = <EchoService as Echo>::StreamStream;
+
+    async fn stream(&self, request: Request<EchoRequest>) -> Result<Response<Self::StreamStream>, Status> {
+        let mut response = self.0.stream(request).await?;
+        response.extensions_mut().insert(ResponseMeta::set("x-cache", "stream"));
+        Ok(response)
+    }
+}
+
+type Invalid = Arc<Mutex<Vec<(String, &'static str)>>>;
+
+async fn spawn() -> (Invalid, EchoClient<Channel>) {
+    let invalid = Invalid::default();
+    let layer = {
+        let invalid = invalid.clone();
+        tonic_interceptor::interceptor(ApplyResponseMeta::new().on_invalid(move |key: &str, reason| invalid.lock().unwrap().push((key.to_owned(), reason))))
+    };
+    let (incoming, addr) = common::listen().await;
+    let router = tonic::transport::Server::builder().layer(layer).add_service(EchoServer::new(CachingEcho(EchoService::default())));
+    tokio::spawn(router.serve_with_incoming(incoming));
+    (invalid, EchoClient::new(common::connect(addr).await))
+}
+
+fn request(message: &str) -> Request<EchoRequest> {
+    Request::new(EchoRequest {
+        message: message.to_owned(),
+    })
+}
+
+#[tokio::test]
+async fn should_write_handler_metadata_into_response() {
+    let (invalid, mut client) = spawn().await;
+
+    let response = client.unary(request("hit")).await.expect("success");
+    assert_eq!(response.metadata().get("x-cache").unwrap(), "hit");
+    assert_eq!(response.metadata().get("x-cache-age").unwrap(), "10");
+
+    let response = client.unary(request("miss")).await.expect("success");
+    assert!(response.metadata().get("x-cache").is_none());
+
+    let mut response = client.stream(request("a b")).await.expect("success");
+    assert_eq!(response.metadata().get("x-cache").unwrap(), "stream");
+    while response.get_mut().message().await.expect("item").is_some() {
+    }
+
+    assert!(invalid.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn should_report_invalid_entries() {
+    let (invalid, mut client) = spawn().await;
+
+    let response = client.unary(request("invalid")).await.expect("success");
+    let values: Vec<_> = response.metadata().get_all("x-cache").iter().map(|value| value.to_str().unwrap().to_owned()).collect();
+    assert_eq!(values, ["miss"]);
+    assert_eq!(response.metadata().get_bin("x-cache-key-bin").unwrap().to_bytes().unwrap().as_ref(), [1, 2]);
+    assert!(response.metadata().get("x-cache-key").is_none());
+
+    assert_eq!(*invalid.lock().unwrap(), [
+        ("X-Cache".to_owned(), "invalid key"),
+        ("grpc-status".to_owned(), "reserved key"),
+        ("x-cache".to_owned(), "invalid value"),
+        ("x-cache-bin".to_owned(), "binary key with ASCII value"),
+        ("x-cache-key".to_owned(), "ASCII key with binary value"),
+    ]);
+}
+
+#[test]
+fn should_drain_container() {
+    use tonic_interceptor::Interceptor;
+
+    let meta = ResponseMeta::set("x-cache", "hit").with("x-cache", "stale");
+    assert_eq!(meta.len(), 2);
+    let mut extensions = http::Extensions::new();
+    extensions.insert(meta);
+
+    let mut headers = http::HeaderMap::new();
+    let interceptor = ApplyResponseMeta::new();
+    interceptor.on_response(tonic::Code::Ok, &mut headers, &extensions);
+    interceptor.on_response(tonic::Code::Ok, &mut headers, &extensions);
+    assert_eq!(headers.get_all("x-cache").iter().collect::<Vec<_>>(), ["hit", "stale"]);
+    assert!(extensions.get::<ResponseMeta>().unwrap().is_empty());
+}
+
+#[test]
+fn should_keep_order_beyond_inline_entries() {
+    use tonic_interceptor::Interceptor;
+
+    const KEYS: [&str; 6] = ["x-a", "x-b", "x-c", "x-d", "x-e", "x-f"];
+
+    let mut meta = ResponseMeta::new();
+    for key in KEYS.iter() {
+        meta.insert(*key, *key);
+    }
+    meta.insert_bin("x-g-bin", &b"\x01"[..]);
+    assert_eq!(meta.len(), KEYS.len() + 1);
+    let mut extensions = http::Extensions::new();
+    extensions.insert(meta.clone());
+
+    let mut headers = http::HeaderMap::new();
+    ApplyResponseMeta::new().on_response(tonic::Code::Ok, &mut headers, &extensions);
+    assert_eq!(headers.keys().map(|key| key.as_str()).collect::<Vec<_>>(), ["x-a", "x-b", "x-c", "x-d", "x-e", "x-f", "x-g-bin"]);
+    assert!(extensions.get::<ResponseMeta>().unwrap().is_empty());
+    //Clone is not drained with original
+    assert_eq!(meta.len(), KEYS.len() + 1);
+}
@@ -0,0 +1,308 @@
+#![cfg(all(feature = "tonic011", feature = "tokio"))]
+
+mod common;
+
+use common::EchoClient;
+use common::echo::EchoRequest;
+
+use tonic_interceptor::client::Retry;
+use tonic_interceptor::testing::TestTimer;
+
+use tonic::{Code, Status};
+
+use core::future::Future;
+use core::time::Duration;
+use std::sync::{Arc, Mutex};
+
+fn request(message: &str) -> EchoRequest {
+    EchoRequest {
+        message: message.to_owned(),
+    }
+}
+
+fn retry(timer: &TestTimer) -> Retry<TestTimer> {
+    Retry::new().jitter(0.0).backoff(Duration::from_millis(100), Duration::from_secs(1)).timer(timer.clone())
+}
+
+//Runs `fut`, advancing `timer` whenever `fut` waits for it
+async fn drive<F: Future>(timer: &TestTimer, fut: F) -> F::Output {
+    let mut fut = core::pin::pin!(fut);
+    loop {
+        if let core::task::Poll::Ready(output) = core::future::poll_fn(|ctx| core::task::Poll::Ready(fut.as_mut().poll(ctx))).await {
+            return output;
+        }
+        if !timer.advance_to_next() {
+            tokio::task::yield_now().await;
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+//Time of each attempt, as told by timer
+struct Attempts(Arc<Mutex<Vec<Duration>>>);
+
+impl Attempts {
+    fn record<B>(&self, timer: &TestTimer) -> impl Fn(http::Request<B>) -> http::Request<B> + Clone {
+        let attempts = self.clone();
+        let timer = timer.clone();
+        move |request| {
+            attempts.0.lock().unwrap().push(timer.elapsed());
+            request
+        }
+    }
+
+    fn delays(&self) -> Vec<Duration> {
+        self.0.lock().unwrap().windows(2).map(|pair| pair[1] - pair[0]).collect()
+    }
+}
+
+fn attempts(service: &common::EchoService) -> Vec<Option<String>> {
+    service.requests().into_iter().map(|(_, metadata)| metadata.get("grpc-previous-rpc-attempts").map(|value| value.to_str().unwrap().to_owned())).collect()
+}
+
+#[tokio::test]
+async fn should_retry_until_success_with_backoff() {
+    let (service, channel) = common::spawn_echo().await;
+    service.fail_next(Status::unavailable("1"));
+    service.fail_next(Status::unavailable("2"));
+    service.fail_next(Status::unavailable("3"));
+
+    let timer = TestTimer::new();
+    let times = Attempts::default();
+    let channel = tower::ServiceBuilder::new().layer(retry(&timer).max_attempts(4)).map_request(times.record(&timer)).service(channel);
+    let mut client = EchoClient::new(channel);
+
+    let response = drive(&timer, client.unary(request("hello"))).await.expect("success");
+    assert_eq!(response.get_ref().message, "hello");
+    assert_eq!(attempts(&service), [None, Some("1".to_owned()), Some("2".to_owned()), Some("3".to_owned())]);
+    assert_eq!(times.delays(), [Duration::from_millis(100), Duration::from_millis(200), Duration::from_millis(400)]);
+}
+
+#[tokio::test]
+async fn should_cap_backoff() {
+    let (service, channel) = common::spawn_echo().await;
+    for _ in 0..3 {
+        service.fail_next(Status::unavailable("fail"));
+    }
+
+    let timer = TestTimer::new();
+    let times = Attempts::default();
+    let channel = tower::ServiceBuilder::new().layer(retry(&timer).max_attempts(4).backoff(Duration::from_millis(100), Duration::from_millis(150))).map_request(times.record(&timer)).service(channel);
+    let mut client = EchoClient::new(channel);
+
+    drive(&timer, client.unary(request("hello"))).await.expect("success");
+    assert_eq!(times.delays(), [Duration::from_millis(100), Duration::from_millis(150), Duration::from_millis(150)]);
+}
+
+#[tokio::test]
+async fn should_give_up_after_max_attempts() {
+    let (service, channel) = common::spawn_echo().await;
+    for _ in 0..3 {
+        service.fail_next(Status::unavailable("fail"));
+    }
+
+    let timer = TestTimer::new();
+    let channel = tower::ServiceBuilder::new().layer(retry(&timer).max_attempts(2)).service(channel);
+    let mut client = EchoClient::new(channel);
+
+    let status = drive(&timer, client.unary(request("hello"))).await.expect_err("to fail");
+    assert_eq!(status.code(), Code::Unavailable);
+    assert_eq!(service.calls(), 2);
+    assert_eq!(timer.elapsed(), Duration::from_millis(100));
+}
+
+#[tokio::test]
+async fn should_retry_only_configured_codes() {
+    let (service, channel) = common::spawn_echo().await;
+    service.fail_next(Status::deadline_exceeded("slow"));
+
+    let timer = TestTimer::new();
+    let channel = tower::ServiceBuilder::new().layer(retry(&timer)).service(channel);
+    let mut client = EchoClient::new(channel);
+    let status = drive(&timer, client.unary(request("hello"))).await.expect_err("to fail");
+    assert_eq!(status.code(), Code::DeadlineExceeded);
+    assert_eq!(service.calls(), 1);
+
+    service.fail_next(Status::deadline_exceeded("slow"));
+    let channel = tower::ServiceBuilder::new().layer(retry(&timer).retry_on(Code::DeadlineExceeded)).service(client_channel(&service).await);
+    let mut client = EchoClient::new(channel);
+    drive(&timer, client.unary(request("hello"))).await.expect("success");
+    assert_eq!(service.calls(), 3);
+}
+
+async fn client_channel(service: &common::EchoService) -> tonic::transport::Channel {
+    let (incoming, addr) = common::listen().await;
+    let server = tonic::transport::Server::builder().add_service(common::EchoServer::new(service.clone()));
+    tokio::spawn(server.serve_with_incoming(incoming));
+    common::connect(addr).await
+}
+
+#[tokio::test]
+async fn should_not_retry_request_exceeding_buffer_limit() {
+    let (service, channel) = common::spawn_echo().await;
+    service.fail_next(Status::unavailable("fail"));
+
+    let timer = TestTimer::new();
+    let channel = tower::ServiceBuilder::new().layer(retry(&timer).buffer_limit(4)).service(channel);
+    let mut client = EchoClient::new(channel);
+
+    let status = drive(&timer, client.unary(request("message longer than limit"))).await.expect_err("to fail");
+    assert_eq!(status.code(), Code::Unavailable);
+    assert_eq!(service.calls(), 1);
+}
+
+//Real server enforces `grpc-timeout` with its own timer, which does not follow test timer so use mock service instead
+#[tokio::test]
+async fn should_respect_deadline() {
+    use tower::ServiceExt;
+
+    let timer = TestTimer::new();
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let service = {
+        let requests = requests.clone();
+        let timer = timer.clone();
+        tower::service_fn(move |request: http::Request<tonic::body::BoxBody>| {
+            requests.lock().unwrap().push((timer.elapsed(), request.headers().clone()));
+            async move {
+                Ok::<_, core::convert::Infallible>(Status::unavailable("fail").to_http())
+            }
+        })
+    };
+    let service = tower::ServiceBuilder::new().layer(retry(&timer).max_attempts(5)).service(service);
+
+    let request = http::Request::builder().header("grpc-timeout", "250m").body(tonic::body::empty_body()).unwrap();
+    let response = drive(&timer, service.oneshot(request)).await.expect("response");
+    assert_eq!(response.headers().get("grpc-status").expect("to have grpc-status"), "14");
+
+    //Second retry would be at 300ms
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[1].0 - requests[0].0, Duration::from_millis(100));
+    assert_eq!(requests[1].1.get("grpc-timeout").expect("to have grpc-timeout"), "150m");
+}
+
+#[tokio::test]
+async fn should_bound_attempt_by_overall_timeout() {
+    use tower::ServiceExt;
+
+    let timer = TestTimer::new();
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let service = {
+        let requests = requests.clone();
+        tower::service_fn(move |request: http::Request<tonic::body::BoxBody>| {
+            requests.lock().unwrap().push(request.headers().clone());
+            core::future::pending::<Result<http::Response<tonic::body::BoxBody>, core::convert::Infallible>>()
+        })
+    };
+    let service = tower::ServiceBuilder::new().layer(retry(&timer).timeout(Duration::from_secs(2))).service(service);
+
+    let request = http::Request::builder().header("grpc-timeout", "5S").body(tonic::body::empty_body()).unwrap();
+    let error = drive(&timer, service.oneshot(request)).await.expect_err("to time out");
+    assert_eq!(error.downcast_ref::<Status>().expect("status").code(), Code::DeadlineExceeded);
+    assert_eq!(timer.elapsed(), Duration::from_secs(2));
+
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].get("grpc-timeout").expect("to have grpc-timeout"), "2S");
+}
+
+mod rpc {
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Duration {
+        #[prost(int64, tag = "1")]
+        pub seconds: i64,
+        #[prost(int32, tag = "2")]
+        pub nanos: i32,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct RetryInfo {
+        #[prost(message, optional, tag = "1")]
+        pub retry_delay: Option<Duration>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Any {
+        #[prost(string, tag = "1")]
+        pub type_url: String,
+        #[prost(bytes = "vec", tag = "2")]
+        pub value: Vec<u8>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Status {
+        #[prost(int32, tag = "1")]
+        pub code: i32,
+        #[prost(string, tag = "2")]
+        pub message: String,
+        #[prost(message, repeated, tag = "3")]
+        pub details: Vec<Any>,
+    }
+}
+
+#[tokio::test]
+async fn should_honor_retry_info_delay() {
+    use prost::Message;
+
+    let retry_info = rpc::RetryInfo {
+        retry_delay: Some(rpc::Duration {
+            seconds: 3,
+            nanos: 500_000_000,
+        }),
+    };
+    let details = rpc::Status {
+        code: Code::Unavailable as i32,
+        message: "overloaded".to_owned(),
+        details: vec![rpc::Any {
+            type_url: "type.googleapis.com/google.rpc.RetryInfo".to_owned(),
+            value: retry_info.encode_to_vec(),
+        }],
+    };
+
+    let (service, channel) = common::spawn_echo().await;
+    service.fail_next(Status::with_details(Code::Unavailable, "overloaded", details.encode_to_vec().into()));
+
+    let timer = TestTimer::new();
+    let times = Attempts::default();
+    let channel = tower::ServiceBuilder::new().layer(retry(&timer)).map_request(times.record(&timer)).service(channel);
+    let mut client = EchoClient::new(channel);
+
+    drive(&timer, client.unary(request("hello"))).await.expect("success");
+    assert_eq!(times.delays(), [Duration::from_millis(3500)]);
+}
+
+#[cfg(feature = "prost-reflect")]
+#[tokio::test]
+async fn should_retry_only_idempotent_methods() {
+    use tonic_interceptor::descriptor::MethodInfo;
+
+    fn with_info(idempotent: bool) -> tonic::Request<EchoRequest> {
+        let mut request = tonic::Request::new(request("hello"));
+        request.extensions_mut().insert(MethodInfo {
+            service: "test.Echo".into(),
+            method: "Unary".into(),
+            client_streaming: false,
+            server_streaming: false,
+            idempotent,
+        });
+        request
+    }
+
+    let (service, channel) = common::spawn_echo().await;
+    let timer = TestTimer::new();
+    let channel = tower::ServiceBuilder::new().layer(retry(&timer).max_attempts(3).idempotent_only(true)).service(channel);
+    let mut client = EchoClient::new(channel);
+
+    service.fail_next(Status::unavailable("fail"));
+    drive(&timer, client.unary(with_info(true))).await.expect("success");
+    assert_eq!(attempts(&service).len(), 2);
+
+    //Neither non-idempotent nor unknown method is retried
+    service.fail_next(Status::unavailable("fail"));
+    let status = drive(&timer, client.unary(with_info(false))).await.expect_err("failure");
+    assert_eq!(status.code(), Code::Unavailable);
+    service.fail_next(Status::unavailable("fail"));
+    let status = drive(&timer, client.unary(request("hello"))).await.expect_err("failure");
+    assert_eq!(status.code(), Code::Unavailable);
+    assert_eq!(attempts(&service).len(), 4);
+}
@@ -0,0 +1,144 @@
+#![cfg(feature = "tonic011")]
+#![allow(clippy::result_large_err)]
+
+use tonic_interceptor::{Interceptor, InterceptorFn, InterceptorService, LazyMetadata};
+use tonic_interceptor::client::{ClientInterceptor, ClientInterceptorService};
+use tonic_interceptor::testing::{poll_once, service_fn};
+
+use tonic::Status;
+use tonic::metadata::MetadataMap;
+use tower_service::Service;
+
+fn corpus() -> Vec<http::HeaderMap> {
+    let mut result = Vec::new();
+
+    let mut headers = http::HeaderMap::new();
+    headers.append("x-dup", http::HeaderValue::from_static("3"));
+    headers.append("x-dup", http::HeaderValue::from_static("1"));
+    headers.append("x-dup", http::HeaderValue::from_static("2"));
+    headers.append("x-dup-bin", http::HeaderValue::from_static("AQ"));
+    headers.append("x-dup-bin", http::HeaderValue::from_static("AA=="));
+    result.push(headers);
+
+    let mut headers = http::HeaderMap::new();
+    //Valid header names, but not valid metadata keys
+    headers.insert("x~weird!key", http::HeaderValue::from_static("1"));
+    headers.insert("x$key|with^symbols", http::HeaderValue::from_static("2"));
+    headers.insert("x-invalid-bin", http::HeaderValue::from_static("not base64!"));
+    headers.append("x-invalid-bin", http::HeaderValue::from_static("%%%"));
+    headers.insert("x-opaque", http::HeaderValue::from_bytes(b"\xfa\xfb").unwrap());
+    result.push(headers);
+
+    let mut headers = http::HeaderMap::new();
+    for idx in 0..200 {
+        let key = http::header::HeaderName::from_bytes(format!("x-key-{}", idx).as_bytes()).unwrap();
+        headers.append(key.clone(), http::HeaderValue::from(idx));
+        if idx % 3 == 0 {
+            headers.append(key, http::HeaderValue::from(idx * 2));
+        }
+    }
+    result.push(headers);
+
+    result
+}
+
+//Headers in their exact iteration order
+fn ordered(headers: &http::HeaderMap) -> Vec<(String, Vec<u8>)> {
+    headers.iter().map(|(key, value)| (key.as_str().to_owned(), value.as_bytes().to_vec())).collect()
+}
+
+#[derive(Clone)]
+struct Lazy;
+
+impl Interceptor for Lazy {
+    fn on_request(&self, _: &mut MetadataMap, _: &mut http::Extensions) -> Option<Status> {
+        unreachable!()
+    }
+
+    fn on_request_lazy(&self, _: &http::Uri, headers: &mut LazyMetadata<'_>, _: &mut http::Extensions) -> Option<Status> {
+        headers.get("x-dup");
+        None
+    }
+
+    fn on_response(&self, _: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions) {
+    }
+}
+
+fn server_roundtrip<I: Interceptor + Clone>(interceptor: I, headers: &http::HeaderMap) -> http::HeaderMap {
+    let mut seen = None;
+    {
+        let svc = service_fn(|request: http::Request<()>| {
+            seen = Some(request.headers().clone());
+            Ok::<_, Status>(http::Response::new(()))
+        });
+        let mut service = InterceptorService::new(interceptor, svc);
+        let mut request = http::Request::new(());
+        *request.headers_mut() = headers.clone();
+        poll_once(service.call(request)).expect("response");
+    }
+    seen.expect("service to be called")
+}
+
+#[test]
+fn should_preserve_headers_on_server() {
+    let metadata = InterceptorFn {
+        on_request: |headers: &mut MetadataMap, _: &mut http::Extensions| {
+            headers.get("x-dup");
+            None
+        },
+        on_response: |_: tonic::Code, _: &mut http::HeaderMap, _: &http::Extensions| {
+        }
+    };
+
+    for headers in corpus() {
+        let seen = server_roundtrip(metadata.clone(), &headers);
+        assert_eq!(seen, headers);
+        assert_eq!(ordered(&seen), ordered(&headers));
+
+        let seen = server_roundtrip(Lazy, &headers);
+        assert_eq!(seen, headers);
+        assert_eq!(ordered(&seen), ordered(&headers));
+    }
+}
+
+#[derive(Clone)]
+struct Observer;
+
+impl ClientInterceptor for Observer {
+    fn on_request(&self, headers: &mut MetadataMap, _: &mut http::Extensions) -> Option<Status> {
+        headers.get("x-dup");
+        None
+    }
+
+    fn on_response(&self, headers: &MetadataMap, _: &http::Extensions) {
+        headers.get("x-dup");
+    }
+}
+
+#[test]
+fn should_preserve_headers_on_client() {
+    for headers in corpus() {
+        let mut seen = None;
+        let response = {
+            let svc = service_fn(|request: http::Request<()>| {
+                seen = Some(request.headers().clone());
+                let mut response = http::Response::new(tonic::body::empty_body());
+                *response.headers_mut() = request.headers().clone();
+                Ok::<_, Status>(response)
+            });
+            let mut service = ClientInterceptorService::new(Observer, svc);
+            let mut request = http::Request::new(());
+            *request.headers_mut() = headers.clone();
+            match poll_once(service.call(request)) {
+                Ok(response) => response.headers().clone(),
+                Err(error) => panic!("unexpected error: {}", error),
+            }
+        };
+
+        let seen = seen.expect("service to be called");
+        assert_eq!(seen, headers);
+        assert_eq!(ordered(&seen), ordered(&headers));
+        assert_eq!(response, headers);
+        assert_eq!(ordered(&response), ordered(&headers));
+    }
+}
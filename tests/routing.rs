@@ -0,0 +1,89 @@
+#![cfg(feature = "tonic011")]
+#![allow(clippy::result_large_err)]
+
+use tonic_interceptor::client::{ClientInterceptor, ClientInterceptorService, RoutingHint};
+use tonic_interceptor::matcher::MethodMatcher;
+
+use tonic::{Code, Status};
+use tower::ServiceExt;
+
+const SHARD_KEY: &str = "x-shard-key";
+
+fn tenant(_: &http::Uri, metadata: &tonic::metadata::MetadataMap) -> Option<tonic::metadata::AsciiMetadataValue> {
+    metadata.get("x-tenant").cloned()
+}
+
+fn hint() -> RoutingHint {
+    RoutingHint::new(SHARD_KEY)
+        .method("*", |_, _| Some("default".parse().unwrap()))
+        .method("/pkg.Users/*", |_, _| Some("users".parse().unwrap()))
+        .require("/pkg.Users/Get", tenant)
+        .method("/pkg.Users/Get", |_, _| Some("ignored".parse().unwrap()))
+}
+
+fn apply(hint: &RoutingHint, path: &str, metadata: &mut tonic::metadata::MetadataMap) -> Option<Status> {
+    let uri = format!("http://localhost{}", path).parse::<http::Uri>().unwrap();
+    hint.on_request_with_uri(&uri, metadata, &mut http::Extensions::new())
+}
+
+fn shard_key(hint: &RoutingHint, path: &str, tenant: Option<&str>) -> Result<Option<String>, Status> {
+    let mut metadata = tonic::metadata::MetadataMap::new();
+    if let Some(tenant) = tenant {
+        metadata.insert("x-tenant", tenant.parse().unwrap());
+    }
+    match apply(hint, path, &mut metadata) {
+        Some(status) => Err(status),
+        None => Ok(metadata.get(SHARD_KEY).map(|value| value.to_str().unwrap().to_owned())),
+    }
+}
+
+#[test]
+fn should_match_patterns() {
+    assert!(MethodMatcher::new("*").matches("/pkg.Users/Get"));
+    assert!(MethodMatcher::new("/pkg.Users/*").matches("/pkg.Users/Get"));
+    assert!(!MethodMatcher::new("/pkg.Users/*").matches("/pkg.UsersAdmin/Get"));
+    assert!(MethodMatcher::new("/pkg.Users/Get").matches("/pkg.Users/Get"));
+    assert!(!MethodMatcher::new("/pkg.Users/Get").matches("/pkg.Users/GetAll"));
+    assert_eq!(MethodMatcher::new("/pkg.Users/*").to_string(), "/pkg.Users/*");
+}
+
+#[test]
+fn should_pick_most_specific_rule() {
+    let hint = hint();
+    assert_eq!(shard_key(&hint, "/pkg.Orders/List", None).unwrap().as_deref(), Some("default"));
+    assert_eq!(shard_key(&hint, "/pkg.Users/List", None).unwrap().as_deref(), Some("users"));
+    //Earlier of equally specific rules wins
+    assert_eq!(shard_key(&hint, "/pkg.Users/Get", Some("acme")).unwrap().as_deref(), Some("acme"));
+}
+
+#[test]
+fn should_skip_methods_without_rule() {
+    let hint = RoutingHint::new(SHARD_KEY).method("/pkg.Users/*", tenant);
+    assert_eq!(shard_key(&hint, "/pkg.Orders/List", Some("acme")).unwrap(), None);
+    //Optional hint
+    assert_eq!(shard_key(&hint, "/pkg.Users/List", None).unwrap(), None);
+}
+
+#[test]
+fn should_keep_explicit_hint() {
+    let mut metadata = tonic::metadata::MetadataMap::new();
+    metadata.insert(SHARD_KEY, "explicit".parse().unwrap());
+    assert!(apply(&hint(), "/pkg.Users/Get", &mut metadata).is_none());
+    assert_eq!(metadata.get(SHARD_KEY).unwrap(), "explicit");
+}
+
+#[tokio::test]
+async fn should_fail_locally_without_required_hint() {
+    let service = tower::service_fn(|_: http::Request<()>| async move {
+        Err::<http::Response<()>, _>(Status::internal("request must not be sent"))
+    });
+    let service = ClientInterceptorService::new(hint(), service);
+
+    let request = http::Request::builder().uri("http://localhost/pkg.Users/Get").body(()).unwrap();
+    let status = match service.oneshot(request).await {
+        Ok(_) => panic!("to fail"),
+        Err(error) => Status::from_error(error),
+    };
+    assert_eq!(status.code(), Code::InvalidArgument);
+    assert_eq!(status.message(), "Missing x-shard-key for /pkg.Users/Get");
+}
@@ -0,0 +1,292 @@
+#![cfg(all(feature = "tonic011", feature = "tokio"))]
+
+use tonic_interceptor::shadow::{Compare, ComparisonReport, Mirror, Mismatch, MirrorBody};
+
+use tonic::Code;
+use tower::ServiceExt;
+use tower_layer::Layer;
+use http_body::Body;
+
+use core::pin::Pin;
+use core::time::Duration;
+use core::convert::Infallible;
+use std::sync::{Arc, Mutex};
+
+//Body without data, which ends with trailers
+#[derive(Default)]
+struct Trailers(Option<http::HeaderMap>);
+
+impl Trailers {
+    fn new(code: Code) -> Self {
+        let mut trailers = http::HeaderMap::new();
+        trailers.insert("grpc-status", http::HeaderValue::from(code as i32));
+        Self(Some(trailers))
+    }
+}
+
+impl Body for Trailers {
+    type Data = bytes::Bytes;
+    type Error = Infallible;
+
+    fn poll_data(self: Pin<&mut Self>, _: &mut core::task::Context<'_>) -> core::task::Poll<Option<Result<Self::Data, Self::Error>>> {
+        core::task::Poll::Ready(None)
+    }
+
+    fn poll_trailers(mut self: Pin<&mut Self>, _: &mut core::task::Context<'_>) -> core::task::Poll<Result<Option<http::HeaderMap>, Self::Error>> {
+        core::task::Poll::Ready(Ok(self.0.take()))
+    }
+}
+
+type Shadowed = Arc<Mutex<Vec<(String, Option<http::HeaderValue>, bytes::Bytes)>>>;
+
+//Shadow service, which records requests and replies with `code`
+fn shadow(shadowed: Shadowed, code: Code) -> impl tower_service::Service<http::Request<tonic::body::BoxBody>, Response = http::Response<Trailers>, Error = Infallible, Future = impl Send> + Clone + Send + 'static {
+    tower::service_fn(move |req: http::Request<tonic::body::BoxBody>| {
+        let shadowed = shadowed.clone();
+        async move {
+            let path = req.uri().path().to_owned();
+            let tenant = req.headers().get("x-tenant").cloned();
+            let mut body = req.into_body();
+            let mut data = bytes::BytesMut::new();
+            while let Some(chunk) = body.data().await {
+                data.extend_from_slice(&chunk.unwrap());
+            }
+            shadowed.lock().unwrap().push((path, tenant, data.freeze()));
+            Ok::<_, Infallible>(http::Response::new(Trailers::new(code)))
+        }
+    })
+}
+
+//Calls primary service, which reads whole request body, and reads its response to the end
+async fn call<S>(svc: S, path: &str, body: &'static [u8]) -> Code
+where
+    S: tower_service::Service<http::Request<http_body::Full<bytes::Bytes>>, Response = http::Response<tonic_interceptor::shadow::MirrorResponseBody<Trailers>>, Error = Infallible>,
+{
+    let req = http::Request::builder().uri(path).header("x-tenant", "a").body(http_body::Full::new(bytes::Bytes::from_static(body))).unwrap();
+    let response = svc.oneshot(req).await.unwrap();
+    let mut body = response.into_body();
+    while body.data().await.is_some() {
+    }
+    let trailers = body.trailers().await.unwrap().unwrap();
+    Code::from_bytes(trailers.get("grpc-status").unwrap().as_bytes())
+}
+
+fn primary(code: Code) -> impl tower_service::Service<http::Request<MirrorBody<http_body::Full<bytes::Bytes>>>, Response = http::Response<Trailers>, Error = Infallible> + Clone {
+    tower::service_fn(move |req: http::Request<MirrorBody<http_body::Full<bytes::Bytes>>>| async move {
+        let mut body = req.into_body();
+        while body.data().await.is_some() {
+        }
+        Ok::<_, Infallible>(http::Response::new(Trailers::new(code)))
+    })
+}
+
+#[tokio::test]
+async fn should_mirror_sampled_requests() {
+    let shadowed = Shadowed::default();
+    let mismatches = Arc::new(Mutex::new(Vec::new()));
+    let mirror = {
+        let mismatches = mismatches.clone();
+        Mirror::new(shadow(shadowed.clone(), Code::Ok), 50).method("/pkg.Users/*").on_mismatch(move |mismatch| mismatches.lock().unwrap().push(mismatch.clone()))
+    };
+    let layer = mirror.layer();
+
+    for idx in 0..4 {
+        let path = if idx % 2 == 0 { "/pkg.Users/Get" } else { "/pkg.Users/List" };
+        assert_eq!(call(layer.layer(primary(Code::Ok)), path, b"message").await, Code::Ok);
+    }
+    //Methods not matched are not sampled
+    assert_eq!(call(layer.layer(primary(Code::Ok)), "/pkg.Orders/Get", b"message").await, Code::Ok);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let shadowed = shadowed.lock().unwrap().clone();
+    assert_eq!(shadowed.len(), 2);
+    for (path, tenant, body) in shadowed {
+        assert_eq!(path, "/pkg.Users/List");
+        assert_eq!(tenant.unwrap(), "a");
+        assert_eq!(body, "message");
+    }
+    assert!(mismatches.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn should_report_mismatched_status() {
+    let shadowed = Shadowed::default();
+    let mismatches = Arc::new(Mutex::new(Vec::new()));
+    let mirror = {
+        let mismatches = mismatches.clone();
+        Mirror::new(shadow(shadowed.clone(), Code::Internal), 100).on_mismatch(move |mismatch| mismatches.lock().unwrap().push(mismatch.clone()))
+    };
+
+    assert_eq!(call(mirror.layer().layer(primary(Code::Ok)), "/pkg.Users/Get", b"message").await, Code::Ok);
+    assert_eq!(call(mirror.layer().layer(primary(Code::Internal)), "/pkg.Users/Get", b"message").await, Code::Internal);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    assert_eq!(shadowed.lock().unwrap().len(), 2);
+    assert_eq!(*mismatches.lock().unwrap(), [Mismatch {
+        method: "/pkg.Users/Get".to_owned(),
+        primary: Code::Ok,
+        shadow: Some(Code::Internal),
+    }]);
+}
+
+#[tokio::test]
+async fn should_not_await_shadow_call() {
+    let mismatches = Arc::new(Mutex::new(Vec::new()));
+    let stuck = tower::service_fn(|_: http::Request<tonic::body::BoxBody>| core::future::pending::<Result<http::Response<Trailers>, Infallible>>());
+    let mirror = {
+        let mismatches = mismatches.clone();
+        Mirror::new(stuck, 100).timeout(Duration::from_millis(50)).on_mismatch(move |mismatch| mismatches.lock().unwrap().push(mismatch.clone()))
+    };
+
+    //Primary call completes while shadow one never does
+    let code = tokio::time::timeout(Duration::from_millis(20), call(mirror.layer().layer(primary(Code::Ok)), "/pkg.Users/Get", b"message")).await;
+    assert_eq!(code, Ok(Code::Ok));
+    assert!(mismatches.lock().unwrap().is_empty());
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(*mismatches.lock().unwrap(), [Mismatch {
+        method: "/pkg.Users/Get".to_owned(),
+        primary: Code::Ok,
+        shadow: None,
+    }]);
+}
+
+#[tokio::test]
+async fn should_not_mirror_large_or_unread_requests() {
+    let shadowed = Shadowed::default();
+    let mirror = Mirror::new(shadow(shadowed.clone(), Code::Ok), 100).max_body_size(4);
+
+    assert_eq!(call(mirror.layer().layer(primary(Code::Ok)), "/pkg.Users/Get", b"message").await, Code::Ok);
+    assert_eq!(call(mirror.layer().layer(primary(Code::Ok)), "/pkg.Users/Get", b"four").await, Code::Ok);
+
+    //Request body, which primary service never reads, is not mirrored
+    let unread = tower::service_fn(|req: http::Request<MirrorBody<http_body::Full<bytes::Bytes>>>| async move {
+        assert!(req.body().is_mirrored());
+        Ok::<_, Infallible>(http::Response::new(Trailers::new(Code::Ok)))
+    });
+    assert_eq!(call(mirror.layer().layer(unread), "/pkg.Users/List", b"1").await, Code::Ok);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let shadowed = shadowed.lock().unwrap().clone();
+    assert_eq!(shadowed.len(), 1);
+    assert_eq!(shadowed[0].2, "four");
+}
+
+//Body with single data chunk, which ends with trailers
+struct Reply(Option<bytes::Bytes>, Trailers);
+
+impl Reply {
+    fn new(data: &'static str, code: Code) -> Self {
+        Self(Some(bytes::Bytes::from_static(data.as_bytes())), Trailers::new(code))
+    }
+}
+
+impl Body for Reply {
+    type Data = bytes::Bytes;
+    type Error = Infallible;
+
+    fn poll_data(mut self: Pin<&mut Self>, _: &mut core::task::Context<'_>) -> core::task::Poll<Option<Result<Self::Data, Self::Error>>> {
+        core::task::Poll::Ready(self.0.take().map(Ok))
+    }
+
+    fn poll_trailers(mut self: Pin<&mut Self>, ctx: &mut core::task::Context<'_>) -> core::task::Poll<Result<Option<http::HeaderMap>, Self::Error>> {
+        Pin::new(&mut self.1).poll_trailers(ctx)
+    }
+}
+
+//Service, which reads whole request body and replies with `data` and `code`
+#[derive(Clone)]
+struct Backend(&'static str, Code);
+
+impl<B: Body + Send + Unpin + 'static> tower_service::Service<http::Request<B>> for Backend {
+    type Response = http::Response<Reply>;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn core::future::Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, _: &mut core::task::Context<'_>) -> core::task::Poll<Result<(), Self::Error>> {
+        core::task::Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        let Backend(data, code) = *self;
+        Box::pin(async move {
+            let mut body = req.into_body();
+            while body.data().await.is_some() {
+            }
+            Ok(http::Response::new(Reply::new(data, code)))
+        })
+    }
+}
+
+async fn compare<S2>(compare: &Compare<S2>, primary: Backend) -> ComparisonReport
+where
+    S2: tower_service::Service<http::Request<tonic::body::BoxBody>, Response = http::Response<Reply>, Error = Infallible> + Clone + Send + 'static,
+    S2::Future: Send,
+{
+    let (sender, mut reports) = tokio::sync::mpsc::channel(1);
+    let compare = compare.clone().report_to(sender);
+    let req = http::Request::builder().uri("/pkg.Users/Get").body(http_body::Full::new(bytes::Bytes::from_static(b"id=1"))).unwrap();
+    let response = compare.layer().layer(primary).oneshot(req).await.unwrap();
+    let mut body = response.into_body();
+    while body.data().await.is_some() {
+    }
+    assert!(body.trailers().await.unwrap().is_some());
+    reports.recv().await.unwrap()
+}
+
+#[tokio::test]
+async fn should_compare_agreeing_services() {
+    let report = compare(&Compare::new(Backend("user:1", Code::Ok), 100), Backend("user:1", Code::Ok)).await;
+    assert_eq!(report, ComparisonReport {
+        method: "/pkg.Users/Get".to_owned(),
+        primary_code: Code::Ok,
+        shadow_code: Some(Code::Ok),
+        bodies_equal: Some(true),
+    });
+    assert!(report.is_equal());
+    assert!(!report.shadow_timeout());
+}
+
+#[tokio::test]
+async fn should_compare_disagreeing_services() {
+    let report = compare(&Compare::new(Backend("user:2", Code::Ok), 100), Backend("user:1", Code::Ok)).await;
+    assert_eq!(report.shadow_code, Some(Code::Ok));
+    assert_eq!(report.bodies_equal, Some(false));
+    assert!(!report.is_equal());
+
+    let report = compare(&Compare::new(Backend("", Code::NotFound), 100), Backend("user:1", Code::Ok)).await;
+    assert_eq!(report.primary_code, Code::Ok);
+    assert_eq!(report.shadow_code, Some(Code::NotFound));
+    assert_eq!(report.bodies_equal, Some(false));
+}
+
+#[tokio::test]
+async fn should_report_shadow_timeout() {
+    let stuck = tower::service_fn(|_: http::Request<tonic::body::BoxBody>| core::future::pending::<Result<http::Response<Reply>, Infallible>>());
+    let report = compare(&Compare::new(stuck, 100).timeout(Duration::from_millis(20)), Backend("user:1", Code::Ok)).await;
+    assert!(report.shadow_timeout());
+    assert_eq!(report.primary_code, Code::Ok);
+    assert_eq!(report.bodies_equal, None);
+}
+
+#[tokio::test]
+async fn should_limit_memory_of_request_copies() {
+    let stuck = tower::service_fn(|_: http::Request<tonic::body::BoxBody>| core::future::pending::<Result<http::Response<Reply>, Infallible>>());
+    let (sender, mut reports) = tokio::sync::mpsc::channel(2);
+    let compare = Compare::new(stuck, 100).budget(6).timeout(Duration::from_millis(50)).report_to(sender);
+
+    for _ in 0..2 {
+        let req = http::Request::builder().uri("/pkg.Users/Get").body(http_body::Full::new(bytes::Bytes::from_static(b"id=1"))).unwrap();
+        let response = compare.layer().layer(Backend("user:1", Code::Ok)).oneshot(req).await.unwrap();
+        let mut body = response.into_body();
+        while body.data().await.is_some() {
+        }
+        assert!(body.trailers().await.unwrap().is_some());
+    }
+    //Copy of the first request is kept until its shadow call times out, while second one is over budget
+    assert_eq!(compare.budget_used(), 4);
+
+    assert!(reports.recv().await.unwrap().shadow_timeout());
+    assert_eq!(compare.budget_used(), 0);
+    assert!(tokio::time::timeout(Duration::from_millis(100), reports.recv()).await.is_err());
+}
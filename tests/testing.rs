@@ -0,0 +1,142 @@
+#![cfg(all(feature = "tonic011", feature = "testing"))]
+#![allow(clippy::result_large_err)]
+
+mod common;
+
+use common::EchoClient;
+use common::echo::EchoRequest;
+
+use tonic_interceptor::client::{self, ClientInterceptor};
+use tonic_interceptor::testing::{ClientRecorder, EventKind, Recorder, poll_once, request as grpc_request, service_fn};
+
+use tonic::{Code, Status};
+use tower_service::Service;
+
+type ClientRecorderService<S> = client::ClientInterceptorService<ClientRecorder, S>;
+
+fn request(message: &str) -> EchoRequest {
+    EchoRequest {
+        message: message.to_owned(),
+    }
+}
+
+//Code under test, which builds outgoing metadata
+#[derive(Clone)]
+struct Tenant(&'static str);
+
+impl ClientInterceptor for Tenant {
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<Status> {
+        headers.insert("x-tenant", tonic::metadata::AsciiMetadataValue::from_static(self.0));
+        None
+    }
+
+    fn on_response(&self, _: &tonic::metadata::MetadataMap, _: &http::Extensions) {
+    }
+}
+
+#[tokio::test]
+async fn should_record_calls_to_real_server() {
+    let (service, channel) = common::spawn_echo().await;
+    let recorder = ClientRecorder::new();
+    let channel = tower::ServiceBuilder::new().layer(client::interceptor(Tenant("acme"))).layer(client::interceptor(recorder.clone())).service(channel);
+    let mut client = EchoClient::new(channel);
+
+    client.unary(request("hello")).await.expect("success");
+    client.stream(request("hello world")).await.expect("success");
+    assert_eq!(service.calls(), 2);
+
+    let calls = recorder.calls();
+    assert_eq!(calls.len(), 2);
+    assert_eq!(calls[0].path(), "/test.Echo/Unary");
+    assert_eq!(calls[0].metadata.get("x-tenant").unwrap(), "acme");
+    assert_eq!(recorder.last().unwrap().path(), "/test.Echo/Stream");
+    assert_eq!(recorder.calls_to("/test.Echo/Unary").len(), 1);
+
+    recorder.clear();
+    assert!(recorder.handle().lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn should_short_circuit_expected_status() {
+    let (service, channel) = common::spawn_echo().await;
+    let recorder = ClientRecorder::new();
+    recorder.expect_status("/test.Echo/Unary", Status::not_found("no such user"));
+    let mut client = EchoClient::new(ClientRecorderService::new(recorder.clone(), channel));
+
+    let status = client.unary(request("hello")).await.expect_err("to fail");
+    assert_eq!(status.code(), Code::NotFound);
+    assert_eq!(status.message(), "no such user");
+    assert_eq!(service.calls(), 0);
+
+    //Other methods pass through
+    client.stream(request("hello")).await.expect("success");
+    assert_eq!(service.calls(), 1);
+    assert_eq!(recorder.calls().len(), 2);
+}
+
+#[tokio::test]
+async fn should_work_with_dummy_service() {
+    let recorder = ClientRecorder::new();
+    recorder.expect_status("/test.Echo/Unary", Status::unavailable("down"));
+    let dummy = tower::service_fn(|_: http::Request<tonic::body::BoxBody>| async move {
+        Err::<http::Response<tonic::body::BoxBody>, _>(Status::unimplemented("dummy"))
+    });
+    let mut client = EchoClient::new(ClientRecorderService::new(recorder.clone(), dummy));
+
+    let mut request = tonic::Request::new(request("hello"));
+    request.metadata_mut().insert("x-request-id", "1".parse().unwrap());
+    let status = client.unary(request).await.expect_err("to fail");
+    assert_eq!(status.code(), Code::Unavailable);
+
+    let call = recorder.last().expect("to record call");
+    assert_eq!(call.path(), "/test.Echo/Unary");
+    assert_eq!(call.metadata.get("x-request-id").unwrap(), "1");
+}
+
+#[test]
+fn should_preset_grpc_request() {
+    let request = grpc_request().uri("/test.Echo/Unary").body(()).unwrap();
+    assert_eq!(request.method(), http::Method::POST);
+    assert_eq!(request.version(), http::Version::HTTP_2);
+    assert_eq!(request.uri().path(), "/test.Echo/Unary");
+    assert_eq!(request.headers().get("content-type").unwrap(), "application/grpc");
+    assert_eq!(request.headers().get("te").unwrap(), "trailers");
+}
+
+#[test]
+#[should_panic(expected = "Future is pending")]
+fn should_panic_on_pending_future() {
+    poll_once(core::future::pending::<()>());
+}
+
+#[test]
+fn should_record_layer_order() {
+    let auth = Recorder::new("auth").with_keys(&["x-user"]);
+    let logging = auth.share("logging");
+    auth.reject_nth(2, Status::unauthenticated("second"));
+
+    let svc = service_fn(|_: http::Request<()>| Ok::<_, Status>(http::Response::builder().header("grpc-status", "0").header("x-user", "service").body(()).unwrap()));
+    let mut service = tower::ServiceBuilder::new().layer(tonic_interceptor::interceptor(auth.clone())).layer(tonic_interceptor::interceptor(logging.clone())).service(svc);
+
+    poll_once(service.call(grpc_request().header("x-user", "admin").body(()).unwrap())).expect("success");
+    let response = poll_once(service.call(grpc_request().header("x-user", "guest").body(()).unwrap())).expect("success");
+    assert_eq!(response.headers().get("grpc-status").unwrap(), "16");
+
+    assert_eq!(auth.sequence(), [
+        ("auth", EventKind::Request),
+        ("logging", EventKind::Request),
+        ("logging", EventKind::Response(Code::Ok)),
+        ("auth", EventKind::Response(Code::Ok)),
+        ("auth", EventKind::Request),
+    ]);
+
+    let events = logging.events();
+    assert!(events.iter().enumerate().all(|(idx, event)| event.seq == idx));
+    assert_eq!(events[0].headers.get("x-user").unwrap(), "admin");
+    assert_eq!(events[2].headers.get("x-user").unwrap(), "service");
+    assert_eq!(events[4].headers.get("x-user").unwrap(), "guest");
+    assert!(events[4].headers.get("content-type").is_none());
+
+    auth.clear();
+    assert!(logging.handle().lock().unwrap().is_empty());
+}
@@ -0,0 +1,157 @@
+#![cfg(feature = "tonic011")]
+use tonic_interceptor::timeout;
+
+use core::time::Duration;
+
+const NANOS: u64 = 1;
+const MICROS: u64 = 1_000;
+const MILLIS: u64 = 1_000_000;
+const SECONDS: u64 = 1_000_000_000;
+const MINUTES: u64 = 60 * SECONDS;
+const HOURS: u64 = 60 * MINUTES;
+
+fn encode(nanos: u128) -> String {
+    let timeout = Duration::new((nanos / 1_000_000_000) as u64, (nanos % 1_000_000_000) as u32);
+    timeout::encode(timeout).to_str().unwrap().to_owned()
+}
+
+#[test]
+fn should_encode_with_largest_unit_that_fits() {
+    let cases: &[(u128, &str)] = &[
+        (0, "0H"),
+        (1, "1n"),
+        (999, "999n"),
+        (1_001, "1001n"),
+        (99_999_999, "99999999n"),
+        //Each unit's boundary: value is whole number of larger unit
+        (MICROS as u128, "1u"),
+        (MILLIS as u128, "1m"),
+        (SECONDS as u128, "1S"),
+        (MINUTES as u128, "1M"),
+        (HOURS as u128, "1H"),
+        (999 * MICROS as u128, "999u"),
+        (1_500 * MILLIS as u128, "1500m"),
+        (59 * SECONDS as u128, "59S"),
+        (90 * SECONDS as u128, "90S"),
+        (120 * MINUTES as u128, "2H"),
+        (100_000_000, "100m"),
+        (99_999_999_000, "99999999u"),
+        (99_999_999_000_000, "99999999m"),
+        (99_999_999 * SECONDS as u128, "99999999S"),
+        (99_999_999 * MINUTES as u128, "99999999M"),
+        (99_999_999 * HOURS as u128, "99999999H"),
+        //Beyond grammar is capped
+        (99_999_999 * HOURS as u128 + 1, "99999999H"),
+        (u64::MAX as u128 * SECONDS as u128, "99999999H"),
+    ];
+
+    for (nanos, expected) in cases {
+        assert_eq!(encode(*nanos), *expected, "nanos={}", nanos);
+    }
+}
+
+#[test]
+fn should_truncate_when_losing_precision() {
+    let cases: &[(u128, &str)] = &[
+        //Value no longer fits 8 digits in previous unit
+        (100_000_001, "100m"),
+        (100_000_999, "100m"),
+        (100_001_000, "100001u"),
+        (99_999_999_001, "99999999u"),
+        (100_000_000 * MICROS as u128 + 1, "100S"),
+        (100_000_000 * MICROS as u128 + MILLIS as u128 - 1, "100S"),
+        (99_999_999_999_999, "99999999m"),
+        (100_000_000 * MILLIS as u128 + 1, "100000S"),
+        (100_000_000 * SECONDS as u128 + 1, "1666666M"),
+        (100_000_000 * MINUTES as u128 + 1, "1666666H"),
+        //Truncated value is represented with the largest unit
+        (SECONDS as u128 + 1, "1S"),
+        (HOURS as u128 + 1, "1H"),
+    ];
+
+    for (nanos, expected) in cases {
+        assert_eq!(encode(*nanos), *expected, "nanos={}", nanos);
+    }
+
+    //Encoded timeout is never longer than original
+    for nanos in (100_000_000u128..100_010_000).step_by(7) {
+        let encoded = timeout::parse(encode(nanos).as_bytes()).unwrap();
+        assert!(encoded.as_nanos() <= nanos);
+        assert!(nanos - encoded.as_nanos() < MICROS as u128);
+    }
+}
+
+#[test]
+fn should_parse_every_unit() {
+    let cases: &[(&str, u64)] = &[
+        ("1n", NANOS),
+        ("1u", MICROS),
+        ("1m", MILLIS),
+        ("1S", SECONDS),
+        ("1M", MINUTES),
+        ("1H", HOURS),
+        ("0n", 0),
+        ("0H", 0),
+        ("00000001S", SECONDS),
+        ("99999999n", 99_999_999 * NANOS),
+        ("99999999u", 99_999_999 * MICROS),
+        ("99999999m", 99_999_999 * MILLIS),
+        ("99999999S", 99_999_999 * SECONDS),
+        ("99999999M", 99_999_999 * MINUTES),
+    ];
+
+    for (value, nanos) in cases {
+        assert_eq!(timeout::parse(value.as_bytes()), Some(Duration::from_nanos(*nanos)), "value={}", value);
+    }
+    assert_eq!(timeout::parse(b"99999999H"), Some(Duration::from_secs(99_999_999 * 60 * 60)));
+}
+
+#[test]
+fn should_reject_invalid_values() {
+    let cases: &[&str] = &[
+        "",
+        "S",
+        "1",
+        "10",
+        "1s",
+        "1h",
+        "1x",
+        "123456789S",
+        "-1S",
+        "+1S",
+        " 1S",
+        "1 S",
+        "1.5S",
+        "1SS",
+    ];
+
+    for value in cases {
+        assert_eq!(timeout::parse(value.as_bytes()), None, "value={:?}", value);
+    }
+}
+
+#[test]
+fn should_round_trip_every_unit() {
+    for unit in [NANOS, MICROS, MILLIS, SECONDS, MINUTES, HOURS].iter() {
+        for value in [1u32, 7, 59, 60, 1_000, 99_999_999].iter() {
+            let duration = Duration::from_nanos(*unit) * *value;
+            let encoded = timeout::encode(duration);
+            assert_eq!(timeout::parse(encoded.as_bytes()), Some(duration), "encoded={:?}", encoded);
+        }
+    }
+}
+
+#[test]
+fn should_be_stable_when_re_encoded() {
+    //Value, which is once encoded, is encoded the same way after parsing
+    for unit in [NANOS, MICROS, MILLIS, SECONDS, MINUTES, HOURS].iter() {
+        for value in [1u32, 59, 100_000, 99_999_999].iter() {
+            for offset in [0u64, 1].iter() {
+                let duration = Duration::from_nanos(*unit) * *value + Duration::from_nanos(*offset);
+                let encoded = timeout::encode(duration);
+                let parsed = timeout::parse(encoded.as_bytes()).unwrap();
+                assert_eq!(timeout::encode(parsed), encoded, "duration={:?}", duration);
+            }
+        }
+    }
+}
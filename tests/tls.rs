@@ -0,0 +1,178 @@
+#![cfg(feature = "tonic011")]
+#![allow(clippy::result_large_err)]
+
+#[cfg(feature = "rustls")]
+mod common;
+
+use tonic_interceptor::Interceptor;
+use tonic_interceptor::tls::{ConnectionInfo, TlsInfo};
+
+use tonic::Code;
+
+#[test]
+fn should_check_server_name() {
+    let info = ConnectionInfo::new().allow_sni("api.example.com").allow_sni("*.tenants.example.com");
+    let check = |sni: Option<&str>| info.check(Some(&TlsInfo {
+        sni: sni.map(str::to_owned),
+        ..TlsInfo::default()
+    })).map_err(|status| status.message().to_owned());
+
+    assert_eq!(check(Some("API.example.com")), Ok(()));
+    assert_eq!(check(Some("acme.tenants.example.com")), Ok(()));
+    assert!(check(Some("tenants.example.com")).is_err());
+    assert!(check(Some("a.b.tenants.example.com")).is_err());
+    assert!(check(Some(".tenants.example.com")).is_err());
+    assert_eq!(check(None), Err("server name is required".to_owned()));
+    assert_eq!(info.check(None).unwrap_err().code(), Code::PermissionDenied);
+
+    //Without allowlist anything goes, even plaintext
+    assert!(ConnectionInfo::new().check(None).is_ok());
+}
+
+#[test]
+fn should_insert_extracted_tls_info() {
+    #[derive(Clone)]
+    struct AcceptorInfo(&'static str);
+
+    let tls_info = |sni: &str| TlsInfo {
+        sni: Some(sni.to_owned()),
+        alpn: Some("h2".to_owned()),
+        cipher: Some("TLS13_AES_128_GCM_SHA256".to_owned()),
+    };
+    let interceptor = ConnectionInfo::new().extractor(move |extensions| extensions.get::<AcceptorInfo>().map(|info| tls_info(info.0))).allow_sni("api.example.com");
+    let mut metadata = tonic::metadata::MetadataMap::new();
+    let mut extensions = http::Extensions::new();
+    extensions.insert(AcceptorInfo("api.example.com"));
+    assert!(interceptor.on_request(&mut metadata, &mut extensions).is_none());
+    assert_eq!(TlsInfo::from_extensions(&extensions), Some(&tls_info("api.example.com")));
+
+    let status = interceptor.on_request(&mut metadata, &mut http::Extensions::new()).expect("rejection");
+    assert_eq!(status.code(), Code::PermissionDenied);
+}
+
+//Real TLS server, which connections carry `TlsInfo` of `rustls` acceptor as their connection info
+#[cfg(feature = "rustls")]
+mod acceptor {
+    use super::common::{EchoClient, EchoServer, EchoService};
+    use super::common::echo::EchoRequest;
+
+    use tonic_interceptor::{InterceptorChain, InterceptorFn};
+    use tonic_interceptor::tls::{ConnectionInfo, TlsInfo};
+
+    use tonic::{Code, Status};
+    use tonic::transport::{Certificate, Channel, ClientTlsConfig};
+    use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+    use tokio_stream::StreamExt;
+
+    use core::pin::Pin;
+    use core::task;
+    use std::sync::Arc;
+
+    //Self-signed for `api.example.com` and `admin.internal`
+    const CERT: &[u8] = include_bytes!("data/tls-cert.pem");
+    const KEY: &[u8] = include_bytes!("data/tls-key.pem");
+
+    struct Accepted(tokio_rustls::server::TlsStream<tokio::net::TcpStream>);
+
+    impl tonic::transport::server::Connected for Accepted {
+        type ConnectInfo = TlsInfo;
+
+        fn connect_info(&self) -> Self::ConnectInfo {
+            TlsInfo::from_rustls(self.0.get_ref().1)
+        }
+    }
+
+    impl AsyncRead for Accepted {
+        fn poll_read(mut self: Pin<&mut Self>, ctx: &mut task::Context<'_>, buf: &mut ReadBuf<'_>) -> task::Poll<std::io::Result<()>> {
+            Pin::new(&mut self.0).poll_read(ctx, buf)
+        }
+    }
+
+    impl AsyncWrite for Accepted {
+        fn poll_write(mut self: Pin<&mut Self>, ctx: &mut task::Context<'_>, buf: &[u8]) -> task::Poll<std::io::Result<usize>> {
+            Pin::new(&mut self.0).poll_write(ctx, buf)
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<std::io::Result<()>> {
+            Pin::new(&mut self.0).poll_flush(ctx)
+        }
+
+        fn poll_shutdown(mut self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> task::Poll<std::io::Result<()>> {
+            Pin::new(&mut self.0).poll_shutdown(ctx)
+        }
+    }
+
+    fn acceptor() -> tokio_rustls::TlsAcceptor {
+        let certs = rustls_pemfile::certs(&mut &CERT[..]).collect::<Result<Vec<_>, _>>().expect("certificate");
+        let key = rustls_pemfile::private_key(&mut &KEY[..]).expect("key").expect("key");
+        let mut config = tokio_rustls::rustls::ServerConfig::builder().with_no_client_auth().with_single_cert(certs, key).expect("config");
+        config.alpn_protocols = vec![b"h2".to_vec()];
+        tokio_rustls::TlsAcceptor::from(Arc::new(config))
+    }
+
+    //Writes server name into metadata, which is echoed back by service
+    fn expose_sni(headers: &mut tonic::metadata::MetadataMap, extensions: &mut http::Extensions) -> Option<Status> {
+        let info = TlsInfo::from_extensions(extensions).expect("tls info");
+        assert_eq!(info.alpn.as_deref(), Some("h2"));
+        assert!(info.cipher.is_some());
+        headers.insert("x-sni", info.sni.as_deref().unwrap_or("-").parse().unwrap());
+        None
+    }
+
+    async fn connect(port: u16, domain: &str) -> EchoClient<Channel> {
+        let tls = ClientTlsConfig::new().ca_certificate(Certificate::from_pem(CERT)).domain_name(domain);
+        let channel = Channel::from_shared(format!("https://127.0.0.1:{}", port)).unwrap().tls_config(tls).expect("tls").connect().await.expect("connect");
+        EchoClient::new(channel)
+    }
+
+    //Starts TLS server, returning client of each host name
+    async fn spawn(info: ConnectionInfo) -> (EchoService, EchoClient<Channel>, EchoClient<Channel>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let port = listener.local_addr().unwrap().port();
+        let acceptor = acceptor();
+        let incoming = tokio_stream::wrappers::TcpListenerStream::new(listener).then(move |stream| {
+            let acceptor = acceptor.clone();
+            async move {
+                acceptor.accept(stream?).await.map(Accepted)
+            }
+        });
+
+        let service = EchoService::default();
+        let interceptor = InterceptorChain::new().with(info).with(InterceptorFn {
+            on_request: expose_sni,
+            on_response: |_: Code, _: &mut http::HeaderMap, _: &http::Extensions| {},
+        });
+        let router = tonic::transport::Server::builder().layer(tonic_interceptor::interceptor(interceptor)).add_service(EchoServer::new(service.clone()));
+        tokio::spawn(router.serve_with_incoming(incoming));
+
+        (service, connect(port, "api.example.com").await, connect(port, "admin.internal").await)
+    }
+
+    fn request() -> tonic::Request<EchoRequest> {
+        tonic::Request::new(EchoRequest {
+            message: "hello".to_owned(),
+        })
+    }
+
+    #[tokio::test]
+    async fn should_expose_tls_info_of_connection() {
+        let (service, mut api, mut admin) = spawn(ConnectionInfo::new()).await;
+        let response = api.unary(request()).await.expect("success");
+        assert_eq!(response.metadata().get("x-sni").unwrap(), "api.example.com");
+        let response = admin.unary(request()).await.expect("success");
+        assert_eq!(response.metadata().get("x-sni").unwrap(), "admin.internal");
+        assert_eq!(service.calls(), 2);
+    }
+
+    #[tokio::test]
+    async fn should_reject_server_name_outside_of_allowlist() {
+        let (service, mut api, mut admin) = spawn(ConnectionInfo::new().allow_sni("*.EXAMPLE.com")).await;
+        let response = api.unary(request()).await.expect("success");
+        assert_eq!(response.metadata().get("x-sni").unwrap(), "api.example.com");
+
+        let status = admin.unary(request()).await.unwrap_err();
+        assert_eq!(status.code(), Code::PermissionDenied);
+        assert_eq!(status.message(), "server name 'admin.internal' is not allowed");
+        assert_eq!(service.calls(), 1);
+    }
+}
@@ -0,0 +1,142 @@
+#![cfg(feature = "tonic011")]
+use tonic_interceptor::client::{BearerAuth, TokenSource};
+
+use tonic::Status;
+use tonic::metadata::{Ascii, MetadataValue};
+use tower::{Layer, ServiceExt};
+
+use core::convert::Infallible;
+
+fn auth_echo() -> impl tower::Service<http::Request<()>, Response = http::Response<Option<http::HeaderValue>>, Error = Infallible, Future = impl Send> + Clone + Send + 'static {
+    tower::service_fn(|req: http::Request<()>| async move {
+        Ok::<_, Infallible>(http::Response::new(req.headers().get(http::header::AUTHORIZATION).cloned()))
+    })
+}
+
+struct Static(&'static str);
+
+impl TokenSource for Static {
+    async fn token(&self) -> Result<MetadataValue<Ascii>, Status> {
+        match self.0 {
+            "" => Err(Status::unauthenticated("no token")),
+            token => Ok(MetadataValue::from_static(token)),
+        }
+    }
+}
+
+#[tokio::test]
+async fn should_inject_bearer_token() {
+    let service = BearerAuth::new(Static("secret")).layer(auth_echo());
+    let response = service.oneshot(http::Request::new(())).await.expect("success");
+    let authorization = response.into_body().expect("to have authorization");
+    assert_eq!(authorization, "Bearer secret");
+    assert!(authorization.is_sensitive());
+}
+
+#[tokio::test]
+async fn should_fail_call_with_token_source_status() {
+    let service = BearerAuth::new(Static("")).layer(auth_echo());
+    let error = service.oneshot(http::Request::new(())).await.expect_err("to fail");
+    let status = Status::from_error(error);
+    assert_eq!(status.code(), tonic::Code::Unauthenticated);
+    assert_eq!(status.message(), "no token");
+}
+
+#[cfg(feature = "tokio")]
+mod cached {
+    use super::*;
+
+    use tonic_interceptor::client::{CachedToken, Token, TokenRefresh};
+
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use core::time::Duration;
+
+    #[derive(Clone)]
+    struct Counting {
+        refreshes: Arc<AtomicUsize>,
+        expires_in: Option<Duration>,
+    }
+
+    impl Counting {
+        fn new(expires_in: Option<Duration>) -> Self {
+            Self {
+                refreshes: Arc::new(AtomicUsize::new(0)),
+                expires_in,
+            }
+        }
+
+        fn refreshes(&self) -> usize {
+            self.refreshes.load(Ordering::SeqCst)
+        }
+    }
+
+    impl TokenRefresh for Counting {
+        async fn refresh(&self) -> Result<Token, Status> {
+            //Give concurrent calls chance to pile up while refresh is in progress
+            for _ in 0..10 {
+                tokio::task::yield_now().await;
+            }
+            let idx = self.refreshes.fetch_add(1, Ordering::SeqCst) + 1;
+            Ok(Token {
+                value: format!("token-{}", idx).parse().unwrap(),
+                expires_in: self.expires_in,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn should_refresh_once_for_concurrent_calls() {
+        let refresh = Counting::new(Some(Duration::from_secs(3600)));
+        let layer = BearerAuth::new(CachedToken::new(refresh.clone()));
+
+        let mut calls = Vec::new();
+        for _ in 0..16 {
+            let service = layer.layer(auth_echo());
+            calls.push(tokio::spawn(service.oneshot(http::Request::new(()))));
+        }
+
+        for call in calls {
+            let response = call.await.expect("to join").expect("success");
+            assert_eq!(response.into_body().expect("to have authorization"), "Bearer token-1");
+        }
+        assert_eq!(refresh.refreshes(), 1);
+    }
+
+    #[tokio::test]
+    async fn should_refresh_token_within_expiry_margin() {
+        let refresh = Counting::new(Some(Duration::from_secs(10)));
+        let source = CachedToken::with_margin(refresh.clone(), Duration::from_secs(30));
+
+        assert_eq!(source.token().await.expect("token"), "token-1");
+        assert_eq!(source.token().await.expect("token"), "token-2");
+        assert_eq!(refresh.refreshes(), 2);
+    }
+
+    #[tokio::test]
+    async fn should_refresh_token_once_expired() {
+        use tonic_interceptor::testing::TestTimer;
+
+        let timer = TestTimer::new();
+        let refresh = Counting::new(Some(Duration::from_secs(60)));
+        let source = CachedToken::with_margin(refresh.clone(), Duration::from_secs(10)).clock(timer.clone());
+
+        assert_eq!(source.token().await.expect("token"), "token-1");
+        timer.advance(Duration::from_secs(49));
+        assert_eq!(source.token().await.expect("token"), "token-1");
+        timer.advance(Duration::from_secs(1));
+        assert_eq!(source.token().await.expect("token"), "token-2");
+        assert_eq!(refresh.refreshes(), 2);
+    }
+
+    #[tokio::test]
+    async fn should_keep_token_without_expiry() {
+        let refresh = Counting::new(None);
+        let source = CachedToken::new(refresh.clone());
+
+        for _ in 0..5 {
+            assert_eq!(source.token().await.expect("token"), "token-1");
+        }
+        assert_eq!(refresh.refreshes(), 1);
+    }
+}
@@ -0,0 +1,226 @@
+#![cfg(feature = "tonic011")]
+#![allow(clippy::result_large_err)]
+
+use tonic_interceptor::body::{InspectedBody, UnaryInspector, Uninspectable};
+use tonic_interceptor::testing::{poll_once, service_fn, with_noop_context};
+
+use tonic::Status;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use core::task;
+use core::pin::Pin;
+use core::convert::Infallible;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+
+//Request body, which chunks are always ready
+#[derive(Default)]
+struct Chunks(VecDeque<Bytes>);
+
+impl http_body::Body for Chunks {
+    type Data = Bytes;
+    type Error = Infallible;
+
+    fn poll_data(mut self: Pin<&mut Self>, _: &mut task::Context<'_>) -> task::Poll<Option<Result<Self::Data, Self::Error>>> {
+        task::Poll::Ready(self.0.pop_front().map(Ok))
+    }
+
+    fn poll_trailers(self: Pin<&mut Self>, _: &mut task::Context<'_>) -> task::Poll<Result<Option<http::HeaderMap>, Self::Error>> {
+        task::Poll::Ready(Ok(None))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+fn frame(compressed: bool, message: &[u8]) -> Vec<u8> {
+    let mut frame = vec![compressed as u8];
+    frame.extend_from_slice(&(message.len() as u32).to_be_bytes());
+    frame.extend_from_slice(message);
+    frame
+}
+
+fn request(path: &str, chunks: &[&[u8]]) -> http::Request<Chunks> {
+    let chunks = chunks.iter().map(|chunk| Bytes::copy_from_slice(chunk)).collect();
+    http::Request::builder().uri(path).header("content-type", "application/grpc").body(Chunks(chunks)).unwrap()
+}
+
+fn drain(mut body: InspectedBody<Chunks>) -> Vec<u8> {
+    let mut data = Vec::new();
+    with_noop_context(|ctx| while let task::Poll::Ready(Some(Ok(chunk))) = http_body::Body::poll_data(Pin::new(&mut body), ctx) {
+        data.extend_from_slice(&chunk);
+    });
+    data
+}
+
+type Received = Arc<Mutex<Vec<(bool, Vec<u8>)>>>;
+
+//Calls inspector with request, returning grpc-status of rejection and bodies received by inner service
+fn call(inspector: &UnaryInspector, req: http::Request<Chunks>) -> (Option<String>, Vec<(bool, Vec<u8>)>) {
+    let received = Received::default();
+    let svc = {
+        let received = received.clone();
+        service_fn(move |req: http::Request<InspectedBody<Chunks>>| {
+            let body = req.into_body();
+            let is_buffered = body.is_buffered();
+            received.lock().unwrap().push((is_buffered, drain(body)));
+            Ok::<_, Infallible>(http::Response::new(()))
+        })
+    };
+    let mut svc = inspector.layer().layer(svc);
+    let response = poll_once(svc.call(req)).unwrap();
+    let status = response.headers().get("grpc-status").map(|status| status.to_str().unwrap().to_owned());
+    let received = received.lock().unwrap().clone();
+    (status, received)
+}
+
+fn non_empty(message: &[u8]) -> Result<(), Status> {
+    match message.is_empty() {
+        true => Err(Status::invalid_argument("empty query")),
+        false => Ok(()),
+    }
+}
+
+#[test]
+fn should_inspect_and_replay_unary_message() {
+    let inspector = UnaryInspector::new("/pkg.Search/Query", non_empty);
+
+    let message = frame(false, b"query");
+    //Split across chunks
+    let (status, received) = call(&inspector, request("/pkg.Search/Query", &[&message[..3], &message[3..]]));
+    assert_eq!(status, None);
+    assert_eq!(received, [(true, message)]);
+
+    let (status, received) = call(&inspector, request("/pkg.Search/Query", &[&frame(false, b"")]));
+    assert_eq!(status.unwrap(), "3");
+    assert!(received.is_empty());
+
+    //Other methods are not buffered
+    let (status, received) = call(&inspector, request("/pkg.Search/Suggest", &[&frame(false, b"")]));
+    assert_eq!(status, None);
+    assert_eq!(received, [(false, frame(false, b""))]);
+}
+
+#[test]
+fn should_handle_oversized_message() {
+    let inspector = UnaryInspector::new("*", non_empty).max_message_size(4);
+    let message = frame(false, b"query");
+
+    let (status, received) = call(&inspector, request("/pkg.Search/Query", &[&message]));
+    assert_eq!(status.unwrap(), "8");
+    assert!(received.is_empty());
+
+    //Only header is needed to reject
+    let (status, _) = call(&inspector, request("/pkg.Search/Query", &[&message[..5]]));
+    assert_eq!(status.unwrap(), "8");
+
+    let inspector = inspector.on_oversized(Uninspectable::PassThrough);
+    let (status, received) = call(&inspector, request("/pkg.Search/Query", &[&message[..6], &message[6..]]));
+    assert_eq!(status, None);
+    assert_eq!(received, [(true, message)]);
+}
+
+#[test]
+fn should_handle_streaming_request() {
+    let inspector = UnaryInspector::new("*", non_empty);
+    let mut messages = frame(false, b"first");
+    messages.extend_from_slice(&frame(false, b""));
+
+    //Second message is not inspected
+    let (status, received) = call(&inspector, request("/pkg.Search/Query", &[&messages[..10], &messages[10..]]));
+    assert_eq!(status, None);
+    assert_eq!(received, [(true, messages.clone())]);
+
+    let inspector = inspector.on_streaming(Uninspectable::Reject);
+    let (status, received) = call(&inspector, request("/pkg.Search/Query", &[&messages]));
+    assert_eq!(status.unwrap(), "12");
+    assert!(received.is_empty());
+}
+
+#[test]
+fn should_pass_incomplete_message_to_inner_service() {
+    let inspector = UnaryInspector::new("*", non_empty);
+    let message = frame(false, b"query");
+
+    let (status, received) = call(&inspector, request("/pkg.Search/Query", &[&message[..7]]));
+    assert_eq!(status, None);
+    assert_eq!(received, [(true, message[..7].to_vec())]);
+}
+
+#[cfg(not(feature = "gzip"))]
+#[test]
+fn should_reject_compressed_message() {
+    let inspector = UnaryInspector::new("*", non_empty);
+    let mut req = request("/pkg.Search/Query", &[&frame(true, b"query")]);
+    req.headers_mut().insert("grpc-encoding", http::HeaderValue::from_static("gzip"));
+
+    let (status, received) = call(&inspector, req);
+    assert_eq!(status.unwrap(), "12");
+    assert!(received.is_empty());
+}
+
+#[cfg(feature = "gzip")]
+#[test]
+fn should_decompress_gzip_message() {
+    use std::io::Write;
+
+    fn gzip(message: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(message).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    let inspector = UnaryInspector::new("*", |message: &[u8]| match message {
+        b"query" => Ok(()),
+        _ => Err(Status::invalid_argument("unexpected query")),
+    }).max_message_size(32);
+
+    for (message, expected) in [(b"query".to_vec(), None), (b"other".to_vec(), Some("3")), (vec![b'q'; 33], Some("8"))].iter() {
+        let compressed = frame(true, &gzip(message));
+        let mut req = request("/pkg.Search/Query", &[&compressed]);
+        req.headers_mut().insert("grpc-encoding", http::HeaderValue::from_static("gzip"));
+        let (status, received) = call(&inspector, req);
+        assert_eq!(status.as_deref(), *expected);
+        //Body is passed compressed
+        if expected.is_none() {
+            assert_eq!(received, [(true, compressed)]);
+        }
+    }
+
+    let mut req = request("/pkg.Search/Query", &[&frame(true, b"query")]);
+    req.headers_mut().insert("grpc-encoding", http::HeaderValue::from_static("deflate"));
+    assert_eq!(call(&inspector, req).0.unwrap(), "12");
+}
+
+#[cfg(feature = "prost")]
+#[test]
+fn should_inspect_decoded_message() {
+    use prost::Message;
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    struct ListRequest {
+        #[prost(int32, tag = "1")]
+        page_size: i32,
+    }
+
+    let inspector = UnaryInspector::with_message("/pkg.Items/List", |request: &ListRequest| match request.page_size {
+        0..=1000 => Ok(()),
+        _ => Err(Status::invalid_argument("page_size must not exceed 1000")),
+    });
+
+    let message = frame(false, &ListRequest { page_size: 100 }.encode_to_vec());
+    let (status, received) = call(&inspector, request("/pkg.Items/List", &[&message]));
+    assert_eq!(status, None);
+    assert_eq!(received, [(true, message)]);
+
+    let message = frame(false, &ListRequest { page_size: 1001 }.encode_to_vec());
+    assert_eq!(call(&inspector, request("/pkg.Items/List", &[&message])).0.unwrap(), "3");
+
+    //Truncated varint
+    let message = frame(false, &[0x08, 0xff]);
+    assert_eq!(call(&inspector, request("/pkg.Items/List", &[&message])).0.unwrap(), "3");
+}
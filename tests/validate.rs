@@ -0,0 +1,232 @@
+#![cfg(feature = "tonic011")]
+use tonic_interceptor::Interceptor;
+use tonic_interceptor::validate::FromValidateRequest;
+
+use tonic::metadata::MetadataMap;
+
+fn metadata(entries: &[(&'static str, &'static str)]) -> MetadataMap {
+    let mut metadata = MetadataMap::new();
+    for (key, value) in entries {
+        metadata.append(*key, value.parse().unwrap());
+    }
+    metadata
+}
+
+fn rejection(status: http::StatusCode) -> http::Response<()> {
+    http::Response::builder().status(status).header("www-authenticate", "Basic realm=\"api\"").header("x-reason", "scope").body(()).unwrap()
+}
+
+#[cfg(feature = "tower-http")]
+#[test]
+fn should_adapt_bearer_validator() {
+    use tonic_interceptor::InterceptorService;
+    use tonic_interceptor::testing::{poll_once, service_fn};
+    use tower_http::auth::require_authorization::Bearer;
+    use tower_http::validate_request::ValidateRequestHeaderLayer;
+    use tower_service::Service;
+
+    use core::convert::Infallible;
+
+    let interceptor = FromValidateRequest::from_layer(ValidateRequestHeaderLayer::<Bearer<tonic::body::BoxBody>>::bearer("secret"));
+
+    assert!(interceptor.on_request(&mut metadata(&[("authorization", "Bearer secret")]), &mut http::Extensions::new()).is_none());
+    let status = interceptor.on_request(&mut metadata(&[("authorization", "Bearer other")]), &mut http::Extensions::new()).unwrap();
+    assert_eq!(status.code(), tonic::Code::Unauthenticated);
+    assert_eq!(status.message(), "Unauthorized");
+    let status = interceptor.on_request(&mut metadata(&[]), &mut http::Extensions::new()).unwrap();
+    assert_eq!(status.code(), tonic::Code::Unauthenticated);
+
+    let mut svc = InterceptorService::new(interceptor, service_fn(|_: http::Request<()>| Ok::<_, Infallible>(http::Response::new(()))));
+    let response = poll_once(svc.call(http::Request::builder().uri("/pkg.Svc/Call").body(()).unwrap())).unwrap();
+    assert_eq!(response.headers()["grpc-status"], "16");
+    let response = poll_once(svc.call(http::Request::builder().uri("/pkg.Svc/Call").header("authorization", "Bearer secret").body(()).unwrap())).unwrap();
+    assert!(!response.headers().contains_key("grpc-status"));
+}
+
+#[cfg(feature = "tower-http")]
+#[test]
+fn should_adapt_tower_http_validator() {
+    use tower_http::validate_request::ValidateRequestHeaderLayer;
+
+    //`tower_http` validator, accepting only `application/grpc`
+    let interceptor = FromValidateRequest::from_layer(ValidateRequestHeaderLayer::<tower_http::validate_request::AcceptHeader<tonic::body::BoxBody>>::accept("application/grpc"));
+    assert!(interceptor.on_request(&mut metadata(&[("accept", "application/grpc")]), &mut http::Extensions::new()).is_none());
+    let status = interceptor.on_request(&mut metadata(&[("accept", "text/html")]), &mut http::Extensions::new()).unwrap();
+    assert_eq!(status.code(), tonic::Code::Internal);
+}
+
+#[test]
+fn should_map_status_and_preserve_headers() {
+    let unauthorized = |_: &mut http::Request<()>| Err(rejection(http::StatusCode::UNAUTHORIZED));
+    let status = FromValidateRequest::new(unauthorized).on_request(&mut metadata(&[]), &mut http::Extensions::new()).unwrap();
+    assert_eq!(status.code(), tonic::Code::Unauthenticated);
+    assert_eq!(status.metadata().get("www-authenticate").unwrap(), "Basic realm=\"api\"");
+    assert!(status.metadata().get("x-reason").is_none());
+
+    let forbidden = |_: &mut http::Request<()>| Err(rejection(http::StatusCode::FORBIDDEN));
+    let status = FromValidateRequest::new(forbidden).preserve(http::header::HeaderName::from_static("x-reason")).on_request(&mut metadata(&[]), &mut http::Extensions::new()).unwrap();
+    assert_eq!(status.code(), tonic::Code::PermissionDenied);
+    assert_eq!(status.message(), "Forbidden");
+    assert_eq!(status.metadata().get("x-reason").unwrap(), "scope");
+
+    let not_acceptable = |_: &mut http::Request<()>| Err(rejection(http::StatusCode::NOT_ACCEPTABLE));
+    let status = FromValidateRequest::new(not_acceptable).on_request(&mut metadata(&[]), &mut http::Extensions::new()).unwrap();
+    assert_eq!(status.code(), tonic::Code::Internal);
+}
+
+#[test]
+fn should_keep_changes_of_validator() {
+    let validator = |request: &mut http::Request<()>| {
+        assert_eq!(request.uri().path(), "/pkg.Svc/Call");
+        request.headers_mut().insert("x-validated", http::HeaderValue::from_static("1"));
+        request.extensions_mut().insert(42u32);
+        Ok::<_, http::Response<()>>(())
+    };
+    let interceptor = FromValidateRequest::new(validator);
+    let mut headers = metadata(&[("x-tenant", "acme")]);
+    let mut extensions = http::Extensions::new();
+    assert!(interceptor.on_request_with_uri(&"/pkg.Svc/Call".parse().unwrap(), &mut headers, &mut extensions).is_none());
+    assert_eq!(headers.get("x-tenant").unwrap(), "acme");
+    assert_eq!(headers.get("x-validated").unwrap(), "1");
+    assert_eq!(extensions.get::<u32>(), Some(&42));
+}
+
+#[cfg(feature = "details")]
+mod message {
+    use tonic_interceptor::validate::{MessageValidator, Rules, Violations};
+    use tonic_interceptor::details::{FieldViolation, StatusDetails};
+
+    use prost::Message;
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    struct Filter {
+        #[prost(string, tag = "1")]
+        query: String,
+        #[prost(uint32, tag = "2")]
+        max_age_days: u32,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    struct ListRequest {
+        #[prost(string, tag = "1")]
+        parent: String,
+        #[prost(int32, tag = "2")]
+        page_size: i32,
+        #[prost(message, optional, tag = "3")]
+        filter: Option<Filter>,
+        #[prost(string, tag = "4")]
+        page_token: String,
+    }
+
+    fn rules() -> Rules<ListRequest> {
+        let filter = Rules::new().len("query", |filter: &Filter| filter.query.as_str(), 1..=8)
+                                 .range("max_age_days", |filter: &Filter| filter.max_age_days, 1..=30);
+        Rules::new().len("parent", |request: &ListRequest| request.parent.as_str(), 1..=16)
+                    .range("page_size", |request: &ListRequest| request.page_size, 0..=1000)
+                    .required("filter", |request: &ListRequest| request.filter.as_ref())
+                    .nested("filter", |request: &ListRequest| request.filter.as_ref(), filter)
+                    .custom(|request: &ListRequest, violations: &mut Violations| if !request.page_token.is_empty() && request.page_size == 0 {
+                        violations.add("page_token", "requires page_size");
+                    })
+    }
+
+    fn valid() -> ListRequest {
+        ListRequest {
+            parent: "shelves/1".to_owned(),
+            page_size: 100,
+            filter: Some(Filter {
+                query: "rust".to_owned(),
+                max_age_days: 7,
+            }),
+            page_token: String::new(),
+        }
+    }
+
+    fn violation(field: &str, description: &str) -> FieldViolation {
+        FieldViolation {
+            field: field.to_owned(),
+            description: description.to_owned(),
+        }
+    }
+
+    fn violations(status: &tonic::Status) -> Vec<FieldViolation> {
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        StatusDetails::from_status(status).expect("valid details").bad_request.expect("bad request").field_violations
+    }
+
+    #[test]
+    fn should_report_every_failing_field() {
+        let rules = rules();
+        assert!(rules.validate(&valid()).is_ok());
+
+        let mut request = valid();
+        //Characters are counted, not bytes
+        request.parent = "ß".repeat(16);
+        assert!(rules.validate(&request).is_ok());
+        request.parent = "ß".repeat(17);
+        request.page_size = 0;
+        request.page_token = "next".to_owned();
+        request.filter = Some(Filter {
+            query: String::new(),
+            max_age_days: 31,
+        });
+        let status = rules.validate(&request).unwrap_err();
+        assert_eq!(status.message(), "invalid request");
+        assert_eq!(violations(&status), [
+            violation("parent", "length must be between 1 and 16"),
+            violation("filter.query", "length must be between 1 and 8"),
+            violation("filter.max_age_days", "must be between 1 and 30"),
+            violation("page_token", "requires page_size"),
+        ]);
+
+        let mut request = valid();
+        request.page_size = -1;
+        request.filter = None;
+        assert_eq!(violations(&rules.validate(&request).unwrap_err()), [
+            violation("page_size", "must be between 0 and 1000"),
+            violation("filter", "must be set"),
+        ]);
+    }
+
+    #[test]
+    fn should_validate_registered_methods() {
+        let validator = MessageValidator::new().register("/pkg.Items/List", rules())
+                                               .register("/pkg.Items/*", Rules::new().required("filter", |request: &ListRequest| request.filter.as_ref()));
+
+        assert!(validator.validate("/pkg.Items/List", &valid().encode_to_vec()).is_ok());
+        let mut request = valid();
+        request.page_size = 1001;
+        assert_eq!(violations(&validator.validate("/pkg.Items/List", &request.encode_to_vec()).unwrap_err()), [violation("page_size", "must be between 0 and 1000")]);
+        //The first matching registration is used
+        assert!(validator.validate("/pkg.Items/Search", &request.encode_to_vec()).is_ok());
+        assert_eq!(violations(&validator.validate("/pkg.Items/Search", &[]).unwrap_err()), [violation("filter", "must be set")]);
+        //Unregistered methods are not validated
+        assert!(validator.validate("/pkg.Other/List", &[0xff]).is_ok());
+
+        let status = validator.validate("/pkg.Items/List", &[0x08, 0xff]).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        assert!(status.message().starts_with("invalid message: "));
+    }
+
+    #[test]
+    fn should_reject_invalid_request_in_service() {
+        use tonic_interceptor::testing::{poll_once, service_fn};
+        use tower_layer::Layer;
+        use tower_service::Service;
+
+        let validator = MessageValidator::new().register("/pkg.Items/List", rules());
+        let mut svc = validator.inspector().layer().layer(service_fn(|_: http::Request<_>| Ok::<_, core::convert::Infallible>(http::Response::new(()))));
+
+        let mut request = valid();
+        request.parent = String::new();
+        let message = request.encode_to_vec();
+        let mut body = vec![0];
+        body.extend_from_slice(&(message.len() as u32).to_be_bytes());
+        body.extend_from_slice(&message);
+        let request = http::Request::builder().uri("/pkg.Items/List").body(http_body::Full::new(bytes::Bytes::from(body))).unwrap();
+
+        let response = poll_once(svc.call(request)).unwrap();
+        let status = tonic::Status::from_header_map(response.headers()).expect("status");
+        assert_eq!(violations(&status), [violation("parent", "length must be between 1 and 16")]);
+    }
+}
@@ -0,0 +1,81 @@
+#![cfg(all(feature = "wasm", target_arch = "wasm32"))]
+#![allow(clippy::result_large_err)]
+
+//Smoke test of client stack within JS environment
+//
+//Run with `wasm-pack test --node -- --features wasm --test wasm`
+
+use tonic_interceptor::client::{ClientInterceptor, ClientInterceptorService, LocalBearerAuth, LocalTokenSource};
+use tonic_interceptor::timer::{Clock, Timer, WasmTimer};
+use tonic_interceptor::{http, tonic};
+use tonic_interceptor::tonic::Status;
+use tonic_interceptor::tonic::metadata::{Ascii, MetadataValue};
+use tower::{ServiceBuilder, ServiceExt};
+use wasm_bindgen_test::wasm_bindgen_test;
+
+use core::future;
+use core::cell::RefCell;
+use core::time::Duration;
+use std::rc::Rc;
+
+//Transport, which is not `Send`, like the one over browser's fetch
+#[derive(Clone)]
+struct Transport(Rc<RefCell<Vec<http::HeaderMap>>>);
+
+impl tower_service::Service<http::Request<()>> for Transport {
+    type Response = http::Response<tonic::body::BoxBody>;
+    type Error = Status;
+    type Future = future::Ready<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _: &mut core::task::Context<'_>) -> core::task::Poll<Result<(), Self::Error>> {
+        core::task::Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<()>) -> Self::Future {
+        self.0.borrow_mut().push(request.headers().clone());
+        future::ready(Ok(http::Response::builder().header("grpc-status", "0").body(tonic::body::empty_body()).unwrap()))
+    }
+}
+
+#[derive(Clone)]
+struct RequestId;
+
+impl ClientInterceptor for RequestId {
+    fn on_request(&self, headers: &mut tonic::metadata::MetadataMap, _: &mut http::Extensions) -> Option<Status> {
+        headers.insert("x-request-id", MetadataValue::from_static("wasm-1"));
+        None
+    }
+
+    fn on_response(&self, _: &tonic::metadata::MetadataMap, _: &http::Extensions) {
+    }
+}
+
+//Token, which is obtained after sleeping with browser's timer
+struct Delayed(&'static str);
+
+impl LocalTokenSource for Delayed {
+    async fn token(&self) -> Result<MetadataValue<Ascii>, Status> {
+        WasmTimer.sleep(Duration::from_millis(1)).await;
+        Ok(MetadataValue::from_static(self.0))
+    }
+}
+
+#[wasm_bindgen_test]
+async fn should_inject_metadata_over_local_transport() {
+    let sent = Rc::new(RefCell::new(Vec::new()));
+    let service = ServiceBuilder::new().layer(LocalBearerAuth::new(Delayed("secret")))
+                                       .service(ClientInterceptorService::new(RequestId, Transport(sent.clone())));
+    service.oneshot(http::Request::new(())).await.expect("success");
+
+    let sent = sent.borrow();
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0]["authorization"], "Bearer secret");
+    assert_eq!(sent[0]["x-request-id"], "wasm-1");
+}
+
+#[wasm_bindgen_test]
+async fn should_sleep_and_tell_time() {
+    let started = WasmTimer.now();
+    WasmTimer.sleep(Duration::from_millis(20)).await;
+    assert!(WasmTimer.now().duration_since(started) >= Duration::from_millis(20));
+}
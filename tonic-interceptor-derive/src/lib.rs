@@ -0,0 +1,184 @@
+//! Derive macro for `tonic-interceptor`
+//!
+//!Use it via `derive` feature of `tonic-interceptor`.
+//!
+//!## `#[derive(Interceptor)]`
+//!
+//!Composes interceptor out of struct fields:
+//!
+//!- Requests are passed to fields from top to bottom, stopping at first rejection;
+//!- Responses are passed to fields from bottom to top;
+//!- Fields marked with `#[interceptor(skip)]` are ignored.
+//!
+//!Every request callback of `Interceptor` is forwarded to the same callback of each field,
+//!hence fields that override them (e.g. `Raw`) keep their behavior.
+//!
+//!```rust
+//!use tonic_interceptor::{Interceptor, OnResponse};
+//!
+//!type Stamp = OnResponse<fn(tonic::Code, &mut http::HeaderMap, &http::Extensions)>;
+//!
+//!fn stamp(_: tonic::Code, headers: &mut http::HeaderMap, _: &http::Extensions) {
+//!    headers.insert("x-stamp", http::HeaderValue::from_static("1"));
+//!}
+//!
+//!#[derive(Interceptor)]
+//!struct Middleware {
+//!    stamp: Stamp,
+//!    #[interceptor(skip)]
+//!    name: &'static str,
+//!}
+//!
+//!let middleware = Middleware {
+//!    stamp: OnResponse(stamp),
+//!    name: "middleware",
+//!};
+//!let mut headers = http::HeaderMap::new();
+//!middleware.on_response(tonic::Code::Ok, &mut headers, &http::Extensions::new());
+//!assert_eq!(headers.get("x-stamp").unwrap(), "1");
+//!```
+//!
+//!Fields that are not interceptors must be skipped:
+//!
+//!```rust,compile_fail
+//!use tonic_interceptor::Interceptor;
+//!
+//!#[derive(Interceptor)]
+//!struct Middleware {
+//!    name: &'static str,
+//!}
+//!```
+//!
+//!Only structs are supported:
+//!
+//!```rust,compile_fail
+//!use tonic_interceptor::Interceptor;
+//!
+//!#[derive(Interceptor)]
+//!enum Middleware {
+//!    First,
+//!}
+//!```
+
+#![forbid(unsafe_code)]
+#![warn(missing_docs)]
+
+use proc_macro::TokenStream;
+use quote::{quote, quote_spanned};
+use syn::spanned::Spanned;
+
+fn is_skipped(field: &syn::Field) -> syn::Result<bool> {
+    let mut skip = false;
+    for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("interceptor")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("skip") {
+                skip = true;
+                Ok(())
+            } else {
+                Err(meta.error("unsupported interceptor attribute, expected `skip`"))
+            }
+        })?;
+    }
+    Ok(skip)
+}
+
+//Calls `method` of every field in order, returning first rejection
+fn chain(fields: &[(syn::Member, &syn::Type)], method: proc_macro2::TokenStream, args: proc_macro2::TokenStream) -> Vec<proc_macro2::TokenStream> {
+    fields.iter().map(|(member, ty)| quote_spanned! {ty.span()=>
+        if let ::core::option::Option::Some(status) = <#ty as ::tonic_interceptor::Interceptor>::#method(&self.#member, #args) {
+            return ::core::option::Option::Some(status);
+        }
+    }).collect()
+}
+
+fn expand(input: syn::DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let fields = match &input.data {
+        syn::Data::Struct(data) => &data.fields,
+        _ => return Err(syn::Error::new(input.ident.span(), "Interceptor can only be derived for structs")),
+    };
+
+    let mut members = Vec::new();
+    let mut types = Vec::new();
+    for (idx, field) in fields.iter().enumerate() {
+        if is_skipped(field)? {
+            continue;
+        }
+        let member = match &field.ident {
+            Some(ident) => syn::Member::Named(ident.clone()),
+            None => syn::Member::Unnamed(syn::Index::from(idx)),
+        };
+        members.push(member);
+        types.push(&field.ty);
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let mut where_clause = where_clause.cloned().unwrap_or_else(|| syn::parse_quote!(where));
+    //Bounds on concrete types are not allowed, hence only generic structs get them
+    if !input.generics.params.is_empty() {
+        for ty in types.iter() {
+            where_clause.predicates.push(syn::parse_quote_spanned!(ty.span()=> #ty: ::tonic_interceptor::Interceptor));
+        }
+    }
+
+    let fields = members.into_iter().zip(types).collect::<Vec<_>>();
+    let on_request = chain(&fields, quote!(on_request), quote!(headers, extensions));
+    let on_request_with_uri = chain(&fields, quote!(on_request_with_uri), quote!(uri, headers, extensions));
+    let on_request_lazy = chain(&fields, quote!(on_request_lazy), quote!(uri, headers, extensions));
+    let on_request_headers = chain(&fields, quote!(on_request_headers), quote!(uri, headers, extensions));
+    let on_response = fields.iter().rev().map(|(member, ty)| quote_spanned! {ty.span()=>
+        <#ty as ::tonic_interceptor::Interceptor>::on_response(&self.#member, status, headers, extensions);
+    });
+    //Whether `on_poll_stats` exists depends on features of `tonic-interceptor`, hence it is left to its macro
+    let on_poll_stats = fields.iter().map(|(member, ty)| quote_spanned! {ty.span()=>
+        #ty => #member
+    });
+
+    Ok(quote! {
+        #[allow(unused_variables)]
+        impl #impl_generics ::tonic_interceptor::Interceptor for #name #ty_generics #where_clause {
+            #[inline]
+            fn on_request(&self, headers: &mut ::tonic_interceptor::__private::tonic::metadata::MetadataMap, extensions: &mut ::tonic_interceptor::__private::http::Extensions) -> ::core::option::Option<::tonic_interceptor::__private::tonic::Status> {
+                #(#on_request)*
+                ::core::option::Option::None
+            }
+
+            #[inline]
+            fn on_request_with_uri(&self, uri: &::tonic_interceptor::__private::http::Uri, headers: &mut ::tonic_interceptor::__private::tonic::metadata::MetadataMap, extensions: &mut ::tonic_interceptor::__private::http::Extensions) -> ::core::option::Option<::tonic_interceptor::__private::tonic::Status> {
+                #(#on_request_with_uri)*
+                ::core::option::Option::None
+            }
+
+            #[inline]
+            fn on_request_lazy(&self, uri: &::tonic_interceptor::__private::http::Uri, headers: &mut ::tonic_interceptor::LazyMetadata<'_>, extensions: &mut ::tonic_interceptor::__private::http::Extensions) -> ::core::option::Option<::tonic_interceptor::__private::tonic::Status> {
+                #(#on_request_lazy)*
+                ::core::option::Option::None
+            }
+
+            #[inline]
+            fn on_request_headers(&self, uri: &::tonic_interceptor::__private::http::Uri, headers: &mut ::tonic_interceptor::__private::http::HeaderMap, extensions: &mut ::tonic_interceptor::__private::http::Extensions) -> ::core::option::Option<::tonic_interceptor::__private::tonic::Status> {
+                #(#on_request_headers)*
+                ::core::option::Option::None
+            }
+
+            #[inline]
+            fn on_response(&self, status: ::tonic_interceptor::__private::tonic::Code, headers: &mut ::tonic_interceptor::__private::http::HeaderMap, extensions: &::tonic_interceptor::__private::http::Extensions) {
+                #(#on_response)*
+            }
+
+            ::tonic_interceptor::__private::forward_poll_stats!(#(#on_poll_stats),*);
+        }
+    })
+}
+
+///Derives `Interceptor`, composing it out of struct fields
+///
+///See crate documentation for details.
+#[proc_macro_derive(Interceptor, attributes(interceptor))]
+pub fn interceptor(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as syn::DeriveInput);
+    match expand(input) {
+        Ok(output) => output.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}